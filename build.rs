@@ -0,0 +1,32 @@
+//! Puts the memory layout of the selected board where the linker looks for
+//! `memory.x`.
+//!
+//! The layouts live in `memory/`, one per `board-*` feature; `cortex-m-rt`'s
+//! `link.x` includes whichever is copied to the build directory.
+//!
+//! A build without the `logging` feature gets an empty `defmt.x`, the linker
+//! script defmt would provide.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() {
+    let layout = if env::var_os("CARGO_FEATURE_BOARD_G431RB").is_some() {
+        "memory/g431rb.x"
+    } else if env::var_os("CARGO_FEATURE_BOARD_G491RE").is_some() {
+        "memory/g491re.x"
+    } else {
+        "memory/g474re.x"
+    };
+
+    let out = PathBuf::from(env::var_os("OUT_DIR").expect("OUT_DIR is set by cargo"));
+    fs::copy(layout, out.join("memory.x")).expect("cannot copy the memory layout");
+    // `.cargo/config.toml` links with `defmt.x` in every build: without the
+    // `logging` feature defmt is not there to provide it, so an empty one is.
+    if env::var_os("CARGO_FEATURE_LOGGING").is_none() {
+        fs::write(out.join("defmt.x"), "").expect("cannot write an empty defmt.x");
+    }
+    println!("cargo:rustc-link-search={}", out.display());
+    println!("cargo:rerun-if-changed=memory");
+}
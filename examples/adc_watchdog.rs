@@ -0,0 +1,158 @@
+//! example: ADC analog watchdog changing the blink pattern.
+//!
+//! A potentiometer wiper is connected to A0 (PA0, ADC1 channel 1). ADC1
+//! converts it continuously and analog watchdog 1 compares every result
+//! against a window in hardware. While the voltage stays inside the window
+//! the LED (PA5) blinks slowly; as soon as it leaves the window the `ADC1_2`
+//! interrupt fires and the LED switches to a fast "alarm" blink. The CPU never
+//! polls the ADC: it only checks the latest sample on timer ticks while the
+//! alarm is active, to find out when the voltage is back in range.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{gpioa, Output, PushPull};
+use hal::adc::{config::{Continuous, SampleTime, Sequence}, AdcClaim, ClockSource};
+use hal::delay::SYSTDelayExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::adc::{AnalogWatchdog, Window};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Blink period while the voltage is inside the window.
+const NORMAL_DELAYMS: u32 = 1000;
+// Blink period while the voltage is outside the window.
+const ALARM_DELAYMS: u32 = 125;
+// Accepted range, in raw 12-bit codes (roughly 0.5 V to 2.8 V with VDDA = 3.3 V).
+const WINDOW: Window = Window::new(620, 3475);
+
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the analog watchdog that I'm going to pass around.
+static G_AWD: Mutex<RefCell<Option<AnalogWatchdog>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable that remembers whether the voltage left the window.
+static G_ALARM: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+
+    let led = gpioa.pa5.into_push_pull_output();
+    // The potentiometer pin must be in analog mode to be sampled.
+    let pot = gpioa.pa0.into_analog();
+
+    // 1) Claim ADC1. The SysTick delay is only used for the regulator start-up time.
+    let mut delay = cp.SYST.delay(&rcc.clocks);
+    let mut adc = dp.ADC1.claim(ClockSource::SystemClock, &rcc, &mut delay, true);
+    // 2) Convert the potentiometer channel over and over again.
+    adc.set_continuous(Continuous::Continuous);
+    adc.reset_sequence();
+    adc.configure_channel(&pot, Sequence::One, SampleTime::Cycles_640_5);
+    let mut adc = adc.enable();
+    // 3) Arm the watchdog before starting: its channel can't change during conversions.
+    let mut awd = AnalogWatchdog::new(&mut adc, &pot, WINDOW);
+    awd.listen();
+    // 4) Start converting. The handle must live as long as the program, so keep it here.
+    let _adc = adc.start_conversion();
+
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(NORMAL_DELAYMS.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_AWD.borrow(cs).replace(Some(awd));
+        defmt::info!("Watching window {} on PA0", WINDOW);
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::ADC1_2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// Analog watchdog interrupt: the voltage just left the window.
+#[interrupt]
+fn ADC1_2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut awd = G_AWD.borrow(cs).borrow_mut();
+        let awd = awd.as_mut().unwrap();
+        // Every out-of-range conversion sets the flag again, so stop listening
+        // until the timer handler sees the voltage back inside the window.
+        awd.unlisten();
+        awd.clear_pending();
+        G_ALARM.borrow(cs).set(true);
+        defmt::warn!("Out of range: {}", awd.latest_sample());
+
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(ALARM_DELAYMS.ms());
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        let timer = timer.as_mut().unwrap();
+        timer.clear_interrupt(Event::TimeOut);
+
+        // While in alarm, check whether the voltage came back and re-arm the watchdog.
+        if G_ALARM.borrow(cs).get() {
+            let mut awd = G_AWD.borrow(cs).borrow_mut();
+            let awd = awd.as_mut().unwrap();
+            let sample = awd.latest_sample();
+            if WINDOW.contains(sample) {
+                G_ALARM.borrow(cs).set(false);
+                awd.clear_pending();
+                awd.listen();
+                defmt::info!("Back in range: {}", sample);
+                timer.start(NORMAL_DELAYMS.ms());
+            }
+        }
+    });
+}
@@ -0,0 +1,329 @@
+//! example: one blink configuration for the button, a shell and the RTC.
+//!
+//! The LED on PA5 blinks as in the main program, the User Button (PC13)
+//! halving the period, but the period, the pattern and the brightness are
+//! one [`AppConfig`] that a shell on the ST-LINK virtual COM port (USART2,
+//! 115200 baud) changes too, and that the RTC backup registers keep through
+//! resets:
+//!
+//! ```text
+//! > period 300
+//! > brightness 20
+//! > pattern solid
+//! > config
+//! period 300 ms, pattern solid, brightness 20%
+//! ```
+//!
+//! Neither writer touches the LED or the timers: each changes the
+//! configuration and calls `apply`, which takes the changes and does what
+//! each one needs, restarting the blink timer (TIM3) for a new period,
+//! setting the PWM duty (TIM2 channel 1 on the LED pin) for a new
+//! brightness, and saving the new settings.
+//!
+//! The settings go to the backup registers with a CRC-32 word after them,
+//! from the CRC unit, and a copy that does not match it is dropped for the
+//! defaults at start. The same unit checks the lines a program sends with a
+//! checksum, `config*9C7D`, and adds one to their replies: see the `shell`
+//! module.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{Alternate, AF1, AF7, ExtiPin, Floating, Input, SignalEdge, gpioa, gpioc};
+use hal::pwm::{ActiveHigh, ComplementaryImpossible, Pwm, C1};
+use hal::serial::{Event as SerialEvent, FullConfig, Serial};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::clocks::{self, ClockConfig};
+use nucleo_g474re::config::{self, AppConfig, Pattern};
+use nucleo_g474re::crc::{Config, Crc};
+use nucleo_g474re::gamma::Gamma28;
+use nucleo_g474re::rtc::Rtc;
+use nucleo_g474re::shell::{Frame, Input as ShellInput, Received, Shell, FRAME_CRC};
+
+use cortex_m_rt::entry;
+
+use core::fmt::Write;
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{TIM2, TIM3, USART2};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for the PWM channel driving the LED
+type LedPwm = Pwm<TIM2, C1, ComplementaryImpossible, ActiveHigh, ActiveHigh>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the virtual COM port
+type SerialPort = Serial<USART2, gpioa::PA2<Alternate<AF7>>, gpioa::PA3<Alternate<AF7>>>;
+
+// Backup registers holding the saved configuration, its CRC word after it.
+const BACKUP: usize = 0;
+
+const HELP: &str = "commands:\r\n  config\r\n  period <125-1000>\r\n  pattern <blink|solid|off>\r\n  brightness <0-100>\r\n  defaults\r\n";
+
+// Create a Global Variable for the LED PWM channel that I'm going to pass around.
+static G_PWM: Mutex<RefCell<Option<LedPwm>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM3>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the serial port that I'm going to pass around.
+static G_SERIAL: Mutex<RefCell<Option<SerialPort>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the shell line.
+static G_SHELL: Mutex<RefCell<Shell>> = Mutex::new(RefCell::new(Shell::new()));
+// Create a Global Variable for the RTC, for its backup registers.
+static G_RTC: Mutex<RefCell<Option<Rtc>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the CRC unit, for the saved words and the frames.
+static G_CRC: Mutex<RefCell<Option<Crc>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the configuration, in place of the delay alone.
+static G_CONFIG: Mutex<RefCell<AppConfig>> = Mutex::new(RefCell::new(AppConfig::new()));
+// Create a Global Variable for the blink phase: lit or not.
+static G_LIT: Mutex<Cell<bool>> = Mutex::new(Cell::new(true));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Sets the LED for the configuration and the blink phase.
+fn show(cs: &cortex_m::interrupt::CriticalSection, config: &AppConfig) {
+    let lit = match config.pattern() {
+        Pattern::Blink => G_LIT.borrow(cs).get(),
+        Pattern::Solid => true,
+        Pattern::Off => false,
+    };
+    let mut pwm = G_PWM.borrow(cs).borrow_mut();
+    let pwm = pwm.as_mut().unwrap();
+    let duty = if lit { Gamma28::duty(config.level(), pwm.get_max_duty()) } else { 0 };
+    pwm.set_duty(duty);
+}
+
+// Applies the changes of the configuration: the one place that does.
+fn apply(cs: &cortex_m::interrupt::CriticalSection) {
+    let mut config = G_CONFIG.borrow(cs).borrow_mut();
+    let changes = config.take_changes();
+    if !changes.any() {
+        return;
+    }
+    if changes.period {
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(config.period_ms().ms());
+        defmt::info!("Delay Atual: {} ms", config.period_ms());
+    }
+    if changes.pattern || changes.brightness {
+        show(cs, &config);
+    }
+
+    // The CRC-32 for the saved words, then back to the CRC of the frames.
+    let mut rtc = G_RTC.borrow(cs).borrow_mut();
+    let mut crc = G_CRC.borrow(cs).borrow_mut();
+    let crc = crc.as_mut().unwrap();
+    crc.set_config(Config::CRC32);
+    rtc.as_mut().unwrap().save_checked(BACKUP, &config.to_words(), crc).ok();
+    crc.set_config(FRAME_CRC);
+}
+
+// Runs one shell line on the configuration.
+fn run<W: Write>(line: &str, config: &mut AppConfig, out: &mut W) {
+    let mut words = line.split_ascii_whitespace();
+    let result = match (words.next(), words.next(), words.next()) {
+        (None, _, _) => Ok(()),
+        (Some("config"), None, _) => {
+            writeln!(
+                out,
+                "period {} ms, pattern {}, brightness {}%\r",
+                config.period_ms(),
+                config.pattern().name(),
+                config.brightness()
+            )
+            .ok();
+            Ok(())
+        }
+        (Some("period"), Some(value), None) => match value.parse() {
+            Ok(period_ms) => config.set_period_ms(period_ms),
+            Err(_) => Err(config::Error::InvalidPeriod),
+        },
+        (Some("pattern"), Some(name), None) => Pattern::from_name(name).map(|pattern| config.set_pattern(pattern)),
+        (Some("brightness"), Some(value), None) => match value.parse() {
+            Ok(brightness) => config.set_brightness(brightness),
+            Err(_) => Err(config::Error::InvalidBrightness),
+        },
+        (Some("defaults"), None, _) => {
+            config.update(&AppConfig::new());
+            Ok(())
+        }
+        _ => {
+            out.write_str(HELP).ok();
+            Ok(())
+        }
+    };
+    if let Err(error) = result {
+        writeln!(out, "{:?}\r", error).ok();
+    }
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+
+    // 1) The LSE (or the LSI) for the RTC, the PLL at 170 MHz.
+    let (mut rcc, sources) = clocks::freeze(dp.RCC.constrain(), ClockConfig::hsi().lse());
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 2) RTC, and the configuration it kept, if any and matching its CRC.
+    let rtc = Rtc::new(dp.RTC, sources.low_speed.expect("no low-speed clock"));
+    let mut crc = Crc::new(dp.CRC, Config::CRC32);
+    let mut saved = [0; 2];
+    let start = match rtc.load_checked(BACKUP, &mut saved, &mut crc).map(|()| AppConfig::from_words(saved)) {
+        Ok(Ok(saved)) => {
+            defmt::info!("Saved configuration: {}", saved);
+            saved
+        }
+        _ => {
+            defmt::info!("No saved configuration, defaults");
+            AppConfig::new()
+        }
+    };
+    crc.set_config(FRAME_CRC);
+
+    // 3) LED pin as TIM2 channel 1 output, PWM at 1 kHz for the brightness.
+    let pin: gpioa::PA5<Alternate<AF1>> = gpioa.pa5.into_alternate();
+    let mut pwm = dp.TIM2.pwm(pin, 1000.hz(), &mut rcc);
+    pwm.set_duty(0);
+    pwm.enable();
+
+    // 4) Shell on the virtual COM port, a character per RX interrupt.
+    let tx = gpioa.pa2.into_alternate();
+    let rx = gpioa.pa3.into_alternate();
+    let mut serial = dp
+        .USART2
+        .usart(tx, rx, FullConfig::default().baudrate(115_200.bps()), &mut rcc)
+        .expect("cannot configure USART2");
+    serial.write_str("\r\nConfig shell, 'help' for the commands\r\n> ").ok();
+    serial.listen(SerialEvent::Rxne);
+
+    // 5) Blink timer, restarted by `apply` at the configured period.
+    let timer = Timer::new(dp.TIM3, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_PWM.borrow(cs).replace(Some(pwm));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_SERIAL.borrow(cs).replace(Some(serial));
+        G_RTC.borrow(cs).replace(Some(rtc));
+        G_CRC.borrow(cs).replace(Some(crc));
+
+        // The saved settings applied as changes, and the LED lit for them.
+        G_CONFIG.borrow(cs).borrow_mut().update(&start);
+        apply(cs);
+        show(cs, &G_CONFIG.borrow(cs).borrow());
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM3);
+        cortex_m::peripheral::NVIC::unmask(interrupt::USART2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// USART2 interrupt: a character for the shell.
+#[interrupt]
+fn USART2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut serial = G_SERIAL.borrow(cs).borrow_mut();
+        let serial = serial.as_mut().unwrap();
+        let mut shell = G_SHELL.borrow(cs).borrow_mut();
+
+        // Reading clears the interrupt; errors (overrun) drop the character.
+        while let Ok(byte) = serial.read() {
+            let input = shell.push(byte);
+            shell.echo(serial, input).ok();
+            if input != ShellInput::Line {
+                continue;
+            }
+            // A line with a checksum gets a reply with one; `apply` takes the
+            // CRC unit after it.
+            {
+                let mut crc = G_CRC.borrow(cs).borrow_mut();
+                let crc = crc.as_mut().unwrap();
+                let mut config = G_CONFIG.borrow(cs).borrow_mut();
+                match shell.received(crc) {
+                    Some(Ok(Received { line, checked: true })) => {
+                        let mut frame = Frame::new(serial, crc);
+                        run(line, &mut config, &mut frame);
+                        frame.finish().ok();
+                    }
+                    Some(Ok(Received { line, checked: false })) => run(line, &mut config, serial),
+                    Some(Err(error)) => {
+                        writeln!(serial, "{:?}\r", error).ok();
+                    }
+                    None => {}
+                }
+            }
+            apply(cs);
+            shell.prompt(serial).ok();
+        }
+    });
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Config Global Data and Adjust Delay
+        G_CONFIG.borrow(cs).borrow_mut().step_period();
+        apply(cs);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt: the next phase of the blink.
+#[interrupt]
+fn TIM3() {
+    cortex_m::interrupt::free(|cs| {
+        G_LIT.borrow(cs).set(!G_LIT.borrow(cs).get());
+        show(cs, &G_CONFIG.borrow(cs).borrow());
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
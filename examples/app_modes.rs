@@ -0,0 +1,192 @@
+//! example: the blink as an application with modes, Idle, Blink, Config and
+//! Fault.
+//!
+//! An `app::App` owns the mode, the LED and the button; the interrupts only
+//! hand it what happens and log what it does:
+//!
+//! | Source                      | Event                                    |
+//! |-----------------------------|------------------------------------------|
+//! | User Button (PC13)          | `Press`, or `Hold` for half a second     |
+//! | TIM2, every 50 ms           | `Tick`, which also plays the LED pattern |
+//! | shell on USART2, 115200 bd  | `Enter(mode)`, `Fault`, `Clear`          |
+//!
+//! The board starts idle, the LED on PA5 flashing once a second; a press
+//! starts the blink, and each further press halves its period. Holding the
+//! button enters Config, the LED blinking fast, where each press moves to
+//! the next pattern (blink, solid, off), until another hold or 5 s with no
+//! press. The shell:
+//!
+//! ```text
+//! > mode
+//! blink
+//! > mode config
+//! > fault
+//! > clear
+//! ```
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{Alternate, AF7, ExtiPin, SignalEdge, gpioa};
+use hal::serial::{Event as SerialEvent, FullConfig, Serial};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::app::{Action, App, TICK_MS};
+use nucleo_g474re::board::{ButtonPin, LedPin};
+use nucleo_g474re::compat::Compat;
+use nucleo_g474re::config::AppConfig;
+use nucleo_g474re::shell::{Input as ShellInput, Shell};
+
+use cortex_m_rt::entry;
+
+use core::fmt::Write;
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{TIM2, USART2};
+
+use core::cell::RefCell;
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for the virtual COM port
+type SerialPort = Serial<USART2, gpioa::PA2<Alternate<AF7>>, gpioa::PA3<Alternate<AF7>>>;
+// Alias for the application, on the board's LED and button
+type ModesApp = App<Compat<LedPin>, Compat<ButtonPin>>;
+
+// Create a Global Variable for the application, on the LED and the Button, that I'm going to pass around.
+static G_APP: Mutex<RefCell<Option<ModesApp>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the serial port that I'm going to pass around.
+static G_SERIAL: Mutex<RefCell<Option<SerialPort>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the shell line.
+static G_SHELL: Mutex<RefCell<Shell>> = Mutex::new(RefCell::new(Shell::new()));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Logs what the application did.
+fn log(action: Action, config: &AppConfig) {
+    match action {
+        Action::None => {}
+        Action::Entered(mode) => defmt::info!("Mode: {}", mode.name()),
+        Action::StepPeriod => defmt::info!("Delay Atual: {} ms", config.period_ms()),
+        Action::NextPattern => defmt::info!("Pattern: {}", config.pattern().name()),
+    }
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) Tick timer for the events and the LED pattern.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(TICK_MS.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+
+    // 2) Shell on the virtual COM port, a character per RX interrupt.
+    let tx = gpioa.pa2.into_alternate();
+    let rx = gpioa.pa3.into_alternate();
+    let mut serial = dp
+        .USART2
+        .usart(tx, rx, FullConfig::default().baudrate(115_200.bps()), &mut rcc)
+        .expect("cannot configure USART2");
+    serial.write_str("\r\nMode shell, 'help' for the commands\r\n> ").ok();
+    serial.listen(SerialEvent::Rxne);
+
+    // Configure Button Pin for Interrupts, on both edges
+    let mut syscfg = dp.SYSCFG.constrain();
+    let mut button = Compat(gpioc.pc13.into_floating_input());
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::RisingFalling);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    let app = App::new(Compat(gpioa.pa5.into_push_pull_output()), button);
+
+    cortex_m::interrupt::free(|cs| {
+        G_APP.borrow(cs).replace(Some(app));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_SERIAL.borrow(cs).replace(Some(serial));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::USART2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// USART2 interrupt: a character for the shell.
+#[interrupt]
+fn USART2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut serial = G_SERIAL.borrow(cs).borrow_mut();
+        let serial = serial.as_mut().unwrap();
+        let mut shell = G_SHELL.borrow(cs).borrow_mut();
+
+        // Reading clears the interrupt; errors (overrun) drop the character.
+        while let Ok(byte) = serial.read() {
+            let input = shell.push(byte);
+            shell.echo(serial, input).ok();
+            if input != ShellInput::Line {
+                continue;
+            }
+            let mut app = G_APP.borrow(cs).borrow_mut();
+            app.as_mut().unwrap().run(shell.line().unwrap_or(""), serial, log);
+            shell.prompt(serial).ok();
+        }
+    });
+}
+
+
+// Both edges: the press starts timing the hold, a release before it lasts
+// is a `Press`.
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut app = G_APP.borrow(cs).borrow_mut();
+        let app = app.as_mut().unwrap();
+        app.button_mut().clear_interrupt_pending_bit();
+        app.edge(log);
+    });
+}
+
+// Timer Interrupt: a tick of the application and of the LED.
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut app = G_APP.borrow(cs).borrow_mut();
+        app.as_mut().unwrap().tick(log);
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,164 @@
+//! example: the blink's button as a future, `button.wait_for_press().await`.
+//!
+//! TIM2 toggles the LED on PA5 as in the main program. The User Button on
+//! PC13 halves the delay, but from an async task rather than the EXTI ISR:
+//!
+//! ```text
+//! loop {
+//!     button.wait_for_press().await;
+//!     // halve the delay
+//! }
+//! ```
+//!
+//! `exti_handlers!` defines the EXTI ISRs: the one of line 13 clears it and
+//! wakes the task. The executor is [`block_on`], a few lines below: it
+//! sleeps in `wfi` and polls the task again once its waker has run, so each
+//! poll logs why it ran.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::board::{ButtonPin, LedPin};
+use nucleo_g474re::button::Button;
+use nucleo_g474re::compat::Compat;
+use nucleo_g474re::exti;
+use nucleo_g474re::led::Led;
+
+use cortex_m_rt::entry;
+
+use core::future::Future;
+use core::panic::PanicInfo;
+use core::pin::pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+nucleo_g474re::exti_handlers!();
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<Led<Compat<LedPin>>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+// Set by the task's waker, cleared by the executor before each poll.
+static WOKEN: AtomicBool = AtomicBool::new(true);
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// The waker of the one task: it only marks the task for a poll. The wfi of
+// `block_on` returns on the interrupt that ran it.
+static VTABLE: RawWakerVTable = RawWakerVTable::new(
+    |_| RawWaker::new(core::ptr::null(), &VTABLE),
+    |_| WOKEN.store(true, Ordering::Release),
+    |_| WOKEN.store(true, Ordering::Release),
+    |_| {},
+);
+
+// Runs `task` to completion, sleeping until its waker runs.
+fn block_on<F: Future>(task: F) -> F::Output {
+    let mut task = pin!(task);
+    // NOTE(unsafe) the vtable functions ignore the null data pointer
+    let waker = unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE)) };
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if WOKEN.swap(false, Ordering::AcqRel) {
+            defmt::info!("Poll");
+            if let Poll::Ready(output) = task.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = Led::new(Compat(gpioa.pa5.into_push_pull_output()));
+
+    // 2) The button, owned by the task: the dispatch clears its pending bit.
+    let mut syscfg = dp.SYSCFG.constrain();
+    let mut button: Button<Compat<ButtonPin>> =
+        Button::new(Compat(gpioc.pc13.into_floating_input()), &mut syscfg, &mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+    });
+
+    exti::unmask_all();
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    // 3) The task: a press, then the delay halved, for ever.
+    block_on(async {
+        loop {
+            button.wait_for_press().await;
+            cortex_m::interrupt::free(|cs| {
+                // Obtain Access to Delay Global Data and Adjust Delay
+                G_DELAYMS
+                    .borrow(cs)
+                    .set(G_DELAYMS.borrow(cs).get()/2);
+
+                if G_DELAYMS.borrow(cs).get() < 125_u32 {
+                    G_DELAYMS.borrow(cs).set(1000_u32);
+                }
+
+                let delayms = G_DELAYMS.borrow(cs).get();
+                let mut timer = G_TIM.borrow(cs).borrow_mut();
+                timer.as_mut().unwrap().start(delayms.ms());
+                defmt::info!("Delay Atual: {} ms", delayms);
+            });
+        }
+    })
+}
+
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,234 @@
+//! example: button feedback beeps and melodies on a passive buzzer.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), with a passive buzzer on PA6 (D12),
+//! driven by TIM3 channel 1. Each press beeps, higher as the blink gets
+//! faster, and the wrap back to 1000 ms plays a short jingle; a jingle also
+//! greets the start.
+//!
+//! The notes are timed by a software timer wheel of millisecond ticks:
+//! starting a note schedules the next one after its duration, so a melody
+//! plays out in the background of the blink, and a press in the middle of
+//! one cancels the rest of it. The wheel is tickless: before sleeping, the
+//! main loop sets SysTick to run out at the end of the note, and stops it
+//! once the melody is over, instead of an interrupt every millisecond.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::timer_wheel::{Tickless, TimerWheel};
+use nucleo_g474re::tone::{self, Buzzer, Melody, Note};
+
+use cortex_m_rt::{entry, exception};
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{TIM2, TIM3};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Timeouts of the timer wheel.
+#[derive(Clone, Copy, PartialEq)]
+enum Timeout {
+    // End of the current note.
+    NextNote,
+}
+
+// A ring of 64 ms, plenty for notes of a few hundred.
+type Wheel = TimerWheel<Timeout, 64, 4>;
+
+const STARTUP: &[Note] = &[(tone::C5, 100), (tone::E5, 100), (tone::G5, 100), (tone::C6, 200)];
+const WRAP: &[Note] = &[(tone::G5, 80), (tone::REST, 40), (tone::G5, 80), (tone::REST, 40), (tone::C5, 200)];
+const BEEP_500MS: &[Note] = &[(tone::C5, 60)];
+const BEEP_250MS: &[Note] = &[(tone::E5, 60)];
+const BEEP_125MS: &[Note] = &[(tone::G5, 60)];
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the buzzer that I'm going to pass around.
+static G_BUZZER: Mutex<RefCell<Option<Buzzer<TIM3>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the melody playing.
+static G_MELODY: Mutex<RefCell<Melody>> = Mutex::new(RefCell::new(Melody::new(STARTUP)));
+// Create a Global Variable for the timer wheel.
+static G_WHEEL: Mutex<RefCell<Wheel>> = Mutex::new(RefCell::new(TimerWheel::new()));
+// Create a Global Variable for SysTick, programmed for the next deadline of the wheel.
+static G_TICKLESS: Mutex<RefCell<Option<Tickless>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Starts the next note and schedules the one after it.
+fn play_next(melody: &mut Melody, buzzer: &mut Buzzer<TIM3>, wheel: &mut Wheel) {
+    if let Some(duration_ms) = melody.play_next(buzzer) {
+        wheel.schedule(duration_ms as u32, Timeout::NextNote).ok();
+    }
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) Buzzer on TIM3 channel 1, silent until the startup jingle.
+    let buzzer = Buzzer::new(dp.TIM3, gpioa.pa6.into_alternate(), rcc.clocks.apb1_tim_clk);
+
+    // 2) SysTick for the timer wheel, stopped until the first deadline.
+    let tickless = Tickless::new(cp.SYST, rcc.clocks.sys_clk);
+
+    // 3) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_BUZZER.borrow(cs).replace(Some(buzzer));
+        G_TICKLESS.borrow(cs).replace(Some(tickless));
+
+        // Startup jingle.
+        let mut buzzer = G_BUZZER.borrow(cs).borrow_mut();
+        play_next(
+            &mut G_MELODY.borrow(cs).borrow_mut(),
+            buzzer.as_mut().unwrap(),
+            &mut G_WHEEL.borrow(cs).borrow_mut(),
+        );
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    // SysTick programmed for the next deadline, and asleep with interrupts
+    // masked: one taken in between still wakes the `wfi`.
+    loop {
+        cortex_m::interrupt::free(|cs| {
+            let mut tickless = G_TICKLESS.borrow(cs).borrow_mut();
+            tickless.as_mut().unwrap().program(&mut G_WHEEL.borrow(cs).borrow_mut());
+            cortex_m::asm::wfi();
+        });
+    }
+}
+
+
+// SysTick: the ticks of the period to the timer wheel, then the timeouts
+// that fell due.
+#[exception]
+fn SysTick() {
+    cortex_m::interrupt::free(|cs| {
+        let mut wheel = G_WHEEL.borrow(cs).borrow_mut();
+        let mut tickless = G_TICKLESS.borrow(cs).borrow_mut();
+        tickless.as_mut().unwrap().expire(&mut wheel);
+        while let Some(timeout) = wheel.expired() {
+            match timeout {
+                Timeout::NextNote => {
+                    let mut buzzer = G_BUZZER.borrow(cs).borrow_mut();
+                    let mut melody = G_MELODY.borrow(cs).borrow_mut();
+                    play_next(&mut melody, buzzer.as_mut().unwrap(), &mut wheel);
+                }
+            }
+        }
+    });
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+        defmt::info!("Delay Atual: {} ms", delayms);
+
+        // Feedback: drop what is playing, then the beep of the new delay.
+        let notes = match delayms {
+            500 => BEEP_500MS,
+            250 => BEEP_250MS,
+            125 => BEEP_125MS,
+            _ => WRAP,
+        };
+        let mut wheel = G_WHEEL.borrow(cs).borrow_mut();
+        let mut buzzer = G_BUZZER.borrow(cs).borrow_mut();
+        let mut melody = G_MELODY.borrow(cs).borrow_mut();
+        // The wheel up to now first, for the beep to count from the press.
+        let mut tickless = G_TICKLESS.borrow(cs).borrow_mut();
+        tickless.as_mut().unwrap().catch_up(&mut wheel);
+        wheel.cancel(Timeout::NextNote);
+        melody.set_notes(notes);
+        play_next(&mut melody, buzzer.as_mut().unwrap(), &mut wheel);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,244 @@
+//! example: interrupt blink with CAN telemetry and remote control.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay) broadcasting its events on FDCAN1 at
+//! 500 kbit/s with the standard identifier 0x123: one frame on every button
+//! press and one every BLINK_CYCLES blink cycles. SysTick counts the
+//! milliseconds for the timestamps.
+//!
+//! Frames with the identifier 0x124 are commands: `01 f4 01` sets the delay
+//! to 500 ms, `02 00` / `02 01` force the LED off / on and `02 02` resumes
+//! blinking. A node that went bus-off is restarted from the interrupt.
+//!
+//! Needs a CAN transceiver on PB9 (TX) and PB8 (RX) and a second node on the
+//! bus, e.g. a USB-CAN adapter, to acknowledge and display the frames and to
+//! send the commands.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, Speed, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+use hal::can::CanExt;
+
+use stm32g4xx_hal as hal;
+
+use fdcan::id::StandardId;
+
+use nucleo_g474re::can::{Bitrate, Command, Config, Event as CanEvent, LedState, Telemetry};
+
+use cortex_m_rt::{entry, exception};
+
+use cortex_m::peripheral::syst::SystClkSource;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{FDCAN1, TIM2};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Blink cycles (LED on and off) between two telemetry frames.
+const BLINK_CYCLES: u32 = 5;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the CAN telemetry that I'm going to pass around.
+static G_CAN: Mutex<RefCell<Option<Telemetry<FDCAN1>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+// Create a Global Variable for the milliseconds elapsed since boot.
+static G_MILLIS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+// Create a Global Variable for the LED toggles since the last blink frame.
+static G_TOGGLES: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+// Create a Global Variable telling whether the LED blinks or is held by a command.
+static G_BLINK: Mutex<Cell<bool>> = Mutex::new(Cell::new(true));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) SysTick interrupt every millisecond for the timestamps.
+    cp.SYST.set_clock_source(SystClkSource::Core);
+    cp.SYST.set_reload(rcc.clocks.sys_clk.0 / 1000 - 1);
+    cp.SYST.clear_current();
+    cp.SYST.enable_counter();
+    cp.SYST.enable_interrupt();
+
+    // 2) FDCAN1 on PB9/PB8, clocked by PCLK1.
+    let tx = gpiob.pb9.into_alternate().set_speed(Speed::VeryHigh);
+    let rx = gpiob.pb8.into_alternate().set_speed(Speed::VeryHigh);
+    let can = dp.FDCAN1.fdcan(tx, rx, &rcc);
+    let config = Config {
+        bitrate: Bitrate::Kbps500,
+        id: StandardId::new(0x123).unwrap().into(),
+    };
+    let mut telemetry = Telemetry::new(can, config, rcc.clocks.apb1_clk);
+    telemetry.listen_commands(StandardId::new(0x124).unwrap().into());
+
+    // 3) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_CAN.borrow(cs).replace(Some(telemetry));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        // Vector 21, FDCAN1 interrupt line 0: the SVD swaps the names of the two lines.
+        cortex_m::peripheral::NVIC::unmask(interrupt::FDCAN1_INTR1_IT);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[exception]
+fn SysTick() {
+    cortex_m::interrupt::free(|cs| {
+        let millis = G_MILLIS.borrow(cs);
+        millis.set(millis.get().wrapping_add(1));
+    });
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+
+        let mut can = G_CAN.borrow(cs).borrow_mut();
+        if !can.as_mut().unwrap().send(CanEvent::ButtonPress, G_MILLIS.borrow(cs).get(), delayms) {
+            defmt::warn!("CAN transmit queue full, frame dropped");
+        }
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// FDCAN1 interrupt line 0: command received or bus-off
+#[interrupt]
+fn FDCAN1_INTR1_IT() {
+    cortex_m::interrupt::free(|cs| {
+        let mut can = G_CAN.borrow(cs).borrow_mut();
+        let can = can.as_mut().unwrap();
+        if can.recover_bus_off() {
+            defmt::warn!("CAN bus-off, restarting");
+        }
+
+        while let Some(command) = can.receive_command() {
+            defmt::info!("CAN command: {}", command);
+            match command {
+                Command::SetPeriod(delayms) => {
+                    // 1000 ms is the longest delay the timer takes with the default clock.
+                    let delayms = delayms.clamp(1, 1000);
+                    G_DELAYMS.borrow(cs).set(delayms);
+                    let mut timer = G_TIM.borrow(cs).borrow_mut();
+                    timer.as_mut().unwrap().start(delayms.ms());
+                }
+                Command::SetLed(state) => {
+                    G_BLINK.borrow(cs).set(state == LedState::Blink);
+                    let mut led = G_LED.borrow(cs).borrow_mut();
+                    let led = led.as_mut().unwrap();
+                    match state {
+                        LedState::Off => led.set_low().ok(),
+                        LedState::On => led.set_high().ok(),
+                        LedState::Blink => None,
+                    };
+                }
+            }
+        }
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        // A command may hold the LED on or off: only blink when allowed.
+        if G_BLINK.borrow(cs).get() {
+            let mut led = G_LED.borrow(cs).borrow_mut();
+            led.as_mut().unwrap().toggle().ok();
+        }
+
+        // Two toggles make one blink cycle.
+        let toggles = G_TOGGLES.borrow(cs).get() + 1;
+        if toggles == 2 * BLINK_CYCLES {
+            G_TOGGLES.borrow(cs).set(0);
+            let mut can = G_CAN.borrow(cs).borrow_mut();
+            let delayms = G_DELAYMS.borrow(cs).get();
+            if !can.as_mut().unwrap().send(CanEvent::BlinkCycles, G_MILLIS.borrow(cs).get(), delayms) {
+                defmt::warn!("CAN transmit queue full, frame dropped");
+            }
+        } else {
+            G_TOGGLES.borrow(cs).set(toggles);
+        }
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,176 @@
+//! example: a running dot on 12 charlieplexed LEDs.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), with 12 LEDs on the four pins PC0 to
+//! PC3, one LED for each ordered pair of pins, each pin through a 150 ohm
+//! resistor. TIM3 refreshes them at 2 kHz, one anode pin at a time; each
+//! blink tick moves a dot with a two-LED trail to the next LED, so the delay
+//! shows as the speed of the chase.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::charlie::{Charlie, Port};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{TIM2, TIM3};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the refresh Timer Peripheral that I'm going to pass around.
+static G_REFRESH: Mutex<RefCell<Option<CountDownTimer<TIM3>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the charlieplexed LEDs that I'm going to pass around.
+static G_CHARLIE: Mutex<RefCell<Option<Charlie<4>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the position of the dot.
+static G_DOT: Mutex<Cell<usize>> = Mutex::new(Cell::new(0));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) The LEDs on PC0 to PC3; `split` turned the port clock on, and the
+    // four pins are left unconfigured for the driver.
+    let charlie = Charlie::new(Port::C, [0, 1, 2, 3]);
+    defmt::info!("{} LEDs on 4 pins", charlie.len());
+
+    // 2) Refresh timer: a quarter of the LEDs per interrupt, 500 frames a second.
+    let refresh = Timer::new(dp.TIM3, &rcc.clocks);
+    let mut refresh = refresh.start_count_down(2000.hz());
+    refresh.listen(Event::TimeOut);
+
+    // 3) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_REFRESH.borrow(cs).replace(Some(refresh));
+        G_CHARLIE.borrow(cs).replace(Some(charlie));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM3);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+        defmt::info!("Delay Atual: {} ms", delayms);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt: the blink, and the dot one LED further.
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        let mut charlie = G_CHARLIE.borrow(cs).borrow_mut();
+        let charlie = charlie.as_mut().unwrap();
+        let count = charlie.len();
+        let dot = (G_DOT.borrow(cs).get() + 1) % count;
+        G_DOT.borrow(cs).set(dot);
+        charlie.clear();
+        for trail in 0..3 {
+            charlie.set((dot + count - trail) % count, true).ok();
+        }
+        charlie.present();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
+
+// Refresh Timer Interrupt: the LEDs of the next anode pin.
+#[interrupt]
+fn TIM3() {
+    cortex_m::interrupt::free(|cs| {
+        let mut charlie = G_CHARLIE.borrow(cs).borrow_mut();
+        charlie.as_mut().unwrap().refresh();
+
+        let mut refresh = G_REFRESH.borrow(cs).borrow_mut();
+        refresh.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,193 @@
+//! example: surviving an HSE failure with the clock security system.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), at 170 MHz from the PLL fed by the HSE:
+//! the 8 MHz MCO output of the ST-LINK, in bypass mode. The clock security
+//! system watches the HSE; short the MCO signal to ground and the chip
+//! falls back to the 16 MHz HSI on its own, raises the NMI and logs the
+//! failure.
+//!
+//! The NMI cannot share the `Mutex` globals, so it only raises a flag and
+//! pends the TIM2 interrupt, which starts the timer again from the HSI
+//! clocks and switches the LED to a double flash every second: the board
+//! tells it lost its clock instead of blinking ten times slower.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::clocks::{self, ClockConfig};
+
+use cortex_m_rt::{entry, exception};
+
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// LED after a clock failure, one step every 100 ms: two short flashes a second.
+const FAILURE_PATTERN: [bool; 10] = [true, false, true, false, false, false, false, false, false, false];
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+// Create a Global Variable for the step of the failure pattern, once it plays.
+static G_STEP: Mutex<Cell<Option<usize>>> = Mutex::new(Cell::new(None));
+// Set by the NMI when the clock security system saw the HSE fail.
+static CSS_FAILED: AtomicBool = AtomicBool::new(false);
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+
+    // 1) 170 MHz from the ST-LINK MCO, then the clock security system on it.
+    let config = ClockConfig::hsi().hse_bypass(clocks::STLINK_MCO);
+    let (mut rcc, sources) = clocks::freeze(dp.RCC.constrain(), config);
+    if clocks::enable_css() {
+        defmt::info!("Clock security system watching the HSE");
+    } else {
+        defmt::warn!("No HSE, running from {}: nothing for the clock security system to watch", sources.pll);
+    }
+
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 2) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// NMI: the HSE failed and the system clock is now the HSI. Unsafe for
+// cortex-m-rt because the NMI preempts critical sections: it touches no
+// `Mutex` global.
+#[exception]
+unsafe fn NonMaskableInt() {
+    if clocks::handle_css() {
+        CSS_FAILED.store(true, Ordering::Relaxed);
+        // TIM2 takes it from here, outside the NMI.
+        cortex_m::peripheral::NVIC::pend(interrupt::TIM2);
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        // The timer plays the failure pattern once the clock is lost.
+        if G_STEP.borrow(cs).get().is_none() {
+            let delayms = G_DELAYMS.borrow(cs).get();
+            let mut timer = G_TIM.borrow(cs).borrow_mut();
+            timer.as_mut().unwrap().start(delayms.ms());
+            defmt::info!("Delay Atual: {} ms", delayms);
+        }
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        let step = G_STEP.borrow(cs);
+
+        // First time after the failure: the timer again from the HSI clocks,
+        // at the pace of the pattern.
+        if step.get().is_none() && CSS_FAILED.load(Ordering::Relaxed) {
+            defmt::error!("HSE failed, running from the HSI at {} Hz", clocks::css_clocks().sys_clk.0);
+            let tim = timer.take().unwrap().release();
+            let mut count_down_timer = Timer::new(tim, &clocks::css_clocks()).start_count_down(100.ms());
+            count_down_timer.listen(Event::TimeOut);
+            timer.replace(count_down_timer);
+            step.set(Some(0));
+        }
+
+        match step.get() {
+            Some(index) => {
+                led.as_mut().unwrap().set_state(FAILURE_PATTERN[index].into()).ok();
+                step.set(Some((index + 1) % FAILURE_PATTERN.len()));
+            }
+            None => {
+                led.as_mut().unwrap().toggle().ok();
+            }
+        }
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,236 @@
+//! example: switching the system clock at runtime from a serial shell.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), with a shell on the ST-LINK virtual COM
+//! port (USART2, 115200 baud) that moves the system clock between the HSI
+//! and the PLL:
+//!
+//! ```text
+//! > clock 16
+//! sysclk 16000000 Hz
+//! > clock 170
+//! sysclk 170000000 Hz
+//! ```
+//!
+//! After each switch the TIM2 blink timer is started again from the new
+//! clocks and USART2 gets the baud rate divider of its new APB clock: the
+//! blink keeps its period and the terminal keeps reading the replies.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{Alternate, AF7, ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::rcc::Clocks;
+use hal::serial::{Event as SerialEvent, FullConfig, Serial};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::clocks::{self, Sysclk};
+use nucleo_g474re::shell::{Input as ShellInput, Shell};
+
+use cortex_m_rt::entry;
+
+use core::fmt::Write;
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{TIM2, USART2};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the virtual COM port
+type SerialPort = Serial<USART2, gpioa::PA2<Alternate<AF7>>, gpioa::PA3<Alternate<AF7>>>;
+
+const HELP: &str = "commands:\r\n  clock <16|170>\r\n  clock\r\n";
+
+const BAUD_RATE: u32 = 115_200;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the serial port that I'm going to pass around.
+static G_SERIAL: Mutex<RefCell<Option<SerialPort>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the shell line.
+static G_SHELL: Mutex<RefCell<Shell>> = Mutex::new(RefCell::new(Shell::new()));
+// Create a Global Variable for the clock frequencies, to start the timer again.
+static G_CLOCKS: Mutex<Cell<Option<Clocks>>> = Mutex::new(Cell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Switches the system clock, then the blink timer and the serial port to it.
+fn switch(sysclk: Sysclk, cs: &cortex_m::interrupt::CriticalSection) -> Clocks {
+    let clocks = clocks::set_sysclk(sysclk);
+    G_CLOCKS.borrow(cs).set(Some(clocks));
+
+    let mut timer = G_TIM.borrow(cs).borrow_mut();
+    let tim = timer.take().unwrap().release();
+    let mut count_down_timer = Timer::new(tim, &clocks).start_count_down(G_DELAYMS.borrow(cs).get().ms());
+    count_down_timer.listen(Event::TimeOut);
+    timer.replace(count_down_timer);
+
+    clocks::set_baud_rate(unsafe { &*USART2::ptr() }, clocks.apb1_clk, BAUD_RATE.bps());
+    clocks
+}
+
+
+// Runs one shell line.
+fn run(line: &str, out: &mut SerialPort, cs: &cortex_m::interrupt::CriticalSection) {
+    let mut words = line.split_ascii_whitespace();
+    let clocks = match (words.next(), words.next(), words.next()) {
+        (None, _, _) => return,
+        (Some("clock"), None, _) => G_CLOCKS.borrow(cs).get().unwrap(),
+        (Some("clock"), Some("16"), None) => switch(Sysclk::Hsi16, cs),
+        (Some("clock"), Some("170"), None) => switch(Sysclk::Pll170, cs),
+        _ => {
+            out.write_str(HELP).ok();
+            return;
+        }
+    };
+    defmt::info!("System clock: {} Hz", clocks.sys_clk.0);
+    writeln!(out, "sysclk {} Hz\r", clocks.sys_clk.0).ok();
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+
+    // 1) 170 MHz from the PLL, which `clocks::set_sysclk` switches to and from.
+    let mut rcc = clocks::freeze_170mhz(dp.RCC.constrain());
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 2) Shell on the virtual COM port, a character per RX interrupt.
+    let tx = gpioa.pa2.into_alternate();
+    let rx = gpioa.pa3.into_alternate();
+    let mut serial = dp
+        .USART2
+        .usart(tx, rx, FullConfig::default().baudrate(BAUD_RATE.bps()), &mut rcc)
+        .expect("cannot configure USART2");
+    serial.write_str("\r\nClock shell, 'help' for the commands\r\n> ").ok();
+    serial.listen(SerialEvent::Rxne);
+
+    // 3) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_SERIAL.borrow(cs).replace(Some(serial));
+        G_CLOCKS.borrow(cs).set(Some(rcc.clocks));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::USART2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// USART2 interrupt: a character for the shell.
+#[interrupt]
+fn USART2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut serial = G_SERIAL.borrow(cs).borrow_mut();
+        let serial = serial.as_mut().unwrap();
+        let mut shell = G_SHELL.borrow(cs).borrow_mut();
+
+        // Reading clears the interrupt; errors (overrun) drop the character.
+        while let Ok(byte) = serial.read() {
+            let input = shell.push(byte);
+            shell.echo(serial, input).ok();
+            if input != ShellInput::Line {
+                continue;
+            }
+            let line = shell.line().unwrap_or("");
+            if line == "help" {
+                serial.write_str(HELP).ok();
+            } else {
+                run(line, serial, cs);
+            }
+            shell.prompt(serial).ok();
+        }
+    });
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+        defmt::info!("Delay Atual: {} ms", delayms);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
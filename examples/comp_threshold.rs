@@ -0,0 +1,96 @@
+//! example: analog comparator interrupt toggling the LED.
+//!
+//! COMP1 compares A1 (PA1) against half of the internal reference voltage
+//! (about 0.6 V). Its output is routed to EXTI line 21 and every crossing, in
+//! either direction, raises the `COMP1_2_3` interrupt, which toggles the LED
+//! (PA5). Connect a potentiometer wiper to PA1 and turn it across ~0.6 V.
+//! Hysteresis keeps a slowly moving or noisy input from firing bursts.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{gpioa, Output, PushPull, SignalEdge};
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::comp::{Comp1, Hysteresis, Threshold};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use core::cell::RefCell;
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the comparator that I'm going to pass around.
+static G_COMP: Mutex<RefCell<Option<Comp1>>> = Mutex::new(RefCell::new(None));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+
+    let mut led = gpioa.pa5.into_push_pull_output();
+
+    // 1) Comparator input pin in analog mode, threshold at 1/2 VREFINT.
+    let mut comp = Comp1::new(&dp.COMP, gpioa.pa1.into_analog(), Threshold::HalfVrefint, Hysteresis::Mv20);
+    // 2) Interrupt on both edges: going above and going below the threshold.
+    comp.listen(SignalEdge::RisingFalling);
+    // Start with the LED showing the current side of the threshold.
+    if comp.output() {
+        led.set_high().ok();
+    }
+
+    cortex_m::interrupt::free(|cs| {
+        defmt::info!("PA1 above threshold: {}", comp.output());
+        G_LED.borrow(cs).replace(Some(led));
+        G_COMP.borrow(cs).replace(Some(comp));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::COMP1_2_3);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn COMP1_2_3() {
+    cortex_m::interrupt::free(|cs| {
+        let mut comp = G_COMP.borrow(cs).borrow_mut();
+        let comp = comp.as_mut().unwrap();
+        // The vector is shared by COMP1, COMP2 and COMP3: check it really was ours.
+        if comp.is_pending() {
+            comp.clear_pending();
+
+            let mut led = G_LED.borrow(cs).borrow_mut();
+            led.as_mut().unwrap().toggle().ok();
+            defmt::info!("Threshold crossed, above: {}", comp.output());
+        }
+    });
+}
@@ -0,0 +1,140 @@
+//! example: breathing LED with the brightness curve computed by the CORDIC.
+//!
+//! The LED (PA5) is driven by TIM2 channel 1 in PWM mode. Every TIM3 tick
+//! advances a phase angle and starts a cosine calculation on the CORDIC, whose
+//! interrupt picks the result up and sets the brightness to
+//! `(1 - cos(phase)) / 2`, so the LED fades smoothly in and out with no
+//! lookup table of the curve. The raised cosine goes through a gamma of 2.8
+//! on the way to the duty cycle, which makes the fade look even to the eye,
+//! far more sensitive to changes at low brightness.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{gpioa, Alternate, AF1};
+use hal::pwm::{ActiveHigh, ComplementaryImpossible, Pwm, C1};
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::cordic::{Cordic, Function};
+use nucleo_g474re::gamma::Gamma28;
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{TIM2, TIM3};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for the PWM channel driving the LED
+type LedPwm = Pwm<TIM2, C1, ComplementaryImpossible, ActiveHigh, ActiveHigh>;
+
+// Brightness updates per second.
+const TICK_HZ: u32 = 100;
+// Length of one full breath (fade in and out), in ticks.
+const BREATH_TICKS: u32 = 3 * TICK_HZ;
+// Phase advance per tick, in units of π: a full turn is 2^32.
+const PHASE_STEP: i32 = (u32::MAX / BREATH_TICKS) as i32;
+
+// Create a Global Variable for the LED PWM channel that I'm going to pass around.
+static G_PWM: Mutex<RefCell<Option<LedPwm>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM3>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the CORDIC that I'm going to pass around.
+static G_CORDIC: Mutex<RefCell<Option<Cordic>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the phase of the breath.
+static G_PHASE: Mutex<Cell<i32>> = Mutex::new(Cell::new(i32::MIN));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+
+    // 1) LED pin as TIM2 channel 1 output, PWM at 1 kHz so it never flickers.
+    let pin: gpioa::PA5<Alternate<AF1>> = gpioa.pa5.into_alternate();
+    let mut pwm = dp.TIM2.pwm(pin, 1000.hz(), &mut rcc);
+    pwm.set_duty(0);
+    pwm.enable();
+
+    // 2) CORDIC raising its interrupt when each cosine is ready.
+    let mut cordic = Cordic::new(dp.CORDIC);
+    cordic.listen();
+
+    // 3) Brightness update tick.
+    let timer = Timer::new(dp.TIM3, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down((1000 / TICK_HZ).ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    cortex_m::interrupt::free(|cs| {
+        G_PWM.borrow(cs).replace(Some(pwm));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_CORDIC.borrow(cs).replace(Some(cordic));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM3);
+        cortex_m::peripheral::NVIC::unmask(interrupt::CORDIC);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// Timer Interrupt
+#[interrupt]
+fn TIM3() {
+    cortex_m::interrupt::free(|cs| {
+        // The angle wraps from +π back to -π on its own.
+        let phase = G_PHASE.borrow(cs).get().wrapping_add(PHASE_STEP);
+        G_PHASE.borrow(cs).set(phase);
+
+        let mut cordic = G_CORDIC.borrow(cs).borrow_mut();
+        cordic.as_mut().unwrap().start(Function::Cosine, phase, i32::MAX);
+
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
+
+// CORDIC Interrupt: the cosine of the new phase is ready.
+#[interrupt]
+fn CORDIC() {
+    cortex_m::interrupt::free(|cs| {
+        let mut cordic = G_CORDIC.borrow(cs).borrow_mut();
+        // Reading the result also clears the interrupt.
+        let (cos, _sin) = cordic.as_mut().unwrap().result::<i32>();
+
+        // Raised cosine in [0, 2^31], to 16 bits, gamma corrected and scaled
+        // to the PWM period.
+        let level = (i32::MAX as i64 - cos as i64) as u64 / 2;
+        let level = Gamma28::correct_fine((level >> 15).min(0xFFFF) as u16);
+
+        let mut pwm = G_PWM.borrow(cs).borrow_mut();
+        let pwm = pwm.as_mut().unwrap();
+        let duty = level as u64 * pwm.get_max_duty() as u64 / 0xFFFF;
+        pwm.set_duty(duty as u32);
+    });
+}
@@ -0,0 +1,183 @@
+//! example: the CPU load of the blink, with a 1 kHz workload to change it.
+//!
+//! The LED on PA5 blinks every second on TIM2, and TIM3 interrupts at 1 kHz
+//! to busy-wait a number of cycles, the workload. Each press of the User
+//! Button (PC13) steps the workload up, through 0, 2000, 4000 and 8000
+//! cycles of the 16 MHz HSI, about 0%, 12%, 25% and 50% of the time, then
+//! back to 0. The main loop sleeps in [`load::sleep`] and logs the load
+//! every two seconds:
+//!
+//! ```text
+//! CPU load: <percent>% (<busy> busy of <total> cycles)
+//! ```
+//!
+//! The load follows the workload, plus the small share of the interrupts
+//! themselves: how much a subsystem costs is the difference in the load
+//! with and without it.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::load;
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use defmt_rtt as _;
+
+use hal::stm32::{TIM2, TIM3};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Cycles of work per TIM3 interrupt, a step per press.
+const WORKLOADS: [u32; 4] = [0, 2000, 4000, 8000];
+
+// LED toggles between two reports.
+const REPORT_TOGGLES: u32 = 2;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the workload Timer Peripheral that I'm going to pass around.
+static G_WORK_TIM: Mutex<RefCell<Option<CountDownTimer<TIM3>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the step of the workload.
+static G_STEP: Mutex<Cell<usize>> = Mutex::new(Cell::new(0));
+// Create a Global Variable for the LED toggles since the last report.
+static G_TOGGLES: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+// Set by the timer when a report is due, for the main loop.
+static REPORT_DUE: AtomicBool = AtomicBool::new(false);
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) The cycle counter, counting in Sleep mode too.
+    load::start(&mut cp.DCB, &mut cp.DWT, &dp.DBGMCU);
+
+    // 2) Blink timer, every second.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    // 3) Workload timer, every millisecond.
+    let work_timer = Timer::new(dp.TIM3, &rcc.clocks);
+    let mut work_count_down_timer = work_timer.start_count_down(1.ms());
+    work_count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_WORK_TIM.borrow(cs).replace(Some(work_count_down_timer));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM3);
+    }
+
+    load::restart();
+    loop {
+        load::sleep();
+        if REPORT_DUE.swap(false, Ordering::Relaxed) {
+            load::report();
+        }
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Workload Global Data and Step it Up
+        let step = (G_STEP.borrow(cs).get() + 1) % WORKLOADS.len();
+        G_STEP.borrow(cs).set(step);
+        defmt::info!("Workload: {} cycles a millisecond", WORKLOADS[step]);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().unwrap();
+
+        let toggles = G_TOGGLES.borrow(cs).get() + 1;
+        if toggles == REPORT_TOGGLES {
+            G_TOGGLES.borrow(cs).set(0);
+            REPORT_DUE.store(true, Ordering::Relaxed);
+        } else {
+            G_TOGGLES.borrow(cs).set(toggles);
+        }
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
+
+// Workload Interrupt: busy for the cycles of the step.
+#[interrupt]
+fn TIM3() {
+    let cycles = cortex_m::interrupt::free(|cs| {
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_WORK_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+        WORKLOADS[G_STEP.borrow(cs).get()]
+    });
+    if cycles > 0 {
+        cortex_m::asm::delay(cycles);
+    }
+}
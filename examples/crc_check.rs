@@ -0,0 +1,116 @@
+//! example: hardware CRC self-test.
+//!
+//! Computes the CRC of the standard check string "123456789" with each of
+//! the preset algorithms and compares it against the published check value.
+//! The results are logged and the LED (PA5) lights up when all of them match.
+//! A failing algorithm makes the LED blink instead, driven by TIM2.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{gpioa, Output, PushPull};
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::crc::{Config, Crc};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::RefCell;
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Input every CRC catalogue publishes its check value for.
+const CHECK_INPUT: &[u8] = b"123456789";
+// Preset algorithms with their names and check values.
+const CHECKS: [(&str, Config, u32); 3] = [
+    ("CRC-32", Config::CRC32, 0xCBF4_3926),
+    ("CRC-16/CCITT", Config::CRC16_CCITT, 0x29B1),
+    ("CRC-8", Config::CRC8, 0xF4),
+];
+
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+
+    let mut led = gpioa.pa5.into_push_pull_output();
+
+    // 1) Run every preset over the check input.
+    let mut crc = Crc::new(dp.CRC, Config::CRC32);
+    let mut passed = true;
+    for (name, config, check) in CHECKS {
+        crc.set_config(config);
+        let result = crc.checksum(CHECK_INPUT);
+        defmt::info!("{}: {=u32:#x} (expected {=u32:#x})", name, result, check);
+        passed &= result == check;
+    }
+
+    // 2) Steady LED on success, blinking LED on failure.
+    if passed {
+        led.set_high().ok();
+        defmt::info!("CRC self-test passed");
+    } else {
+        defmt::error!("CRC self-test failed");
+        let timer = Timer::new(dp.TIM2, &rcc.clocks);
+        let mut count_down_timer = timer.start_count_down(100.ms());
+        count_down_timer.listen(Event::TimeOut);
+
+        cortex_m::interrupt::free(|cs| {
+            G_LED.borrow(cs).replace(Some(led));
+            G_TIM.borrow(cs).replace(Some(count_down_timer));
+        });
+
+        unsafe {
+            cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        }
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,235 @@
+//! example: timer-triggered DAC waveform generator.
+//!
+//! DAC1 channel 1 outputs a waveform on A2 (PA4). TIM6 triggers one
+//! conversion per sample and a DMA1 channel, claimed from the `dma` module,
+//! feeds the samples from a lookup table, so the CPU sleeps while the
+//! waveform is generated. Each press of the User Button (PC13) doubles the
+//! frequency; after the highest frequency the next waveform (sine ->
+//! triangle -> sawtooth) starts over at the lowest one.
+//! A shell on the ST-LINK virtual COM port (USART2, 115200 baud) sets either
+//! directly:
+//!
+//! ```text
+//! > freq 1000
+//! sine at 1000 Hz
+//! > wave triangle
+//! triangle at 1000 Hz
+//! ```
+//!
+//! Probe PA4 with a scope to see the result.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{Alternate, AF7, ExtiPin, Floating, Input, SignalEdge, gpioa, gpioc};
+use hal::serial::{Event as SerialEvent, FullConfig, Serial};
+use hal::syscfg::SysCfgExt;
+use hal::time::Hertz;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::dac::{Dac1Ch1, SampleClock, Table, Waveform};
+use nucleo_g474re::dma::{self, Controller, Mode, Transfer};
+use nucleo_g474re::shell::{Input as ShellInput, Shell};
+
+use cortex_m_rt::entry;
+
+use core::fmt::Write;
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::stm32::USART2;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the running DMA transfer from a waveform table to the DAC
+type WaveTransfer = Transfer<(Dac1Ch1, &'static Table)>;
+
+// Alias for the virtual COM port
+type SerialPort = Serial<USART2, gpioa::PA2<Alternate<AF7>>, gpioa::PA3<Alternate<AF7>>>;
+
+// Waveform frequencies stepped through by the button, in Hz.
+const FREQUENCIES: [u32; 4] = [50, 100, 200, 400];
+// Frequencies the shell takes, in Hz: 64 samples a period keep the top one
+// under the DAC's 1 Msps.
+const MIN_HZ: u32 = 1;
+const MAX_HZ: u32 = 10_000;
+
+const HELP: &str = "commands:\r\n  freq <1-10000 Hz>\r\n  wave <sine|triangle|sawtooth>\r\n";
+
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the sample clock (TIM6) that I'm going to pass around.
+static G_CLOCK: Mutex<RefCell<Option<SampleClock>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the DMA transfer that I'm going to pass around.
+static G_TRANSFER: Mutex<RefCell<Option<WaveTransfer>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the current waveform.
+static G_WAVEFORM: Mutex<Cell<Waveform>> = Mutex::new(Cell::new(Waveform::Sine));
+// Create a Global Variable for the current frequency, in Hz.
+static G_FREQUENCY: Mutex<Cell<u32>> = Mutex::new(Cell::new(FREQUENCIES[0]));
+// Create a Global Variable for the index into FREQUENCIES.
+static G_STEP: Mutex<Cell<usize>> = Mutex::new(Cell::new(0));
+// Create a Global Variable for the serial port that I'm going to pass around.
+static G_SERIAL: Mutex<RefCell<Option<SerialPort>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the shell line.
+static G_SHELL: Mutex<RefCell<Shell>> = Mutex::new(RefCell::new(Shell::new()));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Swaps the table feeding the DAC for the one of `waveform`.
+fn select(cs: &cortex_m::interrupt::CriticalSection, waveform: Waveform) {
+    G_WAVEFORM.borrow(cs).set(waveform);
+    let mut transfer = G_TRANSFER.borrow(cs).borrow_mut();
+    let (channel, (dac, _table)) = transfer.take().unwrap().stop();
+    transfer.replace(channel.write(dac, waveform.table(), Mode::Circular));
+}
+
+// Plays the waveform at `hz`.
+fn tune(cs: &cortex_m::interrupt::CriticalSection, hz: u32) {
+    G_FREQUENCY.borrow(cs).set(hz);
+    let mut clock = G_CLOCK.borrow(cs).borrow_mut();
+    clock.as_mut().unwrap().set_frequency(Hertz(hz));
+}
+
+// Runs one shell line.
+fn run(cs: &cortex_m::interrupt::CriticalSection, line: &str, out: &mut SerialPort) {
+    let mut words = line.split_ascii_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (None, _, _) => return,
+        (Some("freq"), Some(hz), None) => match hz.parse() {
+            Ok(hz) if (MIN_HZ..=MAX_HZ).contains(&hz) => tune(cs, hz),
+            _ => {
+                out.write_str("freq: 1 to 10000 Hz\r\n").ok();
+                return;
+            }
+        },
+        (Some("wave"), Some(name), None) => match Waveform::from_name(name) {
+            Some(waveform) => select(cs, waveform),
+            None => {
+                out.write_str("wave: sine, triangle or sawtooth\r\n").ok();
+                return;
+            }
+        },
+        _ => {
+            out.write_str(HELP).ok();
+            return;
+        }
+    }
+    let (waveform, hz) = (G_WAVEFORM.borrow(cs).get(), G_FREQUENCY.borrow(cs).get());
+    writeln!(out, "{} at {} Hz\r", waveform.name(), hz).ok();
+    defmt::info!("{} at {} Hz", waveform, hz);
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) DAC output pin in analog mode and DAC channel waiting for TIM6 triggers.
+    let dac = Dac1Ch1::new(dp.DAC1, gpioa.pa4.into_analog());
+    // 2) Circular DMA transfer from the lookup table to the DAC data register.
+    let channel = dma::claim(Controller::Dma1).expect("no free DMA channel");
+    let waveform = Waveform::Sine;
+    let transfer = channel.write(dac, waveform.table(), Mode::Circular);
+    // 3) Sample clock: FREQUENCIES[0] periods of the table per second.
+    let mut clock = SampleClock::new(dp.TIM6, &rcc.clocks);
+    clock.set_frequency(Hertz(FREQUENCIES[0]));
+    clock.start();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    // 4) Shell on the virtual COM port, a character per RX interrupt.
+    let tx = gpioa.pa2.into_alternate();
+    let rx = gpioa.pa3.into_alternate();
+    let mut serial = dp
+        .USART2
+        .usart(tx, rx, FullConfig::default().baudrate(115_200.bps()), &mut rcc)
+        .expect("cannot configure USART2");
+    serial.write_str("\r\nWaveform shell, 'help' for the commands\r\n> ").ok();
+    serial.listen(SerialEvent::Rxne);
+
+    cortex_m::interrupt::free(|cs| {
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_SERIAL.borrow(cs).replace(Some(serial));
+        G_CLOCK.borrow(cs).replace(Some(clock));
+        G_TRANSFER.borrow(cs).replace(Some(transfer));
+        defmt::info!("{} at {} Hz", waveform, FREQUENCIES[0]);
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::USART2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        let step = (G_STEP.borrow(cs).get() + 1) % FREQUENCIES.len();
+        G_STEP.borrow(cs).set(step);
+
+        // Wrapped around: swap the table feeding the DAC for the next waveform.
+        if step == 0 {
+            select(cs, G_WAVEFORM.borrow(cs).get().next());
+        }
+
+        tune(cs, FREQUENCIES[step]);
+        defmt::info!("{} at {} Hz", G_WAVEFORM.borrow(cs).get(), FREQUENCIES[step]);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+
+// USART2 interrupt: a character for the shell.
+#[interrupt]
+fn USART2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut serial = G_SERIAL.borrow(cs).borrow_mut();
+        let serial = serial.as_mut().unwrap();
+        let mut shell = G_SHELL.borrow(cs).borrow_mut();
+
+        // Reading clears the interrupt; errors (overrun) drop the character.
+        while let Ok(byte) = serial.read() {
+            let input = shell.push(byte);
+            shell.echo(serial, input).ok();
+            if input != ShellInput::Line {
+                continue;
+            }
+            run(cs, shell.line().unwrap_or(""), serial);
+            shell.prompt(serial).ok();
+        }
+    });
+}
@@ -0,0 +1,205 @@
+//! example: two buttons, each debounced by its own algorithm.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5), the delay
+//! halved by either of two buttons:
+//!
+//! | Button     | Read                               | Debouncer                 |
+//! |------------|------------------------------------|---------------------------|
+//! | B1 (PC13)  | on both edges (EXTI), and each ms  | `Timeout`, a 50 ms window |
+//! | PA0 (A0)   | each ms, from the tick handler     | `Integrator` of 10 samples |
+//!
+//! The external button goes between A0 and GND, on the pin's pull-up. B1
+//! answers on the first edge of its press; A0 10 ms later, but a spike
+//! shorter than that on its wire is no press. Each debounced change is
+//! logged:
+//!
+//! ```text
+//! B1 Pressed at 4120 ms
+//! Delay Atual: 500 ms
+//! B1 Released at 4310 ms
+//! ```
+//!
+//! The millisecond tick is the `tick` module's, on SysTick; built with the
+//! `tick-tim6` feature it comes from TIM6 instead, with no other change.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PullUp, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::debounce::{Debouncer, Event as DebounceEvent, Integrator, Timeout};
+use nucleo_g474re::tick::{self, TickSource};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+nucleo_g474re::tick_handler!();
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pins
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+type A0Pin = gpioa::PA0<Input<PullUp>>;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create Global Variables for the Button GPIO Peripherals that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+static G_A0: Mutex<RefCell<Option<A0Pin>>> = Mutex::new(RefCell::new(None));
+// Create Global Variables for the debouncer of each button.
+static G_B1_DEBOUNCE: Mutex<RefCell<Timeout>> = Mutex::new(RefCell::new(Timeout::new(50)));
+static G_A0_DEBOUNCE: Mutex<RefCell<Integrator>> = Mutex::new(RefCell::new(Integrator::new(10)));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Feeds a raw level of the button `name` to its debouncer, and halves the
+// delay on a debounced press.
+fn feed(cs: &cortex_m::interrupt::CriticalSection, name: &str, debouncer: &mut impl Debouncer, pressed: bool) {
+    let now = tick::now_ms();
+    let Some(event) = debouncer.update(pressed, now) else {
+        return;
+    };
+    defmt::info!("{} {} at {} ms", name, event, now);
+    if event != DebounceEvent::Pressed {
+        return;
+    }
+
+    // Obtain Access to Delay Global Data and Adjust Delay
+    G_DELAYMS
+        .borrow(cs)
+        .set(G_DELAYMS.borrow(cs).get()/2);
+
+    if G_DELAYMS.borrow(cs).get() < 125_u32 {
+        G_DELAYMS.borrow(cs).set(1000_u32);
+    }
+
+    let delayms = G_DELAYMS.borrow(cs).get();
+    let mut timer = G_TIM.borrow(cs).borrow_mut();
+    timer.as_mut().unwrap().start(delayms.ms());
+    defmt::info!("Delay Atual: {} ms", delayms);
+}
+
+// B1's level, high when pressed.
+fn b1(cs: &cortex_m::interrupt::CriticalSection) -> bool {
+    let button = G_BUTTON.borrow(cs).borrow();
+    button.as_ref().unwrap().is_high().unwrap_or(false)
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    #[cfg(not(feature = "tick-tim6"))]
+    let cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) The millisecond tick, which also samples the buttons.
+    #[cfg(not(feature = "tick-tim6"))]
+    let mut source = tick::Source::new(cp.SYST);
+    #[cfg(feature = "tick-tim6")]
+    let mut source = tick::Source::new(dp.TIM6);
+    source.start(&rcc.clocks);
+
+    // 2) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // 3) B1 interrupting on both edges; A0 only sampled.
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::RisingFalling);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    let a0 = gpioa.pa0.into_pull_up_input();
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_A0.borrow(cs).replace(Some(a0));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+    });
+    tick::on_tick(sample);
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// Every millisecond, from the tick interrupt: a sample of A0, and of B1
+// for the level it settles on after its window.
+fn sample() {
+    cortex_m::interrupt::free(|cs| {
+        let a0 = G_A0.borrow(cs).borrow();
+        let pressed = a0.as_ref().unwrap().is_low().unwrap_or(false);
+        feed(cs, "A0", &mut *G_A0_DEBOUNCE.borrow(cs).borrow_mut(), pressed);
+
+        let pressed = b1(cs);
+        feed(cs, "B1", &mut *G_B1_DEBOUNCE.borrow(cs).borrow_mut(), pressed);
+    });
+}
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        G_BUTTON.borrow(cs).borrow_mut().as_mut().unwrap().clear_interrupt_pending_bit();
+
+        let pressed = b1(cs);
+        feed(cs, "B1", &mut *G_B1_DEBOUNCE.borrow(cs).borrow_mut(), pressed);
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,179 @@
+//! example: the blink's logs deferred from its interrupts to the main loop.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), its interrupts formatting nothing: each
+//! pushes a `deferlog::Record`, an id and two words, to a lock-free queue,
+//! and returns. The main loop wakes after them, pops the records and logs
+//! each, with the RTT write outside any interrupt and any critical section:
+//!
+//! ```text
+//! Toggle 12: LED on
+//! Delay Atual: 500 ms (press 1)
+//! ```
+//!
+//! A record pushed on a full queue is dropped, and the main loop logs how
+//! many were.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::deferlog::{Queue, Record};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Record ids: a toggle, [count, LED on], and a new delay, [delay, presses].
+const TOGGLE: u16 = 0;
+const DELAY: u16 = 1;
+
+// The queue of records, from the interrupts to the main loop.
+static LOG: Queue<32> = Queue::new();
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+// Create Global Variables for the toggles and the presses so far.
+static G_TOGGLES: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+static G_PRESSES: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Logs a record as its id says.
+fn emit(record: Record) {
+    match record.id {
+        TOGGLE => defmt::info!("Toggle {}: LED {}", record.args[0], if record.args[1] != 0 { "on" } else { "off" }),
+        DELAY => defmt::info!("Delay Atual: {} ms (press {})", record.args[0], record.args[1]),
+        id => defmt::warn!("Unknown record {}", id),
+    }
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+
+        // 2) The records of the interrupts just served, logged here.
+        while let Some(record) = LOG.pop() {
+            emit(record);
+        }
+        let dropped = LOG.dropped();
+        if dropped > 0 {
+            defmt::warn!("{} records dropped", dropped);
+        }
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+
+        let presses = G_PRESSES.borrow(cs).get() + 1;
+        G_PRESSES.borrow(cs).set(presses);
+        LOG.push(Record::new(DELAY, [delayms, presses]));
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        let led = led.as_mut().unwrap();
+        led.toggle().ok();
+
+        let toggles = G_TOGGLES.borrow(cs).get() + 1;
+        G_TOGGLES.borrow(cs).set(toggles);
+        let on = led.is_set_high().unwrap_or(false);
+        LOG.push(Record::new(TOGGLE, [toggles, on as u32]));
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,260 @@
+//! example: the blink going on when the optional subsystems fail.
+//!
+//! The LED on PA5 blinks as in the main program, the User Button (PC13)
+//! halving the delay, and every five blink cycles a report goes out through
+//! three subsystems the blink does without:
+//!
+//! - the ST-LINK virtual COM port (USART2, 115200 baud), a status line,
+//! - a TMP102 on I2C1 (PB9 SDA, PB8 SCL), the temperature for the report,
+//! - FDCAN1 on PA12 TX / PA11 RX through a transceiver, a telemetry frame.
+//!
+//! Each setup goes through [`Capabilities::check`]: a board without the
+//! sensor, or without a CAN bus, logs what is missing and blinks anyway,
+//! and the report leaves out what is down. A subsystem failing later is
+//! dropped the same way: a sensor read failing, or CAN frames nobody
+//! acknowledges.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{Alternate, AF7, ExtiPin, Floating, Input, Output, PushPull, SignalEdge, Speed, gpioa, gpioc};
+use hal::serial::{FullConfig, Serial};
+use hal::syscfg::SysCfgExt;
+use hal::can::CanExt;
+
+use stm32g4xx_hal as hal;
+
+use fdcan::id::StandardId;
+
+use nucleo_g474re::can::{Bitrate, Config, Event as CanEvent, Telemetry};
+use nucleo_g474re::compat::Compat;
+use nucleo_g474re::i2c::{i2c1, I2c1, Tmp102};
+use nucleo_g474re::init::{Capabilities, Subsystem};
+
+use cortex_m_rt::entry;
+
+use core::fmt::Write;
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{FDCAN1, TIM2, USART2};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the virtual COM port
+type SerialPort = Serial<USART2, gpioa::PA2<Alternate<AF7>>, gpioa::PA3<Alternate<AF7>>>;
+
+// Blink cycles (LED on and off) between two reports.
+const BLINK_CYCLES: u32 = 5;
+
+// Time the CAN node gets to find the bus idle at start, in milliseconds.
+const CAN_JOIN_MS: u32 = 10;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for each optional subsystem, `None` if it failed.
+static G_SERIAL: Mutex<RefCell<Option<SerialPort>>> = Mutex::new(RefCell::new(None));
+static G_SENSOR: Mutex<RefCell<Option<Tmp102<Compat<I2c1>>>>> = Mutex::new(RefCell::new(None));
+static G_CAN: Mutex<RefCell<Option<Telemetry<FDCAN1>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the subsystems that are up.
+static G_CAPS: Mutex<Cell<Capabilities>> = Mutex::new(Cell::new(Capabilities::NONE));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+// Create a Global Variable for the LED toggles since the last report.
+static G_TOGGLES: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Records that `subsystem` failed while running.
+fn lose(cs: &cortex_m::interrupt::CriticalSection, subsystem: Subsystem) {
+    let mut caps = G_CAPS.borrow(cs).get();
+    caps.lose(subsystem);
+    G_CAPS.borrow(cs).set(caps);
+}
+
+// Sends the report through each subsystem that is up.
+fn report(cs: &cortex_m::interrupt::CriticalSection) {
+    let delayms = G_DELAYMS.borrow(cs).get();
+
+    let mut millicelsius = None;
+    if G_CAPS.borrow(cs).get().has(Subsystem::Sensor) {
+        let mut sensor = G_SENSOR.borrow(cs).borrow_mut();
+        match sensor.as_mut().unwrap().read_millicelsius() {
+            Ok(reading) => millicelsius = Some(reading),
+            Err(_) => lose(cs, Subsystem::Sensor),
+        }
+    }
+
+    if G_CAPS.borrow(cs).get().has(Subsystem::Can) {
+        let mut can = G_CAN.borrow(cs).borrow_mut();
+        let can = can.as_mut().unwrap();
+        // A full queue: no node acknowledged the last frames.
+        if !can.is_active() || !can.send(CanEvent::BlinkCycles, 0, delayms) {
+            lose(cs, Subsystem::Can);
+        }
+    }
+
+    let caps = G_CAPS.borrow(cs).get();
+    if caps.has(Subsystem::Serial) {
+        let mut serial = G_SERIAL.borrow(cs).borrow_mut();
+        let serial = serial.as_mut().unwrap();
+        write!(serial, "delay {} ms", delayms).ok();
+        if let Some(millicelsius) = millicelsius {
+            write!(serial, ", {} mC", millicelsius).ok();
+        }
+        if !caps.has(Subsystem::Can) {
+            serial.write_str(", no CAN").ok();
+        }
+        serial.write_str("\r\n").ok();
+    }
+
+    if caps.is_degraded() {
+        defmt::info!("Degraded: {}", caps);
+    }
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+    let mut caps = Capabilities::NONE;
+
+    // 1) Serial port on the virtual COM port.
+    let tx = gpioa.pa2.into_alternate();
+    let rx = gpioa.pa3.into_alternate();
+    let serial = dp.USART2.usart(tx, rx, FullConfig::default().baudrate(115_200.bps()), &mut rcc);
+    let serial = caps.check(Subsystem::Serial, serial);
+
+    // 2) The sensor, there if it answers a first reading.
+    let sda = gpiob.pb9.into_alternate_open_drain();
+    let scl = gpiob.pb8.into_alternate_open_drain();
+    let mut sensor = Tmp102::new(Compat(i2c1(dp.I2C1, sda, scl, &mut rcc)), Tmp102::<Compat<I2c1>>::DEFAULT_ADDRESS);
+    let probe = sensor.read_millicelsius().map(|_| sensor);
+    let sensor = caps.check(Subsystem::Sensor, probe);
+
+    // 3) FDCAN1, there if it joins the bus in CAN_JOIN_MS.
+    let tx = gpioa.pa12.into_alternate().set_speed(Speed::VeryHigh);
+    let rx = gpioa.pa11.into_alternate().set_speed(Speed::VeryHigh);
+    let can = dp.FDCAN1.fdcan(tx, rx, &rcc);
+    let config = Config {
+        bitrate: Bitrate::Kbps500,
+        id: StandardId::new(0x123).unwrap().into(),
+    };
+    let telemetry = Telemetry::new(can, config, rcc.clocks.apb1_clk);
+    let mut waited_ms = 0;
+    while !telemetry.is_active() && waited_ms < CAN_JOIN_MS {
+        cortex_m::asm::delay(rcc.clocks.sys_clk.0 / 1000);
+        waited_ms += 1;
+    }
+    let joined = if telemetry.is_active() { Ok(telemetry) } else { Err(()) };
+    let telemetry = caps.check(Subsystem::Can, joined);
+    defmt::info!("Capabilities: {}", caps);
+
+    // 4) Blink timer, as in the main program: it needs none of the above.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_SERIAL.borrow(cs).replace(serial);
+        G_SENSOR.borrow(cs).replace(sensor);
+        G_CAN.borrow(cs).replace(telemetry);
+        G_CAPS.borrow(cs).set(caps);
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+        defmt::info!("Delay Atual: {} ms", delayms);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().unwrap();
+
+        let toggles = G_TOGGLES.borrow(cs).get() + 1;
+        if toggles == BLINK_CYCLES * 2 {
+            G_TOGGLES.borrow(cs).set(0);
+            report(cs);
+        } else {
+            G_TOGGLES.borrow(cs).set(toggles);
+        }
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,178 @@
+//! example: the blink, and the system bootloader after a 5 s button hold.
+//!
+//! The LED on PA5 blinks as in the main program, a press of the User Button
+//! (PC13) halving the delay. TIM3 looks at the button every 10 ms: held down
+//! for [`bootloader::HOLD_MS`], the blink stops and
+//! [`bootloader::enter_system_dfu`] hands the chip to ST's bootloader, ready
+//! for a new image over the virtual COM port:
+//!
+//! ```text
+//! STM32_Programmer_CLI -c port=/dev/ttyACM0 -w firmware.bin 0x08000000 -v -g
+//! ```
+//!
+//! `-g` starts the new image once written; a reset does too.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::hal::digital::v2::InputPin;
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::bootloader;
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{TIM2, TIM3};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Period of the button polling, in milliseconds.
+const POLL_MS: u32 = 10;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the polling Timer Peripheral that I'm going to pass around.
+static G_POLL: Mutex<RefCell<Option<CountDownTimer<TIM3>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+// Create a Global Variable for how long the button has been held.
+static G_HELDMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    // 2) Polling timer, timing the hold.
+    let poll = Timer::new(dp.TIM3, &rcc.clocks);
+    let mut poll_timer = poll.start_count_down(POLL_MS.ms());
+    poll_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_POLL.borrow(cs).replace(Some(poll_timer));
+    });
+    defmt::info!("Hold the button {} ms for the system bootloader", bootloader::HOLD_MS);
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM3);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+        defmt::info!("Delay Atual: {} ms", delayms);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().unwrap();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
+
+// Button polling: the hold, counted while B1 drives PC13 high.
+#[interrupt]
+fn TIM3() {
+    let held = cortex_m::interrupt::free(|cs| {
+        let button = G_BUTTON.borrow(cs).borrow();
+        let held = if button.as_ref().unwrap().is_high().unwrap_or(false) {
+            G_HELDMS.borrow(cs).get() + POLL_MS
+        } else {
+            0
+        };
+        G_HELDMS.borrow(cs).set(held);
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut poll = G_POLL.borrow(cs).borrow_mut();
+        poll.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+        held
+    });
+
+    if held >= bootloader::HOLD_MS {
+        bootloader::enter_system_dfu();
+    }
+}
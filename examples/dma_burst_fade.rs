@@ -0,0 +1,144 @@
+//! example: two LEDs breathing in turn, their duties fed by DMA bursts.
+//!
+//! TIM2 runs PWM at 500 Hz on channel 1, the user LED on PA5, and channel 2
+//! on A1 (PA1), an LED and resistor to GND. Every update event requests a
+//! DMA burst of both duties from a circular buffer of `ROWS` rows, one row
+//! a period, so the fades run with no interrupt and no CPU: the LED fades
+//! in as the other fades out, gamma corrected, over 2 s. Each press of the
+//! User Button (PC13) encodes the buffer again with twice the breaths in
+//! it, halving the period, back to one after eight.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{Alternate, ExtiPin, Floating, Input, SignalEdge, AF1, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::burst::PwmBurst;
+use nucleo_g474re::dma::{self, Controller, Mode, Transfer};
+use nucleo_g474re::gamma::Gamma28;
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+// PWM channels of the burst: the user LED and A1.
+const CHANNELS: usize = 2;
+// PWM frequency, a row of the buffer a period.
+const PWM_HZ: u32 = 500;
+// Rows of the buffer: 2 s at 500 Hz.
+const ROWS: usize = 1000;
+// Most breaths in the buffer.
+const MAX_BREATHS: usize = 8;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the DMA transfer of the duties to the timer
+type BurstTransfer = Transfer<(PwmBurst<CHANNELS>, &'static mut [u32; ROWS * CHANNELS])>;
+
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the DMA transfer that I'm going to pass around.
+static G_TRANSFER: Mutex<RefCell<Option<BurstTransfer>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the breaths in the buffer.
+static G_BREATHS: Mutex<Cell<usize>> = Mutex::new(Cell::new(1));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Fills `buffer` with `breaths` triangle fades, channel 2 the mirror of
+// channel 1, up to `max_duty`.
+fn encode(buffer: &mut [u32], breaths: usize, max_duty: u32) {
+    for (row, duties) in buffer.chunks_exact_mut(CHANNELS).enumerate() {
+        // 0 to 511 once a breath, up then down.
+        let phase = (row * breaths % ROWS * 512 / ROWS) as u32;
+        let level = if phase < 256 { phase } else { 511 - phase } as u8;
+        duties[0] = Gamma28::duty(level, max_duty);
+        duties[1] = Gamma28::duty(255 - level, max_duty);
+    }
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) TIM2 PWM on PA5 and PA1, requesting a burst of duties every period.
+    let _led: gpioa::PA5<Alternate<AF1>> = gpioa.pa5.into_alternate();
+    let _a1: gpioa::PA1<Alternate<AF1>> = gpioa.pa1.into_alternate();
+    let pwm = PwmBurst::<CHANNELS>::new(dp.TIM2, PWM_HZ.hz(), &rcc.clocks);
+
+    // 2) The duties.
+    let buffer = cortex_m::singleton!(: [u32; ROWS * CHANNELS] = [0; ROWS * CHANNELS]).unwrap();
+    encode(buffer, 1, pwm.max_duty());
+
+    // 3) One circular DMA transfer, on a channel claimed from the `dma`
+    //    module, plays them for good.
+    let channel = dma::claim(Controller::Dma1).expect("no free DMA channel");
+    let transfer = channel.write(pwm, buffer, Mode::Circular);
+    defmt::info!("1 breath per {} ms", ROWS as u32 * 1000 / PWM_HZ);
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TRANSFER.borrow(cs).replace(Some(transfer));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        let breaths = G_BREATHS.borrow(cs).get() * 2;
+        let breaths = if breaths > MAX_BREATHS { 1 } else { breaths };
+        G_BREATHS.borrow(cs).set(breaths);
+
+        // Stop the DMA, encode the buffer again and start it over.
+        let mut transfer = G_TRANSFER.borrow(cs).borrow_mut();
+        let (channel, (mut pwm, buffer)) = transfer.take().unwrap().stop();
+        pwm.rearm();
+        encode(buffer, breaths, pwm.max_duty());
+        transfer.replace(channel.write(pwm, buffer, Mode::Circular));
+        defmt::info!("1 breath per {} ms", ROWS as u32 * 1000 / PWM_HZ / breaths as u32);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
@@ -0,0 +1,171 @@
+//! example: two transfers on channels claimed from the `dma` module.
+//!
+//! One channel writes a line to the ST-LINK virtual COM port (USART2 TX,
+//! 115200 baud) with no CPU, the other copies a table of 256 words from
+//! flash to RAM, memory to memory. Both interrupt on completion and share a
+//! handler set with `dma::on_event`, which logs the channel and the event;
+//! neither program nor driver names a stream. Each press of the User Button
+//! (PC13) checks the copy made before and starts both transfers again:
+//!
+//! ```text
+//! Dma1 channel 1: Complete
+//! Dma1 channel 2: Complete
+//! Copy of 256 words: match
+//! ```
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{Alternate, ExtiPin, Floating, Input, SignalEdge, AF7, gpioa, gpioc};
+use hal::serial::{FullConfig, Tx, DMA};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::dma::{self, Controller, Event, Id, Mode, Transfer};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use core::cell::RefCell;
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+nucleo_g474re::dma_handlers!();
+
+// Words in the table copied.
+const WORDS: usize = 256;
+
+// The line written on every press.
+static LINE: &[u8] = b"Hello from a DMA channel\r\n";
+
+// The table copied: every byte of a word its index.
+static TABLE: [u32; WORDS] = {
+    let mut table = [0; WORDS];
+    let mut i = 0;
+    while i < WORDS {
+        table[i] = i as u32 * 0x0101_0101;
+        i += 1;
+    }
+    table
+};
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the USART2 transmitter, in DMA mode
+type SerialTx = Tx<stm32::USART2, gpioa::PA2<Alternate<AF7>>, DMA>;
+
+// Alias for the DMA transfer of the line to the serial port
+type SendTransfer = Transfer<(SerialTx, &'static [u8])>;
+
+// Alias for the DMA copy of the table
+type CopyTransfer = Transfer<(&'static [u32], &'static mut [u32])>;
+
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the serial transfer that I'm going to pass around.
+static G_SEND: Mutex<RefCell<Option<SendTransfer>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the copy that I'm going to pass around.
+static G_COPY: Mutex<RefCell<Option<CopyTransfer>>> = Mutex::new(RefCell::new(None));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Both channels' events.
+fn on_dma(channel: Id, event: Event) {
+    defmt::info!("{} channel {}: {}", channel.controller, channel.number, event);
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) USART2 on the virtual COM port, its transmitter in DMA mode.
+    let tx = gpioa.pa2.into_alternate();
+    let rx = gpioa.pa3.into_alternate();
+    let serial = dp
+        .USART2
+        .usart(tx, rx, FullConfig::default().baudrate(115_200.bps()), &mut rcc)
+        .expect("cannot configure USART2");
+    let (tx, _rx) = serial.split();
+    let tx = tx.enable_dma();
+
+    // 2) Two channels of DMA1, whichever are free, interrupting on completion.
+    let mut send = dma::claim(Controller::Dma1).expect("no free DMA channel");
+    let mut copy = dma::claim(Controller::Dma1).expect("no free DMA channel");
+    for channel in [&mut send, &mut copy] {
+        channel.listen(Event::Complete);
+        channel.listen(Event::Error);
+        dma::on_event(channel.id(), on_dma);
+        channel.unmask();
+    }
+
+    // 3) The first line and the first copy.
+    let buffer: &'static mut [u32] = cortex_m::singleton!(: [u32; WORDS] = [0; WORDS]).unwrap();
+    let send = send.write(tx, LINE, Mode::Once);
+    let copy = copy.copy(&TABLE, buffer);
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_SEND.borrow(cs).replace(Some(send));
+        G_COPY.borrow(cs).replace(Some(copy));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // The last copy, checked, and the next one.
+        let mut copy = G_COPY.borrow(cs).borrow_mut();
+        let (channel, (table, buffer)) = copy.take().unwrap().stop();
+        let matches = table == &*buffer;
+        defmt::info!("Copy of {} words: {}", WORDS, if matches { "match" } else { "differ" });
+        buffer.fill(0);
+        copy.replace(channel.copy(table, buffer));
+
+        // The line again, once the last one is out.
+        let mut send = G_SEND.borrow(cs).borrow_mut();
+        if send.as_ref().unwrap().is_complete() {
+            let (channel, (tx, line)) = send.take().unwrap().stop();
+            send.replace(channel.write(tx, line, Mode::Once));
+        }
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
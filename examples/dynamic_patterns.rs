@@ -0,0 +1,350 @@
+//! example: blink patterns defined at run time, kept on the heap.
+//!
+//! A shell on the ST-LINK virtual COM port (USART2, 115200 baud) takes blink
+//! patterns as lists of durations in milliseconds, lit and dark in turn,
+//! and the LED on PA5 plays one of them; the User Button (PC13) moves on to
+//! the next:
+//!
+//! ```text
+//! > add sos 150 150 150 150 150 450 450 150 450 150 450 450 150 150 150 150 150 1000
+//! > add heartbeat 100 100 100 700
+//! > play heartbeat
+//! > list
+//! blink: 2 steps
+//! sos: 18 steps
+//! heartbeat: 4 steps
+//! ```
+//!
+//! `heap` gives the bytes of the heap in use and free, and `remove` gives a
+//! pattern's back.
+//!
+//! Neither the number of patterns nor their lengths are known before they
+//! are typed, which is what a heap is for: the patterns are a `Vec`, each a
+//! `String` name and its steps in a `Box<[u16]>`, sized once from the
+//! command. Every allocation goes through `try_reserve`, so a full heap is
+//! an error on the shell instead of a panic. Build it with the heap:
+//!
+//! ```text
+//! cargo run --example dynamic_patterns --features alloc
+//! ```
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{Alternate, AF7, ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::serial::{Event as SerialEvent, FullConfig, Serial};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::heap;
+use nucleo_g474re::shell::{Input as ShellInput, Shell};
+
+use cortex_m_rt::entry;
+
+use core::fmt::Write;
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{TIM2, USART2};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the virtual COM port
+type SerialPort = Serial<USART2, gpioa::PA2<Alternate<AF7>>, gpioa::PA3<Alternate<AF7>>>;
+
+// Longest step: about the most the HAL's `start_count_down` takes in milliseconds.
+const MAX_STEP_MS: u16 = 1000;
+
+const HELP: &str = "commands:\r\n  add <name> <lit ms> <dark ms>...\r\n  play <name>\r\n  remove <name>\r\n  list\r\n  heap\r\n";
+
+// A pattern typed in the shell: lit for the first step, dark for the next, and so on.
+struct UserPattern {
+    name: String,
+    steps: Box<[u16]>,
+}
+
+// Shell command errors.
+#[derive(Debug)]
+enum CommandError {
+    Usage,
+    InvalidStep,
+    Exists,
+    NotFound,
+    OutOfMemory,
+}
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the serial port that I'm going to pass around.
+static G_SERIAL: Mutex<RefCell<Option<SerialPort>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the shell line.
+static G_SHELL: Mutex<RefCell<Shell>> = Mutex::new(RefCell::new(Shell::new()));
+// Create a Global Variable for the patterns, on the heap.
+static G_PATTERNS: Mutex<RefCell<Vec<UserPattern>>> = Mutex::new(RefCell::new(Vec::new()));
+// Create a Global Variable for the pattern playing, and its step.
+static G_PLAYING: Mutex<Cell<Option<usize>>> = Mutex::new(Cell::new(None));
+static G_STEP: Mutex<Cell<usize>> = Mutex::new(Cell::new(0));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Copies `name` and `words` to the heap as a new pattern.
+fn add<'a>(patterns: &mut Vec<UserPattern>, name: &str, words: impl Iterator<Item = &'a str>) -> Result<(), CommandError> {
+    if patterns.iter().any(|pattern| pattern.name == name) {
+        return Err(CommandError::Exists);
+    }
+    let mut steps = Vec::new();
+    for word in words {
+        let step: u16 = word.parse().map_err(|_| CommandError::InvalidStep)?;
+        if !(1..=MAX_STEP_MS).contains(&step) {
+            return Err(CommandError::InvalidStep);
+        }
+        steps.try_reserve(1).map_err(|_| CommandError::OutOfMemory)?;
+        steps.push(step);
+    }
+    // Lit and dark steps in pairs, so each repeat starts lit.
+    if steps.is_empty() || !steps.len().is_multiple_of(2) {
+        return Err(CommandError::Usage);
+    }
+
+    let mut owned = String::new();
+    owned.try_reserve_exact(name.len()).map_err(|_| CommandError::OutOfMemory)?;
+    owned.push_str(name);
+    patterns.try_reserve(1).map_err(|_| CommandError::OutOfMemory)?;
+    // The steps never grow again: a boxed slice drops the spare capacity.
+    patterns.push(UserPattern { name: owned, steps: steps.into_boxed_slice() });
+    Ok(())
+}
+
+// Plays pattern `index` from its first step, or turns the LED off for `None`.
+fn play(cs: &cortex_m::interrupt::CriticalSection, index: Option<usize>) {
+    G_PLAYING.borrow(cs).set(index);
+    G_STEP.borrow(cs).set(0);
+    let patterns = G_PATTERNS.borrow(cs).borrow();
+    let mut led = G_LED.borrow(cs).borrow_mut();
+    let led = led.as_mut().unwrap();
+    let mut timer = G_TIM.borrow(cs).borrow_mut();
+    match index.map(|index| &patterns[index]) {
+        Some(pattern) => {
+            led.set_high().ok();
+            timer.as_mut().unwrap().start((pattern.steps[0] as u32).ms());
+            defmt::info!("Playing pattern {}", index);
+        }
+        None => {
+            led.set_low().ok();
+        }
+    }
+}
+
+// Runs one shell line.
+fn run(cs: &cortex_m::interrupt::CriticalSection, line: &str, out: &mut SerialPort) {
+    let mut words = line.split_ascii_whitespace();
+    let result = match (words.next(), words.next()) {
+        (None, _) => Ok(()),
+        (Some("add"), Some(name)) => add(&mut G_PATTERNS.borrow(cs).borrow_mut(), name, words),
+        (Some("play"), Some(name)) => {
+            let index = G_PATTERNS.borrow(cs).borrow().iter().position(|pattern| pattern.name == name);
+            match index {
+                Some(index) => {
+                    play(cs, Some(index));
+                    Ok(())
+                }
+                None => Err(CommandError::NotFound),
+            }
+        }
+        (Some("remove"), Some(name)) => {
+            let mut patterns = G_PATTERNS.borrow(cs).borrow_mut();
+            match patterns.iter().position(|pattern| pattern.name == name) {
+                Some(index) => {
+                    // Dropping it gives its name and steps back to the heap.
+                    patterns.remove(index);
+                    let playing = G_PLAYING.borrow(cs).get();
+                    let len = patterns.len();
+                    drop(patterns);
+                    match playing {
+                        Some(current) if current == index => play(cs, (len > 0).then_some(0)),
+                        Some(current) if current > index => G_PLAYING.borrow(cs).set(Some(current - 1)),
+                        _ => {}
+                    }
+                    Ok(())
+                }
+                None => Err(CommandError::NotFound),
+            }
+        }
+        (Some("list"), None) => {
+            for pattern in G_PATTERNS.borrow(cs).borrow().iter() {
+                writeln!(out, "{}: {} steps\r", pattern.name, pattern.steps.len()).ok();
+            }
+            Ok(())
+        }
+        (Some("heap"), None) => {
+            writeln!(out, "{} bytes used, {} free\r", heap::used(), heap::free()).ok();
+            Ok(())
+        }
+        _ => {
+            out.write_str(HELP).ok();
+            Ok(())
+        }
+    };
+    if let Err(error) = result {
+        writeln!(out, "{:?}\r", error).ok();
+    }
+}
+
+
+#[entry]
+fn main() -> ! {
+    // 1) The heap, before anything allocates.
+    heap::init();
+
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 2) Shell on the virtual COM port, a character per RX interrupt.
+    let tx = gpioa.pa2.into_alternate();
+    let rx = gpioa.pa3.into_alternate();
+    let mut serial = dp
+        .USART2
+        .usart(tx, rx, FullConfig::default().baudrate(115_200.bps()), &mut rcc)
+        .expect("cannot configure USART2");
+    serial.write_str("\r\nPattern shell, 'help' for the commands\r\n> ").ok();
+    serial.listen(SerialEvent::Rxne);
+
+    // 3) Step timer, restarted at the length of each step.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_SERIAL.borrow(cs).replace(Some(serial));
+
+        // The blink of the main program, the first pattern.
+        let steps = ["1000", "1000"].into_iter();
+        add(&mut G_PATTERNS.borrow(cs).borrow_mut(), "blink", steps).unwrap();
+        play(cs, Some(0));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::USART2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// USART2 interrupt: a character for the shell.
+#[interrupt]
+fn USART2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut serial = G_SERIAL.borrow(cs).borrow_mut();
+        let serial = serial.as_mut().unwrap();
+        let mut shell = G_SHELL.borrow(cs).borrow_mut();
+
+        // Reading clears the interrupt; errors (overrun) drop the character.
+        while let Ok(byte) = serial.read() {
+            let input = shell.push(byte);
+            shell.echo(serial, input).ok();
+            if input != ShellInput::Line {
+                continue;
+            }
+            run(cs, shell.line().unwrap_or(""), serial);
+            shell.prompt(serial).ok();
+        }
+    });
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Pattern Global Data and play the next one
+        let len = G_PATTERNS.borrow(cs).borrow().len();
+        if len > 0 {
+            let next = G_PLAYING.borrow(cs).get().map_or(0, |index| (index + 1) % len);
+            play(cs, Some(next));
+        }
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt: the next step of the pattern.
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        let timer = timer.as_mut().unwrap();
+
+        if let Some(index) = G_PLAYING.borrow(cs).get() {
+            let patterns = G_PATTERNS.borrow(cs).borrow();
+            let steps = &patterns[index].steps;
+            let step = (G_STEP.borrow(cs).get() + 1) % steps.len();
+            G_STEP.borrow(cs).set(step);
+
+            // Even steps lit, odd steps dark.
+            let mut led = G_LED.borrow(cs).borrow_mut();
+            let led = led.as_mut().unwrap();
+            if step.is_multiple_of(2) {
+                led.set_high().ok();
+            } else {
+                led.set_low().ok();
+            }
+            timer.start((steps[step] as u32).ms());
+        }
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        timer.clear_interrupt(Event::TimeOut);
+    });
+}
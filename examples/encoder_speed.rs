@@ -0,0 +1,191 @@
+//! example: a rotary encoder setting the blink delay, and its speed.
+//!
+//! TIM2 toggles the LED on PA5 as in the main program, and a quadrature
+//! encoder on TIM4 sets the delay: A on D10 (PB6), B on PB7 (CN7 21), both
+//! pulled up, as on a KY-040 module, or the A/B outputs of a motor encoder.
+//! Each detent clockwise takes 25 ms off the delay, down to 125 ms; each
+//! detent back adds 25 ms, up to 1000 ms. TIM6 samples the encoder 100 times
+//! a second, and every tenth sample logs the position and the filtered speed.
+//! The User Button (PC13) sets the position back to zero, and the delay back
+//! to 1000 ms.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::encoder::Encoder;
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{TIM2, TIM4, TIM6};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Samples of the encoder per second.
+const SAMPLE_HZ: u32 = 100;
+// Counts per detent of the encoder, and per revolution.
+const COUNTS_PER_DETENT: i32 = 4;
+const COUNTS_PER_REV: u32 = 80;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the sampling Timer Peripheral that I'm going to pass around.
+static G_SAMPLE: Mutex<RefCell<Option<CountDownTimer<TIM6>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the encoder that I'm going to pass around.
+static G_ENCODER: Mutex<RefCell<Option<Encoder<TIM4>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the samples taken, for the log.
+static G_SAMPLES: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Sets the blink delay and restarts the timer on it, if it changed.
+fn set_delay(cs: &cortex_m::interrupt::CriticalSection, delayms: u32) {
+    if delayms == G_DELAYMS.borrow(cs).get() {
+        return;
+    }
+    G_DELAYMS.borrow(cs).set(delayms);
+    let mut timer = G_TIM.borrow(cs).borrow_mut();
+    timer.as_mut().unwrap().start(delayms.ms());
+    defmt::info!("Delay Atual: {} ms", delayms);
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) Encoder on TIM4, the speed filtered over about 8 samples.
+    let pins = (gpiob.pb6.into_alternate(), gpiob.pb7.into_alternate());
+    let encoder = Encoder::new(dp.TIM4, pins, SAMPLE_HZ, 3).unwrap();
+
+    // 2) Sampling timer.
+    let sample = Timer::new(dp.TIM6, &rcc.clocks);
+    let mut sample = sample.start_count_down(SAMPLE_HZ.hz());
+    sample.listen(Event::TimeOut);
+
+    // 3) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_SAMPLE.borrow(cs).replace(Some(sample));
+        G_ENCODER.borrow(cs).replace(Some(encoder));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM6_DACUNDER);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        let mut encoder = G_ENCODER.borrow(cs).borrow_mut();
+        encoder.as_mut().unwrap().reset();
+        set_delay(cs, 1000);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
+
+// Sampling Timer Interrupt: the encoder moves since the last sample.
+#[interrupt]
+fn TIM6_DACUNDER() {
+    cortex_m::interrupt::free(|cs| {
+        let mut encoder = G_ENCODER.borrow(cs).borrow_mut();
+        let encoder = encoder.as_mut().unwrap();
+        encoder.sample();
+
+        let detents = encoder.position() / COUNTS_PER_DETENT;
+        set_delay(cs, (1000 - detents * 25).clamp(125, 1000) as u32);
+
+        let samples = G_SAMPLES.borrow(cs).get() + 1;
+        G_SAMPLES.borrow(cs).set(samples);
+        if samples.is_multiple_of(10) {
+            defmt::info!(
+                "Position: {} counts, speed: {} counts/s ({} rpm)",
+                encoder.position(),
+                encoder.speed(),
+                encoder.rpm(COUNTS_PER_REV)
+            );
+        }
+
+        let mut sample = G_SAMPLE.borrow(cs).borrow_mut();
+        sample.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
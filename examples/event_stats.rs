@@ -0,0 +1,220 @@
+//! example: the blink's interrupts counted, and a summary every 5 s.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), with a `stats::Stats` counting what
+//! the interrupts see:
+//!
+//! | Counter     | When                                                     |
+//! |-------------|----------------------------------------------------------|
+//! | `Presses`   | a press, at least 50 ms after the last edge              |
+//! | `Bounces`   | an edge within the 50 ms, rejected                       |
+//! | `Overruns`  | a toggle served over half a delay late                   |
+//! | `Overflows` | a press dropped, the main loop's queue of 4 being full   |
+//!
+//! The main loop logs the queued presses and, every 5 s, the counts since
+//! the last summary:
+//!
+//! ```text
+//! Stats: Snapshot { presses: 3, bounces: 1, overruns: 0, overflows: 0 }
+//! ```
+//!
+//! SysTick counts the milliseconds, on `monotonic`.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::monotonic;
+use nucleo_g474re::stats::{Counter, Reporter, Stats};
+
+use cortex_m_rt::{entry, exception};
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Edges closer than this to the last one are bounces.
+const DEBOUNCE_MS: u32 = 50;
+// Presses the main loop can fall behind by.
+const QUEUE: usize = 4;
+// Period of the summary.
+const REPORT_MS: u32 = 5000;
+
+// The counters, bumped by the interrupts.
+static STATS: Stats = Stats::new();
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+// Create Global Variables for the times of the last edge and the last toggle.
+static G_LAST_EDGE: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+static G_LAST_TOGGLE: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+// Create Global Variables for the presses queued for the main loop, and how many.
+static G_QUEUE: Mutex<Cell<[u32; QUEUE]>> = Mutex::new(Cell::new([0; QUEUE]));
+static G_QUEUED: Mutex<Cell<usize>> = Mutex::new(Cell::new(0));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Milliseconds since start, modulo 2^32.
+fn now_ms() -> u32 {
+    monotonic::now().millis() as u32
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) Milliseconds on SysTick.
+    monotonic::start(&mut cp.SYST, rcc.clocks.core_clk);
+
+    // 2) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    let mut reporter = Reporter::new(REPORT_MS);
+    loop {
+        cortex_m::asm::wfi();
+
+        // The presses queued, oldest first.
+        let (queue, queued) = cortex_m::interrupt::free(|cs| {
+            (G_QUEUE.borrow(cs).get(), G_QUEUED.borrow(cs).replace(0))
+        });
+        for at in &queue[..queued] {
+            defmt::info!("Press at {} ms", at);
+        }
+
+        if let Some(counts) = reporter.poll(&STATS, now_ms()) {
+            defmt::info!("Stats: {}", counts);
+        }
+    }
+}
+
+
+#[exception]
+fn SysTick() {
+    monotonic::tick();
+}
+
+#[interrupt]
+fn EXTI15_10() {
+    let now = now_ms();
+    cortex_m::interrupt::free(|cs| {
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+
+        // An edge close to the last one is the contact bouncing.
+        let last = G_LAST_EDGE.borrow(cs).replace(now);
+        if now.wrapping_sub(last) < DEBOUNCE_MS {
+            STATS.record(Counter::Bounces);
+            return;
+        }
+        STATS.record(Counter::Presses);
+
+        let queued = G_QUEUED.borrow(cs).get();
+        if queued == QUEUE {
+            STATS.record(Counter::Overflows);
+        } else {
+            let mut queue = G_QUEUE.borrow(cs).get();
+            queue[queued] = now;
+            G_QUEUE.borrow(cs).set(queue);
+            G_QUEUED.borrow(cs).set(queued + 1);
+        }
+
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+        G_LAST_TOGGLE.borrow(cs).set(now);
+        defmt::info!("Delay Atual: {} ms", delayms);
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    let now = now_ms();
+    cortex_m::interrupt::free(|cs| {
+        // Served over half a delay after it was due: a timeout overran.
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let last = G_LAST_TOGGLE.borrow(cs).replace(now);
+        if now.wrapping_sub(last) > delayms + delayms / 2 {
+            STATS.record(Counter::Overruns);
+        }
+
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
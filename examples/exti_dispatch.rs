@@ -0,0 +1,185 @@
+//! example: three buttons on three EXTI vectors, no hand-written EXTI ISR.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), with two more buttons between a pin and
+//! GND, on the pins' pull-ups:
+//!
+//! | Button       | Line | Vector      | Action                         |
+//! |--------------|------|-------------|--------------------------------|
+//! | B1 (PC13)    | 13   | `EXTI15_10` | halves the delay (handler)     |
+//! | PA0 (A0)     | 0    | `EXTI0`     | back to 1000 ms (handler)      |
+//! | PB5 (D4)     | 5    | `EXTI9_5`   | counted by the main loop (flag) |
+//!
+//! `exti_handlers!` defines the vectors; the program only registers a
+//! handler per line, and polls the flag of the line that has none. The lines
+//! trigger on both edges, and each acts on its button's `Pressed` only.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{Input, PullUp, gpioa, gpiob};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::board::{ButtonPin, LedPin};
+use nucleo_g474re::button::{Button, Event as ButtonEvent};
+use nucleo_g474re::compat::Compat;
+use nucleo_g474re::exti;
+use nucleo_g474re::led::Led;
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+nucleo_g474re::exti_handlers!();
+
+// Aliases for the pins of the two buttons to GND
+type A0Pin = gpioa::PA0<Input<PullUp>>;
+type D4Pin = gpiob::PB5<Input<PullUp>>;
+
+// Line of the counted button.
+const COUNT_LINE: u8 = 5;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<Led<Compat<LedPin>>>>> = Mutex::new(RefCell::new(None));
+// Create Global Variables for the Buttons that I'm going to pass around.
+static G_B1: Mutex<RefCell<Option<Button<Compat<ButtonPin>>>>> = Mutex::new(RefCell::new(None));
+static G_A0: Mutex<RefCell<Option<Button<Compat<A0Pin>>>>> = Mutex::new(RefCell::new(None));
+static G_D4: Mutex<RefCell<Option<Button<Compat<D4Pin>>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Restarts the blink timer on the current delay.
+fn restart(cs: &cortex_m::interrupt::CriticalSection) {
+    let delayms = G_DELAYMS.borrow(cs).get();
+    let mut timer = G_TIM.borrow(cs).borrow_mut();
+    timer.as_mut().unwrap().start(delayms.ms());
+    defmt::info!("Delay Atual: {} ms", delayms);
+}
+
+// B1: halve the delay.
+fn halve(_line: u8) {
+    cortex_m::interrupt::free(|cs| {
+        let mut button = G_B1.borrow(cs).borrow_mut();
+        if button.as_mut().unwrap().event() != Some(ButtonEvent::Pressed) {
+            return;
+        }
+
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+        restart(cs);
+    });
+}
+
+// A0: back to the starting delay.
+fn reset(_line: u8) {
+    cortex_m::interrupt::free(|cs| {
+        let mut button = G_A0.borrow(cs).borrow_mut();
+        if button.as_mut().unwrap().event() != Some(ButtonEvent::Pressed) {
+            return;
+        }
+
+        G_DELAYMS.borrow(cs).set(1000_u32);
+        restart(cs);
+    });
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = Led::new(Compat(gpioa.pa5.into_push_pull_output()));
+
+    // 2) The buttons: B1 as on the board, the other two to GND. The dispatch
+    // clears their pending bits; the handlers only read which edge it was.
+    let mut syscfg = dp.SYSCFG.constrain();
+    let b1 = Button::new(Compat(gpioc.pc13.into_floating_input()), &mut syscfg, &mut dp.EXTI);
+    let a0 = Button::active_low(Compat(gpioa.pa0.into_pull_up_input()), &mut syscfg, &mut dp.EXTI);
+    let d4 = Button::active_low(Compat(gpiob.pb5.into_pull_up_input()), &mut syscfg, &mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_B1.borrow(cs).replace(Some(b1));
+        G_A0.borrow(cs).replace(Some(a0));
+        G_D4.borrow(cs).replace(Some(d4));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+    });
+
+    // 3) A handler per line but the counted one.
+    exti::register(13, halve).expect("line 13 taken");
+    exti::register(0, reset).expect("line 0 taken");
+    exti::unmask_all();
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    let mut count = 0_u32;
+    loop {
+        cortex_m::asm::wfi();
+        let pressed = exti::take(COUNT_LINE)
+            && cortex_m::interrupt::free(|cs| {
+                let mut button = G_D4.borrow(cs).borrow_mut();
+                button.as_mut().unwrap().event() == Some(ButtonEvent::Pressed)
+            });
+        if pressed {
+            count += 1;
+            defmt::info!("D4 pressed {} times", count);
+        }
+    }
+}
+
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
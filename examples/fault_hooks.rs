@@ -0,0 +1,182 @@
+//! example: an NMI and two faults, each taken by a hook of the program's.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5), with the
+//! handlers of the `faults` module and the User Button (PC13) setting off
+//! the next event on each press:
+//!
+//! | Press | Event                                  | Hook                           |
+//! |-------|----------------------------------------|--------------------------------|
+//! | 1     | the NMI, pended by software            | `on_nmi`: logs, resumes        |
+//! | 2     | a write to the FMC bank, its clock off | `on_bus_fault`: logs, resumes  |
+//! | 3     | a division by zero                     | the default: logs, blinks 5    |
+//!
+//! The write is buffered, so its error comes as an imprecise BusFault after
+//! the store, which the program can resume past; a precise one would fault
+//! again, and resets instead. After the third press the LED flashes five
+//! times every 2 s, the code of a UsageFault, until the reset button:
+//!
+//! ```text
+//! Nmi: non-maskable interrupt (CFSR 0x00000000, HFSR 0x00000000, PC None, LR None, address None)
+//! BusFault: imprecise data bus error (CFSR 0x00000400, HFSR 0x00000000, PC Some(134220170), ...)
+//! UsageFault: division by zero (CFSR 0x02000000, ...)
+//! ```
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::faults::{self, Action, Fault, FaultInfo};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+use cortex_m::peripheral::SCB;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+nucleo_g474re::fault_handlers!();
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// FMC bank 1, unclocked: a write there is a bus error.
+const FMC_BANK1: u32 = 0x6000_0000;
+// ICSR: pend the NMI.
+const NMIPENDSET: u32 = 1 << 31;
+// CFSR: an imprecise data bus error.
+const IMPRECISERR: u32 = 1 << 10;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the presses so far.
+static G_PRESSES: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// The NMI: nothing wrong, go on.
+fn on_nmi(info: &FaultInfo) -> Action {
+    faults::log(info);
+    Action::Resume
+}
+
+// A BusFault: past an imprecise one, the store done; a precise one would
+// come back. The PC stacked is an instruction or a few past the store.
+fn on_bus_fault(info: &FaultInfo) -> Action {
+    faults::log(info);
+    if info.cfsr & IMPRECISERR != 0 { Action::Resume } else { Action::Reset }
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) The fault handlers on, two of them hooked.
+    faults::enable(&mut cp.SCB);
+    faults::set_hook(Fault::Nmi, on_nmi);
+    faults::set_hook(Fault::BusFault, on_bus_fault);
+
+    // 2) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    let presses = cortex_m::interrupt::free(|cs| {
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+
+        let presses = G_PRESSES.borrow(cs).get() + 1;
+        G_PRESSES.borrow(cs).set(presses);
+        presses
+    });
+
+    // Outside the critical section: a fault with the interrupts masked
+    // escalates to HardFault.
+    match presses {
+        1 => unsafe { (*SCB::PTR).icsr.write(NMIPENDSET) },
+        2 => unsafe { (FMC_BANK1 as *mut u32).write_volatile(0) },
+        _ => {
+            let zero = core::hint::black_box(0_u32);
+            let quotient: u32;
+            // `/` checks for zero itself and panics: the hardware divide.
+            unsafe { core::arch::asm!("udiv {}, {}, {}", out(reg) quotient, in(reg) 1_u32, in(reg) zero) };
+            defmt::info!("1 / 0 = {}", quotient);
+        }
+    }
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,155 @@
+//! example: FMAC low-pass filter smoothing a noisy analog input.
+//!
+//! A potentiometer wiper on A0 (PA0) sets the blink period of the LED (PA5)
+//! between 100 ms and 1 s. TIM2 samples it once per millisecond and every
+//! sample goes through a 32-tap moving average running on the FMAC, so the
+//! period follows the knob without jitter even with a noisy wiper or long
+//! wires. Both the raw and the filtered value are logged twice per second;
+//! touch the wiper or the wire to inject noise and compare them.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{gpioa, Analog, Output, PushPull};
+use hal::adc::{config::SampleTime, Adc, AdcClaim, ClockSource, Disabled};
+use hal::delay::SYSTDelayExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::fmac::{Filter, Fmac};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+use hal::nb;
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for the potentiometer pin
+type PotPin = gpioa::PA0<Analog>;
+
+// Number of taps of the moving average.
+const TAPS: usize = 32;
+// Every tap weighs 1/TAPS, in Q1.15.
+const AVERAGE: [i16; TAPS] = [(32768 / TAPS) as i16; TAPS];
+// Blink period range, in milliseconds (one timer tick each).
+const MIN_DELAYMS: u32 = 100;
+const MAX_DELAYMS: u32 = 1000;
+// Ticks between two log lines.
+const LOG_TICKS: u32 = 500;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the potentiometer pin that I'm going to pass around.
+static G_POT: Mutex<RefCell<Option<PotPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the ADC that I'm going to pass around.
+static G_ADC: Mutex<RefCell<Option<Adc<stm32::ADC1, Disabled>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the FMAC that I'm going to pass around.
+static G_FMAC: Mutex<RefCell<Option<Fmac>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the milliseconds elapsed since start.
+static G_TICKS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+// Create a Global Variable for the tick of the last LED toggle.
+static G_TOGGLED: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+
+    let led = gpioa.pa5.into_push_pull_output();
+    // The potentiometer pin must be in analog mode to be sampled.
+    let pot = gpioa.pa0.into_analog();
+
+    // 1) ADC1 for one-shot conversions of the potentiometer.
+    let mut delay = cp.SYST.delay(&rcc.clocks);
+    let adc = dp.ADC1.claim(ClockSource::SystemClock, &rcc, &mut delay, true);
+
+    // 2) Moving average on the FMAC. The taps add up to 1, so no output gain.
+    let mut fmac = Fmac::new(dp.FMAC);
+    fmac.start(Filter::Fir { b: &AVERAGE }, 0);
+
+    // 3) One sample per millisecond.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_POT.borrow(cs).replace(Some(pot));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_ADC.borrow(cs).replace(Some(adc));
+        G_FMAC.borrow(cs).replace(Some(fmac));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut adc = G_ADC.borrow(cs).borrow_mut();
+        let pot = G_POT.borrow(cs).borrow();
+        let raw = adc.as_mut().unwrap().convert(pot.as_ref().unwrap(), SampleTime::Cycles_24_5);
+
+        // 12-bit code to positive Q1.15 and back: the filter only takes a few
+        // tens of cycles, so waiting for its output here is fine.
+        let mut fmac = G_FMAC.borrow(cs).borrow_mut();
+        let fmac = fmac.as_mut().unwrap();
+        nb::block!(fmac.write((raw << 3) as i16)).ok();
+        let filtered = nb::block!(fmac.read()).unwrap_or(0).max(0) as u32 >> 3;
+
+        let ticks = G_TICKS.borrow(cs).get().wrapping_add(1);
+        G_TICKS.borrow(cs).set(ticks);
+
+        let delayms = MIN_DELAYMS + filtered * (MAX_DELAYMS - MIN_DELAYMS) / 4095;
+        if ticks.wrapping_sub(G_TOGGLED.borrow(cs).get()) >= delayms {
+            G_TOGGLED.borrow(cs).set(ticks);
+            let mut led = G_LED.borrow(cs).borrow_mut();
+            led.as_mut().unwrap().toggle().ok();
+        }
+
+        if ticks.is_multiple_of(LOG_TICKS) {
+            defmt::info!("raw {}, filtered {}, period {} ms", raw, filtered, delayms);
+        }
+
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,141 @@
+//! example: a frequency meter measuring the MCO output.
+//!
+//! TIM2 counts the pulses on A0 (PA0) over one-second gates timed by TIM7,
+//! and every gate logs the frequency measured. For a signal to measure,
+//! MCO puts the HSI on D7 (PA8): wire D7 to A0. Each press of the User
+//! Button (PC13) steps the MCO divider, 1 MHz, 4 MHz then the full 16 MHz,
+//! the meter's prescaler of 8 keeping the count within reach. The LED on PA5
+//! toggles with every gate.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::freqmeter::{FreqMeter, Prescaler};
+use nucleo_g474re::mco::{Divider, Mco, Source};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::rcc::Clocks;
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// MCO dividers stepped through by the button.
+const DIVIDERS: [Divider; 3] = [Divider::Div16, Divider::Div4, Divider::Div1];
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the frequency meter that I'm going to pass around.
+static G_METER: Mutex<RefCell<Option<FreqMeter>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the clock output that I'm going to pass around.
+static G_MCO: Mutex<RefCell<Option<Mco>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the clock configuration, for the expected frequency.
+static G_CLOCKS: Mutex<RefCell<Option<Clocks>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the index into DIVIDERS.
+static G_STEP: Mutex<Cell<usize>> = Mutex::new(Cell::new(0));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) The signal: the HSI on MCO, divided by 16.
+    let mut mco = Mco::new(gpioa.pa8.into_alternate());
+    mco.set(Source::Hsi, DIVIDERS[0]).unwrap();
+
+    // 2) The meter: pulses on PA0 divided by 8, over one-second gates.
+    let mut meter = FreqMeter::new(dp.TIM2, gpioa.pa0.into_alternate(), dp.TIM7, 1000, Prescaler::Div8, &rcc.clocks).unwrap();
+    meter.listen();
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_METER.borrow(cs).replace(Some(meter));
+        G_MCO.borrow(cs).replace(Some(mco));
+        G_CLOCKS.borrow(cs).replace(Some(rcc.clocks));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM7);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        let step = (G_STEP.borrow(cs).get() + 1) % DIVIDERS.len();
+        G_STEP.borrow(cs).set(step);
+
+        let mut mco = G_MCO.borrow(cs).borrow_mut();
+        mco.as_mut().unwrap().set(Source::Hsi, DIVIDERS[step]).unwrap();
+        defmt::info!("HSI / {} on MCO", DIVIDERS[step].divisor());
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Gate Timer Interrupt: the frequency over the gate that just ended.
+#[interrupt]
+fn TIM7() {
+    cortex_m::interrupt::free(|cs| {
+        let mut meter = G_METER.borrow(cs).borrow_mut();
+        if let Some(frequency) = meter.as_mut().unwrap().handle_interrupt() {
+            let clocks = G_CLOCKS.borrow(cs).borrow();
+            let expected = G_MCO.borrow(cs).borrow().as_ref().unwrap().frequency(clocks.as_ref().unwrap());
+            defmt::info!("Frequency: {} Hz (expected {} Hz)", frequency.0, expected.map(|hz| hz.0));
+        }
+
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+    });
+}
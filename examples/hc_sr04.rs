@@ -0,0 +1,189 @@
+//! example: distance from an HC-SR04 setting the blink delay.
+//!
+//! TIM2 toggles the LED on PA5 as in the main program, but the delay comes
+//! from an HC-SR04 ultrasonic sensor: TRIG on D7 (PA8), ECHO on D5 (PB4)
+//! through a 1k / 2k divider, VCC on 5V. TIM6 triggers a measurement ten
+//! times a second and TIM3 times the echo; the delay is the distance in
+//! tenths of a metre, 100 ms at 10 cm or less up to 1000 ms at 1 m or more,
+//! so the LED blinks faster as something comes closer. The User Button
+//! (PC13) freezes the delay, and a second press unfreezes it.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::compat::Compat;
+use nucleo_g474re::hcsr04::{self, HcSr04};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{TIM2, TIM6};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the sensor, TRIG on PA8
+type Sensor = HcSr04<Compat<gpioa::PA8<Output<PushPull>>>>;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the measurement Timer Peripheral that I'm going to pass around.
+static G_MEASURE: Mutex<RefCell<Option<CountDownTimer<TIM6>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the sensor that I'm going to pass around.
+static G_SENSOR: Mutex<RefCell<Option<Sensor>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the frozen state of the delay.
+static G_FROZEN: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) Sensor: TRIG on PA8, ECHO timed by TIM3 on PB4.
+    let trig = Compat(gpioa.pa8.into_push_pull_output());
+    let mut sensor = HcSr04::new(trig, dp.TIM3, gpiob.pb4.into_alternate(), &rcc.clocks);
+    sensor.listen();
+
+    // 2) Measurement timer: one trigger every 100 ms.
+    let measure = Timer::new(dp.TIM6, &rcc.clocks);
+    let mut measure = measure.start_count_down(100.ms());
+    measure.listen(Event::TimeOut);
+
+    // 3) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_MEASURE.borrow(cs).replace(Some(measure));
+        G_SENSOR.borrow(cs).replace(Some(sensor));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM3);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM6_DACUNDER);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        let frozen = G_FROZEN.borrow(cs);
+        frozen.set(!frozen.get());
+        defmt::info!("Delay {}", if frozen.get() { "frozen" } else { "following the distance" });
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
+
+// Capture Interrupt: the end of an echo.
+#[interrupt]
+fn TIM3() {
+    cortex_m::interrupt::free(|cs| {
+        let mut sensor = G_SENSOR.borrow(cs).borrow_mut();
+        let Some(echo_us) = sensor.as_mut().unwrap().handle_interrupt() else {
+            return;
+        };
+        let Some(distance) = hcsr04::distance_mm(echo_us) else {
+            defmt::debug!("No echo in range");
+            return;
+        };
+        defmt::debug!("Distance: {} mm", distance);
+
+        // Obtain Access to Delay Global Data and Adjust Delay: restarting
+        // the blink timer only when the delay changes, or it never ends.
+        let delayms = (distance / 100).clamp(1, 10) * 100;
+        if G_FROZEN.borrow(cs).get() || delayms == G_DELAYMS.borrow(cs).get() {
+            return;
+        }
+        G_DELAYMS.borrow(cs).set(delayms);
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+        defmt::info!("Delay Atual: {} ms", delayms);
+    });
+}
+
+// Measurement Timer Interrupt: the next trigger pulse.
+#[interrupt]
+fn TIM6_DACUNDER() {
+    cortex_m::interrupt::free(|cs| {
+        let mut sensor = G_SENSOR.borrow(cs).borrow_mut();
+        sensor.as_mut().unwrap().trigger();
+
+        let mut measure = G_MEASURE.borrow(cs).borrow_mut();
+        measure.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
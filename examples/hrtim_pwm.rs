@@ -0,0 +1,198 @@
+//! example: HRTIM PWM breathing along with the blink.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), with HRTIM1 timer A driving a PWM output
+//! on PA8 (D7). An LED with a resistor on PA8 brightens while the board LED is
+//! on and dims while it is off: the HRTIM master timer interrupts every
+//! millisecond and steps the duty along that ramp.
+//!
+//! The PWM frequency follows the delay, 20 kHz at 1000 ms and doubling with
+//! every halving, to 160 kHz at 125 ms: watch PA8 on a scope.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, Speed, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+use hal::time::Hertz;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::hrtim::Pwm;
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Rate of the master timer interrupt, one duty step per millisecond.
+const STEP_RATE: Hertz = Hertz(1000);
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the HRTIM PWM that I'm going to pass around.
+static G_PWM: Mutex<RefCell<Option<Pwm>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+// Create a Global Variable for the milliseconds since the LED turned on.
+static G_PHASE: Mutex<Cell<u32>> = Mutex::new(Cell::new(0_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// PWM frequency for a blink delay: one octave per halving.
+fn frequency(delayms: u32) -> Hertz {
+    Hertz(20_000 * 1000 / delayms)
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) HRTIM1 timer A on PA8, and the master timer interrupt.
+    let pin = gpioa.pa8.into_alternate().set_speed(Speed::VeryHigh);
+    let mut pwm = Pwm::new(
+        dp.HRTIM_MASTER,
+        dp.HRTIM_TIMA,
+        dp.HRTIM_COMMON,
+        pin,
+        rcc.clocks.apb2_tim_clk,
+        frequency(1000),
+    );
+    pwm.enable();
+    pwm.listen(STEP_RATE);
+    defmt::info!("HRTIM at {} Hz, {} ps per step", pwm.frequency().0, pwm.tick_picos());
+
+    // 2) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_PWM.borrow(cs).replace(Some(pwm));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::HRTIM_MASTER_IRQN);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// HRTIM master timer interrupt: one step of the ramp.
+#[interrupt]
+fn HRTIM_MASTER_IRQN() {
+    cortex_m::interrupt::free(|cs| {
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let phase = (G_PHASE.borrow(cs).get() + 1).min(2 * delayms);
+        G_PHASE.borrow(cs).set(phase);
+
+        let mut pwm = G_PWM.borrow(cs).borrow_mut();
+        let pwm = pwm.as_mut().unwrap();
+
+        // Up while the LED is on, down while it is off.
+        let ramp = if phase < delayms { phase } else { 2 * delayms - phase };
+        let duty = pwm.max_duty() as u32 * ramp / delayms;
+        pwm.set_duty(duty as u16);
+
+        pwm.clear_interrupt();
+    });
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+
+        // Retune the PWM; the ramp restarts with the next LED on.
+        let mut pwm = G_PWM.borrow(cs).borrow_mut();
+        let pwm = pwm.as_mut().unwrap();
+        pwm.set_frequency(frequency(delayms));
+        defmt::info!("{} ms, PWM at {} Hz", delayms, pwm.frequency().0);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        let led = led.as_mut().unwrap();
+        led.toggle().ok();
+
+        // Keep the ramp in step with the LED.
+        if led.is_set_high().unwrap() {
+            G_PHASE.borrow(cs).set(0);
+        }
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
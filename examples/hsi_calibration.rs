@@ -0,0 +1,167 @@
+//! example: the HSI trimmed against the LSE crystal, then the blink.
+//!
+//! The clocks come from the HSI through the PLL, at 170 MHz, with the LSE
+//! started for the measurement. `calib::Calib` captures the LSE on TIM16,
+//! measures the HSI and steps its trim to the code nearest 16 MHz, logging
+//! both ends:
+//!
+//! ```text
+//! HSI before: trim 64, 16021480 Hz, 1342 ppm
+//! HSI after: trim 63, 15998712 Hz, -80 ppm
+//! ```
+//!
+//! Then the blink of the main program (TIM2 toggles the LED on PA5, the
+//! User Button on PC13 halves the delay), every press measuring the HSI
+//! again: a finger on the chip moves it.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::calib::{self, Calib};
+use nucleo_g474re::clocks::{self, ClockConfig, LowSpeedSource};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+// Create a Global Variable for the calibration timer, measuring on each press.
+static G_CALIB: Mutex<RefCell<Option<Calib>>> = Mutex::new(RefCell::new(None));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+
+    // 1) The PLL from the HSI, and the LSE to measure it against.
+    let (mut rcc, sources) = clocks::freeze(dp.RCC.constrain(), ClockConfig::hsi().lse());
+    if sources.low_speed != Some(LowSpeedSource::Lse) {
+        panic!("no LSE to calibrate against");
+    }
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 2) The trim, before and after.
+    let mut calib = Calib::new(dp.TIM16, &rcc.clocks).expect("cannot capture the LSE");
+    let calibration = calib.trim().expect("LSE stopped");
+    defmt::info!(
+        "HSI before: trim {}, {} Hz, {} ppm",
+        calibration.trim_before,
+        calibration.frequency_before,
+        calibration.error_before_ppm()
+    );
+    defmt::info!("HSI after: trim {}, {} Hz, {} ppm", calibration.trim, calibration.frequency, calibration.error_ppm());
+
+    // 3) Blink timer, as in the main program, on the trimmed clock.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_CALIB.borrow(cs).replace(Some(calib));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+        defmt::info!("Delay Atual: {} ms", delayms);
+
+        // The HSI now, 62.5 ms of measurement.
+        let mut calib = G_CALIB.borrow(cs).borrow_mut();
+        if let Ok(frequency) = calib.as_mut().unwrap().measure() {
+            defmt::info!("HSI: trim {}, {} Hz, {} ppm", calib::hsitrim(), frequency, calib::error_ppm(frequency));
+        }
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
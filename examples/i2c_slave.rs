@@ -0,0 +1,261 @@
+//! example: interrupt blink with its state on I2C, as a slave device.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), readable and writable by an I2C
+//! controller at address 0x42 on D14 (PB9, SDA) and D15 (PB8, SCL):
+//!
+//! | Register  | Content                                   | Access     |
+//! |-----------|-------------------------------------------|------------|
+//! | 0x00-0x01 | blink delay in ms, little endian, 1-1000  | read/write |
+//! | 0x02      | LED: 0 held off, 1 held on, 2 blinking    | read/write |
+//! | 0x03-0x06 | button presses since boot, little endian  | read only  |
+//!
+//! Writes take effect at the end of the transfer, so both bytes of the delay
+//! change together. From a Raspberry Pi (GND connected, its pull-ups serve
+//! the bus): `i2cset -y 1 0x42 0x00 0x01f4 w` sets 500 ms,
+//! `i2cset -y 1 0x42 0x02 0x01` holds the LED on and
+//! `i2cdump -y -r 0-6 1 0x42 b` shows every register.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::i2c::{I2cSlave, Registers, SlaveEvent};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::RefCell;
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Address of the board on the bus.
+const ADDRESS: u8 = 0x42;
+
+// Values of the LED register.
+const LED_OFF: u8 = 0;
+const LED_ON: u8 = 1;
+const LED_BLINK: u8 = 2;
+
+// State of the demo, as seen from the bus.
+struct Map {
+    delayms: u16,
+    led: u8,
+    presses: u32,
+    // Delay bytes written by the controller, applied at the stop condition.
+    staged_delay: [u8; 2],
+    delay_written: bool,
+    led_written: bool,
+}
+
+impl Map {
+    const fn new() -> Self {
+        Map { delayms: 1000, led: LED_BLINK, presses: 0, staged_delay: [0; 2], delay_written: false, led_written: false }
+    }
+}
+
+impl Registers for Map {
+    fn read(&mut self, register: u8) -> u8 {
+        let delay = self.delayms.to_le_bytes();
+        let presses = self.presses.to_le_bytes();
+        match register {
+            0x00..=0x01 => delay[register as usize],
+            0x02 => self.led,
+            0x03..=0x06 => presses[register as usize - 0x03],
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, register: u8, value: u8) {
+        match register {
+            0x00..=0x01 => {
+                // A single byte written keeps the other one.
+                if !self.delay_written {
+                    self.staged_delay = self.delayms.to_le_bytes();
+                }
+                self.staged_delay[register as usize] = value;
+                self.delay_written = true;
+            }
+            0x02 if value <= LED_BLINK => {
+                self.led = value;
+                self.led_written = true;
+            }
+            // Out of range values and read only registers are ignored.
+            _ => {}
+        }
+    }
+}
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the I2C slave that I'm going to pass around.
+static G_I2C: Mutex<RefCell<Option<I2cSlave>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the register map shared with the bus.
+static G_MAP: Mutex<RefCell<Map>> = Mutex::new(RefCell::new(Map::new()));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) I2C1 as a slave on the Arduino header, clocked by PCLK1.
+    let sda = gpiob.pb9.into_alternate_open_drain();
+    let scl = gpiob.pb8.into_alternate_open_drain();
+    let mut slave = I2cSlave::new(dp.I2C1, sda, scl, ADDRESS, rcc.clocks.apb1_clk);
+    slave.listen();
+
+    // 2) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_I2C.borrow(cs).replace(Some(slave));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::I2C1_EV);
+        cortex_m::peripheral::NVIC::unmask(interrupt::I2C1_ER);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// Serves the bus, then applies what the controller wrote once the transfer is over.
+fn serve_i2c(cs: &cortex_m::interrupt::CriticalSection) {
+    let mut slave = G_I2C.borrow(cs).borrow_mut();
+    let slave = slave.as_mut().unwrap();
+    let mut map = G_MAP.borrow(cs).borrow_mut();
+
+    while let Some(event) = slave.handle(&mut *map) {
+        match event {
+            SlaveEvent::Stop => {
+                if map.delay_written {
+                    map.delay_written = false;
+                    // 1000 ms is the longest delay the timer takes with the default clock.
+                    map.delayms = u16::from_le_bytes(map.staged_delay).clamp(1, 1000);
+                    defmt::info!("I2C: delay set to {} ms", map.delayms);
+                    let mut timer = G_TIM.borrow(cs).borrow_mut();
+                    timer.as_mut().unwrap().start((map.delayms as u32).ms());
+                }
+                if map.led_written {
+                    map.led_written = false;
+                    defmt::info!("I2C: LED state {}", map.led);
+                    let mut led = G_LED.borrow(cs).borrow_mut();
+                    let led = led.as_mut().unwrap();
+                    match map.led {
+                        LED_OFF => led.set_low().ok(),
+                        LED_ON => led.set_high().ok(),
+                        _ => None,
+                    };
+                }
+            }
+            SlaveEvent::Error(error) => defmt::warn!("I2C error: {}", error),
+            _ => {}
+        }
+    }
+}
+
+#[interrupt]
+fn I2C1_EV() {
+    cortex_m::interrupt::free(serve_i2c);
+}
+
+#[interrupt]
+fn I2C1_ER() {
+    cortex_m::interrupt::free(serve_i2c);
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        let mut map = G_MAP.borrow(cs).borrow_mut();
+
+        // Obtain Access to Delay Global Data and Adjust Delay
+        map.delayms /= 2;
+
+        if map.delayms < 125_u16 {
+            map.delayms = 1000_u16;
+        }
+
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start((map.delayms as u32).ms());
+
+        map.presses = map.presses.wrapping_add(1);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        // The controller may hold the LED on or off: only blink when allowed.
+        if G_MAP.borrow(cs).borrow().led == LED_BLINK {
+            let mut led = G_LED.borrow(cs).borrow_mut();
+            led.as_mut().unwrap().toggle().ok();
+        }
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,168 @@
+//! example: TMP102 temperature sensor on I2C1 modulating the blink speed.
+//!
+//! A TMP102 breakout is connected to the Arduino I2C pins: SDA on D14 (PB9),
+//! SCL on D15 (PB8), plus 3.3 V and GND. On every TIM2 timeout the LED (PA5)
+//! toggles and the temperature is read and logged. While modulation is on,
+//! the temperature also sets the blink delay: 1000 ms at COLD_MC and below,
+//! 125 ms at HOT_MC and above, so a finger on the sensor speeds the LED up.
+//! The User Button (PC13) switches the modulation on and off.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::compat::Compat;
+use nucleo_g474re::i2c::{i2c1, I2c1, Tmp102};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Temperatures giving the slowest and the fastest blink, in thousandths of °C.
+const COLD_MC: i32 = 20_000;
+const HOT_MC: i32 = 35_000;
+// Blink delays at COLD_MC and HOT_MC.
+const SLOW_DELAYMS: u32 = 1000;
+const FAST_DELAYMS: u32 = 125;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the temperature sensor that I'm going to pass around.
+static G_SENSOR: Mutex<RefCell<Option<Tmp102<Compat<I2c1>>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable telling whether the temperature sets the delay.
+static G_MODULATE: Mutex<Cell<bool>> = Mutex::new(Cell::new(true));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Maps a temperature to a blink delay, linearly between COLD_MC and HOT_MC.
+fn delay_for(millicelsius: i32) -> u32 {
+    let t = millicelsius.clamp(COLD_MC, HOT_MC) - COLD_MC;
+    SLOW_DELAYMS - (t as u32 * (SLOW_DELAYMS - FAST_DELAYMS)) / (HOT_MC - COLD_MC) as u32
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // 1) I2C1 on the Arduino header and the sensor at its default address.
+    let sda = gpiob.pb9.into_alternate_open_drain();
+    let scl = gpiob.pb8.into_alternate_open_drain();
+    let sensor = Tmp102::new(Compat(i2c1(dp.I2C1, sda, scl, &mut rcc)), Tmp102::<Compat<I2c1>>::DEFAULT_ADDRESS);
+
+    // 2) Blink timer, also pacing the readings.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(SLOW_DELAYMS.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_SENSOR.borrow(cs).replace(Some(sensor));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        let modulate = !G_MODULATE.borrow(cs).get();
+        G_MODULATE.borrow(cs).set(modulate);
+        defmt::info!("Temperature modulation: {}", modulate);
+
+        // Back to the fixed delay right away; the next reading applies the modulated one.
+        if !modulate {
+            let mut timer = G_TIM.borrow(cs).borrow_mut();
+            timer.as_mut().unwrap().start(SLOW_DELAYMS.ms());
+        }
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        let timer = timer.as_mut().unwrap();
+
+        let mut sensor = G_SENSOR.borrow(cs).borrow_mut();
+        match sensor.as_mut().unwrap().read_millicelsius() {
+            Ok(millicelsius) => {
+                defmt::info!("Temperature: {}.{=i32:03} °C", millicelsius / 1000, (millicelsius % 1000).abs());
+                if G_MODULATE.borrow(cs).get() {
+                    timer.start(delay_for(millicelsius).ms());
+                }
+            }
+            // Keep blinking at the current delay when the sensor does not answer.
+            Err(error) => defmt::warn!("TMP102 read failed: {}", defmt::Debug2Format(&error)),
+        }
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        timer.clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,184 @@
+//! example: an infrared remote setting the blink delay.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), with a TSOP38238 receiver: OUT on D10
+//! (PB6), VS on 3V3, GND on GND. TIM4 captures its falling edges and decodes
+//! NEC frames; every press is logged with its address and command.
+//!
+//! The keys below are those of the small 21-key remote common in Arduino
+//! kits: digits set the delay in hundreds of milliseconds (`0` for
+//! 1000 ms), `+` halves it as the button does, `-` goes back to 1000 ms.
+//! For another NEC remote, read its codes from the log and edit `DIGITS`,
+//! `PLUS` and `MINUS`.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::ir::{Event, Receiver};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event as TimerEvent, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Commands of the keys 0 to 9.
+const DIGITS: [u8; 10] = [0x16, 0x0C, 0x18, 0x5E, 0x08, 0x1C, 0x5A, 0x42, 0x52, 0x4A];
+// Command of the key halving the delay.
+const PLUS: u8 = 0x15;
+// Command of the key going back to 1000 ms.
+const MINUS: u8 = 0x07;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the IR receiver that I'm going to pass around.
+static G_IR: Mutex<RefCell<Option<Receiver>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Sets the blink delay and restarts the timer on it.
+fn set_delay(cs: &cortex_m::interrupt::CriticalSection, delayms: u32) {
+    G_DELAYMS.borrow(cs).set(delayms);
+    let mut timer = G_TIM.borrow(cs).borrow_mut();
+    timer.as_mut().unwrap().start(delayms.ms());
+    defmt::info!("Delay Atual: {} ms", delayms);
+}
+
+// The delay after the button or `+`.
+fn halved(delayms: u32) -> u32 {
+    if delayms / 2 < 125 { 1000 } else { delayms / 2 }
+}
+
+// Acts on a remote key.
+fn on_command(cs: &cortex_m::interrupt::CriticalSection, command: u8) {
+    if let Some(digit) = DIGITS.iter().position(|&key| key == command) {
+        set_delay(cs, if digit == 0 { 1000 } else { digit as u32 * 100 });
+    } else if command == PLUS {
+        set_delay(cs, halved(G_DELAYMS.borrow(cs).get()));
+    } else if command == MINUS {
+        set_delay(cs, 1000);
+    }
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) IR receiver: TIM4 capturing the falling edges of PB6.
+    let mut ir = Receiver::new(dp.TIM4, gpiob.pb6.into_alternate(), &rcc.clocks);
+    ir.listen();
+
+    // 2) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(TimerEvent::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_IR.borrow(cs).replace(Some(ir));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM4);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        set_delay(cs, halved(G_DELAYMS.borrow(cs).get()));
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(TimerEvent::TimeOut);
+    });
+}
+
+// Capture Interrupt: one falling edge of the receiver, or a timer overflow.
+#[interrupt]
+fn TIM4() {
+    cortex_m::interrupt::free(|cs| {
+        let event = G_IR.borrow(cs).borrow_mut().as_mut().unwrap().handle_interrupt();
+        match event {
+            Some(Event::Pressed(frame)) => {
+                defmt::info!("IR address {=u16:#x} command {=u8:#x}", frame.address, frame.command);
+                on_command(cs, frame.command);
+            }
+            Some(Event::Repeat(frame)) => defmt::debug!("IR command {=u8:#x} held", frame.command),
+            None => {}
+        }
+    });
+}
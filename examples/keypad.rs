@@ -0,0 +1,215 @@
+//! example: a 4x4 membrane keypad setting the blink delay.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), with a keypad scanned by SysTick every
+//! millisecond:
+//!
+//! | Keypad pin      | Board pin                          |
+//! |-----------------|------------------------------------|
+//! | rows 1 to 4     | PC0 to PC3 (CN7 38, 36, 35, 37)    |
+//! | columns 1 to 4  | PB12 to PB15 (CN10 16, 30, 28, 26) |
+//!
+//! Digits set the delay in hundreds of milliseconds (`0` for 1000 ms), `*`
+//! halves it as the button does, `#` goes back to 1000 ms; every key down
+//! and up is logged.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, OpenDrain, Output, PullUp, PushPull, SignalEdge, gpioa, gpiob, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::compat::Compat;
+use nucleo_g474re::keypad::{Event, Keypad, LAYOUT_4X4};
+
+use cortex_m_rt::{entry, exception};
+
+use cortex_m::peripheral::syst::SystClkSource;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event as TimerEvent, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the keypad: rows on port C, columns on port B
+type Keys = Keypad<Compat<gpioc::PC<Output<OpenDrain>>>, Compat<gpiob::PB<Input<PullUp>>>, 4, 4>;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the keypad that I'm going to pass around.
+static G_KEYPAD: Mutex<RefCell<Option<Keys>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Sets the blink delay and restarts the timer on it.
+fn set_delay(cs: &cortex_m::interrupt::CriticalSection, delayms: u32) {
+    G_DELAYMS.borrow(cs).set(delayms);
+    let mut timer = G_TIM.borrow(cs).borrow_mut();
+    timer.as_mut().unwrap().start(delayms.ms());
+    defmt::info!("Delay Atual: {} ms", delayms);
+}
+
+// The delay after the button or `*`.
+fn halved(delayms: u32) -> u32 {
+    if delayms / 2 < 125 { 1000 } else { delayms / 2 }
+}
+
+// Acts on a key.
+fn on_key(cs: &cortex_m::interrupt::CriticalSection, label: u8) {
+    match label {
+        b'0' => set_delay(cs, 1000),
+        b'1'..=b'9' => set_delay(cs, (label - b'0') as u32 * 100),
+        b'*' => set_delay(cs, halved(G_DELAYMS.borrow(cs).get())),
+        b'#' => set_delay(cs, 1000),
+        _ => {}
+    }
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) Keypad: open-drain rows, pulled-up columns.
+    let rows = [
+        Compat(gpioc.pc0.into_open_drain_output().downgrade()),
+        Compat(gpioc.pc1.into_open_drain_output().downgrade()),
+        Compat(gpioc.pc2.into_open_drain_output().downgrade()),
+        Compat(gpioc.pc3.into_open_drain_output().downgrade()),
+    ];
+    let cols = [
+        Compat(gpiob.pb12.into_pull_up_input().downgrade()),
+        Compat(gpiob.pb13.into_pull_up_input().downgrade()),
+        Compat(gpiob.pb14.into_pull_up_input().downgrade()),
+        Compat(gpiob.pb15.into_pull_up_input().downgrade()),
+    ];
+    let keypad = Keypad::new(rows, cols);
+
+    // 2) SysTick interrupt every millisecond for the scan.
+    let mut syst = cp.SYST;
+    syst.set_clock_source(SystClkSource::Core);
+    syst.set_reload(rcc.clocks.sys_clk.0 / 1000 - 1);
+    syst.clear_current();
+    syst.enable_counter();
+    syst.enable_interrupt();
+
+    // 3) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(TimerEvent::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_KEYPAD.borrow(cs).replace(Some(keypad));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+        // 4) The keys queued by the scans since the last wakeup.
+        cortex_m::interrupt::free(|cs| {
+            let mut keypad = G_KEYPAD.borrow(cs).borrow_mut();
+            while let Some(event) = keypad.as_mut().unwrap().next_event() {
+                let key = event.key();
+                let label = LAYOUT_4X4[key.row as usize][key.col as usize];
+                match event {
+                    Event::Down(_) => {
+                        defmt::info!("Key {=u8:a} down", label);
+                        on_key(cs, label);
+                    }
+                    Event::Up(_) => defmt::info!("Key {=u8:a} up", label),
+                }
+            }
+        });
+    }
+}
+
+
+// SysTick: one keypad scan.
+#[exception]
+fn SysTick() {
+    cortex_m::interrupt::free(|cs| {
+        let mut keypad = G_KEYPAD.borrow(cs).borrow_mut();
+        if let Some(keypad) = keypad.as_mut() {
+            keypad.scan();
+        }
+    });
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        set_delay(cs, halved(G_DELAYMS.borrow(cs).get()));
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(TimerEvent::TimeOut);
+    });
+}
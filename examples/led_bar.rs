@@ -0,0 +1,203 @@
+//! example: an 8-LED bar showing the blink delay.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), with eight LEDs on PC0 to PC7, each
+//! through a resistor (330 ohm or so) to GND. SysTick ticks their patterns
+//! every millisecond, and the bar shows the delay in one of two ways,
+//! switching each time a press wraps it back to 1000 ms:
+//!
+//! - bar graph: 8 LEDs for 1000 ms, 4 for 500, 2 for 250, 1 for 125, the
+//!   LED at the top of the bar blinking at 5 Hz;
+//! - binary: the delay in tens of milliseconds (100, 50, 25, 12).
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::compat::Compat;
+use nucleo_g474re::leds::{Leds, Pattern};
+
+use cortex_m_rt::{entry, exception};
+
+use cortex_m::peripheral::syst::SystClkSource;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the LED bar on port C
+type Bar = Leds<Compat<gpioc::PC<Output<PushPull>>>, 8>;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the LED bar that I'm going to pass around.
+static G_BAR: Mutex<RefCell<Option<Bar>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the display mode: binary instead of bar graph.
+static G_BINARY: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Draws the delay on the bar in the current mode.
+fn show(bar: &mut Bar, delayms: u32, binary: bool) {
+    if binary {
+        bar.show_binary(delayms / 10);
+        return;
+    }
+    bar.show_bar(delayms, 1000);
+    // The top of the bar blinks.
+    let top = (0..bar.len()).rev().find(|&index| bar.pattern(index) == Some(Pattern::On));
+    if let Some(top) = top {
+        bar.set(top, Pattern::blink(100, 100)).ok();
+    }
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) The bar, showing the starting delay.
+    let mut bar = Leds::new([
+        Compat(gpioc.pc0.into_push_pull_output().downgrade()),
+        Compat(gpioc.pc1.into_push_pull_output().downgrade()),
+        Compat(gpioc.pc2.into_push_pull_output().downgrade()),
+        Compat(gpioc.pc3.into_push_pull_output().downgrade()),
+        Compat(gpioc.pc4.into_push_pull_output().downgrade()),
+        Compat(gpioc.pc5.into_push_pull_output().downgrade()),
+        Compat(gpioc.pc6.into_push_pull_output().downgrade()),
+        Compat(gpioc.pc7.into_push_pull_output().downgrade()),
+    ]);
+    show(&mut bar, 1000, false);
+
+    // 2) SysTick interrupt every millisecond for the patterns.
+    let mut syst = cp.SYST;
+    syst.set_clock_source(SystClkSource::Core);
+    syst.set_reload(rcc.clocks.sys_clk.0 / 1000 - 1);
+    syst.clear_current();
+    syst.enable_counter();
+    syst.enable_interrupt();
+
+    // 3) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_BAR.borrow(cs).replace(Some(bar));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// SysTick: one tick of the bar patterns.
+#[exception]
+fn SysTick() {
+    cortex_m::interrupt::free(|cs| {
+        let mut bar = G_BAR.borrow(cs).borrow_mut();
+        if let Some(bar) = bar.as_mut() {
+            bar.tick();
+        }
+    });
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+            // Each wrap switches the display mode.
+            let binary = G_BINARY.borrow(cs);
+            binary.set(!binary.get());
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+        defmt::info!("Delay Atual: {} ms", delayms);
+
+        let mut bar = G_BAR.borrow(cs).borrow_mut();
+        show(bar.as_mut().unwrap(), delayms, G_BINARY.borrow(cs).get());
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,176 @@
+//! example: blinking from low-power run mode at 2 MHz.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), on a power profile for a device that
+//! mostly waits: HCLK at 2 MHz (the HSI with the AHB divided by 8), the main
+//! regulator in range 2, and the core on the low-power regulator between
+//! blinks. Each TIM2 interrupt hands the core back to the main regulator for
+//! its work, then returns to low-power run before sleeping again.
+//!
+//! At start and on every press the log compares the estimated current of
+//! this profile with the main program's 170 MHz in range 1 boost. The
+//! figures come from `power::estimated_current_ua`, rough by design: the
+//! IDD jumper (JP5) and an ammeter give the real ones.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::rcc::Clocks;
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::clocks::{self, Sysclk};
+use nucleo_g474re::power::{self, PowerState, VoltageRange};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the clock frequencies of the profile.
+static G_CLOCKS: Mutex<Cell<Option<Clocks>>> = Mutex::new(Cell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Logs the estimated currents of the main program and of this profile.
+fn report(clocks: &Clocks) {
+    let full_speed = power::estimated_current_ua(PowerState::Run(VoltageRange::Range1Boost), clocks::SYSCLK_170MHZ);
+    let awake = power::estimated_current_ua(PowerState::Run(VoltageRange::Range2), clocks.ahb_clk);
+    let idle = power::estimated_current_ua(PowerState::LowPowerRun, clocks.ahb_clk);
+    defmt::info!("Estimated: 170 MHz range 1 boost ~{} uA", full_speed);
+    defmt::info!("Estimated: {} Hz range 2 ~{} uA, low-power run ~{} uA", clocks.ahb_clk.0, awake, idle);
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) HCLK down to 2 MHz, then the regulator down to range 2.
+    let clocks = clocks::set_sysclk(Sysclk::Hsi2);
+    power::set_voltage_range(VoltageRange::Range2, &clocks).expect("clock too fast for range 2");
+    report(&clocks);
+
+    // 2) Blink timer from the 2 MHz clocks.
+    let timer = Timer::new(dp.TIM2, &clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_CLOCKS.borrow(cs).set(Some(clocks));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    // 3) Low-power run until the first interrupt.
+    power::enter_low_power_run(&clocks).expect("clock too fast for low-power run");
+    defmt::info!("Power state: {}", power::power_state());
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        power::exit_low_power_run();
+
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+        defmt::info!("Delay Atual: {} ms", delayms);
+        let clocks = G_CLOCKS.borrow(cs).get().unwrap();
+        report(&clocks);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+
+        power::enter_low_power_run(&clocks).ok();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        // The work of the blink on the main regulator.
+        power::exit_low_power_run();
+
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+
+        // Back to low-power run until the next blink.
+        let clocks = G_CLOCKS.borrow(cs).get().unwrap();
+        power::enter_low_power_run(&clocks).ok();
+    });
+}
@@ -0,0 +1,259 @@
+//! example: clocks on the MCO pin, chosen from a serial shell.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), at 170 MHz from the PLL, with the LSE
+//! running and a shell on the ST-LINK virtual COM port (USART2, 115200 baud).
+//! The `mco` command puts a clock on PA8 (D7) for a scope to measure:
+//!
+//! ```text
+//! > mco pll 8
+//! MCO: Pll / 8 = 21250000 Hz
+//! > mco lse
+//! MCO: Lse / 1 = 32768 Hz
+//! > mco off
+//! ```
+//!
+//! The USART2 RX interrupt feeds each character to the shell and runs the
+//! command once Enter completes the line.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{Alternate, AF7, ExtiPin, Floating, Input, Output, PushPull, SignalEdge, Speed, gpioa, gpioc};
+use hal::rcc::Clocks;
+use hal::serial::{Event as SerialEvent, FullConfig, Serial};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::clocks::{self, ClockConfig};
+use nucleo_g474re::mco::{Divider, Mco, Source};
+use nucleo_g474re::shell::{Input as ShellInput, Shell};
+
+use cortex_m_rt::entry;
+
+use core::fmt::Write;
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{TIM2, USART2};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the virtual COM port
+type SerialPort = Serial<USART2, gpioa::PA2<Alternate<AF7>>, gpioa::PA3<Alternate<AF7>>>;
+
+const HELP: &str = "commands:\r\n  mco <sysclk|hsi|hse|pll|lsi|lse> [1|2|4|8|16]\r\n  mco off\r\n  mco\r\n";
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the serial port that I'm going to pass around.
+static G_SERIAL: Mutex<RefCell<Option<SerialPort>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the shell line.
+static G_SHELL: Mutex<RefCell<Shell>> = Mutex::new(RefCell::new(Shell::new()));
+// Create a Global Variable for the clock output that I'm going to pass around.
+static G_MCO: Mutex<RefCell<Option<Mco>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the clock frequencies, for the MCO report.
+static G_CLOCKS: Mutex<Cell<Option<Clocks>>> = Mutex::new(Cell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Runs one shell line.
+fn run(line: &str, mco: &mut Mco, clocks: &Clocks, out: &mut SerialPort) {
+    let mut words = line.split_ascii_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (None, _, _) => {}
+        (Some("mco"), None, _) => {}
+        (Some("mco"), Some("off"), None) => mco.disable(),
+        (Some("mco"), Some(source), divisor) => {
+            let source = match source {
+                "sysclk" => Source::SysClk,
+                "hsi" => Source::Hsi,
+                "hse" => Source::Hse,
+                "pll" => Source::Pll,
+                "lsi" => Source::Lsi,
+                "lse" => Source::Lse,
+                _ => {
+                    out.write_str(HELP).ok();
+                    return;
+                }
+            };
+            let divider = match divisor.map(|divisor| divisor.parse().ok().and_then(Divider::from_divisor)) {
+                None => Divider::Div1,
+                Some(Some(divider)) => divider,
+                Some(None) => {
+                    out.write_str("divider: 1, 2, 4, 8 or 16\r\n").ok();
+                    return;
+                }
+            };
+            if mco.set(source, divider).is_err() {
+                writeln!(out, "{:?} is not running\r", source).ok();
+                return;
+            }
+        }
+        _ => {
+            out.write_str(HELP).ok();
+            return;
+        }
+    }
+
+    // Report what is on the pin after any `mco` command.
+    match (mco.source(), mco.frequency(clocks)) {
+        (None, _) => out.write_str("MCO: off\r\n").ok(),
+        (Some(source), Some(frequency)) => {
+            writeln!(out, "MCO: {:?} / {} = {} Hz\r", source, mco.divider().divisor(), frequency.0).ok()
+        }
+        (Some(source), None) => writeln!(out, "MCO: {:?} / {}\r", source, mco.divider().divisor()).ok(),
+    };
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+
+    // 1) 170 MHz from the HSI, and the LSE for the MCO to show.
+    let (mut rcc, _sources) = clocks::freeze(dp.RCC.constrain(), ClockConfig::hsi().lse());
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 2) Clock output on PA8, off until asked for.
+    let mco = Mco::new(gpioa.pa8.into_alternate().set_speed(Speed::VeryHigh));
+
+    // 3) Shell on the virtual COM port, a character per RX interrupt.
+    let tx = gpioa.pa2.into_alternate();
+    let rx = gpioa.pa3.into_alternate();
+    let mut serial = dp
+        .USART2
+        .usart(tx, rx, FullConfig::default().baudrate(115_200.bps()), &mut rcc)
+        .expect("cannot configure USART2");
+    serial.write_str("\r\nMCO shell, 'help' for the commands\r\n> ").ok();
+    serial.listen(SerialEvent::Rxne);
+
+    // 4) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_SERIAL.borrow(cs).replace(Some(serial));
+        G_MCO.borrow(cs).replace(Some(mco));
+        G_CLOCKS.borrow(cs).set(Some(rcc.clocks));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::USART2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// USART2 interrupt: a character for the shell.
+#[interrupt]
+fn USART2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut serial = G_SERIAL.borrow(cs).borrow_mut();
+        let serial = serial.as_mut().unwrap();
+        let mut shell = G_SHELL.borrow(cs).borrow_mut();
+
+        // Reading clears the interrupt; errors (overrun) drop the character.
+        while let Ok(byte) = serial.read() {
+            let input = shell.push(byte);
+            shell.echo(serial, input).ok();
+            if input != ShellInput::Line {
+                continue;
+            }
+            let line = shell.line().unwrap_or("");
+            if line == "help" {
+                serial.write_str(HELP).ok();
+            } else {
+                let mut mco = G_MCO.borrow(cs).borrow_mut();
+                let clocks = G_CLOCKS.borrow(cs).get().unwrap();
+                run(line, mco.as_mut().unwrap(), &clocks, serial);
+            }
+            shell.prompt(serial).ok();
+        }
+    });
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+        defmt::info!("Delay Atual: {} ms", delayms);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
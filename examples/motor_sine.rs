@@ -0,0 +1,217 @@
+//! example: open-loop sine drive of a three-phase motor, in step with the blink.
+//!
+//! TIM1 drives three half bridges at 20 kHz, center aligned, wired as listed
+//! in the `motor` module (an X-NUCLEO-IHM08M1 power board fits). Every
+//! fourth PWM period the TIM1 update interrupt advances an electrical angle
+//! and the CORDIC turns it into three sine duties, 120° apart: the bridges
+//! produce a rotating field, turning a small gimbal motor at a steady speed.
+//!
+//! The speed follows the blink of the main program (TIM2 toggles the LED on
+//! PA5, the User Button on PC13 halves the delay): one electrical turn per
+//! second at 1000 ms, doubling with every halving.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, Speed, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+use hal::time::Hertz;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::cordic::Cordic;
+use nucleo_g474re::motor::{Modulation, ThreePhasePwm};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+const PWM_FREQUENCY: Hertz = Hertz(20_000);
+const DEAD_TIME_NS: u32 = 500;
+// New duties every 4 PWM periods: 5 kHz.
+const UPDATE_PERIOD: u8 = 4;
+const UPDATE_RATE: u32 = 20_000 / UPDATE_PERIOD as u32;
+// Peak of the sine around 50% duty, in percent: keeps the current of a small motor low.
+const AMPLITUDE_PERCENT: i64 = 30;
+// A third of a turn, in the CORDIC angle units where a turn is 2^32.
+const THIRD: i32 = (u32::MAX / 3) as i32;
+
+// Sine duties from an electrical angle advancing at a fixed step.
+struct SineDrive {
+    cordic: Cordic,
+    angle: i32,
+    step: i32,
+}
+
+impl SineDrive {
+    // Angle step per update for one electrical turn every `1000 / delayms` s.
+    fn set_speed(&mut self, delayms: u32) {
+        let turns_per_second = 1000 / delayms as u64;
+        self.step = ((turns_per_second << 32) / UPDATE_RATE as u64) as i32;
+    }
+}
+
+impl Modulation for SineDrive {
+    fn duties(&mut self, max_duty: u16) -> [u16; 3] {
+        self.angle = self.angle.wrapping_add(self.step);
+        let half = max_duty as i64 / 2;
+        [0, THIRD, -THIRD].map(|offset| {
+            let (sin, _cos) = self.cordic.sin_cos(self.angle.wrapping_add(offset));
+            (half + ((half * AMPLITUDE_PERCENT * sin as i64 / 100) >> 31)) as u16
+        })
+    }
+}
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the motor PWM that I'm going to pass around.
+static G_PWM: Mutex<RefCell<Option<ThreePhasePwm>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the sine drive that I'm going to pass around.
+static G_DRIVE: Mutex<RefCell<Option<SineDrive>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) The sine drive, starting at one electrical turn per second.
+    let mut drive = SineDrive { cordic: Cordic::new(dp.CORDIC), angle: 0, step: 0 };
+    drive.set_speed(1000);
+
+    // 2) TIM1 on the three phases, new duties from the update interrupt.
+    let pins = (
+        gpioa.pa8.into_alternate().set_speed(Speed::VeryHigh),
+        gpioa.pa9.into_alternate().set_speed(Speed::VeryHigh),
+        gpioa.pa10.into_alternate().set_speed(Speed::VeryHigh),
+        gpiob.pb13.into_alternate().set_speed(Speed::VeryHigh),
+        gpiob.pb14.into_alternate().set_speed(Speed::VeryHigh),
+        gpiob.pb15.into_alternate().set_speed(Speed::VeryHigh),
+    );
+    let mut pwm = ThreePhasePwm::new(dp.TIM1, pins, rcc.clocks.apb2_tim_clk, PWM_FREQUENCY, DEAD_TIME_NS);
+    pwm.set_update_period(UPDATE_PERIOD);
+    pwm.listen();
+    pwm.enable();
+    defmt::info!("PWM at {} Hz, dead-time {} ns", pwm.frequency().0, pwm.dead_time_ns());
+
+    // 3) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_PWM.borrow(cs).replace(Some(pwm));
+        G_DRIVE.borrow(cs).replace(Some(drive));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM1_UP_TIM16);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// TIM1 update interrupt: duties of the next periods.
+#[interrupt]
+fn TIM1_UP_TIM16() {
+    cortex_m::interrupt::free(|cs| {
+        let mut pwm = G_PWM.borrow(cs).borrow_mut();
+        let mut drive = G_DRIVE.borrow(cs).borrow_mut();
+        pwm.as_mut().unwrap().handle(drive.as_mut().unwrap());
+    });
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+
+        // Speed up along with the blink.
+        let mut drive = G_DRIVE.borrow(cs).borrow_mut();
+        drive.as_mut().unwrap().set_speed(delayms);
+        defmt::info!("{} ms, {} electrical turns per second", delayms, 1000 / delayms);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,222 @@
+//! example: two tasks at two rates, one hardware timer each.
+//!
+//! TIM2 blinks the LED on PA5 as in the main program, the User Button (PC13)
+//! halving the delay, while TIM3 runs a second task at a fixed 10 Hz of its
+//! own: it samples a potentiometer wiper on A0 (PA0, ADC1 channel 1) and,
+//! once a second, logs the average, the lowest and the highest voltage of
+//! the last ten samples, with the LED toggles of that second.
+//!
+//! Each task is an interrupt handler with its peripherals in globals, as in
+//! the main program: a second timer is one more global and one more handler,
+//! and what the tasks share, the toggle count here, is a global both take in
+//! their critical sections.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{Analog, ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::adc::{config::SampleTime, Adc, AdcClaim, ClockSource, Disabled};
+use hal::delay::SYSTDelayExt;
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{ADC1, TIM2, TIM3};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the potentiometer pin
+type PotPin = gpioa::PA0<Analog>;
+
+// Rate of the sampling task, and samples per log.
+const SAMPLE_HZ: u32 = 10;
+const SAMPLES_PER_LOG: u32 = SAMPLE_HZ;
+
+// The samples of the sampling task since its last log.
+struct Stats {
+    count: u32,
+    sum: u32,
+    min: u16,
+    max: u16,
+}
+
+impl Stats {
+    const fn new() -> Self {
+        Stats { count: 0, sum: 0, min: u16::MAX, max: 0 }
+    }
+
+    fn add(&mut self, sample: u16) {
+        self.count += 1;
+        self.sum += sample as u32;
+        self.min = self.min.min(sample);
+        self.max = self.max.max(sample);
+    }
+}
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the sampling Timer Peripheral that I'm going to pass around.
+static G_SAMPLE: Mutex<RefCell<Option<CountDownTimer<TIM3>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the ADC that I'm going to pass around.
+static G_ADC: Mutex<RefCell<Option<Adc<ADC1, Disabled>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the potentiometer pin that I'm going to pass around.
+static G_POT: Mutex<RefCell<Option<PotPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the samples since the last log.
+static G_STATS: Mutex<RefCell<Stats>> = Mutex::new(RefCell::new(Stats::new()));
+// Create a Global Variable for the LED toggles since the last log, shared by both tasks.
+static G_TOGGLES: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) ADC1 for single conversions. The SysTick delay is only used for the
+    //    regulator start-up time.
+    let pot = gpioa.pa0.into_analog();
+    let mut delay = cp.SYST.delay(&rcc.clocks);
+    let adc = dp.ADC1.claim(ClockSource::SystemClock, &rcc, &mut delay, true);
+
+    // 2) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    // 3) Sampling timer, at its own rate.
+    let sample = Timer::new(dp.TIM3, &rcc.clocks);
+    let mut sample = sample.start_count_down(SAMPLE_HZ.hz());
+    sample.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_SAMPLE.borrow(cs).replace(Some(sample));
+        G_ADC.borrow(cs).replace(Some(adc));
+        G_POT.borrow(cs).replace(Some(pot));
+        defmt::info!("Delay Atual: {} ms", G_DELAYMS.borrow(cs).get());
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM3);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+        defmt::info!("Delay Atual: {} ms", delayms);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt: the blink task.
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+        G_TOGGLES.borrow(cs).set(G_TOGGLES.borrow(cs).get() + 1);
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
+
+// Sampling Timer Interrupt: the sampling task, and its log once a second.
+#[interrupt]
+fn TIM3() {
+    cortex_m::interrupt::free(|cs| {
+        let mut adc = G_ADC.borrow(cs).borrow_mut();
+        let adc = adc.as_mut().unwrap();
+        let pot = G_POT.borrow(cs).borrow();
+        let sample = adc.convert(pot.as_ref().unwrap(), SampleTime::Cycles_640_5);
+
+        let mut stats = G_STATS.borrow(cs).borrow_mut();
+        stats.add(sample);
+        if stats.count == SAMPLES_PER_LOG {
+            defmt::info!(
+                "Pot: {} mV (min {} mV, max {} mV), {} toggles",
+                adc.sample_to_millivolts((stats.sum / stats.count) as u16),
+                adc.sample_to_millivolts(stats.min),
+                adc.sample_to_millivolts(stats.max),
+                G_TOGGLES.borrow(cs).replace(0)
+            );
+            *stats = Stats::new();
+        }
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut sample = G_SAMPLE.borrow(cs).borrow_mut();
+        sample.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
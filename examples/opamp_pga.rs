@@ -0,0 +1,147 @@
+//! example: internal op-amp as a programmable gain amplifier.
+//!
+//! OPAMP1 amplifies the voltage on A1 (PA1) and its output is routed
+//! internally to ADC1 channel 13, so no external resistor or wiring is needed.
+//! On every TIM2 tick the amplified signal is sampled and logged, together
+//! with the input voltage computed back from the gain. Each press of the User
+//! Button (PC13) selects the next gain: follower (x1), x2, x4 ... x64, x1 ...
+//! Feed PA1 with a small voltage (e.g. a potentiometer between GND and a few
+//! hundred millivolts) and watch the measurement saturate as the gain grows.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Analog, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+use hal::adc::{config::SampleTime, Adc, AdcClaim, ClockSource, Disabled};
+use hal::delay::SYSTDelayExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::opamp::{Gain, Mode, Opamp1};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::RefCell;
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the op-amp amplifying PA1
+type Amplifier = Opamp1<gpioa::PA1<Analog>>;
+
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the ADC that I'm going to pass around.
+static G_ADC: Mutex<RefCell<Option<Adc<stm32::ADC1, Disabled>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the op-amp that I'm going to pass around.
+static G_OPAMP: Mutex<RefCell<Option<Amplifier>>> = Mutex::new(RefCell::new(None));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) Op-amp starts as a follower, output routed to the ADC instead of PA2.
+    let (opamp1, ..) = dp.OPAMP.split(&mut rcc);
+    let opamp = Opamp1::new(opamp1, gpioa.pa1.into_analog(), Mode::Follower);
+
+    // 2) ADC1 for one-shot conversions of the op-amp channel.
+    let mut delay = cp.SYST.delay(&rcc.clocks);
+    let adc = dp.ADC1.claim(ClockSource::SystemClock, &rcc, &mut delay, true);
+
+    // 3) Sample once per second.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_ADC.borrow(cs).replace(Some(adc));
+        G_OPAMP.borrow(cs).replace(Some(opamp));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        let mut opamp = G_OPAMP.borrow(cs).borrow_mut();
+        let opamp = opamp.as_mut().unwrap();
+        let mode = match opamp.mode() {
+            Mode::Follower => Mode::Pga(Gain::X2),
+            Mode::Pga(Gain::X64) => Mode::Follower,
+            Mode::Pga(gain) => Mode::Pga(gain.next()),
+        };
+        opamp.set_mode(mode);
+        defmt::info!("Gain: x{}", mode.gain());
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut adc = G_ADC.borrow(cs).borrow_mut();
+        let adc = adc.as_mut().unwrap();
+        let opamp = G_OPAMP.borrow(cs).borrow();
+        let opamp = opamp.as_ref().unwrap();
+
+        // The op-amp output needs a long sampling time to be measured accurately.
+        let sample = adc.convert(opamp, SampleTime::Cycles_640_5);
+        let output_mv = adc.sample_to_millivolts(sample) as u32;
+        defmt::info!("x{}: output {} mV, input {} mV", opamp.mode().gain(), output_mv, output_mv / opamp.mode().gain());
+
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,261 @@
+//! example: reading and changing the option bytes from a serial shell.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), with a shell on the ST-LINK virtual COM
+//! port (USART2, 115200 baud) over [`optionbytes`]:
+//!
+//! ```text
+//! > options
+//! RDP Level0, BOR Level0, BOOT0 from the pin, nBOOT0 true, nBOOT1 true, BFB2 false, DBANK true
+//! > bor 3
+//! written, 'reload' to apply
+//! > reload
+//! ```
+//!
+//! `bor <0-4>` sets the brown-out level, `boot pin` takes BOOT0 from the
+//! PB8-BOOT0 pin and `boot flash` from the option bit, set to boot the main
+//! flash. Each change is written at once and applies from `reload`, which
+//! resets the chip, or from the next power cycle. [`optionbytes::write`]
+//! turns down the changes it does not make: any under read protection
+//! level 2, and a boot setup that would not reach the main flash.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{Alternate, AF7, ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::serial::{Event as SerialEvent, FullConfig, Serial};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::optionbytes::{self, BorLevel, Changes};
+use nucleo_g474re::shell::{Input as ShellInput, Shell};
+
+use cortex_m_rt::entry;
+
+use core::fmt::Write;
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{FLASH, TIM2, USART2};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the virtual COM port
+type SerialPort = Serial<USART2, gpioa::PA2<Alternate<AF7>>, gpioa::PA3<Alternate<AF7>>>;
+
+const HELP: &str = "commands:\r\n  options\r\n  bor <0-4>\r\n  boot <pin|flash>\r\n  reload\r\n";
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the serial port that I'm going to pass around.
+static G_SERIAL: Mutex<RefCell<Option<SerialPort>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the shell line.
+static G_SHELL: Mutex<RefCell<Shell>> = Mutex::new(RefCell::new(Shell::new()));
+// Create a Global Variable for the flash, which writes the option bytes.
+static G_FLASH: Mutex<RefCell<Option<FLASH>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Writes the option bytes in use.
+fn show(out: &mut SerialPort) {
+    let options = optionbytes::read();
+    defmt::info!("Option bytes: {}", options);
+    writeln!(
+        out,
+        "RDP {:?}, BOR {:?}, BOOT0 from the {}, nBOOT0 {}, nBOOT1 {}, BFB2 {}, DBANK {}\r",
+        options.read_protection,
+        options.bor_level,
+        if options.n_swboot0 { "pin" } else { "option bit" },
+        options.n_boot0,
+        options.n_boot1,
+        options.bfb2,
+        options.dual_bank
+    )
+    .ok();
+}
+
+// Runs one shell line.
+fn run(line: &str, out: &mut SerialPort, cs: &cortex_m::interrupt::CriticalSection) {
+    let mut words = line.split_ascii_whitespace();
+    let changes = match (words.next(), words.next(), words.next()) {
+        (None, _, _) => return,
+        (Some("options"), None, _) => return show(out),
+        (Some("reload"), None, _) => {
+            out.write_str("resetting\r\n").ok();
+            hal::nb::block!(out.flush()).ok();
+            optionbytes::reload(G_FLASH.borrow(cs).borrow_mut().take().unwrap());
+        }
+        (Some("bor"), Some(level), None) => match level.parse::<u8>() {
+            Ok(level @ 0..=4) => Changes { bor_level: Some(BorLevel::from_bits(level)), ..Changes::default() },
+            _ => {
+                out.write_str(HELP).ok();
+                return;
+            }
+        },
+        (Some("boot"), Some("pin"), None) => Changes { n_swboot0: Some(true), ..Changes::default() },
+        (Some("boot"), Some("flash"), None) => {
+            Changes { n_swboot0: Some(false), n_boot0: Some(true), ..Changes::default() }
+        }
+        _ => {
+            out.write_str(HELP).ok();
+            return;
+        }
+    };
+
+    let mut flash = G_FLASH.borrow(cs).borrow_mut();
+    match optionbytes::write(flash.as_mut().unwrap(), changes) {
+        Ok(()) => {
+            defmt::info!("Option bytes written: {}", changes);
+            out.write_str("written, 'reload' to apply\r\n").ok();
+        }
+        Err(error) => {
+            defmt::warn!("Option bytes not written: {}", error);
+            writeln!(out, "not written: {:?}\r", error).ok();
+        }
+    }
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) Shell on the virtual COM port, a character per RX interrupt.
+    let tx = gpioa.pa2.into_alternate();
+    let rx = gpioa.pa3.into_alternate();
+    let mut serial = dp
+        .USART2
+        .usart(tx, rx, FullConfig::default().baudrate(115_200.bps()), &mut rcc)
+        .expect("cannot configure USART2");
+    serial.write_str("\r\nOption byte shell, 'help' for the commands\r\n").ok();
+    show(&mut serial);
+    serial.write_str("> ").ok();
+    serial.listen(SerialEvent::Rxne);
+
+    // 2) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_SERIAL.borrow(cs).replace(Some(serial));
+        G_FLASH.borrow(cs).replace(Some(dp.FLASH));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::USART2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// USART2 interrupt: a character for the shell.
+#[interrupt]
+fn USART2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut serial = G_SERIAL.borrow(cs).borrow_mut();
+        let serial = serial.as_mut().unwrap();
+        let mut shell = G_SHELL.borrow(cs).borrow_mut();
+
+        // Reading clears the interrupt; errors (overrun) drop the character.
+        while let Ok(byte) = serial.read() {
+            let input = shell.push(byte);
+            shell.echo(serial, input).ok();
+            if input != ShellInput::Line {
+                continue;
+            }
+            let line = shell.line().unwrap_or("");
+            if line == "help" {
+                serial.write_str(HELP).ok();
+            } else {
+                run(line, serial, cs);
+            }
+            shell.prompt(serial).ok();
+        }
+    });
+}
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+        defmt::info!("Delay Atual: {} ms", delayms);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,199 @@
+//! example: a firmware update over the virtual COM port, into the other bank.
+//!
+//! The LED on PA5 blinks as in the main program, the User Button (PC13)
+//! halving the delay, and the main loop takes updates on the ST-LINK virtual
+//! COM port (USART2, 115200 baud) with [`Updater`]: the header, then the
+//! image in 2 KiB chunks, each answered with ACK (0x79) once written into
+//! the inactive bank, or NACK (0x1F) on an error. The blink goes on while
+//! the chunks are written: the flash reads from one bank while it writes the
+//! other, and the writes block the main loop, not the interrupts.
+//!
+//! The image is a `.bin` of a program for 0x0800_0000, the header its size
+//! and its CRC-32, both little endian. The sender waits for the answer to
+//! the header and to each chunk before the next, with pyserial:
+//!
+//! ```text
+//! image = open("firmware.bin", "rb").read()
+//! port.write(struct.pack("<II", len(image), zlib.crc32(image)))
+//! assert port.read(1) == b"\x79"
+//! for offset in range(0, len(image), 2048):
+//!     port.write(image[offset:offset + 2048])
+//!     assert port.read(1) == b"\x79"
+//! ```
+//!
+//! The last ACK comes once the CRC of the bank matches, and the board resets
+//! into the new image; the log says which bank each start runs from.
+//!
+//! Needs the dual-bank flash of the G474: `--features board-g474re`, the
+//! default.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::serial::FullConfig;
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::crc::{Config, Crc};
+use nucleo_g474re::ota::{self, Event as OtaEvent, Updater, ACK, NACK};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+    defmt::info!("Running from bank {}", if ota::running_from_bank2() { 2 } else { 1 });
+
+    // 1) Serial port on the virtual COM port, polled by the main loop.
+    let tx = gpioa.pa2.into_alternate();
+    let rx = gpioa.pa3.into_alternate();
+    let mut serial = dp
+        .USART2
+        .usart(tx, rx, FullConfig::default().baudrate(115_200.bps()), &mut rcc)
+        .expect("cannot set up USART2");
+
+    // 2) The updater, with the CRC unit checking the written bank.
+    let crc = Crc::new(dp.CRC, Config::CRC32);
+    let mut updater = Updater::new(dp.FLASH, crc).expect("the flash is not in dual-bank mode");
+
+    // 3) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        // A byte lost to an overrun drops the update: the sender starts over.
+        let byte = match serial.read() {
+            Ok(byte) => byte,
+            Err(hal::nb::Error::WouldBlock) => continue,
+            Err(hal::nb::Error::Other(_)) => {
+                updater.abort();
+                hal::nb::block!(serial.write(NACK)).ok();
+                continue;
+            }
+        };
+        let answer = match updater.feed(byte) {
+            Ok(OtaEvent::Pending) => continue,
+            Ok(OtaEvent::Started(header)) => {
+                defmt::info!("Update of {} bytes, CRC {=u32:#x}", header.size, header.crc);
+                ACK
+            }
+            Ok(OtaEvent::Written { written }) => {
+                defmt::info!("{} bytes written", written);
+                ACK
+            }
+            Ok(OtaEvent::Verified) => {
+                hal::nb::block!(serial.write(ACK)).ok();
+                hal::nb::block!(serial.flush()).ok();
+                updater.swap_and_reset();
+            }
+            Err(error) => {
+                defmt::error!("Update failed: {}", error);
+                NACK
+            }
+        };
+        hal::nb::block!(serial.write(answer)).ok();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+        defmt::info!("Delay Atual: {} ms", delayms);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().unwrap();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
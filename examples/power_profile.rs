@@ -0,0 +1,162 @@
+//! example: the sleeps of the blink marked on a pin, for a power analyzer.
+//!
+//! The LED on PA5 blinks every second on TIM2, on the 16 MHz HSI, and the
+//! main loop sleeps through a [`SleepProbe`] between two interrupts: D2
+//! (PA10) is high for each sleep, to put next to the current on a power
+//! analyzer or a logic analyzer. Each press of the User Button (PC13)
+//! switches the sleeps between `wfi`, with TIM2 blinking on, and Stop 1,
+//! where every clock stops and the next press wakes the chip. After each
+//! wakeup the main loop logs the events of the probe:
+//!
+//! ```text
+//! Wfi Enter at <cycles>
+//! Wfi Exit at <cycles>
+//! ```
+//!
+//! The probe takes any push-pull output: to move it, change the pin the
+//! `ProbePin` alias names and the line that configures it.
+//!
+//! The debug port is kept alive in Stop so RTT keeps logging; without a
+//! debugger, call `power::debug_in_low_power(false)` to see the real draw.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::compat::Compat;
+use nucleo_g474re::power::{self, StopMode};
+use nucleo_g474re::power_probe::SleepProbe;
+use nucleo_g474re::profile;
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the probe pin, D2 on the Arduino header.
+type ProbePin = gpioa::PA10<Output<PushPull>>;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the sleep of the main loop: Stop 1 if set.
+static G_STOP: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+    power::debug_in_low_power(true);
+
+    // 1) The cycle counter for the timestamps, and the probe on its pin.
+    profile::start(&mut cp.DCB, &mut cp.DWT);
+    let probe_pin: ProbePin = gpioa.pa10.into_push_pull_output();
+    let mut probe = SleepProbe::new(Compat(probe_pin));
+
+    // 2) Blink timer, every second.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // 3) The button, also the only wakeup source from Stop 1.
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        // Sleep through the probe; the interrupt that wakes the chip runs
+        // once the pin is low again.
+        if cortex_m::interrupt::free(|cs| G_STOP.borrow(cs).get()) {
+            probe.stop(StopMode::Stop1, &mut cp.SCB);
+        } else {
+            probe.wfi();
+        }
+
+        // Awake: the events of the probe, out of the sleeps.
+        while let Some(event) = probe.pop() {
+            defmt::info!("{} {} at {}", event.sleep, event.edge, event.cycles);
+        }
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to the Sleep Global Data and Switch it
+        let stop = !G_STOP.borrow(cs).get();
+        G_STOP.borrow(cs).set(stop);
+        defmt::info!("Sleeping in {}", if stop { "Stop 1" } else { "wfi" });
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,203 @@
+//! example: TIM1 break input as a hardware fault stop, re-armed by the button.
+//!
+//! TIM1 drives a half bridge at 20 kHz, 50% duty: CH1 on PA8 (D7), CH1N on
+//! PA7 (D11). Two break sources turn both outputs off in hardware, without
+//! waiting for any interrupt:
+//!
+//! * PA6 (D12), pulled up, connected to GND: an external fault signal.
+//! * COMP1, when A1 (PA1) goes above half of VREFINT (about 0.6 V): a
+//!   current-sense voltage, or a potentiometer wiper for testing.
+//!
+//! The `TIM1_BRK_TIM15` interrupt logs the fault and the LED (PA5) stays on
+//! while the outputs are off. They only come back on a press of the User
+//! Button (PC13), which fails and logs a warning while the fault is still
+//! there. Without a fault, the button halves the blink delay as usual.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, Speed, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+use hal::time::Hertz;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::comp::{Comp1, Hysteresis, Threshold};
+use nucleo_g474re::pwm::{BreakComparator, ComplementaryPwm, Event as PwmEvent, Rearm};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{TIM1, TIM2};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+const PWM_FREQUENCY: Hertz = Hertz(20_000);
+const DEAD_TIME_NS: u32 = 1000;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the complementary PWM that I'm going to pass around.
+static G_PWM: Mutex<RefCell<Option<ComplementaryPwm<TIM1>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the comparator that I'm going to pass around.
+static G_COMP: Mutex<RefCell<Option<Comp1>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) COMP1 on PA1, threshold at 1/2 VREFINT.
+    let comp = Comp1::new(&dp.COMP, gpioa.pa1.into_analog(), Threshold::HalfVrefint, Hysteresis::Mv20);
+
+    // 2) TIM1 CH1/CH1N at 50%, broken by PA6 or COMP1 and re-armed by software only.
+    let pins = (
+        gpioa.pa8.into_alternate().set_speed(Speed::VeryHigh),
+        gpioa.pa7.into_alternate().set_speed(Speed::VeryHigh),
+    );
+    let break_pin = gpioa.pa6.into_pull_up_input().into_alternate();
+    let mut pwm = ComplementaryPwm::new(dp.TIM1, pins, rcc.clocks.apb2_tim_clk, PWM_FREQUENCY, DEAD_TIME_NS);
+    pwm.set_duty(pwm.max_duty() / 2);
+    pwm.break_on_pin(break_pin);
+    pwm.break_on_comparator(BreakComparator::Comp1);
+    pwm.enable_break(Rearm::Software);
+    pwm.listen(PwmEvent::Break);
+    if pwm.rearm().is_err() {
+        defmt::warn!("Fault at start up, outputs off");
+    }
+
+    // 3) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_PWM.borrow(cs).replace(Some(pwm));
+        G_COMP.borrow(cs).replace(Some(comp));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM1_BRK_TIM15);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// TIM1 break interrupt: the outputs are already off, log why.
+#[interrupt]
+fn TIM1_BRK_TIM15() {
+    cortex_m::interrupt::free(|cs| {
+        let mut pwm = G_PWM.borrow(cs).borrow_mut();
+        if pwm.as_mut().unwrap().handle_break() {
+            let comp = G_COMP.borrow(cs).borrow();
+            let source = if comp.as_ref().unwrap().output() { "COMP1" } else { "PA6" };
+            defmt::error!("Fault on {}, outputs off until re-armed", source);
+        }
+    });
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        let mut pwm = G_PWM.borrow(cs).borrow_mut();
+        let pwm = pwm.as_mut().unwrap();
+
+        if pwm.is_tripped() {
+            // Re-arm instead of changing the delay.
+            match pwm.rearm() {
+                Ok(()) => defmt::info!("Re-armed, outputs on"),
+                Err(error) => defmt::warn!("Cannot re-arm: {}", error),
+            }
+        } else {
+            // Obtain Access to Delay Global Data and Adjust Delay
+            G_DELAYMS
+                .borrow(cs)
+                .set(G_DELAYMS.borrow(cs).get()/2);
+
+            if G_DELAYMS.borrow(cs).get() < 125_u32 {
+                G_DELAYMS.borrow(cs).set(1000_u32);
+            }
+
+            let delayms = G_DELAYMS.borrow(cs).get();
+            let mut timer = G_TIM.borrow(cs).borrow_mut();
+            timer.as_mut().unwrap().start(delayms.ms());
+        }
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        let led = led.as_mut().unwrap();
+
+        // Steady on while the outputs are off.
+        let pwm = G_PWM.borrow(cs).borrow();
+        if pwm.as_ref().unwrap().is_tripped() {
+            led.set_high().ok();
+        } else {
+            led.toggle().ok();
+        }
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
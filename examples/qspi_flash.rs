@@ -0,0 +1,203 @@
+//! example: interrupt blink with its delay logged to QUADSPI flash.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), with every new delay appended to a log
+//! in the first sector of a W25Q flash, wired as listed in the `qspi` module.
+//! At boot the flash is mapped into memory, the log is scanned for the last
+//! record and the blink resumes with the delay it had before the reset.
+//!
+//! The button interrupt only records the new delay; the main loop programs
+//! it, so the blink keeps running while the flash is busy. When the sector is
+//! full it is erased and the log starts again from its first record.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, Speed, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::qspi::{W25q, SECTOR_SIZE};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Log records: a marker and the delay in ms, little endian. Erased flash reads 0xFF.
+const RECORD_SIZE: u32 = 4;
+const MARKER: [u8; 2] = [0x5A, 0xA5];
+// The log lives in the first sector of the chip.
+const LOG_ADDRESS: u32 = 0;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+// Create a Global Variable for a delay waiting to be logged by the main loop.
+static G_UNSAVED: Mutex<Cell<Option<u32>>> = Mutex::new(Cell::new(None));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Returns the last delay of the log and the offset of the first free record.
+fn scan(log: &[u8]) -> (Option<u32>, u32) {
+    let mut last = None;
+    for (index, record) in log.chunks_exact(RECORD_SIZE as usize).enumerate() {
+        if record[..2] != MARKER {
+            return (last, index as u32 * RECORD_SIZE);
+        }
+        last = Some(u16::from_le_bytes([record[2], record[3]]) as u32);
+    }
+    (last, SECTOR_SIZE)
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) QUADSPI1 and the flash.
+    let pins = (
+        gpiob.pb10.into_alternate().set_speed(Speed::VeryHigh),
+        gpiob.pb11.into_alternate().set_speed(Speed::VeryHigh),
+        gpiob.pb1.into_alternate().set_speed(Speed::VeryHigh),
+        gpiob.pb0.into_alternate().set_speed(Speed::VeryHigh),
+        gpioa.pa7.into_alternate().set_speed(Speed::VeryHigh),
+        gpioa.pa6.into_alternate().set_speed(Speed::VeryHigh),
+    );
+    let flash = W25q::new(dp.QUADSPI, pins, rcc.clocks.ahb_clk).expect("no flash on QUADSPI1");
+    defmt::info!("Flash {=[u8]:#x}, {} KiB", flash.id(), flash.capacity() / 1024);
+
+    // 2) Read the log through the memory mapping and resume the last delay.
+    let mapped = flash.into_memory_mapped();
+    let start = LOG_ADDRESS as usize;
+    let (last, mut next) = scan(&mapped.as_slice()[start..start + SECTOR_SIZE as usize]);
+    let mut flash = mapped.into_indirect();
+    let delayms = match last {
+        Some(delayms) => {
+            defmt::info!("Resuming with {} ms, {} records in the log", delayms, next / RECORD_SIZE);
+            delayms.clamp(125, 1000)
+        }
+        None => 1000,
+    };
+
+    // 3) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(delayms.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_DELAYMS.borrow(cs).set(delayms);
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        let unsaved = cortex_m::interrupt::free(|cs| G_UNSAVED.borrow(cs).take());
+
+        match unsaved {
+            // The flash operations run with the interrupts enabled.
+            Some(delayms) => {
+                if next == SECTOR_SIZE {
+                    defmt::info!("Log full, erasing the sector");
+                    flash.erase_sector(LOG_ADDRESS).unwrap();
+                    next = 0;
+                }
+                let delay = (delayms as u16).to_le_bytes();
+                flash.program(LOG_ADDRESS + next, &[MARKER[0], MARKER[1], delay[0], delay[1]]).unwrap();
+                next += RECORD_SIZE;
+                defmt::info!("Logged {} ms", delayms);
+            }
+            None => cortex_m::asm::wfi(),
+        }
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+
+        // Only the latest delay matters if presses come faster than the flash.
+        G_UNSAVED.borrow(cs).set(Some(delayms));
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
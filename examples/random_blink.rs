@@ -0,0 +1,147 @@
+//! example: LED blinking at random intervals drawn from the hardware RNG.
+//!
+//! On every TIM2 timeout the LED (PA5) toggles and the next period is drawn
+//! from the true random number generator, uniformly within the current
+//! range. Each press of the User Button (PC13) selects the next range from
+//! RANGES: a calm flicker, a nervous one and a slow, irregular blink.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::rng::Rng;
+
+use cortex_m_rt::entry;
+
+use core::ops::RangeInclusive;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Blink period ranges stepped through by the button, in milliseconds.
+// 1000 ms is the longest period the timer takes with the default clock.
+const RANGES: [RangeInclusive<u32>; 3] = [50..=250, 10..=60, 300..=1000];
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the RNG that I'm going to pass around.
+static G_RNG: Mutex<RefCell<Option<Rng>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the index into RANGES.
+static G_RANGE: Mutex<Cell<usize>> = Mutex::new(Cell::new(0));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // 1) RNG, clocked by the internal 48 MHz oscillator.
+    let rng = Rng::new(dp.RNG);
+
+    // 2) The first period is fixed, the following ones are random.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(100.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_RNG.borrow(cs).replace(Some(rng));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        let range = (G_RANGE.borrow(cs).get() + 1) % RANGES.len();
+        G_RANGE.borrow(cs).set(range);
+        defmt::info!("Random period between {} and {} ms", RANGES[range].start(), RANGES[range].end());
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        let range = RANGES[G_RANGE.borrow(cs).get()].clone();
+        let mut rng = G_RNG.borrow(cs).borrow_mut();
+        // On an RNG error keep blinking at the slowest period of the range.
+        let delayms = match rng.as_mut().unwrap().in_range(range.clone()) {
+            Ok(delayms) => delayms,
+            Err(error) => {
+                defmt::warn!("RNG error: {}", error);
+                *range.end()
+            }
+        };
+
+        // Obtain access to Global Timer Peripheral, load the new period and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        let timer = timer.as_mut().unwrap();
+        timer.start(delayms.ms());
+        timer.clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,123 @@
+//! example: a short LED pulse every few seconds from the RTC wakeup timer.
+//!
+//! TIM2 cannot time this: its clock stops in Stop and Standby. The RTC runs
+//! from the LSE (the LSI if the crystal does not start) through both, and
+//! its wakeup timer brings the chip back every `PERIOD_S` seconds for a
+//! `PULSE_MS` flash of the LED on PA5, the rest of the time a few
+//! microamps; the IDD jumper (JP5) and an ammeter show it.
+//!
+//! `STANDBY` picks the mode in between:
+//!
+//! - `false`, Stop 1: the core resumes after `power::enter_stop`, with the
+//!   RAM and the pins kept, on the 16 MHz HSI the program runs from anyway.
+//! - `true`, Standby: lower still, but every wakeup is a reset, the program
+//!   starting over from `main`; only the RTC remembers the period.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::clocks::{self, ClockConfig, Sysclk};
+use nucleo_g474re::power::{self, PowerState, StopMode, VoltageRange};
+use nucleo_g474re::rtc::Rtc;
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use core::cell::RefCell;
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+// Seconds between pulses.
+const PERIOD_S: u32 = 5;
+// Length of a pulse.
+const PULSE_MS: u32 = 20;
+// Standby instead of Stop 1 between pulses.
+const STANDBY: bool = false;
+
+// Create a Global Variable for the RTC Peripheral that I'm going to pass around.
+static G_RTC: Mutex<RefCell<Option<Rtc>>> = Mutex::new(RefCell::new(None));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    if power::woke_from_standby() {
+        defmt::info!("Woken from Standby by the RTC");
+    }
+
+    // 1) The LSE for the RTC, then the HSI, which wakeups from Stop come
+    // back to, in range 2: nothing here needs the PLL.
+    let (mut rcc, sources) = clocks::freeze(dp.RCC.constrain(), ClockConfig::hsi().lse());
+    let clocks = clocks::set_sysclk(Sysclk::Hsi16);
+    power::set_voltage_range(VoltageRange::Range2, &clocks).expect("clock too fast for range 2");
+    power::debug_in_low_power(true);
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let mut led = gpioa.pa5.into_push_pull_output();
+
+    // 2) The wakeup timer.
+    let source = sources.low_speed.expect("no low-speed clock");
+    let mut rtc = Rtc::new(dp.RTC, source);
+    rtc.set_wakeup(PERIOD_S).expect("invalid wakeup period");
+    let mode = if STANDBY { PowerState::Standby } else { PowerState::Stop(StopMode::Stop1) };
+    defmt::info!(
+        "RTC from {}, a pulse every {} s, {} in between (~{} uA)",
+        source,
+        PERIOD_S,
+        mode,
+        power::estimated_current_ua(mode, clocks.ahb_clk)
+    );
+
+    let pulse_cycles = clocks.sys_clk.0 / 1000 * PULSE_MS;
+    if STANDBY {
+        // 3) After each reset: the pulse, and back to Standby.
+        led.set_high().ok();
+        cortex_m::asm::delay(pulse_cycles);
+        led.set_low().ok();
+        power::enter_standby(&mut cp.SCB);
+    }
+
+    cortex_m::interrupt::free(|cs| {
+        G_RTC.borrow(cs).replace(Some(rtc));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::RTC_WKUP);
+    }
+
+    // 3) Stop 1 until the wakeup timer, its interrupt, then the pulse.
+    loop {
+        power::enter_stop(StopMode::Stop1, &mut cp.SCB);
+        led.set_high().ok();
+        cortex_m::asm::delay(pulse_cycles);
+        led.set_low().ok();
+    }
+}
+
+
+#[interrupt]
+fn RTC_WKUP() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain access to Global RTC Peripheral and Clear Interrupt Pending Flag
+        let mut rtc = G_RTC.borrow(cs).borrow_mut();
+        rtc.as_mut().unwrap().handle_wakeup();
+    });
+}
@@ -0,0 +1,197 @@
+//! example: interrupt blink with a matching tone on an I2S DAC.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), heard as well as seen: an I2S DAC or
+//! amplifier on SAI1 (PA8 bit clock, PA9 word select, PA10 data, as listed
+//! in the `sai` module) beeps while the LED is on. A DMA1 channel claimed
+//! from the `dma` module streams a sine table to SAI1 in circular mode, so
+//! the tone runs without the CPU, and the timer interrupt only mutes and
+//! unmutes the output.
+//!
+//! The pitch follows the delay: 244 Hz at 1000 ms, then one octave up with
+//! every halving, to 1953 Hz at 125 ms.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+use hal::time::Hertz;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::dma::{self, Controller, Mode, Transfer};
+use nucleo_g474re::sai::{sine, I2s, I2sData};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the running DMA transfer from a tone table to SAI1
+type ToneTransfer = Transfer<(I2sData, &'static [u32])>;
+
+// Exact from the 16 MHz HSI.
+const SAMPLE_RATE: Hertz = Hertz(31_250);
+// About a quarter of full scale, loud enough on a small speaker.
+const AMPLITUDE: i16 = 8000;
+
+// One period per table, two words (left and right) per frame.
+static TONE_244HZ: [u32; 256] = sine(AMPLITUDE);
+static TONE_488HZ: [u32; 128] = sine(AMPLITUDE);
+static TONE_977HZ: [u32; 64] = sine(AMPLITUDE);
+static TONE_1953HZ: [u32; 32] = sine(AMPLITUDE);
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the I2S output that I'm going to pass around.
+static G_I2S: Mutex<RefCell<Option<I2s>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the DMA transfer that I'm going to pass around.
+static G_TRANSFER: Mutex<RefCell<Option<ToneTransfer>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Tone table for a blink delay: one octave per halving.
+fn tone(delayms: u32) -> &'static [u32] {
+    match delayms {
+        500.. => &TONE_244HZ,
+        250..=499 => &TONE_488HZ,
+        125..=249 => &TONE_977HZ,
+        _ => &TONE_1953HZ,
+    }
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) SAI1 block A as I2S master, muted until the LED turns on.
+    let pins = (gpioa.pa8.into_alternate(), gpioa.pa9.into_alternate(), gpioa.pa10.into_alternate());
+    let (mut i2s, data) = I2s::new(dp.SAI, pins, rcc.clocks.sys_clk, SAMPLE_RATE);
+    i2s.mute();
+    defmt::info!("I2S at {} Hz", i2s.sample_rate().0);
+
+    // 2) Circular DMA transfer from the tone table to SAI1, then the clocks.
+    let channel = dma::claim(Controller::Dma1).expect("no free DMA channel");
+    let transfer = channel.write(data, tone(1000), Mode::Circular);
+    i2s.enable();
+
+    // 3) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_I2S.borrow(cs).replace(Some(i2s));
+        G_TRANSFER.borrow(cs).replace(Some(transfer));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+
+        // Swap the table feeding SAI1 for the tone of the new delay.
+        let mut transfer = G_TRANSFER.borrow(cs).borrow_mut();
+        let (channel, (data, _table)) = transfer.take().unwrap().stop();
+        transfer.replace(channel.write(data, tone(delayms), Mode::Circular));
+        defmt::info!("{} ms, {} Hz", delayms, SAMPLE_RATE.0 * 2 / tone(delayms).len() as u32);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        let led = led.as_mut().unwrap();
+        led.toggle().ok();
+
+        // Beep while the LED is on.
+        let mut i2s = G_I2S.borrow(cs).borrow_mut();
+        let i2s = i2s.as_mut().unwrap();
+        if led.is_set_high().unwrap() {
+            i2s.unmute();
+        } else {
+            i2s.mute();
+        }
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
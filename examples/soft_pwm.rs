@@ -0,0 +1,219 @@
+//! example: software PWM waves on an 8-LED bar, with its interrupt budget.
+//!
+//! Eight LEDs on PC0 to PC7, each through a resistor (330 ohm or so) to GND,
+//! as for the `led_bar` example: none of them needs a timer channel. TIM7
+//! steps an 8-bit software PWM on them, 256 steps a period, and TIM6 moves
+//! a triangle wave of brightness along the bar 50 times a second, gamma
+//! corrected so it ramps evenly to the eye. The User Button (PC13) switches
+//! the carrier between 100, 200 and 50 Hz.
+//!
+//! Once a second the log gives the worst case of the TIM7 handler, timed by
+//! the DWT cycle counter, against its budget, the cycles between two steps:
+//! at 16 MHz, 625 for 100 Hz and 312 for 200 Hz, where the overruns show.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioc};
+use hal::syscfg::SysCfgExt;
+use hal::time::Hertz;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::gamma::Gamma28;
+use nucleo_g474re::profile::{self, Budget};
+use nucleo_g474re::compat::Compat;
+use nucleo_g474re::softpwm::{self, SoftPwm};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{TIM6, TIM7};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the LED bar on port C
+type Bar = SoftPwm<Compat<gpioc::PC<Output<PushPull>>>, 8>;
+
+// Rate of the wave, and its frames per log.
+const FRAME_HZ: u32 = 50;
+// The carriers the button steps through.
+const CARRIERS: [u32; 3] = [100, 200, 50];
+
+// Create a Global Variable for the LED bar that I'm going to pass around.
+static G_BAR: Mutex<RefCell<Option<Bar>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the PWM Timer Peripheral that I'm going to pass around.
+static G_PWM_TIM: Mutex<RefCell<Option<CountDownTimer<TIM7>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the wave Timer Peripheral that I'm going to pass around.
+static G_FRAME_TIM: Mutex<RefCell<Option<CountDownTimer<TIM6>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the budget of the PWM handler.
+static G_BUDGET: Mutex<RefCell<Budget>> = Mutex::new(RefCell::new(Budget::new(0)));
+// Create a Global Variable for the carrier picked, and the core clock.
+static G_CARRIER: Mutex<Cell<usize>> = Mutex::new(Cell::new(0));
+static G_SYSCLK: Mutex<Cell<Hertz>> = Mutex::new(Cell::new(Hertz(0)));
+// Create a Global Variable for the wave frames, for the log.
+static G_FRAMES: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Runs the PWM timer for the carrier picked, with a fresh budget.
+fn set_carrier(cs: &cortex_m::interrupt::CriticalSection, index: usize) {
+    let carrier = Hertz(CARRIERS[index]);
+    G_CARRIER.borrow(cs).set(index);
+    let budget = softpwm::budget(carrier, G_SYSCLK.borrow(cs).get()).unwrap();
+    let mut stats = G_BUDGET.borrow(cs).borrow_mut();
+    stats.set_budget(budget);
+    stats.reset();
+    let mut timer = G_PWM_TIM.borrow(cs).borrow_mut();
+    timer.as_mut().unwrap().start(softpwm::tick_rate(carrier));
+    defmt::info!("Carrier: {} Hz, budget {} cycles", carrier.0, budget);
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) Cycle counter, to time the PWM handler.
+    profile::start(&mut cp.DCB, &mut cp.DWT);
+
+    // 2) The bar, all off.
+    let bar = SoftPwm::new([
+        gpioc.pc0.into_push_pull_output().downgrade(),
+        gpioc.pc1.into_push_pull_output().downgrade(),
+        gpioc.pc2.into_push_pull_output().downgrade(),
+        gpioc.pc3.into_push_pull_output().downgrade(),
+        gpioc.pc4.into_push_pull_output().downgrade(),
+        gpioc.pc5.into_push_pull_output().downgrade(),
+        gpioc.pc6.into_push_pull_output().downgrade(),
+        gpioc.pc7.into_push_pull_output().downgrade(),
+    ]
+    .map(Compat));
+
+    // 3) PWM timer, started at the first carrier by `set_carrier`.
+    let pwm_timer = Timer::new(dp.TIM7, &rcc.clocks);
+    let mut pwm_timer = pwm_timer.start_count_down(softpwm::tick_rate(Hertz(CARRIERS[0])));
+    pwm_timer.listen(Event::TimeOut);
+
+    // 4) Wave timer.
+    let frame_timer = Timer::new(dp.TIM6, &rcc.clocks);
+    let mut frame_timer = frame_timer.start_count_down(FRAME_HZ.hz());
+    frame_timer.listen(Event::TimeOut);
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_BAR.borrow(cs).replace(Some(bar));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_PWM_TIM.borrow(cs).replace(Some(pwm_timer));
+        G_FRAME_TIM.borrow(cs).replace(Some(frame_timer));
+        G_SYSCLK.borrow(cs).set(rcc.clocks.sys_clk);
+        set_carrier(cs, 0);
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM7);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM6_DACUNDER);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Carrier Global Data and Adjust Carrier
+        let next = (G_CARRIER.borrow(cs).get() + 1) % CARRIERS.len();
+        set_carrier(cs, next);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// PWM Timer Interrupt: one step of the period, timed.
+#[interrupt]
+fn TIM7() {
+    cortex_m::interrupt::free(|cs| {
+        let mut budget = G_BUDGET.borrow(cs).borrow_mut();
+        let start = budget.begin();
+
+        let mut bar = G_BAR.borrow(cs).borrow_mut();
+        bar.as_mut().unwrap().tick();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_PWM_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+
+        budget.end(start);
+    });
+}
+
+// Wave Timer Interrupt: the next frame of the wave, and the log once a second.
+#[interrupt]
+fn TIM6_DACUNDER() {
+    cortex_m::interrupt::free(|cs| {
+        let frames = G_FRAMES.borrow(cs).get().wrapping_add(1);
+        G_FRAMES.borrow(cs).set(frames);
+
+        // A triangle of brightness, a period every 64 frames, along the bar.
+        let mut duties = [0_u8; 8];
+        for (led, duty) in duties.iter_mut().enumerate() {
+            let phase = (frames * 4 + led as u32 * 32) as u8;
+            *duty = Gamma28::correct8(if phase < 128 { phase * 2 } else { (255 - phase) * 2 });
+        }
+        let mut bar = G_BAR.borrow(cs).borrow_mut();
+        bar.as_mut().unwrap().set_duties(duties);
+
+        if frames.is_multiple_of(FRAME_HZ) {
+            let budget = G_BUDGET.borrow(cs).borrow();
+            defmt::info!(
+                "PWM handler: worst {} of {} cycles ({}%), {} overruns",
+                budget.worst(),
+                budget.budget(),
+                budget.worst_percent(),
+                budget.overruns()
+            );
+        }
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_FRAME_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
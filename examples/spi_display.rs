@@ -0,0 +1,211 @@
+//! example: interrupt blink with its state on an SSD1306 OLED display.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay) with a 128x64 SSD1306 module on SPI1.
+//! The interrupts only update the shared state and raise a redraw flag: on a
+//! button press, and every second from the SysTick millisecond counter. The
+//! main loop, outside any interrupt, renders the delay, the uptime and the
+//! button count and sends the frame, then sleeps until the next interrupt.
+//!
+//! Wiring: SCK on D3 (PB3), MOSI (often labelled SDA or DIN) on D4 (PB5),
+//! CS on D10 (PB6), DC on D9 (PC7), RES on D8 (PA9), plus 3.3 V and GND.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+use hal::delay::SYSTDelayExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::compat::{Compat, ExclusiveDevice};
+use nucleo_g474re::spi::{spi1, Ssd1306};
+
+use cortex_m_rt::{entry, exception};
+
+use cortex_m::peripheral::syst::SystClkSource;
+
+use core::fmt::Write;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+// Create a Global Variable for the milliseconds elapsed since boot.
+static G_MILLIS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+// Create a Global Variable for the button presses since boot.
+static G_PRESSES: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+// Create a Global Variable telling the main loop that the screen is out of date.
+static G_REDRAW: Mutex<Cell<bool>> = Mutex::new(Cell::new(true));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) Display on SPI1, reset with a SysTick delay before SysTick starts counting.
+    let sck = gpiob.pb3.into_alternate();
+    let mosi = gpiob.pb5.into_alternate();
+    let cs = Compat(gpiob.pb6.into_push_pull_output());
+    let dc = Compat(gpioc.pc7.into_push_pull_output());
+    let mut rst = Compat(gpioa.pa9.into_push_pull_output());
+    let spi = ExclusiveDevice::new(Compat(spi1(dp.SPI1, sck, mosi, &mut rcc)), cs);
+    let mut display = Ssd1306::new(spi, dc);
+
+    let mut delay = Compat(cp.SYST.delay(&rcc.clocks));
+    display.reset(&mut rst, &mut delay);
+    display.init().expect("cannot initialise the display");
+
+    // 2) SysTick interrupt every millisecond for the uptime.
+    let mut syst = delay.into_inner().free();
+    syst.set_clock_source(SystClkSource::Core);
+    syst.set_reload(rcc.clocks.sys_clk.0 / 1000 - 1);
+    syst.clear_current();
+    syst.enable_counter();
+    syst.enable_interrupt();
+
+    // 3) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        // Take a snapshot of the state, so the interrupts are only held off for the copy.
+        let snapshot = cortex_m::interrupt::free(|cs| {
+            if G_REDRAW.borrow(cs).replace(false) {
+                Some((G_DELAYMS.borrow(cs).get(), G_MILLIS.borrow(cs).get() / 1000, G_PRESSES.borrow(cs).get()))
+            } else {
+                None
+            }
+        });
+
+        match snapshot {
+            // Rendering and the 1 ms transfer run with the interrupts enabled.
+            Some((delayms, seconds, presses)) => {
+                display.clear();
+                writeln!(display, "Interrupt blink\n").ok();
+                writeln!(display, "Delay:   {} ms", delayms).ok();
+                writeln!(display, "Uptime:  {:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60).ok();
+                write!(display, "Presses: {}", presses).ok();
+                if display.flush().is_err() {
+                    defmt::warn!("Display update failed");
+                }
+            }
+            // An event raised after the snapshot still wakes the core: SysTick fires every millisecond.
+            None => cortex_m::asm::wfi(),
+        }
+    }
+}
+
+
+#[exception]
+fn SysTick() {
+    cortex_m::interrupt::free(|cs| {
+        let millis = G_MILLIS.borrow(cs);
+        millis.set(millis.get().wrapping_add(1));
+        // Refresh the uptime once per second.
+        if millis.get().is_multiple_of(1000) {
+            G_REDRAW.borrow(cs).set(true);
+        }
+    });
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+
+        let presses = G_PRESSES.borrow(cs);
+        presses.set(presses.get() + 1);
+        G_REDRAW.borrow(cs).set(true);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,185 @@
+//! example: Stop 1 between button presses.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), at 170 MHz, but only for five blinks
+//! after each press. Once they are done the main loop puts the chip in Stop
+//! 1: every clock stops, TIM2 with them, and only the button's EXTI line
+//! can wake it. On the next press the chip resumes right after the stop,
+//! from the 16 MHz HSI; the main loop brings the PLL back with
+//! `clocks::set_sysclk` and starts the next five blinks, at the new delay.
+//!
+//! The debug port is kept alive in Stop so RTT keeps logging; without a
+//! debugger, call `power::debug_in_low_power(false)` to see the real draw.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::clocks::{self, Sysclk};
+use nucleo_g474re::power::{self, StopMode};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// LED toggles after each wakeup: five blinks.
+const TOGGLES: u32 = 10;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the toggles left before stopping.
+static G_TOGGLES: Mutex<Cell<u32>> = Mutex::new(Cell::new(TOGGLES));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = clocks::freeze_170mhz(dp.RCC.constrain());
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+    power::debug_in_low_power(true);
+
+    // 1) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // 2) The button, also the only wakeup source from Stop 1.
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        // Sleep until the next interrupt while blinking. Checked with the
+        // interrupts masked, so the last toggle cannot slip in between: a
+        // pending interrupt still ends `wfi`.
+        let done = cortex_m::interrupt::free(|cs| {
+            let done = G_TOGGLES.borrow(cs).get() == 0;
+            if !done {
+                cortex_m::asm::wfi();
+            }
+            done
+        });
+        if !done {
+            continue;
+        }
+
+        // 3) Blinks done: LED off and Stop 1 until the button.
+        cortex_m::interrupt::free(|cs| {
+            G_LED.borrow(cs).borrow_mut().as_mut().unwrap().set_low().ok();
+        });
+        defmt::info!("Stop 1 until the button");
+        power::enter_stop(StopMode::Stop1, &mut cp.SCB);
+
+        // 4) Woken by the button, whose interrupt already ran: the PLL back,
+        // then five more blinks at the delay it set.
+        let clocks = clocks::set_sysclk(Sysclk::Pll170);
+        cortex_m::interrupt::free(|cs| {
+            let delayms = G_DELAYMS.borrow(cs).get();
+            defmt::info!("Awake at {} Hz, blinking every {} ms", clocks.sys_clk.0, delayms);
+            G_TOGGLES.borrow(cs).set(TOGGLES);
+            let mut timer = G_TIM.borrow(cs).borrow_mut();
+            timer.as_mut().unwrap().start(delayms.ms());
+        });
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        // Still blinking: the new delay at once. After a wakeup, the main
+        // loop starts the timer again once the PLL is back.
+        if G_TOGGLES.borrow(cs).get() > 0 {
+            let delayms = G_DELAYMS.borrow(cs).get();
+            let mut timer = G_TIM.borrow(cs).borrow_mut();
+            timer.as_mut().unwrap().start(delayms.ms());
+            defmt::info!("Delay Atual: {} ms", delayms);
+        }
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let toggles = G_TOGGLES.borrow(cs);
+        if toggles.get() > 0 {
+            let mut led = G_LED.borrow(cs).borrow_mut();
+            led.as_mut().unwrap().toggle().ok();
+            toggles.set(toggles.get() - 1);
+        }
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
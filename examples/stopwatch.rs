@@ -0,0 +1,151 @@
+//! example: a stopwatch on the buttons, logging lap times.
+//!
+//! The User Button (PC13) starts the stopwatch, then ends a lap at every
+//! press; a second button, from A0 (PA0) to GND, stops it and logs the
+//! total and the best of the last laps. Each handler takes its timestamp
+//! from the SysTick monotonic clock first thing, so the times are good to
+//! the microsecond whatever the logging costs. The LED on PA5 is lit while
+//! the stopwatch runs.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PullUp, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::monotonic;
+use nucleo_g474re::stopwatch::Stopwatch;
+
+use cortex_m_rt::{entry, exception};
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use core::cell::RefCell;
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the stop button pin
+type StopPin = gpioa::PA0<Input<PullUp>>;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the stop Button GPIO Peripheral that I'm going to pass around.
+static G_STOP: Mutex<RefCell<Option<StopPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the stopwatch, keeping the last 8 laps.
+static G_STOPWATCH: Mutex<RefCell<Stopwatch<8>>> = Mutex::new(RefCell::new(Stopwatch::new()));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) Monotonic clock on SysTick.
+    monotonic::start(&mut cp.SYST, rcc.clocks.core_clk);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    // 2) Stop button, pressed low.
+    let mut stop = gpioa.pa0.into_pull_up_input();
+    stop.make_interrupt_source(&mut syscfg);
+    stop.trigger_on_edge(&mut dp.EXTI, SignalEdge::Falling);
+    stop.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_STOP.borrow(cs).replace(Some(stop));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI0);
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// SysTick: one millisecond of the monotonic clock.
+#[exception]
+fn SysTick() {
+    monotonic::tick();
+}
+
+
+// User Button: start, or a lap.
+#[interrupt]
+fn EXTI15_10() {
+    let now = monotonic::now();
+    cortex_m::interrupt::free(|cs| {
+        let mut stopwatch = G_STOPWATCH.borrow(cs).borrow_mut();
+        if let Some(lap) = stopwatch.lap(now) {
+            defmt::info!("Lap {}: {} us (total {} us)", lap.number, lap.split_us, lap.total_us);
+        } else {
+            stopwatch.start(now);
+            G_LED.borrow(cs).borrow_mut().as_mut().unwrap().set_high().ok();
+            defmt::info!("Started at {} us", now.micros());
+        }
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Stop button: the total, and the best lap. Its bounces find the
+// stopwatch stopped already.
+#[interrupt]
+fn EXTI0() {
+    let now = monotonic::now();
+    cortex_m::interrupt::free(|cs| {
+        let mut stopwatch = G_STOPWATCH.borrow(cs).borrow_mut();
+        if let Some(total) = stopwatch.stop(now) {
+            G_LED.borrow(cs).borrow_mut().as_mut().unwrap().set_low().ok();
+            defmt::info!("Stopped: {} us, {} laps", total, stopwatch.laps());
+            if let Some((number, split)) = stopwatch.best() {
+                defmt::info!("Best lap: {} in {} us", number, split);
+            }
+        }
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut stop = G_STOP.borrow(cs).borrow_mut();
+        stop.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
@@ -0,0 +1,181 @@
+//! example: the blink guarded against a sagging supply.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), with the supply watched at two levels.
+//! At boot the brown-out reset level goes to `BOR_LEVEL`, about 2.2 V, in
+//! the option bytes, the chip reloading them once if they had another; the
+//! PVD then watches for VDD dipping under `PVD_LEVEL`, about 2.75 V. On a
+//! dip the LED goes off, the timer stops, and the chip parks until the
+//! supply comes back, to restart from reset:
+//!
+//! ```text
+//! Brown-out reset at Level2, PVD at Level5
+//! VDD under the PVD level: parked
+//! ```
+//!
+//! A bench supply on the E5V pin, the board's power jumper on E5V, turned
+//! down slowly until 3V3 sags shows it; so does a long, thin USB cable
+//! with a load.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::optionbytes::{self, BorLevel};
+use nucleo_g474re::supply::{self, Event as SupplyEvent, Pvd, PvdLevel};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// The brown-out reset level, kept in the option bytes.
+const BOR_LEVEL: BorLevel = BorLevel::Level2;
+// The PVD level, above it.
+const PVD_LEVEL: PvdLevel = PvdLevel::Level5;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the PVD that I'm going to pass around.
+static G_PVD: Mutex<RefCell<Option<Pvd>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) The brown-out reset level, reloaded once if it changed.
+    match supply::set_bor_level(&mut dp.FLASH, BOR_LEVEL) {
+        Ok(true) => {
+            defmt::info!("Brown-out reset level set to {}: reloading", BOR_LEVEL);
+            optionbytes::reload(dp.FLASH);
+        }
+        Ok(false) => {}
+        Err(error) => defmt::warn!("Brown-out reset level left at {}: {}", supply::bor_level(), error),
+    }
+
+    // 2) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    // 3) The PVD, once everything it parks is set up.
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_PVD.borrow(cs).replace(Some(Pvd::enable(PVD_LEVEL, &mut dp.EXTI)));
+    });
+    defmt::info!("Brown-out reset at {}, PVD at {}", supply::bor_level(), PVD_LEVEL);
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// PVD Interrupt
+#[interrupt]
+fn PVD_PVM() {
+    cortex_m::interrupt::free(|cs| {
+        let mut pvd = G_PVD.borrow(cs).borrow_mut();
+        if pvd.as_mut().unwrap().on_interrupt() != SupplyEvent::Low {
+            return;
+        }
+
+        // Outputs safe before parking: the LED off, the blink stopped.
+        G_LED.borrow(cs).borrow_mut().as_mut().unwrap().set_low().ok();
+        G_TIM.borrow(cs).borrow_mut().as_mut().unwrap().unlisten(Event::TimeOut);
+        supply::park();
+    });
+}
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+        defmt::info!("Delay Atual: {} ms", delayms);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
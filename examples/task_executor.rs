@@ -0,0 +1,195 @@
+//! example: the blink, a button, a sensor and a shell as tasks of one loop.
+//!
+//! No interrupt of the program's but the millisecond tick: the main loop
+//! wakes on each one and the `executor` polls its four tasks in turn, each
+//! owning its peripherals and answering at once:
+//!
+//! | Task     | Polls                             | Runs                                 |
+//! |----------|-----------------------------------|--------------------------------------|
+//! | `blink`  | its period                        | toggles the LED (PA5)                |
+//! | `button` | the User Button (PC13), debounced | halves the period, 1 s under 125 ms  |
+//! | `sensor` | its deadline, every `READ_MS`     | reads the temperature sensor on ADC1 |
+//! | `shell`  | USART2, the virtual COM port      | runs `period` and `temp`             |
+//!
+//! What they share, the period and the temperature, are `Cell`s of `main`
+//! they borrow: the tasks run one after the other, never preempted by each
+//! other. Every `REPORT_MS` the loop logs the counters of each task:
+//!
+//! ```text
+//! blink: 10 runs of 10000 polls
+//! button: 2 runs of 10000 polls
+//! ```
+//!
+//! Built with the `tick-tim6` feature the tick comes from TIM6 instead of
+//! SysTick.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::adc::{config::SampleTime, AdcClaim, ClockSource, Temperature};
+use hal::delay::DelayFromCountDownTimer;
+use hal::serial::FullConfig;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::debounce::{Debouncer, Event as DebounceEvent, Timeout};
+use nucleo_g474re::executor::Executor;
+use nucleo_g474re::shell::{Input as ShellInput, Shell};
+use nucleo_g474re::tick::{self, TickSource};
+
+use cortex_m_rt::entry;
+
+use core::fmt::Write;
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use core::cell::Cell;
+
+use hal::timer::Timer;
+
+nucleo_g474re::tick_handler!();
+
+// Debounce window of the button.
+const DEBOUNCE_MS: u32 = 20;
+// Period of the temperature reads.
+const READ_MS: u32 = 1000;
+// Period of the task report.
+const REPORT_MS: u32 = 10_000;
+
+const HELP: &str = "commands:\r\n  period\r\n  temp\r\n";
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    #[cfg(not(feature = "tick-tim6"))]
+    let cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) The millisecond tick, waking the loop.
+    #[cfg(not(feature = "tick-tim6"))]
+    let mut source = tick::Source::new(cp.SYST);
+    #[cfg(feature = "tick-tim6")]
+    let mut source = tick::Source::new(dp.TIM6);
+    source.start(&rcc.clocks);
+
+    // 2) The peripherals of the tasks: the LED, the button, ADC1 on the
+    //    temperature sensor, TIM3 timing its start-up, and USART2.
+    let mut led = gpioa.pa5.into_push_pull_output();
+    let button = gpioc.pc13.into_floating_input();
+
+    let mut delay = DelayFromCountDownTimer::new(Timer::new(dp.TIM3, &rcc.clocks).start_count_down(100.ms()));
+    let mut adc = dp.ADC1.claim(ClockSource::SystemClock, &rcc, &mut delay, true);
+    adc.enable_temperature(&dp.ADC12_COMMON);
+    delay.delay_us(20_u32);
+
+    let tx = gpioa.pa2.into_alternate();
+    let rx = gpioa.pa3.into_alternate();
+    let mut serial = dp
+        .USART2
+        .usart(tx, rx, FullConfig::default().baudrate(115_200.bps()), &mut rcc)
+        .expect("cannot configure USART2");
+    serial.write_str("\r\nTask shell, 'help' for the commands\r\n> ").ok();
+
+    // What the tasks share.
+    let period = Cell::new(1000_u32);
+    let celsius = Cell::new(0_i16);
+    let (period, celsius) = (&period, &celsius);
+
+    // 3) The tasks.
+    let mut toggled = 0;
+    let mut blink = move |now: u32| {
+        if now.wrapping_sub(toggled) < period.get() {
+            return false;
+        }
+        toggled = now;
+        led.toggle().ok();
+        true
+    };
+
+    let mut debouncer = Timeout::new(DEBOUNCE_MS);
+    let mut press = move |now: u32| {
+        let pressed = button.is_high().unwrap_or(false);
+        if debouncer.update(pressed, now) != Some(DebounceEvent::Pressed) {
+            return false;
+        }
+        let delayms = match period.get() / 2 {
+            delayms if delayms < 125 => 1000,
+            delayms => delayms,
+        };
+        period.set(delayms);
+        defmt::info!("Delay Atual: {} ms", delayms);
+        true
+    };
+
+    let mut read = 0;
+    let mut sensor = move |now: u32| {
+        if now.wrapping_sub(read) < READ_MS {
+            return false;
+        }
+        read = now;
+        let sample = adc.convert(&Temperature, SampleTime::Cycles_640_5);
+        celsius.set(Temperature::temperature_to_degrees_centigrade(sample) as i16);
+        true
+    };
+
+    let mut shell = Shell::new();
+    let mut console = move |_now: u32| {
+        let mut ran = false;
+        while let Ok(byte) = serial.read() {
+            ran = true;
+            let input = shell.push(byte);
+            shell.echo(&mut serial, input).ok();
+            if input != ShellInput::Line {
+                continue;
+            }
+            match shell.line().unwrap_or("").trim() {
+                "" => {}
+                "period" => {
+                    writeln!(serial, "period: {} ms\r", period.get()).ok();
+                }
+                "temp" => {
+                    writeln!(serial, "temperature: {} C\r", celsius.get()).ok();
+                }
+                _ => {
+                    serial.write_str(HELP).ok();
+                }
+            }
+            shell.prompt(&mut serial).ok();
+        }
+        ran
+    };
+
+    let mut executor = Executor::<4>::new();
+    executor.spawn("blink", &mut blink).ok();
+    executor.spawn("button", &mut press).ok();
+    executor.spawn("sensor", &mut sensor).ok();
+    executor.spawn("shell", &mut console).ok();
+
+    let mut reported = tick::now_ms();
+    loop {
+        cortex_m::asm::wfi();
+        executor.run(tick::now_ms());
+
+        // 4) The counters of the tasks, now and then.
+        if tick::elapsed_ms(reported) >= REPORT_MS {
+            reported = tick::now_ms();
+            for task in executor.tasks() {
+                defmt::info!("{}: {} runs of {} polls", task.name, task.runs, task.polls);
+            }
+        }
+    }
+}
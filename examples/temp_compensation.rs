@@ -0,0 +1,200 @@
+//! example: the blink period corrected for the HSI's drift with temperature.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), clocked from the HSI16 as it is, with
+//! no crystal. The RC oscillator is trimmed at the factory at 30 °C and
+//! drifts away from it, so the timer's 1 s is not quite a second; every 2 s
+//! the main loop reads the core's temperature sensor, looks the HSI's error
+//! up on `tempcomp::TYPICAL`, and stretches or shortens the TIM2
+//! auto-reload by it:
+//!
+//! ```text
+//! Temperature 41 °C: HSI 550 ppm, ARR 65306 -> 65342
+//! ```
+//!
+//! The correction is logged each time it changes. Warming the chip with a
+//! finger, or a hair dryer, moves it; `TYPICAL` is a rough curve, and one
+//! measured on the board (against a frequency counter on MCO, `mco_shell`)
+//! takes its place in `CURVE`.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::adc::{config::SampleTime, AdcClaim, ClockSource, Temperature, Vref};
+use hal::delay::SYSTDelayExt;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::signature::VrefCal;
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::tempcomp::{corrected, Curve, TYPICAL};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// The HSI's error against the temperature.
+const CURVE: Curve = TYPICAL;
+// Period of the temperature readings.
+const READ_MS: u32 = 2000;
+// Factory calibration voltage of VREFINT_CAL and TS_CAL, in millivolts.
+const CAL_MV: u32 = 3000;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+// Create a Global Variable for the HSI's error at the last reading, in ppm.
+static G_PPM: Mutex<Cell<i32>> = Mutex::new(Cell::new(0));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Restarts the blink timer on the current delay, its auto-reload corrected
+// by the HSI's error; returns the auto-reload before and after.
+fn restart(cs: &cortex_m::interrupt::CriticalSection) -> (u32, u32) {
+    let delayms = G_DELAYMS.borrow(cs).get();
+    let mut timer = G_TIM.borrow(cs).borrow_mut();
+    timer.as_mut().unwrap().start(delayms.ms());
+
+    // The period is ARR + 1 ticks; with no preload the new value counts from
+    // the current period on.
+    let tim = unsafe { &*TIM2::ptr() };
+    let arr = tim.arr.read().bits();
+    let trimmed = corrected(arr + 1, G_PPM.borrow(cs).get()) - 1;
+    tim.arr.write(|w| unsafe { w.bits(trimmed) });
+    (arr, trimmed)
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    // 2) ADC1 on the temperature sensor, and Vrefint for VDDA, as the
+    //    sensor's calibration was taken at 3.0 V.
+    let mut delay = cp.SYST.delay(&rcc.clocks);
+    let mut adc = dp.ADC1.claim(ClockSource::SystemClock, &rcc, &mut delay, true);
+    adc.enable_vref(&dp.ADC12_COMMON);
+    adc.enable_temperature(&dp.ADC12_COMMON);
+    delay.delay_us(20_u32);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+    }
+
+    loop {
+        // 3) The temperature, its sample scaled to the 3.0 V of the calibration.
+        let vref = adc.convert(&Vref, SampleTime::Cycles_640_5) as u32;
+        let vdda_mv = CAL_MV * VrefCal::get().read() as u32 / vref.max(1);
+        let sample = adc.convert(&Temperature, SampleTime::Cycles_640_5) as u32;
+        let sample = (sample * vdda_mv / CAL_MV) as u16;
+        let celsius = Temperature::temperature_to_degrees_centigrade(sample) as i16;
+
+        // A new correction starts the period in progress again: an ARR
+        // written under the count would have TIM2 count on to 2^32.
+        let ppm = CURVE.ppm_at(celsius);
+        let changed = cortex_m::interrupt::free(|cs| {
+            if G_PPM.borrow(cs).replace(ppm) == ppm {
+                return None;
+            }
+            Some(restart(cs))
+        });
+        if let Some((arr, trimmed)) = changed {
+            defmt::info!("Temperature {} °C: HSI {} ppm, ARR {} -> {}", celsius, ppm, arr, trimmed);
+        }
+
+        delay.delay_ms(READ_MS);
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        restart(cs);
+        defmt::info!("Delay Atual: {} ms", G_DELAYMS.borrow(cs).get());
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,202 @@
+//! example: complementary PWM with dead-time and a break input on TIM1.
+//!
+//! The blink of the main program (TIM2 toggles the LED on PA5, the User
+//! Button on PC13 halves the delay), with TIM1 driving a half bridge at
+//! 20 kHz: CH1 on PA8 (D7) and CH1N on PA7 (D11), 1 us of dead-time between
+//! them. Watch both pins on a scope: the TIM1 update interrupt, every
+//! millisecond, sweeps the duty of CH1 up while the LED is on and down while
+//! it is off, and CH1N follows as its complement.
+//!
+//! PA6 (D12) is the break input, pulled up. Connecting it to GND turns both
+//! outputs off in hardware; they come back once it is released, and the
+//! update interrupt logs both transitions.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, Speed, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+use hal::time::Hertz;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::pwm::{ComplementaryPwm, Event as PwmEvent, Rearm};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::{TIM1, TIM2};
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+const PWM_FREQUENCY: Hertz = Hertz(20_000);
+const DEAD_TIME_NS: u32 = 1000;
+// One update event per millisecond.
+const REPETITIONS: u16 = 20 - 1;
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the complementary PWM that I'm going to pass around.
+static G_PWM: Mutex<RefCell<Option<ComplementaryPwm<TIM1>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the delay of the timer.
+static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000_u32));
+// Create a Global Variable for the milliseconds since the LED turned on.
+static G_PHASE: Mutex<Cell<u32>> = Mutex::new(Cell::new(0_u32));
+// Create a Global Variable for the state of the outputs at the last update.
+static G_ENABLED: Mutex<Cell<bool>> = Mutex::new(Cell::new(true));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) TIM1 CH1/CH1N, with the break input on PA6 (the pull-up stays on in alternate mode).
+    let pins = (
+        gpioa.pa8.into_alternate().set_speed(Speed::VeryHigh),
+        gpioa.pa7.into_alternate().set_speed(Speed::VeryHigh),
+    );
+    let break_pin = gpioa.pa6.into_pull_up_input().into_alternate();
+    let mut pwm = ComplementaryPwm::new(dp.TIM1, pins, rcc.clocks.apb2_tim_clk, PWM_FREQUENCY, DEAD_TIME_NS);
+    pwm.break_on_pin(break_pin);
+    pwm.enable_break(Rearm::Automatic);
+    pwm.set_repetitions(REPETITIONS);
+    pwm.listen(PwmEvent::Update);
+    pwm.enable();
+    defmt::info!("PWM at {} Hz, dead-time {} ns", pwm.frequency().0, pwm.dead_time_ns());
+
+    // 2) Blink timer, as in the main program.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_PWM.borrow(cs).replace(Some(pwm));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM1_UP_TIM16);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// TIM1 update interrupt: one step of the duty sweep.
+#[interrupt]
+fn TIM1_UP_TIM16() {
+    cortex_m::interrupt::free(|cs| {
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let phase = (G_PHASE.borrow(cs).get() + 1).min(2 * delayms);
+        G_PHASE.borrow(cs).set(phase);
+
+        let mut pwm = G_PWM.borrow(cs).borrow_mut();
+        let pwm = pwm.as_mut().unwrap();
+
+        // Up while the LED is on, down while it is off.
+        let ramp = if phase < delayms { phase } else { 2 * delayms - phase };
+        let duty = pwm.max_duty() as u32 * ramp / delayms;
+        pwm.set_duty(duty as u16);
+
+        // Report the break input turning the outputs off and back on.
+        let enabled = pwm.is_enabled();
+        if enabled != G_ENABLED.borrow(cs).replace(enabled) {
+            if enabled {
+                defmt::info!("Break released, outputs on");
+            } else {
+                defmt::warn!("Break, outputs off");
+            }
+        }
+
+        pwm.clear_interrupt(PwmEvent::Update);
+    });
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        // Obtain Access to Delay Global Data and Adjust Delay
+        G_DELAYMS
+            .borrow(cs)
+            .set(G_DELAYMS.borrow(cs).get()/2);
+
+        if G_DELAYMS.borrow(cs).get() < 125_u32 {
+            G_DELAYMS.borrow(cs).set(1000_u32);
+        }
+
+        let delayms = G_DELAYMS.borrow(cs).get();
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().start(delayms.ms());
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        let led = led.as_mut().unwrap();
+        led.toggle().ok();
+
+        // Keep the sweep in step with the LED.
+        if led.is_set_high().unwrap() {
+            G_PHASE.borrow(cs).set(0);
+        }
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,146 @@
+//! example: the TIM2 timeout handler swapped at run time, one ISR for all.
+//!
+//! `timer_handlers!` defines the TIM2 vector; what a timeout does is the
+//! handler registered with `irq::on_timeout`. The User Button on PC13, on
+//! the EXTI dispatch, moves to the next handler of [`PATTERNS`]:
+//!
+//! | Pattern   | Each 250 ms timeout                      |
+//! |-----------|------------------------------------------|
+//! | `blink`   | toggles the LED on PA5                   |
+//! | `flash`   | the LED on for one timeout out of four   |
+//! | `off`     | the LED off, a log line every 4 timeouts |
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::board::{ButtonPin, LedPin};
+use nucleo_g474re::button::{Button, Event as ButtonEvent};
+use nucleo_g474re::compat::Compat;
+use nucleo_g474re::exti;
+use nucleo_g474re::irq::{self, Handler, TimerId};
+use nucleo_g474re::led::Led;
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::timer::{Timer, Event};
+
+nucleo_g474re::exti_handlers!();
+nucleo_g474re::timer_handlers!(TIM2);
+
+// The timeout handlers, in button order, with their names.
+const PATTERNS: [(Handler, &str); 3] = [(blink, "blink"), (flash, "flash"), (off, "off")];
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<Led<Compat<LedPin>>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<Button<Compat<ButtonPin>>>>> = Mutex::new(RefCell::new(None));
+// Create Global Variables for the current pattern and the timeouts counted.
+static G_PATTERN: Mutex<Cell<usize>> = Mutex::new(Cell::new(0));
+static G_TICKS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Counts a timeout and runs `f` with the count and the LED.
+fn tick(f: impl FnOnce(u32, &mut Led<Compat<LedPin>>)) {
+    cortex_m::interrupt::free(|cs| {
+        let ticks = G_TICKS.borrow(cs).get().wrapping_add(1);
+        G_TICKS.borrow(cs).set(ticks);
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        f(ticks, led.as_mut().unwrap());
+    });
+}
+
+fn blink() {
+    tick(|_, led| {
+        led.toggle().ok();
+    });
+}
+
+fn flash() {
+    tick(|ticks, led| {
+        if ticks.is_multiple_of(4) { led.on().ok() } else { led.off().ok() };
+    });
+}
+
+fn off() {
+    tick(|ticks, led| {
+        led.off().ok();
+        if ticks.is_multiple_of(4) {
+            defmt::info!("Timeout {}", ticks);
+        }
+    });
+}
+
+// B1: the next pattern, on a press; the line also triggers on releases.
+fn next(_line: u8) {
+    let pattern = cortex_m::interrupt::free(|cs| {
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        if button.as_mut().unwrap().event() != Some(ButtonEvent::Pressed) {
+            return None;
+        }
+        let pattern = (G_PATTERN.borrow(cs).get() + 1) % PATTERNS.len();
+        G_PATTERN.borrow(cs).set(pattern);
+        Some(pattern)
+    });
+    let Some(pattern) = pattern else {
+        return;
+    };
+    let (handler, name) = PATTERNS[pattern];
+    irq::on_timeout(TimerId::Tim2, handler);
+    defmt::info!("Pattern: {}", name);
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) TIM2 timing out every 250 ms, its handler the first pattern's.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(250.ms());
+    count_down_timer.listen(Event::TimeOut);
+    irq::on_timeout(TimerId::Tim2, PATTERNS[0].0);
+
+    let led = Led::new(Compat(gpioa.pa5.into_push_pull_output()));
+
+    // 2) B1 on the EXTI dispatch, which clears its pending bit.
+    let mut syscfg = dp.SYSCFG.constrain();
+    let button = Button::new(Compat(gpioc.pc13.into_floating_input()), &mut syscfg, &mut dp.EXTI);
+    exti::register(13, next).expect("line 13 taken");
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+    });
+
+    exti::unmask_all();
+    irq::unmask(TimerId::Tim2);
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
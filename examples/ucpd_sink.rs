@@ -0,0 +1,182 @@
+//! example: USB Power Delivery sink negotiating 5 V or 9 V.
+//!
+//! A USB-C receptacle breakout has CC1 wired to PB6, CC2 to PB4 and GND to the
+//! board; its VBUS goes to the load only, never to the Nucleo. When a PD
+//! charger is plugged in, UCPD1 logs the supplies it offers and requests 9 V
+//! at 1 A, or 5 V if the charger has no 9 V supply. Every step of the
+//! negotiation is logged, and the User Button (PC13) switches the request
+//! between 9 V and 5 V.
+//!
+//! The LED (PA5) shows the contract: slow blink on the default 5 V without a
+//! contract, faster at 5 V negotiated and fastest above 5 V.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, Output, PushPull, SignalEdge, gpioa, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::ucpd::{Config, Sink, SinkEvent};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use hal::stm32::TIM2;
+
+use core::cell::RefCell;
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+use hal::timer::{Timer, Event, CountDownTimer};
+
+// Alias for led pin
+type LedPin = gpioa::PA5<Output<PushPull>>;
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// The two requests the button switches between.
+const HIGH: Config = Config { max_millivolts: 9000, milliamps: 1000 };
+const LOW: Config = Config { max_millivolts: 5000, milliamps: 1000 };
+
+// Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
+static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the Timer Peripheral that I'm going to pass around.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the PD sink that I'm going to pass around.
+static G_SINK: Mutex<RefCell<Option<Sink>>> = Mutex::new(RefCell::new(None));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// Blink delay showing the contract.
+fn delay_for(sink: &Sink) -> u32 {
+    match sink.contract() {
+        None => 1000,
+        Some(contract) if contract.millivolts <= 5000 => 500,
+        Some(_) => 125,
+    }
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpiob = dp.GPIOB.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) UCPD1 as a sink on PB6 (CC1) and PB4 (CC2).
+    let cc1 = gpiob.pb6.into_analog();
+    let cc2 = gpiob.pb4.into_analog();
+    let mut sink = Sink::new(dp.UCPD1, cc1, cc2, HIGH);
+    sink.listen();
+
+    // 2) Blink timer showing the contract.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut count_down_timer = timer.start_count_down(1000.ms());
+    count_down_timer.listen(Event::TimeOut);
+
+    let led = gpioa.pa5.into_push_pull_output();
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_LED.borrow(cs).replace(Some(led));
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TIM.borrow(cs).replace(Some(count_down_timer));
+        G_SINK.borrow(cs).replace(Some(sink));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
+        cortex_m::peripheral::NVIC::unmask(interrupt::UCPD1);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn UCPD1() {
+    cortex_m::interrupt::free(|cs| {
+        let mut sink = G_SINK.borrow(cs).borrow_mut();
+        let sink = sink.as_mut().unwrap();
+
+        while let Some(event) = sink.handle() {
+            match event {
+                SinkEvent::Capabilities => {
+                    for (position, pdo) in sink.capabilities().enumerate() {
+                        defmt::info!("PD source supply {}: {}", position + 1, pdo);
+                    }
+                }
+                SinkEvent::Dropped => defmt::warn!("PD: message dropped"),
+                event => defmt::info!("PD: {}", event),
+            }
+
+            let delayms = delay_for(sink);
+            let mut timer = G_TIM.borrow(cs).borrow_mut();
+            timer.as_mut().unwrap().start(delayms.ms());
+        }
+    });
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        let mut sink = G_SINK.borrow(cs).borrow_mut();
+        let sink = sink.as_mut().unwrap();
+
+        // Switch between the two requests.
+        let config = match sink.contract() {
+            Some(contract) if contract.millivolts > LOW.max_millivolts => LOW,
+            _ => HIGH,
+        };
+        defmt::info!("PD: requesting up to {} mV", config.max_millivolts);
+        sink.set_config(config);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
+
+// Timer Interrupt
+#[interrupt]
+fn TIM2() {
+    cortex_m::interrupt::free(|cs| {
+        let mut led = G_LED.borrow(cs).borrow_mut();
+        led.as_mut().unwrap().toggle().ok();
+
+        // Obtain access to Global Timer Peripheral and Clear Interrupt Pending Flag
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        timer.as_mut().unwrap().clear_interrupt(Event::TimeOut);
+    });
+}
@@ -0,0 +1,136 @@
+//! example: a WS2812 strip cycling through colors.
+//!
+//! An 8-LED WS2812 strip with its data input on D12 (PA6), through a level
+//! shifter for a 5 V strip, and its ground to the board's. TIM3 clocks the
+//! bits out at 800 kHz and a DMA1 channel, claimed from the `dma` module,
+//! feeds their duties, so the CPU only encodes the colors. Each press of the
+//! User Button (PC13) fills the strip with the next color, at an eighth of
+//! full brightness to spare the USB supply.
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::gpio::{ExtiPin, Floating, Input, SignalEdge, gpioc};
+use hal::syscfg::SysCfgExt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::dma::{self, Controller, Mode, Transfer};
+use nucleo_g474re::ws2812::{self, Frame, Rgb, Ws2812};
+
+use cortex_m_rt::entry;
+
+use core::panic::PanicInfo;
+
+use defmt_rtt as _;
+
+use core::cell::{Cell, RefCell};
+
+use cortex_m::interrupt::Mutex;
+
+use hal::interrupt;
+
+// Number of LEDs on the strip.
+const LEDS: usize = 8;
+
+// Slots in the duties of one frame of the strip.
+const SLOTS: usize = ws2812::buffer_len(LEDS);
+
+// Alias for button pin
+type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+// Alias for the DMA transfer of a frame to the timer
+type StripTransfer = Transfer<(Ws2812, &'static mut [u16; SLOTS])>;
+
+// Colors stepped through by the button.
+const COLORS: [Rgb; 7] = [Rgb::RED, Rgb::GREEN, Rgb::BLUE, Rgb::YELLOW, Rgb::CYAN, Rgb::MAGENTA, Rgb::WHITE];
+
+// Brightness of the colors, out of 255.
+const BRIGHTNESS: u8 = 31;
+
+// Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
+static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the DMA transfer that I'm going to pass around.
+static G_TRANSFER: Mutex<RefCell<Option<StripTransfer>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the index into COLORS.
+static G_COLOR: Mutex<Cell<usize>> = Mutex::new(Cell::new(0));
+
+
+// Minimal panic handler for `no_std` embedded programs.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    loop {}
+}
+
+
+// The frame of the strip filled with COLORS[index].
+fn frame(index: usize) -> Frame<LEDS> {
+    let mut frame = Frame::new();
+    frame.fill(COLORS[index].scaled(BRIGHTNESS));
+    frame
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut rcc = dp.RCC.constrain();
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let gpioc = dp.GPIOC.split(&mut rcc);
+
+    // 1) TIM3 PWM on PA6, the line low until the first frame.
+    let strip = Ws2812::new(dp.TIM3, gpioa.pa6.into_alternate(), &rcc.clocks);
+    // 2) The first frame, encoded into the DMA buffer.
+    let buffer = cortex_m::singleton!(: [u16; SLOTS] = [0; SLOTS]).unwrap();
+    strip.encode(frame(0).colors(), buffer).unwrap();
+    // 3) One DMA transfer from the buffer to the timer sends it.
+    let channel = dma::claim(Controller::Dma1).expect("no free DMA channel");
+    let transfer = channel.write(strip, buffer, Mode::Once);
+    defmt::info!("{} LEDs: {}", LEDS, COLORS[0]);
+
+    // Configure Button Pin for Interrupts
+    let mut button = gpioc.pc13.into_floating_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    button.make_interrupt_source(&mut syscfg);
+    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    button.enable_interrupt(&mut dp.EXTI);
+
+    cortex_m::interrupt::free(|cs| {
+        G_BUTTON.borrow(cs).replace(Some(button));
+        G_TRANSFER.borrow(cs).replace(Some(transfer));
+    });
+
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+    }
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+#[interrupt]
+fn EXTI15_10() {
+    cortex_m::interrupt::free(|cs| {
+        let index = (G_COLOR.borrow(cs).get() + 1) % COLORS.len();
+        G_COLOR.borrow(cs).set(index);
+
+        // Let the last frame finish, at most half a millisecond, then send
+        // the next one from the same buffer.
+        let mut transfer = G_TRANSFER.borrow(cs).borrow_mut();
+        let last = transfer.take().unwrap();
+        last.wait().ok();
+        let (channel, (strip, buffer)) = last.stop();
+        strip.encode(frame(index).colors(), buffer).unwrap();
+        transfer.replace(channel.write(strip, buffer, Mode::Once));
+        defmt::info!("{} LEDs: {}", LEDS, COLORS[index]);
+
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        button.as_mut().unwrap().clear_interrupt_pending_bit();
+    });
+}
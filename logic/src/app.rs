@@ -0,0 +1,313 @@
+//! Operating modes of the blink application, and the events moving it
+//! between them.
+//!
+//! [`StateMachine`] holds the [`Mode`] and takes the events of the button,
+//! the timer and a shell, one at a time, through [`StateMachine::handle`].
+//! It changes no pin and no timer itself: it returns the [`Action`] the
+//! application carries out, and [`Mode::pattern`] is what the LED shows in
+//! each mode.
+//!
+//! | Mode     | `Press`          | `Hold`      | `Tick`                        | LED              |
+//! |----------|------------------|-------------|-------------------------------|------------------|
+//! | `Idle`   | to `Blink`       |             |                               | a short flash    |
+//! | `Blink`  | step the period  | to `Config` |                               | the blink period |
+//! | `Config` | next pattern     | to `Blink`  | to `Blink` after the timeout  | fast blink       |
+//! | `Fault`  |                  | to `Idle`   |                               | on               |
+//!
+//! In any mode `Fault` goes to `Fault`, and a shell's `Enter` to the mode
+//! it names. `Fault` is left only by asking: a `Hold` of the button or a
+//! `Clear`, both back to `Idle`.
+//!
+//! [`App`] is the whole application on an LED and a button: the machine,
+//! the blink settings, the hold timed in ticks, the LED patterns and the
+//! shell commands. The `app_modes` example runs it on the board's pins from
+//! its interrupts, the `sim` module on fakes, the same calls in both:
+//!
+//! ```text
+//! // an edge of the button
+//! app.edge(log);
+//! // every TICK_MS
+//! app.tick(log);
+//! // a line of the shell
+//! app.run(line, &mut serial, log);
+//! ```
+//!
+//! `log` is told each [`Action`] carried out, with the settings after it,
+//! for the caller's log.
+
+use core::fmt::Write;
+
+use embedded_hal::digital::{InputPin, OutputPin};
+
+use crate::config::{AppConfig, Pattern as BlinkPattern};
+use crate::leds::{Leds, Pattern};
+
+/// The tick of the application, TIM2's in `app_modes`.
+pub const TICK_MS: u32 = 50;
+/// Ticks the button is held down for a [`Event::Hold`].
+pub const HOLD_TICKS: u32 = 500 / TICK_MS;
+/// Ticks of Config with no press before it goes back to Blink.
+pub const CONFIG_TIMEOUT_TICKS: u32 = 5000 / TICK_MS;
+
+/// The reply of [`App::run`] to a line it does not know.
+pub const HELP: &str = "commands:\r\n  mode [idle|blink|config|fault]\r\n  fault\r\n  clear\r\n";
+
+/// An operating mode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mode {
+    /// Waiting for a first press.
+    Idle,
+    /// The blink, the button stepping its period.
+    Blink,
+    /// The button picking the pattern.
+    Config,
+    /// Stopped on an error, until acknowledged.
+    Fault,
+}
+
+impl Mode {
+    /// The mode of `name`, as the shell takes it.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "idle" => Some(Mode::Idle),
+            "blink" => Some(Mode::Blink),
+            "config" => Some(Mode::Config),
+            "fault" => Some(Mode::Fault),
+            _ => None,
+        }
+    }
+
+    /// The name of the mode.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Mode::Idle => "idle",
+            Mode::Blink => "blink",
+            Mode::Config => "config",
+            Mode::Fault => "fault",
+        }
+    }
+
+    /// What the LED shows in the mode, in ticks of the timer feeding
+    /// [`Event::Tick`]; `period` is the blink period in those ticks.
+    pub fn pattern(self, period: u16) -> Pattern {
+        match self {
+            Mode::Idle => Pattern::blink(1, 19),
+            Mode::Blink => Pattern::blink(period.max(1), period.max(1)),
+            Mode::Config => Pattern::blink(1, 1),
+            Mode::Fault => Pattern::On,
+        }
+    }
+}
+
+/// An input of the state machine.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    /// A short press of the button.
+    Press,
+    /// The button held down.
+    Hold,
+    /// A timer tick.
+    Tick,
+    /// A shell command for the mode.
+    Enter(Mode),
+    /// An error of the application.
+    Fault,
+    /// A shell command acknowledging the fault.
+    Clear,
+}
+
+/// What the application does for an event.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Action {
+    /// Nothing: the event means nothing in the mode.
+    None,
+    /// The mode changed, to this one: show its pattern.
+    Entered(Mode),
+    /// Step the blink period, as the main program's button.
+    StepPeriod,
+    /// Move to the next LED pattern.
+    NextPattern,
+}
+
+/// The mode of the application, from [`Mode::Idle`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StateMachine {
+    mode: Mode,
+    // Ticks since `Config` was entered or last pressed.
+    idle: u32,
+    timeout: u32,
+}
+
+impl StateMachine {
+    /// A machine leaving `Config` after `timeout` ticks with no press.
+    pub const fn new(timeout: u32) -> Self {
+        StateMachine { mode: Mode::Idle, idle: 0, timeout }
+    }
+
+    /// The current mode.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Takes `event` and returns what to do for it.
+    pub fn handle(&mut self, event: Event) -> Action {
+        match (self.mode, event) {
+            (Mode::Fault, Event::Hold | Event::Clear) => self.enter(Mode::Idle),
+            (Mode::Fault, _) => Action::None,
+            (_, Event::Fault) => self.enter(Mode::Fault),
+            (_, Event::Enter(mode)) => self.enter(mode),
+
+            (Mode::Idle, Event::Press) => self.enter(Mode::Blink),
+            (Mode::Blink, Event::Press) => Action::StepPeriod,
+            (Mode::Blink, Event::Hold) => self.enter(Mode::Config),
+            (Mode::Config, Event::Press) => {
+                self.idle = 0;
+                Action::NextPattern
+            }
+            (Mode::Config, Event::Hold) => self.enter(Mode::Blink),
+            (Mode::Config, Event::Tick) => {
+                self.idle += 1;
+                if self.idle >= self.timeout { self.enter(Mode::Blink) } else { Action::None }
+            }
+            _ => Action::None,
+        }
+    }
+
+    fn enter(&mut self, mode: Mode) -> Action {
+        if mode == self.mode {
+            return Action::None;
+        }
+        self.mode = mode;
+        self.idle = 0;
+        Action::Entered(mode)
+    }
+}
+
+/// The application, on the LED `L` and the button `B`, high while pressed.
+pub struct App<L, B> {
+    led: Leds<L, 1>,
+    button: B,
+    pressed: bool,
+    // Ticks the button has been held, from its press.
+    held: Option<u32>,
+    machine: StateMachine,
+    config: AppConfig,
+}
+
+impl<L: OutputPin, B: InputPin> App<L, B> {
+    /// Idle, the LED on its pattern.
+    pub fn new(led: L, mut button: B) -> Self {
+        let pressed = button.is_high().unwrap_or(false);
+        let mut app = App {
+            led: Leds::new([led]),
+            button,
+            pressed,
+            held: None,
+            machine: StateMachine::new(CONFIG_TIMEOUT_TICKS),
+            config: AppConfig::new(),
+        };
+        app.show();
+        app
+    }
+
+    /// The current mode.
+    pub fn mode(&self) -> Mode {
+        self.machine.mode()
+    }
+
+    /// The blink settings.
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    /// The button, for its interrupt flag.
+    pub fn button_mut(&mut self) -> &mut B {
+        &mut self.button
+    }
+
+    /// An edge of the button: the press starts timing the hold, a release
+    /// before it lasts is a `Press`. An edge leaving the level as it was, a
+    /// bounce, is nothing.
+    pub fn edge(&mut self, mut log: impl FnMut(Action, &AppConfig)) {
+        let pressed = self.button.is_high().unwrap_or(false);
+        if pressed == core::mem::replace(&mut self.pressed, pressed) {
+            return;
+        }
+        if pressed {
+            self.held = Some(0);
+        } else if let Some(held) = self.held.take()
+            && held < HOLD_TICKS
+        {
+            self.dispatch(Event::Press, &mut log);
+        }
+    }
+
+    /// A tick, every [`TICK_MS`]: a press is a hold once it lasts, then the
+    /// tick of the machine and of the LED.
+    pub fn tick(&mut self, mut log: impl FnMut(Action, &AppConfig)) {
+        if let Some(held) = self.held {
+            self.held = Some(held + 1);
+            if held + 1 == HOLD_TICKS {
+                self.dispatch(Event::Hold, &mut log);
+            }
+        }
+        self.dispatch(Event::Tick, &mut log);
+        self.led.tick();
+    }
+
+    /// Runs one shell line, its reply to `out`.
+    pub fn run<W: Write>(&mut self, line: &str, out: &mut W, mut log: impl FnMut(Action, &AppConfig)) {
+        let mut words = line.split_ascii_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (None, _, _) => {}
+            (Some("mode"), None, _) => {
+                write!(out, "{}\r\n", self.machine.mode().name()).ok();
+            }
+            (Some("mode"), Some(name), None) if let Some(mode) = Mode::from_name(name) => {
+                self.dispatch(Event::Enter(mode), &mut log);
+            }
+            (Some("fault"), None, _) => self.dispatch(Event::Fault, &mut log),
+            (Some("clear"), None, _) => self.dispatch(Event::Clear, &mut log),
+            _ => {
+                out.write_str(HELP).ok();
+            }
+        }
+    }
+
+    // Sets the LED pattern for the mode and the configuration.
+    fn show(&mut self) {
+        let mode = self.machine.mode();
+        let pattern = match (mode, self.config.pattern()) {
+            (Mode::Blink, BlinkPattern::Solid) => Pattern::On,
+            (Mode::Blink, BlinkPattern::Off) => Pattern::Off,
+            _ => mode.pattern((self.config.period_ms() / TICK_MS) as u16),
+        };
+        self.led.set(0, pattern).ok();
+    }
+
+    // Feeds `event` to the state machine and carries out its action.
+    fn dispatch(&mut self, event: Event, log: &mut impl FnMut(Action, &AppConfig)) {
+        let action = self.machine.handle(event);
+        match action {
+            Action::None => return,
+            Action::Entered(_) => {}
+            Action::StepPeriod => {
+                self.config.step_period();
+            }
+            Action::NextPattern => {
+                let next = match self.config.pattern() {
+                    BlinkPattern::Blink => BlinkPattern::Solid,
+                    BlinkPattern::Solid => BlinkPattern::Off,
+                    BlinkPattern::Off => BlinkPattern::Blink,
+                };
+                self.config.set_pattern(next);
+            }
+        }
+        log(action, &self.config);
+        self.show();
+    }
+}
@@ -0,0 +1,77 @@
+//! The application of the `app_modes` example on the host, played from a
+//! script of inputs.
+//!
+//! Runs a `sim::Board` a millisecond at a time over the steps of a
+//! `Timeline`, the script given as the first argument or
+//! `sim/app_modes.txt`, and prints what a board would show, with the time:
+//! the LED going on and off, the logs and the shell replies.
+//!
+//! ```text
+//! cd logic && cargo run --features sim --bin sim -- my_script.txt
+//! ```
+
+use std::cell::Cell;
+use std::process::ExitCode;
+use std::{env, fs};
+
+use nucleo_g474re_logic::sim::{Board, Error, SimButton, SimLed, Stimulus, Timeline};
+
+// The script played with no argument.
+const DEFAULT_SCRIPT: &str = include_str!("../../sim/app_modes.txt");
+
+// Time run past the last step, for what it sets off to show.
+const TAIL_MS: u32 = 2000;
+
+fn main() -> ExitCode {
+    let script = match env::args().nth(1) {
+        Some(path) => match fs::read_to_string(&path) {
+            Ok(script) => script,
+            Err(error) => {
+                eprintln!("{path}: {error}");
+                return ExitCode::FAILURE;
+            }
+        },
+        None => DEFAULT_SCRIPT.to_string(),
+    };
+    let mut timeline = match Timeline::parse(&script) {
+        Ok(timeline) => timeline,
+        Err(Error::InvalidStep(line)) => {
+            eprintln!("line {line}: expected `<ms> press`, `<ms> release` or `<ms> shell <line>`");
+            return ExitCode::FAILURE;
+        }
+        Err(Error::OutOfOrder(line)) => {
+            eprintln!("line {line}: earlier than the step before it");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let led = Cell::new(false);
+    let button = Cell::new(false);
+    let mut board = Board::new(SimLed::new(&led), SimButton::new(&button));
+    let mut lit = led.get();
+    let mut out = String::new();
+
+    for now in 0..=timeline.end_ms() + TAIL_MS {
+        while let Some(stimulus) = timeline.due(now) {
+            match stimulus {
+                Stimulus::Press => button.set(true),
+                Stimulus::Release => button.set(false),
+                Stimulus::Line(line) => {
+                    println!("{now:>7} ms  > {line}");
+                    board.run(line, &mut out);
+                }
+            }
+        }
+        board.poll(now, &mut out);
+
+        for line in out.lines() {
+            println!("{now:>7} ms  {line}");
+        }
+        out.clear();
+        if led.get() != lit {
+            lit = led.get();
+            println!("{now:>7} ms  LED {}", if lit { "on" } else { "off" });
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,278 @@
+//! Runtime configuration of the blink, shared by its writers.
+//!
+//! [`AppConfig`] holds what the button, a serial shell and the settings kept
+//! across resets all change: the blink period, the pattern and the LED
+//! brightness. Every setter checks its value against the ranges here, so a
+//! shell typo or a corrupt saved copy never reaches the timer, and records
+//! what changed: the code owning the timer and the LED takes the
+//! [`Changes`] and applies only those, wherever the change came from.
+//!
+//! # Compile-time settings
+//!
+//! The const parameters of [`AppConfig`] set the period at start, the
+//! shortest period and the divider of each step of the button, so a fork
+//! changes the blink with a type, not in the interrupt handlers:
+//!
+//! ```text
+//! // Starting at 800 ms, down a third per press to 100 ms.
+//! type BlinkConfig = AppConfig<800, 100, 3>;
+//! static G_CONFIG: Mutex<RefCell<BlinkConfig>> = Mutex::new(RefCell::new(BlinkConfig::new()));
+//! ```
+//!
+//! Their defaults are those of the main program, [`DEFAULT_PERIOD_MS`],
+//! [`MIN_PERIOD_MS`] and [`DEFAULT_DIVIDER`], and a bad combination fails
+//! the build.
+//!
+//! [`AppConfig::to_words`] packs the settings in two 32-bit words, tagged so
+//! [`AppConfig::from_words`] tells a saved copy from leftover contents: the
+//! RTC backup registers keep them through resets, and `Rtc::save_checked`
+//! of the firmware crate stores a CRC word after them, from the CRC unit,
+//! that `Rtc::load_checked` checks before the words reach `from_words`.
+
+/// Shortest blink period by default, in milliseconds.
+pub const MIN_PERIOD_MS: u32 = 125;
+
+/// Longest blink period, in milliseconds: about the most the HAL's
+/// `start_count_down` takes in milliseconds.
+pub const MAX_PERIOD_MS: u32 = 1000;
+
+/// Blink period at start by default.
+pub const DEFAULT_PERIOD_MS: u32 = 1000;
+
+/// Divider of the period at each step by default: halving it.
+pub const DEFAULT_DIVIDER: u32 = 2;
+
+/// Brightness at start, in percent.
+pub const DEFAULT_BRIGHTNESS: u8 = 100;
+
+// Tag of the first saved word.
+const MAGIC: u32 = 0xC0F1_0000;
+
+/// Configuration errors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// A period outside the shortest of the configuration to
+    /// [`MAX_PERIOD_MS`].
+    InvalidPeriod,
+    /// A brightness above 100%.
+    InvalidBrightness,
+    /// No pattern of that name or number.
+    InvalidPattern,
+    /// Saved words without the tag, or with a value out of range.
+    Corrupt,
+}
+
+/// What the LED does.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Pattern {
+    /// On and off, a period each.
+    Blink,
+    /// Always on.
+    Solid,
+    /// Always off.
+    Off,
+}
+
+impl Pattern {
+    /// The pattern of `name`, as the shell takes it.
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "blink" => Ok(Pattern::Blink),
+            "solid" => Ok(Pattern::Solid),
+            "off" => Ok(Pattern::Off),
+            _ => Err(Error::InvalidPattern),
+        }
+    }
+
+    /// The name of the pattern.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Pattern::Blink => "blink",
+            Pattern::Solid => "solid",
+            Pattern::Off => "off",
+        }
+    }
+
+    const fn from_bits(bits: u32) -> Result<Self, Error> {
+        match bits {
+            0 => Ok(Pattern::Blink),
+            1 => Ok(Pattern::Solid),
+            2 => Ok(Pattern::Off),
+            _ => Err(Error::InvalidPattern),
+        }
+    }
+
+    const fn bits(self) -> u32 {
+        match self {
+            Pattern::Blink => 0,
+            Pattern::Solid => 1,
+            Pattern::Off => 2,
+        }
+    }
+}
+
+/// The settings changed since the last [`AppConfig::take_changes`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Changes {
+    /// [`AppConfig::period_ms`].
+    pub period: bool,
+    /// [`AppConfig::pattern`].
+    pub pattern: bool,
+    /// [`AppConfig::brightness`].
+    pub brightness: bool,
+}
+
+impl Changes {
+    /// Every setting, to apply a whole configuration.
+    pub const ALL: Changes = Changes { period: true, pattern: true, brightness: true };
+
+    /// Returns `true` if any setting changed.
+    pub const fn any(self) -> bool {
+        self.period || self.pattern || self.brightness
+    }
+}
+
+/// The blink settings: starting at `INITIAL_MS`, divided by `DIVIDER` at
+/// each step, down to `MIN_MS`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AppConfig<
+    const INITIAL_MS: u32 = DEFAULT_PERIOD_MS,
+    const MIN_MS: u32 = MIN_PERIOD_MS,
+    const DIVIDER: u32 = DEFAULT_DIVIDER,
+> {
+    period_ms: u32,
+    pattern: Pattern,
+    brightness: u8,
+    changes: Changes,
+}
+
+impl<const INITIAL_MS: u32, const MIN_MS: u32, const DIVIDER: u32> AppConfig<INITIAL_MS, MIN_MS, DIVIDER> {
+    // Fails the build for parameters out of order, or a divider below 2.
+    const VALID: () = assert!(
+        0 < MIN_MS && MIN_MS <= INITIAL_MS && INITIAL_MS <= MAX_PERIOD_MS && DIVIDER >= 2,
+        "AppConfig takes 0 < MIN_MS <= INITIAL_MS <= MAX_PERIOD_MS and DIVIDER >= 2"
+    );
+
+    /// The period at start, in milliseconds.
+    pub const INITIAL_MS: u32 = INITIAL_MS;
+
+    /// The shortest period, in milliseconds.
+    pub const MIN_MS: u32 = MIN_MS;
+
+    /// The divider of each step.
+    pub const DIVIDER: u32 = DIVIDER;
+
+    /// The defaults, for a `static`.
+    pub const fn new() -> Self {
+        let () = Self::VALID;
+        AppConfig {
+            period_ms: INITIAL_MS,
+            pattern: Pattern::Blink,
+            brightness: DEFAULT_BRIGHTNESS,
+            changes: Changes { period: false, pattern: false, brightness: false },
+        }
+    }
+
+    /// The blink period, in milliseconds.
+    pub fn period_ms(&self) -> u32 {
+        self.period_ms
+    }
+
+    /// Sets the blink period.
+    pub fn set_period_ms(&mut self, period_ms: u32) -> Result<(), Error> {
+        if !(MIN_MS..=MAX_PERIOD_MS).contains(&period_ms) {
+            return Err(Error::InvalidPeriod);
+        }
+        if period_ms != self.period_ms {
+            self.period_ms = period_ms;
+            self.changes.period = true;
+        }
+        Ok(())
+    }
+
+    /// Divides the period by `DIVIDER`, back to `INITIAL_MS` below
+    /// `MIN_MS`, as the button does; returns the new period.
+    pub fn step_period(&mut self) -> u32 {
+        let divided = self.period_ms / DIVIDER;
+        let period_ms = if divided < MIN_MS { INITIAL_MS } else { divided };
+        self.set_period_ms(period_ms).ok();
+        self.period_ms
+    }
+
+    /// The pattern.
+    pub fn pattern(&self) -> Pattern {
+        self.pattern
+    }
+
+    /// Sets the pattern.
+    pub fn set_pattern(&mut self, pattern: Pattern) {
+        if pattern != self.pattern {
+            self.pattern = pattern;
+            self.changes.pattern = true;
+        }
+    }
+
+    /// The brightness of the LED when on, in percent.
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    /// Sets the brightness, 0 to 100%.
+    pub fn set_brightness(&mut self, brightness: u8) -> Result<(), Error> {
+        if brightness > 100 {
+            return Err(Error::InvalidBrightness);
+        }
+        if brightness != self.brightness {
+            self.brightness = brightness;
+            self.changes.brightness = true;
+        }
+        Ok(())
+    }
+
+    /// The brightness as an 8-bit level, for a gamma table.
+    pub fn level(&self) -> u8 {
+        (self.brightness as u32 * 255 / 100) as u8
+    }
+
+    /// Takes the settings changed since the last call.
+    pub fn take_changes(&mut self) -> Changes {
+        core::mem::take(&mut self.changes)
+    }
+
+    /// Replaces the settings with those of `other`, recording the changes.
+    pub fn update(&mut self, other: &Self) {
+        // `other` holds valid settings: the setters cannot fail.
+        self.set_period_ms(other.period_ms).ok();
+        self.set_pattern(other.pattern);
+        self.set_brightness(other.brightness).ok();
+    }
+
+    /// The settings packed in two words, for [`AppConfig::from_words`].
+    pub fn to_words(&self) -> [u32; 2] {
+        [MAGIC | (self.brightness as u32) << 8 | self.pattern.bits(), self.period_ms]
+    }
+
+    /// The settings of two words from [`AppConfig::to_words`], nothing
+    /// changed.
+    pub fn from_words(words: [u32; 2]) -> Result<Self, Error> {
+        if words[0] & 0xFFFF_0000 != MAGIC {
+            return Err(Error::Corrupt);
+        }
+        let mut config = Self::new();
+        config.set_pattern(Pattern::from_bits(words[0] & 0xFF).map_err(|_| Error::Corrupt)?);
+        config.set_brightness((words[0] >> 8) as u8).map_err(|_| Error::Corrupt)?;
+        config.set_period_ms(words[1]).map_err(|_| Error::Corrupt)?;
+        config.take_changes();
+        Ok(config)
+    }
+}
+
+impl<const INITIAL_MS: u32, const MIN_MS: u32, const DIVIDER: u32> Default for AppConfig<INITIAL_MS, MIN_MS, DIVIDER> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,107 @@
+//! Debouncing of a button, by one of two algorithms behind [`Debouncer`].
+//!
+//! A contact bounces for a few milliseconds when it closes and when it
+//! opens. Each input takes the algorithm that suits how it is read, as a
+//! value of its own:
+//!
+//! | Debouncer      | Fed                                   | Reacts                           |
+//! |----------------|---------------------------------------|----------------------------------|
+//! | [`Timeout`]    | on each edge (EXTI), and on a tick    | on the first edge, then ignores the input for a window |
+//! | [`Integrator`] | on each tick of a timer               | once the samples have moved a counter from one end to the other |
+//!
+//! The timeout debouncer answers without delay, and needs no tick while the
+//! button is left alone; a spike on the line is a press to it. The
+//! integrator rides out spikes shorter than its count, and answers a count
+//! of ticks late.
+//!
+//! Both take the raw level, `true` for pressed, with the time in
+//! milliseconds, which wraps at 2^32, and return an [`Event`] when the
+//! debounced level changes.
+
+/// A change of the debounced level.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    Pressed,
+    Released,
+}
+
+impl Event {
+    fn of(pressed: bool) -> Self {
+        if pressed { Event::Pressed } else { Event::Released }
+    }
+}
+
+/// A debounce algorithm, fed the raw level of one input.
+pub trait Debouncer {
+    /// Feeds the raw level `pressed`, read at `now_ms`; returns the change
+    /// of the debounced level it makes, if any.
+    fn update(&mut self, pressed: bool, now_ms: u32) -> Option<Event>;
+
+    /// Returns `true` while the debounced level is pressed.
+    fn is_pressed(&self) -> bool;
+}
+
+/// Takes the first edge, then holds the level for `window_ms`.
+pub struct Timeout {
+    window_ms: u32,
+    pressed: bool,
+    since_ms: u32,
+}
+
+impl Timeout {
+    /// Released, and taking an edge from the time 0.
+    pub const fn new(window_ms: u32) -> Self {
+        // The last change a window before 0, so the first edge is taken.
+        Timeout { window_ms, pressed: false, since_ms: 0_u32.wrapping_sub(window_ms) }
+    }
+}
+
+impl Debouncer for Timeout {
+    fn update(&mut self, pressed: bool, now_ms: u32) -> Option<Event> {
+        if pressed == self.pressed || now_ms.wrapping_sub(self.since_ms) < self.window_ms {
+            return None;
+        }
+        self.pressed = pressed;
+        self.since_ms = now_ms;
+        Some(Event::of(pressed))
+    }
+
+    fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+}
+
+/// Counts the samples pressed up and those released down, between 0 and
+/// `samples`; the level changes at either end.
+pub struct Integrator {
+    samples: u8,
+    count: u8,
+    pressed: bool,
+}
+
+impl Integrator {
+    /// Released, changing after `samples` samples agreeing, at least 1.
+    pub const fn new(samples: u8) -> Self {
+        Integrator { samples: if samples == 0 { 1 } else { samples }, count: 0, pressed: false }
+    }
+}
+
+impl Debouncer for Integrator {
+    fn update(&mut self, pressed: bool, _now_ms: u32) -> Option<Event> {
+        self.count = if pressed { (self.count + 1).min(self.samples) } else { self.count.saturating_sub(1) };
+        let level = match self.count {
+            0 => false,
+            count if count == self.samples => true,
+            _ => return None,
+        };
+        if level == core::mem::replace(&mut self.pressed, level) {
+            return None;
+        }
+        Some(Event::of(level))
+    }
+
+    fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+}
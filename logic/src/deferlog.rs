@@ -0,0 +1,149 @@
+//! Deferred logging: interrupts queue compact records, the main loop formats
+//! them.
+//!
+//! A log line from an interrupt formats its arguments, and writes them to
+//! the RTT buffer in a critical section, while the interrupt runs and every
+//! other one waits. [`Queue::push`] instead stores a [`Record`], an event id
+//! and [`ARGS`] words, in a few instructions and with no lock; the main loop
+//! [`Queue::pop`]s the records once the interrupts are done and logs each as
+//! it likes, the id picking the message:
+//!
+//! ```text
+//! static LOG: Queue<32> = Queue::new();
+//!
+//! // TIM2 interrupt:
+//! LOG.push(Record::new(TOGGLE, [toggles, 0]));
+//!
+//! // main loop:
+//! while let Some(record) = LOG.pop() {
+//!     match record.id { TOGGLE => defmt::info!("Toggle {}", record.args[0]), _ => {} }
+//! }
+//! ```
+//!
+//! Any number of interrupts push, at any priorities, and any context pops,
+//! the main loop usually: the queue is the bounded ring of Dmitry Vyukov,
+//! each slot with a sequence number telling whether it holds a record yet.
+//! A push on a full queue drops the record and counts it, for
+//! [`Queue::dropped`]. The records come out in the order of their pushes;
+//! a pop finds the queue empty while the push of the oldest is preempted
+//! halfway, and the records after it come out on the next pop.
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+/// Argument words in a [`Record`].
+pub const ARGS: usize = 2;
+
+/// A log event: what happened, and its values.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Record {
+    /// The event, one of the application's ids.
+    pub id: u16,
+    pub args: [u32; ARGS],
+}
+
+impl Record {
+    pub const fn new(id: u16, args: [u32; ARGS]) -> Self {
+        Record { id, args }
+    }
+}
+
+struct Slot {
+    // `pos` when free for the push at `pos`, `pos + 1` when holding its
+    // record for the pop at `pos`.
+    sequence: AtomicUsize,
+    record: UnsafeCell<Record>,
+}
+
+/// A queue of up to `N` records, `N` a power of two.
+pub struct Queue<const N: usize> {
+    slots: [Slot; N],
+    // Positions of the next push and the next pop, counting on forever.
+    push: AtomicUsize,
+    pop: AtomicUsize,
+    dropped: AtomicU32,
+}
+
+// Records move between contexts through the sequence numbers: a slot is
+// written by the one push that claimed it, then read by the one pop that
+// claimed it.
+unsafe impl<const N: usize> Sync for Queue<N> {}
+
+impl<const N: usize> Queue<N> {
+    /// An empty queue, for a `static`.
+    pub const fn new() -> Self {
+        const { assert!(N.is_power_of_two()) };
+        let mut slots = [const { Slot { sequence: AtomicUsize::new(0), record: UnsafeCell::new(Record::new(0, [0; ARGS])) } }; N];
+        let mut i = 0;
+        while i < N {
+            slots[i].sequence = AtomicUsize::new(i);
+            i += 1;
+        }
+        Queue { slots, push: AtomicUsize::new(0), pop: AtomicUsize::new(0), dropped: AtomicU32::new(0) }
+    }
+
+    /// Queues `record`; `false`, with the record dropped and counted, if
+    /// the queue is full.
+    pub fn push(&self, record: Record) -> bool {
+        let mut pos = self.push.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos % N];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            match sequence.wrapping_sub(pos) as isize {
+                // Free: claim it, unless another push got there first.
+                0 => match self.push.compare_exchange_weak(pos, pos.wrapping_add(1), Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => {
+                        unsafe { *slot.record.get() = record };
+                        slot.sequence.store(pos.wrapping_add(1), Ordering::Release);
+                        return true;
+                    }
+                    Err(current) => pos = current,
+                },
+                // Still holding the record of the lap before: full.
+                diff if diff < 0 => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return false;
+                }
+                // Claimed by another push since the load: try the next.
+                _ => pos = self.push.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// The oldest record, if its push has finished.
+    pub fn pop(&self) -> Option<Record> {
+        let mut pos = self.pop.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos % N];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            match sequence.wrapping_sub(pos.wrapping_add(1)) as isize {
+                // Holding its record: take it, unless another pop got there first.
+                0 => match self.pop.compare_exchange_weak(pos, pos.wrapping_add(1), Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => {
+                        let record = unsafe { *slot.record.get() };
+                        // Free for the push of the next lap.
+                        slot.sequence.store(pos.wrapping_add(N), Ordering::Release);
+                        return Some(record);
+                    }
+                    Err(current) => pos = current,
+                },
+                // Not pushed yet, or its push not finished: empty for now.
+                diff if diff < 0 => return None,
+                // Taken by another pop since the load: try the next.
+                _ => pos = self.pop.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Records dropped on a full queue since the last call.
+    pub fn dropped(&self) -> u32 {
+        self.dropped.swap(0, Ordering::Relaxed)
+    }
+}
+
+impl<const N: usize> Default for Queue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
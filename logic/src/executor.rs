@@ -0,0 +1,131 @@
+//! Cooperative round-robin executor for the main loop.
+//!
+//! The examples grow their main loop one `if` at a time: the blink, a shell,
+//! a sensor read, a report. [`Executor`] gives it one shape instead. Each
+//! part is a [`Task`], polled with the time in milliseconds and answering
+//! whether it had anything to do; [`Executor::spawn`] registers up to `N` of
+//! them, and [`Executor::run`] polls each once, in turn, on every wakeup of
+//! the loop:
+//!
+//! ```text
+//! let mut executor = Executor::<4>::new();
+//! executor.spawn("blink", &mut blink)?;
+//! executor.spawn("shell", &mut shell)?;
+//! loop {
+//!     cortex_m::asm::wfi();
+//!     executor.run(tick::now_ms());
+//! }
+//! ```
+//!
+//! A task never blocks: it checks its own deadline or its input and returns,
+//! so a pass takes as long as the work due in it. A closure
+//! `FnMut(u32) -> bool` is a task. The executor counts, per task, the polls
+//! and the runs, the polls that did something, read back as [`TaskStats`]
+//! with [`Executor::stats`] and [`Executor::tasks`]; like the other
+//! counters of the crate they wrap at 2^32.
+
+/// Executor errors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// All the `N` tasks are spawned.
+    Full,
+}
+
+/// A part of the main loop, polled on each pass of the executor.
+pub trait Task {
+    /// Does what is due at `now_ms`, without waiting for anything; returns
+    /// `true` if it did anything.
+    fn poll(&mut self, now_ms: u32) -> bool;
+}
+
+impl<F: FnMut(u32) -> bool> Task for F {
+    fn poll(&mut self, now_ms: u32) -> bool {
+        self(now_ms)
+    }
+}
+
+/// A spawned task, as [`Executor::spawn`] returns it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TaskId(usize);
+
+/// The counters of a task.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TaskStats {
+    pub name: &'static str,
+    /// Passes that polled the task.
+    pub polls: u32,
+    /// Polls that did something.
+    pub runs: u32,
+}
+
+struct Slot<'a> {
+    task: &'a mut dyn Task,
+    stats: TaskStats,
+}
+
+/// Up to `N` tasks, borrowed for `'a`, polled in turn.
+pub struct Executor<'a, const N: usize> {
+    slots: [Option<Slot<'a>>; N],
+    passes: u32,
+}
+
+impl<'a, const N: usize> Executor<'a, N> {
+    /// No task yet.
+    pub const fn new() -> Self {
+        Executor { slots: [const { None }; N], passes: 0 }
+    }
+
+    /// Polls `task` on every pass from the next one, after the tasks
+    /// spawned before it, or in the place of one removed.
+    pub fn spawn(&mut self, name: &'static str, task: &'a mut dyn Task) -> Result<TaskId, Error> {
+        let index = self.slots.iter().position(Option::is_none).ok_or(Error::Full)?;
+        self.slots[index] = Some(Slot { task, stats: TaskStats { name, polls: 0, runs: 0 } });
+        Ok(TaskId(index))
+    }
+
+    /// Stops polling the task of `id`; returns `false` if it was not
+    /// spawned. The id then names the next task spawned in its place.
+    pub fn remove(&mut self, id: TaskId) -> bool {
+        self.slots[id.0].take().is_some()
+    }
+
+    /// Polls every task once, at `now_ms`; returns `true` if any did
+    /// something, for a loop that passes again before it sleeps.
+    pub fn run(&mut self, now_ms: u32) -> bool {
+        self.passes = self.passes.wrapping_add(1);
+        let mut busy = false;
+        for slot in self.slots.iter_mut().flatten() {
+            let ran = slot.task.poll(now_ms);
+            slot.stats.polls = slot.stats.polls.wrapping_add(1);
+            if ran {
+                slot.stats.runs = slot.stats.runs.wrapping_add(1);
+            }
+            busy |= ran;
+        }
+        busy
+    }
+
+    /// Passes run so far.
+    pub fn passes(&self) -> u32 {
+        self.passes
+    }
+
+    /// The counters of the task of `id`, if it is spawned.
+    pub fn stats(&self, id: TaskId) -> Option<TaskStats> {
+        self.slots[id.0].as_ref().map(|slot| slot.stats)
+    }
+
+    /// The counters of every task, in the order they are polled.
+    pub fn tasks(&self) -> impl Iterator<Item = TaskStats> + '_ {
+        self.slots.iter().flatten().map(|slot| slot.stats)
+    }
+}
+
+impl<const N: usize> Default for Executor<'_, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
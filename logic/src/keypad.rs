@@ -0,0 +1,159 @@
+//! Scanning of a row/column key matrix, with debouncing and an event queue.
+//!
+//! The rows are open-drain outputs, the columns inputs with pull-ups: a
+//! pressed key pulls its column low while its row is driven low, and the
+//! rows not scanned float, so two keys held on one column cannot short two
+//! outputs. [`Keypad::scan`] runs on a timer tick (1 ms is typical): each
+//! call reads the columns of the row driven since the previous call, so the
+//! lines have a whole tick to settle, then drives the next row.
+//!
+//! A key changes state after [`DEBOUNCE_SCANS`] reads in a row agree, and
+//! each change queues an [`Event`] for [`Keypad::next_event`], up to
+//! [`QUEUE_LEN`] waiting. With a 1 ms tick and four rows, a key is read every
+//! 4 ms and debounced in 16 ms.
+//!
+//! The rows share one pin type, as do the columns: pins of one port
+//! `downgrade` to the same type.
+
+use embedded_hal::digital::{InputPin, OutputPin};
+
+/// Agreeing reads before a key changes state.
+pub const DEBOUNCE_SCANS: u8 = 4;
+
+/// Events queued before new ones are dropped.
+pub const QUEUE_LEN: usize = 16;
+
+/// The labels of the common 4x4 membrane keypad, by row and column.
+pub const LAYOUT_4X4: [[u8; 4]; 4] = [*b"123A", *b"456B", *b"789C", *b"*0#D"];
+
+/// A key, by its row and column.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Key {
+    pub row: u8,
+    pub col: u8,
+}
+
+/// A debounced change of a key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    /// The key was pressed.
+    Down(Key),
+    /// The key was released.
+    Up(Key),
+}
+
+impl Event {
+    /// The key that changed.
+    pub fn key(self) -> Key {
+        match self {
+            Event::Down(key) | Event::Up(key) => key,
+        }
+    }
+}
+
+/// A `ROWS` x `COLS` key matrix.
+pub struct Keypad<R, C, const ROWS: usize, const COLS: usize> {
+    rows: [R; ROWS],
+    cols: [C; COLS],
+    // The row driven low.
+    row: usize,
+    pressed: [[bool; COLS]; ROWS],
+    // Reads in a row disagreeing with `pressed`.
+    changing: [[u8; COLS]; ROWS],
+    queue: [Option<Event>; QUEUE_LEN],
+    head: usize,
+    len: usize,
+    dropped: u32,
+}
+
+impl<R: OutputPin, C: InputPin, const ROWS: usize, const COLS: usize> Keypad<R, C, ROWS, COLS> {
+    /// Takes the row outputs, open-drain, and the column inputs, pulled up,
+    /// and drives the first row.
+    pub fn new(rows: [R; ROWS], cols: [C; COLS]) -> Self {
+        let mut keypad = Keypad {
+            rows,
+            cols,
+            row: 0,
+            pressed: [[false; COLS]; ROWS],
+            changing: [[0; COLS]; ROWS],
+            queue: [None; QUEUE_LEN],
+            head: 0,
+            len: 0,
+            dropped: 0,
+        };
+        for row in keypad.rows.iter_mut() {
+            row.set_high().ok();
+        }
+        if let Some(row) = keypad.rows.first_mut() {
+            row.set_low().ok();
+        }
+        keypad
+    }
+
+    /// Reads the driven row and drives the next: call on every tick.
+    pub fn scan(&mut self) {
+        let row = self.row;
+        for col in 0..COLS {
+            let down = matches!(self.cols[col].is_low(), Ok(true));
+            if down == self.pressed[row][col] {
+                self.changing[row][col] = 0;
+                continue;
+            }
+            self.changing[row][col] += 1;
+            if self.changing[row][col] >= DEBOUNCE_SCANS {
+                self.changing[row][col] = 0;
+                self.pressed[row][col] = down;
+                let key = Key { row: row as u8, col: col as u8 };
+                self.push(if down { Event::Down(key) } else { Event::Up(key) });
+            }
+        }
+
+        self.rows[row].set_high().ok();
+        self.row = (row + 1) % ROWS;
+        self.rows[self.row].set_low().ok();
+    }
+
+    /// The oldest event waiting, if any.
+    pub fn next_event(&mut self) -> Option<Event> {
+        if self.len == 0 {
+            return None;
+        }
+        let event = self.queue[self.head].take();
+        self.head = (self.head + 1) % QUEUE_LEN;
+        self.len -= 1;
+        event
+    }
+
+    /// Returns `true` while `key` is held down, debounced.
+    pub fn is_pressed(&self, key: Key) -> bool {
+        self.pressed
+            .get(key.row as usize)
+            .and_then(|row| row.get(key.col as usize))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Events dropped on a full queue since the start.
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Releases the rows and returns the pins.
+    pub fn release(mut self) -> ([R; ROWS], [C; COLS]) {
+        for row in self.rows.iter_mut() {
+            row.set_high().ok();
+        }
+        (self.rows, self.cols)
+    }
+
+    fn push(&mut self, event: Event) {
+        if self.len == QUEUE_LEN {
+            self.dropped += 1;
+            return;
+        }
+        self.queue[(self.head + self.len) % QUEUE_LEN] = Some(event);
+        self.len += 1;
+    }
+}
@@ -0,0 +1,137 @@
+//! A row of GPIO-driven LEDs, such as an 8-LED bar.
+//!
+//! [`Leds`] gives each LED a [`Pattern`], on, off or blinking, and plays
+//! them on a timer tick: [`Leds::tick`] advances a shared tick count and
+//! writes every pin, so blinking LEDs keep in step with each other. On top of
+//! the patterns, [`Leds::show_bar`] draws a value as a bar graph and
+//! [`Leds::show_binary`] as a binary number, LED 0 the lowest bit.
+//!
+//! The LEDs share one pin type, lit with the pin high: pins of one port
+//! `downgrade` to the same type.
+
+use embedded_hal::digital::OutputPin;
+
+/// What an LED does on each tick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Pattern {
+    Off,
+    On,
+    /// Lit for `on` ticks, then dark for `off`, starting `phase` ticks in.
+    Blink { on: u16, off: u16, phase: u16 },
+}
+
+impl Pattern {
+    /// Blinks `on` ticks lit, `off` ticks dark, in step with the tick count.
+    pub const fn blink(on: u16, off: u16) -> Self {
+        Pattern::Blink { on, off, phase: 0 }
+    }
+
+    /// Returns `true` if the pattern lights the LED at tick `ticks`.
+    pub fn is_lit(self, ticks: u32) -> bool {
+        match self {
+            Pattern::Off => false,
+            Pattern::On => true,
+            Pattern::Blink { on, off, phase } => {
+                let period = on as u32 + off as u32;
+                period > 0 && (ticks + phase as u32) % period < on as u32
+            }
+        }
+    }
+}
+
+/// LED errors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// No LED of that index.
+    InvalidLed,
+}
+
+/// `N` LEDs with a pattern each.
+pub struct Leds<P, const N: usize> {
+    pins: [P; N],
+    patterns: [Pattern; N],
+    ticks: u32,
+}
+
+impl<P: OutputPin, const N: usize> Leds<P, N> {
+    /// Takes the pins, all LEDs off.
+    pub fn new(pins: [P; N]) -> Self {
+        let mut leds = Leds { pins, patterns: [Pattern::Off; N], ticks: 0 };
+        leds.write();
+        leds
+    }
+
+    /// The number of LEDs.
+    pub fn len(&self) -> usize {
+        N
+    }
+
+    /// Returns `true` for a row of no LED.
+    pub fn is_empty(&self) -> bool {
+        N == 0
+    }
+
+    /// Gives LED `index` its pattern, from the next tick.
+    pub fn set(&mut self, index: usize, pattern: Pattern) -> Result<(), Error> {
+        let slot = self.patterns.get_mut(index).ok_or(Error::InvalidLed)?;
+        *slot = pattern;
+        Ok(())
+    }
+
+    /// The pattern of LED `index`.
+    pub fn pattern(&self, index: usize) -> Option<Pattern> {
+        self.patterns.get(index).copied()
+    }
+
+    /// Gives every LED `pattern`.
+    pub fn set_all(&mut self, pattern: Pattern) {
+        self.patterns = [pattern; N];
+    }
+
+    /// Lights a bar from LED 0 in proportion to `value` out of `max`,
+    /// rounded, at least one LED for any value above zero.
+    pub fn show_bar(&mut self, value: u32, max: u32) {
+        let value = value.min(max) as u64;
+        let mut lit = if max == 0 { 0 } else { ((value * N as u64 * 2 + max as u64) / (max as u64 * 2)) as usize };
+        if value > 0 && lit == 0 {
+            lit = 1;
+        }
+        for (index, pattern) in self.patterns.iter_mut().enumerate() {
+            *pattern = if index < lit { Pattern::On } else { Pattern::Off };
+        }
+    }
+
+    /// Shows the `N` low bits of `value`, LED 0 the lowest.
+    pub fn show_binary(&mut self, value: u32) {
+        for (index, pattern) in self.patterns.iter_mut().enumerate() {
+            let bit = index < 32 && value & (1 << index) != 0;
+            *pattern = if bit { Pattern::On } else { Pattern::Off };
+        }
+    }
+
+    /// Advances the patterns by one tick and updates the pins: call from
+    /// the timer interrupt.
+    pub fn tick(&mut self) {
+        self.ticks = self.ticks.wrapping_add(1);
+        self.write();
+    }
+
+    /// Turns every LED off and returns the pins.
+    pub fn release(mut self) -> [P; N] {
+        self.set_all(Pattern::Off);
+        self.write();
+        self.pins
+    }
+
+    fn write(&mut self) {
+        for (pin, pattern) in self.pins.iter_mut().zip(self.patterns) {
+            if pattern.is_lit(self.ticks) {
+                pin.set_high().ok();
+            } else {
+                pin.set_low().ok();
+            }
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! Hardware-independent logic of the Nucleo G474RE examples.
+//!
+//! The modules here touch no register: the keypad debouncing and the LED
+//! patterns drive their pins through the embedded-hal traits, the timer
+//! wheel, the blink configuration, the button debouncers, the application
+//! modes, the interrupt statistics, the temperature compensation of the
+//! timer periods, the deferred log queue and the main loop's task executor
+//! are plain state. The firmware crate re-exports them under the same
+//! names, and `cargo test` in this directory runs their tests on the host,
+//! with mock pins from `embedded-hal-mock`.
+//!
+//! The `defmt` feature derives `defmt::Format` for the types, for the
+//! firmware's logs.
+//!
+//! The `sim` feature adds the `sim` module, fakes of the board's LED, button
+//! and timer, and the `sim` binary, which plays a script of inputs through
+//! the application of the `app_modes` example on the host.
+
+// `no_std`: embedded environment without the standard library.
+#![no_std]
+
+pub mod app;
+pub mod config;
+pub mod debounce;
+pub mod deferlog;
+pub mod executor;
+pub mod keypad;
+pub mod leds;
+#[cfg(feature = "sim")]
+pub mod sim;
+pub mod stats;
+pub mod tempcomp;
+pub mod timer_wheel;
@@ -0,0 +1,249 @@
+//! Host simulation: the application of the `app_modes` example on in-memory
+//! fakes of the board.
+//!
+//! The firmware reads the User Button and drives the LED through the HAL,
+//! on the ticks of TIM2. Here [`SimLed`] and [`SimButton`] are pins over a
+//! `Cell<bool>` of the caller's, and [`SimTimer`] times out on a clock in
+//! milliseconds the caller advances. A [`Board`] runs the example's
+//! `app::App` on them, with the calls of its interrupts: an edge of the
+//! button each millisecond, as the EXTI line sees the level change, a tick
+//! each `app::TICK_MS`, and the shell commands, from whole lines.
+//!
+//! A [`Timeline`] scripts the inputs, one step a line, the time first:
+//!
+//! ```text
+//! # ms  step
+//! 500   press
+//! 600   release
+//! 2000  press
+//! 2800  release
+//! 4000  shell mode
+//! 4500  shell fault
+//! ```
+//!
+//! The `sim` binary plays a script, the default one or a file, and prints
+//! the LED, the logs and the shell replies with their times, on the host:
+//!
+//! ```text
+//! cd logic && cargo run --features sim --bin sim -- sim/app_modes.txt
+//! ```
+
+use core::cell::Cell;
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_hal::digital::{ErrorType, InputPin, OutputPin};
+
+use crate::app::{Action, App, TICK_MS};
+use crate::config::AppConfig;
+
+/// The LED, lit with its cell `true`.
+pub struct SimLed<'a>(&'a Cell<bool>);
+
+impl<'a> SimLed<'a> {
+    /// Drives `level`.
+    pub fn new(level: &'a Cell<bool>) -> Self {
+        SimLed(level)
+    }
+}
+
+impl ErrorType for SimLed<'_> {
+    type Error = Infallible;
+}
+
+impl OutputPin for SimLed<'_> {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.0.set(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.0.set(true);
+        Ok(())
+    }
+}
+
+/// The button, high while pressed as B1 is, its cell `true`.
+pub struct SimButton<'a>(&'a Cell<bool>);
+
+impl<'a> SimButton<'a> {
+    /// Reads `level`.
+    pub fn new(level: &'a Cell<bool>) -> Self {
+        SimButton(level)
+    }
+}
+
+impl ErrorType for SimButton<'_> {
+    type Error = Infallible;
+}
+
+impl InputPin for SimButton<'_> {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(self.0.get())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        Ok(!self.0.get())
+    }
+}
+
+/// A periodic timer on the simulated clock, started at 0.
+pub struct SimTimer {
+    period_ms: u32,
+    next_ms: u32,
+}
+
+impl SimTimer {
+    /// Times out every `period_ms`, at least 1.
+    pub const fn new(period_ms: u32) -> Self {
+        let period_ms = if period_ms == 0 { 1 } else { period_ms };
+        SimTimer { period_ms, next_ms: period_ms }
+    }
+
+    /// Returns `true` once for each period ended at `now_ms`, a late poll
+    /// catching up one period at a time.
+    pub fn poll(&mut self, now_ms: u32) -> bool {
+        if (now_ms.wrapping_sub(self.next_ms) as i32) < 0 {
+            return false;
+        }
+        self.next_ms = self.next_ms.wrapping_add(self.period_ms);
+        true
+    }
+}
+
+/// An input of the script.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stimulus<'a> {
+    /// The button goes down.
+    Press,
+    /// The button comes up.
+    Release,
+    /// A line typed in the shell.
+    Line(&'a str),
+}
+
+/// A stimulus and its time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Step<'a> {
+    pub at_ms: u32,
+    pub stimulus: Stimulus<'a>,
+}
+
+/// Script errors, with the number of the line, from 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// Not a time and a step.
+    InvalidStep(usize),
+    /// Earlier than the step before it.
+    OutOfOrder(usize),
+}
+
+// The step of a line: `None` for a blank line or a comment, an error for
+// anything else not a step.
+fn parse_step(line: &str) -> Result<Option<Step<'_>>, ()> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let (at_ms, rest) = line.split_once(char::is_whitespace).ok_or(())?;
+    let at_ms = at_ms.parse().map_err(|_| ())?;
+    let stimulus = match rest.trim_start().split_once(char::is_whitespace) {
+        None if rest.trim_start() == "press" => Stimulus::Press,
+        None if rest.trim_start() == "release" => Stimulus::Release,
+        Some(("shell", line)) => Stimulus::Line(line.trim()),
+        _ => return Err(()),
+    };
+    Ok(Some(Step { at_ms, stimulus }))
+}
+
+/// The steps of a script, in order, handed out as the clock reaches them.
+pub struct Timeline<'a> {
+    lines: core::str::Lines<'a>,
+    next: Option<Step<'a>>,
+    end_ms: u32,
+}
+
+impl<'a> Timeline<'a> {
+    /// Checks every line of `script` and starts at its first step.
+    pub fn parse(script: &'a str) -> Result<Self, Error> {
+        let mut end_ms = 0;
+        for (index, line) in script.lines().enumerate() {
+            match parse_step(line) {
+                Err(()) => return Err(Error::InvalidStep(index + 1)),
+                Ok(Some(step)) if step.at_ms < end_ms => return Err(Error::OutOfOrder(index + 1)),
+                Ok(Some(step)) => end_ms = step.at_ms,
+                Ok(None) => {}
+            }
+        }
+        let mut timeline = Timeline { lines: script.lines(), next: None, end_ms };
+        timeline.next = timeline.read();
+        Ok(timeline)
+    }
+
+    fn read(&mut self) -> Option<Step<'a>> {
+        self.lines.by_ref().find_map(|line| parse_step(line).ok().flatten())
+    }
+
+    /// The time of the last step.
+    pub fn end_ms(&self) -> u32 {
+        self.end_ms
+    }
+
+    /// Takes the next step due at `now_ms`, if any.
+    pub fn due(&mut self, now_ms: u32) -> Option<Stimulus<'a>> {
+        let step = self.next.filter(|step| step.at_ms <= now_ms)?;
+        self.next = self.read();
+        Some(step.stimulus)
+    }
+}
+
+/// Writes the log line of `action`, as `app_modes` logs it.
+pub fn log<W: Write>(out: &mut W, action: Action, config: &AppConfig) {
+    match action {
+        Action::None => {}
+        Action::Entered(mode) => {
+            writeln!(out, "Mode: {}", mode.name()).ok();
+        }
+        Action::StepPeriod => {
+            writeln!(out, "Delay Atual: {} ms", config.period_ms()).ok();
+        }
+        Action::NextPattern => {
+            writeln!(out, "Pattern: {}", config.pattern().name()).ok();
+        }
+    }
+}
+
+/// The board of `app_modes`, its application on the fakes.
+pub struct Board<'a> {
+    app: App<SimLed<'a>, SimButton<'a>>,
+    timer: SimTimer,
+}
+
+impl<'a> Board<'a> {
+    /// The application idle, the clock at 0.
+    pub fn new(led: SimLed<'a>, button: SimButton<'a>) -> Self {
+        Board { app: App::new(led, button), timer: SimTimer::new(TICK_MS) }
+    }
+
+    /// The application.
+    pub fn app(&self) -> &App<SimLed<'a>, SimButton<'a>> {
+        &self.app
+    }
+
+    /// One millisecond of the firmware, at `now_ms`: the button's edge, if
+    /// any, and a tick if one is due. Logs go to `out`.
+    pub fn poll<W: Write>(&mut self, now_ms: u32, out: &mut W) {
+        self.app.edge(|action, config| log(out, action, config));
+        if self.timer.poll(now_ms) {
+            self.app.tick(|action, config| log(out, action, config));
+        }
+    }
+
+    /// Runs one shell line, its reply or its log to `out`.
+    pub fn run<W: Write>(&mut self, line: &str, out: &mut W) {
+        // A line replies or dispatches, never both: its action logs after.
+        let mut done = Action::None;
+        self.app.run(line, out, |action, _| done = action);
+        log(out, done, self.app.config());
+    }
+}
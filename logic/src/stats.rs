@@ -0,0 +1,125 @@
+//! Counters of what the interrupts saw: presses, bounces, overruns and
+//! overflows.
+//!
+//! [`Stats`] is a set of atomic counters, for a `static` that any interrupt
+//! bumps with [`Stats::record`], no critical section needed:
+//!
+//! | [`Counter`]  | Counts                                                  |
+//! |--------------|---------------------------------------------------------|
+//! | `Presses`    | button presses taken                                    |
+//! | `Bounces`    | edges of the button rejected by the debouncing          |
+//! | `Overruns`   | timeouts of a timer arriving before the last was served |
+//! | `Overflows`  | events dropped, a queue being full                      |
+//!
+//! [`Stats::snapshot`] reads them all, as a [`Snapshot`], and a [`Reporter`]
+//! hands one over once a period, with the counts since its last one, for a
+//! periodic log line. The counters wrap at 2^32, and so do the differences.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// What a counter of [`Stats`] counts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Counter {
+    Presses,
+    Bounces,
+    Overruns,
+    Overflows,
+}
+
+/// The counters at one time, or their change over a period.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Snapshot {
+    pub presses: u32,
+    pub bounces: u32,
+    pub overruns: u32,
+    pub overflows: u32,
+}
+
+impl Snapshot {
+    /// The counts from `earlier` to `self`.
+    pub fn since(self, earlier: Snapshot) -> Snapshot {
+        Snapshot {
+            presses: self.presses.wrapping_sub(earlier.presses),
+            bounces: self.bounces.wrapping_sub(earlier.bounces),
+            overruns: self.overruns.wrapping_sub(earlier.overruns),
+            overflows: self.overflows.wrapping_sub(earlier.overflows),
+        }
+    }
+}
+
+/// The counters, all from zero.
+pub struct Stats {
+    counts: [AtomicU32; 4],
+}
+
+impl Stats {
+    /// All the counters at zero, for a `static`.
+    pub const fn new() -> Self {
+        Stats { counts: [const { AtomicU32::new(0) }; 4] }
+    }
+
+    /// Counts one of `counter`.
+    pub fn record(&self, counter: Counter) {
+        self.add(counter, 1);
+    }
+
+    /// Counts `count` of `counter`.
+    pub fn add(&self, counter: Counter, count: u32) {
+        self.counts[counter as usize].fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// The count of `counter`.
+    pub fn get(&self, counter: Counter) -> u32 {
+        self.counts[counter as usize].load(Ordering::Relaxed)
+    }
+
+    /// All the counts.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            presses: self.get(Counter::Presses),
+            bounces: self.get(Counter::Bounces),
+            overruns: self.get(Counter::Overruns),
+            overflows: self.get(Counter::Overflows),
+        }
+    }
+
+    /// Sets every counter back to zero.
+    pub fn reset(&self) {
+        for count in &self.counts {
+            count.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The counts since the last report, once every `period` milliseconds.
+pub struct Reporter {
+    period: u32,
+    last_ms: u32,
+    last: Snapshot,
+}
+
+impl Reporter {
+    /// A reporter due `period` milliseconds after the time 0.
+    pub const fn new(period: u32) -> Self {
+        Reporter { period, last_ms: 0, last: Snapshot { presses: 0, bounces: 0, overruns: 0, overflows: 0 } }
+    }
+
+    /// At `now_ms`, returns the counts of `stats` since the last report, if
+    /// a period has passed since it.
+    pub fn poll(&mut self, stats: &Stats, now_ms: u32) -> Option<Snapshot> {
+        if now_ms.wrapping_sub(self.last_ms) < self.period {
+            return None;
+        }
+        self.last_ms = now_ms;
+        let snapshot = stats.snapshot();
+        Some(snapshot.since(core::mem::replace(&mut self.last, snapshot)))
+    }
+}
@@ -0,0 +1,75 @@
+//! Temperature compensation of timer periods clocked from the HSI.
+//!
+//! Without a crystal the timers count the HSI16, an RC oscillator trimmed at
+//! the factory at one temperature: away from it the frequency moves by
+//! thousands of ppm, and a 1 s blink by milliseconds, so an hour of ticks
+//! drifts by seconds. The drift follows the temperature closely, though,
+//! and the core's own sensor tells it.
+//!
+//! A [`Curve`] gives the HSI's error in ppm, positive when it runs fast, at
+//! a few temperatures, and [`Curve::ppm_at`] interpolates between them;
+//! [`corrected`] stretches a count of timer ticks by that error, so the
+//! period comes out right in real time:
+//!
+//! ```text
+//! let ppm = CURVE.ppm_at(celsius);
+//! let arr = corrected(arr + 1, ppm) - 1;
+//! ```
+//!
+//! [`TYPICAL`] is only a starting point, of the usual shape; a curve measured
+//! on the board, against a crystal or a frequency counter on MCO, does much
+//! better. The correction is as fine as a tick of the timer: one in 65536
+//! on a 16-bit auto-reload, 15 ppm.
+
+/// The HSI's error, in ppm, at some temperatures in degrees Celsius.
+pub struct Curve {
+    points: &'static [(i16, i32)],
+}
+
+/// A rough HSI16 curve: exact at the factory's 30 °C, slow when cold and
+/// fast when hot, 0.5 % at the ends of the range.
+pub const TYPICAL: Curve = Curve::new(&[(-40, -5000), (0, -1800), (30, 0), (60, 1500), (85, 2800), (125, 5000)]);
+
+impl Curve {
+    /// The curve through `points`, `(celsius, ppm)` by rising temperature.
+    ///
+    /// Panics, at build time for a `const`, if the temperatures do not rise.
+    pub const fn new(points: &'static [(i16, i32)]) -> Self {
+        let mut i = 1;
+        while i < points.len() {
+            assert!(points[i - 1].0 < points[i].0);
+            i += 1;
+        }
+        Curve { points }
+    }
+
+    /// The error at `celsius`, on the line between the two points around
+    /// it, or the point at the end of the curve beyond them; 0 for a curve
+    /// of no point.
+    pub fn ppm_at(&self, celsius: i16) -> i32 {
+        let (Some(&first), Some(&last)) = (self.points.first(), self.points.last()) else {
+            return 0;
+        };
+        if celsius <= first.0 {
+            return first.1;
+        }
+        if celsius >= last.0 {
+            return last.1;
+        }
+        let (below, above) = self
+            .points
+            .windows(2)
+            .map(|pair| (pair[0], pair[1]))
+            .find(|(_, above)| celsius < above.0)
+            .unwrap_or((last, last));
+        let span = (above.0 - below.0) as i32;
+        below.1 + (above.1 - below.1) * (celsius - below.0) as i32 / span
+    }
+}
+
+/// `ticks` of a clock `ppm` fast, the count lasting as long as `ticks` of
+/// the nominal clock, to the nearest tick.
+pub fn corrected(ticks: u32, ppm: i32) -> u32 {
+    let scaled = ticks as i64 * (1_000_000 + ppm as i64);
+    ((scaled + 500_000) / 1_000_000).clamp(1, u32::MAX as i64) as u32
+}
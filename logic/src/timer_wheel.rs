@@ -0,0 +1,138 @@
+//! Software timer wheel: many one-shot timers on a single periodic tick.
+//!
+//! A hardware timer per delay runs out quickly, and most delays of an
+//! application (a beep, a debounce, a timeout) need no better than a
+//! millisecond. [`TimerWheel`] keeps up to `TIMERS` pending events on a ring
+//! of `SLOTS` slots, one slot per tick: a timer due in `ticks` sits in the
+//! slot the cursor reaches then, and counts the turns of the ring it still
+//! has to wait. Call [`TimerWheel::tick`] from a periodic interrupt, SysTick
+//! for example, then take the events that fell due with
+//! [`TimerWheel::expired`] until it returns `None`. Shared with other
+//! interrupts, the wheel goes in a `Mutex` like any other global.
+//!
+//! Events are plain values, an enum of the application for example, handed
+//! back as they were scheduled; an event can schedule the next one while it
+//! is being handled.
+//!
+//! The firmware crate runs the wheel on SysTick, ticking or tickless.
+
+/// Timer wheel errors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// All the timers are pending.
+    Full,
+}
+
+#[derive(Clone, Copy)]
+struct Timer<E> {
+    event: E,
+    slot: usize,
+    // Turns of the ring left before the timer is due.
+    rounds: u32,
+    due: bool,
+}
+
+/// Up to `TIMERS` one-shot timers on a ring of `SLOTS` ticks.
+pub struct TimerWheel<E, const SLOTS: usize, const TIMERS: usize> {
+    cursor: usize,
+    timers: [Option<Timer<E>>; TIMERS],
+}
+
+impl<E: Copy + PartialEq, const SLOTS: usize, const TIMERS: usize> TimerWheel<E, SLOTS, TIMERS> {
+    /// An empty wheel, for a `static`.
+    pub const fn new() -> Self {
+        TimerWheel { cursor: 0, timers: [None; TIMERS] }
+    }
+
+    /// Hands `event` back from [`TimerWheel::expired`] after `ticks` ticks,
+    /// at least 1.
+    pub fn schedule(&mut self, ticks: u32, event: E) -> Result<(), Error> {
+        let ticks = ticks.max(1);
+        let free = self.timers.iter_mut().find(|timer| timer.is_none()).ok_or(Error::Full)?;
+        // The cursor reaches the slot first after `(ticks - 1) % SLOTS + 1`
+        // ticks, then once every turn.
+        *free = Some(Timer {
+            event,
+            slot: (self.cursor + ticks as usize % SLOTS) % SLOTS,
+            rounds: (ticks - 1) / SLOTS as u32,
+            due: false,
+        });
+        Ok(())
+    }
+
+    /// Drops the pending timers of `event`.
+    ///
+    /// Returns `false` if none was pending.
+    pub fn cancel(&mut self, event: E) -> bool {
+        let mut cancelled = false;
+        for timer in self.timers.iter_mut() {
+            if timer.is_some_and(|timer| timer.event == event) {
+                *timer = None;
+                cancelled = true;
+            }
+        }
+        cancelled
+    }
+
+    /// Returns `true` if a timer of `event` is pending.
+    pub fn is_scheduled(&self, event: E) -> bool {
+        self.timers.iter().flatten().any(|timer| timer.event == event)
+    }
+
+    /// Advances the wheel by one tick.
+    pub fn tick(&mut self) {
+        self.cursor = (self.cursor + 1) % SLOTS;
+        for timer in self.timers.iter_mut().flatten() {
+            if timer.slot != self.cursor {
+                continue;
+            }
+            if timer.rounds == 0 {
+                timer.due = true;
+            } else {
+                timer.rounds -= 1;
+            }
+        }
+    }
+
+    /// Advances the wheel by `ticks` ticks, as many calls of
+    /// [`TimerWheel::tick`] would.
+    pub fn advance(&mut self, ticks: u32) {
+        if self.timers.iter().all(Option::is_none) {
+            self.cursor = (self.cursor + ticks as usize % SLOTS) % SLOTS;
+            return;
+        }
+        for _ in 0..ticks {
+            self.tick();
+        }
+    }
+
+    /// Ticks until the nearest pending timer falls due: 0 for one due
+    /// already, `None` with no timer pending.
+    pub fn next_due(&self) -> Option<u32> {
+        self.timers
+            .iter()
+            .flatten()
+            .map(|timer| {
+                if timer.due {
+                    return 0;
+                }
+                let ahead = (timer.slot + SLOTS - self.cursor) % SLOTS;
+                let ahead = if ahead == 0 { SLOTS } else { ahead };
+                ahead as u32 + timer.rounds * SLOTS as u32
+            })
+            .min()
+    }
+
+    /// Takes one event that fell due at the last tick, if any.
+    pub fn expired(&mut self) -> Option<E> {
+        let timer = self.timers.iter_mut().find(|timer| timer.is_some_and(|timer| timer.due))?;
+        timer.take().map(|timer| timer.event)
+    }
+}
+
+impl<E: Copy + PartialEq, const SLOTS: usize, const TIMERS: usize> Default for TimerWheel<E, SLOTS, TIMERS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
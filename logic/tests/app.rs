@@ -0,0 +1,65 @@
+//! Mode transitions of the application state machine.
+
+use nucleo_g474re_logic::app::{Action, Event, Mode, StateMachine};
+use nucleo_g474re_logic::leds::Pattern;
+
+#[test]
+fn button_walks_the_modes() {
+    let mut app = StateMachine::new(10);
+    assert_eq!(app.mode(), Mode::Idle);
+    assert_eq!(app.handle(Event::Hold), Action::None);
+    assert_eq!(app.handle(Event::Press), Action::Entered(Mode::Blink));
+    assert_eq!(app.handle(Event::Press), Action::StepPeriod);
+    assert_eq!(app.handle(Event::Hold), Action::Entered(Mode::Config));
+    assert_eq!(app.handle(Event::Press), Action::NextPattern);
+    assert_eq!(app.handle(Event::Hold), Action::Entered(Mode::Blink));
+    assert_eq!(app.handle(Event::Tick), Action::None);
+}
+
+#[test]
+fn config_times_out() {
+    let mut app = StateMachine::new(3);
+    app.handle(Event::Enter(Mode::Config));
+    app.handle(Event::Tick);
+    app.handle(Event::Tick);
+    // A press starts the timeout again.
+    app.handle(Event::Press);
+    assert_eq!(app.handle(Event::Tick), Action::None);
+    assert_eq!(app.handle(Event::Tick), Action::None);
+    assert_eq!(app.handle(Event::Tick), Action::Entered(Mode::Blink));
+}
+
+#[test]
+fn fault_is_left_only_by_asking() {
+    let mut app = StateMachine::new(10);
+    app.handle(Event::Press);
+    assert_eq!(app.handle(Event::Fault), Action::Entered(Mode::Fault));
+    for event in [Event::Press, Event::Tick, Event::Fault, Event::Enter(Mode::Blink)] {
+        assert_eq!(app.handle(event), Action::None);
+    }
+    assert_eq!(app.mode(), Mode::Fault);
+    assert_eq!(app.handle(Event::Clear), Action::Entered(Mode::Idle));
+
+    app.handle(Event::Enter(Mode::Fault));
+    assert_eq!(app.handle(Event::Hold), Action::Entered(Mode::Idle));
+}
+
+#[test]
+fn shell_enters_any_mode() {
+    let mut app = StateMachine::new(10);
+    assert_eq!(app.handle(Event::Enter(Mode::Config)), Action::Entered(Mode::Config));
+    assert_eq!(app.handle(Event::Enter(Mode::Config)), Action::None);
+    assert_eq!(app.handle(Event::Enter(Mode::Idle)), Action::Entered(Mode::Idle));
+    assert_eq!(app.handle(Event::Clear), Action::None);
+}
+
+#[test]
+fn names_and_patterns() {
+    for mode in [Mode::Idle, Mode::Blink, Mode::Config, Mode::Fault] {
+        assert_eq!(Mode::from_name(mode.name()), Some(mode));
+    }
+    assert_eq!(Mode::from_name("sleep"), None);
+    assert_eq!(Mode::Blink.pattern(4), Pattern::blink(4, 4));
+    assert_eq!(Mode::Blink.pattern(0), Pattern::blink(1, 1));
+    assert_eq!(Mode::Fault.pattern(4), Pattern::On);
+}
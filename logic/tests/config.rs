@@ -0,0 +1,60 @@
+//! Validation and saving of the blink configuration.
+
+use nucleo_g474re_logic::config::{AppConfig, Changes, Error, Pattern, MAX_PERIOD_MS};
+
+#[test]
+fn setters_check_their_range() {
+    let mut config = AppConfig::<1000, 125, 2>::new();
+    assert_eq!(config.set_period_ms(124), Err(Error::InvalidPeriod));
+    assert_eq!(config.set_period_ms(MAX_PERIOD_MS + 1), Err(Error::InvalidPeriod));
+    assert_eq!(config.set_brightness(101), Err(Error::InvalidBrightness));
+    assert_eq!(Pattern::from_name("strobe"), Err(Error::InvalidPattern));
+    assert_eq!(config.take_changes(), Changes::default());
+    assert_eq!(config, AppConfig::new());
+}
+
+#[test]
+fn changes_recorded_once() {
+    let mut config = AppConfig::<1000, 125, 2>::new();
+    config.set_period_ms(500).unwrap();
+    config.set_brightness(100).unwrap();
+    config.set_pattern(Pattern::Solid);
+    assert_eq!(config.take_changes(), Changes { period: true, pattern: true, brightness: false });
+    assert!(!config.take_changes().any());
+}
+
+#[test]
+fn steps_wrap_at_the_shortest() {
+    let mut thirds = AppConfig::<900, 100, 3>::new();
+    let periods: Vec<_> = (0..4).map(|_| thirds.step_period()).collect();
+    assert_eq!(periods, [300, 100, 900, 300]);
+
+    let mut halves = AppConfig::<1000, 125, 2>::new();
+    let periods: Vec<_> = (0..5).map(|_| halves.step_period()).collect();
+    assert_eq!(periods, [500, 250, 125, 1000, 500]);
+}
+
+#[test]
+fn words_round_trip() {
+    let mut config = AppConfig::<1000, 125, 2>::new();
+    config.set_period_ms(300).unwrap();
+    config.set_pattern(Pattern::Off);
+    config.set_brightness(20).unwrap();
+    config.take_changes();
+
+    let words = config.to_words();
+    assert_eq!(AppConfig::<1000, 125, 2>::from_words(words), Ok(config));
+    // Leftover contents, and a period out of range.
+    assert_eq!(AppConfig::<1000, 125, 2>::from_words([0, 0]), Err(Error::Corrupt));
+    assert_eq!(AppConfig::<1000, 125, 2>::from_words([words[0], 50]), Err(Error::Corrupt));
+}
+
+#[test]
+fn level_of_the_brightness() {
+    let mut config = AppConfig::<1000, 125, 2>::new();
+    assert_eq!(config.level(), 255);
+    config.set_brightness(0).unwrap();
+    assert_eq!(config.level(), 0);
+    config.set_brightness(50).unwrap();
+    assert_eq!(config.level(), 127);
+}
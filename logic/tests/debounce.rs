@@ -0,0 +1,64 @@
+//! The debounce algorithms compared on recorded bounce traces.
+
+use nucleo_g474re_logic::debounce::{Debouncer, Event, Integrator, Timeout};
+
+// B1 read every millisecond through a press and its release, `#` pressed:
+// the contact bounces for 8 ms closing and 7 ms opening.
+const PRESS: &str = "_____#_#__##_#####################################_##_#__#___#________________";
+// A 2 ms spike on an idle line, as from a motor starting nearby.
+const SPIKE: &str = "__________##____________________________________________________________________";
+// Two presses 30 ms apart, the second bouncing too.
+const DOUBLE: &str = "____###########################_____________________________#_##_#############################_________________";
+
+// Feeds `trace` to `debouncer` a millisecond a sample, returning the events
+// with their times.
+fn run(debouncer: &mut impl Debouncer, trace: &str) -> Vec<(u32, Event)> {
+    trace
+        .bytes()
+        .enumerate()
+        .filter_map(|(ms, level)| debouncer.update(level == b'#', ms as u32).map(|event| (ms as u32, event)))
+        .collect()
+}
+
+#[test]
+fn both_take_one_press_of_a_bouncing_contact() {
+    let timeout = run(&mut Timeout::new(20), PRESS);
+    let integrator = run(&mut Integrator::new(5), PRESS);
+    assert_eq!(timeout, [(5, Event::Pressed), (50, Event::Released)]);
+    assert_eq!(integrator, [(16, Event::Pressed), (63, Event::Released)]);
+}
+
+#[test]
+fn timeout_answers_first() {
+    let timeout = run(&mut Timeout::new(20), PRESS);
+    let integrator = run(&mut Integrator::new(5), PRESS);
+    for (fast, slow) in timeout.iter().zip(&integrator) {
+        assert_eq!(fast.1, slow.1);
+        assert!(fast.0 < slow.0);
+    }
+}
+
+#[test]
+fn integrator_rides_out_a_spike() {
+    assert_eq!(run(&mut Integrator::new(5), SPIKE), []);
+    // The timeout takes it for a press, released once the window is over.
+    assert_eq!(run(&mut Timeout::new(20), SPIKE), [(10, Event::Pressed), (30, Event::Released)]);
+}
+
+#[test]
+fn both_take_two_presses() {
+    let pressed = |events: Vec<(u32, Event)>| events.iter().filter(|(_, e)| *e == Event::Pressed).count();
+    assert_eq!(pressed(run(&mut Timeout::new(20), DOUBLE)), 2);
+    assert_eq!(pressed(run(&mut Integrator::new(5), DOUBLE)), 2);
+}
+
+#[test]
+fn timeout_window_across_the_wrap() {
+    let mut timeout = Timeout::new(20);
+    assert_eq!(timeout.update(true, 0), Some(Event::Pressed));
+    assert_eq!(timeout.update(false, 30), Some(Event::Released));
+    assert_eq!(timeout.update(true, u32::MAX - 5), Some(Event::Pressed));
+    assert_eq!(timeout.update(false, 10), None);
+    assert!(timeout.is_pressed());
+    assert_eq!(timeout.update(false, 14), Some(Event::Released));
+}
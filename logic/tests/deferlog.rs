@@ -0,0 +1,77 @@
+//! The deferred log queue: order, overflow, and pushes from many threads.
+
+use nucleo_g474re_logic::deferlog::{Queue, Record};
+
+#[test]
+fn records_come_out_in_order() {
+    let queue: Queue<4> = Queue::new();
+    assert_eq!(queue.pop(), None);
+    assert!(queue.push(Record::new(1, [10, 0])));
+    assert!(queue.push(Record::new(2, [20, 21])));
+    assert_eq!(queue.pop(), Some(Record::new(1, [10, 0])));
+    assert_eq!(queue.pop(), Some(Record::new(2, [20, 21])));
+    assert_eq!(queue.pop(), None);
+}
+
+#[test]
+fn full_queue_drops_and_counts() {
+    let queue: Queue<4> = Queue::new();
+    for id in 0..6 {
+        queue.push(Record::new(id, [0; 2]));
+    }
+    assert_eq!(queue.dropped(), 2);
+    assert_eq!(queue.dropped(), 0);
+    // The first four kept, and room again once one is out.
+    assert_eq!(queue.pop().map(|record| record.id), Some(0));
+    assert!(queue.push(Record::new(6, [0; 2])));
+    let ids: Vec<u16> = std::iter::from_fn(|| queue.pop()).map(|record| record.id).collect();
+    assert_eq!(ids, [1, 2, 3, 6]);
+}
+
+#[test]
+fn slots_are_reused_lap_after_lap() {
+    let queue: Queue<2> = Queue::new();
+    for id in 0..1000 {
+        assert!(queue.push(Record::new(id, [id as u32, 0])));
+        assert_eq!(queue.pop(), Some(Record::new(id, [id as u32, 0])));
+    }
+    assert_eq!(queue.dropped(), 0);
+}
+
+#[test]
+fn concurrent_pushes_arrive_once() {
+    static QUEUE: Queue<64> = Queue::new();
+    const PRODUCERS: u16 = 4;
+    const EACH: u32 = 10_000;
+
+    let producers: Vec<_> = (0..PRODUCERS)
+        .map(|id| {
+            std::thread::spawn(move || {
+                for n in 0..EACH {
+                    while !QUEUE.push(Record::new(id, [n, 0])) {
+                        std::thread::yield_now();
+                    }
+                }
+            })
+        })
+        .collect();
+
+    // Each producer's records in its order, none lost or repeated.
+    let mut next = [0_u32; PRODUCERS as usize];
+    let mut received = 0;
+    while received < PRODUCERS as u32 * EACH {
+        match QUEUE.pop() {
+            Some(record) => {
+                assert_eq!(record.args[0], next[record.id as usize]);
+                next[record.id as usize] += 1;
+                received += 1;
+            }
+            None => std::thread::yield_now(),
+        }
+    }
+    for producer in producers {
+        producer.join().unwrap();
+    }
+    assert_eq!(QUEUE.pop(), None);
+    assert_eq!(next, [EACH; PRODUCERS as usize]);
+}
@@ -0,0 +1,87 @@
+//! The executor: polling order, counters, and the slots of removed tasks.
+
+use std::cell::RefCell;
+
+use nucleo_g474re_logic::executor::{Error, Executor, Task};
+
+// A task that logs its polls and runs every `period` ms.
+struct Every<'l> {
+    name: char,
+    period: u32,
+    last: u32,
+    log: &'l RefCell<Vec<(char, u32)>>,
+}
+
+impl Task for Every<'_> {
+    fn poll(&mut self, now_ms: u32) -> bool {
+        self.log.borrow_mut().push((self.name, now_ms));
+        if now_ms.wrapping_sub(self.last) < self.period {
+            return false;
+        }
+        self.last = now_ms;
+        true
+    }
+}
+
+#[test]
+fn tasks_polled_in_turn_and_counted() {
+    let log = RefCell::new(Vec::new());
+    let mut a = Every { name: 'a', period: 2, last: 0, log: &log };
+    let mut b = Every { name: 'b', period: 1, last: 0, log: &log };
+    let mut executor = Executor::<4>::new();
+    let a_id = executor.spawn("a", &mut a).unwrap();
+    let b_id = executor.spawn("b", &mut b).unwrap();
+
+    let busy: Vec<bool> = (1..=4).map(|now| executor.run(now)).collect();
+    assert_eq!(busy, [true, true, true, true]);
+    assert_eq!(executor.passes(), 4);
+
+    let a_stats = executor.stats(a_id).unwrap();
+    assert_eq!((a_stats.name, a_stats.polls, a_stats.runs), ("a", 4, 2));
+    let b_stats = executor.stats(b_id).unwrap();
+    assert_eq!((b_stats.polls, b_stats.runs), (4, 4));
+    assert_eq!(log.into_inner()[..4], [('a', 1), ('b', 1), ('a', 2), ('b', 2)]);
+}
+
+#[test]
+fn closures_are_tasks() {
+    let mut count = 0;
+    let mut counter = |_now: u32| {
+        count += 1;
+        count % 3 == 0
+    };
+    let mut idle = |_now: u32| false;
+    let mut executor = Executor::<2>::new();
+    executor.spawn("counter", &mut counter).unwrap();
+    executor.spawn("idle", &mut idle).unwrap();
+
+    let busy: Vec<bool> = (0..3).map(|now| executor.run(now)).collect();
+    assert_eq!(busy, [false, false, true]);
+    let runs: Vec<(&str, u32)> = executor.tasks().map(|stats| (stats.name, stats.runs)).collect();
+    assert_eq!(runs, [("counter", 1), ("idle", 0)]);
+    assert_eq!(count, 3);
+}
+
+#[test]
+fn full_and_remove() {
+    let mut first = |_now: u32| true;
+    let mut second = |_now: u32| true;
+    let mut third = |_now: u32| true;
+    let mut executor = Executor::<2>::new();
+    let first_id = executor.spawn("first", &mut first).unwrap();
+    executor.spawn("second", &mut second).unwrap();
+    executor.run(0);
+
+    let mut extra = |_now: u32| true;
+    assert_eq!(executor.spawn("extra", &mut extra).err(), Some(Error::Full));
+    assert!(executor.remove(first_id));
+    assert!(!executor.remove(first_id));
+    assert_eq!(executor.stats(first_id), None);
+
+    // The free slot goes to the next task, polled first, its counters new.
+    let third_id = executor.spawn("third", &mut third).unwrap();
+    assert_eq!(third_id, first_id);
+    executor.run(1);
+    let names: Vec<(&str, u32)> = executor.tasks().map(|stats| (stats.name, stats.polls)).collect();
+    assert_eq!(names, [("third", 1), ("second", 2)]);
+}
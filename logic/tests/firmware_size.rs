@@ -0,0 +1,80 @@
+//! Flash and RAM of the `minimal` firmware, the blink alone.
+//!
+//! Builds the main binary of the parent crate in release, with
+//! `--no-default-features --features minimal`, in its own `target/size`
+//! directory, and adds up the sections of the ELF: the ones loaded into
+//! flash, and the ones in RAM. `--nocapture` prints both:
+//!
+//! ```text
+//! cargo test --test firmware_size -- --nocapture
+//! ```
+//!
+//! The test fails past the budgets below, to catch a change that pulls
+//! logging or a support module back into the minimal build.
+
+use std::path::Path;
+use std::process::Command;
+
+// The minimal build measured 8084 bytes of flash and 36 bytes of RAM.
+const FLASH_BUDGET: u32 = 10 * 1024;
+const RAM_BUDGET: u32 = 256;
+
+const BINARY: &str = "NUCLEO-G474RE-interrupt-blink-for-embedded-rust";
+
+// Section flags and types of the ELF32 section headers.
+const SHF_WRITE: u32 = 0x1;
+const SHF_ALLOC: u32 = 0x2;
+const SHT_NOBITS: u32 = 8;
+
+fn u16_at(elf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(elf[offset..offset + 2].try_into().unwrap())
+}
+
+fn u32_at(elf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(elf[offset..offset + 4].try_into().unwrap())
+}
+
+// The flash and RAM bytes of a little-endian ELF32: the allocated sections
+// with contents, `.data` included, and the writable ones, `.bss` included.
+fn flash_and_ram(elf: &[u8]) -> (u32, u32) {
+    assert_eq!(&elf[..6], b"\x7fELF\x01\x01", "not a little-endian ELF32");
+    let section_headers = u32_at(elf, 0x20) as usize;
+    let entry_size = u16_at(elf, 0x2E) as usize;
+    let sections = u16_at(elf, 0x30) as usize;
+
+    let (mut flash, mut ram) = (0, 0);
+    for section in 0..sections {
+        let header = section_headers + section * entry_size;
+        let (kind, flags, size) = (u32_at(elf, header + 4), u32_at(elf, header + 8), u32_at(elf, header + 20));
+        if flags & SHF_ALLOC == 0 {
+            continue;
+        }
+        if kind != SHT_NOBITS {
+            flash += size;
+        }
+        if flags & SHF_WRITE != 0 {
+            ram += size;
+        }
+    }
+    (flash, ram)
+}
+
+#[test]
+fn minimal_build_fits_its_budget() {
+    let firmware = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let status = Command::new(env!("CARGO"))
+        .current_dir(firmware)
+        .args(["build", "--release", "--no-default-features", "--features", "minimal"])
+        .args(["--bin", BINARY, "--target-dir", "target/size"])
+        .status()
+        .expect("cannot run cargo");
+    assert!(status.success(), "the minimal build failed");
+
+    let elf = std::fs::read(firmware.join("target/size/thumbv7em-none-eabihf/release").join(BINARY))
+        .expect("cannot read the firmware");
+    let (flash, ram) = flash_and_ram(&elf);
+    println!("minimal build: {} bytes of flash, {} bytes of RAM", flash, ram);
+
+    assert!(flash <= FLASH_BUDGET, "{} bytes of flash, {} budgeted", flash, FLASH_BUDGET);
+    assert!(ram <= RAM_BUDGET, "{} bytes of RAM, {} budgeted", ram, RAM_BUDGET);
+}
@@ -0,0 +1,95 @@
+//! Debouncing of the keypad, on a one-key pad of mock pins.
+
+use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction};
+
+use nucleo_g474re_logic::keypad::{Event, Key, Keypad, DEBOUNCE_SCANS};
+
+const KEY: Key = Key { row: 0, col: 0 };
+
+// The row set high then driven low by `new`, and again by each scan.
+fn row(scans: usize) -> PinMock {
+    let mut expectations = vec![Transaction::set(State::High), Transaction::set(State::Low)];
+    for _ in 0..scans {
+        expectations.push(Transaction::set(State::High));
+        expectations.push(Transaction::set(State::Low));
+    }
+    PinMock::new(&expectations)
+}
+
+// The column read once per scan, low for a key down.
+fn column(downs: &[bool]) -> PinMock {
+    let expectations: Vec<_> = downs
+        .iter()
+        .map(|&down| Transaction::get(if down { State::Low } else { State::High }))
+        .collect();
+    PinMock::new(&expectations)
+}
+
+// Scans a one-key pad through `downs`, returning the events after each scan.
+fn scan(downs: &[bool]) -> Vec<Option<Event>> {
+    let mut row = row(downs.len());
+    let mut col = column(downs);
+    let mut keypad = Keypad::new([row.clone()], [col.clone()]);
+    let events = downs
+        .iter()
+        .map(|_| {
+            keypad.scan();
+            keypad.next_event()
+        })
+        .collect();
+    row.done();
+    col.done();
+    events
+}
+
+#[test]
+fn press_after_agreeing_scans() {
+    let events = scan(&[true; DEBOUNCE_SCANS as usize]);
+    let (last, before) = events.split_last().unwrap();
+    assert!(before.iter().all(Option::is_none));
+    assert_eq!(*last, Some(Event::Down(KEY)));
+}
+
+#[test]
+fn bounce_restarts_the_count() {
+    let n = DEBOUNCE_SCANS as usize;
+    let mut downs = vec![true; n - 1];
+    downs.push(false);
+    downs.extend(vec![true; n]);
+    let events = scan(&downs);
+    assert_eq!(events.iter().filter(|event| event.is_some()).count(), 1);
+    assert_eq!(events.last().copied().flatten(), Some(Event::Down(KEY)));
+}
+
+#[test]
+fn release_after_agreeing_scans() {
+    let n = DEBOUNCE_SCANS as usize;
+    let mut downs = vec![true; n];
+    downs.extend(vec![false; n]);
+    let events: Vec<_> = scan(&downs).into_iter().flatten().collect();
+    assert_eq!(events, [Event::Down(KEY), Event::Up(KEY)]);
+}
+
+#[test]
+fn rows_scanned_in_turn() {
+    // Two rows: each scan releases the row read and drives the next.
+    let mut first = PinMock::new(&[
+        Transaction::set(State::High),
+        Transaction::set(State::Low),
+        Transaction::set(State::High),
+        Transaction::set(State::Low),
+    ]);
+    let mut second = PinMock::new(&[
+        Transaction::set(State::High),
+        Transaction::set(State::Low),
+        Transaction::set(State::High),
+    ]);
+    let mut col = column(&[false, true]);
+    let mut keypad = Keypad::new([first.clone(), second.clone()], [col.clone()]);
+    keypad.scan();
+    keypad.scan();
+    assert!(!keypad.is_pressed(Key { row: 1, col: 0 }));
+    first.done();
+    second.done();
+    col.done();
+}
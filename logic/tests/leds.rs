@@ -0,0 +1,60 @@
+//! LED patterns, on mock pins.
+
+use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction};
+
+use nucleo_g474re_logic::leds::{Error, Leds, Pattern};
+
+fn levels(states: &[State]) -> PinMock {
+    let expectations: Vec<_> = states.iter().map(|&state| Transaction::set(state)).collect();
+    PinMock::new(&expectations)
+}
+
+#[test]
+fn blink_on_and_off_ticks() {
+    let blink = Pattern::blink(2, 3);
+    let lit: Vec<_> = (0..10).map(|tick| blink.is_lit(tick)).collect();
+    assert_eq!(lit, [true, true, false, false, false, true, true, false, false, false]);
+
+    let shifted = Pattern::Blink { on: 2, off: 3, phase: 1 };
+    assert!(shifted.is_lit(0));
+    assert!(!shifted.is_lit(1));
+    assert!(!Pattern::Blink { on: 0, off: 0, phase: 0 }.is_lit(0));
+}
+
+#[test]
+fn ticks_write_the_patterns() {
+    use State::{High, Low};
+    // Off at start, then ticks 1 to 3.
+    let mut on = levels(&[Low, High, High, High]);
+    let mut blink = levels(&[Low, Low, High, Low]);
+    let mut leds = Leds::new([on.clone(), blink.clone()]);
+    leds.set(0, Pattern::On).unwrap();
+    leds.set(1, Pattern::blink(1, 1)).unwrap();
+    assert_eq!(leds.set(2, Pattern::On), Err(Error::InvalidLed));
+    for _ in 0..3 {
+        leds.tick();
+    }
+    on.done();
+    blink.done();
+}
+
+#[test]
+fn bar_and_binary() {
+    let mut pins: Vec<_> = (0..4).map(|_| levels(&[State::Low])).collect();
+    let mut leds = Leds::new([pins[0].clone(), pins[1].clone(), pins[2].clone(), pins[3].clone()]);
+
+    leds.show_bar(50, 100);
+    let bar: Vec<_> = (0..4).map(|index| leds.pattern(index).unwrap()).collect();
+    assert_eq!(bar, [Pattern::On, Pattern::On, Pattern::Off, Pattern::Off]);
+    leds.show_bar(1, 100);
+    assert_eq!(leds.pattern(0), Some(Pattern::On));
+    assert_eq!(leds.pattern(1), Some(Pattern::Off));
+
+    leds.show_binary(0b1010);
+    let bits: Vec<_> = (0..4).map(|index| leds.pattern(index).unwrap()).collect();
+    assert_eq!(bits, [Pattern::Off, Pattern::On, Pattern::Off, Pattern::On]);
+
+    for pin in pins.iter_mut() {
+        pin.done();
+    }
+}
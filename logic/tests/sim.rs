@@ -0,0 +1,115 @@
+//! The host simulation: scripts, and the application on the fakes.
+
+use std::cell::Cell;
+
+use nucleo_g474re_logic::app::{App, Mode, TICK_MS};
+use nucleo_g474re_logic::sim::{self, Board, Error, SimButton, SimLed, SimTimer, Stimulus, Timeline};
+
+// Plays `script` through a board to its end and returns the app's logs and
+// replies, one string per line, and the LED toggles.
+fn play(script: &str, app_check: impl FnOnce(&App<SimLed, SimButton>)) -> (Vec<String>, u32) {
+    let mut timeline = Timeline::parse(script).unwrap();
+    let led = Cell::new(false);
+    let button = Cell::new(false);
+    let mut board = Board::new(SimLed::new(&led), SimButton::new(&button));
+    let mut out = String::new();
+    let (mut lit, mut toggles) = (false, 0);
+    for now in 0..=timeline.end_ms() {
+        while let Some(stimulus) = timeline.due(now) {
+            match stimulus {
+                Stimulus::Press => button.set(true),
+                Stimulus::Release => button.set(false),
+                Stimulus::Line(line) => board.run(line, &mut out),
+            }
+        }
+        board.poll(now, &mut out);
+        if led.get() != lit {
+            lit = led.get();
+            toggles += 1;
+        }
+    }
+    app_check(board.app());
+    (out.lines().map(str::to_string).collect(), toggles)
+}
+
+#[test]
+fn scripts_parse_in_order() {
+    let mut timeline = Timeline::parse("# a comment\n\n10 press\n 20  release \n20 shell mode config\n").unwrap();
+    assert_eq!(timeline.end_ms(), 20);
+    assert_eq!(timeline.due(9), None);
+    assert_eq!(timeline.due(15), Some(Stimulus::Press));
+    assert_eq!(timeline.due(15), None);
+    assert_eq!(timeline.due(20), Some(Stimulus::Release));
+    assert_eq!(timeline.due(20), Some(Stimulus::Line("mode config")));
+    assert_eq!(timeline.due(1000), None);
+
+    assert_eq!(Timeline::parse("10 press\nten release\n").err(), Some(Error::InvalidStep(2)));
+    assert_eq!(Timeline::parse("10 jump\n").err(), Some(Error::InvalidStep(1)));
+    assert_eq!(Timeline::parse("10 shell\n").err(), Some(Error::InvalidStep(1)));
+    assert_eq!(Timeline::parse("10 press\n\n5 release\n").err(), Some(Error::OutOfOrder(3)));
+}
+
+#[test]
+fn timer_times_out_each_period() {
+    let mut timer = SimTimer::new(TICK_MS);
+    let ticks = (0..=10 * TICK_MS).filter(|&now| timer.poll(now)).count();
+    assert_eq!(ticks, 10);
+    // A late poll catches up a period at a time.
+    let mut timer = SimTimer::new(TICK_MS);
+    assert!(timer.poll(3 * TICK_MS));
+    assert!(timer.poll(3 * TICK_MS));
+    assert!(timer.poll(3 * TICK_MS));
+    assert!(!timer.poll(3 * TICK_MS));
+}
+
+#[test]
+fn presses_start_and_step_the_blink() {
+    let (log, toggles) = play("1000 press\n1100 release\n2000 press\n2100 release\n", |app| {
+        assert_eq!(app.mode(), Mode::Blink);
+        assert_eq!(app.config().period_ms(), 500);
+    });
+    assert_eq!(log, ["Mode: blink", "Delay Atual: 500 ms"]);
+    assert!(toggles > 0);
+}
+
+#[test]
+fn edges_leaving_the_level_are_nothing() {
+    let (led, button) = (Cell::new(false), Cell::new(false));
+    let mut app = App::new(SimLed::new(&led), SimButton::new(&button));
+    let mut out = String::new();
+    // Two edges for each change, the second seeing the level the first did.
+    for level in [true, false] {
+        button.set(level);
+        app.edge(|action, config| sim::log(&mut out, action, config));
+        app.edge(|action, config| sim::log(&mut out, action, config));
+    }
+    assert_eq!(out, "Mode: blink\n");
+}
+
+#[test]
+fn holds_go_to_config_and_back() {
+    let (log, _) = play(
+        "100 press\n200 release\n1000 press\n1700 release\n2000 press\n2100 release\n3000 press\n3700 release\n",
+        |app| assert_eq!(app.mode(), Mode::Blink),
+    );
+    assert_eq!(log, ["Mode: blink", "Mode: config", "Pattern: solid", "Mode: blink"]);
+}
+
+#[test]
+fn config_times_out() {
+    let (log, _) = play("100 press\n200 release\n1000 press\n1700 release\n7000 shell mode\n", |app| {
+        assert_eq!(app.mode(), Mode::Blink)
+    });
+    assert_eq!(log, ["Mode: blink", "Mode: config", "Mode: blink", "blink"]);
+}
+
+#[test]
+fn shell_faults_and_clears() {
+    let (log, toggles) = play(
+        "100 shell fault\n200 press\n300 release\n400 shell mode\n500 shell clear\n600 shell jump\n",
+        |app| assert_eq!(app.mode(), Mode::Idle),
+    );
+    assert_eq!(log[..4], ["Mode: fault", "fault", "Mode: idle", "commands:"]);
+    // Lit solid in Fault, from the first tick.
+    assert!(toggles >= 1);
+}
@@ -0,0 +1,46 @@
+//! Interrupt statistics counters and their periodic reports.
+
+use nucleo_g474re_logic::stats::{Counter, Reporter, Snapshot, Stats};
+
+#[test]
+fn counters_count_apart() {
+    let stats = Stats::new();
+    stats.record(Counter::Presses);
+    stats.record(Counter::Presses);
+    stats.add(Counter::Bounces, 5);
+    stats.record(Counter::Overflows);
+    assert_eq!(stats.snapshot(), Snapshot { presses: 2, bounces: 5, overruns: 0, overflows: 1 });
+    assert_eq!(stats.get(Counter::Bounces), 5);
+
+    stats.reset();
+    assert_eq!(stats.snapshot(), Snapshot::default());
+}
+
+#[test]
+fn differences_wrap() {
+    let stats = Stats::new();
+    stats.add(Counter::Overruns, u32::MAX);
+    let before = stats.snapshot();
+    stats.add(Counter::Overruns, 3);
+    assert_eq!(stats.snapshot().since(before).overruns, 3);
+}
+
+#[test]
+fn reports_once_a_period() {
+    static STATS: Stats = Stats::new();
+    let mut reporter = Reporter::new(1000);
+    STATS.record(Counter::Presses);
+    assert_eq!(reporter.poll(&STATS, 999), None);
+    assert_eq!(reporter.poll(&STATS, 1000), Some(Snapshot { presses: 1, ..Snapshot::default() }));
+
+    STATS.add(Counter::Bounces, 4);
+    assert_eq!(reporter.poll(&STATS, 1500), None);
+    let report = reporter.poll(&STATS, 2100).unwrap();
+    assert_eq!(report, Snapshot { bounces: 4, ..Snapshot::default() });
+
+    // Across the wrap of the milliseconds.
+    let mut late = Reporter::new(1000);
+    assert!(late.poll(&STATS, u32::MAX - 10).is_some());
+    assert_eq!(late.poll(&STATS, 500), None);
+    assert_eq!(late.poll(&STATS, 1000), Some(Snapshot::default()));
+}
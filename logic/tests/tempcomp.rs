@@ -0,0 +1,27 @@
+//! The HSI error curve and the corrected timer periods.
+
+use nucleo_g474re_logic::tempcomp::{corrected, Curve, TYPICAL};
+
+const CURVE: Curve = Curve::new(&[(0, -2000), (30, 0), (80, 1000)]);
+
+#[test]
+fn curve_interpolates_and_clamps() {
+    assert_eq!(CURVE.ppm_at(30), 0);
+    assert_eq!(CURVE.ppm_at(15), -1000);
+    assert_eq!(CURVE.ppm_at(55), 500);
+    assert_eq!(CURVE.ppm_at(-40), -2000);
+    assert_eq!(CURVE.ppm_at(125), 1000);
+    assert_eq!(Curve::new(&[]).ppm_at(25), 0);
+    assert_eq!(TYPICAL.ppm_at(30), 0);
+}
+
+#[test]
+fn fast_clock_counts_more_ticks() {
+    // 1 s of a 16 MHz clock 1000 ppm fast is 16 016 000 of its ticks.
+    assert_eq!(corrected(16_000_000, 1000), 16_016_000);
+    assert_eq!(corrected(16_000_000, -1000), 15_984_000);
+    assert_eq!(corrected(65_306, 0), 65_306);
+    // To the nearest tick, never none.
+    assert_eq!(corrected(65_306, 15), 65_307);
+    assert_eq!(corrected(1, -1_000_000), 1);
+}
@@ -0,0 +1,69 @@
+//! Deadlines of the timer wheel.
+
+use nucleo_g474re_logic::timer_wheel::{Error, TimerWheel};
+
+// The events expired after each of `ticks` ticks.
+fn run<const SLOTS: usize, const TIMERS: usize>(wheel: &mut TimerWheel<u8, SLOTS, TIMERS>, ticks: u32) -> Vec<(u32, u8)> {
+    let mut expired = Vec::new();
+    for tick in 1..=ticks {
+        wheel.tick();
+        while let Some(event) = wheel.expired() {
+            expired.push((tick, event));
+        }
+    }
+    expired
+}
+
+#[test]
+fn events_expire_on_their_tick() {
+    let mut wheel = TimerWheel::<u8, 8, 4>::new();
+    wheel.schedule(3, 1).unwrap();
+    wheel.schedule(1, 2).unwrap();
+    wheel.schedule(0, 3).unwrap();
+    assert_eq!(wheel.next_due(), Some(1));
+    assert_eq!(run(&mut wheel, 5), [(1, 2), (1, 3), (3, 1)]);
+    assert_eq!(wheel.next_due(), None);
+}
+
+#[test]
+fn deadlines_past_a_turn() {
+    let mut wheel = TimerWheel::<u8, 8, 4>::new();
+    wheel.advance(5);
+    for ticks in [8, 9, 16, 20] {
+        wheel.schedule(ticks, ticks as u8).unwrap();
+    }
+    assert_eq!(wheel.next_due(), Some(8));
+    assert_eq!(run(&mut wheel, 20), [(8, 8), (9, 9), (16, 16), (20, 20)]);
+}
+
+#[test]
+fn advance_is_many_ticks() {
+    let mut ticked = TimerWheel::<u8, 8, 4>::new();
+    let mut advanced = TimerWheel::<u8, 8, 4>::new();
+    for wheel in [&mut ticked, &mut advanced] {
+        wheel.schedule(13, 1).unwrap();
+        wheel.schedule(6, 2).unwrap();
+    }
+    run(&mut ticked, 10);
+    advanced.advance(10);
+    // The event of tick 6 waits to be taken, as after the tick itself.
+    assert_eq!(advanced.next_due(), Some(0));
+    assert_eq!(advanced.expired(), Some(2));
+    assert_eq!(ticked.next_due(), Some(3));
+    assert_eq!(advanced.next_due(), Some(3));
+}
+
+#[test]
+fn full_and_cancel() {
+    let mut wheel = TimerWheel::<u8, 8, 2>::new();
+    wheel.schedule(4, 1).unwrap();
+    wheel.schedule(2, 1).unwrap();
+    assert_eq!(wheel.schedule(1, 2), Err(Error::Full));
+    assert!(wheel.is_scheduled(1));
+
+    assert!(wheel.cancel(1));
+    assert!(!wheel.cancel(1));
+    assert!(!wheel.is_scheduled(1));
+    assert_eq!(wheel.next_due(), None);
+    assert_eq!(wheel.schedule(1, 2), Ok(()));
+}
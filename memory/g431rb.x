@@ -0,0 +1,7 @@
+MEMORY
+{
+  /* Endereço base da Flash: 0x08000000, Tamanho: 128K */
+  FLASH : ORIGIN = 0x08000000, LENGTH = 128K
+  /* Endereço base da RAM (SRAM1 + SRAM2 + CCM): 0x20000000, Tamanho: 32K */
+  RAM : ORIGIN = 0x20000000, LENGTH = 32K
+}
@@ -0,0 +1,112 @@
+#!/usr/bin/env python3
+"""Plots the `watch` RTT channel of the firmware live.
+
+Reads the `key=value` lines of `nucleo_g474re::watch::publish` from an RTT
+server, OpenOCD's say, or from standard input, and plots every key against
+the time the line arrived. With `--csv`, or without matplotlib, prints them
+as CSV instead.
+
+OpenOCD, with the board running a `watch` build:
+
+    openocd -f board/st_nucleo_g4.cfg \
+        -c 'init; rtt setup 0x20000000 0x20000 "SEGGER RTT"; rtt start; rtt server start 9091 1'
+    python3 scripts/watch_plot.py --port 9091
+"""
+
+import argparse
+import collections
+import socket
+import sys
+import threading
+import time
+
+# Points kept per key.
+HISTORY = 600
+
+
+def parse(line):
+    """The pairs of a line, as a dict of ints; malformed pairs are skipped."""
+    values = {}
+    for pair in line.split():
+        key, _, value = pair.partition("=")
+        try:
+            values[key] = int(value)
+        except ValueError:
+            pass
+    return values
+
+
+def lines(args):
+    """The lines of the channel, from the server or standard input."""
+    if args.port is None:
+        yield from sys.stdin
+        return
+    with socket.create_connection((args.host, args.port)) as connection:
+        for line in connection.makefile("r", encoding="ascii", errors="replace"):
+            yield line
+
+
+def print_csv(source):
+    keys = None
+    for line in source:
+        values = parse(line)
+        if not values:
+            continue
+        if keys is None:
+            keys = list(values)
+            print(",".join(["time"] + keys), flush=True)
+        print(",".join([f"{time.monotonic():.3f}"] + [str(values.get(key, "")) for key in keys]), flush=True)
+
+
+def plot(source, plt, animation):
+    start = time.monotonic()
+    series = collections.defaultdict(lambda: (collections.deque(maxlen=HISTORY), collections.deque(maxlen=HISTORY)))
+
+    def read():
+        for line in source:
+            now = time.monotonic() - start
+            for key, value in parse(line).items():
+                times, points = series[key]
+                times.append(now)
+                points.append(value)
+
+    threading.Thread(target=read, daemon=True).start()
+    figure = plt.figure("watch")
+
+    def update(_frame):
+        keys = sorted(series)
+        figure.clear()
+        for index, key in enumerate(keys):
+            axes = figure.add_subplot(len(keys), 1, index + 1)
+            times, points = series[key]
+            axes.plot(list(times), list(points))
+            axes.set_ylabel(key)
+        if keys:
+            axes.set_xlabel("s")
+
+    _ = animation.FuncAnimation(figure, update, interval=200, cache_frame_data=False)
+    plt.show()
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("--host", default="localhost", help="RTT server host (default: localhost)")
+    parser.add_argument("--port", type=int, help="RTT server port of channel 1; standard input without it")
+    parser.add_argument("--csv", action="store_true", help="print CSV instead of plotting")
+    args = parser.parse_args()
+
+    source = lines(args)
+    if not args.csv:
+        try:
+            import matplotlib.pyplot as plt
+            from matplotlib import animation
+        except ImportError:
+            print("matplotlib not found, printing CSV", file=sys.stderr)
+        else:
+            plot(source, plt, animation)
+            return
+    print_csv(source)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,113 @@
+//! ADC analog watchdog helpers.
+//!
+//! The HAL takes care of clocking, calibrating and sequencing ADC1, but it has
+//! no API for the analog watchdogs. Analog watchdog 1 (AWD1) compares every
+//! conversion of a channel against a window in hardware and raises the
+//! `ADC1_2` interrupt when a result falls outside of it, so the CPU never has
+//! to poll the samples itself.
+
+use stm32g4xx_hal as hal;
+
+use hal::adc::{Adc, Configured};
+use hal::hal::adc::Channel;
+use hal::stm32::ADC1;
+
+/// Largest raw value produced by a 12-bit conversion.
+pub const MAX_SAMPLE: u16 = 0x0FFF;
+
+/// Inclusive range of raw ADC codes considered "in range".
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct Window {
+    /// Lowest accepted sample.
+    pub low: u16,
+    /// Highest accepted sample.
+    pub high: u16,
+}
+
+impl Window {
+    /// Creates a window, panicking if the bounds are reversed or above 12 bits.
+    pub const fn new(low: u16, high: u16) -> Self {
+        assert!(low <= high && high <= MAX_SAMPLE);
+        Window { low, high }
+    }
+
+    /// Returns `true` when `sample` lies inside the window.
+    pub fn contains(&self, sample: u16) -> bool {
+        (self.low..=self.high).contains(&sample)
+    }
+}
+
+/// Analog watchdog 1 of ADC1, guarding a single regular channel.
+pub struct AnalogWatchdog {
+    _private: (),
+}
+
+impl AnalogWatchdog {
+    /// Arms AWD1 on the channel of `pin` with the given `window`.
+    ///
+    /// The watchdog channel can only be selected while no conversion is
+    /// running, which is why a configured (not yet started) ADC is required.
+    /// Overrun mode is switched to "overwrite" so the data register always
+    /// holds the most recent conversion, even if nobody reads it.
+    pub fn new<PIN>(_adc: &mut Adc<ADC1, Configured>, _pin: &PIN, window: Window) -> Self
+    where
+        PIN: Channel<ADC1, ID = u8>,
+    {
+        let mut awd = AnalogWatchdog { _private: () };
+        awd.set_window(window);
+
+        let adc = Self::regs();
+        adc.cfgr.modify(|_, w| unsafe {
+            w.awd1ch()
+                .bits(PIN::channel())
+                .awd1sgl()
+                .set_bit()
+                .awd1en()
+                .set_bit()
+                .ovrmod()
+                .set_bit()
+        });
+        awd.clear_pending();
+        awd
+    }
+
+    /// Changes the thresholds. Safe to call while conversions are running.
+    pub fn set_window(&mut self, window: Window) {
+        Self::regs()
+            .tr1
+            .modify(|_, w| w.lt1().bits(window.low).ht1().bits(window.high));
+    }
+
+    /// Enables the AWD1 interrupt.
+    ///
+    /// Note, you will also have to unmask `ADC1_2` in the NVIC.
+    pub fn listen(&mut self) {
+        Self::regs().ier.modify(|_, w| w.awd1ie().set_bit());
+    }
+
+    /// Disables the AWD1 interrupt. The flag keeps being set by hardware.
+    pub fn unlisten(&mut self) {
+        Self::regs().ier.modify(|_, w| w.awd1ie().clear_bit());
+    }
+
+    /// Returns `true` if a conversion fell outside the window.
+    pub fn is_pending(&self) -> bool {
+        Self::regs().isr.read().awd1().is_event()
+    }
+
+    /// Clears the AWD1 flag (write 1 to clear, other flags are untouched).
+    pub fn clear_pending(&mut self) {
+        Self::regs().isr.write(|w| w.awd1().clear());
+    }
+
+    /// Returns the latest raw conversion result of ADC1.
+    pub fn latest_sample(&self) -> u16 {
+        Self::regs().dr.read().rdata().bits()
+    }
+
+    fn regs() -> &'static hal::stm32::adc1::RegisterBlock {
+        // NOTE(unsafe) only AWD1 related fields and read-only registers are
+        // touched, the rest of ADC1 stays owned by the HAL driver.
+        unsafe { &*ADC1::ptr() }
+    }
+}
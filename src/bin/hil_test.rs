@@ -0,0 +1,230 @@
+//! Hardware-in-the-loop test: timer periods, EXTI edges and ADC reads on
+//! the board, with a pass/fail exit status for a bench runner.
+//!
+//! One jumper wire, from D7 (PA8) to D8 (PA9), loops an output back to an
+//! input for the EXTI checks; nothing else is needed:
+//!
+//! ```text
+//! cargo run --bin hil_test
+//! ```
+//!
+//! Each check logs PASS or FAIL with what it measured, and the run ends
+//! with a semihosting exit, success if every check passed and failure
+//! otherwise, so `probe-rs run` exits with 0 or 1. A panic exits with a
+//! failure too.
+//!
+//! | Check           | Passes when                                            |
+//! |-----------------|--------------------------------------------------------|
+//! | Timer periods   | TIM2 timeouts of 1 to 500 ms are within 0.5%, by DWT   |
+//! | EXTI edges      | PA8 high, then low, sets the rising, then falling, flag|
+//! | ADC             | VDDA from Vrefint is 3.0-3.6 V, the core is 0-85 °C    |
+//! | Parked sleep    | `supply::sleep_while` inside `PVD_PVM` wakes on a TIM3 |
+//! |                 | timeout 10 ms later, within 5%; it hangs otherwise     |
+
+#![no_main]
+#![no_std]
+
+use hal::prelude::*;
+use hal::stm32;
+use hal::adc::{config::SampleTime, AdcClaim, ClockSource, Temperature, Vref};
+use hal::gpio::{ExtiPin, SignalEdge};
+use hal::hal::digital::v2::{InputPin, OutputPin};
+use hal::hal::timer::CountDown;
+use hal::signature::VrefCal;
+use hal::syscfg::SysCfgExt;
+use hal::stm32::{Interrupt, TIM3};
+use hal::timer::{CountDownTimer, Event, Timer};
+use hal::interrupt;
+
+use stm32g4xx_hal as hal;
+
+use nucleo_g474re::profile;
+use nucleo_g474re::supply;
+#[cfg(feature = "swo")]
+use nucleo_g474re::swo;
+
+use cortex_m_rt::entry;
+
+use cortex_m_semihosting::debug::{self, EXIT_FAILURE, EXIT_SUCCESS};
+
+use core::cell::RefCell;
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use cortex_m::interrupt::Mutex;
+use cortex_m::peripheral::NVIC;
+
+// The logs over RTT, or over SWO with the `swo` feature; with `watch`, the
+// crate's own RTT.
+#[cfg(not(any(feature = "swo", feature = "watch")))]
+use defmt_rtt as _;
+
+// The periods TIM2 is checked at, and the timeouts timed at each.
+const PERIODS_MS: [u32; 4] = [1, 10, 100, 500];
+const TIMEOUTS: u32 = 4;
+
+// Longest wait for an edge to reach the EXTI flag, in microseconds.
+const EDGE_TIMEOUT_US: u32 = 100;
+
+// Factory calibration voltage of VREFINT_CAL, in millivolts.
+const VREFINT_CAL_MV: u32 = 3000;
+
+// The timeout ending the parked sleep.
+const PARK_MS: u32 = 10;
+
+// Create a Global Variable for the timer waking the parked sleep.
+static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM3>>>> = Mutex::new(RefCell::new(None));
+// Cycles the parked sleep lasted, set by `PVD_PVM`.
+static SLEPT: AtomicU32 = AtomicU32::new(0);
+
+
+// Panics fail the run.
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    defmt::error!("Error type: {}", _info);
+    debug::exit(EXIT_FAILURE);
+    loop {}
+}
+
+
+// The checks passed and failed.
+#[derive(Default)]
+struct Report {
+    passed: u32,
+    failed: u32,
+}
+
+impl Report {
+    fn check(&mut self, name: &str, ok: bool) {
+        if ok {
+            self.passed += 1;
+            defmt::info!("PASS: {}", name);
+        } else {
+            self.failed += 1;
+            defmt::error!("FAIL: {}", name);
+        }
+    }
+}
+
+// Runs `done` until it returns `true` or `timeout_us` elapse.
+fn wait_for(cycles_per_us: u32, timeout_us: u32, mut done: impl FnMut() -> bool) -> bool {
+    let start = profile::cycles();
+    while profile::cycles_since(start) < timeout_us * cycles_per_us {
+        if done() {
+            return true;
+        }
+    }
+    done()
+}
+
+
+#[entry]
+fn main() -> ! {
+    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut cp = cortex_m::Peripherals::take().expect("cannot take core peripherals");
+    let mut rcc = dp.RCC.constrain();
+    #[cfg(feature = "swo")]
+    swo::init(&mut cp.DCB, &mut cp.ITM, &mut cp.TPIU, &dp.DBGMCU, &rcc.clocks);
+    let gpioa = dp.GPIOA.split(&mut rcc);
+    let sysclk = rcc.clocks.sys_clk.0;
+    let cycles_per_us = sysclk / 1_000_000;
+    let mut report = Report::default();
+
+    // 1) Cycle counter, the reference for the timer periods.
+    profile::start(&mut cp.DCB, &mut cp.DWT);
+
+    // 2) Timer periods: the cycles of TIMEOUTS timeouts of TIM2, polled.
+    let timer = Timer::new(dp.TIM2, &rcc.clocks);
+    let mut timer = timer.start_count_down(PERIODS_MS[0].ms());
+    for period_ms in PERIODS_MS {
+        timer.start(period_ms.ms());
+        // From a timeout, so the first period is a whole one.
+        while timer.wait().is_err() {}
+        let start = profile::cycles();
+        for _ in 0..TIMEOUTS {
+            while timer.wait().is_err() {}
+        }
+        let measured = profile::cycles_since(start);
+        let expected = sysclk / 1000 * period_ms * TIMEOUTS;
+        defmt::info!("TIM2 {} ms: {} cycles, {} expected", period_ms, measured, expected);
+        report.check("timer period", measured.abs_diff(expected) <= expected / 200);
+    }
+
+    // 3) EXTI edges through the D7-D8 jumper, the flag polled: the line is
+    //    never unmasked in the NVIC.
+    let mut output = gpioa.pa8.into_push_pull_output();
+    output.set_low().ok();
+    let mut input = gpioa.pa9.into_pull_down_input();
+    let mut syscfg = dp.SYSCFG.constrain();
+    input.make_interrupt_source(&mut syscfg);
+    input.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
+    input.enable_interrupt(&mut dp.EXTI);
+    input.clear_interrupt_pending_bit();
+
+    output.set_high().ok();
+    let rising = wait_for(cycles_per_us, EDGE_TIMEOUT_US, || input.check_interrupt());
+    defmt::info!("PA9 {} after PA8 high, rising flag {}", input.is_high().unwrap_or(false), rising);
+    report.check("EXTI rising edge (jumper D7 to D8)", rising && input.is_high().unwrap_or(false));
+    input.clear_interrupt_pending_bit();
+
+    input.trigger_on_edge(&mut dp.EXTI, SignalEdge::Falling);
+    output.set_low().ok();
+    let falling = wait_for(cycles_per_us, EDGE_TIMEOUT_US, || input.check_interrupt());
+    defmt::info!("PA9 {} after PA8 low, falling flag {}", input.is_high().unwrap_or(true), falling);
+    report.check("EXTI falling edge (jumper D7 to D8)", falling && input.is_low().unwrap_or(false));
+    input.clear_interrupt_pending_bit();
+    input.disable_interrupt(&mut dp.EXTI);
+
+    // 4) ADC1 on its internal channels: Vrefint for VDDA, and the
+    //    temperature sensor, both sampled at the longest time they ask for.
+    let mut delay = cp.SYST.delay(&rcc.clocks);
+    let mut adc = dp.ADC1.claim(ClockSource::SystemClock, &rcc, &mut delay, true);
+    adc.enable_vref(&dp.ADC12_COMMON);
+    adc.enable_temperature(&dp.ADC12_COMMON);
+    delay.delay_us(20_u32);
+
+    let vref = adc.convert(&Vref, SampleTime::Cycles_640_5);
+    let vdda_mv = VREFINT_CAL_MV * VrefCal::get().read() as u32 / (vref as u32).max(1);
+    defmt::info!("Vrefint sample {}: VDDA {} mV", vref, vdda_mv);
+    report.check("ADC VDDA from Vrefint", (3000..=3600).contains(&vdda_mv));
+
+    let temperature = adc.convert(&Temperature, SampleTime::Cycles_640_5);
+    let celsius = Temperature::temperature_to_degrees_centigrade(temperature);
+    defmt::info!("Temperature sample {}: {} °C", temperature, celsius);
+    report.check("ADC temperature sensor", (0.0..=85.0).contains(&celsius));
+
+    // 5) The sleep of `supply::park`, from inside `PVD_PVM` as a program
+    //    parks: the vector pended by hand, TIM3's masked, at the same
+    //    priority, its timeout the only wakeup.
+    let mut timeout = Timer::new(dp.TIM3, &rcc.clocks).start_count_down(PARK_MS.ms());
+    timeout.listen(Event::TimeOut);
+    cortex_m::interrupt::free(|cs| G_TIM.borrow(cs).replace(Some(timeout)));
+    unsafe { NVIC::unmask(Interrupt::PVD_PVM) };
+    NVIC::pend(Interrupt::PVD_PVM);
+    cortex_m::asm::isb();
+    NVIC::mask(Interrupt::PVD_PVM);
+    let slept = SLEPT.load(Ordering::Relaxed);
+    let expected = sysclk / 1000 * PARK_MS;
+    defmt::info!("Parked sleep: {} cycles, {} expected", slept, expected);
+    report.check("parked sleep wakes inside its handler", slept.abs_diff(expected) <= expected / 20);
+
+    defmt::info!("{} passed, {} failed", report.passed, report.failed);
+    debug::exit(if report.failed == 0 { EXIT_SUCCESS } else { EXIT_FAILURE });
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+
+// The parked sleep, until the TIM3 timeout.
+#[interrupt]
+fn PVD_PVM() {
+    cortex_m::interrupt::free(|cs| {
+        let mut timer = G_TIM.borrow(cs).borrow_mut();
+        let timer = timer.as_mut().unwrap();
+        let start = profile::cycles();
+        supply::sleep_while(Interrupt::TIM3, || timer.wait().is_err());
+        SLEPT.store(profile::cycles_since(start), Ordering::Relaxed);
+        NVIC::unpend(Interrupt::TIM3);
+    });
+}
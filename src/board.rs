@@ -0,0 +1,90 @@
+//! Pins and memory of the supported Nucleo-G4 boards.
+//!
+//! One `board-*` feature picks the board, its chip feature of
+//! `stm32g4xx-hal` and its memory layout (`memory/<board>.x`, copied by
+//! `build.rs`):
+//!
+//! | Feature                  | Chip            | Flash  | RAM    |
+//! |--------------------------|-----------------|--------|--------|
+//! | `board-g474re` (default) | STM32G474RETx   | 512K   | 128K   |
+//! | `board-g431rb`           | STM32G431RBTx   | 128K   | 32K    |
+//! | `board-g491re`           | STM32G491RETx   | 512K   | 112K   |
+//!
+//! The three are Nucleo-64 boards with the same pinout: the user LED LD2 on
+//! PA5 (D13) and the user button B1 on PC13, on the `EXTI15_10` interrupt.
+//! Code written against the aliases here, wrapped in `led::Led` and
+//! `button::Button`, runs on any of them:
+//!
+//! ```text
+//! cargo run --no-default-features --features board-g431rb
+//! ```
+//!
+//! The runner in `.cargo/config.toml` names the G474's chip; for another
+//! board, pass its [`CHIP`] to `probe-rs` instead.
+//!
+//! Not every module fits every chip: the `hrtim` and `quadspi` features,
+//! which the boards with the peripheral turn on, gate the modules and
+//! examples that need it, and the G431 has fewer comparators and op-amps.
+//! The HAL does not know the G491 yet, so its board builds with the G474
+//! register maps, which cover its peripherals.
+
+use stm32g4xx_hal as hal;
+
+use hal::gpio::{Floating, Input, Output, PushPull, gpioa, gpioc};
+use hal::stm32::Interrupt;
+
+#[cfg(any(
+    all(feature = "board-g474re", feature = "board-g431rb"),
+    all(feature = "board-g474re", feature = "board-g491re"),
+    all(feature = "board-g431rb", feature = "board-g491re"),
+))]
+compile_error!("select one board: `--no-default-features --features board-<name>`");
+
+/// Name of the board.
+#[cfg(feature = "board-g474re")]
+pub const NAME: &str = "NUCLEO-G474RE";
+/// Name of the board.
+#[cfg(feature = "board-g431rb")]
+pub const NAME: &str = "NUCLEO-G431RB";
+/// Name of the board.
+#[cfg(feature = "board-g491re")]
+pub const NAME: &str = "NUCLEO-G491RE";
+
+/// Chip name for `probe-rs --chip`.
+#[cfg(feature = "board-g474re")]
+pub const CHIP: &str = "STM32G474RETx";
+/// Chip name for `probe-rs --chip`.
+#[cfg(feature = "board-g431rb")]
+pub const CHIP: &str = "STM32G431RBTx";
+/// Chip name for `probe-rs --chip`.
+#[cfg(feature = "board-g491re")]
+pub const CHIP: &str = "STM32G491RETx";
+
+/// Flash size, in bytes, as in the memory layout.
+#[cfg(any(feature = "board-g474re", feature = "board-g491re"))]
+pub const FLASH_SIZE: u32 = 512 * 1024;
+/// Flash size, in bytes, as in the memory layout.
+#[cfg(feature = "board-g431rb")]
+pub const FLASH_SIZE: u32 = 128 * 1024;
+
+/// RAM size, in bytes, as in the memory layout: SRAM1, SRAM2 and CCM SRAM,
+/// contiguous from 0x2000_0000.
+#[cfg(feature = "board-g474re")]
+pub const RAM_SIZE: u32 = 128 * 1024;
+/// RAM size, in bytes, as in the memory layout: SRAM1, SRAM2 and CCM SRAM,
+/// contiguous from 0x2000_0000.
+#[cfg(feature = "board-g431rb")]
+pub const RAM_SIZE: u32 = 32 * 1024;
+/// RAM size, in bytes, as in the memory layout: SRAM1, SRAM2 and CCM SRAM,
+/// contiguous from 0x2000_0000.
+#[cfg(feature = "board-g491re")]
+pub const RAM_SIZE: u32 = 112 * 1024;
+
+/// The user LED LD2.
+pub type LedPin = gpioa::PA5<Output<PushPull>>;
+
+/// The user button B1, high while pressed.
+pub type ButtonPin = gpioc::PC13<Input<Floating>>;
+
+/// The interrupt of the button's EXTI line (13).
+pub const BUTTON_INTERRUPT: Interrupt = Interrupt::EXTI15_10;
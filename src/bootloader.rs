@@ -0,0 +1,98 @@
+//! Jump to the ST system bootloader, for reflashing without a debugger.
+//!
+//! The system memory of the G4 holds the bootloader ST programs at the
+//! factory (AN2606): it takes a new image over USART2 on PA2/PA3, the pins of
+//! the ST-LINK virtual COM port, and over the other interfaces AN2606 lists
+//! for the chip, USB DFU on PA11/PA12 among them. [`enter_system_dfu`] gets
+//! there from the running program, with no BOOT0 jumper:
+//!
+//! 1. interrupts off, SysTick stopped, every NVIC line disabled and cleared,
+//! 2. the clocks back to their reset state, the core on the 16 MHz HSI, and
+//!    every peripheral reset, so the bootloader finds the chip as out of
+//!    reset,
+//! 3. the system memory remapped at address 0, and the vector table with it,
+//! 4. the stack pointer and the reset vector of the bootloader loaded from
+//!    [`SYSTEM_MEMORY`], and a jump.
+//!
+//! Then, over the virtual COM port for example:
+//!
+//! ```text
+//! STM32_Programmer_CLI -c port=/dev/ttyACM0 -w firmware.bin 0x08000000 -v -g
+//! ```
+//!
+//! It does not come back: the bootloader runs until a reset, or until it is
+//! told to start the new image. [`HOLD_MS`] is how long the examples want
+//! the button held before they call it.
+
+use cortex_m::peripheral::{NVIC, SCB, SYST};
+
+use stm32g4xx_hal as hal;
+
+use hal::stm32::{RCC, SYSCFG};
+
+/// The start of the system memory: the bootloader's vector table.
+pub const SYSTEM_MEMORY: u32 = 0x1FFF_0000;
+
+/// How long the button is held to enter the bootloader, in milliseconds.
+pub const HOLD_MS: u32 = 5000;
+
+// The NVIC enable and pending registers: 8 of them cover every interrupt.
+const NVIC_REGISTERS: usize = 8;
+
+/// Puts the chip back to its reset state and jumps to the system bootloader.
+pub fn enter_system_dfu() -> ! {
+    defmt::info!("Entering the system bootloader");
+    cortex_m::interrupt::disable();
+
+    // NOTE(unsafe) interrupts are off and nothing runs after this: the core
+    // and device peripherals are ours, whoever owned them before.
+    unsafe {
+        let syst = &*SYST::PTR;
+        syst.csr.write(0);
+        syst.rvr.write(0);
+        syst.cvr.write(0);
+
+        let nvic = &*NVIC::PTR;
+        for register in 0..NVIC_REGISTERS {
+            nvic.icer[register].write(0xFFFF_FFFF);
+            nvic.icpr[register].write(0xFFFF_FFFF);
+        }
+        // The SysTick and PendSV exceptions, pending still.
+        (*SCB::PTR).icsr.write(1 << 25 | 1 << 27);
+
+        let rcc = &*RCC::ptr();
+        // The HSI as the system clock, no prescaler, then the rest off.
+        rcc.cr.modify(|_, w| w.hsion().set_bit());
+        while rcc.cr.read().hsirdy().bit_is_clear() {}
+        rcc.cfgr.write(|w| w.bits(0b01));
+        while rcc.cfgr.read().sws().bits() != 0b01 {}
+        rcc.cr.modify(|_, w| w.pllon().clear_bit().csson().clear_bit().hseon().clear_bit().hsebyp().clear_bit());
+        rcc.cier.write(|w| w.bits(0));
+        rcc.cicr.write(|w| w.bits(0xFFFF_FFFF));
+
+        // Every peripheral through reset.
+        rcc.ahb1rstr.write(|w| w.bits(0xFFFF_FFFF));
+        rcc.ahb2rstr.write(|w| w.bits(0xFFFF_FFFF));
+        rcc.ahb3rstr.write(|w| w.bits(0xFFFF_FFFF));
+        rcc.apb1rstr1.write(|w| w.bits(0xFFFF_FFFF));
+        rcc.apb1rstr2.write(|w| w.bits(0xFFFF_FFFF));
+        rcc.apb2rstr.write(|w| w.bits(0xFFFF_FFFF));
+        rcc.ahb1rstr.write(|w| w.bits(0));
+        rcc.ahb2rstr.write(|w| w.bits(0));
+        rcc.ahb3rstr.write(|w| w.bits(0));
+        rcc.apb1rstr1.write(|w| w.bits(0));
+        rcc.apb1rstr2.write(|w| w.bits(0));
+        rcc.apb2rstr.write(|w| w.bits(0));
+
+        // The system memory at address 0, its vector table the one in use.
+        rcc.apb2enr.modify(|_, w| w.syscfgen().set_bit());
+        (*SYSCFG::ptr()).memrmp.modify(|_, w| w.mem_mode().bits(0b001));
+        (*SCB::PTR).vtor.write(0);
+        cortex_m::asm::dsb();
+        cortex_m::asm::isb();
+
+        // The bootloader expects interrupts on; every NVIC line is off still.
+        cortex_m::interrupt::enable();
+        cortex_m::asm::bootload(SYSTEM_MEMORY as *const u32)
+    }
+}
@@ -0,0 +1,118 @@
+//! DMA bursts to the compare registers of TIM2: the PWM duties of its
+//! channels reloaded every period, with no CPU.
+//!
+//! A timer's DMA burst moves several registers per request through one
+//! address: DCR names the first register (DBA) and how many follow (DBL),
+//! and each DMA write to DMAR lands in the next of them. Here the update
+//! event requests the burst and the block is CCR1 to CCR`CHANNELS`, so a
+//! buffer of duties, `CHANNELS` words a period, plays out one row per PWM
+//! period; the preload applies each row from the period after, however late
+//! the DMA. In circular mode the buffer loops: a fade, a breathing LED, or
+//! a bit stream like the WS2812's, without an interrupt.
+//!
+//! Channel `n` drives the pins of TIM2_CHn in their alternate function, the
+//! user LED's PA5 for CH1 (AF1):
+//!
+//! | Channel | Pins, AF1           |
+//! |---------|---------------------|
+//! | CH1     | PA0, PA5, PA15      |
+//! | CH2     | PA1, PB3            |
+//! | CH3     | PA2, PB10           |
+//! | CH4     | PA3, PB11           |
+//!
+//! [`PwmBurst`] is the peripheral side of a memory-to-peripheral DMA
+//! transfer of `u32` words, of a buffer `CHANNELS` words a row.
+
+use stm32g4xx_hal as hal;
+
+use hal::dma::{traits::TargetAddress, MemoryToPeripheral};
+use hal::rcc::{Clocks, Enable, GetBusFreq, Reset};
+use hal::stm32::{RCC, TIM2};
+use hal::time::Hertz;
+
+/// DMAMUX request line of the TIM2 update event.
+const TIM2_UP_REQUEST: u8 = 60;
+
+/// DBA of CCR1: its offset from CR1, in words.
+const CCR1_WORD: u8 = 0x34 / 4;
+
+/// TIM2 PWM on channels 1 to `CHANNELS`, 1 to 4, fed a row of duties by a
+/// DMA burst every period.
+pub struct PwmBurst<const CHANNELS: usize> {
+    tim: TIM2,
+    max_duty: u32,
+}
+
+impl<const CHANNELS: usize> PwmBurst<CHANNELS> {
+    /// Starts TIM2 at `frequency`, all duties 0, and a DMA burst of
+    /// `CHANNELS` duties requested on every update.
+    pub fn new(tim: TIM2, frequency: Hertz, clocks: &Clocks) -> Self {
+        const { assert!(CHANNELS >= 1 && CHANNELS <= 4) };
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            TIM2::enable(rcc);
+            TIM2::reset(rcc);
+        }
+
+        let period = TIM2::get_timer_frequency(clocks).0 / frequency.0.max(1);
+        tim.psc.write(|w| unsafe { w.psc().bits(0) });
+        tim.arr.write(|w| unsafe { w.bits(period - 1) });
+        // PWM mode 1, preloaded, on every channel of the burst.
+        tim.ccmr1_output().write(|w| {
+            w.oc1m().pwm_mode1().oc1pe().set_bit();
+            w.oc2m().pwm_mode1().oc2pe().set_bit()
+        });
+        tim.ccmr2_output().write(|w| {
+            w.oc3m().pwm_mode1().oc3pe().set_bit();
+            w.oc4m().pwm_mode1().oc4pe().set_bit()
+        });
+        // CCxE every 4 bits.
+        let enable = (0..CHANNELS).fold(0, |bits, channel| bits | 1 << (4 * channel));
+        tim.ccer.write(|w| unsafe { w.bits(enable) });
+        tim.dcr.write(|w| unsafe { w.dba().bits(CCR1_WORD).dbl().bits(CHANNELS as u8 - 1) });
+        tim.cr1.modify(|_, w| w.arpe().set_bit());
+        // Load the registers, without a DMA request yet.
+        tim.egr.write(|w| w.ug().set_bit());
+        tim.dier.write(|w| w.ude().set_bit());
+        tim.cr1.modify(|_, w| w.cen().set_bit());
+
+        PwmBurst { tim, max_duty: period }
+    }
+
+    /// The duty of an output always high; 0 is always low.
+    pub fn max_duty(&self) -> u32 {
+        self.max_duty
+    }
+
+    /// The duty of channel `channel`, 1 to `CHANNELS`, in the period now.
+    pub fn duty(&self, channel: usize) -> Option<u32> {
+        (1..=CHANNELS).contains(&channel).then(|| self.tim.ccr[channel - 1].read().bits())
+    }
+
+    /// Starts the burst over from CCR1, for a new transfer after a transfer
+    /// stopped with a row half written.
+    pub fn rearm(&mut self) {
+        self.tim.dier.modify(|_, w| w.ude().clear_bit());
+        self.tim.dcr.write(|w| unsafe { w.dba().bits(CCR1_WORD).dbl().bits(CHANNELS as u8 - 1) });
+        self.tim.dier.modify(|_, w| w.ude().set_bit());
+    }
+
+    /// Stops the timer and returns it.
+    pub fn release(self) -> TIM2 {
+        self.tim.dier.write(|w| w.ude().clear_bit());
+        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+        self.tim.ccer.reset();
+        self.tim
+    }
+}
+
+unsafe impl<const CHANNELS: usize> TargetAddress<MemoryToPeripheral> for PwmBurst<CHANNELS> {
+    type MemSize = u32;
+
+    const REQUEST_LINE: Option<u8> = Some(TIM2_UP_REQUEST);
+
+    fn address(&self) -> u32 {
+        &self.tim.dmar as *const _ as u32
+    }
+}
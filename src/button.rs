@@ -0,0 +1,167 @@
+//! A push button on any EXTI-capable input pin.
+//!
+//! [`Button`] wraps an input pin implementing the HAL's `ExtiPin` and
+//! embedded-hal 1.0's `InputPin`, a pin of the HAL in a `compat::Compat`:
+//! the board's B1 on PC13 (`board::ButtonPin`), or an external button on
+//! any port, pulling the pin high when pressed or, with the pin's pull-up,
+//! low. Either way the EXTI line fires on both edges, and the interrupt code
+//! clears it with the same call, then asks [`Button::event`] which edge it
+//! was: the level of the pin tells a [`Event::Pressed`] from a
+//! [`Event::Released`], so a program times how long the button is held.
+//!
+//! A normally-closed button is the same two wirings the other way round:
+//! between the pin and GND, on the pull-up, it drives the pin high when
+//! pressed, as [`Button::new`] takes; from 3.3 V, on the pull-down, low, as
+//! [`Button::active_low`].
+//!
+//! The pin number picks the EXTI line, and the line the interrupt vector:
+//!
+//! | Pin number | Interrupt              |
+//! |------------|------------------------|
+//! | 0 to 4     | `EXTI0` to `EXTI4`     |
+//! | 5 to 9     | `EXTI9_5`              |
+//! | 10 to 15   | `EXTI15_10`            |
+//!
+//! [`interrupt`] returns it, for the NVIC. A button moved to another group
+//! needs its handler renamed; the code inside stays the same.
+//!
+//! Edges closer together than the interrupt takes to run, a bounce, leave
+//! the button where the level says: [`Button::event`] returns an event only
+//! when the level differs from the last one it returned.
+//!
+//! With the `exti` module's handlers in place of the program's own,
+//! [`Button::wait_for_press`] is a future for async code, woken by the
+//! interrupt of the press, on the line of [`ExtiLine`], which every pin of
+//! the HAL and a `Compat` of one implements.
+
+use embedded_hal::digital::InputPin;
+
+use stm32g4xx_hal as hal;
+
+use hal::gpio::{self, ExtiPin, SignalEdge};
+use hal::stm32::{EXTI, Interrupt};
+use hal::syscfg::SysCfg;
+
+/// The interrupt of EXTI line `line`, the number of the pin on it.
+pub fn interrupt(line: u8) -> Interrupt {
+    match line {
+        0 => Interrupt::EXTI0,
+        1 => Interrupt::EXTI1,
+        2 => Interrupt::EXTI2,
+        3 => Interrupt::EXTI3,
+        4 => Interrupt::EXTI4,
+        5..=9 => Interrupt::EXTI9_5,
+        _ => Interrupt::EXTI15_10,
+    }
+}
+
+/// A pin on an EXTI line, the number of the pin.
+pub trait ExtiLine {
+    /// The line of the pin.
+    const LINE: u8;
+}
+
+macro_rules! exti_lines {
+    ($($port:ident: $($pin:ident $line:literal)+;)+) => {
+        $($(
+            impl<MODE> ExtiLine for gpio::$port::$pin<MODE> {
+                const LINE: u8 = $line;
+            }
+        )+)+
+    };
+}
+
+exti_lines! {
+    gpioa: PA0 0 PA1 1 PA2 2 PA3 3 PA4 4 PA5 5 PA6 6 PA7 7 PA8 8 PA9 9 PA10 10 PA11 11 PA12 12 PA13 13 PA14 14 PA15 15;
+    gpiob: PB0 0 PB1 1 PB2 2 PB3 3 PB4 4 PB5 5 PB6 6 PB7 7 PB8 8 PB9 9 PB10 10 PB11 11 PB12 12 PB13 13 PB14 14 PB15 15;
+    gpioc: PC0 0 PC1 1 PC2 2 PC3 3 PC4 4 PC5 5 PC6 6 PC7 7 PC8 8 PC9 9 PC10 10 PC11 11 PC12 12 PC13 13 PC14 14 PC15 15;
+    gpiod: PD0 0 PD1 1 PD2 2 PD3 3 PD4 4 PD5 5 PD6 6 PD7 7 PD8 8 PD9 9 PD10 10 PD11 11 PD12 12 PD13 13 PD14 14 PD15 15;
+    gpioe: PE0 0 PE1 1 PE2 2 PE3 3 PE4 4 PE5 5 PE6 6 PE7 7 PE8 8 PE9 9 PE10 10 PE11 11 PE12 12 PE13 13 PE14 14 PE15 15;
+    gpiof: PF0 0 PF1 1 PF2 2 PF3 3 PF4 4 PF5 5 PF6 6 PF7 7 PF8 8 PF9 9 PF10 10 PF11 11 PF12 12 PF13 13 PF14 14 PF15 15;
+    gpiog: PG0 0 PG1 1 PG2 2 PG3 3 PG4 4 PG5 5 PG6 6 PG7 7 PG8 8 PG9 9 PG10 10 PG11 11 PG12 12 PG13 13 PG14 14 PG15 15;
+}
+
+/// What the button did, from the level of its pin after an edge.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "logging", derive(defmt::Format))]
+pub enum Event {
+    Pressed,
+    Released,
+}
+
+/// A button raising its EXTI interrupt when pressed and when released.
+pub struct Button<P> {
+    pin: P,
+    active_low: bool,
+    pressed: bool,
+}
+
+impl<P: ExtiPin + InputPin> Button<P> {
+    /// A button driving the pin high when pressed, as B1.
+    pub fn new(pin: P, syscfg: &mut SysCfg, exti: &mut EXTI) -> Self {
+        Self::with_polarity(pin, false, syscfg, exti)
+    }
+
+    /// A button pulling the pin low when pressed: between the pin, with its
+    /// pull-up on, and GND.
+    pub fn active_low(pin: P, syscfg: &mut SysCfg, exti: &mut EXTI) -> Self {
+        Self::with_polarity(pin, true, syscfg, exti)
+    }
+
+    fn with_polarity(mut pin: P, active_low: bool, syscfg: &mut SysCfg, exti: &mut EXTI) -> Self {
+        pin.make_interrupt_source(syscfg);
+        pin.trigger_on_edge(exti, SignalEdge::RisingFalling);
+        pin.enable_interrupt(exti);
+        let mut button = Button { pin, active_low, pressed: false };
+        button.pressed = button.is_pressed();
+        button
+    }
+
+    /// Returns `true` while the button is held down.
+    pub fn is_pressed(&mut self) -> bool {
+        matches!(self.pin.is_high(), Ok(high) if high != self.active_low)
+    }
+
+    /// The edge just seen, from the interrupt: `Pressed` or `Released` if
+    /// the level of the pin changed since the last event, `None` after a
+    /// bounce that left it as it was.
+    pub fn event(&mut self) -> Option<Event> {
+        let pressed = self.is_pressed();
+        if pressed == core::mem::replace(&mut self.pressed, pressed) {
+            return None;
+        }
+        Some(if pressed { Event::Pressed } else { Event::Released })
+    }
+
+    /// Returns `true` if a press is pending on the EXTI line.
+    pub fn check_interrupt(&self) -> bool {
+        self.pin.check_interrupt()
+    }
+
+    /// Clears the pending press, from the interrupt.
+    pub fn clear_interrupt_pending_bit(&mut self) {
+        self.pin.clear_interrupt_pending_bit();
+    }
+
+    /// Stops the interrupt and returns the pin.
+    pub fn release(mut self, exti: &mut EXTI) -> P {
+        self.pin.disable_interrupt(exti);
+        self.pin
+    }
+}
+
+impl<P: ExtiPin + InputPin + ExtiLine> Button<P> {
+    /// Waits for the next press, on the EXTI line of the pin.
+    ///
+    /// The ISRs of `exti_handlers!` clear the line and wake the task: the
+    /// program defines no EXTI handler of its own. The releases are skipped.
+    #[cfg(feature = "peripherals")]
+    pub async fn wait_for_press(&mut self) {
+        loop {
+            crate::exti::wait(P::LINE).await;
+            if self.event() == Some(Event::Pressed) {
+                return;
+            }
+        }
+    }
+}
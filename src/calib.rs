@@ -0,0 +1,220 @@
+//! HSI calibration against the LSE crystal, by TIM16 input capture.
+//!
+//! The HSI16 leaves the factory trimmed to 1 % at 30 °C, and every timer,
+//! baud rate and millisecond of a board with no HSE follows its error. The
+//! 32.768 kHz LSE crystal of the Nucleo is good to some 20 ppm: TIM16 can
+//! take it on its channel 1 (TISEL), with no pin, and capture its counter,
+//! clocked from the HSI, every 8 LSE cycles. [`Calib::measure`] adds up the
+//! ticks over [`CAPTURES`] captures, 62.5 ms, and scales them to the HSI's
+//! frequency; [`Calib::trim`] steps HSITRIM (RCC_ICSCR) a code at a time to
+//! the frequency nearest 16 MHz and reports the [`Calibration`], before and
+//! after.
+//!
+//! A trim code moves the HSI by some 0.3 %, so the trim leaves it within
+//! about 1500 ppm, where the factory's may be a few thousand off at another
+//! temperature; the measurement itself is good to a few ppm.
+//!
+//! The LSE has to be running, from [`crate::clocks::freeze`] with
+//! `ClockConfig::hsi().lse()`, and the system clock has to come from the
+//! HSI, directly or through the PLL, so that TIM16 counts it. A measurement
+//! polls with the interrupts off, as a capture missed behind an interrupt
+//! would count as a shorter period.
+
+use stm32g4xx_hal as hal;
+
+use hal::rcc::{Clocks, Enable, GetBusFreq, Reset};
+use hal::stm32::{RCC, TIM16};
+use hal::time::Hertz;
+
+#[cfg(feature = "logging")]
+use defmt::Format;
+
+use crate::clocks::HSI;
+
+/// Frequency of the LSE crystal.
+pub const LSE_HZ: u32 = 32_768;
+
+/// Captures in a measurement, each 8 LSE cycles.
+pub const CAPTURES: u32 = 256;
+
+/// LSE cycles between two captures, the input prescaler.
+const CYCLES_PER_CAPTURE: u32 = 8;
+
+/// The trim code out of reset, the middle of its 7 bits.
+pub const DEFAULT_TRIM: u8 = 64;
+
+/// The largest trim code.
+pub const MAX_TRIM: u8 = 127;
+
+// TIM16 TI1 input: the LSE, in place of the CH1 pin.
+const TI1SEL_LSE: u8 = 0b0010;
+
+// Trim steps tried before giving up on crossing 16 MHz.
+const MAX_STEPS: u8 = 16;
+
+/// Calibration errors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "logging", derive(Format))]
+pub enum Error {
+    /// The LSE is off, or captures stopped coming.
+    NoLse,
+    /// The system clock does not come from the HSI.
+    NotFromHsi,
+}
+
+/// The HSI before and after [`Calib::trim`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "logging", derive(Format))]
+pub struct Calibration {
+    /// The trim code found, and its frequency.
+    pub trim: u8,
+    pub frequency: u32,
+    /// The trim code before, and its frequency.
+    pub trim_before: u8,
+    pub frequency_before: u32,
+}
+
+impl Calibration {
+    /// The error before the trim, in ppm.
+    pub fn error_before_ppm(&self) -> i32 {
+        error_ppm(self.frequency_before)
+    }
+
+    /// The error after the trim, in ppm.
+    pub fn error_ppm(&self) -> i32 {
+        error_ppm(self.frequency)
+    }
+}
+
+/// The error of an HSI at `frequency` Hz against its 16 MHz, in ppm.
+pub fn error_ppm(frequency: u32) -> i32 {
+    ((frequency as i64 - HSI.0 as i64) * 1_000_000 / HSI.0 as i64) as i32
+}
+
+/// The HSI trim code in use.
+pub fn hsitrim() -> u8 {
+    let rcc = unsafe { &(*RCC::ptr()) };
+    rcc.icscr.read().hsitrim().bits()
+}
+
+/// Sets the HSI trim code, up to [`MAX_TRIM`].
+pub fn set_hsitrim(trim: u8) {
+    let rcc = unsafe { &(*RCC::ptr()) };
+    rcc.icscr.modify(|_, w| unsafe { w.hsitrim().bits(trim.min(MAX_TRIM)) });
+}
+
+/// TIM16 capturing the LSE.
+pub struct Calib {
+    tim: TIM16,
+    // The frequency of TIM16 for an HSI of exactly 16 MHz.
+    timer_clock: Hertz,
+}
+
+impl Calib {
+    /// Sets TIM16 capturing the LSE: its counter free on the timer clock,
+    /// channel 1 on every 8th LSE rising edge.
+    pub fn new(tim: TIM16, clocks: &Clocks) -> Result<Self, Error> {
+        let rcc = unsafe { &(*RCC::ptr()) };
+        if rcc.bdcr.read().lserdy().bit_is_clear() {
+            return Err(Error::NoLse);
+        }
+        // SWS 01: the HSI; 11: the PLL, from the HSI if PLLSRC is 10.
+        let from_hsi = match rcc.cfgr.read().sws().bits() {
+            0b01 => true,
+            0b11 => rcc.pllcfgr.read().pllsrc().bits() == 0b10,
+            _ => false,
+        };
+        if !from_hsi {
+            return Err(Error::NotFromHsi);
+        }
+        TIM16::enable(rcc);
+        TIM16::reset(rcc);
+
+        tim.tisel.write(|w| unsafe { w.ti1sel().bits(TI1SEL_LSE) });
+        // CC1 on TI1, unfiltered, a capture every 8 edges.
+        tim.ccmr1_input().write(|w| unsafe { w.cc1s().bits(0b01).ic1psc().bits(0b11).ic1f().bits(0) });
+        tim.ccer.write(|w| w.cc1e().set_bit());
+        tim.psc.write(|w| unsafe { w.psc().bits(0) });
+        tim.arr.write(|w| unsafe { w.bits(0xFFFF) });
+        tim.cr1.modify(|_, w| w.cen().set_bit());
+
+        let timer_clock = TIM16::get_timer_frequency(clocks);
+        Ok(Calib { tim, timer_clock })
+    }
+
+    // Waits for the next capture and returns it; `None` if the counter
+    // wrapped twice with none, the period being under one wrap.
+    fn capture(&self) -> Option<u16> {
+        let mut wraps = 0;
+        while self.tim.sr.read().cc1if().bit_is_clear() {
+            if self.tim.sr.read().uif().bit_is_set() {
+                self.tim.sr.write(|w| unsafe { w.bits(!1) });
+                wraps += 1;
+                if wraps == 2 {
+                    return None;
+                }
+            }
+        }
+        // Reading CCR1 clears CC1IF.
+        Some(self.tim.ccr1().read().bits() as u16)
+    }
+
+    /// The HSI's frequency, in hertz, measured over [`CAPTURES`] captures.
+    pub fn measure(&mut self) -> Result<u32, Error> {
+        let ticks = cortex_m::interrupt::free(|_| {
+            // From a fresh capture, the one pending being stale.
+            self.tim.sr.write(|w| unsafe { w.bits(0) });
+            let mut last = self.capture()?;
+            let mut ticks = 0_u64;
+            for _ in 0..CAPTURES {
+                let now = self.capture()?;
+                ticks += now.wrapping_sub(last) as u64;
+                last = now;
+            }
+            Some(ticks)
+        })
+        .ok_or(Error::NoLse)?;
+
+        // ticks / cycles is the timer clock over the LSE's; the HSI is that
+        // clock in the ratio of their nominal frequencies.
+        let cycles = (CAPTURES * CYCLES_PER_CAPTURE) as u64;
+        let hsi = ticks * LSE_HZ as u64 * HSI.0 as u64 / (cycles * self.timer_clock.0 as u64);
+        Ok(hsi as u32)
+    }
+
+    /// Steps HSITRIM towards 16 MHz until the error changes sign, and keeps
+    /// the code nearest.
+    pub fn trim(&mut self) -> Result<Calibration, Error> {
+        let trim_before = hsitrim();
+        let frequency_before = self.measure()?;
+
+        let (mut trim, mut frequency) = (trim_before, frequency_before);
+        let fast = frequency > HSI.0;
+        for _ in 0..MAX_STEPS {
+            let next = match fast {
+                true if trim > 0 => trim - 1,
+                false if trim < MAX_TRIM => trim + 1,
+                _ => break,
+            };
+            set_hsitrim(next);
+            let measured = self.measure()?;
+            // Past 16 MHz: back to the previous code if it was nearer.
+            if (measured > HSI.0) != fast {
+                if error_ppm(measured).abs() < error_ppm(frequency).abs() {
+                    (trim, frequency) = (next, measured);
+                }
+                break;
+            }
+            (trim, frequency) = (next, measured);
+        }
+        set_hsitrim(trim);
+        Ok(Calibration { trim, frequency, trim_before, frequency_before })
+    }
+
+    /// Stops TIM16 and returns it.
+    pub fn release(self) -> TIM16 {
+        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+        self.tim.ccer.write(|w| w.cc1e().clear_bit());
+        self.tim
+    }
+}
@@ -0,0 +1,258 @@
+//! FDCAN telemetry and remote control of the blink over the CAN bus.
+//!
+//! [`Telemetry`] takes an FDCAN instance from the HAL (`dp.FDCAN1.fdcan(tx,
+//! rx, &rcc)`), sets its bitrate and sends one classic CAN frame per event,
+//! with a standard or extended identifier of your choice. Every telemetry
+//! frame carries 8 data bytes:
+//!
+//! | Byte | Content                                            |
+//! |------|----------------------------------------------------|
+//! | 0    | event type, see [`Event`]                          |
+//! | 1-4  | timestamp, milliseconds since boot, little endian  |
+//! | 5-6  | current blink delay in milliseconds, little endian |
+//! | 7    | sequence number, wrapping, to detect lost frames   |
+//!
+//! Once [`Telemetry::listen_commands`] is called, frames with the command
+//! identifier are accepted into RX FIFO 0 and raise interrupt line 0; any
+//! other frame is dropped by the hardware filters. A command frame starts
+//! with an opcode:
+//!
+//! | Opcode | Arguments                              | Command                 |
+//! |--------|----------------------------------------|-------------------------|
+//! | 0x01   | bytes 1-2: period in ms, little endian | [`Command::SetPeriod`]  |
+//! | 0x02   | byte 1: 0 off, 1 on, 2 blink           | [`Command::SetLed`]     |
+//!
+//! The board needs a CAN transceiver on the FDCAN pins (e.g. PB9 TX, PB8 RX)
+//! and a bus with at least one other node to acknowledge the frames.
+
+use core::num::{NonZeroU16, NonZeroU8};
+
+use stm32g4xx_hal as hal;
+
+use fdcan::config::{GlobalFilter, NominalBitTiming};
+use fdcan::filter::{ExtendedFilter, ExtendedFilterSlot, FilterType, Action, StandardFilter, StandardFilterSlot};
+use fdcan::frame::{FrameFormat, TxFrameHeader};
+use fdcan::id::Id;
+use fdcan::interrupt::{Interrupt, InterruptLine};
+use fdcan::{ConfigMode, FdCan, Instance, NormalOperationMode};
+use hal::can::Can;
+use hal::time::Hertz;
+
+/// Time quanta per bit: 1 sync + 13 before and 2 after the sample point (87.5%).
+const QUANTA_PER_BIT: u32 = 16;
+
+/// Nominal bitrate of the bus.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Bitrate {
+    Kbps125 = 125_000,
+    Kbps250 = 250_000,
+    Kbps500 = 500_000,
+    Mbps1 = 1_000_000,
+}
+
+impl Bitrate {
+    /// Bit timing for `clock`, the FDCAN kernel clock.
+    ///
+    /// The HAL clocks FDCAN from PCLK1 (`rcc.clocks.apb1_clk`), which must be a
+    /// multiple of 16 times the bitrate.
+    pub fn bit_timing(self, clock: Hertz) -> NominalBitTiming {
+        let quanta = self as u32 * QUANTA_PER_BIT;
+        assert!(clock.0.is_multiple_of(quanta), "FDCAN clock is not a multiple of the bitrate");
+        let prescaler = clock.0 / quanta;
+        assert!((1..=512).contains(&prescaler));
+
+        NominalBitTiming {
+            prescaler: NonZeroU16::new(prescaler as u16).unwrap(),
+            seg1: NonZeroU8::new(13).unwrap(),
+            seg2: NonZeroU8::new(2).unwrap(),
+            sync_jump_width: NonZeroU8::new(1).unwrap(),
+        }
+    }
+}
+
+/// Event type, byte 0 of every telemetry frame.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Event {
+    /// The User Button was pressed.
+    ButtonPress = 1,
+    /// The LED completed another batch of blink cycles.
+    BlinkCycles = 2,
+}
+
+/// Opcode of [`Command::SetPeriod`] frames.
+const OPCODE_SET_PERIOD: u8 = 0x01;
+/// Opcode of [`Command::SetLed`] frames.
+const OPCODE_SET_LED: u8 = 0x02;
+
+/// LED state requested by a remote node.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum LedState {
+    /// LED off, blinking stopped.
+    Off = 0,
+    /// LED on, blinking stopped.
+    On = 1,
+    /// Normal blinking.
+    Blink = 2,
+}
+
+/// Command received from another node.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Command {
+    /// Sets the blink delay, in milliseconds.
+    SetPeriod(u32),
+    /// Forces the LED on or off, or resumes blinking.
+    SetLed(LedState),
+}
+
+impl Command {
+    /// Decodes the data bytes of a command frame.
+    fn parse(data: &[u8]) -> Option<Command> {
+        match *data {
+            [OPCODE_SET_PERIOD, low, high, ..] => Some(Command::SetPeriod(u16::from_le_bytes([low, high]) as u32)),
+            [OPCODE_SET_LED, 0, ..] => Some(Command::SetLed(LedState::Off)),
+            [OPCODE_SET_LED, 1, ..] => Some(Command::SetLed(LedState::On)),
+            [OPCODE_SET_LED, 2, ..] => Some(Command::SetLed(LedState::Blink)),
+            _ => None,
+        }
+    }
+}
+
+/// Bus settings of the telemetry.
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    pub bitrate: Bitrate,
+    /// Identifier of the telemetry frames, standard (11-bit) or extended (29-bit).
+    pub id: Id,
+}
+
+/// Telemetry transmitter on an FDCAN instance.
+pub struct Telemetry<FDCAN>
+where
+    Can<FDCAN>: fdcan::Instance,
+{
+    can: FdCan<Can<FDCAN>, NormalOperationMode>,
+    id: Id,
+    sequence: u8,
+}
+
+impl<FDCAN> Telemetry<FDCAN>
+where
+    Can<FDCAN>: fdcan::Instance,
+{
+    /// Configures the bitrate and joins the bus.
+    ///
+    /// `clock` is the FDCAN kernel clock, see [`Bitrate::bit_timing`].
+    pub fn new(mut can: FdCan<Can<FDCAN>, ConfigMode>, config: Config, clock: Hertz) -> Self {
+        can.set_protocol_exception_handling(false);
+        can.set_nominal_bit_timing(config.bitrate.bit_timing(clock));
+        // Nothing is received until `listen_commands` adds a filter.
+        can.set_global_filter(GlobalFilter::reject_all());
+
+        Telemetry { can: can.into_normal(), id: config.id, sequence: 0 }
+    }
+
+    /// Queues the frame of one event.
+    ///
+    /// Returns `false` if all transmit buffers were busy, in which case a
+    /// pending frame was replaced: the sequence number shows the gap.
+    pub fn send(&mut self, event: Event, timestamp_ms: u32, delay_ms: u32) -> bool {
+        let timestamp = timestamp_ms.to_le_bytes();
+        let delay = (delay_ms.min(u16::MAX as u32) as u16).to_le_bytes();
+        let data = [
+            event as u8,
+            timestamp[0],
+            timestamp[1],
+            timestamp[2],
+            timestamp[3],
+            delay[0],
+            delay[1],
+            self.sequence,
+        ];
+        self.sequence = self.sequence.wrapping_add(1);
+
+        let header = TxFrameHeader {
+            len: data.len() as u8,
+            frame_format: FrameFormat::Standard,
+            id: self.id,
+            bit_rate_switching: false,
+            marker: None,
+        };
+        matches!(self.can.transmit(header, &data), Ok(None))
+    }
+
+    /// Accepts command frames with identifier `id` and raises interrupt line 0
+    /// when one arrives or when the node goes bus-off.
+    ///
+    /// Note, you will also have to unmask the line 0 interrupt of the FDCAN
+    /// instance in the NVIC.
+    pub fn listen_commands(&mut self, id: Id) {
+        match id {
+            Id::Standard(id) => self.can.set_standard_filter(
+                StandardFilterSlot::_0,
+                StandardFilter { filter: FilterType::DedicatedSingle(id), action: Action::StoreInFifo0 },
+            ),
+            Id::Extended(id) => self.can.set_extended_filter(
+                ExtendedFilterSlot::_0,
+                ExtendedFilter { filter: FilterType::DedicatedSingle(id), action: Action::StoreInFifo0 },
+            ),
+        }
+        self.can.enable_interrupt(Interrupt::RxFifo0NewMsg);
+        self.can.enable_interrupt(Interrupt::BusOff);
+        self.can.enable_interrupt_line(InterruptLine::_0, true);
+    }
+
+    /// Returns the next valid command waiting in RX FIFO 0.
+    ///
+    /// Malformed frames are skipped. Call it until it returns `None` from the
+    /// interrupt handler: that also clears the new message interrupt.
+    pub fn receive_command(&mut self) -> Option<Command> {
+        self.can.clear_interrupt(Interrupt::RxFifo0NewMsg);
+        let mut buffer = [0; 8];
+        while let Ok(frame) = self.can.receive0(&mut buffer) {
+            let frame = frame.unwrap();
+            if frame.rtr {
+                continue;
+            }
+            let len = (frame.len as usize).min(buffer.len());
+            match Command::parse(&buffer[..len]) {
+                Some(command) => return Some(command),
+                None => defmt::warn!("Malformed CAN command: {=[u8]:#x}", buffer[..len]),
+            }
+        }
+        None
+    }
+
+    /// Returns `true` once the node has found the bus idle and joined it,
+    /// and until errors take it off: `false` right after [`Telemetry::new`]
+    /// with nothing on the pins, or in bus-off.
+    pub fn is_active(&self) -> bool {
+        // NOTE(unsafe) a read of the status register, which `self` owns.
+        let registers = unsafe { &*<Can<FDCAN> as Instance>::REGISTERS };
+        let psr = registers.psr.read();
+        // Activity 0b00: still synchronizing to the bus.
+        psr.act().bits() != 0b00 && psr.bo().bit_is_clear()
+    }
+
+    /// Restarts the node if too many errors took it off the bus.
+    ///
+    /// In bus-off the controller stops, and it only joins the bus again
+    /// after software restarts it; it then waits for 128 occurrences of 11
+    /// recessive bits before transmitting. Returns `true` if a recovery was
+    /// started.
+    pub fn recover_bus_off(&mut self) -> bool {
+        self.can.clear_interrupt(Interrupt::BusOff);
+        // NOTE(unsafe) only the bus-off flag and the INIT bit are touched, and
+        // `self` owns the instance.
+        let registers = unsafe { &*<Can<FDCAN> as Instance>::REGISTERS };
+        if registers.psr.read().bo().bit_is_clear() {
+            return false;
+        }
+        registers.cccr.modify(|_, w| w.init().clear_bit());
+        true
+    }
+
+    /// Leaves the bus and returns the FDCAN instance in configuration mode.
+    pub fn release(self) -> FdCan<Can<FDCAN>, ConfigMode> {
+        self.can.into_config_mode()
+    }
+}
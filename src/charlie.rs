@@ -0,0 +1,187 @@
+//! Charlieplexed LEDs: N * (N - 1) LEDs on N pins.
+//!
+//! Between every ordered pair of pins sits one LED, anode on the first: it
+//! lights when the first pin drives high, the second low, and the others
+//! float so no other LED sees a voltage. Only one anode pin can be high at a
+//! time, so [`Charlie::refresh`] lights one anode's LEDs per call, in turn,
+//! from a fast timer interrupt: with 4 pins, a 2 kHz interrupt refreshes
+//! the whole frame 500 times a second, each LED lit a quarter of the time.
+//!
+//! Each LED needs a resistor in series, or one per pin, half the value.
+//!
+//! Drawing goes to a frame buffer, one bit per LED; [`Charlie::present`]
+//! hands it to the display at the start of the next scan, so a frame never
+//! shows half drawn. LED `i` has anode pin `i / (N - 1)`, cathode the
+//! `i % (N - 1)`th of the other pins; [`Charlie::led`] finds it from a pair.
+//!
+//! The pins switch between output and input at every refresh, which the
+//! HAL's pin types cannot follow: [`Charlie::new`] takes pin numbers of one
+//! port, left unconfigured, and drives their registers itself. The port's
+//! clock must be on: `split` the port first.
+
+use stm32g4xx_hal as hal;
+
+use hal::stm32::{self, GPIOA, GPIOB, GPIOC, GPIOD};
+
+/// Most pins: the frame of 8 * 7 LEDs fits a `u64`.
+pub const MAX_PINS: usize = 8;
+
+/// A GPIO port, for [`Charlie::new`].
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Port {
+    A,
+    B,
+    C,
+    D,
+}
+
+/// Charlieplexing errors.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    /// No LED of that index, or between those pins.
+    InvalidLed,
+}
+
+/// `N` pins driving `N * (N - 1)` LEDs.
+pub struct Charlie<const N: usize> {
+    port: Port,
+    pins: [u8; N],
+    // The frame being drawn, and the one on display.
+    buffer: u64,
+    shown: u64,
+    pending: bool,
+    // The anode pin lit by the last refresh.
+    anode: usize,
+}
+
+impl<const N: usize> Charlie<N> {
+    /// Drives `pins`, numbers 0 to 15 on `port`, all LEDs off.
+    ///
+    /// # Panics
+    ///
+    /// For fewer than 2 or more than [`MAX_PINS`] pins, or a pin number
+    /// above 15.
+    pub fn new(port: Port, pins: [u8; N]) -> Self {
+        assert!((2..=MAX_PINS).contains(&N));
+        assert!(pins.iter().all(|&pin| pin < 16));
+        let charlie = Charlie { port, pins, buffer: 0, shown: 0, pending: false, anode: N - 1 };
+        charlie.float_all();
+        charlie
+    }
+
+    /// The number of LEDs.
+    pub const fn len(&self) -> usize {
+        N * (N - 1)
+    }
+
+    /// Always `false`: two pins drive two LEDs.
+    pub const fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// The LED from pin `anode` to pin `cathode`, indices in the pin array.
+    pub fn led(&self, anode: usize, cathode: usize) -> Option<usize> {
+        if anode >= N || cathode >= N || anode == cathode {
+            return None;
+        }
+        let offset = if cathode < anode { cathode } else { cathode - 1 };
+        Some(anode * (N - 1) + offset)
+    }
+
+    /// Turns LED `index` on or off in the frame buffer.
+    pub fn set(&mut self, index: usize, on: bool) -> Result<(), Error> {
+        if index >= self.len() {
+            return Err(Error::InvalidLed);
+        }
+        if on {
+            self.buffer |= 1 << index;
+        } else {
+            self.buffer &= !(1 << index);
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if LED `index` is on in the frame buffer.
+    pub fn get(&self, index: usize) -> bool {
+        index < self.len() && self.buffer & (1 << index) != 0
+    }
+
+    /// Replaces the frame buffer, bit `i` for LED `i`.
+    pub fn set_frame(&mut self, frame: u64) {
+        self.buffer = frame & self.mask();
+    }
+
+    /// The frame buffer.
+    pub fn frame(&self) -> u64 {
+        self.buffer
+    }
+
+    /// Turns every LED off in the frame buffer.
+    pub fn clear(&mut self) {
+        self.buffer = 0;
+    }
+
+    /// Shows the frame buffer from the next scan.
+    pub fn present(&mut self) {
+        self.pending = true;
+    }
+
+    /// Lights the LEDs of the next anode pin: call from a fast timer
+    /// interrupt, at `N` times the frame rate or more.
+    pub fn refresh(&mut self) {
+        self.anode = (self.anode + 1) % N;
+        if self.anode == 0 && self.pending {
+            self.shown = self.buffer;
+            self.pending = false;
+        }
+
+        let gpio = self.gpio();
+        // Everything floats first, so no LED of the last anode flashes.
+        self.float_all();
+        let mut high = 0_u32;
+        let mut low = 0_u32;
+        let mut outputs = 0_u32;
+        let row = (self.shown >> (self.anode * (N - 1))) & ((1 << (N - 1)) - 1);
+        if row != 0 {
+            let anode = self.pins[self.anode];
+            high |= 1 << anode;
+            outputs |= 1 << (2 * anode);
+            for (offset, &pin) in self.pins.iter().filter(|&&pin| pin != anode).enumerate() {
+                if row & (1 << offset) != 0 {
+                    low |= 1 << pin;
+                    outputs |= 1 << (2 * pin);
+                }
+            }
+        }
+        gpio.bsrr.write(|w| unsafe { w.bits(high | (low << 16)) });
+        gpio.moder.modify(|r, w| unsafe { w.bits(r.bits() | outputs) });
+    }
+
+    /// Floats every pin and returns the port and pin numbers.
+    pub fn release(self) -> (Port, [u8; N]) {
+        self.float_all();
+        (self.port, self.pins)
+    }
+
+    fn mask(&self) -> u64 {
+        if self.len() == 64 { u64::MAX } else { (1 << self.len()) - 1 }
+    }
+
+    fn float_all(&self) {
+        let modes = self.pins.iter().fold(0_u32, |mask, &pin| mask | (0b11 << (2 * pin)));
+        self.gpio().moder.modify(|r, w| unsafe { w.bits(r.bits() & !modes) });
+    }
+
+    fn gpio(&self) -> &'static stm32::gpioc::RegisterBlock {
+        // NOTE(unsafe) only the bits of the pins handed to `new` are written;
+        // the GPIO ports share one register layout.
+        unsafe {
+            match self.port {
+                Port::A => &*(GPIOA::ptr() as *const stm32::gpioc::RegisterBlock),
+                Port::B => &*(GPIOB::ptr() as *const stm32::gpioc::RegisterBlock),
+                Port::C => &*GPIOC::ptr(),
+                Port::D => &*GPIOD::ptr(),
+            }
+        }
+    }
+}
@@ -0,0 +1,461 @@
+//! System clock from the PLL at 170 MHz, the top speed of the G474.
+//!
+//! Out of reset the chip runs from the 16 MHz HSI. [`freeze_170mhz`] takes
+//! the constrained RCC and runs the core, the buses and the timers at
+//! 170 MHz from the PLL, fed by the HSI:
+//!
+//! | Step          | Setting                                   |
+//! |---------------|-------------------------------------------|
+//! | PLL           | HSI / 4 (M) x 85 (N) = 340 MHz, / 2 (R)   |
+//! | Regulator     | range 1 boost mode, needed above 150 MHz  |
+//! | Flash         | 4 wait states, prefetch and caches on     |
+//! | AHB, APB1/2   | not divided: 170 MHz                      |
+//!
+//! The HAL's `freeze` sets at most 2 flash wait states and leaves the
+//! regulator in normal mode, enough up to 90 MHz only; the switch goes
+//! through the half-speed AHB step the reference manual asks for when
+//! raising the clock past 80 MHz, then finishes the job by hand.
+//!
+//! Every module and example takes its clocks from `rcc.clocks`, which the
+//! switch keeps up to date, so timer periods, baud rates and dead-times
+//! follow without any change.
+//!
+//! # Clock sources
+//!
+//! [`freeze`] does the same from the sources of a [`ClockConfig`]: the PLL
+//! from the HSE, a crystal or the 8 MHz MCO output of the ST-LINK in bypass
+//! mode, and the low-speed clock for the RTC from the 32.768 kHz LSE crystal.
+//! A source that does not start within its timeout, a missing crystal or an
+//! open solder bridge, is turned off again and replaced by the internal
+//! oscillator, HSI or LSI, with a warning in the log: the board still runs,
+//! and [`Sources`] tells which oscillators it actually runs from.
+//!
+//! # Clock security
+//!
+//! An HSE that stops later, a broken crystal or an unplugged ST-LINK, would
+//! stall the PLL and everything clocked from it. With [`enable_css`], the
+//! clock security system watches the HSE: on a failure the hardware turns
+//! the HSE and the PLL off, switches the system clock to the HSI and raises
+//! the non-maskable interrupt, where [`handle_css`] clears the event. From
+//! then on the chip runs at 16 MHz, the frequencies of [`css_clocks`]: the
+//! timers have to be started again from those.
+//!
+//! # Switching at runtime
+//!
+//! [`set_sysclk`] moves the running system between the 16 MHz HSI and the
+//! 170 MHz PLL set up by [`freeze`], in the order the reference manual asks
+//! for, and hands back the new clocks. Everything that divided the old ones
+//! has to divide the new ones: start the HAL timers again with
+//! `Timer::new(tim, &clocks)`, and give the serial ports their new baud
+//! rate divider with [`set_baud_rate`].
+
+use stm32g4xx_hal as hal;
+
+use hal::rcc::{Clocks, Config, Enable, PLLClocks, PLLSrc, PllConfig, PllMDiv, PllNMul, PllRDiv, Prescaler, Rcc};
+use hal::stm32::{usart1, FLASH, PWR, RCC};
+use hal::time::{Bps, Hertz};
+
+#[cfg(feature = "logging")]
+use defmt::Format;
+
+use crate::power::{self, VoltageRange};
+
+/// HSI frequency, the system clock out of reset.
+pub const HSI: Hertz = Hertz(16_000_000);
+/// System clock after [`freeze_170mhz`].
+pub const SYSCLK_170MHZ: Hertz = Hertz(170_000_000);
+/// Frequency of the ST-LINK MCO output, for [`ClockConfig::hse_bypass`].
+pub const STLINK_MCO: Hertz = Hertz(8_000_000);
+/// Time the HSE gets to start, in milliseconds.
+pub const HSE_TIMEOUT_MS: u32 = 100;
+/// Time the LSE gets to start, in milliseconds: a 32 kHz crystal is slow.
+pub const LSE_TIMEOUT_MS: u32 = 2000;
+
+// PLL input after the M divider, for any HSE that is a multiple of it.
+const PLL_INPUT: u32 = 4_000_000;
+const PLL_M: [PllMDiv; 16] = [
+    PllMDiv::DIV_1,
+    PllMDiv::DIV_2,
+    PllMDiv::DIV_3,
+    PllMDiv::DIV_4,
+    PllMDiv::DIV_5,
+    PllMDiv::DIV_6,
+    PllMDiv::DIV_7,
+    PllMDiv::DIV_8,
+    PllMDiv::DIV_9,
+    PllMDiv::DIV_10,
+    PllMDiv::DIV_11,
+    PllMDiv::DIV_12,
+    PllMDiv::DIV_13,
+    PllMDiv::DIV_14,
+    PllMDiv::DIV_15,
+    PllMDiv::DIV_16,
+];
+
+/// Oscillator feeding the PLL.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "logging", derive(Format))]
+pub enum PllSource {
+    /// Internal 16 MHz RC oscillator.
+    Hsi,
+    /// HSE from a crystal, at the given frequency in hertz.
+    HseCrystal(u32),
+    /// HSE from an external clock signal, such as the ST-LINK MCO, at the
+    /// given frequency in hertz.
+    HseBypass(u32),
+}
+
+/// Low-speed oscillator, for the RTC.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "logging", derive(Format))]
+pub enum LowSpeedSource {
+    /// Internal 32 kHz RC oscillator.
+    Lsi,
+    /// 32.768 kHz crystal.
+    Lse,
+}
+
+/// System clock speeds for [`set_sysclk`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "logging", derive(Format))]
+pub enum Sysclk {
+    /// The HSI, 16 MHz.
+    Hsi16,
+    /// The HSI with the AHB divided by 8: 2 MHz, for low-power run mode.
+    Hsi2,
+    /// The PLL, 170 MHz.
+    Pll170,
+}
+
+/// Oscillators wanted, as passed to [`freeze`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "logging", derive(Format))]
+pub struct ClockConfig {
+    pll: PllSource,
+    low_speed: Option<LowSpeedSource>,
+}
+
+impl ClockConfig {
+    /// The PLL from the HSI and no low-speed oscillator, as [`freeze_170mhz`].
+    pub fn hsi() -> Self {
+        ClockConfig { pll: PllSource::Hsi, low_speed: None }
+    }
+
+    /// The PLL from an HSE crystal of `frequency`, a multiple of 4 MHz.
+    pub fn hse(mut self, frequency: Hertz) -> Self {
+        self.pll = PllSource::HseCrystal(frequency.0);
+        self
+    }
+
+    /// The PLL from an external clock of `frequency` on the HSE input, a
+    /// multiple of 4 MHz: [`STLINK_MCO`] with the ST-LINK solder bridges set.
+    pub fn hse_bypass(mut self, frequency: Hertz) -> Self {
+        self.pll = PllSource::HseBypass(frequency.0);
+        self
+    }
+
+    /// Starts the LSE crystal for the RTC.
+    pub fn lse(mut self) -> Self {
+        self.low_speed = Some(LowSpeedSource::Lse);
+        self
+    }
+
+    /// Starts the LSI for the RTC.
+    pub fn lsi(mut self) -> Self {
+        self.low_speed = Some(LowSpeedSource::Lsi);
+        self
+    }
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self::hsi()
+    }
+}
+
+/// Oscillators actually in use after [`freeze`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "logging", derive(Format))]
+pub struct Sources {
+    /// Feeding the PLL, and so the system clock.
+    pub pll: PllSource,
+    /// Running for the RTC, if one was asked for.
+    pub low_speed: Option<LowSpeedSource>,
+}
+
+/// Flash wait states for an AHB clock of `hclk` in voltage `range`.
+pub fn flash_wait_states(hclk: Hertz, range: VoltageRange) -> u8 {
+    // One more wait state every 34 MHz in boost mode, every 30 MHz without,
+    // every 12 MHz in range 2.
+    let step = match range {
+        VoltageRange::Range1Boost => 34_000_000,
+        VoltageRange::Range1 => 30_000_000,
+        VoltageRange::Range2 => 12_000_000,
+    };
+    (hclk.0.saturating_sub(1) / step) as u8
+}
+
+/// Prescaler and auto-reload register values for a timer clocked at `clock`
+/// to count `rate` periods a second: the smallest prescaler that fits the
+/// period in 16 bits, so the period keeps as many ticks as it can.
+pub fn timer_prescaler(clock: Hertz, rate: Hertz) -> (u16, u32) {
+    let ticks = (clock.0 / rate.0.max(1)).max(1);
+    let psc = (ticks - 1) / (1 << 16);
+    let arr = ticks / (psc + 1) - 1;
+    (psc as u16, arr)
+}
+
+/// Runs the system clock at 170 MHz from the PLL fed by the HSI, with flash
+/// wait states and boost mode set to match, and returns the RCC with the new
+/// clocks.
+pub fn freeze_170mhz(rcc: Rcc) -> Rcc {
+    freeze(rcc, ClockConfig::hsi()).0
+}
+
+/// Runs the system clock at 170 MHz from the PLL fed by the sources of
+/// `config`, falling back to the internal oscillators, and returns the RCC
+/// with the new clocks and the sources in use.
+pub fn freeze(rcc: Rcc, config: ClockConfig) -> (Rcc, Sources) {
+    unsafe {
+        //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+        let rcc = &(*RCC::ptr());
+        PWR::enable(rcc);
+
+        // Boost mode before the clock goes past 150 MHz.
+        let pwr = &(*PWR::ptr());
+        pwr.cr5.modify(|_, w| w.r1mode().clear_bit());
+    }
+
+    let sources = Sources {
+        pll: start_pll_source(config.pll),
+        low_speed: config.low_speed.map(start_low_speed),
+    };
+    #[cfg(feature = "logging")]
+    crate::info!("Clock sources: PLL from {:?}, low-speed {:?}", sources.pll, sources.low_speed);
+
+    // HSI / 4 or HSE / M, then x 85 / 2. Switch to the PLL with the AHB at
+    // half speed, 85 MHz, for which the HAL's 2 wait states are enough.
+    let (mux, m) = match sources.pll {
+        PllSource::Hsi => (PLLSrc::HSI, PllMDiv::DIV_4),
+        PllSource::HseCrystal(frequency) => (PLLSrc::HSE(Hertz(frequency)), PLL_M[(frequency / PLL_INPUT - 1) as usize]),
+        PllSource::HseBypass(frequency) => (PLLSrc::HSE_BYPASS(Hertz(frequency)), PLL_M[(frequency / PLL_INPUT - 1) as usize]),
+    };
+    let pll = PllConfig {
+        mux,
+        m,
+        n: PllNMul::MUL_85,
+        r: Some(PllRDiv::DIV_2),
+        q: None,
+        p: None,
+    };
+    let mut rcc = rcc.freeze(Config::pll().pll_cfg(pll).ahb_psc(Prescaler::Div2));
+
+    unsafe {
+        let flash = &(*FLASH::ptr());
+        let latency = flash_wait_states(SYSCLK_170MHZ, VoltageRange::Range1Boost);
+        flash.acr.modify(|_, w| w.latency().bits(latency).prften().set_bit().icen().set_bit().dcen().set_bit());
+        while flash.acr.read().latency().bits() != latency {}
+    }
+
+    // At least 1 us at half speed before the AHB goes to full speed.
+    cortex_m::asm::delay(SYSCLK_170MHZ.0 / 2 / 1_000_000);
+    unsafe {
+        let rcc = &(*RCC::ptr());
+        rcc.cfgr.modify(|_, w| w.hpre().bits(0b0000));
+    }
+
+    // The HAL derives the APB clocks from the system clock, so only the AHB
+    // and core clocks need fixing.
+    rcc.clocks.ahb_clk = rcc.clocks.sys_clk;
+    rcc.clocks.core_clk = rcc.clocks.sys_clk;
+    (rcc, sources)
+}
+
+// Polls `ready` once a millisecond, at the 16 MHz of the HSI the chip runs
+// from until `freeze` switches, for up to `timeout_ms`.
+fn wait_ready(timeout_ms: u32, ready: impl Fn() -> bool) -> bool {
+    for _ in 0..timeout_ms {
+        if ready() {
+            return true;
+        }
+        cortex_m::asm::delay(HSI.0 / 1000);
+    }
+    ready()
+}
+
+// Starts the HSE of `source`, or returns `Hsi` if it cannot feed the PLL.
+fn start_pll_source(source: PllSource) -> PllSource {
+    let (frequency, bypass) = match source {
+        PllSource::Hsi => return PllSource::Hsi,
+        PllSource::HseCrystal(frequency) => (frequency, false),
+        PllSource::HseBypass(frequency) => (frequency, true),
+    };
+    if !frequency.is_multiple_of(PLL_INPUT) || !(PLL_INPUT..=48_000_000).contains(&frequency) {
+        #[cfg(feature = "logging")]
+        crate::warn!("HSE of {} Hz is not a multiple of 4 MHz up to 48 MHz, PLL from HSI", frequency);
+        return PllSource::Hsi;
+    }
+
+    let rcc = unsafe { &(*RCC::ptr()) };
+    // The bypass is set while the HSE is off.
+    rcc.cr.modify(|_, w| w.hsebyp().bit(bypass));
+    rcc.cr.modify(|_, w| w.hseon().set_bit());
+    if wait_ready(HSE_TIMEOUT_MS, || rcc.cr.read().hserdy().bit_is_set()) {
+        return source;
+    }
+    rcc.cr.modify(|_, w| w.hseon().clear_bit().hsebyp().clear_bit());
+    #[cfg(feature = "logging")]
+    crate::warn!("HSE did not start within {} ms, PLL from HSI", HSE_TIMEOUT_MS);
+    PllSource::Hsi
+}
+
+// Starts the low-speed oscillator of `source`, the LSI if the LSE fails.
+fn start_low_speed(source: LowSpeedSource) -> LowSpeedSource {
+    let rcc = unsafe { &(*RCC::ptr()) };
+    if source == LowSpeedSource::Lse {
+        // The LSE lives in the backup domain, write protected out of reset.
+        let pwr = unsafe { &(*PWR::ptr()) };
+        pwr.cr1.modify(|_, w| w.dbp().set_bit());
+        rcc.bdcr.modify(|_, w| w.lseon().set_bit());
+        if wait_ready(LSE_TIMEOUT_MS, || rcc.bdcr.read().lserdy().bit_is_set()) {
+            return LowSpeedSource::Lse;
+        }
+        rcc.bdcr.modify(|_, w| w.lseon().clear_bit());
+        #[cfg(feature = "logging")]
+        crate::warn!("LSE did not start within {} ms, falling back to LSI", LSE_TIMEOUT_MS);
+    }
+    rcc.csr.modify(|_, w| w.lsion().set_bit());
+    while rcc.csr.read().lsirdy().bit_is_clear() {}
+    LowSpeedSource::Lsi
+}
+
+/// Turns the clock security system on, if the HSE is running.
+///
+/// Returns `false`, with nothing to watch, when it is not.
+pub fn enable_css() -> bool {
+    let rcc = unsafe { &(*RCC::ptr()) };
+    if rcc.cr.read().hserdy().bit_is_clear() {
+        return false;
+    }
+    rcc.cr.modify(|_, w| w.csson().set_bit());
+    true
+}
+
+/// Call from `NonMaskableInt`: clears an HSE failure detected by the clock
+/// security system.
+///
+/// Returns `false` if the NMI came from something else.
+pub fn handle_css() -> bool {
+    let rcc = unsafe { &(*RCC::ptr()) };
+    if rcc.cifr.read().cssf().bit_is_clear() {
+        return false;
+    }
+    // The NMI keeps firing until the flag is cleared.
+    rcc.cicr.write(|w| w.cssc().set_bit());
+    true
+}
+
+/// The clocks after the clock security system switched to the HSI: 16 MHz
+/// everywhere, the bus prescalers of [`freeze`] being 1.
+pub fn css_clocks() -> Clocks {
+    Clocks::default()
+}
+
+/// Switches the system clock to `sysclk`, with the flash wait states and
+/// regulator mode to match, and returns the new clocks.
+///
+/// Going to the PLL or to 16 MHz leaves low-power run mode, and to the PLL
+/// also puts the regulator back in range 1 boost; going slower leaves the
+/// regulator to the `power` module. Call after [`freeze`] for the PLL: it
+/// keeps the source it was given there.
+pub fn set_sysclk(sysclk: Sysclk) -> Clocks {
+    let rcc = unsafe { &(*RCC::ptr()) };
+    let flash = unsafe { &(*FLASH::ptr()) };
+    let pwr = unsafe { &(*PWR::ptr()) };
+    match sysclk {
+        Sysclk::Hsi16 | Sysclk::Hsi2 => {
+            // 16 MHz is past what the low-power regulator allows.
+            if sysclk == Sysclk::Hsi16 {
+                power::exit_low_power_run();
+            }
+            rcc.cr.modify(|_, w| w.hsion().set_bit());
+            while rcc.cr.read().hsirdy().bit_is_clear() {}
+            // The wait states of the range the regulator is in: 0 in range
+            // 1, 1 in range 2; `power::set_voltage_range` raises them for a
+            // lower range later.
+            let range = power::voltage_range();
+            let latency = flash_wait_states(HSI, range);
+            if latency > flash.acr.read().latency().bits() {
+                flash.acr.modify(|_, w| unsafe { w.latency().bits(latency) });
+                while flash.acr.read().latency().bits() != latency {}
+            }
+            let (hpre, hclk) = match sysclk {
+                Sysclk::Hsi2 => (0b1010, Hertz(HSI.0 / 8)),
+                _ => (0b0000, HSI),
+            };
+            rcc.cfgr.modify(|_, w| unsafe { w.hpre().bits(hpre).sw().bits(0b01) });
+            while rcc.cfgr.read().sws().bits() != 0b01 {}
+            rcc.cr.modify(|_, w| w.pllon().clear_bit());
+
+            // Fewer wait states and normal mode only once the clock is slow.
+            flash.acr.modify(|_, w| unsafe { w.latency().bits(flash_wait_states(hclk, range)) });
+            if range == VoltageRange::Range1Boost {
+                pwr.cr5.modify(|_, w| w.r1mode().set_bit());
+            }
+            Clocks {
+                sys_clk: HSI,
+                core_clk: hclk,
+                ahb_clk: hclk,
+                apb1_clk: hclk,
+                apb1_tim_clk: hclk,
+                apb2_clk: hclk,
+                apb2_tim_clk: hclk,
+                pll_clk: PLLClocks { r: None, q: None, p: None },
+            }
+        }
+        Sysclk::Pll170 => {
+            // Main regulator, range 1 boost and wait states before the clock
+            // is fast.
+            power::exit_low_power_run();
+            pwr.cr1.modify(|_, w| unsafe { w.vos().bits(0b01) });
+            while pwr.sr2.read().vosf().bit_is_set() {}
+            pwr.cr5.modify(|_, w| w.r1mode().clear_bit());
+            let latency = flash_wait_states(SYSCLK_170MHZ, VoltageRange::Range1Boost);
+            flash.acr.modify(|_, w| unsafe { w.latency().bits(latency) });
+            while flash.acr.read().latency().bits() != latency {}
+
+            rcc.cr.modify(|_, w| w.pllon().set_bit());
+            while rcc.cr.read().pllrdy().bit_is_clear() {}
+            // Through the half-speed AHB step, as in `freeze`.
+            rcc.cfgr.modify(|_, w| unsafe { w.hpre().bits(0b1000).sw().bits(0b11) });
+            while rcc.cfgr.read().sws().bits() != 0b11 {}
+            cortex_m::asm::delay(SYSCLK_170MHZ.0 / 2 / 1_000_000);
+            rcc.cfgr.modify(|_, w| unsafe { w.hpre().bits(0b0000) });
+
+            Clocks {
+                sys_clk: SYSCLK_170MHZ,
+                core_clk: SYSCLK_170MHZ,
+                ahb_clk: SYSCLK_170MHZ,
+                apb1_clk: SYSCLK_170MHZ,
+                apb1_tim_clk: SYSCLK_170MHZ,
+                apb2_clk: SYSCLK_170MHZ,
+                apb2_tim_clk: SYSCLK_170MHZ,
+                pll_clk: PLLClocks { r: Some(SYSCLK_170MHZ), q: None, p: None },
+            }
+        }
+    }
+}
+
+/// Sets the baud rate divider of a USART clocked from its APB clock `pclk`,
+/// as the HAL does when it opens the port, after the last character went
+/// out.
+///
+/// The USART registers come from the PAC, `unsafe { &*USART2::ptr() }`:
+/// the HAL's `Serial` keeps its peripheral.
+pub fn set_baud_rate(usart: &usart1::RegisterBlock, pclk: Hertz, baud_rate: Bps) {
+    while usart.isr.read().tc().bit_is_clear() {}
+    // BRR is only written with the USART disabled.
+    usart.cr1.modify(|_, w| w.ue().clear_bit());
+    usart.brr.write(|w| unsafe { w.bits(pclk.0 / baud_rate.0) });
+    usart.cr1.modify(|_, w| w.ue().set_bit());
+}
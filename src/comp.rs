@@ -0,0 +1,238 @@
+//! Analog comparators (COMP1 to COMP7).
+//!
+//! Each comparator compares its non-inverting input pin against a threshold
+//! on the inverting input: a fraction of the internal reference (VREFINT,
+//! about 1.21 V) or a channel of the internal-only DAC3/DAC4. The output is
+//! routed to a configurable EXTI line, so a threshold crossing raises an
+//! interrupt without the CPU ever sampling the voltage.
+//!
+//! | Comparator | Input pin | Threshold DAC | EXTI line | Interrupt   |
+//! |------------|-----------|---------------|-----------|-------------|
+//! | COMP1      | PA1       | DAC3 ch1      | 21        | `COMP1_2_3` |
+//! | COMP2      | PA7       | DAC3 ch2      | 22        | `COMP1_2_3` |
+//! | COMP3      | PA0       | DAC3 ch1      | 29        | `COMP1_2_3` |
+//! | COMP4      | PB0       | DAC3 ch2      | 30        | `COMP4_5_6` |
+//! | COMP5      | PB13      | DAC4 ch1      | 31        | `COMP4_5_6` |
+//! | COMP6      | PB11      | DAC4 ch2      | 32        | `COMP4_5_6` |
+//! | COMP7      | PB14      | DAC4 ch1      | 33        | `COMP7`     |
+//!
+//! Comparators sharing a DAC channel also share the `Dac` threshold value.
+//! The G431 has COMP1 to COMP4 only.
+
+use stm32g4xx_hal as hal;
+
+use hal::gpio::{gpioa, gpiob, Analog, SignalEdge};
+use hal::rcc::Enable;
+#[cfg(not(feature = "board-g431rb"))]
+use hal::stm32::DAC4;
+use hal::stm32::{COMP, DAC3, EXTI, RCC};
+
+/// Voltage on the inverting input.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Threshold {
+    /// 1/4 of VREFINT (about 0.30 V).
+    QuarterVrefint,
+    /// 1/2 of VREFINT (about 0.60 V).
+    HalfVrefint,
+    /// 3/4 of VREFINT (about 0.91 V).
+    ThreeQuarterVrefint,
+    /// VREFINT (about 1.21 V).
+    Vrefint,
+    /// Raw 12-bit code of the internal DAC channel, `code / 4095 * VDDA`.
+    Dac(u16),
+}
+
+impl Threshold {
+    /// INMSEL value selecting this threshold.
+    fn inmsel(self) -> u8 {
+        match self {
+            Threshold::QuarterVrefint => 0b000,
+            Threshold::HalfVrefint => 0b001,
+            Threshold::ThreeQuarterVrefint => 0b010,
+            Threshold::Vrefint => 0b011,
+            Threshold::Dac(_) => 0b100,
+        }
+    }
+}
+
+/// Comparator hysteresis.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Hysteresis {
+    None = 0,
+    Mv10 = 1,
+    Mv20 = 2,
+    Mv30 = 3,
+    Mv40 = 4,
+    Mv50 = 5,
+    Mv60 = 6,
+    Mv70 = 7,
+}
+
+fn exti() -> &'static hal::stm32::exti::RegisterBlock {
+    // NOTE(unsafe) only the bits of the comparator's own EXTI line are modified.
+    unsafe { &*EXTI::ptr() }
+}
+
+fn exti_listen(line: u8, edge: SignalEdge) {
+    let exti = exti();
+    let rising = matches!(edge, SignalEdge::Rising | SignalEdge::RisingFalling);
+    let falling = matches!(edge, SignalEdge::Falling | SignalEdge::RisingFalling);
+    if line < 32 {
+        let mask = 1 << line;
+        exti.rtsr1.modify(|r, w| unsafe { w.bits(if rising { r.bits() | mask } else { r.bits() & !mask }) });
+        exti.ftsr1.modify(|r, w| unsafe { w.bits(if falling { r.bits() | mask } else { r.bits() & !mask }) });
+        exti.imr1.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+    } else {
+        let mask = 1 << (line - 32);
+        exti.rtsr2.modify(|r, w| unsafe { w.bits(if rising { r.bits() | mask } else { r.bits() & !mask }) });
+        exti.ftsr2.modify(|r, w| unsafe { w.bits(if falling { r.bits() | mask } else { r.bits() & !mask }) });
+        exti.imr2.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+    }
+}
+
+fn exti_unlisten(line: u8) {
+    let exti = exti();
+    if line < 32 {
+        exti.imr1.modify(|r, w| unsafe { w.bits(r.bits() & !(1 << line)) });
+    } else {
+        exti.imr2.modify(|r, w| unsafe { w.bits(r.bits() & !(1 << (line - 32))) });
+    }
+}
+
+fn exti_is_pending(line: u8) -> bool {
+    let exti = exti();
+    if line < 32 {
+        exti.pr1.read().bits() & (1 << line) != 0
+    } else {
+        exti.pr2.read().bits() & (1 << (line - 32)) != 0
+    }
+}
+
+fn exti_unpend(line: u8) {
+    let exti = exti();
+    // Pending bits are cleared by writing 1, zeros are ignored.
+    if line < 32 {
+        exti.pr1.write(|w| unsafe { w.bits(1 << line) });
+    } else {
+        exti.pr2.write(|w| unsafe { w.bits(1 << (line - 32)) });
+    }
+}
+
+macro_rules! comparators {
+    ($($COMPX:ident: ($csr:ident, $PIN:ty, $DAC:ident, $dhr:ident, $dhr_field:ident,
+        $mode:ident, $en:ident, $rdy:ident, $line:literal),)+) => {
+        $(
+            #[doc = concat!("Analog comparator `", stringify!($COMPX), "`.")]
+            pub struct $COMPX {
+                _pin: $PIN,
+            }
+
+            impl $COMPX {
+                /// EXTI line the comparator output is routed to.
+                pub const EXTI_LINE: u8 = $line;
+
+                /// Enables the comparator on its input pin.
+                ///
+                /// `COMP` is shared by all comparators, so it is only borrowed.
+                pub fn new(_comp: &COMP, pin: $PIN, threshold: Threshold, hysteresis: Hysteresis) -> Self {
+                    unsafe {
+                        //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+                        let rcc = &(*RCC::ptr());
+                        // The comparators are clocked together with SYSCFG.
+                        rcc.apb2enr.modify(|_, w| w.syscfgen().set_bit());
+                        $DAC::enable(rcc);
+                    }
+
+                    let mut comp = $COMPX { _pin: pin };
+                    comp.set_threshold(threshold);
+                    Self::regs().$csr.modify(|_, w| unsafe {
+                        w.hyst().bits(hysteresis as u8).inpsel().clear_bit().en().set_bit()
+                    });
+                    comp
+                }
+
+                /// Changes the inverting input.
+                pub fn set_threshold(&mut self, threshold: Threshold) {
+                    if let Threshold::Dac(code) = threshold {
+                        // NOTE(unsafe) only this comparator's DAC channel is touched.
+                        let dac = unsafe { &*$DAC::ptr() };
+                        // The mode can only be changed while the channel is off; a
+                        // comparator sharing the channel may have enabled it already.
+                        if dac.dac_cr.read().$en().bit_is_clear() {
+                            // Connected to on-chip peripherals only, buffer disabled.
+                            dac.dac_mcr.modify(|_, w| unsafe { w.$mode().bits(0b011) });
+                            dac.dac_cr.modify(|_, w| w.$en().set_bit());
+                            while dac.dac_sr.read().$rdy().bit_is_clear() {}
+                        }
+                        dac.$dhr.write(|w| unsafe { w.$dhr_field().bits(code.min(0x0FFF)) });
+                    }
+
+                    let vrefint = !matches!(threshold, Threshold::Dac(_));
+                    let fraction = vrefint && threshold != Threshold::Vrefint;
+                    Self::regs().$csr.modify(|_, w| unsafe {
+                        w.inmsel()
+                            .bits(threshold.inmsel())
+                            .scalen()
+                            .bit(vrefint)
+                            .brgen()
+                            .bit(fraction)
+                    });
+                }
+
+                /// Returns `true` while the input is above the threshold.
+                pub fn output(&self) -> bool {
+                    Self::regs().$csr.read().value().bit_is_set()
+                }
+
+                /// Routes the output to its EXTI line and unmasks it.
+                ///
+                /// Note, you will also have to unmask the comparator interrupt in the NVIC.
+                pub fn listen(&mut self, edge: SignalEdge) {
+                    exti_listen($line, edge);
+                }
+
+                /// Masks the EXTI line of the comparator.
+                pub fn unlisten(&mut self) {
+                    exti_unlisten($line);
+                }
+
+                /// Returns `true` if an output edge is pending on the EXTI line.
+                pub fn is_pending(&self) -> bool {
+                    exti_is_pending($line)
+                }
+
+                /// Clears the pending EXTI flag.
+                pub fn clear_pending(&mut self) {
+                    exti_unpend($line);
+                }
+
+                /// Disables the comparator and returns the input pin.
+                pub fn release(self) -> $PIN {
+                    exti_unlisten($line);
+                    Self::regs().$csr.modify(|_, w| w.en().clear_bit());
+                    self._pin
+                }
+
+                fn regs() -> &'static hal::stm32::comp::RegisterBlock {
+                    // NOTE(unsafe) each comparator only writes its own CSR.
+                    unsafe { &*COMP::ptr() }
+                }
+            }
+        )+
+    };
+}
+
+comparators! {
+    Comp1: (c1csr, gpioa::PA1<Analog>, DAC3, dac_dhr12r1, dacc1dhr, mode1, en1, dac1rdy, 21),
+    Comp2: (c2csr, gpioa::PA7<Analog>, DAC3, dac_dhr12r2, dacc2dhr, mode2, en2, dac2rdy, 22),
+    Comp3: (c3csr, gpioa::PA0<Analog>, DAC3, dac_dhr12r1, dacc1dhr, mode1, en1, dac1rdy, 29),
+    Comp4: (c4csr, gpiob::PB0<Analog>, DAC3, dac_dhr12r2, dacc2dhr, mode2, en2, dac2rdy, 30),
+}
+
+// The G431 stops at COMP4, and has no DAC4.
+#[cfg(not(feature = "board-g431rb"))]
+comparators! {
+    Comp5: (c5csr, gpiob::PB13<Analog>, DAC4, dac_dhr12r1, dacc1dhr, mode1, en1, dac1rdy, 31),
+    Comp6: (c6csr, gpiob::PB11<Analog>, DAC4, dac_dhr12r2, dacc2dhr, mode2, en2, dac2rdy, 32),
+    Comp7: (c7csr, gpiob::PB14<Analog>, DAC4, dac_dhr12r1, dacc1dhr, mode1, en1, dac1rdy, 33),
+}
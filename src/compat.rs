@@ -0,0 +1,296 @@
+//! embedded-hal 1.0 traits for the HAL's pins, delays and buses.
+//!
+//! The drivers of the crate take the traits of the released embedded-hal,
+//! 1.0, so drivers written for any 1.0 HAL plug into them and they plug
+//! into other boards. `stm32g4xx-hal` implements the traits of 0.2: wrapped
+//! in a [`Compat`], a type of the HAL implements the 1.0 traits from its 0.2
+//! ones:
+//!
+//! | The HAL type implements (0.2)             | `Compat` implements (1.0)            |
+//! |-------------------------------------------|--------------------------------------|
+//! | `digital::v2::OutputPin`                  | `digital::OutputPin`                 |
+//! | and `digital::v2::StatefulOutputPin`      | and `digital::StatefulOutputPin`     |
+//! | `digital::v2::InputPin`                   | `digital::InputPin`                  |
+//! | `blocking::delay::DelayUs<u32>`           | `delay::DelayNs`                     |
+//! | `blocking::i2c::{Read, Write, WriteRead}` | `i2c::I2c`                           |
+//! | `blocking::spi::{Transfer, Write}`        | `spi::SpiBus`                        |
+//! | the HAL's `gpio::ExtiPin`                 | the same, passed through             |
+//! | `button::ExtiLine`                        | the same, passed through             |
+//!
+//! ```text
+//! let led = Led::new(Compat(gpioa.pa5.into_push_pull_output()));
+//! ```
+//!
+//! An `SpiDevice`, the bus with its chip select, is an [`ExclusiveDevice`]
+//! of a wrapped bus and pin. The pins of the HAL have a `()` error they
+//! never return: the digital traits of a `Compat` have `Infallible` ones.
+//!
+//! 0.2 has no I2C transaction: [`I2c::transaction`] runs the sequences it
+//! has a call for, a write, a read, or a write then a read as a write-read,
+//! with a repeated start, and returns [`Error::Unsupported`] for any other,
+//! touching no line, rather than split it with stops the device would see.
+
+use core::convert::Infallible;
+use core::fmt::Debug;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{self, InputPin, OutputPin, StatefulOutputPin};
+use embedded_hal::i2c::{self, I2c, SevenBitAddress};
+use embedded_hal::spi::{self, SpiBus, SpiDevice};
+
+use stm32g4xx_hal as hal;
+
+use hal::gpio::{ExtiPin, SignalEdge};
+use hal::hal::blocking::delay::DelayUs;
+use hal::hal::blocking::i2c::{Read, Write, WriteRead};
+use hal::hal::blocking::spi::{Transfer, Write as SpiWrite};
+use hal::hal::digital::v2;
+use hal::stm32::EXTI;
+use hal::syscfg::SysCfg;
+
+use crate::button::ExtiLine;
+
+/// A type of the HAL, with the embedded-hal 1.0 traits of its 0.2 ones.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Compat<T>(pub T);
+
+impl<T> Compat<T> {
+    /// Returns the wrapped type.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+/// An error of a wrapped bus.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error<E> {
+    /// The 0.2 error of the bus.
+    Bus(E),
+    /// An I2C transaction 0.2 cannot run as one.
+    Unsupported,
+}
+
+impl<E: Debug> i2c::Error for Error<E> {
+    fn kind(&self) -> i2c::ErrorKind {
+        i2c::ErrorKind::Other
+    }
+}
+
+impl<E: Debug> spi::Error for Error<E> {
+    fn kind(&self) -> spi::ErrorKind {
+        spi::ErrorKind::Other
+    }
+}
+
+impl<T> digital::ErrorType for Compat<T> {
+    type Error = Infallible;
+}
+
+impl<T: v2::OutputPin<Error = ()>> OutputPin for Compat<T> {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.0.set_low().ok();
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.0.set_high().ok();
+        Ok(())
+    }
+}
+
+impl<T: v2::StatefulOutputPin<Error = ()>> StatefulOutputPin for Compat<T> {
+    fn is_set_high(&mut self) -> Result<bool, Infallible> {
+        Ok(self.0.is_set_high().unwrap_or(false))
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Infallible> {
+        Ok(self.0.is_set_low().unwrap_or(false))
+    }
+}
+
+impl<T: v2::InputPin<Error = ()>> InputPin for Compat<T> {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(self.0.is_high().unwrap_or(false))
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        Ok(self.0.is_low().unwrap_or(false))
+    }
+}
+
+impl<T: ExtiLine> ExtiLine for Compat<T> {
+    const LINE: u8 = T::LINE;
+}
+
+impl<T: ExtiPin> ExtiPin for Compat<T> {
+    fn make_interrupt_source(&mut self, syscfg: &mut SysCfg) {
+        self.0.make_interrupt_source(syscfg)
+    }
+
+    fn trigger_on_edge(&mut self, exti: &mut EXTI, level: SignalEdge) {
+        self.0.trigger_on_edge(exti, level)
+    }
+
+    fn enable_interrupt(&mut self, exti: &mut EXTI) {
+        self.0.enable_interrupt(exti)
+    }
+
+    fn disable_interrupt(&mut self, exti: &mut EXTI) {
+        self.0.disable_interrupt(exti)
+    }
+
+    fn clear_interrupt_pending_bit(&mut self) {
+        self.0.clear_interrupt_pending_bit()
+    }
+
+    fn check_interrupt(&self) -> bool {
+        self.0.check_interrupt()
+    }
+}
+
+impl<T: DelayUs<u32>> DelayNs for Compat<T> {
+    fn delay_ns(&mut self, ns: u32) {
+        self.0.delay_us(ns.div_ceil(1000))
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self.0.delay_us(us)
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        for _ in 0..ms {
+            self.0.delay_us(1000)
+        }
+    }
+}
+
+impl<T, E> i2c::ErrorType for Compat<T>
+where
+    T: Read<Error = E> + Write<Error = E> + WriteRead<Error = E>,
+    E: Debug,
+{
+    type Error = Error<E>;
+}
+
+impl<T, E> I2c<SevenBitAddress> for Compat<T>
+where
+    T: Read<Error = E> + Write<Error = E> + WriteRead<Error = E>,
+    E: Debug,
+{
+    fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        Read::read(&mut self.0, address, read).map_err(Error::Bus)
+    }
+
+    fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        Write::write(&mut self.0, address, write).map_err(Error::Bus)
+    }
+
+    fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        WriteRead::write_read(&mut self.0, address, write, read).map_err(Error::Bus)
+    }
+
+    fn transaction(&mut self, address: u8, operations: &mut [i2c::Operation<'_>]) -> Result<(), Self::Error> {
+        match operations {
+            [] => Ok(()),
+            [i2c::Operation::Write(write)] => I2c::write(self, address, write),
+            [i2c::Operation::Read(read)] => I2c::read(self, address, read),
+            [i2c::Operation::Write(write), i2c::Operation::Read(read)] => I2c::write_read(self, address, write, read),
+            // Two operations of a kind merged, or a repeated start after a
+            // read, need the peripheral itself.
+            _ => Err(Error::Unsupported),
+        }
+    }
+}
+
+impl<T, E> spi::ErrorType for Compat<T>
+where
+    T: Transfer<u8, Error = E> + SpiWrite<u8, Error = E>,
+    E: Debug,
+{
+    type Error = Error<E>;
+}
+
+impl<T, E> SpiBus<u8> for Compat<T>
+where
+    T: Transfer<u8, Error = E> + SpiWrite<u8, Error = E>,
+    E: Debug,
+{
+    fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        words.fill(0);
+        self.0.transfer(words).map(|_| ()).map_err(Error::Bus)
+    }
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        self.0.write(words).map_err(Error::Bus)
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        // A word at a time, zeros past the end of `write`, the words past
+        // the end of `read` dropped.
+        for index in 0..read.len().max(write.len()) {
+            let mut word = [write.get(index).copied().unwrap_or(0)];
+            self.0.transfer(&mut word).map_err(Error::Bus)?;
+            if let Some(slot) = read.get_mut(index) {
+                *slot = word[0];
+            }
+        }
+        Ok(())
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.0.transfer(words).map(|_| ()).map_err(Error::Bus)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        // The blocking 0.2 calls return once the last word is back.
+        Ok(())
+    }
+}
+
+// Core cycles per microsecond at the top clock: a delay counted at this
+// rate lasts at least as long at any slower one.
+const MAX_CYCLES_PER_US: u32 = 170;
+
+/// An SPI bus with a device of its own, its chip select low during each
+/// transaction.
+pub struct ExclusiveDevice<BUS, CS> {
+    bus: BUS,
+    cs: CS,
+}
+
+impl<BUS: SpiBus<u8>, CS: OutputPin> ExclusiveDevice<BUS, CS> {
+    /// The device on `bus` selected by `cs`, which it drives high.
+    pub fn new(bus: BUS, mut cs: CS) -> Self {
+        cs.set_high().ok();
+        ExclusiveDevice { bus, cs }
+    }
+
+    /// Returns the bus and the chip select.
+    pub fn release(self) -> (BUS, CS) {
+        (self.bus, self.cs)
+    }
+}
+
+impl<BUS: SpiBus<u8>, CS> spi::ErrorType for ExclusiveDevice<BUS, CS> {
+    type Error = BUS::Error;
+}
+
+impl<BUS: SpiBus<u8>, CS: OutputPin> SpiDevice<u8> for ExclusiveDevice<BUS, CS> {
+    fn transaction(&mut self, operations: &mut [spi::Operation<'_, u8>]) -> Result<(), Self::Error> {
+        self.cs.set_low().ok();
+        let result = operations.iter_mut().try_for_each(|operation| match operation {
+            spi::Operation::Read(read) => self.bus.read(read),
+            spi::Operation::Write(write) => self.bus.write(write),
+            spi::Operation::Transfer(read, write) => self.bus.transfer(read, write),
+            spi::Operation::TransferInPlace(words) => self.bus.transfer_in_place(words),
+            spi::Operation::DelayNs(ns) => {
+                self.bus.flush()?;
+                cortex_m::asm::delay(ns.div_ceil(1000).saturating_mul(MAX_CYCLES_PER_US));
+                Ok(())
+            }
+        });
+        let result = result.and_then(|()| self.bus.flush());
+        self.cs.set_high().ok();
+        result
+    }
+}
@@ -0,0 +1,287 @@
+//! CORDIC co-processor: hardware trigonometry in fixed point.
+//!
+//! The CORDIC evaluates sine, cosine, atan2 and square root with an iterative
+//! shift-and-add algorithm, taking a few tens of cycles per result instead of
+//! a software routine or a lookup table. Arguments and results are fixed point
+//! numbers in [-1, 1): Q1.31 stored in an `i32`, or Q1.15 stored in an `i16`.
+//! Angles are in units of π, so `i32::MIN` is -π and an angle advancing with
+//! `wrapping_add` turns around the circle forever without any range check.
+//!
+//! There are three ways to run a calculation:
+//!
+//! - blocking: [`Cordic::sin_cos`], [`Cordic::atan2`] and [`Cordic::sqrt`]
+//!   return the result. Reading the result stalls the bus until it is ready.
+//! - interrupt: [`Cordic::start`] returns at once, [`Cordic::listen`] raises
+//!   the `CORDIC` interrupt when the result is ready and [`Cordic::result`]
+//!   picks it up.
+//! - DMA: [`Cordic::into_dma`] gives the write and read sides of the data
+//!   registers, to use as the peripheral of two DMA transfers streaming
+//!   arguments in and results out, on channels of [`crate::dma`]: the read
+//!   started first, so no result waits for its channel.
+//!
+//! ```text
+//! let (write, read) = cordic.into_dma::<i32>(Function::Sine);
+//! let results = dma::claim(Controller::Dma1)?.read(read, results, Mode::Once);
+//! let arguments = dma::claim(Controller::Dma1)?.write(write, angles, Mode::Once);
+//! results.wait()?;
+//! ```
+
+use stm32g4xx_hal as hal;
+
+use hal::dma::{traits::TargetAddress, MemoryToPeripheral, PeripheralToMemory};
+use hal::rcc::{Enable, Reset};
+use hal::stm32::{CORDIC, RCC};
+
+/// DMAMUX request line of the CORDIC write (argument) side.
+const WRITE_REQUEST: u8 = 113;
+/// DMAMUX request line of the CORDIC read (result) side.
+const READ_REQUEST: u8 = 112;
+
+/// Q1.31 value of 0.75: the largest square root argument without scaling.
+const SQRT_SCALE_LIMIT: i32 = 0x6000_0000;
+
+/// Fixed point format of arguments and results.
+pub trait Fixed: Copy {
+    /// `true` for Q1.15, where both arguments share one 32-bit write.
+    const Q15: bool;
+    /// Iterations / 4 needed to reach the full precision of the format.
+    const PRECISION: u8;
+
+    /// Widens the value to Q1.31.
+    fn to_q31(self) -> i32;
+    /// Narrows a Q1.31 value to the format.
+    fn from_q31(value: i32) -> Self;
+}
+
+impl Fixed for i32 {
+    const Q15: bool = false;
+    const PRECISION: u8 = 6;
+
+    fn to_q31(self) -> i32 {
+        self
+    }
+
+    fn from_q31(value: i32) -> Self {
+        value
+    }
+}
+
+impl Fixed for i16 {
+    const Q15: bool = true;
+    const PRECISION: u8 = 3;
+
+    fn to_q31(self) -> i32 {
+        (self as i32) << 16
+    }
+
+    fn from_q31(value: i32) -> Self {
+        (value >> 16) as i16
+    }
+}
+
+/// Function computed by the CORDIC.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Function {
+    /// Arguments: angle, modulus. Results: `m * cos(angle)`, `m * sin(angle)`.
+    Cosine = 0,
+    /// Arguments: angle, modulus. Results: `m * sin(angle)`, `m * cos(angle)`.
+    Sine = 1,
+    /// Arguments: x, y. Results: `atan2(y, x)`, `sqrt(x² + y²)`.
+    Phase = 2,
+    /// Arguments: x, y. Results: `sqrt(x² + y²)`, `atan2(y, x)`.
+    Modulus = 3,
+    /// Argument: x, accurate from 0.027. Result: `sqrt(x)`.
+    SquareRoot = 9,
+}
+
+impl Function {
+    /// `false` for the functions with a single argument and a single result.
+    fn is_pair(self) -> bool {
+        self != Function::SquareRoot
+    }
+}
+
+/// The CORDIC co-processor.
+pub struct Cordic {
+    cordic: CORDIC,
+    // Left shift undoing the argument scaling of the pending square root.
+    shift: u8,
+}
+
+impl Cordic {
+    /// Enables the CORDIC clock.
+    pub fn new(cordic: CORDIC) -> Self {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            CORDIC::enable(rcc);
+            CORDIC::reset(rcc);
+        }
+
+        Cordic { cordic, shift: 0 }
+    }
+
+    /// Returns `(sin(angle), cos(angle))`, with the angle in units of π.
+    pub fn sin_cos<T: Fixed>(&mut self, angle: T) -> (T, T) {
+        self.start(Function::Cosine, angle, T::from_q31(i32::MAX));
+        let (cos, sin) = self.result();
+        (sin, cos)
+    }
+
+    /// Returns `atan2(y, x)` in units of π.
+    pub fn atan2<T: Fixed>(&mut self, y: T, x: T) -> T {
+        self.start(Function::Phase, x, y);
+        self.result::<T>().0
+    }
+
+    /// Returns the square root of `x`, for `x` in [0, 1).
+    ///
+    /// Arguments below 0.027 lose precision.
+    pub fn sqrt<T: Fixed>(&mut self, x: T) -> T {
+        self.start(Function::SquareRoot, x, x);
+        self.result::<T>().0
+    }
+
+    /// Starts a calculation and returns immediately.
+    ///
+    /// `arg2` is ignored by [`Function::SquareRoot`]. Pick the results up with
+    /// [`Cordic::result`] once [`Cordic::is_ready`], or from the interrupt.
+    pub fn start<T: Fixed>(&mut self, function: Function, arg1: T, arg2: T) {
+        let mut arg1 = arg1.to_q31();
+        let mut scale = 0;
+        // Above 0.75 the square root needs the argument halved, and the
+        // result comes out halved too.
+        if function == Function::SquareRoot && arg1 >= SQRT_SCALE_LIMIT {
+            arg1 >>= 1;
+            scale = 1;
+        }
+        self.shift = scale;
+
+        self.configure::<T>(function, scale, function.is_pair());
+        if T::Q15 {
+            let word = (arg1 as u32 >> 16) | (arg2.to_q31() as u32 & 0xFFFF_0000);
+            self.cordic.wdata.write(|w| w.arg().bits(word));
+        } else {
+            self.cordic.wdata.write(|w| w.arg().bits(arg1 as u32));
+            if function.is_pair() {
+                self.cordic.wdata.write(|w| w.arg().bits(arg2.to_q31() as u32));
+            }
+        }
+    }
+
+    /// Returns `true` when the results of the last calculation can be read.
+    pub fn is_ready(&self) -> bool {
+        self.cordic.csr.read().rrdy().bit_is_set()
+    }
+
+    /// Reads the two results of the last calculation.
+    ///
+    /// The second result is zero for [`Function::SquareRoot`]. The format must
+    /// be the one the calculation was started with. Reading clears the ready
+    /// flag, and with it the interrupt.
+    pub fn result<T: Fixed>(&mut self) -> (T, T) {
+        let (res1, res2) = if T::Q15 {
+            let word = self.cordic.rdata.read().res().bits();
+            ((word << 16) as i32, (word & 0xFFFF_0000) as i32)
+        } else if self.cordic.csr.read().nres().bit_is_set() {
+            let res1 = self.cordic.rdata.read().res().bits() as i32;
+            (res1, self.cordic.rdata.read().res().bits() as i32)
+        } else {
+            (self.cordic.rdata.read().res().bits() as i32, 0)
+        };
+        let res1 = if self.shift > 0 { res1.saturating_mul(1 << self.shift) } else { res1 };
+        (T::from_q31(res1), T::from_q31(res2))
+    }
+
+    /// Raises the `CORDIC` interrupt when a result is ready.
+    ///
+    /// Note, you will also have to unmask the CORDIC interrupt in the NVIC.
+    pub fn listen(&mut self) {
+        self.cordic.csr.modify(|_, w| w.ien().set_bit());
+    }
+
+    /// Stops raising the `CORDIC` interrupt.
+    pub fn unlisten(&mut self) {
+        self.cordic.csr.modify(|_, w| w.ien().clear_bit());
+    }
+
+    /// Switches to DMA mode: one word written per calculation, one word read back.
+    ///
+    /// In Q1.15 the word holds both arguments, then both results. In Q1.31 it
+    /// holds the first argument only and the second one keeps its last value,
+    /// +1 after reset, which is the modulus `Cosine` and `Sine` expect; only the
+    /// first result is read back.
+    pub fn into_dma<T: Fixed>(mut self, function: Function) -> (CordicWrite, CordicRead) {
+        self.configure::<T>(function, 0, false);
+        self.cordic.csr.modify(|_, w| w.dmawen().set_bit().dmaren().set_bit());
+        (CordicWrite { cordic: self.cordic }, CordicRead { _0: () })
+    }
+
+    /// Leaves DMA mode once both transfers are done.
+    pub fn from_dma(write: CordicWrite, _read: CordicRead) -> Self {
+        write.cordic.csr.modify(|_, w| w.dmawen().clear_bit().dmaren().clear_bit());
+        Cordic { cordic: write.cordic, shift: 0 }
+    }
+
+    /// Disables the CORDIC clock and returns the peripheral.
+    pub fn release(self) -> CORDIC {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            CORDIC::disable(rcc);
+        }
+        self.cordic
+    }
+
+    fn configure<T: Fixed>(&mut self, function: Function, scale: u8, pair: bool) {
+        // Q1.15 always packs both arguments and both results in one word.
+        let two_words = pair && !T::Q15;
+        self.cordic.csr.modify(|_, w| unsafe {
+            w.func()
+                .bits(function as u8)
+                .precision()
+                .bits(T::PRECISION)
+                .scale()
+                .bits(scale)
+                .nargs()
+                .bit(two_words)
+                .nres()
+                .bit(two_words)
+                .argsize()
+                .bit(T::Q15)
+                .ressize()
+                .bit(T::Q15)
+        });
+    }
+}
+
+/// Argument side of the CORDIC in DMA mode.
+pub struct CordicWrite {
+    cordic: CORDIC,
+}
+
+/// Result side of the CORDIC in DMA mode.
+pub struct CordicRead {
+    _0: (),
+}
+
+unsafe impl TargetAddress<MemoryToPeripheral> for CordicWrite {
+    type MemSize = u32;
+
+    const REQUEST_LINE: Option<u8> = Some(WRITE_REQUEST);
+
+    fn address(&self) -> u32 {
+        &self.cordic.wdata as *const _ as u32
+    }
+}
+
+unsafe impl TargetAddress<PeripheralToMemory> for CordicRead {
+    type MemSize = u32;
+
+    const REQUEST_LINE: Option<u8> = Some(READ_REQUEST);
+
+    fn address(&self) -> u32 {
+        // NOTE(unsafe) only the address of the read-only result register is taken.
+        unsafe { &(*CORDIC::ptr()).rdata as *const _ as u32 }
+    }
+}
@@ -0,0 +1,167 @@
+//! CRC calculation unit.
+//!
+//! The CRC unit computes a 7, 8, 16 or 32-bit CRC with any polynomial over
+//! the data written to it, one word per AHB cycle. [`Config`] describes an
+//! algorithm with the usual parameters (polynomial, initial value, input and
+//! output reflection, final XOR), and preset algorithms are provided for the
+//! most common ones with their standard check values.
+
+use stm32g4xx_hal as hal;
+
+use hal::rcc::{Enable, Reset};
+use hal::stm32::{CRC, RCC};
+
+/// Width of the CRC.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Width {
+    Bits32 = 0b00,
+    Bits16 = 0b01,
+    Bits8 = 0b10,
+    Bits7 = 0b11,
+}
+
+impl Width {
+    /// Mask keeping the bits of a CRC of this width.
+    fn mask(self) -> u32 {
+        match self {
+            Width::Bits32 => 0xFFFF_FFFF,
+            Width::Bits16 => 0xFFFF,
+            Width::Bits8 => 0xFF,
+            Width::Bits7 => 0x7F,
+        }
+    }
+}
+
+/// A CRC algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct Config {
+    pub width: Width,
+    /// Generator polynomial, without the implicit top bit.
+    pub polynomial: u32,
+    /// Value the CRC starts from.
+    pub init: u32,
+    /// Process each input byte least significant bit first.
+    pub reflect_in: bool,
+    /// Reverse the bits of the result.
+    pub reflect_out: bool,
+    /// Value XORed into the result.
+    pub xor_out: u32,
+}
+
+impl Config {
+    /// CRC-32 (ISO-HDLC), as used by Ethernet and zip. Check value 0xCBF43926.
+    pub const CRC32: Config = Config {
+        width: Width::Bits32,
+        polynomial: 0x04C1_1DB7,
+        init: 0xFFFF_FFFF,
+        reflect_in: true,
+        reflect_out: true,
+        xor_out: 0xFFFF_FFFF,
+    };
+
+    /// CRC-16/IBM-3740, also known as CRC-16/CCITT-FALSE. Check value 0x29B1.
+    pub const CRC16_CCITT: Config = Config {
+        width: Width::Bits16,
+        polynomial: 0x1021,
+        init: 0xFFFF,
+        reflect_in: false,
+        reflect_out: false,
+        xor_out: 0,
+    };
+
+    /// CRC-8/SMBUS. Check value 0xF4.
+    pub const CRC8: Config = Config {
+        width: Width::Bits8,
+        polynomial: 0x07,
+        init: 0,
+        reflect_in: false,
+        reflect_out: false,
+        xor_out: 0,
+    };
+}
+
+/// The CRC calculation unit.
+pub struct Crc {
+    crc: CRC,
+    config: Config,
+}
+
+impl Crc {
+    /// Enables the CRC unit for `config`.
+    pub fn new(crc: CRC, config: Config) -> Self {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            CRC::enable(rcc);
+            CRC::reset(rcc);
+        }
+
+        let mut crc = Crc { crc, config };
+        crc.set_config(config);
+        crc
+    }
+
+    /// Switches to another algorithm and restarts the calculation.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+        self.crc.pol.write(|w| unsafe { w.pol().bits(config.polynomial) });
+        self.crc.init.write(|w| unsafe { w.crc_init().bits(config.init) });
+        self.crc.cr.write(|w| unsafe {
+            w.polysize()
+                .bits(config.width as u8)
+                // Bit reversal done byte by byte, so it does not depend on the write size.
+                .rev_in()
+                .bits(if config.reflect_in { 0b01 } else { 0b00 })
+                .rev_out()
+                .bit(config.reflect_out)
+        });
+        self.reset();
+    }
+
+    /// Returns the current algorithm.
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    /// Restarts the calculation from the initial value.
+    pub fn reset(&mut self) {
+        self.crc.cr.modify(|_, w| w.reset().set_bit());
+    }
+
+    /// Adds `data` to the running calculation.
+    pub fn feed(&mut self, data: &[u8]) {
+        let mut words = data.chunks_exact(4);
+        for word in &mut words {
+            // The unit takes the most significant byte of a word first.
+            let word = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+            self.crc.dr.write(|w| unsafe { w.dr().bits(word) });
+        }
+        let dr = self.crc.dr.as_ptr() as *mut u8;
+        for &byte in words.remainder() {
+            // NOTE(unsafe) a byte write to DR adds a single byte to the calculation.
+            unsafe { core::ptr::write_volatile(dr, byte) };
+        }
+    }
+
+    /// Returns the CRC of everything fed since the last reset.
+    pub fn result(&self) -> u32 {
+        (self.crc.dr.read().bits() ^ self.config.xor_out) & self.config.width.mask()
+    }
+
+    /// Returns the CRC of `data` alone.
+    pub fn checksum(&mut self, data: &[u8]) -> u32 {
+        self.reset();
+        self.feed(data);
+        self.result()
+    }
+
+    /// Disables the CRC clock and returns the peripheral.
+    pub fn release(self) -> CRC {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            CRC::disable(rcc);
+        }
+        self.crc
+    }
+}
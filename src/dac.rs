@@ -0,0 +1,221 @@
+//! DAC1 waveform generation.
+//!
+//! The basic timer TIM6 produces a trigger output (TRGO) on every update
+//! event. DAC1 channel 1 latches a new sample on each trigger and requests the
+//! next one from the DMA, which walks a lookup table in circular mode. Once
+//! started the waveform runs without any CPU work: the output frequency is the
+//! TIM6 update rate divided by [`SAMPLES`], so retuning it only means changing
+//! the TIM6 period.
+
+use stm32g4xx_hal as hal;
+
+use hal::dma::{traits::TargetAddress, MemoryToPeripheral};
+use hal::gpio::{gpioa::PA4, Analog};
+use hal::rcc::{Clocks, Enable, GetBusFreq, Reset};
+use hal::stm32::{DAC1, RCC, TIM6};
+use hal::time::Hertz;
+
+use crate::clocks::timer_prescaler;
+
+/// Number of samples in one period of every waveform table.
+pub const SAMPLES: usize = 64;
+
+/// One period of a waveform, as right-aligned 12-bit DAC codes.
+///
+/// The entries are words because the DAC registers only accept 32-bit writes.
+pub type Table = [u32; SAMPLES];
+
+/// DMAMUX request line of DAC1 channel 1.
+const DAC1_CH1_REQUEST: u8 = 6;
+/// DAC trigger selection (TSELx) value for TIM6_TRGO.
+const TSEL_TIM6_TRGO: u8 = 7;
+
+/// Waveforms available as lookup tables.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Waveform {
+    Sine,
+    Triangle,
+    Sawtooth,
+}
+
+impl Waveform {
+    /// Returns the lookup table of the waveform.
+    pub fn table(self) -> &'static Table {
+        match self {
+            Waveform::Sine => &SINE,
+            Waveform::Triangle => &TRIANGLE,
+            Waveform::Sawtooth => &SAWTOOTH,
+        }
+    }
+
+    /// The waveform of `name`, as the shell takes it.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sine" => Some(Waveform::Sine),
+            "triangle" => Some(Waveform::Triangle),
+            "sawtooth" => Some(Waveform::Sawtooth),
+            _ => None,
+        }
+    }
+
+    /// The name of the waveform.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Waveform::Sine => "sine",
+            Waveform::Triangle => "triangle",
+            Waveform::Sawtooth => "sawtooth",
+        }
+    }
+
+    /// Returns the waveform that follows `self`, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            Waveform::Sine => Waveform::Triangle,
+            Waveform::Triangle => Waveform::Sawtooth,
+            Waveform::Sawtooth => Waveform::Sine,
+        }
+    }
+}
+
+static SINE: Table = [
+    2048, 2249, 2447, 2642, 2831, 3013, 3185, 3347,
+    3495, 3630, 3750, 3853, 3939, 4007, 4056, 4085,
+    4095, 4085, 4056, 4007, 3939, 3853, 3750, 3630,
+    3495, 3347, 3185, 3013, 2831, 2642, 2447, 2249,
+    2048, 1847, 1649, 1454, 1265, 1083, 911, 749,
+    601, 466, 346, 243, 157, 89, 40, 11,
+    1, 11, 40, 89, 157, 243, 346, 466,
+    601, 749, 911, 1083, 1265, 1454, 1649, 1847,
+];
+
+static TRIANGLE: Table = triangle();
+
+static SAWTOOTH: Table = sawtooth();
+
+const fn triangle() -> Table {
+    let mut table = [0; SAMPLES];
+    let half = SAMPLES / 2;
+    let mut i = 0;
+    while i < SAMPLES {
+        let step = if i < half { i } else { SAMPLES - i };
+        table[i] = (step * 4095 / half) as u32;
+        i += 1;
+    }
+    table
+}
+
+const fn sawtooth() -> Table {
+    let mut table = [0; SAMPLES];
+    let mut i = 0;
+    while i < SAMPLES {
+        table[i] = (i * 4095 / (SAMPLES - 1)) as u32;
+        i += 1;
+    }
+    table
+}
+
+/// DAC1 channel 1 on PA4, converting on TIM6 triggers with samples fed by DMA.
+///
+/// Use it as the peripheral side of a memory-to-peripheral DMA transfer.
+pub struct Dac1Ch1 {
+    dac: DAC1,
+    _pin: PA4<Analog>,
+}
+
+impl Dac1Ch1 {
+    /// Enables DAC1 channel 1 with its output buffer driving PA4.
+    pub fn new(dac: DAC1, pin: PA4<Analog>) -> Self {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            DAC1::enable(rcc);
+            DAC1::reset(rcc);
+        }
+
+        // Normal mode, output buffer enabled, connected to the external pin.
+        dac.dac_mcr.modify(|_, w| unsafe { w.mode1().bits(0b000) });
+        dac.dac_cr.modify(|_, w| unsafe {
+            w.tsel1()
+                .bits(TSEL_TIM6_TRGO)
+                .ten1()
+                .set_bit()
+                .dmaen1()
+                .set_bit()
+                .en1()
+                .set_bit()
+        });
+        // The channel needs a few microseconds before it accepts triggers.
+        while dac.dac_sr.read().dac1rdy().bit_is_clear() {}
+
+        Dac1Ch1 { dac, _pin: pin }
+    }
+
+    /// Disables the channel and returns the peripheral and the pin.
+    pub fn release(self) -> (DAC1, PA4<Analog>) {
+        self.dac.dac_cr.modify(|_, w| w.en1().clear_bit());
+        (self.dac, self._pin)
+    }
+}
+
+unsafe impl TargetAddress<MemoryToPeripheral> for Dac1Ch1 {
+    type MemSize = u32;
+
+    const REQUEST_LINE: Option<u8> = Some(DAC1_CH1_REQUEST);
+
+    fn address(&self) -> u32 {
+        &self.dac.dac_dhr12r1 as *const _ as u32
+    }
+}
+
+/// TIM6 running as the sample clock of the DAC.
+pub struct SampleClock {
+    tim: TIM6,
+    clk: Hertz,
+}
+
+impl SampleClock {
+    /// Enables TIM6 and routes its update event to TRGO. The timer is stopped.
+    pub fn new(tim: TIM6, clocks: &Clocks) -> Self {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            TIM6::enable(rcc);
+            TIM6::reset(rcc);
+        }
+        // MMS = 0b010: update event is used as trigger output.
+        tim.cr2.modify(|_, w| unsafe { w.mms().bits(0b010) });
+
+        SampleClock {
+            clk: TIM6::get_timer_frequency(clocks),
+            tim,
+        }
+    }
+
+    /// Sets the waveform frequency, that is `SAMPLES` triggers per period.
+    pub fn set_frequency(&mut self, frequency: Hertz) {
+        self.set_sample_rate(Hertz(frequency.0 * SAMPLES as u32));
+    }
+
+    /// Sets the number of triggers per second.
+    pub fn set_sample_rate(&mut self, rate: Hertz) {
+        let (psc, arr) = timer_prescaler(self.clk, rate);
+
+        self.tim.psc.write(|w| unsafe { w.psc().bits(psc) });
+        self.tim.arr.write(|w| unsafe { w.arr().bits(arr as u16) });
+        // Load the new prescaler now instead of at the next update event.
+        // URS keeps the forced update from raising an interrupt or DMA request.
+        self.tim.cr1.modify(|_, w| w.urs().set_bit());
+        self.tim.egr.write(|w| w.ug().set_bit());
+        self.tim.cr1.modify(|_, w| w.urs().clear_bit());
+    }
+
+    /// Starts triggering conversions.
+    pub fn start(&mut self) {
+        self.tim.cr1.modify(|_, w| w.cen().set_bit());
+    }
+
+    /// Stops triggering conversions. The DAC holds its last sample.
+    pub fn stop(&mut self) {
+        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+    }
+}
@@ -0,0 +1,581 @@
+//! DMA channels for the drivers: allocation, memory copies and the
+//! transfer interrupts.
+//!
+//! DMA1 and DMA2 have [`CHANNELS`] channels each, and the DMAMUX routes the
+//! request line of any peripheral to any of them. [`claim`] hands out a
+//! channel nobody holds, so the USART, the ADC, the timer bursts and the
+//! rest stop picking fixed streams of their own that might clash; the
+//! [`Channel`] then runs one [`Transfer`] at a time:
+//!
+//! - [`Channel::copy`], memory to memory, as fast as the bus goes;
+//! - [`Channel::write`] and [`Channel::read`], to and from the peripheral
+//!   side of any of the HAL's DMA transfers, its `TargetAddress`: a USART
+//!   `Tx` or `Rx` with DMA enabled, an ADC in DMA mode, or the sides of
+//!   [`crate::burst`], [`crate::dac`], [`crate::sai`], [`crate::ws2812`],
+//!   [`crate::cordic`] and [`crate::fmac`], request line included.
+//!
+//! The examples streaming to those sides, `dac_waveform`, `sai_tone`,
+//! `ws2812_strip` and `dma_burst_fade`, claim their channel here, as does
+//! `dma_channels`; none sets up a stream of the HAL's.
+//!
+//! The transfer interrupts are dispatched as the EXTI lines are (see
+//! [`crate::exti`]): [`dma_handlers!`] defines the vectors of every
+//! channel once in the application; each clears its channel's flags and,
+//! for each [`Event`] the channel listens to, sets the event's flag for
+//! [`take`] and calls the handler set with [`on_event`]:
+//!
+//! ```text
+//! nucleo_g474re::dma_handlers!();
+//!
+//! let mut channel = dma::claim(Controller::Dma1)?;
+//! channel.listen(Event::Complete);
+//! channel.unmask();
+//! dma::on_event(channel.id(), |_, event| defmt::info!("{}", event));
+//! let transfer = channel.copy(source, destination);
+//! ```
+//!
+//! A transfer moves at most [`MAX_LEN`] words; its buffers are `'static`,
+//! as the DMA goes on with them after the call returns, and come back with
+//! the channel from [`Transfer::stop`]: a [`Source`] written from may be
+//! mutable, to refill it before the next transfer. The programs defining their own DMA
+//! ISRs must leave the macro out.
+
+use core::cell::Cell;
+use core::sync::atomic::{fence, AtomicU16, Ordering};
+
+use cortex_m::interrupt::Mutex;
+use cortex_m::peripheral::NVIC;
+
+use stm32g4xx_hal as hal;
+
+use hal::dma::{traits::TargetAddress, MemoryToPeripheral, PeripheralToMemory};
+use hal::rcc::Enable;
+use hal::stm32::{DMA1, DMA2, DMAMUX, Interrupt, RCC};
+
+/// Channels of each DMA controller.
+#[cfg(not(feature = "board-g431rb"))]
+pub const CHANNELS: usize = 8;
+/// Channels of each DMA controller.
+#[cfg(feature = "board-g431rb")]
+pub const CHANNELS: usize = 6;
+
+/// Most words in a transfer, CNDTR being 16 bits.
+pub const MAX_LEN: usize = u16::MAX as usize;
+
+/// A transfer event handler, called with the channel and the event.
+pub type Handler = fn(Id, Event);
+
+// Channel registers, in words from CCR.
+const CCR: usize = 0;
+const CNDTR: usize = 1;
+const CPAR: usize = 2;
+const CMAR: usize = 3;
+// Controller registers, in words from the base.
+const ISR: usize = 0;
+const IFCR: usize = 1;
+
+// CCR bits.
+const EN: u32 = 1 << 0;
+const DIR: u32 = 1 << 4;
+const CIRC: u32 = 1 << 5;
+const PINC: u32 = 1 << 6;
+const MINC: u32 = 1 << 7;
+const PSIZE_SHIFT: u32 = 8;
+const MSIZE_SHIFT: u32 = 10;
+const MEM2MEM: u32 = 1 << 14;
+// TCIE, HTIE and TEIE in CCR; TCIF, HTIF and TEIF in ISR, from the
+// channel's first bit.
+const EVENTS: u32 = 0b1110;
+
+/// DMA errors.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    /// Not a channel of the controller: 1 to [`CHANNELS`].
+    InvalidChannel,
+    /// The channel is claimed already, or all of them are.
+    Busy,
+    /// The transfer stopped on a bus error.
+    Transfer,
+}
+
+/// A DMA controller.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Controller {
+    Dma1,
+    Dma2,
+}
+
+impl Controller {
+    fn base(self) -> *mut u32 {
+        match self {
+            Controller::Dma1 => DMA1::ptr() as *mut u32,
+            Controller::Dma2 => DMA2::ptr() as *mut u32,
+        }
+    }
+}
+
+/// A channel of a controller, 1 to [`CHANNELS`].
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct Id {
+    pub controller: Controller,
+    pub number: u8,
+}
+
+impl Id {
+    // Both controllers' channels in a row, DMA1's first, as the DMAMUX
+    // numbers them.
+    fn index(self) -> usize {
+        self.controller as usize * CHANNELS + self.number as usize - 1
+    }
+
+    fn registers(self) -> *mut u32 {
+        unsafe { self.controller.base().add(2 + 5 * (self.number as usize - 1)) }
+    }
+
+    // The channel's flags in ISR and IFCR, 4 bits each.
+    fn shift(self) -> u32 {
+        4 * (self.number as u32 - 1)
+    }
+
+    /// The channel's interrupt, for the NVIC.
+    pub fn interrupt(self) -> Interrupt {
+        match self.controller {
+            Controller::Dma1 => DMA1_VECTORS[self.number as usize - 1],
+            Controller::Dma2 => DMA2_VECTORS[self.number as usize - 1],
+        }
+    }
+}
+
+const DMA1_VECTORS: [Interrupt; CHANNELS] = [
+    Interrupt::DMA1_CH1,
+    Interrupt::DMA1_CH2,
+    Interrupt::DMA1_CH3,
+    Interrupt::DMA1_CH4,
+    Interrupt::DMA1_CH5,
+    Interrupt::DMA1_CH6,
+    #[cfg(not(feature = "board-g431rb"))]
+    Interrupt::DMA1_CH7,
+    #[cfg(not(feature = "board-g431rb"))]
+    Interrupt::DMA1_CH8,
+];
+
+const DMA2_VECTORS: [Interrupt; CHANNELS] = [
+    Interrupt::DMA2_CH1,
+    Interrupt::DMA2_CH2,
+    Interrupt::DMA2_CH3,
+    Interrupt::DMA2_CH4,
+    Interrupt::DMA2_CH5,
+    Interrupt::DMA2_CH6,
+    #[cfg(not(feature = "board-g431rb"))]
+    Interrupt::DMA2_CH7,
+    #[cfg(not(feature = "board-g431rb"))]
+    Interrupt::DMA2_CH8,
+];
+
+/// A transfer event.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Event {
+    /// All the words moved; in circular mode, once a lap.
+    Complete,
+    /// Half the words moved, for a circular buffer refilled by halves.
+    HalfComplete,
+    /// A bus error: the channel turned itself off.
+    Error,
+}
+
+impl Event {
+    fn bit(self) -> u32 {
+        match self {
+            Event::Complete => 1 << 1,
+            Event::HalfComplete => 1 << 2,
+            Event::Error => 1 << 3,
+        }
+    }
+}
+
+/// Whether a transfer stops at its end or starts over.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Mode {
+    Once,
+    Circular,
+}
+
+/// A word the DMA moves: `u8`, `u16` or `u32`.
+pub trait Word: Copy {
+    /// PSIZE and MSIZE of the word.
+    const SIZE: u32;
+}
+
+impl Word for u8 {
+    const SIZE: u32 = 0b00;
+}
+
+impl Word for u16 {
+    const SIZE: u32 = 0b01;
+}
+
+impl Word for u32 {
+    const SIZE: u32 = 0b10;
+}
+
+/// The memory [`Channel::write`] reads: a `'static` array or slice,
+/// mutable for a buffer refilled between transfers.
+///
+/// # Safety
+///
+/// The words stay where [`Source::words`] finds them for as long as the
+/// value lives, wherever it moves.
+pub unsafe trait Source<W> {
+    /// The words to write.
+    fn words(&self) -> &[W];
+}
+
+unsafe impl<W> Source<W> for &'static [W] {
+    fn words(&self) -> &[W] {
+        self
+    }
+}
+
+unsafe impl<W> Source<W> for &'static mut [W] {
+    fn words(&self) -> &[W] {
+        self
+    }
+}
+
+unsafe impl<W, const N: usize> Source<W> for &'static [W; N] {
+    fn words(&self) -> &[W] {
+        &self[..]
+    }
+}
+
+unsafe impl<W, const N: usize> Source<W> for &'static mut [W; N] {
+    fn words(&self) -> &[W] {
+        &self[..]
+    }
+}
+
+// Create a Global Variable for the claimed channels, one bit each.
+static CLAIMED: Mutex<Cell<u16>> = Mutex::new(Cell::new(0));
+// Create a Global Variable for the event handlers.
+static HANDLERS: Mutex<Cell<[Option<Handler>; 2 * CHANNELS]>> = Mutex::new(Cell::new([None; 2 * CHANNELS]));
+// Channels that had each event since their last `take`, one bit each.
+static FLAGS: [AtomicU16; 3] = [const { AtomicU16::new(0) }; 3];
+
+/// Claims the first free channel of `controller`.
+pub fn claim(controller: Controller) -> Result<Channel, Error> {
+    (1..=CHANNELS as u8)
+        .find_map(|number| claim_channel(controller, number).ok())
+        .ok_or(Error::Busy)
+}
+
+/// Claims channel `number` of `controller`, for a program that wants the
+/// same one every time.
+pub fn claim_channel(controller: Controller, number: u8) -> Result<Channel, Error> {
+    if number == 0 || number as usize > CHANNELS {
+        return Err(Error::InvalidChannel);
+    }
+    let id = Id { controller, number };
+    cortex_m::interrupt::free(|cs| {
+        let claimed = CLAIMED.borrow(cs);
+        let mask = 1 << id.index();
+        if claimed.get() & mask != 0 {
+            return Err(Error::Busy);
+        }
+        claimed.set(claimed.get() | mask);
+        Ok(())
+    })?;
+
+    // No reset: the other channels may be running.
+    let rcc = unsafe { &(*RCC::ptr()) };
+    match controller {
+        Controller::Dma1 => DMA1::enable(rcc),
+        Controller::Dma2 => DMA2::enable(rcc),
+    }
+    DMAMUX::enable(rcc);
+    Ok(Channel { id, interrupts: 0 })
+}
+
+/// Calls `handler` on every event `channel` listens to, in place of the
+/// handler before it, which is returned.
+pub fn on_event(channel: Id, handler: Handler) -> Option<Handler> {
+    cortex_m::interrupt::free(|cs| swap(cs, channel, Some(handler)))
+}
+
+/// Removes the handler of `channel` and returns it: its events only set
+/// their flags.
+pub fn clear(channel: Id) -> Option<Handler> {
+    cortex_m::interrupt::free(|cs| swap(cs, channel, None))
+}
+
+fn swap(cs: &cortex_m::interrupt::CriticalSection, channel: Id, handler: Option<Handler>) -> Option<Handler> {
+    let handlers = HANDLERS.borrow(cs);
+    let mut table = handlers.get();
+    let previous = core::mem::replace(&mut table[channel.index()], handler);
+    handlers.set(table);
+    previous
+}
+
+/// Returns `true`, once, if `channel` had `event` since the last call.
+pub fn take(channel: Id, event: Event) -> bool {
+    let mask = 1 << channel.index();
+    FLAGS[event as usize].fetch_and(!mask, Ordering::AcqRel) & mask != 0
+}
+
+/// A claimed DMA channel.
+pub struct Channel {
+    id: Id,
+    // TCIE, HTIE and TEIE, set on every transfer.
+    interrupts: u32,
+}
+
+impl Channel {
+    /// The controller and number of the channel.
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// Interrupts on `event`, from the next transfer on.
+    pub fn listen(&mut self, event: Event) {
+        self.interrupts |= event.bit();
+    }
+
+    /// Stops interrupting on `event`, from the next transfer on.
+    pub fn unlisten(&mut self, event: Event) {
+        self.interrupts &= !event.bit();
+    }
+
+    /// Unmasks the channel's interrupt in the NVIC.
+    pub fn unmask(&self) {
+        unsafe { NVIC::unmask(self.id.interrupt()) };
+    }
+
+    /// Copies `source` to `destination`, as many words as the shorter has.
+    pub fn copy<W: Word>(
+        mut self,
+        source: &'static [W],
+        destination: &'static mut [W],
+    ) -> Transfer<(&'static [W], &'static mut [W])> {
+        let len = source.len().min(destination.len());
+        // From CPAR to CMAR, no request line.
+        let ccr = MEM2MEM | PINC | MINC | W::SIZE << PSIZE_SHIFT | W::SIZE << MSIZE_SHIFT;
+        self.start(ccr, 0, source.as_ptr() as u32, destination.as_mut_ptr() as u32, len);
+        Transfer { channel: self, parts: (source, destination) }
+    }
+
+    /// Writes `buffer` to `peripheral`, a word on each of its requests.
+    pub fn write<P, W, B>(mut self, peripheral: P, buffer: B, mode: Mode) -> Transfer<(P, B)>
+    where
+        P: TargetAddress<MemoryToPeripheral, MemSize = W>,
+        W: Word,
+        B: Source<W>,
+    {
+        let ccr = DIR | MINC | mode.bits() | W::SIZE << PSIZE_SHIFT | W::SIZE << MSIZE_SHIFT;
+        let request = P::REQUEST_LINE.unwrap_or(0);
+        let words = buffer.words();
+        self.start(ccr, request, peripheral.address(), words.as_ptr() as u32, words.len());
+        Transfer { channel: self, parts: (peripheral, buffer) }
+    }
+
+    /// Reads `peripheral` into `buffer`, a word on each of its requests.
+    pub fn read<P, W>(mut self, peripheral: P, buffer: &'static mut [W], mode: Mode) -> Transfer<(P, &'static mut [W])>
+    where
+        P: TargetAddress<PeripheralToMemory, MemSize = W>,
+        W: Word,
+    {
+        let ccr = MINC | mode.bits() | W::SIZE << PSIZE_SHIFT | W::SIZE << MSIZE_SHIFT;
+        let request = P::REQUEST_LINE.unwrap_or(0);
+        self.start(ccr, request, peripheral.address(), buffer.as_mut_ptr() as u32, buffer.len());
+        Transfer { channel: self, parts: (peripheral, buffer) }
+    }
+
+    fn start(&mut self, ccr: u32, request: u8, peripheral: u32, memory: u32, len: usize) {
+        let registers = self.id.registers();
+        unsafe {
+            registers.add(CCR).write_volatile(0);
+            // The channel's DMAREQ_ID, with the synchronisation and event
+            // generation off.
+            (DMAMUX::ptr() as *mut u32).add(self.id.index()).write_volatile(request as u32);
+            self.id.controller.base().add(IFCR).write_volatile(0xF << self.id.shift());
+            registers.add(CPAR).write_volatile(peripheral);
+            registers.add(CMAR).write_volatile(memory);
+            registers.add(CNDTR).write_volatile(len.min(MAX_LEN) as u32);
+            // The buffer written before the DMA reads it.
+            fence(Ordering::SeqCst);
+            registers.add(CCR).write_volatile(ccr | self.interrupts);
+            registers.add(CCR).write_volatile(ccr | self.interrupts | EN);
+        }
+    }
+
+    /// Gives the channel back, its handler removed, for [`claim`] to hand
+    /// out again.
+    pub fn release(self) {
+        clear(self.id);
+        for flags in &FLAGS {
+            flags.fetch_and(!(1 << self.id.index()), Ordering::AcqRel);
+        }
+        cortex_m::interrupt::free(|cs| {
+            let claimed = CLAIMED.borrow(cs);
+            claimed.set(claimed.get() & !(1 << self.id.index()));
+        });
+    }
+}
+
+impl Mode {
+    fn bits(self) -> u32 {
+        match self {
+            Mode::Once => 0,
+            Mode::Circular => CIRC,
+        }
+    }
+}
+
+/// A transfer running on a channel, holding its buffers and peripheral.
+pub struct Transfer<T> {
+    channel: Channel,
+    parts: T,
+}
+
+impl<T> Transfer<T> {
+    /// The controller and number of the channel.
+    pub fn id(&self) -> Id {
+        self.channel.id
+    }
+
+    /// Words left to move; in circular mode, in the lap under way.
+    pub fn remaining(&self) -> u16 {
+        unsafe { self.channel.id.registers().add(CNDTR).read_volatile() as u16 }
+    }
+
+    /// Whether all the words moved, never for a circular transfer.
+    pub fn is_complete(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// Whether the channel turned itself off with words left: a bus error.
+    pub fn has_failed(&self) -> bool {
+        let ccr = unsafe { self.channel.id.registers().add(CCR).read_volatile() };
+        ccr & EN == 0 && self.remaining() != 0
+    }
+
+    /// Waits for the transfer to complete.
+    pub fn wait(&self) -> Result<(), Error> {
+        loop {
+            if self.is_complete() {
+                return Ok(());
+            }
+            if self.has_failed() {
+                return Err(Error::Transfer);
+            }
+        }
+    }
+
+    /// Stops the transfer, if still running, and returns the channel and
+    /// the parts.
+    pub fn stop(self) -> (Channel, T) {
+        unsafe {
+            let ccr = self.channel.id.registers().add(CCR);
+            ccr.write_volatile(ccr.read_volatile() & !EN);
+        }
+        // The buffer read after the DMA wrote it.
+        fence(Ordering::SeqCst);
+        (self.channel, self.parts)
+    }
+}
+
+#[doc(hidden)]
+pub fn dispatch(controller: Controller, number: u8) {
+    let id = Id { controller, number };
+    let (isr, ccr) = unsafe {
+        let base = controller.base();
+        let isr = base.add(ISR).read_volatile() >> id.shift();
+        // Clear the channel's flags, the global one with them.
+        base.add(IFCR).write_volatile(0xF << id.shift());
+        (isr, id.registers().add(CCR).read_volatile())
+    };
+
+    // The events the channel listens to: HTIF is set in every transfer.
+    let events = isr & ccr & EVENTS;
+    let handler = cortex_m::interrupt::free(|cs| HANDLERS.borrow(cs).get()[id.index()]);
+    for event in [Event::HalfComplete, Event::Complete, Event::Error] {
+        if events & event.bit() != 0 {
+            FLAGS[event as usize].fetch_or(1 << id.index(), Ordering::AcqRel);
+            if let Some(handler) = handler {
+                handler(id, event);
+            }
+        }
+    }
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use stm32g4xx_hal::interrupt;
+}
+
+/// Defines the interrupt handlers of every DMA channel, dispatching to the
+/// [`on_event`] handlers. Invoke once, at the top level of the program.
+#[macro_export]
+macro_rules! dma_handlers {
+    () => {
+        mod __dma_handlers {
+            use $crate::dma::__private::interrupt;
+            use $crate::dma::Controller;
+
+            $crate::__dma_vectors!();
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __dma_vector {
+    ($vector:ident, $controller:ident, $number:literal) => {
+        #[interrupt]
+        fn $vector() {
+            $crate::dma::dispatch(Controller::$controller, $number);
+        }
+    };
+}
+
+#[doc(hidden)]
+#[cfg(not(feature = "board-g431rb"))]
+#[macro_export]
+macro_rules! __dma_vectors {
+    () => {
+        $crate::__dma_vector!(DMA1_CH1, Dma1, 1);
+        $crate::__dma_vector!(DMA1_CH2, Dma1, 2);
+        $crate::__dma_vector!(DMA1_CH3, Dma1, 3);
+        $crate::__dma_vector!(DMA1_CH4, Dma1, 4);
+        $crate::__dma_vector!(DMA1_CH5, Dma1, 5);
+        $crate::__dma_vector!(DMA1_CH6, Dma1, 6);
+        $crate::__dma_vector!(DMA1_CH7, Dma1, 7);
+        $crate::__dma_vector!(DMA1_CH8, Dma1, 8);
+        $crate::__dma_vector!(DMA2_CH1, Dma2, 1);
+        $crate::__dma_vector!(DMA2_CH2, Dma2, 2);
+        $crate::__dma_vector!(DMA2_CH3, Dma2, 3);
+        $crate::__dma_vector!(DMA2_CH4, Dma2, 4);
+        $crate::__dma_vector!(DMA2_CH5, Dma2, 5);
+        $crate::__dma_vector!(DMA2_CH6, Dma2, 6);
+        $crate::__dma_vector!(DMA2_CH7, Dma2, 7);
+        $crate::__dma_vector!(DMA2_CH8, Dma2, 8);
+    };
+}
+
+#[doc(hidden)]
+#[cfg(feature = "board-g431rb")]
+#[macro_export]
+macro_rules! __dma_vectors {
+    () => {
+        $crate::__dma_vector!(DMA1_CH1, Dma1, 1);
+        $crate::__dma_vector!(DMA1_CH2, Dma1, 2);
+        $crate::__dma_vector!(DMA1_CH3, Dma1, 3);
+        $crate::__dma_vector!(DMA1_CH4, Dma1, 4);
+        $crate::__dma_vector!(DMA1_CH5, Dma1, 5);
+        $crate::__dma_vector!(DMA1_CH6, Dma1, 6);
+        $crate::__dma_vector!(DMA2_CH1, Dma2, 1);
+        $crate::__dma_vector!(DMA2_CH2, Dma2, 2);
+        $crate::__dma_vector!(DMA2_CH3, Dma2, 3);
+        $crate::__dma_vector!(DMA2_CH4, Dma2, 4);
+        $crate::__dma_vector!(DMA2_CH5, Dma2, 5);
+        $crate::__dma_vector!(DMA2_CH6, Dma2, 6);
+    };
+}
@@ -0,0 +1,161 @@
+//! Quadrature encoders on TIM3 or TIM4, with velocity estimation.
+//!
+//! In encoder mode the timer counts the edges of both channels, A and B, up
+//! or down from their order: four counts per cycle of the encoder, with no
+//! CPU work and no count lost however fast the shaft turns.
+//!
+//! | Timer | A          | B          |
+//! |-------|------------|------------|
+//! | TIM3  | PA6 (D12)  | PA7 (D11)  |
+//! | TIM4  | PB6 (D10)  | PB7        |
+//!
+//! The 16-bit count wraps around: [`Encoder::sample`], called at a fixed
+//! rate from a timer interrupt, takes the change since the last sample as a
+//! signed difference, which holds as long as the shaft moves less than
+//! 32767 counts between two samples. The samples extend the count to a
+//! 32-bit [`Encoder::position`] and feed a first-order low-pass filter, so
+//! [`Encoder::speed`] gives counts per second without the jitter of one
+//! count more or less in a sample.
+
+use core::ops::Deref;
+
+use stm32g4xx_hal as hal;
+
+use hal::gpio::{gpioa, gpiob, Alternate, AF2};
+use hal::rcc::{Enable, Reset};
+use hal::stm32::{tim3, RCC, TIM3, TIM4};
+
+/// Channel pins of TIM3: A and B.
+pub type Tim3Pins = (gpioa::PA6<Alternate<AF2>>, gpioa::PA7<Alternate<AF2>>);
+
+/// Channel pins of TIM4: A and B.
+pub type Tim4Pins = (gpiob::PB6<Alternate<AF2>>, gpiob::PB7<Alternate<AF2>>);
+
+/// A general-purpose timer for an encoder, with its pins.
+pub trait Instance: Deref<Target = tim3::RegisterBlock> + Enable + Reset {
+    /// CH1 and CH2.
+    type Pins;
+}
+
+impl Instance for TIM3 {
+    type Pins = Tim3Pins;
+}
+
+impl Instance for TIM4 {
+    type Pins = Tim4Pins;
+}
+
+/// Encoder errors.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    /// A sample rate of zero.
+    InvalidRate,
+}
+
+/// Direction of the last move.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Direction {
+    /// Counting up: A leads B.
+    Forward,
+    /// Counting down: B leads A.
+    Backward,
+}
+
+/// A quadrature encoder on channels 1 and 2 of `TIM`.
+pub struct Encoder<TIM: Instance> {
+    tim: TIM,
+    pins: TIM::Pins,
+    sample_hz: u32,
+    // Weight of a new sample in the filter: 1 / 2^shift.
+    shift: u8,
+    // The count at the last sample, the position it extends to, and the
+    // filtered counts per sample in 24.8 fixed point.
+    last: u16,
+    position: i32,
+    speed: i32,
+}
+
+impl<TIM: Instance> Encoder<TIM> {
+    /// Starts counting from zero, the pins filtered over 8 timer clocks.
+    ///
+    /// [`Encoder::sample`] is to be called `sample_hz` times a second, and
+    /// each sample weighs `1 / 2^shift` in the speed: 0 for none of the
+    /// filtering, 3 for a time constant of about 8 samples.
+    pub fn new(tim: TIM, pins: TIM::Pins, sample_hz: u32, shift: u8) -> Result<Self, Error> {
+        if sample_hz == 0 {
+            return Err(Error::InvalidRate);
+        }
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            TIM::enable(rcc);
+            TIM::reset(rcc);
+        }
+
+        // CC1 on TI1, CC2 on TI2, neither inverted.
+        tim.ccmr1_input().write(|w| unsafe {
+            w.cc1s().bits(0b01).ic1f().bits(0b0011).cc2s().bits(0b01).ic2f().bits(0b0011)
+        });
+        tim.ccer.write(|w| w.cc1p().clear_bit().cc2p().clear_bit());
+        // Encoder mode 3: counting the edges of both channels.
+        tim.smcr.write(|w| unsafe { w.sms().bits(0b011) });
+        tim.arr.write(|w| unsafe { w.bits(0xFFFF) });
+        tim.cnt.write(|w| unsafe { w.bits(0) });
+        tim.cr1.modify(|_, w| w.cen().set_bit());
+
+        Ok(Encoder { tim, pins, sample_hz, shift: shift.min(8), last: 0, position: 0, speed: 0 })
+    }
+
+    /// The raw 16-bit count.
+    pub fn count(&self) -> u16 {
+        self.tim.cnt.read().bits() as u16
+    }
+
+    /// The direction of the last count.
+    pub fn direction(&self) -> Direction {
+        if self.tim.cr1.read().dir().bit_is_set() { Direction::Backward } else { Direction::Forward }
+    }
+
+    /// Takes the change of the count since the last sample, updates the
+    /// position and the speed, and returns the change: call at the sample
+    /// rate given to [`Encoder::new`].
+    pub fn sample(&mut self) -> i32 {
+        let count = self.count();
+        let delta = count.wrapping_sub(self.last) as i16 as i32;
+        self.last = count;
+        self.position = self.position.wrapping_add(delta);
+        self.speed += ((delta << 8) - self.speed) >> self.shift;
+        delta
+    }
+
+    /// The count extended over the samples, from zero at [`Encoder::new`]
+    /// or the last [`Encoder::reset`].
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// The filtered speed, in counts per second, negative backwards.
+    pub fn speed(&self) -> i32 {
+        ((self.speed as i64 * self.sample_hz as i64) >> 8) as i32
+    }
+
+    /// The filtered speed in revolutions per minute, for an encoder of
+    /// `counts_per_rev` counts, four times its lines or cycles.
+    pub fn rpm(&self, counts_per_rev: u32) -> i32 {
+        if counts_per_rev == 0 {
+            return 0;
+        }
+        (self.speed() as i64 * 60 / counts_per_rev as i64) as i32
+    }
+
+    /// Sets the position to zero, keeping the speed.
+    pub fn reset(&mut self) {
+        self.position = 0;
+    }
+
+    /// Stops the timer and returns it and the pins.
+    pub fn release(self) -> (TIM, TIM::Pins) {
+        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+        (self.tim, self.pins)
+    }
+}
@@ -0,0 +1,227 @@
+//! Dispatch of the GPIO EXTI lines to per-line handlers.
+//!
+//! The sixteen GPIO lines share seven interrupt vectors (`EXTI0` to `EXTI4`,
+//! `EXTI9_5`, `EXTI15_10`; see `button`). [`exti_handlers!`] defines all
+//! seven once in the application; each reads which of its lines are
+//! pending, clears them and, for each, sets the line's flag and calls the
+//! handler [`register`]ed for it. A second input is then a pin configured as
+//! an interrupt source and a `register` call, not a new ISR:
+//!
+//! ```text
+//! nucleo_g474re::exti_handlers!();
+//!
+//! fn on_press(line: u8) { /* ... */ }
+//!
+//! exti::register(13, on_press).unwrap();
+//! exti::unmask_all();
+//! ```
+//!
+//! Handlers run in the interrupt, one line after the other from the lowest;
+//! a line with no handler only sets its flag, for the main loop to [`take`].
+//! The programs defining their own EXTI ISRs must leave the macro out.
+//!
+//! Async code [`wait`]s for a line instead: the future completes on the
+//! line's next interrupt, which wakes the task polling it. It needs no
+//! particular executor, only one that polls again once woken:
+//!
+//! ```text
+//! exti::wait(13).await;
+//! ```
+
+use core::cell::{Cell, RefCell};
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU16, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use cortex_m::interrupt::Mutex;
+use cortex_m::peripheral::NVIC;
+
+use stm32g4xx_hal as hal;
+
+use hal::stm32::{EXTI, Interrupt};
+
+/// GPIO EXTI lines, one per pin number.
+pub const LINES: usize = 16;
+
+/// A line handler, called with the line number.
+pub type Handler = fn(u8);
+
+/// Dispatch errors.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    /// Not a GPIO line: 0 to 15.
+    InvalidLine,
+    /// The line already has a handler.
+    Busy,
+}
+
+// Create a Global Variable for the line handlers.
+static HANDLERS: Mutex<Cell<[Option<Handler>; LINES]>> = Mutex::new(Cell::new([None; LINES]));
+// Lines that fired since their last `take`, one bit each.
+static FLAGS: AtomicU16 = AtomicU16::new(0);
+// Create a Global Variable for the tasks waiting on each line.
+static WAKERS: Mutex<RefCell<[Option<Waker>; LINES]>> = Mutex::new(RefCell::new([const { None }; LINES]));
+
+/// The vectors, with the lines each serves.
+const VECTORS: [(Interrupt, u8, u8); 7] = [
+    (Interrupt::EXTI0, 0, 0),
+    (Interrupt::EXTI1, 1, 1),
+    (Interrupt::EXTI2, 2, 2),
+    (Interrupt::EXTI3, 3, 3),
+    (Interrupt::EXTI4, 4, 4),
+    (Interrupt::EXTI9_5, 5, 9),
+    (Interrupt::EXTI15_10, 10, 15),
+];
+
+/// Calls `handler` for every interrupt of `line`.
+pub fn register(line: u8, handler: Handler) -> Result<(), Error> {
+    if line as usize >= LINES {
+        return Err(Error::InvalidLine);
+    }
+    cortex_m::interrupt::free(|cs| {
+        let handlers = HANDLERS.borrow(cs);
+        let mut table = handlers.get();
+        if table[line as usize].is_some() {
+            return Err(Error::Busy);
+        }
+        table[line as usize] = Some(handler);
+        handlers.set(table);
+        Ok(())
+    })
+}
+
+/// Removes the handler of `line`; its flag still gets set.
+pub fn unregister(line: u8) {
+    if line as usize >= LINES {
+        return;
+    }
+    cortex_m::interrupt::free(|cs| {
+        let handlers = HANDLERS.borrow(cs);
+        let mut table = handlers.get();
+        table[line as usize] = None;
+        handlers.set(table);
+    });
+}
+
+/// Returns `true`, once, if `line` fired since the last call.
+pub fn take(line: u8) -> bool {
+    let mask = 1 << (line as usize % LINES);
+    FLAGS.fetch_and(!mask, Ordering::AcqRel) & mask != 0
+}
+
+/// A future completing on the next interrupt of `line`, from [`wait`].
+#[must_use = "futures do nothing unless polled"]
+pub struct Wait {
+    line: u8,
+}
+
+/// Waits for the next interrupt of `line`.
+///
+/// An interrupt before the call is not counted: its flag is dropped. One
+/// task waits on a line at a time; a second one takes the line over.
+pub fn wait(line: u8) -> Wait {
+    let line = line % LINES as u8;
+    take(line);
+    Wait { line }
+}
+
+impl Future for Wait {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if take(self.line) {
+            return Poll::Ready(());
+        }
+        cortex_m::interrupt::free(|cs| {
+            WAKERS.borrow(cs).borrow_mut()[self.line as usize] = Some(cx.waker().clone());
+        });
+        // An interrupt between the first check and the waker stored set the
+        // flag with nobody to wake: check again.
+        if take(self.line) { Poll::Ready(()) } else { Poll::Pending }
+    }
+}
+
+/// Unmasks the seven EXTI vectors in the NVIC.
+pub fn unmask_all() {
+    for (interrupt, _, _) in VECTORS {
+        unsafe { NVIC::unmask(interrupt) };
+    }
+}
+
+/// Serves the pending lines `first` to `last`: called by the ISRs of
+/// [`exti_handlers!`].
+pub fn dispatch(first: u8, last: u8) {
+    // NOTE(unsafe) only the pending bits of the lines served are written.
+    let exti = unsafe { &*EXTI::ptr() };
+    let range = (0xffff_u32 >> (15 - last)) & !((1 << first) - 1);
+    let pending = exti.pr1.read().bits() & exti.imr1.read().bits() & range;
+    // Writing 1 clears: one write for all of them, before the handlers run,
+    // so an edge arriving meanwhile raises the interrupt again.
+    exti.pr1.write(|w| unsafe { w.bits(pending) });
+    FLAGS.fetch_or(pending as u16, Ordering::AcqRel);
+
+    let handlers = cortex_m::interrupt::free(|cs| HANDLERS.borrow(cs).get());
+    for line in first..=last {
+        if pending & (1 << line) == 0 {
+            continue;
+        }
+        if let Some(handler) = handlers[line as usize] {
+            handler(line);
+        }
+        if let Some(waker) = cortex_m::interrupt::free(|cs| WAKERS.borrow(cs).borrow_mut()[line as usize].take()) {
+            waker.wake();
+        }
+    }
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use stm32g4xx_hal::interrupt;
+}
+
+/// Defines the seven EXTI interrupt handlers, dispatching to [`register`]ed
+/// handlers. Invoke once, at the top level of the program.
+#[macro_export]
+macro_rules! exti_handlers {
+    () => {
+        mod __exti_handlers {
+            use $crate::exti::__private::interrupt;
+
+            #[interrupt]
+            fn EXTI0() {
+                $crate::exti::dispatch(0, 0);
+            }
+
+            #[interrupt]
+            fn EXTI1() {
+                $crate::exti::dispatch(1, 1);
+            }
+
+            #[interrupt]
+            fn EXTI2() {
+                $crate::exti::dispatch(2, 2);
+            }
+
+            #[interrupt]
+            fn EXTI3() {
+                $crate::exti::dispatch(3, 3);
+            }
+
+            #[interrupt]
+            fn EXTI4() {
+                $crate::exti::dispatch(4, 4);
+            }
+
+            #[interrupt]
+            fn EXTI9_5() {
+                $crate::exti::dispatch(5, 9);
+            }
+
+            #[interrupt]
+            fn EXTI15_10() {
+                $crate::exti::dispatch(10, 15);
+            }
+        }
+    };
+}
@@ -0,0 +1,336 @@
+//! Fault and NMI handlers with hooks of the program's.
+//!
+//! With no handler of its own, a fault lands in the `cortex-m-rt` default,
+//! a loop with nothing to say why. [`fault_handlers!`] defines the handlers
+//! of the NMI and of the four faults; [`enable`] turns on MemManage,
+//! BusFault and UsageFault, which otherwise escalate to HardFault, and the
+//! trap on a division by zero. Each handler reads the fault status
+//! registers, and for a fault the PC and LR of the stacked frame, into a
+//! [`FaultInfo`] and calls the [`Hook`] set for its
+//! [`Fault`] with [`set_hook`], which returns the [`Action`] to take:
+//!
+//! | Action              | Then                                              |
+//! |---------------------|---------------------------------------------------|
+//! | `Action::Resume`    | return, for an NMI handled; a fault comes back    |
+//! | `Action::Halt`      | a breakpoint under a debugger, then sleep forever |
+//! | `Action::Blink(n)`  | bursts of `n` flashes of the user LED, forever    |
+//! | `Action::Reset`     | a system reset                                    |
+//!
+//! The [`default_hook`] logs the fault over defmt and blinks its
+//! [`Fault::code`]: 1 for the NMI up to 5 for a UsageFault. A program keeps
+//! the log and resets instead, or handles the clock security NMI
+//! (`clocks::handle_css`) and resumes:
+//!
+//! ```text
+//! nucleo_g474re::fault_handlers!();
+//!
+//! fn on_fault(info: &FaultInfo) -> Action {
+//!     faults::log(info);
+//!     Action::Reset
+//! }
+//!
+//! faults::enable(&mut cp.SCB);
+//! faults::set_hook(Fault::UsageFault, on_fault);
+//! ```
+//!
+//! The NMI and HardFault preempt the critical sections, so the hooks run
+//! with the program stopped wherever it was: they touch no `Mutex` global.
+//! A fault inside a defmt log line finds the logger taken, and panics.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use cortex_m::peripheral::scb::Exception;
+use cortex_m::peripheral::SCB;
+pub use cortex_m_rt::ExceptionFrame;
+
+use stm32g4xx_hal as hal;
+
+use hal::stm32::{GPIOA, RCC};
+
+use crate::clocks::HSI;
+
+/// A fault hook, called with what the handler found.
+pub type Hook = fn(&FaultInfo) -> Action;
+
+// CFSR bits: MemManage in the first byte, BusFault in the second,
+// UsageFault in the upper half word.
+const MMARVALID: u32 = 1 << 7;
+const BFARVALID: u32 = 1 << 15;
+// CCR: trap on a division by zero.
+const DIV_0_TRP: u32 = 1 << 4;
+// HFSR: a vector table read failed; a fault escalated.
+const VECTTBL: u32 = 1 << 1;
+const FORCED: u32 = 1 << 30;
+
+// The user LED, LD2 on PA5 on every Nucleo-64 of the crate.
+const LED_PIN: u32 = 5;
+
+/// A fault, or the NMI.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Fault {
+    Nmi,
+    HardFault,
+    MemManage,
+    BusFault,
+    UsageFault,
+}
+
+/// Faults of [`Fault`].
+pub const FAULTS: usize = 5;
+
+impl Fault {
+    /// The blink code of the fault: 1 for the NMI to 5 for a UsageFault.
+    pub fn code(self) -> u8 {
+        self as u8 + 1
+    }
+}
+
+/// What a hook wants done after it.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Action {
+    /// Return from the handler: the program goes on after an NMI, and the
+    /// faulting instruction runs again after a fault.
+    Resume,
+    /// Stop for a debugger, then sleep.
+    Halt,
+    /// Flash the user LED `n` times, pause, and again, forever.
+    Blink(u8),
+    /// Reset the chip.
+    Reset,
+}
+
+/// The fault status registers, and the frame stacked by the fault.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct FaultInfo {
+    pub fault: Fault,
+    /// Configurable fault status: MMFSR, BFSR and UFSR.
+    pub cfsr: u32,
+    /// HardFault status.
+    pub hfsr: u32,
+    /// The address of a MemManage fault, if it has one.
+    pub mmfar: Option<u32>,
+    /// The address of a precise BusFault, if it has one.
+    pub bfar: Option<u32>,
+    /// The PC and LR of the code that faulted, for any fault but the NMI.
+    pub pc: Option<u32>,
+    pub lr: Option<u32>,
+}
+
+impl FaultInfo {
+    /// The first cause the status registers give, in words.
+    pub fn cause(&self) -> &'static str {
+        const CAUSES: [(u32, &str); 14] = [
+            (1 << 0, "instruction access violation"),
+            (1 << 1, "data access violation"),
+            (1 << 3, "MemManage on unstacking"),
+            (1 << 4, "MemManage on stacking"),
+            (1 << 8, "instruction bus error"),
+            (1 << 9, "precise data bus error"),
+            (1 << 10, "imprecise data bus error"),
+            (1 << 11, "BusFault on unstacking"),
+            (1 << 12, "BusFault on stacking"),
+            (1 << 16, "undefined instruction"),
+            (1 << 17, "invalid state, an ARM instruction"),
+            (1 << 18, "invalid PC on exception return"),
+            (1 << 24, "unaligned access"),
+            (1 << 25, "division by zero"),
+        ];
+        if let Some((_, cause)) = CAUSES.iter().find(|(bit, _)| self.cfsr & bit != 0) {
+            return cause;
+        }
+        match self.fault {
+            Fault::Nmi => "non-maskable interrupt",
+            _ if self.hfsr & VECTTBL != 0 => "vector table read",
+            _ if self.hfsr & FORCED != 0 => "escalated fault",
+            _ => "unknown",
+        }
+    }
+}
+
+// Create a Global Variable for the hooks, fn pointers stored as words:
+// the NMI preempts the critical sections a `Mutex` needs. 0 is none.
+static HOOKS: [AtomicUsize; FAULTS] = [const { AtomicUsize::new(0) }; FAULTS];
+
+/// Turns on the MemManage, BusFault and UsageFault handlers and the trap on
+/// a division by zero. Without it the three escalate to HardFault.
+pub fn enable(scb: &mut SCB) {
+    scb.enable(Exception::MemoryManagement);
+    scb.enable(Exception::BusFault);
+    scb.enable(Exception::UsageFault);
+    unsafe { scb.ccr.modify(|ccr| ccr | DIV_0_TRP) };
+}
+
+/// Calls `hook` on `fault` in place of the hook before it, the
+/// [`default_hook`] if none, which is returned.
+pub fn set_hook(fault: Fault, hook: Hook) -> Option<Hook> {
+    let previous = HOOKS[fault as usize].swap(hook as usize, Ordering::AcqRel);
+    // SAFETY: the words stored are all `Hook`s, or 0.
+    (previous != 0).then(|| unsafe { core::mem::transmute::<usize, Hook>(previous) })
+}
+
+fn hook(fault: Fault) -> Hook {
+    match HOOKS[fault as usize].load(Ordering::Acquire) {
+        0 => default_hook,
+        // SAFETY: as in `set_hook`.
+        raw => unsafe { core::mem::transmute::<usize, Hook>(raw) },
+    }
+}
+
+/// Logs `info` over defmt, then blinks the fault's code.
+pub fn default_hook(info: &FaultInfo) -> Action {
+    log(info);
+    Action::Blink(info.fault.code())
+}
+
+/// Logs the fault, its cause and addresses, as an error.
+pub fn log(info: &FaultInfo) {
+    defmt::error!(
+        "{}: {} (CFSR {=u32:#010x}, HFSR {=u32:#010x}, PC {}, LR {}, address {})",
+        info.fault,
+        info.cause(),
+        info.cfsr,
+        info.hfsr,
+        info.pc,
+        info.lr,
+        info.mmfar.or(info.bfar)
+    );
+}
+
+#[doc(hidden)]
+pub fn handle(fault: Fault, frame: Option<&ExceptionFrame>) {
+    // NOTE(unsafe) reads of the status registers, and the write clearing
+    // the bits read
+    let scb = unsafe { &*SCB::PTR };
+    let cfsr = scb.cfsr.read();
+    let hfsr = scb.hfsr.read();
+    let info = FaultInfo {
+        fault,
+        cfsr,
+        hfsr,
+        mmfar: (cfsr & MMARVALID != 0).then(|| scb.mmfar.read()),
+        bfar: (cfsr & BFARVALID != 0).then(|| scb.bfar.read()),
+        pc: frame.map(|frame| frame.pc()),
+        lr: frame.map(|frame| frame.lr()),
+    };
+    unsafe {
+        scb.cfsr.write(cfsr);
+        scb.hfsr.write(hfsr);
+    }
+
+    match hook(fault)(&info) {
+        Action::Resume => {}
+        Action::Halt => halt(),
+        Action::Blink(count) => blink(count),
+        Action::Reset => SCB::sys_reset(),
+    }
+}
+
+/// Stops for a debugger, if one is attached, then sleeps forever.
+pub fn halt() -> ! {
+    // A breakpoint with no debugger attached would escalate to a lockup.
+    if cortex_m::peripheral::DCB::is_debugger_attached() {
+        cortex_m::asm::bkpt();
+    }
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+/// Flashes the user LED `count` times every 2 s, forever, taking PA5 over
+/// whatever drove it. The timing counts core cycles at the 16 MHz of the
+/// HSI; a faster clock blinks the same code faster.
+pub fn blink(count: u8) -> ! {
+    // NOTE(unsafe) the program is stopped: PA5 is the fault's alone
+    let (rcc, gpioa) = unsafe { (&*RCC::ptr(), &*GPIOA::ptr()) };
+    rcc.ahb2enr.modify(|_, w| w.gpioaen().set_bit());
+    gpioa.moder.modify(|r, w| unsafe { w.bits(r.bits() & !(0b11 << (2 * LED_PIN)) | 0b01 << (2 * LED_PIN)) });
+
+    let ms = HSI.0 / 1000;
+    loop {
+        for _ in 0..count {
+            gpioa.bsrr.write(|w| unsafe { w.bits(1 << LED_PIN) });
+            cortex_m::asm::delay(200 * ms);
+            gpioa.bsrr.write(|w| unsafe { w.bits(1 << (LED_PIN + 16)) });
+            cortex_m::asm::delay(200 * ms);
+        }
+        cortex_m::asm::delay(2000 * ms);
+    }
+}
+
+// The handlers the trampolines of `fault_handlers!` branch to, the frame
+// in r0.
+#[doc(hidden)]
+pub extern "C" fn __mem_manage(frame: &ExceptionFrame) {
+    handle(Fault::MemManage, Some(frame));
+}
+
+#[doc(hidden)]
+pub extern "C" fn __bus_fault(frame: &ExceptionFrame) {
+    handle(Fault::BusFault, Some(frame));
+}
+
+#[doc(hidden)]
+pub extern "C" fn __usage_fault(frame: &ExceptionFrame) {
+    handle(Fault::UsageFault, Some(frame));
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use core::arch::naked_asm;
+    pub use cortex_m_rt::{exception, ExceptionFrame};
+}
+
+// A vector of a fault, in naked code: bit 2 of EXC_RETURN, in LR, tells the
+// stack the frame went on, passed in r0 to `$handler`, which returns from
+// the exception itself. HardFault's own trampoline in cortex-m-rt does the
+// same.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __fault_trampoline {
+    ($vector:literal, $name:ident, $handler:path) => {
+        #[unsafe(export_name = $vector)]
+        #[unsafe(naked)]
+        unsafe extern "C" fn $name() {
+            $crate::faults::__private::naked_asm!(
+                "tst lr, #4",
+                "ite eq",
+                "mrseq r0, MSP",
+                "mrsne r0, PSP",
+                "b {handler}",
+                handler = sym $handler,
+            );
+        }
+    };
+}
+
+/// Defines the NMI and fault handlers, dispatching to the [`set_hook`]
+/// hooks. Invoke once, at the top level of the program.
+#[macro_export]
+macro_rules! fault_handlers {
+    () => {
+        mod __fault_handlers {
+            use $crate::faults::__private::{exception, ExceptionFrame};
+            use $crate::faults::Fault;
+
+            // Unsafe for cortex-m-rt, as the NMI and HardFault preempt the
+            // critical sections: the hooks touch no `Mutex` global.
+            #[exception]
+            unsafe fn NonMaskableInt() {
+                $crate::faults::handle(Fault::Nmi, None);
+            }
+
+            #[exception]
+            unsafe fn HardFault(frame: &ExceptionFrame) -> ! {
+                $crate::faults::handle(Fault::HardFault, Some(frame));
+                // Resumed: with nowhere to go back to, stop there.
+                $crate::faults::halt()
+            }
+
+            // The stacked frame for the three others too, for the PC of
+            // the instruction that faulted.
+            $crate::__fault_trampoline!("MemoryManagement", __mem_manage, $crate::faults::__mem_manage);
+            $crate::__fault_trampoline!("BusFault", __bus_fault, $crate::faults::__bus_fault);
+            $crate::__fault_trampoline!("UsageFault", __usage_fault, $crate::faults::__usage_fault);
+        }
+    };
+}
@@ -0,0 +1,261 @@
+//! FMAC (filter math accelerator): FIR and IIR filters in hardware.
+//!
+//! The FMAC holds the filter coefficients and the sample history in its own
+//! 256-word memory and runs the multiply-accumulate loop itself. Once started,
+//! every sample written to it produces one filtered sample to read back, a few
+//! tens of cycles later, so even long filters cost the CPU two register
+//! accesses per sample. Samples and coefficients are Q1.15 values in an `i16`.
+//!
+//! Samples can be written and read one by one with [`Fmac::write`] and
+//! [`Fmac::read`], from the `FMAC` interrupt, or streamed by DMA with the two
+//! sides returned by [`Fmac::into_dma`], each on a channel of [`crate::dma`]:
+//!
+//! ```text
+//! let (write, read) = fmac.into_dma();
+//! let output = dma::claim(Controller::Dma1)?.read(read, filtered, Mode::Circular);
+//! let input = dma::claim(Controller::Dma1)?.write(write, samples, Mode::Circular);
+//! ```
+
+use core::convert::Infallible;
+
+use stm32g4xx_hal as hal;
+
+use hal::dma::{traits::TargetAddress, MemoryToPeripheral, PeripheralToMemory};
+use hal::nb;
+use hal::rcc::{Enable, Reset};
+use hal::stm32::{FMAC, RCC};
+
+/// DMAMUX request line of the FMAC write (input) side.
+const WRITE_REQUEST: u8 = 111;
+/// DMAMUX request line of the FMAC read (output) side.
+const READ_REQUEST: u8 = 110;
+
+/// Size of the FMAC local memory, in 16-bit words.
+const MEMORY_SIZE: usize = 256;
+/// Free places kept in the input and output buffers on top of the history.
+const HEADROOM: usize = 4;
+
+/// PARAM.FUNC values.
+const FUNC_LOAD_X1: u8 = 1;
+const FUNC_LOAD_X2: u8 = 2;
+const FUNC_LOAD_Y: u8 = 3;
+const FUNC_FIR: u8 = 8;
+const FUNC_IIR: u8 = 9;
+
+/// Filter to run, with its Q1.15 coefficients.
+#[derive(Clone, Copy, Debug)]
+pub enum Filter<'a> {
+    /// `y[n] = 2^gain * Σ b[k] * x[n - k]`, with 2 to 127 taps.
+    Fir { b: &'a [i16] },
+    /// `y[n] = 2^gain * (Σ b[k] * x[n - k] + Σ a[k] * y[n - 1 - k])`.
+    ///
+    /// 2 to 64 feed-forward coefficients `b` and fewer feedback coefficients
+    /// `a`. Note that the feedback is added: `a` holds the negated denominator
+    /// coefficients of the usual transfer function, without the leading 1.
+    Iir { b: &'a [i16], a: &'a [i16] },
+}
+
+/// FMAC interrupt sources.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Event {
+    /// The input buffer has room for a new sample.
+    InputSpace,
+    /// A filtered sample is waiting in the output buffer.
+    OutputReady,
+}
+
+/// The filter math accelerator.
+pub struct Fmac {
+    fmac: FMAC,
+}
+
+impl Fmac {
+    /// Enables the FMAC clock.
+    pub fn new(fmac: FMAC) -> Self {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            FMAC::enable(rcc);
+            FMAC::reset(rcc);
+        }
+
+        Fmac { fmac }
+    }
+
+    /// Loads the coefficients of `filter` and starts it from a zero history.
+    ///
+    /// The output is multiplied by `2^gain` (up to 7), to get back the range
+    /// lost by coefficients scaled down to fit Q1.15. Results are clipped
+    /// instead of wrapping around when they overflow.
+    pub fn start(&mut self, filter: Filter, gain: u8) {
+        let (b, a) = match filter {
+            Filter::Fir { b } => {
+                assert!((2..=127).contains(&b.len()));
+                (b, &[][..])
+            }
+            Filter::Iir { b, a } => {
+                assert!((2..=64).contains(&b.len()) && !a.is_empty() && a.len() < b.len());
+                (b, a)
+            }
+        };
+        assert!(gain <= 7);
+
+        // Memory layout: coefficients, then input history, then output history.
+        let x2_size = b.len() + a.len();
+        let x1_size = b.len() + HEADROOM;
+        let y_size = a.len() + HEADROOM;
+        assert!(x2_size + x1_size + y_size <= MEMORY_SIZE);
+
+        self.stop();
+        self.fmac.x2bufcfg.write(|w| unsafe {
+            w.x2_base().bits(0).x2_buf_size().bits(x2_size as u8)
+        });
+        self.fmac.x1bufcfg.write(|w| unsafe {
+            w.x1_base().bits(x2_size as u8).x1_buf_size().bits(x1_size as u8).full_wm().bits(0)
+        });
+        self.fmac.ybufcfg.write(|w| unsafe {
+            w.y_base().bits((x2_size + x1_size) as u8).y_buf_size().bits(y_size as u8).empty_wm().bits(0)
+        });
+
+        // b coefficients go first, followed by the a coefficients.
+        self.load(FUNC_LOAD_X2, b.len(), a.len(), b.iter().chain(a.iter()).copied());
+        // Zero history, so the first sample written gives the first output.
+        self.load(FUNC_LOAD_X1, b.len() - 1, 0, core::iter::repeat_n(0, b.len() - 1));
+        if !a.is_empty() {
+            self.load(FUNC_LOAD_Y, a.len(), 0, core::iter::repeat_n(0, a.len()));
+        }
+
+        let func = if a.is_empty() { FUNC_FIR } else { FUNC_IIR };
+        self.fmac.cr.modify(|_, w| w.clipen().set_bit());
+        self.fmac.param.write(|w| unsafe {
+            w.func()
+                .bits(func)
+                .p()
+                .bits(b.len() as u8)
+                .q()
+                .bits(a.len() as u8)
+                .r()
+                .bits(gain)
+                .start()
+                .set_bit()
+        });
+    }
+
+    /// Stops the running filter and clears its buffers.
+    pub fn stop(&mut self) {
+        self.fmac.param.modify(|_, w| w.start().clear_bit());
+        self.fmac.cr.modify(|_, w| w.reset().set_bit());
+        while self.fmac.cr.read().reset().bit_is_set() {}
+    }
+
+    /// Feeds one input sample to the filter.
+    pub fn write(&mut self, sample: i16) -> nb::Result<(), Infallible> {
+        if self.fmac.sr.read().x1full().bit_is_set() {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.fmac.wdata.write(|w| unsafe { w.wdata().bits(sample as u16) });
+        Ok(())
+    }
+
+    /// Reads one filtered sample.
+    pub fn read(&mut self) -> nb::Result<i16, Infallible> {
+        if self.fmac.sr.read().yempty().bit_is_set() {
+            return Err(nb::Error::WouldBlock);
+        }
+        Ok(self.fmac.rdata.read().rdata().bits() as i16)
+    }
+
+    /// Returns `true` if an output was clipped since the filter started.
+    pub fn is_saturated(&self) -> bool {
+        self.fmac.sr.read().sat().bit_is_set()
+    }
+
+    /// Starts raising the `FMAC` interrupt for `event`.
+    ///
+    /// Note, you will also have to unmask the FMAC interrupt in the NVIC.
+    pub fn listen(&mut self, event: Event) {
+        match event {
+            Event::InputSpace => self.fmac.cr.modify(|_, w| w.wien().set_bit()),
+            Event::OutputReady => self.fmac.cr.modify(|_, w| w.rien().set_bit()),
+        }
+    }
+
+    /// Stops raising the `FMAC` interrupt for `event`.
+    pub fn unlisten(&mut self, event: Event) {
+        match event {
+            Event::InputSpace => self.fmac.cr.modify(|_, w| w.wien().clear_bit()),
+            Event::OutputReady => self.fmac.cr.modify(|_, w| w.rien().clear_bit()),
+        }
+    }
+
+    /// Switches the running filter to DMA mode.
+    ///
+    /// Each word written holds one input sample in its low half; each word
+    /// read holds one output sample in its low half.
+    pub fn into_dma(self) -> (FmacWrite, FmacRead) {
+        self.fmac.cr.modify(|_, w| w.dmawen().set_bit().dmaren().set_bit());
+        (FmacWrite { fmac: self.fmac }, FmacRead { _0: () })
+    }
+
+    /// Leaves DMA mode once both transfers are done.
+    pub fn from_dma(write: FmacWrite, _read: FmacRead) -> Self {
+        write.fmac.cr.modify(|_, w| w.dmawen().clear_bit().dmaren().clear_bit());
+        Fmac { fmac: write.fmac }
+    }
+
+    /// Stops the filter, disables the FMAC clock and returns the peripheral.
+    pub fn release(mut self) -> FMAC {
+        self.stop();
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            FMAC::disable(rcc);
+        }
+        self.fmac
+    }
+
+    // Runs one of the buffer load functions, which ends on its own after `p` writes.
+    fn load(&mut self, func: u8, p: usize, q: usize, values: impl Iterator<Item = i16>) {
+        if p + q == 0 {
+            return;
+        }
+        self.fmac.param.write(|w| unsafe {
+            w.func().bits(func).p().bits(p as u8).q().bits(q as u8).start().set_bit()
+        });
+        for value in values {
+            self.fmac.wdata.write(|w| unsafe { w.wdata().bits(value as u16) });
+        }
+        while self.fmac.param.read().start().bit_is_set() {}
+    }
+}
+
+/// Input side of the FMAC in DMA mode.
+pub struct FmacWrite {
+    fmac: FMAC,
+}
+
+/// Output side of the FMAC in DMA mode.
+pub struct FmacRead {
+    _0: (),
+}
+
+unsafe impl TargetAddress<MemoryToPeripheral> for FmacWrite {
+    type MemSize = u32;
+
+    const REQUEST_LINE: Option<u8> = Some(WRITE_REQUEST);
+
+    fn address(&self) -> u32 {
+        &self.fmac.wdata as *const _ as u32
+    }
+}
+
+unsafe impl TargetAddress<PeripheralToMemory> for FmacRead {
+    type MemSize = u32;
+
+    const REQUEST_LINE: Option<u8> = Some(READ_REQUEST);
+
+    fn address(&self) -> u32 {
+        // NOTE(unsafe) only the address of the read-only output register is taken.
+        unsafe { &(*FMAC::ptr()).rdata as *const _ as u32 }
+    }
+}
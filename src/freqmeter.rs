@@ -0,0 +1,156 @@
+//! Frequency meter: TIM2 counting pulses on its external clock input, over
+//! a gate period timed by TIM7.
+//!
+//! In external clock mode 2, TIM2 counts the rising edges of its ETR input,
+//! PA0 (A0), instead of the timer clock: its 32 bits never wrap within a
+//! gate. TIM7 interrupts at the end of every gate period, and
+//! [`FreqMeter::handle_interrupt`] turns the pulses counted since the last
+//! one into hertz. The counter runs free, so no pulse falls between two
+//! gates. The resolution is the prescaler over the gate: 1 Hz undivided
+//! over a second.
+//!
+//! After the [`Prescaler`], the pulses may come at up to a quarter of the
+//! timer clock: 4 MHz from the 16 MHz HSI, 42 MHz from the 170 MHz PLL.
+
+use stm32g4xx_hal as hal;
+
+use hal::gpio::{gpioa::PA0, Alternate, AF14};
+use hal::rcc::{Clocks, Enable, GetBusFreq, Reset};
+use hal::stm32::{RCC, TIM2, TIM7};
+use hal::time::Hertz;
+
+/// Frequency of the gate timer.
+const GATE_TICK_HZ: u32 = 10_000;
+
+/// Longest gate, in milliseconds, for the 16 bits of TIM7 at 10 kHz.
+pub const MAX_GATE_MS: u32 = 6_553;
+
+/// Divider of the pulses ahead of the counter.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Prescaler {
+    Div1 = 0,
+    Div2,
+    Div4,
+    Div8,
+}
+
+impl Prescaler {
+    /// The pulses are divided by this.
+    pub fn divisor(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// Frequency meter errors.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    /// A gate of zero or more than [`MAX_GATE_MS`].
+    InvalidGate,
+}
+
+/// TIM2 counting the pulses on PA0, TIM7 timing the gate.
+pub struct FreqMeter {
+    counter: TIM2,
+    _pin: PA0<Alternate<AF14>>,
+    gate: TIM7,
+    gate_ms: u32,
+    prescaler: Prescaler,
+    // The count at the end of the last gate, and the frequency it gave.
+    last: u32,
+    frequency: Option<Hertz>,
+}
+
+impl FreqMeter {
+    /// Starts counting the pulses on PA0 over gates of `gate_ms`
+    /// milliseconds.
+    pub fn new(
+        counter: TIM2,
+        pin: PA0<Alternate<AF14>>,
+        gate: TIM7,
+        gate_ms: u32,
+        prescaler: Prescaler,
+        clocks: &Clocks,
+    ) -> Result<Self, Error> {
+        if gate_ms == 0 || gate_ms > MAX_GATE_MS {
+            return Err(Error::InvalidGate);
+        }
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            TIM2::enable(rcc);
+            TIM2::reset(rcc);
+            TIM7::enable(rcc);
+            TIM7::reset(rcc);
+        }
+
+        // External clock mode 2: rising edges of ETR, unfiltered, as a filter
+        // would shorten the shortest pulse counted.
+        counter.smcr.write(|w| unsafe {
+            w.ece()
+                .set_bit()
+                .etp()
+                .clear_bit()
+                .etps()
+                .bits(prescaler as u8)
+                .etf()
+                .bits(0)
+        });
+        counter.arr.write(|w| unsafe { w.bits(u32::MAX) });
+        counter.cr1.modify(|_, w| w.cen().set_bit());
+
+        let psc = TIM7::get_timer_frequency(clocks).0 / GATE_TICK_HZ - 1;
+        gate.psc.write(|w| unsafe { w.psc().bits(psc as u16) });
+        gate.arr.write(|w| unsafe { w.arr().bits((gate_ms * GATE_TICK_HZ / 1000 - 1) as u16) });
+        // Only overflows raise the update interrupt, not the UG loading the
+        // prescaler.
+        gate.cr1.modify(|_, w| w.urs().set_bit());
+        gate.egr.write(|w| w.ug().set_bit());
+        gate.cr1.modify(|_, w| w.cen().set_bit());
+
+        let last = counter.cnt.read().bits();
+        Ok(FreqMeter { counter, _pin: pin, gate, gate_ms, prescaler, last, frequency: None })
+    }
+
+    /// Starts interrupting at the end of every gate.
+    pub fn listen(&mut self) {
+        self.gate.dier.modify(|_, w| w.uie().set_bit());
+    }
+
+    /// Stops the interrupt.
+    pub fn unlisten(&mut self) {
+        self.gate.dier.modify(|_, w| w.uie().clear_bit());
+    }
+
+    /// The length of the gate, in milliseconds.
+    pub fn gate_ms(&self) -> u32 {
+        self.gate_ms
+    }
+
+    /// Measures the pulses over the gate that just ended and returns their
+    /// frequency: call from the TIM7 interrupt.
+    pub fn handle_interrupt(&mut self) -> Option<Hertz> {
+        if self.gate.sr.read().uif().bit_is_clear() {
+            return None;
+        }
+        self.gate.sr.write(|w| w.uif().clear_bit());
+        let count = self.counter.cnt.read().bits();
+        let pulses = count.wrapping_sub(self.last) as u64 * self.prescaler.divisor() as u64;
+        self.last = count;
+        let frequency = Hertz((pulses * 1000 / self.gate_ms as u64) as u32);
+        self.frequency = Some(frequency);
+        self.frequency
+    }
+
+    /// The frequency of the last gate, `None` before the first ended.
+    pub fn frequency(&self) -> Option<Hertz> {
+        self.frequency
+    }
+
+    /// Stops both timers and returns them and the pin.
+    pub fn release(self) -> (TIM2, PA0<Alternate<AF14>>, TIM7) {
+        self.gate.dier.write(|w| w.uie().clear_bit());
+        self.gate.cr1.modify(|_, w| w.cen().clear_bit());
+        self.counter.cr1.modify(|_, w| w.cen().clear_bit());
+        (self.counter, self._pin, self.gate)
+    }
+}
@@ -0,0 +1,126 @@
+//! Gamma correction: perceptually even LED brightness.
+//!
+//! The eye sees brightness on a curve: the step from 1% to 2% duty looks
+//! much larger than the step from 90% to 91%, so a linear fade rushes
+//! through the dark end and crawls at the bright one. Raising the level to
+//! a power, the gamma, undoes that curve: `duty = level ^ gamma`.
+//!
+//! [`Gamma`] takes the gamma in tenths as a const parameter and builds its
+//! 256-entry table at compile time, in flash: `Gamma<28>` is a gamma of
+//! 2.8, a common pick for LEDs, and [`Gamma28`] names it. The table maps an
+//! 8-bit level to a 16-bit duty, [`Gamma::duty`] scales that to the top of a
+//! PWM, and [`Gamma::correct_fine`] takes a 16-bit level for fades slow
+//! enough to show the steps between two entries.
+//!
+//! ```text
+//! type Curve = gamma::Gamma<22>;
+//! pwm.set_duty(Curve::duty(level, pwm.get_max_duty()));
+//! ```
+
+/// The common gamma for LEDs, 2.8.
+pub type Gamma28 = Gamma<28>;
+
+/// The sRGB gamma, 2.2: gentler on the low end.
+pub type Gamma22 = Gamma<22>;
+
+/// The gamma curve of `GAMMA_X10` tenths.
+pub struct Gamma<const GAMMA_X10: u32>;
+
+impl<const GAMMA_X10: u32> Gamma<GAMMA_X10> {
+    /// The gamma, as given.
+    pub const GAMMA: f64 = GAMMA_X10 as f64 / 10.0;
+
+    /// The duty of each 8-bit level, out of 65535.
+    pub const TABLE: [u16; 256] = table(GAMMA_X10 as f64 / 10.0);
+
+    /// The duty of `level`, out of 65535.
+    pub const fn correct(level: u8) -> u16 {
+        Self::TABLE[level as usize]
+    }
+
+    /// The duty of `level`, out of 255.
+    pub const fn correct8(level: u8) -> u8 {
+        ((Self::TABLE[level as usize] as u32 + 128) / 257) as u8
+    }
+
+    /// The duty of the 16-bit `level`, out of 65535: the table entries on
+    /// either side of it, interpolated.
+    pub const fn correct_fine(level: u16) -> u16 {
+        let index = (level >> 8) as usize;
+        let low = Self::TABLE[index] as u32;
+        let high = if index == 255 { 65535 } else { Self::TABLE[index + 1] as u32 };
+        (low + (high - low) * (level & 0xFF) as u32 / 256) as u16
+    }
+
+    /// The duty of `level` for a PWM whose full duty is `max_duty`.
+    pub const fn duty(level: u8, max_duty: u32) -> u32 {
+        (Self::TABLE[level as usize] as u64 * max_duty as u64 / 65535) as u32
+    }
+}
+
+// `(level / 255) ^ gamma` for each level, out of 65535.
+const fn table(gamma: f64) -> [u16; 256] {
+    let mut table = [0_u16; 256];
+    let mut level = 1;
+    while level < 256 {
+        let value = exp2(gamma * log2(level as f64 / 255.0));
+        table[level] = (value * 65535.0 + 0.5) as u16;
+        level += 1;
+    }
+    table
+}
+
+// Base 2 logarithm of `x` above zero: the exponent, then the bits of the
+// mantissa one by one, squaring it.
+const fn log2(x: f64) -> f64 {
+    let mut mantissa = x;
+    let mut result = 0.0;
+    while mantissa >= 2.0 {
+        mantissa /= 2.0;
+        result += 1.0;
+    }
+    while mantissa < 1.0 {
+        mantissa *= 2.0;
+        result -= 1.0;
+    }
+    let mut bit = 0.5;
+    let mut i = 0;
+    while i < 48 {
+        mantissa *= mantissa;
+        if mantissa >= 2.0 {
+            mantissa /= 2.0;
+            result += bit;
+        }
+        bit /= 2.0;
+        i += 1;
+    }
+    result
+}
+
+// 2 to the power `y`: the whole powers of 2, and the series of e^(f ln 2)
+// for the fraction.
+const fn exp2(y: f64) -> f64 {
+    let mut whole = y as i64;
+    if (whole as f64) > y {
+        whole -= 1;
+    }
+    let fraction = (y - whole as f64) * core::f64::consts::LN_2;
+
+    let mut result = 1.0;
+    let mut term = 1.0;
+    let mut k = 1;
+    while k < 24 {
+        term *= fraction / k as f64;
+        result += term;
+        k += 1;
+    }
+    while whole > 0 {
+        result *= 2.0;
+        whole -= 1;
+    }
+    while whole < 0 {
+        result /= 2.0;
+        whole += 1;
+    }
+    result
+}
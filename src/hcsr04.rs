@@ -0,0 +1,115 @@
+//! HC-SR04 ultrasonic distance sensor, echo timed by TIM3 input capture.
+//!
+//! A 10 us pulse on TRIG sends a 40 kHz burst; ECHO then goes high until the
+//! sound comes back, 5.8 us per millimetre of distance there and back, or for
+//! about 38 ms when nothing answers. [`HcSr04::trigger`] sends the pulse
+//! from any output pin, and TIM3 times ECHO on PB4 (D5) in PWM input mode:
+//! channel 1 resets the counter on the rising edge, channel 2 captures it on
+//! the falling edge, so CCR2 holds the width of the echo, in microseconds,
+//! without the CPU taking part.
+//!
+//! ECHO is a 5 V output: bring it down to 3.3 V with a divider (1k / 2k)
+//! before PB4. Leave 60 ms between measurements, for the echoes of one burst
+//! to die out.
+
+use embedded_hal::digital::OutputPin;
+
+use stm32g4xx_hal as hal;
+
+use hal::gpio::{gpiob::PB4, Alternate, AF2};
+use hal::rcc::{Clocks, Enable, GetBusFreq, Reset};
+use hal::stm32::{RCC, TIM3};
+
+/// Frequency of the capture timer: one tick per microsecond.
+const TICK_HZ: u32 = 1_000_000;
+
+/// The farthest echo trusted, 4 m there and back, in microseconds.
+pub const MAX_ECHO_US: u32 = 23_300;
+
+/// The distance of an echo of `echo_us` microseconds, in millimetres, at
+/// 343 m/s; `None` beyond the range of the sensor.
+pub const fn distance_mm(echo_us: u32) -> Option<u32> {
+    if echo_us > MAX_ECHO_US {
+        return None;
+    }
+    Some(echo_us * 343 / 2000)
+}
+
+/// The sensor: TRIG on `P`, ECHO on PB4.
+pub struct HcSr04<P> {
+    trig: P,
+    tim: TIM3,
+    _echo: PB4<Alternate<AF2>>,
+    // Core clock cycles of the 10 us trigger pulse.
+    pulse_cycles: u32,
+}
+
+impl<P: OutputPin> HcSr04<P> {
+    /// Starts TIM3 timing the echoes, TRIG low.
+    pub fn new(mut trig: P, tim: TIM3, echo: PB4<Alternate<AF2>>, clocks: &Clocks) -> Self {
+        trig.set_low().ok();
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            TIM3::enable(rcc);
+            TIM3::reset(rcc);
+        }
+
+        let psc = TIM3::get_timer_frequency(clocks).0 / TICK_HZ - 1;
+        tim.psc.write(|w| unsafe { w.psc().bits(psc as u16) });
+        tim.arr.write(|w| unsafe { w.bits(0xFFFF) });
+        // CC1 on TI1 for the rising edge, CC2 on TI1 as well for the falling
+        // one, both filtered over 8 timer clocks.
+        tim.ccmr1_input().write(|w| unsafe {
+            w.cc1s().bits(0b01).ic1f().bits(0b0011).cc2s().bits(0b10).ic2f().bits(0b0011)
+        });
+        tim.ccer.write(|w| w.cc1p().clear_bit().cc1e().set_bit().cc2p().set_bit().cc2e().set_bit());
+        // Slave reset mode on TI1FP1: each rising edge restarts the count.
+        tim.smcr.write(|w| unsafe { w.ts().bits(0b101).sms().bits(0b100) });
+        // Load the prescaler, without an update interrupt.
+        tim.cr1.modify(|_, w| w.urs().set_bit());
+        tim.egr.write(|w| w.ug().set_bit());
+        tim.cr1.modify(|_, w| w.cen().set_bit());
+
+        HcSr04 {
+            trig,
+            tim,
+            _echo: echo,
+            pulse_cycles: clocks.sys_clk.0 / 100_000,
+        }
+    }
+
+    /// Starts interrupting at the end of every echo.
+    pub fn listen(&mut self) {
+        self.tim.dier.modify(|_, w| w.cc2ie().set_bit());
+    }
+
+    /// Stops the interrupt.
+    pub fn unlisten(&mut self) {
+        self.tim.dier.modify(|_, w| w.cc2ie().clear_bit());
+    }
+
+    /// Sends the 10 us trigger pulse, busy waiting.
+    pub fn trigger(&mut self) {
+        self.trig.set_high().ok();
+        cortex_m::asm::delay(self.pulse_cycles);
+        self.trig.set_low().ok();
+    }
+
+    /// Returns the width of the echo that just ended, in microseconds, or
+    /// `None` if none did: call from the TIM3 interrupt.
+    pub fn handle_interrupt(&mut self) -> Option<u32> {
+        if self.tim.sr.read().cc2if().bit_is_clear() {
+            return None;
+        }
+        // Reading the capture clears its flag.
+        Some(self.tim.ccr2().read().bits() & 0xFFFF)
+    }
+
+    /// Stops the timer and returns TRIG, the timer and ECHO.
+    pub fn release(self) -> (P, TIM3, PB4<Alternate<AF2>>) {
+        self.tim.dier.write(|w| unsafe { w.bits(0) });
+        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+        (self.trig, self.tim, self._echo)
+    }
+}
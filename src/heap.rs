@@ -0,0 +1,53 @@
+//! Heap for `alloc`, from a fixed pool in RAM (the `alloc` feature).
+//!
+//! This crate and nearly all the examples allocate nothing: their buffers
+//! are arrays sized at compile time, so memory running out shows when
+//! linking, and a handler takes the same time on every call. Some data has
+//! no size before it arrives, though, like patterns typed in a shell. With
+//! the `alloc` feature this module is the global allocator, a first-fit
+//! linked-list heap from `embedded-alloc` over a static pool of
+//! [`HEAP_SIZE`] bytes, so `Vec`, `Box` and `String` of the `alloc` crate
+//! work once [`init`] handed it the pool, first thing in `main`.
+//!
+//! The heap has its costs:
+//!
+//! - an allocation can fail at run time: where an input decides the size,
+//!   `try_reserve` makes it an error to report, not a panic,
+//! - freed blocks fragment the pool: allocate at setup or on a command, not
+//!   on every interrupt,
+//! - each allocation walks the free list in a critical section: keep it out
+//!   of time-critical handlers.
+
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embedded_alloc::LlffHeap as Heap;
+
+/// Bytes of the pool.
+pub const HEAP_SIZE: usize = 4096;
+
+#[global_allocator]
+static HEAP: Heap = Heap::empty();
+
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Hands the pool to the allocator: call once, before the first
+/// allocation. Later calls do nothing.
+pub fn init() {
+    static mut POOL: [MaybeUninit<u8>; HEAP_SIZE] = [MaybeUninit::uninit(); HEAP_SIZE];
+    if INITIALIZED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    // NOTE(unsafe) the flag lets this run once: the pool is the heap's alone.
+    unsafe { HEAP.init(&raw mut POOL as usize, HEAP_SIZE) }
+}
+
+/// Bytes allocated.
+pub fn used() -> usize {
+    HEAP.used()
+}
+
+/// Bytes free, in one block or several.
+pub fn free() -> usize {
+    HEAP.free()
+}
@@ -0,0 +1,239 @@
+//! High-resolution PWM on HRTIM1 timer A.
+//!
+//! [`Pwm`] drives output TA1 on PA8 (D7, AF13): set on each period of timer A,
+//! reset on its compare 1. Period and compare are preloaded and take effect
+//! at the next period, so [`Pwm::set_duty`] and [`Pwm::set_frequency`] never
+//! produce a glitch on the output.
+//!
+//! The counters run at `fHRTIM` times 32 when the delay-locked loop is
+//! available, which needs `fHRTIM` (the APB2 timer clock) between 100 and
+//! 170 MHz: 184 ps steps at 170 MHz. Below 100 MHz, as on the 16 MHz HSI, the
+//! timer counts at `fHRTIM` like any other timer.
+//!
+//! The master timer of HRTIM1 is left free for an interrupt at a fixed rate
+//! ([`Pwm::listen`]), on the `HRTIM_MASTER_IRQN` vector.
+
+use stm32g4xx_hal as hal;
+
+use hal::gpio::{gpioa, Alternate, AF13};
+use hal::rcc::{Enable, Reset};
+use hal::stm32::{HRTIM_COMMON, HRTIM_MASTER, HRTIM_TIMA, RCC};
+use hal::time::Hertz;
+
+/// Largest period and compare value of the HRTIM counters.
+const MAX_PERIOD: u32 = 0xFFDF;
+/// First prescaler setting counting at `fHRTIM` or slower, the only ones
+/// without the delay-locked loop.
+const PSC_NO_DLL: u8 = 5;
+/// Slowest prescaler setting: `fHRTIM / 4`.
+const PSC_MAX: u8 = 7;
+/// Lowest `fHRTIM` for the delay-locked loop.
+const DLL_MIN_CLOCK: u32 = 100_000_000;
+
+/// Output pin of timer A, channel 1.
+pub type PwmPin = gpioa::PA8<Alternate<AF13>>;
+
+/// Counter settings for one frequency.
+#[derive(Clone, Copy)]
+struct Timing {
+    prescaler: u8,
+    period: u16,
+    repetitions: u8,
+}
+
+impl Timing {
+    /// Finds the finest prescaler from `prescaler` on that fits `frequency`.
+    ///
+    /// With `repeat`, what the slowest prescaler cannot fit is spread on the
+    /// repetition counter; otherwise the period saturates.
+    fn new(clock: Hertz, frequency: Hertz, mut prescaler: u8, repeat: bool) -> Self {
+        let frequency = frequency.0.max(1) as u64;
+        let ticks = loop {
+            let ticks = counter_clock(clock, prescaler) / frequency;
+            if ticks <= MAX_PERIOD as u64 || prescaler == PSC_MAX {
+                break ticks;
+            }
+            prescaler += 1;
+        };
+        let repetitions = if repeat { ticks.div_ceil(MAX_PERIOD as u64).clamp(1, 256) } else { 1 };
+        let period = (ticks / repetitions).clamp(min_compare(prescaler) as u64, MAX_PERIOD as u64);
+        Timing { prescaler, period: period as u16, repetitions: (repetitions - 1) as u8 }
+    }
+}
+
+/// Counting frequency of a prescaler setting, from `fHRTIM * 32` down to `fHRTIM / 4`.
+fn counter_clock(clock: Hertz, prescaler: u8) -> u64 {
+    (clock.0 as u64 * 32) >> prescaler
+}
+
+/// Smallest period and compare value of a prescaler setting: three `fHRTIM` cycles.
+fn min_compare(prescaler: u8) -> u16 {
+    (96 >> prescaler).max(3)
+}
+
+/// PWM output on TA1, and the master timer interrupt.
+pub struct Pwm {
+    master: HRTIM_MASTER,
+    tima: HRTIM_TIMA,
+    common: HRTIM_COMMON,
+    pin: PwmPin,
+    clock: Hertz,
+    timing: Timing,
+    duty: u16,
+}
+
+impl Pwm {
+    /// Starts timer A at `frequency`, with the output disabled and the duty at 0.
+    ///
+    /// `clock` is `fHRTIM`, `rcc.clocks.apb2_tim_clk`. The delay-locked loop
+    /// is calibrated first when the clock allows it.
+    pub fn new(
+        master: HRTIM_MASTER,
+        tima: HRTIM_TIMA,
+        common: HRTIM_COMMON,
+        pin: PwmPin,
+        clock: Hertz,
+        frequency: Hertz,
+    ) -> Self {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            HRTIM_TIMA::enable(rcc);
+            HRTIM_TIMA::reset(rcc);
+        }
+
+        let first = if clock.0 >= DLL_MIN_CLOCK {
+            // Calibrate once, then keep it calibrated every 14 us or so.
+            common.dllcr.write(|w| w.cal().set_bit());
+            while common.isr.read().dllrdy().bit_is_clear() {}
+            common.dllcr.write(|w| unsafe { w.calen().set_bit().calrte().bits(0b11) });
+            0
+        } else {
+            PSC_NO_DLL
+        };
+
+        let timing = Timing::new(clock, frequency, first, false);
+        // Continuous mode, preloaded registers updated on every period.
+        tima.timacr.write(|w| unsafe {
+            w.cont()
+                .set_bit()
+                .preen()
+                .set_bit()
+                .tx_repu()
+                .set_bit()
+                .ck_pscx()
+                .bits(timing.prescaler)
+        });
+        tima.perar.write(|w| unsafe { w.perx().bits(timing.period) });
+        tima.cmp1ar.write(|w| unsafe { w.cmp1x().bits(min_compare(timing.prescaler)) });
+        // TA1: high from the period to compare 1.
+        tima.seta1r.write(|w| w.per().set_bit());
+        tima.rsta1r.write(|w| w.cmp1().set_bit());
+
+        // Load the preloaded values, then start the counter.
+        common.cr2.write(|w| w.taswu().set_bit());
+        master.mcr.modify(|_, w| w.tacen().set_bit());
+
+        Pwm { master, tima, common, pin, clock, timing, duty: 0 }
+    }
+
+    /// The frequency of the output, as the prescaler and period round it.
+    pub fn frequency(&self) -> Hertz {
+        Hertz((counter_clock(self.clock, self.timing.prescaler) / self.timing.period as u64) as u32)
+    }
+
+    /// Changes the frequency from the next period, keeping the duty ratio.
+    ///
+    /// The counter restarts when the new frequency needs another prescaler.
+    pub fn set_frequency(&mut self, frequency: Hertz) {
+        let first = if self.clock.0 >= DLL_MIN_CLOCK { 0 } else { PSC_NO_DLL };
+        let timing = Timing::new(self.clock, frequency, first, false);
+        let duty = (self.duty as u32 * timing.period as u32 / self.timing.period as u32) as u16;
+        let restart = timing.prescaler != self.timing.prescaler;
+
+        if restart {
+            self.master.mcr.modify(|_, w| w.tacen().clear_bit());
+            self.tima.timacr.modify(|_, w| unsafe { w.ck_pscx().bits(timing.prescaler) });
+        }
+        self.tima.perar.write(|w| unsafe { w.perx().bits(timing.period) });
+        self.timing = timing;
+        self.set_duty(duty);
+        if restart {
+            self.tima.cntar.write(|w| unsafe { w.bits(0) });
+            self.common.cr2.write(|w| w.taswu().set_bit());
+            self.master.mcr.modify(|_, w| w.tacen().set_bit());
+        }
+    }
+
+    /// The duty of a full period: [`set_duty`](Self::set_duty) takes `0..=max_duty()`.
+    pub fn max_duty(&self) -> u16 {
+        self.timing.period
+    }
+
+    /// The high time of the output, in counter ticks.
+    pub fn duty(&self) -> u16 {
+        self.duty
+    }
+
+    /// Sets the high time of the output from the next period.
+    ///
+    /// Values below three cycles of `fHRTIM` are raised to that minimum.
+    pub fn set_duty(&mut self, duty: u16) {
+        self.duty = duty.min(self.timing.period);
+        let compare = self.duty.max(min_compare(self.timing.prescaler));
+        self.tima.cmp1ar.write(|w| unsafe { w.cmp1x().bits(compare) });
+    }
+
+    /// Length of one counter tick, in picoseconds.
+    pub fn tick_picos(&self) -> u32 {
+        (1_000_000_000_000 / counter_clock(self.clock, self.timing.prescaler)) as u32
+    }
+
+    /// Drives PA8 from the timer.
+    pub fn enable(&mut self) {
+        self.common.oenr.write(|w| w.ta1oen().set_bit());
+    }
+
+    /// Puts PA8 back to its idle (low) level.
+    pub fn disable(&mut self) {
+        self.common.odisr.write(|w| w.ta1odis().set_bit());
+    }
+
+    /// Starts the master timer with an interrupt `rate` times per second.
+    pub fn listen(&mut self, rate: Hertz) {
+        let timing = Timing::new(self.clock, rate, PSC_NO_DLL, true);
+        self.master.mcr.modify(|_, w| w.mcen().clear_bit());
+        self.master.mcr.modify(|_, w| unsafe { w.cont().set_bit().ck_psc().bits(timing.prescaler) });
+        self.master.mper.write(|w| unsafe { w.mper().bits(timing.period) });
+        self.master.mrep.write(|w| unsafe { w.mrep().bits(timing.repetitions) });
+        self.master.mcntr.write(|w| unsafe { w.bits(0) });
+        self.common.cr2.write(|w| w.mswu().set_bit());
+        self.master.micr.write(|w| w.mrepc().set_bit());
+        self.master.mdier.modify(|_, w| w.mrepie().set_bit());
+        self.master.mcr.modify(|_, w| w.mcen().set_bit());
+    }
+
+    /// Stops the master timer and its interrupt.
+    pub fn unlisten(&mut self) {
+        self.master.mdier.modify(|_, w| w.mrepie().clear_bit());
+        self.master.mcr.modify(|_, w| w.mcen().clear_bit());
+    }
+
+    /// Clears the master timer interrupt. Call it from `HRTIM_MASTER_IRQN`.
+    pub fn clear_interrupt(&mut self) {
+        self.master.micr.write(|w| w.mrepc().set_bit());
+    }
+
+    /// Stops both timers and returns the peripherals and the pin.
+    pub fn release(mut self) -> (HRTIM_MASTER, HRTIM_TIMA, HRTIM_COMMON, PwmPin) {
+        self.disable();
+        self.unlisten();
+        self.master.mcr.modify(|_, w| w.tacen().clear_bit());
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            HRTIM_TIMA::disable(rcc);
+        }
+        (self.master, self.tima, self.common, self.pin)
+    }
+}
@@ -0,0 +1,286 @@
+//! I2C1 on the Arduino header: controller with a TMP102 driver, or slave.
+//!
+//! The Nucleo routes I2C1 to the Arduino connector: SDA on D14 (PB9) and SCL
+//! on D15 (PB8), which is where most sensor shields and breakout boards end
+//! up. [`i2c1`] configures the bus in standard mode (100 kHz). The pins are
+//! open drain: the breakout board, or external 4.7 kΩ resistors to 3.3 V,
+//! must provide the pull-ups.
+//!
+//! [`Tmp102`] only needs the I2C trait of `embedded-hal` 1.0, so it works on
+//! any bus of the HAL wrapped in a [`Compat`](crate::compat::Compat), and on
+//! the buses of other 1.0 HALs.
+//!
+//! The HAL only drives the bus as a controller. [`I2cSlave`] turns I2C1 into
+//! a device on somebody else's bus instead, a Raspberry Pi or another board,
+//! and serves reads and writes of a register map from its interrupts.
+
+use stm32g4xx_hal as hal;
+
+use hal::gpio::{gpiob, AlternateOD, AF4};
+use hal::i2c::{Config, I2c, I2cExt};
+use hal::rcc::{Enable, Rcc, Reset};
+use hal::stm32::{I2C1, RCC};
+use hal::time::{Hertz, U32Ext};
+
+/// I2C1 with SDA on PB9 and SCL on PB8.
+pub type I2c1 = I2c<I2C1, gpiob::PB9<AlternateOD<AF4>>, gpiob::PB8<AlternateOD<AF4>>>;
+
+/// Configures I2C1 on the Arduino header pins at 100 kHz.
+pub fn i2c1(i2c: I2C1, sda: gpiob::PB9<AlternateOD<AF4>>, scl: gpiob::PB8<AlternateOD<AF4>>, rcc: &mut Rcc) -> I2c1 {
+    i2c.i2c(sda, scl, Config::new(100.khz()), rcc)
+}
+
+/// Temperature register of the TMP102.
+const TMP102_TEMPERATURE: u8 = 0x00;
+
+/// Texas Instruments TMP102 digital temperature sensor.
+pub struct Tmp102<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C> Tmp102<I2C>
+where
+    I2C: embedded_hal::i2c::I2c,
+{
+    /// Address with the ADD0 pin tied to GND, the default of most breakout boards.
+    pub const DEFAULT_ADDRESS: u8 = 0x48;
+
+    /// Creates the driver for the sensor at `address` (0x48 to 0x4B).
+    ///
+    /// The sensor starts converting on power-up, so nothing is written to it.
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        Tmp102 { i2c, address }
+    }
+
+    /// Reads the last conversion, in thousandths of a degree Celsius.
+    ///
+    /// The sensor converts four times per second with a resolution of 0.0625 °C.
+    pub fn read_millicelsius(&mut self) -> Result<i32, I2C::Error> {
+        let mut buffer = [0; 2];
+        self.i2c.write_read(self.address, &[TMP102_TEMPERATURE], &mut buffer)?;
+        // 12-bit two's complement value, left aligned.
+        let raw = i16::from_be_bytes(buffer) >> 4;
+        Ok(raw as i32 * 625 / 10)
+    }
+
+    /// Returns the bus.
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+}
+
+/// Register file exposed by an [`I2cSlave`].
+///
+/// A controller selects a register by writing its address as the first byte
+/// of a write transfer; the following bytes, written or read, go to
+/// consecutive registers.
+pub trait Registers {
+    /// Returns the value of `register`, about to be sent to the controller.
+    fn read(&mut self, register: u8) -> u8;
+    /// Stores `value`, written by the controller, into `register`.
+    fn write(&mut self, register: u8, value: u8);
+}
+
+/// Bus error seen by an [`I2cSlave`].
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum SlaveError {
+    /// Misplaced start or stop condition.
+    Bus,
+    /// Another device drove SDA low while this one sent a 1.
+    ArbitrationLost,
+    /// A byte was received before the previous one was read.
+    Overrun,
+}
+
+/// What [`I2cSlave::handle`] did.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum SlaveEvent {
+    /// A controller addressed this device, to `read` from it or to write to it.
+    AddressMatch { read: bool },
+    /// The controller wrote `value` into `register`.
+    Written { register: u8, value: u8 },
+    /// `register` was loaded for the controller to read.
+    Read { register: u8 },
+    /// The transfer ended with a stop condition.
+    Stop,
+    /// The transfer was disturbed.
+    Error(SlaveError),
+}
+
+/// I2C1 as a slave (target) device with a register map, on PB9/PB8.
+///
+/// Clock stretching holds the bus while the interrupt handler fetches or
+/// stores each byte, so the controller can run at any speed up to 400 kHz.
+pub struct I2cSlave {
+    i2c: I2C1,
+    sda: gpiob::PB9<AlternateOD<AF4>>,
+    scl: gpiob::PB8<AlternateOD<AF4>>,
+    register: u8,
+    select: bool,
+}
+
+impl I2cSlave {
+    /// Answers to the 7-bit `address` on I2C1.
+    ///
+    /// `clock` is the I2C1 kernel clock, PCLK1 by default
+    /// (`rcc.clocks.apb1_clk`): it sets the data setup and hold times.
+    pub fn new(
+        i2c: I2C1,
+        sda: gpiob::PB9<AlternateOD<AF4>>,
+        scl: gpiob::PB8<AlternateOD<AF4>>,
+        address: u8,
+        clock: Hertz,
+    ) -> Self {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            I2C1::enable(rcc);
+            I2C1::reset(rcc);
+        }
+
+        // At least 500 ns data setup and 250 ns hold time, enough for the
+        // standard and the fast mode, in ticks of the prescaled clock: about
+        // 250 ns up to 64 MHz, shorter above, PRESC topping out at 16 (94 ns at
+        // 170 MHz).
+        let presc = (clock.0 / 4_000_000).clamp(1, 16);
+        let ticks_hz = clock.0 / presc;
+        let scldel = ticks_hz.div_ceil(2_000_000).saturating_sub(1);
+        let sdadel = ticks_hz.div_ceil(4_000_000);
+        i2c.timingr.write(|w| {
+            w.presc()
+                .bits(presc as u8 - 1)
+                .scldel()
+                .bits(scldel as u8)
+                .sdadel()
+                .bits(sdadel as u8)
+        });
+        i2c.oar1.write(|w| w.oa1().bits((address as u16 & 0x7F) << 1).oa1en().set_bit());
+        i2c.cr1.write(|w| w.pe().set_bit());
+
+        I2cSlave { i2c, sda, scl, register: 0, select: false }
+    }
+
+    /// Starts interrupt generation on address match, data, stop and errors.
+    ///
+    /// Note, you will also have to unmask the I2C1_EV and I2C1_ER interrupts in
+    /// the NVIC, and call [`handle`](Self::handle) from both handlers.
+    pub fn listen(&mut self) {
+        self.i2c.cr1.modify(|_, w| {
+            w.addrie()
+                .set_bit()
+                .rxie()
+                .set_bit()
+                .txie()
+                .set_bit()
+                .stopie()
+                .set_bit()
+                .nackie()
+                .set_bit()
+                .errie()
+                .set_bit()
+        });
+    }
+
+    /// Stops interrupt generation.
+    pub fn unlisten(&mut self) {
+        self.i2c.cr1.modify(|_, w| {
+            w.addrie()
+                .clear_bit()
+                .rxie()
+                .clear_bit()
+                .txie()
+                .clear_bit()
+                .stopie()
+                .clear_bit()
+                .nackie()
+                .clear_bit()
+                .errie()
+                .clear_bit()
+        });
+    }
+
+    /// Serves one pending condition of the bus against `registers`.
+    ///
+    /// Call it until it returns `None` from the interrupt handlers: that
+    /// clears every flag raising the interrupts.
+    pub fn handle<R: Registers>(&mut self, registers: &mut R) -> Option<SlaveEvent> {
+        loop {
+            let isr = self.i2c.isr.read();
+
+            if isr.berr().bit_is_set() {
+                self.i2c.icr.write(|w| w.berrcf().set_bit());
+                return Some(SlaveEvent::Error(SlaveError::Bus));
+            }
+            if isr.arlo().bit_is_set() {
+                self.i2c.icr.write(|w| w.arlocf().set_bit());
+                return Some(SlaveEvent::Error(SlaveError::ArbitrationLost));
+            }
+            if isr.ovr().bit_is_set() {
+                self.i2c.icr.write(|w| w.ovrcf().set_bit());
+                return Some(SlaveEvent::Error(SlaveError::Overrun));
+            }
+
+            if isr.addr().bit_is_set() {
+                let read = isr.dir().is_read();
+                if read {
+                    // Drop a byte left over from an earlier read.
+                    self.i2c.isr.write(|w| w.txe().set_bit());
+                } else {
+                    self.select = true;
+                }
+                self.i2c.icr.write(|w| w.addrcf().set_bit());
+                return Some(SlaveEvent::AddressMatch { read });
+            }
+
+            if isr.rxne().bit_is_set() {
+                let value = self.i2c.rxdr.read().rxdata().bits();
+                if self.select {
+                    // First byte of a write: the register address.
+                    self.select = false;
+                    self.register = value;
+                    continue;
+                }
+                let register = self.register;
+                registers.write(register, value);
+                self.register = register.wrapping_add(1);
+                return Some(SlaveEvent::Written { register, value });
+            }
+
+            if isr.txis().bit_is_set() {
+                let register = self.register;
+                let value = registers.read(register);
+                self.i2c.txdr.write(|w| w.txdata().bits(value));
+                self.register = register.wrapping_add(1);
+                return Some(SlaveEvent::Read { register });
+            }
+
+            if isr.nackf().bit_is_set() {
+                // The controller has read enough. The byte already loaded for
+                // it was not sent: the next read starts from that register.
+                if isr.txe().bit_is_clear() {
+                    self.register = self.register.wrapping_sub(1);
+                }
+                self.i2c.icr.write(|w| w.nackcf().set_bit());
+                continue;
+            }
+
+            if isr.stopf().bit_is_set() {
+                self.i2c.icr.write(|w| w.stopcf().set_bit());
+                return Some(SlaveEvent::Stop);
+            }
+
+            return None;
+        }
+    }
+
+    /// Disables I2C1 and returns the peripheral and the pins.
+    pub fn release(self) -> (I2C1, gpiob::PB9<AlternateOD<AF4>>, gpiob::PB8<AlternateOD<AF4>>) {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            I2C1::disable(rcc);
+        }
+        (self.i2c, self.sda, self.scl)
+    }
+}
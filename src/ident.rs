@@ -0,0 +1,85 @@
+//! The identity of the chip: unique ID, flash size and revision.
+//!
+//! Every STM32G4 leaves the factory with a 96-bit unique ID (wafer
+//! coordinates, wafer and lot numbers) and its flash size in the system
+//! memory, and answers its device and revision IDs in DBGMCU_IDCODE.
+//! [`read`] gathers them into an [`Identity`], and [`log`] logs it, once at
+//! start, so a log says which board it came from; [`Identity::short_id`]
+//! folds the unique ID into 32 bits for a telemetry frame.
+//!
+//! | Device ID | Chips                |
+//! |-----------|----------------------|
+//! | 0x468     | STM32G431, STM32G441 |
+//! | 0x469     | STM32G47x, STM32G48x |
+//! | 0x479     | STM32G491, STM32G4A1 |
+
+use stm32g4xx_hal as hal;
+
+use hal::signature::FlashSize;
+use hal::stm32::DBGMCU;
+
+// Where the unique ID lives, in the system memory.
+const UID_BASE: u32 = 0x1FFF_7590;
+
+/// What identifies the chip.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "logging", derive(defmt::Format))]
+pub struct Identity {
+    /// The 96-bit unique ID, lowest word first.
+    pub uid: [u32; 3],
+    /// The flash size, in KiB.
+    pub flash_kib: u16,
+    /// The device ID of DBGMCU_IDCODE: the line of the chip.
+    pub dev_id: u16,
+    /// The revision ID of DBGMCU_IDCODE: the silicon revision.
+    pub rev_id: u16,
+}
+
+impl Identity {
+    /// The chips of the device ID, or `"unknown"`.
+    pub fn device(&self) -> &'static str {
+        match self.dev_id {
+            0x468 => "STM32G431/441",
+            0x469 => "STM32G47x/48x",
+            0x479 => "STM32G491/4A1",
+            _ => "unknown",
+        }
+    }
+
+    /// The unique ID folded into 32 bits, for a frame with no room for 96.
+    pub fn short_id(&self) -> u32 {
+        self.uid[0] ^ self.uid[1] ^ self.uid[2]
+    }
+}
+
+/// Reads the identity of the chip.
+pub fn read() -> Identity {
+    let uid = UID_BASE as *const u32;
+    // NOTE(unsafe) read-only words of the system memory, and atomic read
+    // with no side effects.
+    let (uid, idcode) = unsafe {
+        ([uid.read_volatile(), uid.add(1).read_volatile(), uid.add(2).read_volatile()], (*DBGMCU::ptr()).idcode.read())
+    };
+    Identity {
+        uid,
+        flash_kib: FlashSize::get().kilo_bytes(),
+        dev_id: idcode.dev_id().bits(),
+        rev_id: idcode.rev_id().bits(),
+    }
+}
+
+/// Logs the identity of the chip.
+#[cfg(feature = "logging")]
+pub fn log() {
+    let identity = read();
+    crate::info!(
+        "{} (device {:#x}, revision {:#x}), {} KiB flash, UID {:08x}{:08x}{:08x}",
+        identity.device(),
+        identity.dev_id,
+        identity.rev_id,
+        identity.flash_kib,
+        identity.uid[2],
+        identity.uid[1],
+        identity.uid[0]
+    );
+}
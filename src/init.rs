@@ -0,0 +1,166 @@
+//! Start-up errors, logged and blinked on the LED.
+//!
+//! Setup code returns an [`InitError`] instead of panicking on the first
+//! `unwrap`, so a failure says which step failed: [`halt`] logs it with
+//! defmt and then blinks its [`InitError::code`] on LD2 forever, the code's
+//! number of short flashes and a pause, for a board with no probe attached.
+//!
+//! | Flashes | Error                                    |
+//! |---------|------------------------------------------|
+//! | 1       | [`InitError::PeripheralsTaken`]          |
+//! | 2       | [`InitError::CorePeripheralsTaken`]      |
+//! | 3       | [`InitError::ClockMismatch`]             |
+//!
+//! [`halt`] drives PA5 through its registers, so it works whether or not the
+//! setup got as far as the LED, and times the flashes with busy waits on the
+//! HSI, switched back to first: an [`InitError::ClockMismatch`] leaves the
+//! PLL at an unknown frequency.
+//!
+//! # Degraded mode
+//!
+//! Not every failure is worth a halt: without its serial port, its sensor or
+//! its CAN bus, the program can still blink. [`Capabilities`] records which
+//! of those optional [`Subsystem`]s came up: [`Capabilities::check`] takes
+//! the result of each setup, logs a failure and goes on, and the code using
+//! a subsystem asks [`Capabilities::has`] first. A subsystem failing later,
+//! a sensor unplugged, is [`Capabilities::lose`]: the rest of the program
+//! carries on without it the same way.
+
+use stm32g4xx_hal as hal;
+
+use hal::stm32::{GPIOA, RCC};
+
+// The LD2 pin of PA5, and the HSI the core runs from out of reset.
+const LED_PIN: u32 = 5;
+const HSI_HZ: u32 = 16_000_000;
+
+/// An optional part of the program, which it can run without.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "logging", derive(defmt::Format))]
+pub enum Subsystem {
+    /// The serial port.
+    Serial,
+    /// The I2C sensor.
+    Sensor,
+    /// The CAN bus.
+    Can,
+}
+
+impl Subsystem {
+    /// All of them.
+    pub const ALL: [Subsystem; 3] = [Subsystem::Serial, Subsystem::Sensor, Subsystem::Can];
+
+    const fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// The optional subsystems that are up.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Capabilities(u8);
+
+impl Capabilities {
+    /// None of them, before the setup.
+    pub const NONE: Capabilities = Capabilities(0);
+
+    /// Records the setup of `subsystem`: `Some` of its value and the
+    /// subsystem up, or `None` and a warning in the log.
+    pub fn check<T, E>(&mut self, subsystem: Subsystem, result: Result<T, E>) -> Option<T> {
+        match result {
+            Ok(value) => {
+                self.0 |= subsystem.bit();
+                Some(value)
+            }
+            Err(_) => {
+                #[cfg(feature = "logging")]
+                crate::warn!("{:?} unavailable: running without it", subsystem);
+                None
+            }
+        }
+    }
+
+    /// Returns `true` if `subsystem` is up.
+    pub fn has(self, subsystem: Subsystem) -> bool {
+        self.0 & subsystem.bit() != 0
+    }
+
+    /// Records that `subsystem` failed, and logs it the first time.
+    pub fn lose(&mut self, subsystem: Subsystem) {
+        if self.has(subsystem) {
+            self.0 &= !subsystem.bit();
+            #[cfg(feature = "logging")]
+            crate::warn!("{:?} lost: running without it", subsystem);
+        }
+    }
+
+    /// Returns `true` if a subsystem is down.
+    pub fn is_degraded(self) -> bool {
+        Subsystem::ALL.iter().any(|&subsystem| !self.has(subsystem))
+    }
+}
+
+#[cfg(feature = "logging")]
+impl defmt::Format for Capabilities {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "serial {}, sensor {}, CAN {}",
+            self.has(Subsystem::Serial),
+            self.has(Subsystem::Sensor),
+            self.has(Subsystem::Can)
+        );
+    }
+}
+
+/// Why the setup failed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "logging", derive(defmt::Format))]
+pub enum InitError {
+    /// The device peripherals were taken already.
+    PeripheralsTaken,
+    /// The core peripherals were taken already.
+    CorePeripheralsTaken,
+    /// The system clock came up at `actual` Hz instead of `expected`.
+    ClockMismatch { expected: u32, actual: u32 },
+}
+
+impl InitError {
+    /// The flashes of the error on the LED.
+    pub const fn code(self) -> u8 {
+        match self {
+            InitError::PeripheralsTaken => 1,
+            InitError::CorePeripheralsTaken => 2,
+            InitError::ClockMismatch { .. } => 3,
+        }
+    }
+}
+
+/// Logs `error` and blinks its code on LD2, forever.
+pub fn halt(error: InitError) -> ! {
+    #[cfg(feature = "logging")]
+    crate::error!("Init failed: {:?} (code {})", error, error.code());
+    cortex_m::interrupt::disable();
+
+    // NOTE(unsafe) nothing else runs any more: the LED pin is ours.
+    let (rcc, gpioa) = unsafe { (&*RCC::ptr(), &*GPIOA::ptr()) };
+    rcc.ahb2enr.modify(|_, w| w.gpioaen().set_bit());
+    gpioa.moder.modify(|r, w| unsafe { w.bits(r.bits() & !(0b11 << (LED_PIN * 2)) | 0b01 << (LED_PIN * 2)) });
+
+    // The HSI as the system clock, no prescaler: the flash wait states of a
+    // faster clock are more than it needs.
+    rcc.cr.modify(|_, w| w.hsion().set_bit());
+    while rcc.cr.read().hsirdy().bit_is_clear() {}
+    rcc.cfgr.write(|w| unsafe { w.bits(0b01) });
+    while rcc.cfgr.read().sws().bits() != 0b01 {}
+
+    let cycles_per_ms = HSI_HZ / 1000;
+    loop {
+        for _ in 0..error.code() {
+            gpioa.bsrr.write(|w| unsafe { w.bits(1 << LED_PIN) });
+            cortex_m::asm::delay(150 * cycles_per_ms);
+            gpioa.bsrr.write(|w| unsafe { w.bits(1 << (LED_PIN + 16)) });
+            cortex_m::asm::delay(250 * cycles_per_ms);
+        }
+        cortex_m::asm::delay(1200 * cycles_per_ms);
+    }
+}
@@ -0,0 +1,215 @@
+//! NEC infrared remote frames from a TSOP receiver, on TIM4 input capture.
+//!
+//! A TSOP38238 (or any 38 kHz demodulating receiver) pulls its output low
+//! while it sees the carrier. NEC sends a frame per key press: a 9 ms burst
+//! and a 4.5 ms space, then 32 bits, least significant first, each a 562 us
+//! burst followed by a 562 us space for a 0 or a 1687 us space for a 1, and
+//! a last burst to end the space of bit 31. The bytes are the address, its
+//! inverse (or the high byte of a 16-bit address), the command and its
+//! inverse. While the key is held the remote sends a repeat frame every
+//! 108 ms: a 9 ms burst, a 2.25 ms space and a burst.
+//!
+//! Only the falling edges, the starts of the bursts, matter: the time from
+//! one to the next is 13.5 ms for the start of a frame, 11.25 ms for a
+//! repeat, 1.125 ms for a 0 and 2.25 ms for a 1. TIM4 counts microseconds
+//! and captures the count on every falling edge of PB6 (D10);
+//! [`Receiver::handle_interrupt`] hands the intervals to a [`Decoder`], which
+//! knows nothing of the timer.
+
+use stm32g4xx_hal as hal;
+
+use hal::gpio::{gpiob::PB6, Alternate, AF2};
+use hal::rcc::{Clocks, Enable, GetBusFreq, Reset};
+use hal::stm32::{RCC, TIM4};
+
+/// Frequency of the capture timer: one tick per microsecond.
+const TICK_HZ: u32 = 1_000_000;
+
+// Accepted intervals between falling edges, in microseconds: the nominal
+// value of each give or take about 15%, the repeat and start ranges meeting.
+const START_US: core::ops::Range<u32> = 12_400..15_500;
+const REPEAT_US: core::ops::Range<u32> = 9_500..12_400;
+const ZERO_US: core::ops::Range<u32> = 900..1_500;
+const ONE_US: core::ops::Range<u32> = 1_900..2_600;
+
+/// A decoded frame.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct Command {
+    /// The 8-bit address, or the 16-bit address of extended NEC.
+    pub address: u16,
+    pub command: u8,
+}
+
+/// What the remote sent.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Event {
+    /// A key press.
+    Pressed(Command),
+    /// The key of the last press, still held.
+    Repeat(Command),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Idle,
+    Bits { count: u8, data: u32 },
+}
+
+/// The NEC protocol, fed the intervals between falling edges.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decoder {
+    state: State,
+    last: Option<Command>,
+}
+
+impl Decoder {
+    /// A decoder waiting for the start of a frame.
+    pub const fn new() -> Self {
+        Decoder { state: State::Idle, last: None }
+    }
+
+    /// Takes the time from the last falling edge to this one, in
+    /// microseconds, and returns the event it completes.
+    pub fn edge(&mut self, interval_us: u32) -> Option<Event> {
+        if START_US.contains(&interval_us) {
+            self.state = State::Bits { count: 0, data: 0 };
+            return None;
+        }
+        match self.state {
+            State::Idle => {
+                if REPEAT_US.contains(&interval_us) {
+                    self.last.map(Event::Repeat)
+                } else {
+                    None
+                }
+            }
+            State::Bits { count, data } => {
+                let bit = if ZERO_US.contains(&interval_us) {
+                    0
+                } else if ONE_US.contains(&interval_us) {
+                    1
+                } else {
+                    // Noise, or a frame cut short.
+                    self.state = State::Idle;
+                    self.last = None;
+                    return None;
+                };
+                let data = data | bit << count;
+                if count < 31 {
+                    self.state = State::Bits { count: count + 1, data };
+                    return None;
+                }
+                self.state = State::Idle;
+                self.last = frame(data);
+                self.last.map(Event::Pressed)
+            }
+        }
+    }
+
+    /// Forgets any frame in progress and the last command.
+    pub fn reset(&mut self) {
+        *self = Decoder::new();
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Checks the 32 bits of a frame, the address in the low byte.
+fn frame(data: u32) -> Option<Command> {
+    let [low, high, command, inverse] = data.to_le_bytes();
+    if command != !inverse {
+        return None;
+    }
+    let address = if high == !low { low as u16 } else { u16::from_le_bytes([low, high]) };
+    Some(Command { address, command })
+}
+
+/// TIM4 channel 1 capturing the falling edges of a receiver on PB6.
+pub struct Receiver {
+    tim: TIM4,
+    _pin: PB6<Alternate<AF2>>,
+    decoder: Decoder,
+    // The count at the last falling edge, and the timer overflows since.
+    last: u16,
+    overflows: u16,
+}
+
+impl Receiver {
+    /// Starts TIM4 counting microseconds and capturing on PB6.
+    pub fn new(tim: TIM4, pin: PB6<Alternate<AF2>>, clocks: &Clocks) -> Self {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            TIM4::enable(rcc);
+            TIM4::reset(rcc);
+        }
+
+        let psc = TIM4::get_timer_frequency(clocks).0 / TICK_HZ - 1;
+        tim.psc.write(|w| unsafe { w.psc().bits(psc as u16) });
+        tim.arr.write(|w| unsafe { w.bits(0xFFFF) });
+        // CC1 on TI1, filtered over 8 timer clocks, falling edges.
+        tim.ccmr1_input().write(|w| unsafe { w.cc1s().bits(0b01).ic1f().bits(0b0011) });
+        tim.ccer.write(|w| w.cc1p().set_bit().cc1np().clear_bit().cc1e().set_bit());
+        // Only overflows raise the update interrupt, not the UG loading the
+        // prescaler.
+        tim.cr1.modify(|_, w| w.urs().set_bit());
+        tim.egr.write(|w| w.ug().set_bit());
+        tim.cr1.modify(|_, w| w.cen().set_bit());
+
+        Receiver { tim, _pin: pin, decoder: Decoder::new(), last: 0, overflows: 0 }
+    }
+
+    /// Starts interrupting on captures and on timer overflows.
+    pub fn listen(&mut self) {
+        self.tim.dier.modify(|_, w| w.cc1ie().set_bit().uie().set_bit());
+    }
+
+    /// Stops the interrupts.
+    pub fn unlisten(&mut self) {
+        self.tim.dier.modify(|_, w| w.cc1ie().clear_bit().uie().clear_bit());
+    }
+
+    /// Handles the capture or overflow of the interrupt and returns the
+    /// event it completes: call from the TIM4 interrupt.
+    pub fn handle_interrupt(&mut self) -> Option<Event> {
+        let sr = self.tim.sr.read();
+        let overflowed = sr.uif().bit_is_set();
+        if overflowed {
+            self.tim.sr.write(|w| unsafe { w.bits(!1) });
+        }
+        if sr.cc1if().bit_is_clear() {
+            self.overflows = self.overflows.saturating_add(overflowed as u16);
+            return None;
+        }
+
+        // Reading the capture clears its flag.
+        let capture = self.tim.ccr1().read().bits() as u16;
+        // With both pending, a capture in the upper half of the count came
+        // before the overflow, which then starts the next interval.
+        let after = overflowed && capture >= 0x8000;
+        if overflowed && !after {
+            self.overflows = self.overflows.saturating_add(1);
+        }
+        let interval = match self.overflows {
+            0 => capture.wrapping_sub(self.last) as u32,
+            1 => (capture as u32 + 0x1_0000) - self.last as u32,
+            // Longer than a frame could be.
+            _ => u32::MAX,
+        };
+        self.last = capture;
+        self.overflows = after as u16;
+        self.decoder.edge(interval)
+    }
+
+    /// Stops the timer and returns the peripheral and the pin.
+    pub fn release(self) -> (TIM4, PB6<Alternate<AF2>>) {
+        self.tim.dier.write(|w| unsafe { w.bits(0) });
+        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+        self.tim.ccer.write(|w| w.cc1e().clear_bit());
+        (self.tim, self._pin)
+    }
+}
@@ -0,0 +1,162 @@
+//! Timer interrupts dispatched to handlers registered at run time.
+//!
+//! The update interrupt of a timer, its timeout, usually has its body
+//! written in the program's `#[interrupt] fn TIM2()`. [`timer_handlers!`]
+//! defines the vectors of the timers named instead; each clears the update
+//! flag and calls the handler set with [`on_timeout`], which the program
+//! swaps as it goes, with no ISR to rewrite:
+//!
+//! ```text
+//! nucleo_g474re::timer_handlers!(TIM2);
+//!
+//! irq::on_timeout(TimerId::Tim2, blink);
+//! irq::unmask(TimerId::Tim2);
+//! // later, from anywhere, an interrupt too:
+//! irq::on_timeout(TimerId::Tim2, || defmt::info!("Timeout"));
+//! ```
+//!
+//! A handler is a `fn()`, or a closure capturing nothing. Its state lives in
+//! the usual `Mutex` globals. A timeout with no handler is only cleared. The
+//! timers are the general-purpose and basic ones of every G4: TIM2, TIM3,
+//! TIM4, TIM6 and TIM7. The program still starts them and listens to their
+//! timeouts with the HAL's `Timer`.
+
+use core::cell::Cell;
+
+use cortex_m::interrupt::Mutex;
+use cortex_m::peripheral::NVIC;
+
+use stm32g4xx_hal as hal;
+
+use hal::stm32::{Interrupt, TIM2, TIM3, TIM4, TIM6, TIM7};
+
+/// A timeout handler.
+pub type Handler = fn();
+
+/// A timer with a dispatched update interrupt.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum TimerId {
+    Tim2,
+    Tim3,
+    Tim4,
+    Tim6,
+    Tim7,
+}
+
+/// Timers of [`TimerId`].
+pub const TIMERS: usize = 5;
+
+impl TimerId {
+    /// The timer's update interrupt, for the NVIC.
+    pub fn interrupt(self) -> Interrupt {
+        match self {
+            TimerId::Tim2 => Interrupt::TIM2,
+            TimerId::Tim3 => Interrupt::TIM3,
+            TimerId::Tim4 => Interrupt::TIM4,
+            TimerId::Tim6 => Interrupt::TIM6_DACUNDER,
+            TimerId::Tim7 => Interrupt::TIM7,
+        }
+    }
+}
+
+// Create a Global Variable for the timeout handlers.
+static HANDLERS: Mutex<Cell<[Option<Handler>; TIMERS]>> = Mutex::new(Cell::new([None; TIMERS]));
+
+/// Calls `handler` on every timeout of `timer`, in place of the handler
+/// before it, which is returned.
+pub fn on_timeout(timer: TimerId, handler: Handler) -> Option<Handler> {
+    cortex_m::interrupt::free(|cs| swap(cs, timer, Some(handler)))
+}
+
+/// Removes the handler of `timer` and returns it: its timeouts are only
+/// cleared.
+pub fn clear(timer: TimerId) -> Option<Handler> {
+    cortex_m::interrupt::free(|cs| swap(cs, timer, None))
+}
+
+fn swap(cs: &cortex_m::interrupt::CriticalSection, timer: TimerId, handler: Option<Handler>) -> Option<Handler> {
+    let handlers = HANDLERS.borrow(cs);
+    let mut table = handlers.get();
+    let previous = core::mem::replace(&mut table[timer as usize], handler);
+    handlers.set(table);
+    previous
+}
+
+/// Unmasks the update interrupt of `timer` in the NVIC.
+pub fn unmask(timer: TimerId) {
+    unsafe { NVIC::unmask(timer.interrupt()) };
+}
+
+/// Clears the timeout of `timer` and calls its handler: called by the ISRs
+/// of [`timer_handlers!`].
+pub fn dispatch(timer: TimerId) {
+    // NOTE(unsafe) the status register only, which the ISR owns; as the
+    // HAL's `clear_interrupt`.
+    unsafe {
+        match timer {
+            TimerId::Tim2 => (*TIM2::ptr()).sr.write(|w| w.uif().clear_bit()),
+            TimerId::Tim3 => (*TIM3::ptr()).sr.write(|w| w.uif().clear_bit()),
+            TimerId::Tim4 => (*TIM4::ptr()).sr.write(|w| w.uif().clear_bit()),
+            TimerId::Tim6 => (*TIM6::ptr()).sr.write(|w| w.uif().clear_bit()),
+            TimerId::Tim7 => (*TIM7::ptr()).sr.write(|w| w.uif().clear_bit()),
+        }
+    }
+    if let Some(handler) = cortex_m::interrupt::free(|cs| HANDLERS.borrow(cs).get()[timer as usize]) {
+        handler();
+    }
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use stm32g4xx_hal::interrupt;
+}
+
+/// Defines the update interrupt handlers of the timers named, out of TIM2,
+/// TIM3, TIM4, TIM6 and TIM7, dispatching to [`on_timeout`] handlers.
+/// Invoke once, at the top level of the program.
+#[macro_export]
+macro_rules! timer_handlers {
+    ($($timer:ident),+ $(,)?) => {
+        mod __timer_handlers {
+            use $crate::irq::__private::interrupt;
+            use $crate::irq::TimerId;
+
+            $($crate::__timer_handler!($timer);)+
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __timer_handler {
+    (TIM2) => {
+        #[interrupt]
+        fn TIM2() {
+            $crate::irq::dispatch(TimerId::Tim2);
+        }
+    };
+    (TIM3) => {
+        #[interrupt]
+        fn TIM3() {
+            $crate::irq::dispatch(TimerId::Tim3);
+        }
+    };
+    (TIM4) => {
+        #[interrupt]
+        fn TIM4() {
+            $crate::irq::dispatch(TimerId::Tim4);
+        }
+    };
+    (TIM6) => {
+        #[interrupt]
+        fn TIM6_DACUNDER() {
+            $crate::irq::dispatch(TimerId::Tim6);
+        }
+    };
+    (TIM7) => {
+        #[interrupt]
+        fn TIM7() {
+            $crate::irq::dispatch(TimerId::Tim7);
+        }
+    };
+}
@@ -0,0 +1,71 @@
+//! An LED on any output pin.
+//!
+//! [`Led`] wraps a pin implementing embedded-hal 1.0's `OutputPin`, a pin of
+//! the HAL in a `compat::Compat`: the board's LD2 on PA5 (`board::LedPin`),
+//! or an external LED on any port, wired to light with the pin high, or low
+//! for one sinking its current into the pin. The interrupt code that turns
+//! it on, off or toggles it stays the same whichever pin it is on; only the
+//! pin type of the global changes.
+
+use embedded_hal::digital::OutputPin;
+
+/// An LED, off at the start.
+pub struct Led<P> {
+    pin: P,
+    active_low: bool,
+    on: bool,
+}
+
+impl<P: OutputPin> Led<P> {
+    /// An LED lit with the pin high, as LD2.
+    pub fn new(pin: P) -> Self {
+        Self::with_polarity(pin, false)
+    }
+
+    /// An LED lit with the pin low: anode to 3.3 V, cathode to the pin.
+    pub fn active_low(pin: P) -> Self {
+        Self::with_polarity(pin, true)
+    }
+
+    fn with_polarity(pin: P, active_low: bool) -> Self {
+        let mut led = Led { pin, active_low, on: false };
+        led.off().ok();
+        led
+    }
+
+    /// Lights the LED.
+    pub fn on(&mut self) -> Result<(), P::Error> {
+        self.set(true)
+    }
+
+    /// Turns the LED off.
+    pub fn off(&mut self) -> Result<(), P::Error> {
+        self.set(false)
+    }
+
+    /// Switches the LED to the other state.
+    pub fn toggle(&mut self) -> Result<(), P::Error> {
+        self.set(!self.on)
+    }
+
+    /// Lights the LED if `on`, turns it off otherwise.
+    pub fn set(&mut self, on: bool) -> Result<(), P::Error> {
+        if on != self.active_low {
+            self.pin.set_high()?;
+        } else {
+            self.pin.set_low()?;
+        }
+        self.on = on;
+        Ok(())
+    }
+
+    /// Returns `true` while the LED is lit.
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    /// Returns the pin, in its current state.
+    pub fn release(self) -> P {
+        self.pin
+    }
+}
@@ -0,0 +1,93 @@
+//! Support library for the Nucleo G474RE interrupt examples.
+//!
+//! The main binary (`src/main.rs`) and the programs under `examples/` share
+//! the helpers in this crate. Each module wraps a peripheral, or a feature
+//! of a peripheral, that `stm32g4xx-hal` does not expose yet, so the examples
+//! can stay focused on the interrupt flow instead of raw register access.
+
+// `no_std`: embedded environment without the standard library.
+#![no_std]
+
+// The blink's own modules, in every build.
+pub mod board;
+pub mod button;
+pub mod clocks;
+pub mod compat;
+#[cfg(feature = "alloc")]
+pub mod heap;
+pub mod ident;
+pub mod init;
+pub mod led;
+pub mod log;
+pub mod power;
+#[cfg(feature = "swo")]
+pub mod swo;
+#[cfg(feature = "watch")]
+pub mod watch;
+
+#[cfg(all(feature = "swo", feature = "watch"))]
+compile_error!("the `swo` and `watch` features both replace the RTT logger: pick one");
+
+// Everything else the examples use, left out of a `minimal` build.
+cfg_if::cfg_if! {
+    if #[cfg(feature = "peripherals")] {
+        pub mod adc;
+        pub mod bootloader;
+        pub mod burst;
+        pub mod calib;
+        pub mod can;
+        pub mod charlie;
+        pub mod comp;
+        pub mod cordic;
+        pub mod crc;
+        pub mod dac;
+        pub mod dma;
+        pub mod encoder;
+        pub mod exti;
+        pub mod faults;
+        pub mod fmac;
+        pub mod freqmeter;
+        pub mod gamma;
+        pub mod hcsr04;
+        #[cfg(feature = "hrtim")]
+        pub mod hrtim;
+        pub mod i2c;
+        pub mod ir;
+        pub mod irq;
+        pub mod load;
+        pub mod mco;
+        pub mod monotonic;
+        pub mod motor;
+        pub mod opamp;
+        pub mod optionbytes;
+        #[cfg(feature = "dual-bank")]
+        pub mod ota;
+        pub mod power_probe;
+        pub mod profile;
+        pub mod pwm;
+        #[cfg(feature = "quadspi")]
+        pub mod qspi;
+        pub mod rng;
+        pub mod rtc;
+        pub mod sai;
+        pub mod servo;
+        pub mod shell;
+        pub mod softpwm;
+        pub mod spi;
+        pub mod stack;
+        pub mod stopwatch;
+        pub mod supply;
+        pub mod tick;
+        pub mod timebase;
+        pub mod timer_wheel;
+        pub mod tone;
+        pub mod ucpd;
+        pub mod ws2812;
+
+        // The hardware-independent modules, from the `logic` crate.
+        pub use nucleo_g474re_logic::{app, debounce, deferlog, executor, keypad, leds, stats, tempcomp};
+    }
+}
+
+// The blink settings, from the `logic` crate too.
+pub use nucleo_g474re_logic::config;
@@ -0,0 +1,80 @@
+//! CPU load: the share of time spent out of `wfi`.
+//!
+//! The main loop calls [`sleep`] where it called `cortex_m::asm::wfi`: it
+//! runs the `wfi` with interrupts masked, so the wake-up interrupt waits
+//! until the sleep is timed with the DWT cycle counter, then runs. Every
+//! cycle not slept, the main loop and all the interrupts, is load:
+//! [`cpu_load_percent`] is their share of the cycles since the last
+//! [`restart`], and [`report`] logs it and starts the next window.
+//!
+//! The cycle counter counts the core clock, which stops in Sleep mode:
+//! [`start`] sets DBG_SLEEP of DBGMCU_CR to keep it running there, at the
+//! cost of the Sleep mode savings on the core clock. A window must last
+//! less than a wrap of the counter, 25 s at 170 MHz.
+
+use core::cell::Cell;
+
+use cortex_m::interrupt::Mutex;
+use cortex_m::peripheral::{DCB, DWT};
+
+use stm32g4xx_hal as hal;
+
+use hal::stm32::DBGMCU;
+
+use crate::profile;
+
+// The start of the window, and the cycles slept since.
+static WINDOW_START: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+static SLEPT: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+/// Runs the cycle counter, in Sleep mode too, and starts the first window.
+pub fn start(dcb: &mut DCB, dwt: &mut DWT, dbgmcu: &DBGMCU) {
+    profile::start(dcb, dwt);
+    dbgmcu.cr.modify(|_, w| w.dbg_sleep().set_bit());
+    restart();
+}
+
+/// Sleeps until an interrupt, counting the cycles slept. The interrupt runs
+/// on return.
+pub fn sleep() {
+    cortex_m::interrupt::free(|cs| {
+        let start = profile::cycles();
+        cortex_m::asm::wfi();
+        let slept = SLEPT.borrow(cs);
+        slept.set(slept.get().wrapping_add(profile::cycles_since(start)));
+    });
+}
+
+/// The cycles since the window started, and those slept.
+pub fn window() -> (u32, u32) {
+    cortex_m::interrupt::free(|cs| (profile::cycles_since(WINDOW_START.borrow(cs).get()), SLEPT.borrow(cs).get()))
+}
+
+/// The busy share of the window, in percent.
+pub fn cpu_load_percent() -> u8 {
+    let (elapsed, slept) = window();
+    percent(elapsed, slept)
+}
+
+// The busy share of `elapsed` cycles, `slept` of them slept.
+fn percent(elapsed: u32, slept: u32) -> u8 {
+    if elapsed == 0 {
+        return 0;
+    }
+    (elapsed.saturating_sub(slept) as u64 * 100 / elapsed as u64) as u8
+}
+
+/// Starts a new window.
+pub fn restart() {
+    cortex_m::interrupt::free(|cs| {
+        WINDOW_START.borrow(cs).set(profile::cycles());
+        SLEPT.borrow(cs).set(0);
+    });
+}
+
+/// Logs the load of the window, and starts a new one.
+pub fn report() {
+    let (elapsed, slept) = window();
+    defmt::info!("CPU load: {}% ({} busy of {} cycles)", percent(elapsed, slept), elapsed.saturating_sub(slept), elapsed);
+    restart();
+}
@@ -0,0 +1,56 @@
+//! The logs of the blink's own modules, over defmt or semihosting.
+//!
+//! The main program, and the modules in every build, log through the
+//! [`info!`](crate::info), [`warn!`](crate::warn) and
+//! [`error!`](crate::error) macros of the crate rather than defmt's own:
+//! with the `logging` feature they are defmt's, over RTT or SWO, and with
+//! `log-semihosting` they print a line of text to the host through
+//! semihosting instead, for a plain OpenOCD or GDB setup with no defmt
+//! decoder:
+//!
+//! ```text
+//! INFO Delay Atual: 500 ms
+//! ```
+//!
+//! The format strings and arguments work either way: `{}` for the numbers
+//! and strings, `{:?}` for the crate's types, which derive both `Debug` and
+//! `defmt::Format`. A semihosting call stops the core until the debugger
+//! answers it, a few milliseconds, and without a debugger it never returns:
+//! a `log-semihosting` build only runs under one. The examples keep defmt.
+
+/// Logs at the info level.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::__log!(info, "INFO", $($arg)*) };
+}
+
+/// Logs at the warning level.
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => { $crate::__log!(warn, "WARN", $($arg)*) };
+}
+
+/// Logs at the error level.
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => { $crate::__log!(error, "ERROR", $($arg)*) };
+}
+
+// The backend of the macros, for the features of the crate they expand in:
+// this one and its programs.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log {
+    ($level:ident, $tag:literal, $($arg:tt)*) => {{
+        #[cfg(all(feature = "logging", not(feature = "log-semihosting")))]
+        defmt::$level!($($arg)*);
+        #[cfg(feature = "log-semihosting")]
+        $crate::log::print($tag, format_args!($($arg)*));
+    }};
+}
+
+/// Prints a log line to the host, `tag` before `args`.
+#[cfg(feature = "log-semihosting")]
+pub fn print(tag: &str, args: core::fmt::Arguments) {
+    cortex_m_semihosting::hprintln!("{} {}", tag, args);
+}
@@ -2,7 +2,7 @@
 //!
 //! This binary is built with `no_std` and `cortex-m-rt` and demonstrates an
 //! application with interrupts driven by both GPIO (button) and a hardware timer (TIM2).
-//! It provides information via Real Time Transfer (RTT) logs.
+//! It provides information via Real Time Transfer (RTT) logs, or SWO with the `swo` feature.
 //! Inline comments provide guidance for learning and documentation.
 
 // Deny warnings and unsafe code to simplify teaching and testing.
@@ -18,13 +18,6 @@ use hal::prelude::*;
 // Access device peripheral structures from the HAL.
 // stm32 deppends on what board do you use.
 use hal::stm32;
-use hal::gpio::{ExtiPin,
-                Floating,
-                PushPull,
-                Input,
-                Output,
-                gpioc,
-                gpioa};
 
 
 // Example HAL structure
@@ -33,6 +26,22 @@ use hal::syscfg::SysCfgExt;
 
 // Alias the HAL crate for consistent usage in the code.
 use stm32g4xx_hal as hal;
+// Clock configuration from the support library.
+use nucleo_g474re::clocks::{self, ClockConfig};
+// Blink settings, checked and shared by everything that changes them.
+use nucleo_g474re::config::AppConfig;
+// Start-up errors, reported on the LED.
+use nucleo_g474re::init::{self, InitError};
+// Which chip this is, for the log.
+#[cfg(feature = "logging")]
+use nucleo_g474re::ident;
+// LED and button pins of the board picked by the `board-*` feature.
+use nucleo_g474re::board::{self, ButtonPin, LedPin};
+// LED and button wrappers, generic over their embedded-hal 1.0 pins, and the
+// wrapper giving the HAL's pins those traits.
+use nucleo_g474re::button::{Button, Event as ButtonEvent};
+use nucleo_g474re::compat::Compat;
+use nucleo_g474re::led::Led;
 
 
 // `#[entry]` macro marks the program entry point.
@@ -40,19 +49,22 @@ use cortex_m_rt::entry;
 
 use core::panic::PanicInfo;
 
-use defmt;
-
+// The RTT channel of the logs, left out of a `minimal` build with them, and
+// replaced by the SWO with the `swo` feature, or by the crate's own RTT with
+// the `watch` feature.
+#[cfg(all(feature = "logging", not(any(feature = "swo", feature = "watch"))))]
 use defmt_rtt as _;
+// Logs over ITM and SWO, with the `swo` feature.
+#[cfg(feature = "swo")]
+use nucleo_g474re::swo;
 
 // Configuring interrupts
 use hal::stm32::TIM2;
 
-use core::cell::{Cell, RefCell};
+use core::cell::RefCell;
 
 use cortex_m::interrupt::Mutex;
 
-use hal::gpio::SignalEdge as SignalEdge;
-
 use hal::interrupt;
 
 // Configuring Timer
@@ -61,47 +73,75 @@ use hal::timer::{Timer,
                  Event,
                  CountDownTimer};
 
-// Alias for button pin
-type ButtonPin = gpioc::PC13<Input<Floating>>;
-
-// Alias for led pin
-type LedPin = gpioa::PA5<Output<PushPull>>;
-
+// Blink timing, fixed at compile time: the delay at start, the shortest delay,
+// and the divider of each press (1000 ms, halved down to 125 ms, then back).
+// Change these to change the blink; the interrupt handlers follow.
+type BlinkConfig = AppConfig<1000, 125, 2>;
 
 // Setting Mutex for interrupts
 // Create a Global Variable for the Button GPIO Peripheral that I'm going to pass around.
-static G_BUTTON: Mutex<RefCell<Option<ButtonPin>>> = Mutex::new(RefCell::new(None));
+static G_BUTTON: Mutex<RefCell<Option<Button<Compat<ButtonPin>>>>> = Mutex::new(RefCell::new(None));
 // Create a Global Variable for the Timer Peripheral that I'm going to pass around.
 static G_TIM: Mutex<RefCell<Option<CountDownTimer<TIM2>>>> = Mutex::new(RefCell::new(None));
 // Create a Global Variable for the LED GPIO Peripheral that I'm going to pass around.
-static G_LED: Mutex<RefCell<Option<LedPin>>> = Mutex::new(RefCell::new(None));
-// Create a Global Variable for the delay value that I'm going to use to manage the delay.
-static G_DELAYMS: Mutex<Cell<u32>> = Mutex::new(Cell::new(1000));
+static G_LED: Mutex<RefCell<Option<Led<Compat<LedPin>>>>> = Mutex::new(RefCell::new(None));
+// Create a Global Variable for the configuration that I'm going to use to manage the delay.
+static G_CONFIG: Mutex<RefCell<BlinkConfig>> = Mutex::new(RefCell::new(BlinkConfig::new()));
 
 
 // Minimal panic handler for `no_std` embedded programs.
 #[panic_handler]
 fn panic(_info: &PanicInfo) -> ! {
-    defmt::error!("Error type: {}", _info);
+    #[cfg(feature = "logging")]
+    nucleo_g474re::error!("Error type: {}", _info);
     loop {}
 }
 
 
-// Application entry point.
-#[entry]
-fn main() -> ! {
+// What `init` hands over to the interrupts.
+struct Board {
+    button: Button<Compat<ButtonPin>>,
+    led: Led<Compat<LedPin>>,
+    timer: CountDownTimer<TIM2>,
+}
+
+
+// Hardware initialization: every step that can fail returns its `InitError`.
+fn init() -> Result<Board, InitError> {
     // Acquire access to microcontroller peripherals.
     // `take()` returns `Some(Peripherals)` only once; it will fail if
     // peripherals have already been taken elsewhere.
-    let mut dp = stm32::Peripherals::take().expect("cannot take peripherals");
+    let mut dp = stm32::Peripherals::take().ok_or(InitError::PeripheralsTaken)?;
     // Build the Reset & Clock Control (RCC) configuration.
-    let mut rcc = dp.RCC.constrain();
-    // Hardware initialization.
+    // Constrain method sets clock as default --> HSI clock: 16mhz
+    let rcc = dp.RCC.constrain();
+    // With the `swo` feature, the SWO output first, at the 16 MHz HSI, so the
+    // warnings of the clock setup reach it too.
+    #[cfg(feature = "swo")]
+    let mut cp = cortex_m::Peripherals::take().ok_or(InitError::CorePeripheralsTaken)?;
+    #[cfg(feature = "swo")]
+    swo::init(&mut cp.DCB, &mut cp.ITM, &mut cp.TPIU, &dp.DBGMCU, &rcc.clocks);
+    // Then switch to the PLL at 170mhz, fed by the 8mhz ST-LINK MCO if it is wired
+    // to the HSE input, with the LSE crystal for the RTC. Missing sources fall back
+    // to HSI and LSI; `rcc.clocks` holds the new frequencies.
+    let config = ClockConfig::hsi().hse_bypass(clocks::STLINK_MCO).lse();
+    let (mut rcc, _sources) = clocks::freeze(rcc, config);
+    // The SWO prescaler for the new clock.
+    #[cfg(feature = "swo")]
+    swo::set_clock(&cp.ITM, &mut cp.TPIU, &rcc.clocks);
+    #[cfg(feature = "logging")]
+    nucleo_g474re::info!("{}: system clock {} Hz, PLL from {:?}", board::NAME, rcc.clocks.sys_clk.0, _sources.pll);
+    #[cfg(feature = "logging")]
+    ident::log();
+    // The blink timing follows from the clock: a wrong one is an error, not a wrong blink.
+    if rcc.clocks.sys_clk != clocks::SYSCLK_170MHZ {
+        return Err(InitError::ClockMismatch { expected: clocks::SYSCLK_170MHZ.0, actual: rcc.clocks.sys_clk.0 });
+    }
     // Split GPIOA and GPIOC for pin configuration.
     let gpioa = dp.GPIOA.split(&mut rcc);
     let gpioc = dp.GPIOC.split(&mut rcc);
     // Setting clocks
-    // Constrain method already set clock as default --> HSI clock: 16mhz
+    // The timer takes its clock from `rcc.clocks`, so the period math follows the PLL.
     let timer = Timer::new(dp.TIM2, &rcc.clocks);
 
     // Turn it into a CountDownTimer.
@@ -109,7 +149,7 @@ fn main() -> ! {
     // due to integer math limitations when converting ms to Hz (frequency = 1/period).
     // To achieve longer time spans, you should use `fugit` types or manual prescalers.
 
-    let mut count_down_timer = timer.start_count_down(1000.ms());
+    let mut count_down_timer = timer.start_count_down(BlinkConfig::INITIAL_MS.ms());
     
     // starts watching timeouts to trigger interrupts
     count_down_timer.listen(Event::TimeOut);
@@ -118,23 +158,36 @@ fn main() -> ! {
    // Configure Button Pin for Interrupts
     
     // Configure PA5 as push-pull output — LED pin on Nucleo boards.
-    let led = gpioa.pa5.into_push_pull_output();
+    // An external LED on another pin only needs another `LedPin`; `Led::active_low`
+    // for one lit with the pin low.
+    let led = Led::new(Compat(gpioa.pa5.into_push_pull_output()));
     // Configure PC13 as input. No need to be mutable, we're only reading it.
-    let mut button = gpioc.pc13.into_floating_input();
+    let button = gpioc.pc13.into_floating_input();
     
     
     // 1) Promote SYSCFG structure to HAL to be able to configure interrupts
     let mut syscfg = dp.SYSCFG.constrain();
-    // 2) Make button an interrupt source
-    button.make_interrupt_source(&mut syscfg);
-    // 3) Make button an interrupt source
-    button.trigger_on_edge(&mut dp.EXTI, SignalEdge::Rising);
-    // 4) Enable gpio interrupt for button
-    button.enable_interrupt(&mut dp.EXTI);
+    // 2) Make button an interrupt source, on both edges (B1 drives PC13 high when
+    //    pressed, low when released), and enable its gpio interrupt; `Button::active_low`
+    //    for a button to GND.
+    let button = Button::new(Compat(button), &mut syscfg, &mut dp.EXTI);
+
+    Ok(Board { button, led, timer: count_down_timer })
+}
+
+
+// Application entry point.
+#[entry]
+fn main() -> ! {
+    // Set everything up, or log why not and blink the error code on the LED.
+    let Board { button, led, timer: count_down_timer } = match init() {
+        Ok(board) => board,
+        Err(error) => init::halt(error),
+    };
 
     // Enable the external interrupt in the NVIC by passing the button interrupt number
     unsafe {
-        cortex_m::peripheral::NVIC::unmask(interrupt::EXTI15_10);
+        cortex_m::peripheral::NVIC::unmask(board::BUTTON_INTERRUPT);
         cortex_m::peripheral::NVIC::unmask(interrupt::TIM2);
     }
 
@@ -144,7 +197,8 @@ fn main() -> ! {
         G_BUTTON.borrow(cs).replace(Some(button));
         G_LED.borrow(cs).replace(Some(led));
         G_TIM.borrow(cs).replace(Some(count_down_timer));
-        defmt::info!("Delay Atual: {} ms", G_DELAYMS.borrow(cs).get());
+        #[cfg(feature = "logging")]
+        nucleo_g474re::info!("Delay Atual: {} ms", G_CONFIG.borrow(cs).borrow().period_ms());
     });
 
     loop {
@@ -159,25 +213,26 @@ fn main() -> ! {
 fn EXTI15_10() {
     // Start a Critical Section
     cortex_m::interrupt::free(|cs| {
-        // Obtain Access to Delay Global Data and Adjust Delay
-        G_DELAYMS
-            .borrow(cs)
-            .set(G_DELAYMS.borrow(cs).get()/2);
-
-        if G_DELAYMS.borrow(cs).get() < 125_u32 {
-            G_DELAYMS.borrow(cs).set(1000_u32);
+        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag.
+        // The line fires on the release too: only a press changes the delay.
+        let mut button = G_BUTTON.borrow(cs).borrow_mut();
+        let button = button.as_mut().unwrap();
+        button.clear_interrupt_pending_bit();
+        if button.event() != Some(ButtonEvent::Pressed) {
+            return;
         }
 
+        // Obtain Access to Config Global Data and Adjust Delay:
+        // one step down, back to the start below the shortest delay.
+        let delayms = G_CONFIG.borrow(cs).borrow_mut().step_period();
+
         let mut timer = G_TIM.borrow(cs).borrow_mut();
-        defmt::info!("Delay Atual: {} ms", G_DELAYMS.borrow(cs).get());
+        #[cfg(feature = "logging")]
+        nucleo_g474re::info!("Delay Atual: {} ms", delayms);
         timer
             .as_mut()
             .unwrap()
-            .start(G_DELAYMS.borrow(cs).get().ms());
-
-        // Obtain access to Global Button Peripheral and Clear Interrupt Pending Flag
-        let mut button = G_BUTTON.borrow(cs).borrow_mut();
-        button.as_mut().unwrap().clear_interrupt_pending_bit();
+            .start(delayms.ms());
     });
 }
 
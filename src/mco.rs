@@ -0,0 +1,157 @@
+//! Clock output on the MCO pin, to check the clock tree with a scope.
+//!
+//! [`Mco`] puts one of the clocks of the chip, divided by 1 to 16, on PA8
+//! (D7), where a scope or a frequency counter shows what the configuration
+//! actually produced: the HSI at 16 MHz, the PLL or the system clock at
+//! 170 MHz, or the LSE at 32.768 kHz. The source and the divider can change
+//! at any time with [`Mco::set`].
+//!
+//! A pin does not toggle cleanly much above 50 MHz: divide the system clock
+//! and the PLL by 4 or more.
+
+use stm32g4xx_hal as hal;
+
+use hal::gpio::{gpioa, Alternate, AF0};
+use hal::rcc::Clocks;
+use hal::stm32::RCC;
+use hal::time::Hertz;
+
+use crate::clocks;
+
+/// MCO pin.
+pub type McoPin = gpioa::PA8<Alternate<AF0>>;
+
+/// Clocks that can go out on MCO.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Source {
+    SysClk = 0b0001,
+    Hsi = 0b0011,
+    Hse = 0b0100,
+    /// The main PLL output, `PLLCLK` (R).
+    Pll = 0b0101,
+    Lsi = 0b0110,
+    Lse = 0b0111,
+}
+
+/// MCO divider.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Divider {
+    Div1 = 0,
+    Div2,
+    Div4,
+    Div8,
+    Div16,
+}
+
+impl Divider {
+    /// The divider for `divisor`: 1, 2, 4, 8 or 16.
+    pub fn from_divisor(divisor: u32) -> Option<Self> {
+        match divisor {
+            1 => Some(Divider::Div1),
+            2 => Some(Divider::Div2),
+            4 => Some(Divider::Div4),
+            8 => Some(Divider::Div8),
+            16 => Some(Divider::Div16),
+            _ => None,
+        }
+    }
+
+    /// The clock is divided by this.
+    pub fn divisor(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// MCO errors.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    /// The oscillator of the source is not running.
+    NotRunning,
+}
+
+/// Clock output on PA8.
+pub struct Mco {
+    pin: McoPin,
+}
+
+impl Mco {
+    /// Takes the pin, with no clock on it yet.
+    pub fn new(pin: McoPin) -> Self {
+        let mut mco = Mco { pin };
+        mco.disable();
+        mco
+    }
+
+    /// Puts `source` divided by `divider` on the pin.
+    ///
+    /// The oscillator is not started here: the HSE, LSE and LSI come from
+    /// the `clocks` module, and the PLL is off unless it runs the system.
+    pub fn set(&mut self, source: Source, divider: Divider) -> Result<(), Error> {
+        let rcc = unsafe { &(*RCC::ptr()) };
+        let running = match source {
+            Source::SysClk => true,
+            Source::Hsi => rcc.cr.read().hsirdy().bit_is_set(),
+            Source::Hse => rcc.cr.read().hserdy().bit_is_set(),
+            Source::Pll => rcc.cr.read().pllrdy().bit_is_set(),
+            Source::Lsi => rcc.csr.read().lsirdy().bit_is_set(),
+            Source::Lse => rcc.bdcr.read().lserdy().bit_is_set(),
+        };
+        if !running {
+            return Err(Error::NotRunning);
+        }
+        rcc.cfgr.modify(|_, w| unsafe { w.mcopre().bits(divider as u8).mcosel().bits(source as u8) });
+        Ok(())
+    }
+
+    /// The source on the pin, `None` while off.
+    pub fn source(&self) -> Option<Source> {
+        let rcc = unsafe { &(*RCC::ptr()) };
+        match rcc.cfgr.read().mcosel().bits() {
+            0b0001 => Some(Source::SysClk),
+            0b0011 => Some(Source::Hsi),
+            0b0100 => Some(Source::Hse),
+            0b0101 => Some(Source::Pll),
+            0b0110 => Some(Source::Lsi),
+            0b0111 => Some(Source::Lse),
+            _ => None,
+        }
+    }
+
+    /// The divider of the source.
+    pub fn divider(&self) -> Divider {
+        let rcc = unsafe { &(*RCC::ptr()) };
+        match rcc.cfgr.read().mcopre().bits() {
+            0 => Divider::Div1,
+            1 => Divider::Div2,
+            2 => Divider::Div4,
+            3 => Divider::Div8,
+            _ => Divider::Div16,
+        }
+    }
+
+    /// The frequency expected on the pin, from `clocks`, for the sources
+    /// the chip knows the frequency of: not the HSE, which is the board's.
+    pub fn frequency(&self, clocks: &Clocks) -> Option<Hertz> {
+        let source = match self.source()? {
+            Source::SysClk => clocks.sys_clk,
+            Source::Hsi => clocks::HSI,
+            Source::Hse => return None,
+            Source::Pll => clocks.pll_clk.r?,
+            Source::Lsi => Hertz(32_000),
+            Source::Lse => Hertz(32_768),
+        };
+        Some(Hertz(source.0 / self.divider().divisor()))
+    }
+
+    /// Takes the clock off the pin.
+    pub fn disable(&mut self) {
+        let rcc = unsafe { &(*RCC::ptr()) };
+        rcc.cfgr.modify(|_, w| unsafe { w.mcosel().bits(0) });
+    }
+
+    /// Takes the clock off the pin and returns it.
+    pub fn release(mut self) -> McoPin {
+        self.disable();
+        self.pin
+    }
+}
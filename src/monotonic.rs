@@ -0,0 +1,103 @@
+//! Monotonic clock: microseconds since boot, on SysTick.
+//!
+//! [`start`] runs SysTick with an interrupt every millisecond, and the
+//! SysTick handler calls [`tick`] to count them. [`now`] adds to that count
+//! the microseconds SysTick has counted down since, so timestamps have
+//! microsecond resolution for one interrupt a millisecond. It works from any
+//! interrupt: a timestamp taken while the millisecond interrupt is pending,
+//! as in a handler of the same priority, counts the millisecond already.
+//!
+//! The count is 64 bits of microseconds: it does not wrap.
+//!
+//! `tick::SysTickSource` is this clock: a program on the `tick` module's
+//! SysTick source calls neither [`start`] nor [`tick`] itself, the source
+//! and `tick_handler!` do, and [`now`] reads it all the same.
+//!
+//! ```text
+//! #[exception]
+//! fn SysTick() {
+//!     monotonic::tick();
+//! }
+//! ```
+
+use core::cell::Cell;
+
+use cortex_m::interrupt::Mutex;
+use cortex_m::peripheral::syst::SystClkSource;
+use cortex_m::peripheral::{SCB, SYST};
+
+use stm32g4xx_hal as hal;
+
+use hal::time::Hertz;
+
+// Milliseconds counted by `tick`, and SysTick cycles per microsecond.
+static MILLIS: Mutex<Cell<u64>> = Mutex::new(Cell::new(0));
+static CYCLES_PER_US: Mutex<Cell<u32>> = Mutex::new(Cell::new(1));
+
+/// A point in time, in microseconds since [`start`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, defmt::Format)]
+pub struct Instant(u64);
+
+impl Instant {
+    /// The instant of [`start`].
+    pub const ZERO: Instant = Instant(0);
+
+    /// Microseconds since [`start`].
+    pub const fn micros(self) -> u64 {
+        self.0
+    }
+
+    /// Whole milliseconds since [`start`].
+    pub const fn millis(self) -> u64 {
+        self.0 / 1000
+    }
+
+    /// Microseconds from `earlier` to `self`, zero if `earlier` is later.
+    pub const fn since(self, earlier: Instant) -> u64 {
+        self.0.saturating_sub(earlier.0)
+    }
+
+    /// Microseconds from `self` to now.
+    pub fn elapsed(self) -> u64 {
+        now().since(self)
+    }
+}
+
+/// Starts SysTick interrupting every millisecond on the core clock, `hclk`
+/// (`rcc.clocks.core_clk`), and the clock from zero.
+pub fn start(syst: &mut SYST, hclk: Hertz) {
+    cortex_m::interrupt::free(|cs| {
+        MILLIS.borrow(cs).set(0);
+        CYCLES_PER_US.borrow(cs).set((hclk.0 / 1_000_000).max(1));
+    });
+    syst.set_clock_source(SystClkSource::Core);
+    syst.set_reload(hclk.0 / 1000 - 1);
+    syst.clear_current();
+    syst.enable_counter();
+    syst.enable_interrupt();
+}
+
+/// Counts a millisecond: call from the SysTick handler.
+pub fn tick() {
+    cortex_m::interrupt::free(|cs| {
+        let millis = MILLIS.borrow(cs);
+        millis.set(millis.get() + 1);
+    });
+}
+
+/// The time now.
+pub fn now() -> Instant {
+    cortex_m::interrupt::free(|cs| {
+        let mut millis = MILLIS.borrow(cs).get();
+        let mut current = SYST::get_current();
+        // A wrap before the pending flag was read counts a millisecond `tick`
+        // has not seen yet, and the counter was read before or after it: read
+        // it again, after.
+        if SCB::is_pendst_pending() {
+            millis += 1;
+            current = SYST::get_current();
+        }
+        let counted = SYST::get_reload() - current;
+        Instant(millis * 1000 + (counted / CYCLES_PER_US.borrow(cs).get()) as u64)
+    })
+}
@@ -0,0 +1,215 @@
+//! Three-phase center-aligned PWM on TIM1, the skeleton of a motor drive.
+//!
+//! [`ThreePhasePwm`] drives the three half bridges of a brushless motor or
+//! inverter power stage, such as the X-NUCLEO-IHM08M1, from TIM1:
+//!
+//! | Phase | High side       | Low side          |
+//! |-------|-----------------|-------------------|
+//! | U     | CH1: PA8 (D7)   | CH1N: PB13 (CN10) |
+//! | V     | CH2: PA9 (D8)   | CH2N: PB14 (CN10) |
+//! | W     | CH3: PA10 (D2)  | CH3N: PB15 (CN10) |
+//!
+//! The counter runs up and down (center-aligned mode 1), so the three phases
+//! are centered on the same instant and their switching edges spread around
+//! it, and each low side turns on a dead-time after its high side turned off.
+//! The outputs idle low, bridge off, until [`ThreePhasePwm::enable`].
+//!
+//! Duty modulation hooks into the update interrupt, `TIM1_UP_TIM16`, which
+//! fires once per PWM period (or every few periods) when the counter is at
+//! zero: [`ThreePhasePwm::handle`] hands the duties of the next period to a
+//! [`Modulation`], and they are loaded together at the next update.
+//!
+//! Fault handling is not part of this skeleton; the break input of the `pwm`
+//! module is the place to start.
+
+use stm32g4xx_hal as hal;
+
+use hal::gpio::{gpioa, gpiob, Alternate, AF4, AF6};
+use hal::rcc::{Enable, Reset};
+use hal::stm32::{RCC, TIM1};
+use hal::time::Hertz;
+
+use crate::pwm::{dead_time_ticks, dtg};
+
+/// Pins of the three phases: CH1, CH2, CH3, then CH1N, CH2N, CH3N.
+pub type PhasePins = (
+    gpioa::PA8<Alternate<AF6>>,
+    gpioa::PA9<Alternate<AF6>>,
+    gpioa::PA10<Alternate<AF6>>,
+    gpiob::PB13<Alternate<AF6>>,
+    gpiob::PB14<Alternate<AF6>>,
+    gpiob::PB15<Alternate<AF4>>,
+);
+
+/// Source of the duties of the three phases, called once per update event.
+pub trait Modulation {
+    /// Returns the duties of phases U, V and W for the next period, in
+    /// `0..=max_duty` timer ticks.
+    fn duties(&mut self, max_duty: u16) -> [u16; 3];
+}
+
+/// Three complementary pairs on TIM1, center aligned.
+pub struct ThreePhasePwm {
+    tim: TIM1,
+    pins: PhasePins,
+    clock: Hertz,
+}
+
+impl ThreePhasePwm {
+    /// Starts TIM1 at `frequency` with a dead-time of `dead_time_ns`, the
+    /// three duties at 50% and the outputs off.
+    ///
+    /// `clock` is the TIM1 kernel clock, `rcc.clocks.apb2_tim_clk`.
+    pub fn new(tim: TIM1, pins: PhasePins, clock: Hertz, frequency: Hertz, dead_time_ns: u32) -> Self {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            TIM1::enable(rcc);
+            TIM1::reset(rcc);
+        }
+
+        // Up and down: the counter takes two periods of the prescaled clock
+        // per tick of the ARR.
+        let ticks = clock.0 / frequency.0.max(1) / 2;
+        let psc = (ticks - 1) / (1 << 16);
+        tim.psc.write(|w| unsafe { w.psc().bits(psc as u16) });
+        tim.arr.write(|w| unsafe { w.bits(ticks / (psc + 1)) });
+
+        let dead_time = (dead_time_ns as u64 * clock.0 as u64 / 1_000_000_000) as u32;
+        // Off-state outputs driven at their idle level instead of floating.
+        tim.bdtr.write(|w| unsafe { w.dtg().bits(dtg(dead_time)).ossr().set_bit().ossi().set_bit() });
+
+        // PWM mode 1 with preload on the three channels, all active high.
+        tim.ccmr1_output().write(|w| {
+            w.oc1m()
+                .pwm_mode1()
+                .oc1pe()
+                .set_bit()
+                .oc2m()
+                .pwm_mode1()
+                .oc2pe()
+                .set_bit()
+        });
+        tim.ccmr2_output().write(|w| w.oc3m().pwm_mode1().oc3pe().set_bit());
+        tim.ccer.write(|w| {
+            w.cc1e()
+                .set_bit()
+                .cc1ne()
+                .set_bit()
+                .cc2e()
+                .set_bit()
+                .cc2ne()
+                .set_bit()
+                .cc3e()
+                .set_bit()
+                .cc3ne()
+                .set_bit()
+        });
+
+        let mut pwm = ThreePhasePwm { tim, pins, clock };
+        let half = pwm.max_duty() / 2;
+        pwm.set_duties([half; 3]);
+        pwm.set_update_period(1);
+
+        // Center-aligned mode 1, then load the preloaded registers without an
+        // update interrupt.
+        pwm.tim.cr1.write(|w| unsafe { w.cms().bits(0b01).arpe().set_bit().urs().set_bit() });
+        pwm.tim.egr.write(|w| w.ug().set_bit());
+        pwm.tim.cr1.modify(|_, w| w.urs().clear_bit().cen().set_bit());
+        pwm
+    }
+
+    /// The frequency of the outputs, as the prescaler and period round it.
+    pub fn frequency(&self) -> Hertz {
+        let psc = self.tim.psc.read().bits() + 1;
+        let arr = self.tim.arr.read().bits();
+        Hertz(self.clock.0 / psc / arr / 2)
+    }
+
+    /// The dead-time, in nanoseconds, as the timer clock rounds it.
+    pub fn dead_time_ns(&self) -> u32 {
+        let ticks = dead_time_ticks(self.tim.bdtr.read().dtg().bits()) as u64;
+        (ticks * 1_000_000_000 / self.clock.0 as u64) as u32
+    }
+
+    /// The duty of a full period.
+    pub fn max_duty(&self) -> u16 {
+        self.tim.arr.read().bits() as u16
+    }
+
+    /// The duties of phases U, V and W, in timer ticks.
+    pub fn duties(&self) -> [u16; 3] {
+        [0, 1, 2].map(|channel| self.tim.ccr[channel].read().bits() as u16)
+    }
+
+    /// Sets the duties of phases U, V and W from the next update event.
+    pub fn set_duties(&mut self, duties: [u16; 3]) {
+        let max_duty = self.max_duty();
+        for (ccr, duty) in self.tim.ccr.iter().zip(duties) {
+            ccr.write(|w| unsafe { w.bits(duty.min(max_duty) as u32) });
+        }
+    }
+
+    /// Generates the update event, and loads new duties, once every
+    /// `periods` PWM periods, at most 128.
+    pub fn set_update_period(&mut self, periods: u8) {
+        // Two repetitions per period: the counter under- and overflows in each.
+        let periods = periods.clamp(1, 128) as u32;
+        self.tim.rcr.write(|w| unsafe { w.bits(2 * periods - 1) });
+    }
+
+    /// Turns the main output enable on: the six outputs follow the timer.
+    pub fn enable(&mut self) {
+        self.tim.bdtr.modify(|_, w| w.moe().set_bit());
+    }
+
+    /// Turns the main output enable off: the six outputs go low, bridges off.
+    pub fn disable(&mut self) {
+        self.tim.bdtr.modify(|_, w| w.moe().clear_bit());
+    }
+
+    /// Returns `true` while the outputs follow the timer.
+    pub fn is_enabled(&self) -> bool {
+        self.tim.bdtr.read().moe().bit_is_set()
+    }
+
+    /// Starts listening for the update interrupt.
+    ///
+    /// Note, you will also have to unmask `TIM1_UP_TIM16` in the NVIC.
+    pub fn listen(&mut self) {
+        self.tim.dier.modify(|_, w| w.uie().set_bit());
+    }
+
+    /// Stops listening for the update interrupt.
+    pub fn unlisten(&mut self) {
+        self.tim.dier.modify(|_, w| w.uie().clear_bit());
+    }
+
+    /// Call from `TIM1_UP_TIM16`: on an update event, clears it and loads the
+    /// duties of the next period from `modulation`.
+    ///
+    /// Returns `false` if no update event was pending.
+    pub fn handle<M: Modulation>(&mut self, modulation: &mut M) -> bool {
+        if self.tim.sr.read().uif().bit_is_clear() {
+            return false;
+        }
+        // Flags are cleared by writing 0, ones are ignored.
+        self.tim.sr.write(|w| unsafe { w.bits(!0).uif().clear_bit() });
+        let duties = modulation.duties(self.max_duty());
+        self.set_duties(duties);
+        true
+    }
+
+    /// Stops TIM1 and returns the peripheral and the pins.
+    pub fn release(mut self) -> (TIM1, PhasePins) {
+        self.disable();
+        self.unlisten();
+        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            TIM1::disable(rcc);
+        }
+        (self.tim, self.pins)
+    }
+}
@@ -0,0 +1,196 @@
+//! Internal op-amps in follower and PGA modes, feeding the ADC.
+//!
+//! `stm32g4xx-hal` configures the op-amps as followers or open-loop
+//! amplifiers driving their output pin. This module adds the programmable
+//! gain amplifier (PGA) mode, where the feedback network is internal and the
+//! gain is selected by software, and routes the output straight to an ADC
+//! channel so no pin or external component is needed. Each op-amp type
+//! implements the HAL's ADC `Channel` trait, so it can be passed to
+//! `Adc::convert` or `Adc::configure_channel` like an analog pin.
+//!
+//! | Op-amp | Non-inverting inputs | ADC channel            |
+//! |--------|----------------------|------------------------|
+//! | OPAMP1 | PA1, PA3, PA7        | ADC1 ch13              |
+//! | OPAMP2 | PA7, PB14, PB0       | ADC2 ch16              |
+//! | OPAMP3 | PB0, PB13, PA1       | ADC2 ch18, ADC3 ch13   |
+//! | OPAMP4 | PB13, PB11           | ADC5 ch5               |
+//! | OPAMP5 | PB14, PC3            | ADC5 ch3               |
+//! | OPAMP6 | PB12, PB13           | ADC4 ch17              |
+//!
+//! The G431 has OPAMP1 to OPAMP3 only, and OPAMP3 on ADC2 only.
+
+use stm32g4xx_hal as hal;
+
+use hal::gpio::{gpioa, gpiob, Analog};
+#[cfg(not(feature = "board-g431rb"))]
+use hal::gpio::gpioc;
+use hal::hal::adc::Channel;
+use hal::opamp::{opamp1, opamp2, opamp3};
+#[cfg(not(feature = "board-g431rb"))]
+use hal::opamp::{opamp4, opamp5, opamp6};
+use hal::stm32::{self, OPAMP};
+
+/// Non-inverting gain of the PGA.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Gain {
+    X2 = 0,
+    X4 = 1,
+    X8 = 2,
+    X16 = 3,
+    X32 = 4,
+    X64 = 5,
+}
+
+impl Gain {
+    /// Returns the multiplication factor.
+    pub fn factor(self) -> u32 {
+        2 << (self as u32)
+    }
+
+    /// Returns the next higher gain, wrapping from x64 back to x2.
+    pub fn next(self) -> Self {
+        match self {
+            Gain::X2 => Gain::X4,
+            Gain::X4 => Gain::X8,
+            Gain::X8 => Gain::X16,
+            Gain::X16 => Gain::X32,
+            Gain::X32 => Gain::X64,
+            Gain::X64 => Gain::X2,
+        }
+    }
+}
+
+/// Operating mode of an op-amp.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Mode {
+    /// Unity gain buffer: output follows the input.
+    Follower,
+    /// Programmable gain amplifier with the internal resistor network.
+    Pga(Gain),
+}
+
+impl Mode {
+    /// Returns the voltage gain of the mode.
+    pub fn gain(self) -> u32 {
+        match self {
+            Mode::Follower => 1,
+            Mode::Pga(gain) => gain.factor(),
+        }
+    }
+}
+
+/// Pins that can be connected to the non-inverting input of `OPAMP`.
+///
+/// `OPAMP` is the HAL's `Disabled` state type of the op-amp.
+pub trait NonInverting<OPAMP> {
+    /// VP_SEL value selecting the pin.
+    const VP_SEL: u8;
+}
+
+fn regs() -> &'static stm32::opamp::RegisterBlock {
+    // NOTE(unsafe) each op-amp only writes its own CSR.
+    unsafe { &*OPAMP::ptr() }
+}
+
+macro_rules! opamps {
+    ($($Opamp:ident: ($opamp:ident, $csr:ident, [$($PIN:ty: $vp:literal),+], [$($ADC:ident: $chan:literal),+]),)+) => {
+        $(
+            #[doc = concat!("`", stringify!($opamp), "` with its output routed to the ADC.")]
+            pub struct $Opamp<INPUT> {
+                input: INPUT,
+                mode: Mode,
+            }
+
+            impl<INPUT> $Opamp<INPUT>
+            where
+                INPUT: NonInverting<$opamp::Disabled>,
+            {
+                /// Enables the op-amp on `input`.
+                ///
+                /// The disabled op-amp comes from `dp.OPAMP.split(&mut rcc)`, which
+                /// also enables the clock of the op-amp block.
+                pub fn new(_opamp: $opamp::Disabled, input: INPUT, mode: Mode) -> Self {
+                    let mut opamp = $Opamp { input, mode };
+                    opamp.set_mode(mode);
+                    opamp
+                }
+
+                /// Switches between follower and PGA, or changes the gain.
+                pub fn set_mode(&mut self, mode: Mode) {
+                    self.mode = mode;
+                    regs().$csr.write(|w| {
+                        let w = match mode {
+                            Mode::Follower => w.vm_sel().output(),
+                            Mode::Pga(gain) => unsafe { w.vm_sel().pga().pga_gain().bits(gain as u8) },
+                        };
+                        w.vp_sel()
+                            .bits(INPUT::VP_SEL)
+                            .opaintoen()
+                            .adcchannel()
+                            .opaen()
+                            .enabled()
+                    });
+                }
+
+                /// Returns the current mode.
+                pub fn mode(&self) -> Mode {
+                    self.mode
+                }
+
+                /// Disables the op-amp and returns the resources it held.
+                pub fn disable(self) -> ($opamp::Disabled, INPUT) {
+                    regs().$csr.reset();
+                    ($opamp::Disabled, self.input)
+                }
+            }
+
+            $(
+                impl NonInverting<$opamp::Disabled> for $PIN {
+                    const VP_SEL: u8 = $vp;
+                }
+            )+
+
+            $(
+                impl<INPUT> Channel<stm32::$ADC> for $Opamp<INPUT> {
+                    type ID = u8;
+                    fn channel() -> u8 {
+                        $chan
+                    }
+                }
+            )+
+        )+
+    };
+}
+
+opamps! {
+    Opamp1: (opamp1, opamp1_csr,
+        [gpioa::PA1<Analog>: 0, gpioa::PA3<Analog>: 1, gpioa::PA7<Analog>: 2],
+        [ADC1: 13]),
+    Opamp2: (opamp2, opamp2_csr,
+        [gpioa::PA7<Analog>: 0, gpiob::PB14<Analog>: 1, gpiob::PB0<Analog>: 2],
+        [ADC2: 16]),
+}
+
+// The G431 stops at OPAMP3, and has no ADC3.
+#[cfg(feature = "board-g431rb")]
+opamps! {
+    Opamp3: (opamp3, opamp3_csr,
+        [gpiob::PB0<Analog>: 0, gpiob::PB13<Analog>: 1, gpioa::PA1<Analog>: 2],
+        [ADC2: 18]),
+}
+
+#[cfg(not(feature = "board-g431rb"))]
+opamps! {
+    Opamp3: (opamp3, opamp3_csr,
+        [gpiob::PB0<Analog>: 0, gpiob::PB13<Analog>: 1, gpioa::PA1<Analog>: 2],
+        [ADC2: 18, ADC3: 13]),
+    Opamp4: (opamp4, opamp4_csr,
+        [gpiob::PB13<Analog>: 0, gpiob::PB11<Analog>: 2],
+        [ADC5: 5]),
+    Opamp5: (opamp5, opamp5_csr,
+        [gpiob::PB14<Analog>: 0, gpioc::PC3<Analog>: 2],
+        [ADC5: 3]),
+    Opamp6: (opamp6, opamp6_csr,
+        [gpiob::PB12<Analog>: 0, gpiob::PB13<Analog>: 2],
+        [ADC4: 17]),
+}
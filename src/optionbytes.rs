@@ -0,0 +1,232 @@
+//! The option bytes: read protection, brown-out level, boot and bank setup.
+//!
+//! The option bytes live in flash and configure the chip at each reset.
+//! [`read`] reports the fields of FLASH_OPTR the examples care about, and
+//! [`write`] changes some of them, those a mistake in does not lock the
+//! board up:
+//!
+//! | Field                       | Read | Written by [`write`]                    |
+//! |-----------------------------|------|-----------------------------------------|
+//! | RDP, read protection        | yes  | never: level 2 cannot be undone         |
+//! | BOR_LEV, brown-out level    | yes  | yes                                     |
+//! | nSWBOOT0, nBOOT0, nBOOT1    | yes  | yes, if the chip still boots main flash |
+//! | BFB2, boot from bank 2      | yes  | yes, in dual-bank mode                  |
+//! | DBANK, dual-bank mode       | yes  | never: it moves every page of the flash |
+//!
+//! A write leaves the chip running with the old values: [`reload`] loads
+//! the new ones, which resets the chip, and so do a power cycle and a
+//! return from Standby. With read protection at level 2 nothing can be
+//! written at all.
+//!
+//! The flash unlocking and the wait for the end of an operation are shared
+//! with the other flash writers of the crate, the `ota` module.
+
+use stm32g4xx_hal as hal;
+
+use hal::stm32::FLASH;
+
+// Flash unlock keys, for the flash and for the option bytes.
+const KEYS: [u32; 2] = [0x4567_0123, 0xCDEF_89AB];
+const OPTION_KEYS: [u32; 2] = [0x0819_2A3B, 0x4C5D_6E7F];
+
+// FLASH_SR: busy, and the error bits, cleared by writing them back.
+const SR_BSY: u32 = 1 << 16;
+const SR_ERRORS: u32 = 0xC3FA;
+
+// Fields of FLASH_OPTR.
+const OPTR_RDP: u32 = 0xFF;
+const OPTR_BOR_LEV_SHIFT: u32 = 8;
+const OPTR_BOR_LEV: u32 = 0b111 << OPTR_BOR_LEV_SHIFT;
+const OPTR_BFB2: u32 = 1 << 20;
+const OPTR_DBANK: u32 = 1 << 22;
+const OPTR_NBOOT1: u32 = 1 << 23;
+const OPTR_NSWBOOT0: u32 = 1 << 26;
+const OPTR_NBOOT0: u32 = 1 << 27;
+
+/// Errors of an option byte write.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    /// Read protection is at level 2: the option bytes are frozen.
+    Frozen,
+    /// BFB2 only means something in dual-bank mode.
+    SingleBank,
+    /// The boot setup asked for would never boot the main flash.
+    NoFlashBoot,
+    /// The flash reported an error, its FLASH_SR bits.
+    Flash(u32),
+}
+
+/// The read protection level of RDP.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum ReadProtection {
+    /// No protection: RDP is 0xAA.
+    Level0,
+    /// The flash unreadable by the debugger: any other value.
+    Level1,
+    /// The debugger disabled for good: RDP is 0xCC.
+    Level2,
+}
+
+/// The brown-out reset threshold, on a rising supply.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum BorLevel {
+    /// About 1.7 V.
+    Level0 = 0,
+    /// About 2.0 V.
+    Level1 = 1,
+    /// About 2.2 V.
+    Level2 = 2,
+    /// About 2.5 V.
+    Level3 = 3,
+    /// About 2.8 V.
+    Level4 = 4,
+}
+
+impl BorLevel {
+    /// The level of a BOR_LEV value, the reserved ones taken as the highest.
+    pub fn from_bits(bits: u8) -> Self {
+        match bits {
+            0 => BorLevel::Level0,
+            1 => BorLevel::Level1,
+            2 => BorLevel::Level2,
+            3 => BorLevel::Level3,
+            _ => BorLevel::Level4,
+        }
+    }
+}
+
+/// The option bytes in use.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct OptionBytes {
+    pub read_protection: ReadProtection,
+    pub bor_level: BorLevel,
+    /// BOOT0 taken from the PB8-BOOT0 pin if set, from `n_boot0` if clear.
+    pub n_swboot0: bool,
+    /// BOOT0 inverted, when `n_swboot0` is clear.
+    pub n_boot0: bool,
+    /// With BOOT0 high: system memory if set, SRAM if clear.
+    pub n_boot1: bool,
+    /// Boot from bank 2, in dual-bank mode.
+    pub bfb2: bool,
+    /// Dual-bank mode.
+    pub dual_bank: bool,
+    /// The whole of FLASH_OPTR.
+    pub raw: u32,
+}
+
+impl OptionBytes {
+    fn from_bits(raw: u32) -> Self {
+        OptionBytes {
+            read_protection: match raw & OPTR_RDP {
+                0xAA => ReadProtection::Level0,
+                0xCC => ReadProtection::Level2,
+                _ => ReadProtection::Level1,
+            },
+            bor_level: BorLevel::from_bits(((raw & OPTR_BOR_LEV) >> OPTR_BOR_LEV_SHIFT) as u8),
+            n_swboot0: raw & OPTR_NSWBOOT0 != 0,
+            n_boot0: raw & OPTR_NBOOT0 != 0,
+            n_boot1: raw & OPTR_NBOOT1 != 0,
+            bfb2: raw & OPTR_BFB2 != 0,
+            dual_bank: raw & OPTR_DBANK != 0,
+            raw,
+        }
+    }
+
+    /// Returns `true` if the chip can boot from the main flash: BOOT0 from
+    /// the pin, or nBOOT0 set.
+    pub fn boots_main_flash(&self) -> bool {
+        self.n_swboot0 || self.n_boot0
+    }
+}
+
+/// The fields [`write`] changes, `None` for the ones it leaves.
+#[derive(Clone, Copy, Debug, Default, PartialEq, defmt::Format)]
+pub struct Changes {
+    pub bor_level: Option<BorLevel>,
+    pub n_swboot0: Option<bool>,
+    pub n_boot0: Option<bool>,
+    pub n_boot1: Option<bool>,
+    pub bfb2: Option<bool>,
+}
+
+/// Reads the option bytes in use.
+pub fn read() -> OptionBytes {
+    // NOTE(unsafe) atomic read with no side effects
+    OptionBytes::from_bits(unsafe { (*FLASH::ptr()).optr.read().bits() })
+}
+
+/// Writes `changes` into the option bytes, for the next [`reload`] or
+/// reset. Nothing is written if a change is refused, or if there is none.
+pub fn write(flash: &mut FLASH, changes: Changes) -> Result<(), Error> {
+    let current = OptionBytes::from_bits(flash.optr.read().bits());
+    if current.read_protection == ReadProtection::Level2 {
+        return Err(Error::Frozen);
+    }
+    if changes.bfb2.is_some() && !current.dual_bank {
+        return Err(Error::SingleBank);
+    }
+
+    let mut raw = current.raw;
+    let mut set = |bit: u32, value: Option<bool>| {
+        if let Some(value) = value {
+            raw = if value { raw | bit } else { raw & !bit };
+        }
+    };
+    set(OPTR_NSWBOOT0, changes.n_swboot0);
+    set(OPTR_NBOOT0, changes.n_boot0);
+    set(OPTR_NBOOT1, changes.n_boot1);
+    set(OPTR_BFB2, changes.bfb2);
+    if let Some(level) = changes.bor_level {
+        raw = raw & !OPTR_BOR_LEV | (level as u32) << OPTR_BOR_LEV_SHIFT;
+    }
+    if !OptionBytes::from_bits(raw).boots_main_flash() {
+        return Err(Error::NoFlashBoot);
+    }
+    if raw == current.raw {
+        return Ok(());
+    }
+
+    unlock(flash);
+    if flash.cr.read().optlock().bit_is_set() {
+        flash.optkeyr.write(|w| unsafe { w.bits(OPTION_KEYS[0]) });
+        flash.optkeyr.write(|w| unsafe { w.bits(OPTION_KEYS[1]) });
+    }
+    let result = wait(flash).and_then(|()| {
+        flash.optr.write(|w| unsafe { w.bits(raw) });
+        flash.cr.modify(|_, w| w.optstrt().set_bit());
+        wait(flash)
+    });
+    // Locking the flash locks the option bytes too.
+    flash.cr.modify(|_, w| w.lock().set_bit());
+    result.map_err(Error::Flash)
+}
+
+/// Loads the option bytes written, which resets the chip.
+pub fn reload(flash: FLASH) -> ! {
+    cortex_m::interrupt::disable();
+    unlock(&flash);
+    if flash.cr.read().optlock().bit_is_set() {
+        flash.optkeyr.write(|w| unsafe { w.bits(OPTION_KEYS[0]) });
+        flash.optkeyr.write(|w| unsafe { w.bits(OPTION_KEYS[1]) });
+    }
+    flash.cr.modify(|_, w| w.obl_launch().set_bit());
+    loop {
+        cortex_m::asm::nop();
+    }
+}
+
+/// Unlocks the flash for erasing and programming.
+pub(crate) fn unlock(flash: &FLASH) {
+    if flash.cr.read().lock().bit_is_set() {
+        flash.keyr.write(|w| unsafe { w.bits(KEYS[0]) });
+        flash.keyr.write(|w| unsafe { w.bits(KEYS[1]) });
+    }
+}
+
+/// Waits for the flash, then returns and clears its error bits.
+pub(crate) fn wait(flash: &FLASH) -> Result<(), u32> {
+    while flash.sr.read().bits() & SR_BSY != 0 {}
+    let errors = flash.sr.read().bits() & SR_ERRORS;
+    flash.sr.write(|w| unsafe { w.bits(errors | 1) });
+    if errors != 0 { Err(errors) } else { Ok(()) }
+}
@@ -0,0 +1,267 @@
+//! Firmware updates into the inactive bank of the dual-bank flash.
+//!
+//! In dual-bank mode (the `DBANK` option bit, set out of the factory) the
+//! 512 KiB of the G474 are two banks of 256 KiB. The bank the program runs
+//! from is mapped at 0x0800_0000 and the other one at [`INACTIVE_BANK`], so
+//! the other bank can be erased and written while the program runs, and an
+//! image built for 0x0800_0000 runs from either once its bank is booted:
+//!
+//! 1. [`Updater::feed`] takes the image, a byte at a time from any
+//!    transport: a [`Header`], then the image in chunks of [`CHUNK`] bytes,
+//!    each erased and programmed into the inactive bank as it completes,
+//! 2. the last chunk written, the CRC-32 (ISO-HDLC, zip's) of the image in
+//!    the inactive bank, read back from the flash, must match the header's,
+//! 3. [`Updater::swap_and_reset`] points the `BFB2` option bit at the
+//!    inactive bank and reloads the option bytes, which resets the chip into
+//!    the new image; the old one stays in the other bank, for the next
+//!    update or to swap back to.
+//!
+//! The transport, UART or CAN, answers each [`Event`] but
+//! [`Event::Pending`] with [`ACK`], and an [`Error`] with [`NACK`]; the
+//! sender waits for each answer before the next header or chunk, so nothing
+//! arrives while a chunk is written. Over CAN, the header fills the 8 bytes
+//! of one frame, and a chunk the payloads of 256 frames:
+//!
+//! | Step       | Sender sends                                | Answer       |
+//! |------------|---------------------------------------------|--------------|
+//! | Header     | image size and CRC-32, u32s, little endian  | `ACK`        |
+//! | Each chunk | [`CHUNK`] bytes, the last one shorter       | `ACK`        |
+//! | Last chunk | the CRC matches the header's                | `ACK`, reset |
+//!
+//! The update takes no more than a bank: an image must fit in 256 KiB to
+//! be updated this way, even though the linker script gives it 512 KiB.
+
+use stm32g4xx_hal as hal;
+
+use hal::stm32::{FLASH, SYSCFG};
+
+use crate::crc::{Config, Crc};
+use crate::optionbytes::{self, Changes, unlock, wait};
+
+/// Where the inactive bank is mapped, whichever bank it is.
+pub const INACTIVE_BANK: u32 = 0x0804_0000;
+
+/// The size of a bank in dual-bank mode, in bytes.
+pub const BANK_SIZE: u32 = 256 * 1024;
+
+/// The bytes of a chunk: a page of the flash in dual-bank mode.
+pub const CHUNK: usize = 2048;
+
+/// The answer to an event, as the ST bootloader does.
+pub const ACK: u8 = 0x79;
+
+/// The answer to an error.
+pub const NACK: u8 = 0x1F;
+
+// The bank bit of FLASH_CR, which the register API lacks.
+const CR_BKER: u32 = 1 << 11;
+
+/// Errors of an update.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    /// The flash is in single-bank mode: `DBANK` is clear.
+    SingleBank,
+    /// The header gives no image, or one larger than a bank.
+    InvalidSize(u32),
+    /// The flash reported an error, its FLASH_SR bits.
+    Flash(u32),
+    /// The image in the inactive bank does not have the header's CRC.
+    Crc { expected: u32, actual: u32 },
+}
+
+/// What the header of an update gives.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct Header {
+    /// Size of the image, in bytes.
+    pub size: u32,
+    /// CRC-32 (ISO-HDLC) of the image.
+    pub crc: u32,
+}
+
+impl Header {
+    /// The bytes the header takes: one CAN frame.
+    pub const SIZE: usize = 8;
+
+    /// Reads a header from its bytes.
+    pub fn from_bytes(bytes: [u8; Header::SIZE]) -> Result<Self, Error> {
+        let size = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let crc = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        if size == 0 || size > BANK_SIZE {
+            return Err(Error::InvalidSize(size));
+        }
+        Ok(Header { size, crc })
+    }
+}
+
+/// What the last byte fed did.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Event {
+    /// Nothing for the sender yet.
+    Pending,
+    /// The header is taken: the chunks can come.
+    Started(Header),
+    /// A chunk is written, `written` bytes of the image so far.
+    Written { written: u32 },
+    /// The image is written and its CRC matches: ready for [`Updater::swap_and_reset`].
+    Verified,
+}
+
+/// An update into the inactive bank, fed a byte at a time.
+pub struct Updater {
+    flash: FLASH,
+    crc: Crc,
+    header: Option<Header>,
+    buffer: [u8; CHUNK],
+    filled: usize,
+    written: u32,
+}
+
+impl Updater {
+    /// Takes the flash and the CRC unit, if the flash is in dual-bank mode.
+    pub fn new(flash: FLASH, crc: Crc) -> Result<Self, Error> {
+        if !optionbytes::read().dual_bank {
+            return Err(Error::SingleBank);
+        }
+        Ok(Updater { flash, crc, header: None, buffer: [0; CHUNK], filled: 0, written: 0 })
+    }
+
+    /// Takes the next byte of an update. After an error, the next byte
+    /// starts a header again.
+    pub fn feed(&mut self, byte: u8) -> Result<Event, Error> {
+        self.buffer[self.filled] = byte;
+        self.filled += 1;
+
+        let Some(header) = self.header else {
+            if self.filled < Header::SIZE {
+                return Ok(Event::Pending);
+            }
+            let mut bytes = [0; Header::SIZE];
+            bytes.copy_from_slice(&self.buffer[..Header::SIZE]);
+            self.filled = 0;
+            let header = Header::from_bytes(bytes)?;
+            self.header = Some(header);
+            self.written = 0;
+            return Ok(Event::Started(header));
+        };
+
+        let remaining = (header.size - self.written) as usize;
+        if self.filled < CHUNK.min(remaining) {
+            return Ok(Event::Pending);
+        }
+        let result = self.write_chunk();
+        if result.is_err() || self.written == header.size {
+            self.header = None;
+        }
+        result
+    }
+
+    /// Drops the update under way: the next byte starts a header.
+    pub fn abort(&mut self) {
+        self.header = None;
+        self.filled = 0;
+    }
+
+    /// Points the boot at the inactive bank and resets into it. Only after
+    /// [`Event::Verified`]: nothing checks the bank again. If the option
+    /// bytes cannot be written, it logs why and resets into the same bank.
+    pub fn swap_and_reset(self) -> ! {
+        defmt::info!("Booting the updated bank");
+        // Off until the reset: no interrupt touches the flash between the
+        // unlock, the OPTR write and the launch.
+        cortex_m::interrupt::disable();
+        let mut flash = self.flash;
+        // BFB2 set boots bank 2: the inactive bank is bank 2 while bank 1 runs.
+        let changes = Changes { bfb2: Some(!running_from_bank2()), ..Changes::default() };
+        if let Err(error) = optionbytes::write(&mut flash, changes) {
+            defmt::error!("Bank swap failed: {}", error);
+        }
+        optionbytes::reload(flash)
+    }
+
+    /// Returns the flash and the CRC unit.
+    pub fn release(self) -> (FLASH, Crc) {
+        (self.flash, self.crc)
+    }
+
+    // Erases the next page of the inactive bank and programs the buffer,
+    // the CRC checked after the last one.
+    fn write_chunk(&mut self) -> Result<Event, Error> {
+        let header = self.header.unwrap();
+        let chunk = self.filled;
+        self.filled = 0;
+        // The program writes double words: the last chunk is padded.
+        self.buffer[chunk..chunk.next_multiple_of(8)].fill(0xFF);
+
+        unlock(&self.flash);
+        let page = self.written / CHUNK as u32;
+        let result = erase_page(&self.flash, page).and_then(|()| {
+            let address = INACTIVE_BANK + self.written;
+            program(&self.flash, address, &self.buffer[..chunk.next_multiple_of(8)])
+        });
+        self.flash.cr.modify(|_, w| w.lock().set_bit());
+        result?;
+
+        self.written += chunk as u32;
+        if self.written < header.size {
+            return Ok(Event::Written { written: self.written });
+        }
+
+        flush_data_cache(&self.flash);
+        // NOTE(unsafe) the inactive bank is mapped and just written.
+        let image = unsafe { core::slice::from_raw_parts(INACTIVE_BANK as *const u8, header.size as usize) };
+        let config = self.crc.config();
+        self.crc.set_config(Config::CRC32);
+        let actual = self.crc.checksum(image);
+        self.crc.set_config(config);
+        if actual != header.crc {
+            return Err(Error::Crc { expected: header.crc, actual });
+        }
+        Ok(Event::Verified)
+    }
+}
+
+/// Returns `true` if the program runs from bank 2, mapped at 0x0800_0000.
+pub fn running_from_bank2() -> bool {
+    // NOTE(unsafe) atomic read with no side effects
+    unsafe { (*SYSCFG::ptr()).memrmp.read().fb_mode().bit_is_set() }
+}
+
+// Erases page `page` of the inactive bank.
+fn erase_page(flash: &FLASH, page: u32) -> Result<(), Error> {
+    wait(flash).map_err(Error::Flash)?;
+    let bank = if running_from_bank2() { 0 } else { CR_BKER };
+    flash.cr.modify(|r, w| unsafe { w.bits(r.bits() & !CR_BKER | bank).per().set_bit().pnb().bits(page as u8) });
+    flash.cr.modify(|_, w| w.strt().set_bit());
+    let result = wait(flash).map_err(Error::Flash);
+    flash.cr.modify(|r, w| unsafe { w.bits(r.bits() & !CR_BKER).per().clear_bit() });
+    result
+}
+
+// Programs `data`, whole double words, from `address` on.
+fn program(flash: &FLASH, address: u32, data: &[u8]) -> Result<(), Error> {
+    wait(flash).map_err(Error::Flash)?;
+    flash.cr.modify(|_, w| w.pg().set_bit());
+    let mut result = Ok(());
+    for (offset, double) in (0..).step_by(8).zip(data.chunks_exact(8)) {
+        let target = (address + offset) as *mut u32;
+        // NOTE(unsafe) an erased double word of the inactive bank, written
+        // whole: first word, then second, as the flash interface requires.
+        unsafe {
+            target.write_volatile(u32::from_le_bytes([double[0], double[1], double[2], double[3]]));
+            target.add(1).write_volatile(u32::from_le_bytes([double[4], double[5], double[6], double[7]]));
+        }
+        result = wait(flash).map_err(Error::Flash);
+        if result.is_err() {
+            break;
+        }
+    }
+    flash.cr.modify(|_, w| w.pg().clear_bit());
+    result
+}
+
+// Drops the data cache lines of the old contents of the bank.
+fn flush_data_cache(flash: &FLASH) {
+    flash.acr.modify(|_, w| w.dcen().clear_bit());
+    flash.acr.modify(|_, w| w.dcrst().set_bit());
+    flash.acr.modify(|_, w| w.dcrst().clear_bit().dcen().set_bit());
+}
@@ -0,0 +1,261 @@
+//! Voltage scaling, low-power run and stop modes of the PWR controller.
+//!
+//! The main regulator of the G474 has three settings, each with a top
+//! clock speed, and a low-power regulator for very slow clocks:
+//!
+//! | Mode               | HCLK up to | Regulator            |
+//! |--------------------|------------|----------------------|
+//! | Range 1 boost      | 170 MHz    | main, 1.28 V         |
+//! | Range 1            | 150 MHz    | main, 1.2 V          |
+//! | Range 2            | 26 MHz     | main, 1.0 V          |
+//! | Low-power run      | 2 MHz      | low-power            |
+//!
+//! Slower settings use less current for the same work. [`set_voltage_range`]
+//! and [`enter_low_power_run`] refuse a setting the clock is too fast for:
+//! lower the clock first with `clocks::set_sysclk`, and raise the range
+//! before raising the clock. Low-power run needs range 2's clock limits too;
+//! `Sysclk::Hsi2` gives the 2 MHz it asks for.
+//!
+//! # Stop modes
+//!
+//! [`enter_stop`] stops every clock but the low-speed ones until an EXTI
+//! line wakes the chip: the button, an RTC alarm, a comparator. The core
+//! resumes after the call, from the 16 MHz HSI with the PLL off, and the
+//! register and RAM contents kept. Stop 0 keeps the main regulator on for a
+//! faster wakeup, Stop 1 hands over to the low-power one for a lower draw.
+//! Timers stop with their clocks: a TIM2 blink waits for the wakeup.
+//!
+//! [`enter_standby`] goes further: the regulator off, the RAM and registers
+//! lost but for the backup domain (the RTC), and a wakeup that restarts the
+//! chip from reset, where [`woke_from_standby`] tells it from a power-on.
+//!
+//! [`estimated_current_ua`] gives rough figures for the core and flash, all
+//! peripherals off, to compare the modes with; for the board's real draw,
+//! put an ammeter in place of the IDD jumper (JP5).
+
+use cortex_m::peripheral::SCB;
+
+use stm32g4xx_hal as hal;
+
+use hal::rcc::{Clocks, Enable};
+use hal::stm32::{DBGMCU, FLASH, PWR, RCC};
+
+use crate::clocks::flash_wait_states;
+use hal::time::Hertz;
+
+/// Top clock of range 1 boost mode.
+pub const RANGE1_BOOST_MAX: Hertz = Hertz(170_000_000);
+/// Top clock of range 1 normal mode.
+pub const RANGE1_MAX: Hertz = Hertz(150_000_000);
+/// Top clock of range 2.
+pub const RANGE2_MAX: Hertz = Hertz(26_000_000);
+/// Top clock of low-power run mode.
+pub const LOW_POWER_RUN_MAX: Hertz = Hertz(2_000_000);
+
+/// Main regulator voltage range.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "logging", derive(defmt::Format))]
+pub enum VoltageRange {
+    Range1Boost,
+    Range1,
+    Range2,
+}
+
+impl VoltageRange {
+    /// The fastest HCLK of the range.
+    pub fn max_clock(self) -> Hertz {
+        match self {
+            VoltageRange::Range1Boost => RANGE1_BOOST_MAX,
+            VoltageRange::Range1 => RANGE1_MAX,
+            VoltageRange::Range2 => RANGE2_MAX,
+        }
+    }
+}
+
+/// Stop modes, for [`enter_stop`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "logging", derive(defmt::Format))]
+pub enum StopMode {
+    /// Main regulator on: a few microseconds to wake up.
+    Stop0 = 0b000,
+    /// Low-power regulator: lower draw, a little slower to wake up.
+    Stop1 = 0b001,
+}
+
+/// What the core runs on, for [`estimated_current_ua`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "logging", derive(defmt::Format))]
+pub enum PowerState {
+    /// The main regulator, in a range.
+    Run(VoltageRange),
+    /// The low-power regulator.
+    LowPowerRun,
+    /// Stopped.
+    Stop(StopMode),
+    /// Standby: regulator off.
+    Standby,
+}
+
+/// Power errors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "logging", derive(defmt::Format))]
+pub enum Error {
+    /// HCLK is above what the setting allows.
+    ClockTooFast,
+    /// The range cannot change in low-power run mode.
+    LowPowerRun,
+}
+
+fn pwr() -> &'static hal::stm32::pwr::RegisterBlock {
+    unsafe {
+        //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+        let rcc = &(*RCC::ptr());
+        PWR::enable(rcc);
+        &(*PWR::ptr())
+    }
+}
+
+/// The range the main regulator is in.
+pub fn voltage_range() -> VoltageRange {
+    let pwr = pwr();
+    match pwr.cr1.read().vos().bits() {
+        0b10 => VoltageRange::Range2,
+        _ if pwr.cr5.read().r1mode().bit_is_clear() => VoltageRange::Range1Boost,
+        _ => VoltageRange::Range1,
+    }
+}
+
+/// Sets the main regulator to `range`, for a system running at `clocks`,
+/// with the flash wait states of the range: raised before the voltage goes
+/// down, lowered once it is up.
+pub fn set_voltage_range(range: VoltageRange, clocks: &Clocks) -> Result<(), Error> {
+    if clocks.ahb_clk.0 > range.max_clock().0 {
+        return Err(Error::ClockTooFast);
+    }
+    if is_low_power_run() {
+        return Err(Error::LowPowerRun);
+    }
+    let pwr = pwr();
+    // NOTE(unsafe) only LATENCY is modified.
+    let flash = unsafe { &(*FLASH::ptr()) };
+    let latency = flash_wait_states(clocks.ahb_clk, range);
+    match range {
+        VoltageRange::Range2 => {
+            // Range 2 takes a wait state every 12 MHz: more of them first.
+            if latency > flash.acr.read().latency().bits() {
+                flash.acr.modify(|_, w| unsafe { w.latency().bits(latency) });
+                while flash.acr.read().latency().bits() != latency {}
+            }
+            pwr.cr1.modify(|_, w| unsafe { w.vos().bits(0b10) });
+        }
+        VoltageRange::Range1Boost | VoltageRange::Range1 => {
+            pwr.cr1.modify(|_, w| unsafe { w.vos().bits(0b01) });
+            // The regulator takes a while to reach the higher voltage.
+            while pwr.sr2.read().vosf().bit_is_set() {}
+            pwr.cr5.modify(|_, w| w.r1mode().bit(range == VoltageRange::Range1));
+            flash.acr.modify(|_, w| unsafe { w.latency().bits(latency) });
+        }
+    }
+    Ok(())
+}
+
+/// Hands the core to the low-power regulator, for a system running at
+/// `clocks`, at most [`LOW_POWER_RUN_MAX`].
+pub fn enter_low_power_run(clocks: &Clocks) -> Result<(), Error> {
+    if clocks.ahb_clk.0 > LOW_POWER_RUN_MAX.0 {
+        return Err(Error::ClockTooFast);
+    }
+    let pwr = pwr();
+    pwr.cr1.modify(|_, w| w.lpr().set_bit());
+    Ok(())
+}
+
+/// Hands the core back to the main regulator, before raising the clock.
+pub fn exit_low_power_run() {
+    let pwr = pwr();
+    pwr.cr1.modify(|_, w| w.lpr().clear_bit());
+    while pwr.sr2.read().reglpf().bit_is_set() {}
+}
+
+/// Returns `true` while the low-power regulator runs the core.
+pub fn is_low_power_run() -> bool {
+    pwr().sr2.read().reglpf().bit_is_set()
+}
+
+/// Stops the chip in `mode` until an EXTI line wakes it, then returns,
+/// with the system clock on the 16 MHz HSI: `clocks::set_sysclk` brings the
+/// PLL back.
+///
+/// An interrupt already pending returns at once, as `wfi` does.
+pub fn enter_stop(mode: StopMode, scb: &mut SCB) {
+    let pwr = pwr();
+    pwr.cr1.modify(|_, w| unsafe { w.lpms().bits(mode as u8) });
+    scb.set_sleepdeep();
+    // Make sure the register writes are done before stopping.
+    cortex_m::asm::dsb();
+    cortex_m::asm::wfi();
+    scb.clear_sleepdeep();
+}
+
+/// Puts the chip in Standby until a wakeup pin or the RTC wakes it, which
+/// restarts it from reset.
+///
+/// The pins float meanwhile: an LED they drive goes off.
+pub fn enter_standby(scb: &mut SCB) -> ! {
+    let pwr = pwr();
+    // A wakeup flag left set would end Standby at once.
+    pwr.scr.write(|w| {
+        w.cwuf1().set_bit().cwuf2().set_bit().cwuf3().set_bit().cwuf4().set_bit().cwuf5().set_bit()
+    });
+    pwr.cr1.modify(|_, w| unsafe { w.lpms().bits(0b011) });
+    scb.set_sleepdeep();
+    cortex_m::asm::dsb();
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+/// Returns `true`, once, if the reset was a wakeup from Standby.
+pub fn woke_from_standby() -> bool {
+    let pwr = pwr();
+    let standby = pwr.sr1.read().sbf().bit_is_set();
+    pwr.scr.write(|w| w.csbf().set_bit());
+    standby
+}
+
+/// Keeps the debug port and RTT alive in the stop and standby modes (the
+/// draw then does not drop), or lets them stop with the chip.
+pub fn debug_in_low_power(enable: bool) {
+    let dbgmcu = unsafe { &(*DBGMCU::ptr()) };
+    dbgmcu.cr.modify(|_, w| w.dbg_stop().bit(enable).dbg_standby().bit(enable));
+}
+
+/// The state the core runs in now.
+pub fn power_state() -> PowerState {
+    if is_low_power_run() {
+        PowerState::LowPowerRun
+    } else {
+        PowerState::Run(voltage_range())
+    }
+}
+
+/// A rough estimate of the current of the core and flash running from
+/// flash at `hclk` in `state`, all peripherals off, in microamps.
+///
+/// Good for comparing the modes, not for a power budget: the datasheet has
+/// the real figures, and they depend on the code, the temperature and the
+/// chip.
+pub fn estimated_current_ua(state: PowerState, hclk: Hertz) -> u32 {
+    // A static part and a part per MHz, in microamps.
+    let (base, per_mhz) = match state {
+        PowerState::Run(VoltageRange::Range1Boost) => (1000, 150),
+        PowerState::Run(VoltageRange::Range1) => (900, 130),
+        PowerState::Run(VoltageRange::Range2) => (500, 100),
+        PowerState::LowPowerRun => (150, 100),
+        // No clock to scale with.
+        PowerState::Stop(StopMode::Stop0) => return 100,
+        PowerState::Stop(StopMode::Stop1) => return 20,
+        PowerState::Standby => return 1,
+    };
+    base + per_mhz * hclk.0 / 1_000_000
+}
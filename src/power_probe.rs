@@ -0,0 +1,130 @@
+//! Markers of the sleeps, on a pin and in a log, for power profiling.
+//!
+//! A power analyzer shows how much the board draws, not what the firmware
+//! was doing at the time. [`SleepProbe`] takes the sleeps of the main loop,
+//! `wfi` and the stop modes, and marks each one: its pin high from just
+//! before the sleep to just after the wakeup, for the analyzer's or a logic
+//! analyzer's second channel, and an [`Event`] with a timestamp at both ends
+//! for the log.
+//!
+//! The sleep runs with interrupts masked, as in `load::sleep`: the
+//! interrupt that wakes the core runs after the pin is low again, so the
+//! high time of the pin is the sleep alone, and the time the interrupt
+//! takes is low time. The pin is any push-pull output the probe is given.
+//!
+//! The timestamps are DWT cycles, `profile::start` running the counter. It
+//! counts the core clock, which stops in the sleeps: the events then time
+//! the work between two sleeps, and the pin how long each sleep lasted.
+//! `load::start` keeps the counter running in Sleep mode, and
+//! `power::debug_in_low_power` in the stop modes, at the cost of the draw
+//! those save. The events wait in a queue of [`EVENTS`] for the main loop to
+//! log them, outside of the sleeps; the oldest go once it is full, and
+//! [`SleepProbe::dropped`] counts them.
+
+use cortex_m::peripheral::SCB;
+
+use embedded_hal::digital::OutputPin;
+
+use crate::power::{self, StopMode};
+use crate::profile;
+
+/// Events the queue holds.
+pub const EVENTS: usize = 16;
+
+/// A sleep of the probe.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Sleep {
+    /// `wfi`: the core clock stops, the peripherals run.
+    Wfi,
+    /// A stop mode: every clock but the low-speed ones stops.
+    Stop(StopMode),
+}
+
+/// Which end of a sleep.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Edge {
+    /// Going to sleep: the pin just went high.
+    Enter,
+    /// Woken up: the pin just went low.
+    Exit,
+}
+
+/// An end of a sleep, in DWT cycles.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct Event {
+    pub sleep: Sleep,
+    pub edge: Edge,
+    pub cycles: u32,
+}
+
+/// The sleeps of the main loop, marked on `pin` and queued as events.
+pub struct SleepProbe<P> {
+    pin: P,
+    events: [Option<Event>; EVENTS],
+    // Index of the oldest event, and how many are queued.
+    head: usize,
+    len: usize,
+    dropped: u32,
+}
+
+impl<P: OutputPin> SleepProbe<P> {
+    /// A probe marking its sleeps on `pin`, which it drives low.
+    pub fn new(mut pin: P) -> Self {
+        pin.set_low().ok();
+        SleepProbe { pin, events: [None; EVENTS], head: 0, len: 0, dropped: 0 }
+    }
+
+    /// Sleeps in `wfi` until an interrupt, which runs on return.
+    pub fn wfi(&mut self) {
+        self.mark(Sleep::Wfi, cortex_m::asm::wfi);
+    }
+
+    /// Stops the chip in `mode` until an EXTI line wakes it, as
+    /// `power::enter_stop`; the interrupt runs on return.
+    pub fn stop(&mut self, mode: StopMode, scb: &mut SCB) {
+        self.mark(Sleep::Stop(mode), || power::enter_stop(mode, scb));
+    }
+
+    /// Takes the oldest event of the queue.
+    pub fn pop(&mut self) -> Option<Event> {
+        if self.len == 0 {
+            return None;
+        }
+        let event = self.events[self.head].take();
+        self.head = (self.head + 1) % EVENTS;
+        self.len -= 1;
+        event
+    }
+
+    /// Events dropped from a full queue.
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Returns the pin.
+    pub fn release(self) -> P {
+        self.pin
+    }
+
+    // Runs `sleep` between the two edges of the pin, interrupts masked.
+    fn mark(&mut self, kind: Sleep, sleep: impl FnOnce()) {
+        cortex_m::interrupt::free(|_| {
+            self.pin.set_high().ok();
+            let enter = profile::cycles();
+            sleep();
+            self.pin.set_low().ok();
+            let exit = profile::cycles();
+            self.push(Event { sleep: kind, edge: Edge::Enter, cycles: enter });
+            self.push(Event { sleep: kind, edge: Edge::Exit, cycles: exit });
+        });
+    }
+
+    fn push(&mut self, event: Event) {
+        if self.len == EVENTS {
+            self.pop();
+            self.dropped = self.dropped.wrapping_add(1);
+        }
+        self.events[(self.head + self.len) % EVENTS] = Some(event);
+        self.len += 1;
+    }
+}
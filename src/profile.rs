@@ -0,0 +1,106 @@
+//! Execution time profiling with the DWT cycle counter.
+//!
+//! The cycle counter of the Data Watchpoint and Trace unit counts core
+//! clocks, wrapping every 2^32 of them, 25 s at 170 MHz: [`start`] runs it,
+//! [`cycles`] reads it, and the difference of two reads is the time between
+//! them as long as it is below a wrap.
+//!
+//! [`Budget`] keeps the worst case of a piece of code against the cycles it
+//! may take, the period of the interrupt it runs from for example: the
+//! handler calls [`Budget::begin`] first thing and [`Budget::end`] last, and
+//! the worst case, the budget and their ratio are there for the log.
+
+use cortex_m::peripheral::{DCB, DWT};
+
+/// Runs the cycle counter. It keeps counting from where it was.
+pub fn start(dcb: &mut DCB, dwt: &mut DWT) {
+    dcb.enable_trace();
+    dwt.enable_cycle_counter();
+}
+
+/// The cycle counter now.
+pub fn cycles() -> u32 {
+    DWT::cycle_count()
+}
+
+/// Cycles since `since`, a read of [`cycles`].
+pub fn cycles_since(since: u32) -> u32 {
+    cycles().wrapping_sub(since)
+}
+
+/// The worst case of a piece of code against its budget, in cycles.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct Budget {
+    budget: u32,
+    worst: u32,
+    last: u32,
+    runs: u32,
+    over: u32,
+}
+
+impl Budget {
+    /// A budget of `budget` cycles, nothing measured yet.
+    pub const fn new(budget: u32) -> Self {
+        Budget { budget, worst: 0, last: 0, runs: 0, over: 0 }
+    }
+
+    /// Starts a run: returns the start for [`Budget::end`].
+    pub fn begin(&self) -> u32 {
+        cycles()
+    }
+
+    /// Ends the run started at `start`, and returns its cycles.
+    pub fn end(&mut self, start: u32) -> u32 {
+        let taken = cycles_since(start);
+        self.last = taken;
+        self.worst = self.worst.max(taken);
+        self.runs = self.runs.wrapping_add(1);
+        if taken > self.budget {
+            self.over = self.over.wrapping_add(1);
+        }
+        taken
+    }
+
+    /// Sets a new budget, keeping the worst case.
+    pub fn set_budget(&mut self, budget: u32) {
+        self.budget = budget;
+    }
+
+    /// The budget, in cycles.
+    pub fn budget(&self) -> u32 {
+        self.budget
+    }
+
+    /// The longest run since [`Budget::new`] or the last [`Budget::reset`].
+    pub fn worst(&self) -> u32 {
+        self.worst
+    }
+
+    /// The last run.
+    pub fn last(&self) -> u32 {
+        self.last
+    }
+
+    /// The runs measured.
+    pub fn runs(&self) -> u32 {
+        self.runs
+    }
+
+    /// The runs over the budget.
+    pub fn overruns(&self) -> u32 {
+        self.over
+    }
+
+    /// The worst case in percent of the budget, above 100 over it.
+    pub fn worst_percent(&self) -> u32 {
+        if self.budget == 0 {
+            return 0;
+        }
+        (self.worst as u64 * 100 / self.budget as u64) as u32
+    }
+
+    /// Forgets the runs, keeping the budget.
+    pub fn reset(&mut self) {
+        *self = Budget::new(self.budget);
+    }
+}
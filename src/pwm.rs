@@ -0,0 +1,361 @@
+//! Complementary PWM with dead-time and break input on TIM1 and TIM8.
+//!
+//! [`ComplementaryPwm`] drives channel 1 of an advanced-control timer and its
+//! complementary output, the pair of switches of a half bridge:
+//!
+//! | Signal           | TIM1 pin  | TIM8 pin  |
+//! |------------------|-----------|-----------|
+//! | CH1 (high side)  | PA8 (D7)  | PC6       |
+//! | CH1N (low side)  | PA7 (D11) | PA7 (D11) |
+//! | BKIN (optional)  | PA6 (D12) | PA6 (D12) |
+//!
+//! CH1N is the inverse of CH1, and each output turns on a dead-time after the
+//! other one turned off, so the two switches never conduct together. Both
+//! outputs idle low: while the main output enable is off, before
+//! [`ComplementaryPwm::enable`] or during a break, the bridge is off.
+//!
+//! # Break
+//!
+//! The break input clears the main output enable in hardware, without any
+//! interrupt latency, when the BKIN pin goes low or when one of the
+//! comparators ([`comp`](crate::comp)) goes high. What happens next depends on
+//! [`Rearm`]: the outputs either come back on their own once the fault is
+//! gone, or stay off until [`ComplementaryPwm::rearm`].
+
+use core::ops::Deref;
+
+use stm32g4xx_hal as hal;
+
+use hal::gpio::{gpioa, gpioc, Alternate, AF4, AF6};
+use hal::rcc::{Enable, Reset};
+use hal::stm32::{tim1, RCC, TIM1, TIM8};
+use hal::time::Hertz;
+
+use crate::clocks::timer_prescaler;
+
+/// Output pins of TIM1 channel 1: CH1 and CH1N.
+pub type Tim1Pins = (gpioa::PA8<Alternate<AF6>>, gpioa::PA7<Alternate<AF6>>);
+
+/// Break input pin of TIM1.
+pub type Tim1BreakPin = gpioa::PA6<Alternate<AF6>>;
+
+/// Output pins of TIM8 channel 1: CH1 and CH1N.
+pub type Tim8Pins = (gpioc::PC6<Alternate<AF4>>, gpioa::PA7<Alternate<AF4>>);
+
+/// Break input pin of TIM8.
+pub type Tim8BreakPin = gpioa::PA6<Alternate<AF4>>;
+
+/// An advanced-control timer, with its pins.
+pub trait Instance: Deref<Target = tim1::RegisterBlock> + Enable + Reset {
+    /// CH1 and CH1N.
+    type Pins;
+    /// BKIN.
+    type BreakPin;
+}
+
+impl Instance for TIM1 {
+    type Pins = Tim1Pins;
+    type BreakPin = Tim1BreakPin;
+}
+
+impl Instance for TIM8 {
+    type Pins = Tim8Pins;
+    type BreakPin = Tim8BreakPin;
+}
+
+/// Timer interrupt sources.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Event {
+    /// Update event, every `repetitions + 1` periods: `TIM1_UP_TIM16` or `TIM8_UP`.
+    Update,
+    /// The break input went active: `TIM1_BRK_TIM15` or `TIM8_BRK`.
+    Break,
+}
+
+/// How the outputs come back after a break.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Rearm {
+    /// At the first update event after the break input is released.
+    Automatic,
+    /// Only on [`ComplementaryPwm::rearm`].
+    Software,
+}
+
+/// Comparators that can trip the break input.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum BreakComparator {
+    Comp1 = 1,
+    Comp2,
+    Comp3,
+    Comp4,
+    Comp5,
+    Comp6,
+    Comp7,
+}
+
+/// Break errors.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    /// The break input is still active.
+    BreakActive,
+}
+
+/// Complementary PWM on channel 1 of TIM1 or TIM8.
+pub struct ComplementaryPwm<TIM: Instance> {
+    tim: TIM,
+    pins: TIM::Pins,
+    break_pin: Option<TIM::BreakPin>,
+    break_listen: bool,
+    clock: Hertz,
+}
+
+impl<TIM: Instance> ComplementaryPwm<TIM> {
+    /// Starts the timer at `frequency` with a dead-time of `dead_time_ns`,
+    /// the duty at 0 and the outputs off.
+    ///
+    /// `clock` is the timer kernel clock, `rcc.clocks.apb2_tim_clk`.
+    pub fn new(tim: TIM, pins: TIM::Pins, clock: Hertz, frequency: Hertz, dead_time_ns: u32) -> Self {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            TIM::enable(rcc);
+            TIM::reset(rcc);
+        }
+
+        let mut pwm = ComplementaryPwm { tim, pins, break_pin: None, break_listen: false, clock };
+        pwm.set_frequency(frequency);
+        pwm.set_dead_time(dead_time_ns);
+
+        // PWM mode 1 with preload, both outputs active high and idle low.
+        pwm.tim.ccmr1_output().write(|w| w.oc1m().pwm_mode1().oc1pe().set_bit());
+        pwm.tim.ccer.write(|w| w.cc1e().set_bit().cc1ne().set_bit());
+        // Off-state outputs driven at their idle level instead of floating.
+        pwm.tim.bdtr.modify(|_, w| w.ossr().set_bit().ossi().set_bit());
+
+        pwm.tim.cr1.write(|w| w.arpe().set_bit());
+        // Load the preloaded registers, without an update interrupt.
+        pwm.tim.cr1.modify(|_, w| w.urs().set_bit());
+        pwm.tim.egr.write(|w| w.ug().set_bit());
+        pwm.tim.cr1.modify(|_, w| w.urs().clear_bit().cen().set_bit());
+        pwm
+    }
+
+    /// The frequency of the outputs, as the prescaler and period round it.
+    pub fn frequency(&self) -> Hertz {
+        let psc = self.tim.psc.read().bits() + 1;
+        let arr = self.tim.arr.read().bits() + 1;
+        Hertz(self.clock.0 / psc / arr)
+    }
+
+    /// Changes the frequency from the next period.
+    ///
+    /// The duty is in timer ticks, so it is set again after this.
+    pub fn set_frequency(&mut self, frequency: Hertz) {
+        let (psc, arr) = timer_prescaler(self.clock, frequency);
+        self.tim.psc.write(|w| unsafe { w.psc().bits(psc) });
+        self.tim.arr.write(|w| unsafe { w.bits(arr) });
+    }
+
+    /// The duty of a full period.
+    pub fn max_duty(&self) -> u16 {
+        self.tim.arr.read().bits() as u16 + 1
+    }
+
+    /// The duty of CH1, in timer ticks.
+    pub fn duty(&self) -> u16 {
+        self.tim.ccr[0].read().bits() as u16
+    }
+
+    /// Sets the duty of CH1 from the next period. CH1N is its complement.
+    pub fn set_duty(&mut self, duty: u16) {
+        let duty = duty.min(self.max_duty());
+        self.tim.ccr[0].write(|w| unsafe { w.bits(duty as u32) });
+    }
+
+    /// The dead-time, in nanoseconds, as the timer clock rounds it.
+    pub fn dead_time_ns(&self) -> u32 {
+        let ticks = dead_time_ticks(self.tim.bdtr.read().dtg().bits()) as u64;
+        (ticks * 1_000_000_000 / self.clock.0 as u64) as u32
+    }
+
+    /// Changes the dead-time, rounded down to what the timer clock allows.
+    ///
+    /// The longest dead-time is 1008 ticks of the timer clock: 63 us at 16 MHz.
+    pub fn set_dead_time(&mut self, dead_time_ns: u32) {
+        let ticks = (dead_time_ns as u64 * self.clock.0 as u64 / 1_000_000_000) as u32;
+        self.tim.bdtr.modify(|_, w| unsafe { w.dtg().bits(dtg(ticks)) });
+    }
+
+    /// Generates the update event every `repetitions + 1` periods, so
+    /// [`Event::Update`] can run slower than the PWM.
+    pub fn set_repetitions(&mut self, repetitions: u16) {
+        self.tim.rcr.write(|w| unsafe { w.bits(repetitions as u32) });
+    }
+
+    /// Turns the main output enable on: the outputs follow the timer.
+    pub fn enable(&mut self) {
+        self.tim.bdtr.modify(|_, w| w.moe().set_bit());
+    }
+
+    /// Turns the main output enable off: both outputs go to their idle level.
+    pub fn disable(&mut self) {
+        self.tim.bdtr.modify(|_, w| w.moe().clear_bit());
+    }
+
+    /// Returns `true` while the outputs follow the timer, `false` while they
+    /// are disabled or held off by a break.
+    pub fn is_enabled(&self) -> bool {
+        self.tim.bdtr.read().moe().bit_is_set()
+    }
+
+    /// Enables the break input, with its sources added by
+    /// [`break_on_pin`](Self::break_on_pin) and
+    /// [`break_on_comparator`](Self::break_on_comparator).
+    ///
+    /// The sources are filtered over 8 timer clocks, so spikes shorter than
+    /// that do not trip the break.
+    pub fn enable_break(&mut self, rearm: Rearm) {
+        // Active high: the comparators as they are, the pin inverted.
+        self.tim.bdtr.modify(|_, w| unsafe {
+            w.bkf()
+                .bits(0b0011)
+                .bkp()
+                .set_bit()
+                .aoe()
+                .bit(rearm == Rearm::Automatic)
+                .bke()
+                .set_bit()
+        });
+    }
+
+    /// Adds the BKIN pin to the break sources, active low.
+    pub fn break_on_pin(&mut self, pin: TIM::BreakPin) {
+        self.tim.af1.modify(|_, w| w.bkinp().set_bit().bkine().set_bit());
+        self.break_pin = Some(pin);
+    }
+
+    /// Adds a comparator to the break sources, active while its output is high.
+    ///
+    /// The comparator itself is configured with [`comp`](crate::comp).
+    pub fn break_on_comparator(&mut self, comparator: BreakComparator) {
+        self.tim.af1.modify(|r, w| unsafe { w.bits(r.bits() | 1 << comparator as u8) });
+    }
+
+    /// Disables the break input, clears its sources and returns the BKIN pin.
+    pub fn disable_break(&mut self) -> Option<TIM::BreakPin> {
+        self.tim.bdtr.modify(|_, w| w.bke().clear_bit().aoe().clear_bit());
+        // BKINE and BKCMP1E to BKCMP7E.
+        self.tim.af1.modify(|r, w| unsafe { w.bits(r.bits() & !0xFF).bkinp().clear_bit() });
+        self.break_pin.take()
+    }
+
+    /// Returns `true` if a break occurred since the last [`rearm`](Self::rearm).
+    pub fn is_tripped(&self) -> bool {
+        self.tim.sr.read().bif().bit_is_set()
+    }
+
+    /// Call from the break interrupt: returns `true` on a break.
+    ///
+    /// The break interrupt stays masked until [`rearm`](Self::rearm): its flag
+    /// cannot be cleared while the break input is active, so the interrupt
+    /// would fire again and again.
+    pub fn handle_break(&mut self) -> bool {
+        if !self.is_tripped() {
+            return false;
+        }
+        self.tim.dier.modify(|_, w| w.bie().clear_bit());
+        true
+    }
+
+    /// Clears the break and turns the outputs back on.
+    ///
+    /// Fails, with the outputs still off, while the break input is active.
+    pub fn rearm(&mut self) -> Result<(), Error> {
+        self.clear_interrupt(Event::Break);
+        if self.is_tripped() {
+            return Err(Error::BreakActive);
+        }
+        if self.break_listen {
+            self.tim.dier.modify(|_, w| w.bie().set_bit());
+        }
+        self.enable();
+        Ok(())
+    }
+
+    /// Starts listening for an interrupt event.
+    pub fn listen(&mut self, event: Event) {
+        match event {
+            Event::Update => self.tim.dier.modify(|_, w| w.uie().set_bit()),
+            Event::Break => {
+                self.break_listen = true;
+                self.tim.dier.modify(|_, w| w.bie().set_bit())
+            }
+        }
+    }
+
+    /// Stops listening for an interrupt event.
+    pub fn unlisten(&mut self, event: Event) {
+        match event {
+            Event::Update => self.tim.dier.modify(|_, w| w.uie().clear_bit()),
+            Event::Break => {
+                self.break_listen = false;
+                self.tim.dier.modify(|_, w| w.bie().clear_bit())
+            }
+        }
+    }
+
+    /// Returns `true` if the event is pending.
+    pub fn is_pending(&self, event: Event) -> bool {
+        let sr = self.tim.sr.read();
+        match event {
+            Event::Update => sr.uif().bit_is_set(),
+            Event::Break => sr.bif().bit_is_set(),
+        }
+    }
+
+    /// Clears the interrupt flag of the event.
+    ///
+    /// The break flag cannot be cleared while the break input is active.
+    pub fn clear_interrupt(&mut self, event: Event) {
+        // Flags are cleared by writing 0, ones are ignored.
+        match event {
+            Event::Update => self.tim.sr.write(|w| unsafe { w.bits(!0).uif().clear_bit() }),
+            Event::Break => self.tim.sr.write(|w| unsafe { w.bits(!0).bif().clear_bit() }),
+        }
+    }
+
+    /// Stops the timer and returns the peripheral, the output pins and the break pin.
+    pub fn release(mut self) -> (TIM, TIM::Pins, Option<TIM::BreakPin>) {
+        self.disable();
+        let break_pin = self.disable_break();
+        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            TIM::disable(rcc);
+        }
+        (self.tim, self.pins, break_pin)
+    }
+}
+
+/// Encodes a dead-time of `ticks` timer clocks as a DTG field, rounding down.
+pub(crate) fn dtg(ticks: u32) -> u8 {
+    match ticks {
+        0..=127 => ticks as u8,
+        128..=255 => 0x80 | (ticks / 2 - 64) as u8,
+        256..=511 => 0xC0 | (ticks / 8 - 32) as u8,
+        512..=1023 => 0xE0 | (ticks / 16 - 32) as u8,
+        _ => 0xFF,
+    }
+}
+
+/// Decodes a DTG field to timer clocks.
+pub(crate) fn dead_time_ticks(dtg: u8) -> u32 {
+    let dtg = dtg as u32;
+    match dtg >> 5 {
+        0..=3 => dtg,
+        4 | 5 => (64 + (dtg & 0x3F)) * 2,
+        6 => (32 + (dtg & 0x1F)) * 8,
+        _ => (32 + (dtg & 0x1F)) * 16,
+    }
+}
@@ -0,0 +1,338 @@
+//! QUADSPI driver for Winbond W25Q serial NOR flash.
+//!
+//! [`W25q`] drives a W25Q chip (W25Q16 to W25Q128, the common footprints of
+//! breakout boards) on QUADSPI1 bank 1. The pins avoid the ST-LINK virtual
+//! COM port (PA2/PA3) and the user LED:
+//!
+//! | Flash pin   | Board pin              |
+//! |-------------|------------------------|
+//! | CLK         | PB10 (D6)              |
+//! | /CS         | PB11 (CN10 pin 18)     |
+//! | DI (IO0)    | PB1 (CN10 pin 24)      |
+//! | DO (IO1)    | PB0 (A3)               |
+//! | /WP (IO2)   | PA7 (D11)              |
+//! | /HOLD (IO3) | PA6 (D12)              |
+//!
+//! Reads and page programs move the data on all four lines; commands and
+//! addresses use one. The chip size comes from its JEDEC ID. Besides the
+//! indirect read, erase and program operations, [`W25q::into_memory_mapped`]
+//! maps the whole chip at 0x9000_0000, where it reads like internal memory.
+
+use core::ptr;
+use core::slice;
+
+use stm32g4xx_hal as hal;
+
+use hal::gpio::{gpioa, gpiob, Alternate, AF10};
+use hal::rcc::{Enable, Reset};
+use hal::stm32::{QUADSPI, RCC};
+use hal::time::Hertz;
+
+/// Start of the memory-mapped flash.
+const MAPPED_ADDRESS: usize = 0x9000_0000;
+/// Highest clock of the bus, kept well below the 104 MHz of the chips for breakout board wiring.
+const MAX_CLOCK: u32 = 50_000_000;
+/// Bytes per page: a program operation stays within one page.
+pub const PAGE_SIZE: u32 = 256;
+/// Bytes per sector, the smallest erasable unit.
+pub const SECTOR_SIZE: u32 = 4096;
+
+/// W25Q instructions.
+const WRITE_ENABLE: u8 = 0x06;
+const READ_STATUS_1: u8 = 0x05;
+const READ_STATUS_2: u8 = 0x35;
+const WRITE_STATUS_2: u8 = 0x31;
+const JEDEC_ID: u8 = 0x9F;
+const FAST_READ_QUAD_OUTPUT: u8 = 0x6B;
+const QUAD_PAGE_PROGRAM: u8 = 0x32;
+const SECTOR_ERASE: u8 = 0x20;
+const CHIP_ERASE: u8 = 0xC7;
+
+/// BUSY bit of status register 1.
+const STATUS_BUSY: u8 = 0x01;
+/// QE bit of status register 2: IO2 and IO3 carry data instead of /WP and /HOLD.
+const STATUS_QUAD_ENABLE: u8 = 0x02;
+
+/// Pins of QUADSPI1 bank 1: CLK, /CS, IO0, IO1, IO2, IO3.
+pub type QspiPins = (
+    gpiob::PB10<Alternate<AF10>>,
+    gpiob::PB11<Alternate<AF10>>,
+    gpiob::PB1<Alternate<AF10>>,
+    gpiob::PB0<Alternate<AF10>>,
+    gpioa::PA7<Alternate<AF10>>,
+    gpioa::PA6<Alternate<AF10>>,
+);
+
+/// Flash error.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    /// No chip answered the JEDEC ID command.
+    NoDevice,
+    /// The operation reaches beyond the end of the chip.
+    OutOfRange,
+}
+
+/// Phases of an indirect command, besides the instruction.
+#[derive(Clone, Copy)]
+struct Command {
+    instruction: u8,
+    address: Option<u32>,
+    dummy_cycles: u8,
+    quad_data: bool,
+}
+
+impl Command {
+    const fn new(instruction: u8) -> Self {
+        Command { instruction, address: None, dummy_cycles: 0, quad_data: false }
+    }
+}
+
+/// W25Q flash on QUADSPI1, in indirect mode.
+pub struct W25q {
+    qspi: QUADSPI,
+    pins: QspiPins,
+    id: [u8; 3],
+}
+
+impl W25q {
+    /// Configures QUADSPI1, identifies the chip and enables its quad mode.
+    ///
+    /// `clock` is the QUADSPI kernel clock, HCLK by default
+    /// (`rcc.clocks.ahb_clk`).
+    pub fn new(qspi: QUADSPI, pins: QspiPins, clock: Hertz) -> Result<Self, Error> {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            QUADSPI::enable(rcc);
+            QUADSPI::reset(rcc);
+        }
+
+        let prescaler = clock.0.div_ceil(MAX_CLOCK).clamp(1, 256) - 1;
+        qspi.cr.write(|w| unsafe { w.prescaler().bits(prescaler as u8).fthres().bits(0).en().set_bit() });
+        // Largest size until the chip is identified; /CS high for 2 cycles between commands.
+        qspi.dcr.write(|w| unsafe { w.fsize().bits(31).csht().bits(1) });
+
+        let mut flash = W25q { qspi, pins, id: [0; 3] };
+        let mut id = [0; 3];
+        flash.read_command(Command::new(JEDEC_ID), &mut id);
+        if id[0] == 0x00 || id[0] == 0xFF {
+            return Err(Error::NoDevice);
+        }
+        flash.id = id;
+        // The third byte is the base 2 logarithm of the size, e.g. 0x18 for 16 MiB.
+        flash.qspi.dcr.modify(|_, w| unsafe { w.fsize().bits(id[2] - 1) });
+
+        let mut status = [0];
+        flash.read_command(Command::new(READ_STATUS_2), &mut status);
+        if status[0] & STATUS_QUAD_ENABLE == 0 {
+            flash.write_command(Command::new(WRITE_ENABLE), &[]);
+            flash.write_command(Command::new(WRITE_STATUS_2), &[status[0] | STATUS_QUAD_ENABLE]);
+            flash.wait_ready();
+        }
+        Ok(flash)
+    }
+
+    /// JEDEC manufacturer, memory type and capacity bytes, e.g. `[0xEF, 0x40, 0x18]`
+    /// for a W25Q128JV.
+    pub fn id(&self) -> [u8; 3] {
+        self.id
+    }
+
+    /// Size of the chip in bytes.
+    pub fn capacity(&self) -> u32 {
+        1 << self.id[2]
+    }
+
+    /// Reads `buffer.len()` bytes from `address`.
+    pub fn read(&mut self, address: u32, buffer: &mut [u8]) -> Result<(), Error> {
+        self.check_range(address, buffer.len() as u32)?;
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        self.read_command(Self::fast_read(address), buffer);
+        Ok(())
+    }
+
+    /// Erases (sets to 0xFF) the 4 KiB sector holding `address`.
+    ///
+    /// Takes 45 ms typically, 400 ms at most.
+    pub fn erase_sector(&mut self, address: u32) -> Result<(), Error> {
+        self.check_range(address, 1)?;
+        self.write_command(Command::new(WRITE_ENABLE), &[]);
+        self.write_command(Command { address: Some(address & !(SECTOR_SIZE - 1)), ..Command::new(SECTOR_ERASE) }, &[]);
+        self.wait_ready();
+        Ok(())
+    }
+
+    /// Erases the whole chip. Takes up to a few minutes on the largest ones.
+    pub fn erase_chip(&mut self) {
+        self.write_command(Command::new(WRITE_ENABLE), &[]);
+        self.write_command(Command::new(CHIP_ERASE), &[]);
+        self.wait_ready();
+    }
+
+    /// Programs `data` at `address`, page by page.
+    ///
+    /// Programming only clears bits: the area must have been erased before.
+    pub fn program(&mut self, mut address: u32, mut data: &[u8]) -> Result<(), Error> {
+        self.check_range(address, data.len() as u32)?;
+        while !data.is_empty() {
+            let room = (PAGE_SIZE - address % PAGE_SIZE) as usize;
+            let (page, rest) = data.split_at(room.min(data.len()));
+            self.write_command(Command::new(WRITE_ENABLE), &[]);
+            self.write_command(
+                Command { address: Some(address), quad_data: true, ..Command::new(QUAD_PAGE_PROGRAM) },
+                page,
+            );
+            self.wait_ready();
+            address += page.len() as u32;
+            data = rest;
+        }
+        Ok(())
+    }
+
+    /// Maps the chip into the address space for reading.
+    pub fn into_memory_mapped(self) -> MemoryMapped {
+        let command = Self::fast_read(0);
+        self.wait_idle();
+        // Memory-mapped mode: every read of 0x9000_0000 onwards fetches from the chip.
+        self.qspi.ccr.write(|w| Self::configure(w, command, 0b11));
+        MemoryMapped { flash: self }
+    }
+
+    /// Disables QUADSPI1 and returns the peripheral and the pins.
+    pub fn release(self) -> (QUADSPI, QspiPins) {
+        self.qspi.cr.modify(|_, w| w.en().clear_bit());
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            QUADSPI::disable(rcc);
+        }
+        (self.qspi, self.pins)
+    }
+
+    fn fast_read(address: u32) -> Command {
+        Command { address: Some(address), dummy_cycles: 8, quad_data: true, ..Command::new(FAST_READ_QUAD_OUTPUT) }
+    }
+
+    fn check_range(&self, address: u32, len: u32) -> Result<(), Error> {
+        match address.checked_add(len) {
+            Some(end) if end <= self.capacity() => Ok(()),
+            _ => Err(Error::OutOfRange),
+        }
+    }
+
+    /// Fills the communication configuration register for `command` in `fmode`.
+    fn configure(
+        w: &mut hal::stm32::quadspi::ccr::W,
+        command: Command,
+        fmode: u8,
+    ) -> &mut hal::stm32::quadspi::ccr::W {
+        let w = unsafe {
+            w.fmode()
+                .bits(fmode)
+                .imode()
+                .bits(0b01)
+                .instruction()
+                .bits(command.instruction)
+                .dcyc()
+                .bits(command.dummy_cycles)
+                .dmode()
+                .bits(if command.quad_data { 0b11 } else { 0b01 })
+        };
+        if command.address.is_some() {
+            // 24-bit address on a single line.
+            unsafe { w.admode().bits(0b01).adsize().bits(0b10) }
+        } else {
+            w
+        }
+    }
+
+    /// Runs an indirect read command into `buffer`.
+    fn read_command(&mut self, command: Command, buffer: &mut [u8]) {
+        self.wait_idle();
+        self.qspi.dlr.write(|w| unsafe { w.dl().bits(buffer.len() as u32 - 1) });
+        self.qspi.ccr.write(|w| Self::configure(w, command, 0b01));
+        // The transfer starts with the write of the address, or of CCR without one.
+        if let Some(address) = command.address {
+            self.qspi.ar.write(|w| unsafe { w.address().bits(address) });
+        }
+
+        let dr = self.qspi.dr.as_ptr() as *const u8;
+        for byte in buffer.iter_mut() {
+            while self.qspi.sr.read().flevel().bits() == 0 {}
+            // NOTE(unsafe) a byte read of DR takes a single byte from the FIFO.
+            *byte = unsafe { ptr::read_volatile(dr) };
+        }
+        self.wait_complete();
+    }
+
+    /// Runs an indirect write command sending `data`, possibly none.
+    fn write_command(&mut self, command: Command, data: &[u8]) {
+        self.wait_idle();
+        if !data.is_empty() {
+            self.qspi.dlr.write(|w| unsafe { w.dl().bits(data.len() as u32 - 1) });
+        }
+        self.qspi.ccr.write(|w| {
+            let w = Self::configure(w, command, 0b00);
+            if data.is_empty() { unsafe { w.dmode().bits(0b00) } } else { w }
+        });
+        if let Some(address) = command.address {
+            self.qspi.ar.write(|w| unsafe { w.address().bits(address) });
+        }
+
+        let dr = self.qspi.dr.as_ptr() as *mut u8;
+        for &byte in data {
+            while self.qspi.sr.read().ftf().bit_is_clear() {}
+            // NOTE(unsafe) a byte write of DR adds a single byte to the FIFO.
+            unsafe { ptr::write_volatile(dr, byte) };
+        }
+        self.wait_complete();
+    }
+
+    /// Polls status register 1 in automatic polling mode until the chip is done.
+    fn wait_ready(&mut self) {
+        self.wait_idle();
+        self.qspi.psmkr.write(|w| unsafe { w.mask().bits(STATUS_BUSY as u32) });
+        self.qspi.psmar.write(|w| unsafe { w.match_().bits(0) });
+        self.qspi.pir.write(|w| unsafe { w.interval().bits(16) });
+        self.qspi.dlr.write(|w| unsafe { w.dl().bits(0) });
+        self.qspi.cr.modify(|_, w| w.apms().set_bit());
+        self.qspi.ccr.write(|w| Self::configure(w, Command::new(READ_STATUS_1), 0b10));
+        while self.qspi.sr.read().smf().bit_is_clear() {}
+        self.qspi.fcr.write(|w| w.csmf().set_bit());
+        self.wait_idle();
+    }
+
+    fn wait_complete(&self) {
+        while self.qspi.sr.read().tcf().bit_is_clear() {}
+        self.qspi.fcr.write(|w| w.ctcf().set_bit());
+    }
+
+    fn wait_idle(&self) {
+        while self.qspi.sr.read().busy().bit_is_set() {}
+    }
+}
+
+/// W25Q flash mapped at 0x9000_0000.
+pub struct MemoryMapped {
+    flash: W25q,
+}
+
+impl MemoryMapped {
+    /// The whole chip as a byte slice.
+    pub fn as_slice(&self) -> &[u8] {
+        // NOTE(unsafe) the mapping covers the capacity of the chip, and it
+        // cannot change while `self` is borrowed: programming needs `into_indirect`.
+        unsafe { slice::from_raw_parts(MAPPED_ADDRESS as *const u8, self.flash.capacity() as usize) }
+    }
+
+    /// Leaves memory-mapped mode for the erase and program operations.
+    pub fn into_indirect(self) -> W25q {
+        let flash = self.flash;
+        flash.qspi.cr.modify(|_, w| w.abort().set_bit());
+        while flash.qspi.cr.read().abort().bit_is_set() {}
+        flash.wait_idle();
+        flash
+    }
+}
@@ -0,0 +1,116 @@
+//! True random number generator (RNG).
+//!
+//! The RNG samples analog noise and conditions it into 32-bit random words,
+//! one every few hundred cycles of its 48 MHz kernel clock. This module
+//! starts the internal HSI48 oscillator for that clock, hands out words and
+//! values from a range, and reports the two failure modes of the peripheral:
+//! a bad noise source (seed error) and a kernel clock too slow for the AHB
+//! clock (clock error).
+
+use core::ops::RangeInclusive;
+
+use stm32g4xx_hal as hal;
+
+use hal::nb;
+use hal::rcc::{Enable, Reset};
+use hal::stm32::{RCC, RNG};
+
+/// RNG failures.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    /// The noise source misbehaved. The generator has been restarted and the
+    /// next words are valid again.
+    Seed,
+    /// The 48 MHz kernel clock is missing or too slow for the AHB clock.
+    Clock,
+}
+
+/// The hardware random number generator.
+pub struct Rng {
+    rng: RNG,
+}
+
+impl Rng {
+    /// Starts HSI48 as the RNG kernel clock and enables the generator.
+    pub fn new(rng: RNG) -> Self {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            rcc.crrcr.modify(|_, w| w.hsi48on().set_bit());
+            while rcc.crrcr.read().hsi48rdy().bit_is_clear() {}
+            rcc.ccipr.modify(|_, w| w.clk48sel().hsi48());
+            RNG::enable(rcc);
+            RNG::reset(rcc);
+        }
+
+        // Clock error detection stays enabled (CED = 0).
+        rng.cr.modify(|_, w| w.rngen().set_bit());
+        Rng { rng }
+    }
+
+    /// Returns the next random word if one is ready.
+    pub fn read(&mut self) -> nb::Result<u32, Error> {
+        let sr = self.rng.sr.read();
+        if sr.secs().bit_is_set() || sr.seis().bit_is_set() {
+            // Recovery sequence: clear the flag and restart the generator.
+            self.rng.sr.modify(|_, w| w.seis().clear_bit());
+            self.rng.cr.modify(|_, w| w.rngen().clear_bit());
+            self.rng.cr.modify(|_, w| w.rngen().set_bit());
+            return Err(nb::Error::Other(Error::Seed));
+        }
+        if sr.cecs().bit_is_set() {
+            self.rng.sr.modify(|_, w| w.ceis().clear_bit());
+            return Err(nb::Error::Other(Error::Clock));
+        }
+        if sr.drdy().bit_is_clear() {
+            return Err(nb::Error::WouldBlock);
+        }
+        Ok(self.rng.dr.read().bits())
+    }
+
+    /// Waits for the next random word.
+    pub fn next_u32(&mut self) -> Result<u32, Error> {
+        nb::block!(self.read())
+    }
+
+    /// Returns a random value in `range`, every value equally likely.
+    pub fn in_range(&mut self, range: RangeInclusive<u32>) -> Result<u32, Error> {
+        let (low, high) = range.into_inner();
+        assert!(low <= high);
+        let span = (high - low) as u64 + 1;
+        // Rejection keeps the result unbiased: drop the words at the top of
+        // the u32 range that would make the lowest values more frequent.
+        let zone = u32::MAX as u64 + 1 - (u32::MAX as u64 + 1) % span;
+        loop {
+            let word = self.next_u32()? as u64;
+            if word < zone {
+                return Ok(low + (word % span) as u32);
+            }
+        }
+    }
+
+    /// Raises the `RNG` interrupt when a word is ready or an error occurs.
+    ///
+    /// Note, you will also have to unmask the RNG interrupt in the NVIC.
+    pub fn listen(&mut self) {
+        self.rng.cr.modify(|_, w| w.ie().set_bit());
+    }
+
+    /// Stops raising the `RNG` interrupt.
+    pub fn unlisten(&mut self) {
+        self.rng.cr.modify(|_, w| w.ie().clear_bit());
+    }
+
+    /// Disables the generator and its clock and returns the peripheral.
+    ///
+    /// HSI48 is left running: other peripherals, such as USB, may use it.
+    pub fn release(self) -> RNG {
+        self.rng.cr.modify(|_, w| w.rngen().clear_bit());
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            RNG::disable(rcc);
+        }
+        self.rng
+    }
+}
@@ -0,0 +1,219 @@
+//! Wakeup timer of the real-time clock (RTC).
+//!
+//! The RTC runs from the 32.768 kHz LSE crystal or the 32 kHz LSI, which keep
+//! running in the Stop and Standby modes when every other clock, TIM2's
+//! among them, has stopped. Its wakeup timer counts seconds on that clock
+//! and raises EXTI line 20 at the end of each period: the `RTC_WKUP`
+//! interrupt, and a wakeup from Stop (the core resumes after
+//! `power::enter_stop`) or from Standby (the chip restarts from reset).
+//!
+//! The RTC sits in the backup domain, which a reset does not clear: after a
+//! Standby wakeup [`Rtc::new`] finds it running and keeps its clock. So do
+//! the 32 backup registers of the tamper block next to it, which hold their
+//! words through resets and Standby for as long as VBAT or VDD lasts:
+//! [`Rtc::backup`] and [`Rtc::set_backup`] read and write them, and
+//! [`Rtc::save_checked`] and [`Rtc::load_checked`] keep a CRC word after a
+//! block of them, computed by the CRC unit, so a torn or stale copy is not
+//! taken for settings.
+
+use stm32g4xx_hal as hal;
+
+use hal::stm32::{EXTI, PWR, RCC, RTC, TAMP};
+
+use crate::clocks::LowSpeedSource;
+use crate::crc::Crc;
+
+/// EXTI line of the wakeup timer.
+pub const EXTI_LINE: u8 = 20;
+
+/// Longest wakeup period, in seconds.
+pub const MAX_WAKEUP_S: u32 = 1 << 16;
+
+/// Backup registers of the tamper block.
+pub const BACKUP_REGISTERS: usize = 32;
+
+/// RTC errors.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    /// The wakeup period is not within 1 to [`MAX_WAKEUP_S`] seconds.
+    InvalidPeriod,
+    /// No backup register of that index.
+    InvalidRegister,
+    /// Saved words that do not match their CRC word.
+    Checksum,
+}
+
+fn exti() -> &'static hal::stm32::exti::RegisterBlock {
+    // NOTE(unsafe) only the bits of the RTC's own EXTI line are modified.
+    unsafe { &*EXTI::ptr() }
+}
+
+/// The real-time clock, for its wakeup timer.
+pub struct Rtc {
+    rtc: RTC,
+    source: LowSpeedSource,
+}
+
+impl Rtc {
+    /// Clocks the RTC from `source`, which must be running: the
+    /// `low_speed` of the `clocks::Sources` that `clocks::freeze` returns.
+    pub fn new(rtc: RTC, source: LowSpeedSource) -> Self {
+        let rtcsel = match source {
+            LowSpeedSource::Lse => 0b01,
+            LowSpeedSource::Lsi => 0b10,
+        };
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            let pwr = &(*PWR::ptr());
+            rcc.apb1enr1.modify(|_, w| w.pwren().set_bit().rtcapben().set_bit());
+            // The backup domain is write protected out of reset.
+            pwr.cr1.modify(|_, w| w.dbp().set_bit());
+
+            let selected = rcc.bdcr.read().rtcsel().bits();
+            if selected != rtcsel && selected != 0 {
+                // The clock only changes with a reset of the backup domain,
+                // which stops the LSE too.
+                let lse = rcc.bdcr.read().lseon().bit_is_set();
+                rcc.bdcr.modify(|_, w| w.bdrst().set_bit());
+                rcc.bdcr.modify(|_, w| w.bdrst().clear_bit());
+                if lse {
+                    rcc.bdcr.modify(|_, w| w.lseon().set_bit());
+                    while rcc.bdcr.read().lserdy().bit_is_clear() {}
+                }
+            }
+            rcc.bdcr.modify(|_, w| w.rtcsel().bits(rtcsel).rtcen().set_bit());
+        }
+
+        let rtc = Rtc { rtc, source };
+        // Prescalers for the 1 Hz clock of the wakeup timer.
+        let prediv_s = match source {
+            LowSpeedSource::Lse => 32_768 / 128 - 1,
+            LowSpeedSource::Lsi => 32_000 / 128 - 1,
+        };
+        rtc.unlocked(|rtc| {
+            rtc.icsr.modify(|_, w| w.init().set_bit());
+            while rtc.icsr.read().initf().bit_is_clear() {}
+            rtc.prer.write(|w| unsafe { w.prediv_a().bits(128 - 1).prediv_s().bits(prediv_s) });
+            rtc.icsr.modify(|_, w| w.init().clear_bit());
+        });
+        rtc
+    }
+
+    /// The clock the RTC runs from.
+    pub fn source(&self) -> LowSpeedSource {
+        self.source
+    }
+
+    /// Raises the wakeup every `seconds`, from now on.
+    ///
+    /// Note, you will also have to unmask the RTC_WKUP interrupt in the NVIC.
+    pub fn set_wakeup(&mut self, seconds: u32) -> Result<(), Error> {
+        if !(1..=MAX_WAKEUP_S).contains(&seconds) {
+            return Err(Error::InvalidPeriod);
+        }
+        self.unlocked(|rtc| {
+            rtc.cr.modify(|_, w| w.wute().clear_bit());
+            while rtc.icsr.read().wutwf().bit_is_clear() {}
+            rtc.wutr.write(|w| unsafe { w.wut().bits((seconds - 1) as u16) });
+            rtc.scr.write(|w| w.cwutf().set_bit());
+            // 0b100: the 1 Hz clock, a 16-bit count.
+            rtc.cr.modify(|_, w| unsafe { w.wucksel().bits(0b100).wutie().set_bit().wute().set_bit() });
+        });
+        let exti = exti();
+        exti.rtsr1.modify(|_, w| w.rt20().set_bit());
+        exti.imr1.modify(|_, w| w.im20().set_bit());
+        Ok(())
+    }
+
+    /// Stops the wakeup timer.
+    pub fn disable_wakeup(&mut self) {
+        exti().imr1.modify(|_, w| w.im20().clear_bit());
+        self.unlocked(|rtc| rtc.cr.modify(|_, w| w.wute().clear_bit().wutie().clear_bit()));
+        self.clear_wakeup();
+    }
+
+    /// Call from `RTC_WKUP`: clears a wakeup.
+    ///
+    /// Returns `false` if the wakeup timer did not fire.
+    pub fn handle_wakeup(&mut self) -> bool {
+        if self.rtc.sr.read().wutf().bit_is_clear() {
+            return false;
+        }
+        self.clear_wakeup();
+        true
+    }
+
+    /// The word in backup register `index`, zero after a backup domain reset.
+    pub fn backup(&self, index: usize) -> Result<u32, Error> {
+        let tamp = Self::tamp();
+        let register = tamp.bkpr.get(index).ok_or(Error::InvalidRegister)?;
+        Ok(register.read().bits())
+    }
+
+    /// Writes `value` to backup register `index`.
+    pub fn set_backup(&mut self, index: usize, value: u32) -> Result<(), Error> {
+        let tamp = Self::tamp();
+        let register = tamp.bkpr.get(index).ok_or(Error::InvalidRegister)?;
+        register.write(|w| unsafe { w.bits(value) });
+        Ok(())
+    }
+
+    /// Writes `words` from backup register `index` on, and their CRC with
+    /// the algorithm of `crc` in the register after them.
+    pub fn save_checked(&mut self, index: usize, words: &[u32], crc: &mut Crc) -> Result<(), Error> {
+        if index + words.len() >= BACKUP_REGISTERS {
+            return Err(Error::InvalidRegister);
+        }
+        crc.reset();
+        for (offset, &word) in words.iter().enumerate() {
+            self.set_backup(index + offset, word)?;
+            crc.feed(&word.to_le_bytes());
+        }
+        self.set_backup(index + words.len(), crc.result())
+    }
+
+    /// Reads the words saved by [`Rtc::save_checked`] into `words`, or
+    /// returns [`Error::Checksum`] if they do not match their CRC word.
+    pub fn load_checked(&self, index: usize, words: &mut [u32], crc: &mut Crc) -> Result<(), Error> {
+        if index + words.len() >= BACKUP_REGISTERS {
+            return Err(Error::InvalidRegister);
+        }
+        crc.reset();
+        for (offset, word) in words.iter_mut().enumerate() {
+            *word = self.backup(index + offset)?;
+            crc.feed(&word.to_le_bytes());
+        }
+        if self.backup(index + words.len())? != crc.result() {
+            return Err(Error::Checksum);
+        }
+        Ok(())
+    }
+
+    /// Stops the wakeup timer and returns the peripheral. The RTC keeps
+    /// running in the backup domain.
+    pub fn release(mut self) -> RTC {
+        self.disable_wakeup();
+        self.rtc
+    }
+
+    fn clear_wakeup(&mut self) {
+        self.rtc.scr.write(|w| w.cwutf().set_bit());
+        exti().pr1.write(|w| w.pif20().set_bit());
+    }
+
+    fn tamp() -> &'static hal::stm32::tamp::RegisterBlock {
+        // NOTE(unsafe) clocked and write enabled with the RTC by `Rtc::new`;
+        // only the backup registers are used.
+        unsafe { &*TAMP::ptr() }
+    }
+
+    // Runs `f` with the RTC registers write enabled.
+    fn unlocked<F: FnOnce(&RTC)>(&self, f: F) {
+        self.rtc.wpr.write(|w| unsafe { w.key().bits(0xca) });
+        self.rtc.wpr.write(|w| unsafe { w.key().bits(0x53) });
+        f(&self.rtc);
+        // Any wrong key locks them again.
+        self.rtc.wpr.write(|w| unsafe { w.key().bits(0xff) });
+    }
+}
@@ -0,0 +1,183 @@
+//! SAI1 block A as an I2S master transmitter, fed by DMA.
+//!
+//! [`I2s`] configures block A of SAI1 for the I2S standard: 16-bit stereo
+//! samples in 32-bit slots, the clock and the word select generated by the
+//! board. Any I2S DAC or amplifier that derives its clocks from the bit clock
+//! works, such as the PCM5102A or the MAX98357A:
+//!
+//! | Signal             | Board pin |
+//! |--------------------|-----------|
+//! | bit clock (BCK)    | PA8 (D7)  |
+//! | word select (LRCK) | PA9 (D8)  |
+//! | data (DIN)         | PA10 (D2) |
+//!
+//! The samples come from memory through a DMA transfer with [`I2sData`] as
+//! the peripheral side; [`sine`] builds a table for such a transfer at compile
+//! time, so a circular transfer plays a steady tone without any CPU work.
+
+use stm32g4xx_hal as hal;
+
+use hal::dma::{traits::TargetAddress, MemoryToPeripheral};
+use hal::gpio::{gpioa, Alternate, AF14};
+use hal::rcc::{Enable, Reset};
+use hal::stm32::{RCC, SAI};
+use hal::time::Hertz;
+
+/// DMAMUX request line of SAI1 block A.
+const SAI1_A_REQUEST: u8 = 108;
+/// Bit clocks per frame: two 32-bit slots.
+const FRAME_BITS: u32 = 64;
+
+/// Pins of SAI1 block A: bit clock, word select and data.
+pub type I2sPins = (gpioa::PA8<Alternate<AF14>>, gpioa::PA9<Alternate<AF14>>, gpioa::PA10<Alternate<AF14>>);
+
+/// I2S output on SAI1 block A.
+pub struct I2s {
+    sai: SAI,
+    pins: I2sPins,
+    sample_rate: Hertz,
+}
+
+impl I2s {
+    /// Configures the block for `sample_rate` frames per second. The output
+    /// stays off until [`enable`](Self::enable).
+    ///
+    /// `clock` is the SAI1 kernel clock, SYSCLK by default
+    /// (`rcc.clocks.sys_clk`). The rate is rounded to the nearest one the
+    /// clock divides to: from the 16 MHz HSI, 31.25 kHz is exact.
+    ///
+    /// Returns the block and the target of the sample DMA transfer.
+    pub fn new(sai: SAI, pins: I2sPins, clock: Hertz, sample_rate: Hertz) -> (Self, I2sData) {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            SAI::enable(rcc);
+            SAI::reset(rcc);
+        }
+
+        let bit_clock = sample_rate.0 * FRAME_BITS;
+        let mckdiv = ((clock.0 + bit_clock / 2) / bit_clock).clamp(1, 63);
+        let block = sai.cha();
+
+        // Master transmitter, free protocol, 16-bit data, MSB first, bit clock
+        // straight from the divider (no master clock).
+        block.cr1.write(|w| unsafe {
+            w.mode()
+                .bits(0b00)
+                .prtcfg()
+                .bits(0b00)
+                .ds()
+                .bits(0b100)
+                .nodiv()
+                .set_bit()
+                .mckdiv()
+                .bits(mckdiv as u8)
+                .dmaen()
+                .set_bit()
+        });
+        // I2S frame: word select low for the left slot, one bit ahead of the
+        // data and high for the right slot.
+        block.frcr.write(|w| unsafe {
+            w.frl()
+                .bits((FRAME_BITS - 1) as u8)
+                .fsall()
+                .bits((FRAME_BITS / 2 - 1) as u8)
+                .fsdef()
+                .set_bit()
+                .fsoff()
+                .set_bit()
+        });
+        block.slotr.write(|w| unsafe { w.slotsz().bits(0b10).nbslot().bits(1).sloten().bits(0b11) });
+        // DMA request while the FIFO is at most half full.
+        block.cr2.write(|w| unsafe { w.fth().bits(0b010) });
+
+        let i2s = I2s { sai, pins, sample_rate: Hertz(clock.0 / mckdiv / FRAME_BITS) };
+        (i2s, I2sData { _private: () })
+    }
+
+    /// The exact sample rate.
+    pub fn sample_rate(&self) -> Hertz {
+        self.sample_rate
+    }
+
+    /// Starts the clocks and the data output.
+    ///
+    /// Start the DMA transfer first, so the FIFO is filled from the first frame.
+    pub fn enable(&mut self) {
+        self.sai.cha().cr1.modify(|_, w| w.saien().set_bit());
+    }
+
+    /// Stops at the end of the current frame.
+    pub fn disable(&mut self) {
+        self.sai.cha().cr1.modify(|_, w| w.saien().clear_bit());
+        while self.sai.cha().cr1.read().saien().bit_is_set() {}
+    }
+
+    /// Sends silence instead of the samples, from the next frame on.
+    ///
+    /// The DMA transfer keeps running, so unmuting resumes where the table is.
+    pub fn mute(&mut self) {
+        self.sai.cha().cr2.modify(|_, w| w.mute().set_bit());
+    }
+
+    /// Sends the samples again.
+    pub fn unmute(&mut self) {
+        self.sai.cha().cr2.modify(|_, w| w.mute().clear_bit());
+    }
+
+    /// Disables SAI1 and returns the peripheral and the pins.
+    ///
+    /// The [`I2sData`] of the block should be freed from its transfer first.
+    pub fn release(mut self) -> (SAI, I2sPins) {
+        self.disable();
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            SAI::disable(rcc);
+        }
+        (self.sai, self.pins)
+    }
+}
+
+/// Data register of SAI1 block A, the peripheral side of a memory-to-peripheral
+/// DMA transfer of interleaved left/right samples.
+pub struct I2sData {
+    _private: (),
+}
+
+unsafe impl TargetAddress<MemoryToPeripheral> for I2sData {
+    type MemSize = u32;
+
+    const REQUEST_LINE: Option<u8> = Some(SAI1_A_REQUEST);
+
+    fn address(&self) -> u32 {
+        // NOTE(unsafe) only the address of the register is taken.
+        unsafe { (*SAI::ptr()).cha().dr.as_ptr() as u32 }
+    }
+}
+
+/// One period of a sine wave of `N / 2` stereo frames, both channels alike,
+/// with peak `amplitude`.
+///
+/// Each word holds a signed 16-bit sample, as the DMA transfer moves them.
+/// The sine uses the approximation of Bhaskara I, about 0.2% of the peak off
+/// the real curve: plenty for a test tone.
+pub const fn sine<const N: usize>(amplitude: i16) -> [u32; N] {
+    let mut table = [0; N];
+    let frames = N / 2;
+    let half = (frames / 2) as i64;
+    let mut i = 0;
+    while i < frames {
+        let u = (i % (frames / 2)) as i64;
+        // sin(pi u / half) ~ 16 u (half - u) / (5 half^2 - 4 u (half - u))
+        let p = u * (half - u);
+        let mut sample = amplitude as i64 * 16 * p / (5 * half * half - 4 * p);
+        if i >= frames / 2 {
+            sample = -sample;
+        }
+        table[2 * i] = sample as i16 as u16 as u32;
+        table[2 * i + 1] = sample as i16 as u16 as u32;
+        i += 1;
+    }
+    table
+}
@@ -0,0 +1,93 @@
+//! Hobby servo control on any 16-bit timer PWM channel.
+//!
+//! A hobby servo expects a pulse every 20 ms (50 Hz) and turns to a position
+//! given by the pulse width, usually from 1 ms at one end of its travel to
+//! 2 ms at the other. [`Servo`] wraps a PWM channel of the HAL, set up by the
+//! caller at [`FREQUENCY`] on the timer and pin of their choice (for example
+//! `dp.TIM3.pwm(pa6, servo::FREQUENCY, &mut rcc)`), and turns pulse widths
+//! and angles into duties.
+//!
+//! Power the servo from 5 V, not from the board: only its signal wire goes
+//! to the pin, and its ground to the board's.
+
+use stm32g4xx_hal as hal;
+
+use hal::hal::PwmPin;
+use hal::time::Hertz;
+
+/// PWM frequency servos expect.
+pub const FREQUENCY: Hertz = Hertz(50);
+/// PWM period at [`FREQUENCY`], in microseconds.
+pub const PERIOD_US: u32 = 20_000;
+/// Travel of [`Servo::set_angle`], in degrees.
+pub const MAX_ANGLE: u16 = 180;
+
+/// Servo on one PWM channel.
+pub struct Servo<P> {
+    pwm: P,
+    min_us: u16,
+    max_us: u16,
+}
+
+impl<P: PwmPin<Duty = u16>> Servo<P> {
+    /// Starts the pulses, with the common 1000 to 2000 us range and the
+    /// servo centered.
+    pub fn new(pwm: P) -> Self {
+        Self::with_range(pwm, 1000, 2000)
+    }
+
+    /// Starts the pulses with the servo centered, for a servo going from
+    /// 0° at `min_us` to [`MAX_ANGLE`] at `max_us`.
+    ///
+    /// Many servos travel further than 1000 to 2000 us, some from 500 to
+    /// 2500 us; check before widening the range, past its end stops a servo
+    /// stalls and heats up.
+    pub fn with_range(pwm: P, min_us: u16, max_us: u16) -> Self {
+        let mut servo = Servo { pwm, min_us, max_us };
+        servo.set_angle(MAX_ANGLE / 2);
+        servo.pwm.enable();
+        servo
+    }
+
+    /// The pulse width, in microseconds.
+    pub fn pulse_us(&self) -> u16 {
+        (self.pwm.get_duty() as u32 * PERIOD_US / self.pwm.get_max_duty() as u32) as u16
+    }
+
+    /// Sets the pulse width from the next period, clamped to the range.
+    pub fn set_pulse_us(&mut self, pulse_us: u16) {
+        let pulse_us = pulse_us.clamp(self.min_us, self.max_us) as u32;
+        let duty = pulse_us * self.pwm.get_max_duty() as u32 / PERIOD_US;
+        self.pwm.set_duty(duty as u16);
+    }
+
+    /// The angle, in degrees.
+    pub fn angle(&self) -> u16 {
+        let span = (self.max_us - self.min_us) as u32;
+        let offset = self.pulse_us().saturating_sub(self.min_us) as u32;
+        ((offset * MAX_ANGLE as u32 + span / 2) / span) as u16
+    }
+
+    /// Turns to `degrees`, from 0 to [`MAX_ANGLE`].
+    pub fn set_angle(&mut self, degrees: u16) {
+        let span = (self.max_us - self.min_us) as u32;
+        let degrees = degrees.min(MAX_ANGLE) as u32;
+        self.set_pulse_us(self.min_us + (span * degrees / MAX_ANGLE as u32) as u16);
+    }
+
+    /// Stops the pulses: most servos then stop holding their position.
+    pub fn disable(&mut self) {
+        self.pwm.disable();
+    }
+
+    /// Starts the pulses again, at the last position.
+    pub fn enable(&mut self) {
+        self.pwm.enable();
+    }
+
+    /// Stops the pulses and returns the PWM channel.
+    pub fn release(mut self) -> P {
+        self.pwm.disable();
+        self.pwm
+    }
+}
@@ -0,0 +1,194 @@
+//! Line-oriented command shell over a serial port.
+//!
+//! The Nucleo's ST-LINK forwards USART2 (TX PA2, RX PA3) to the USB virtual
+//! COM port, so a terminal on the PC (`picocom -b 115200 /dev/ttyACM0`, PuTTY)
+//! can talk to the board. [`Shell`] collects the characters received, one at
+//! a time from the RX interrupt, into a line: simple editing with backspace,
+//! and the whole line handed back once Enter is pressed, for the application
+//! to split into words and run. The shell itself knows no command.
+//!
+//! Replies go out through `core::fmt::Write` on the serial port; the HAL
+//! writes block until each character is sent, about 87 us at 115200 baud, so
+//! keep them short inside an interrupt.
+//!
+//! # Checksummed frames
+//!
+//! A program on the PC, rather than a person, can end a line with `*` and
+//! the CRC-16/CCITT of the characters before it in four hex digits, the
+//! CRC unit computing it on the board: [`Shell::received`] checks and strips
+//! it, and a [`Frame`] ends the reply with the checksum of its characters
+//! on a line of its own, so a line garbled on the wire is rejected rather
+//! than run, and an empty reply still acknowledges the command:
+//!
+//! ```text
+//! > period 300*8491
+//! *FFFF
+//! > config*9C7D
+//! period 300 ms, pattern blink, brightness 100%
+//! *B544
+//! ```
+
+use core::fmt::{self, Write};
+
+use crate::crc::{Config, Crc};
+
+/// Characters in a line past which input is dropped.
+pub const LINE_LENGTH: usize = 64;
+
+/// The prompt, written by [`Shell::prompt`].
+pub const PROMPT: &str = "> ";
+
+/// Separator of the checksum at the end of a frame.
+pub const CHECKSUM_SEPARATOR: char = '*';
+
+/// The CRC of the frames, for the [`Crc`] given to [`Shell::received`]
+/// and [`Frame::new`].
+pub const FRAME_CRC: Config = Config::CRC16_CCITT;
+
+/// Shell errors.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    /// A checksum that is not four hex digits or not the CRC of the line.
+    Checksum,
+}
+
+/// A line, its checksum checked.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct Received<'a> {
+    /// The line without its checksum, trimmed.
+    pub line: &'a str,
+    /// The line came with a checksum: reply with a [`Frame`].
+    pub checked: bool,
+}
+
+/// What a received character did to the line.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Input {
+    /// Added to the line: echo it.
+    Char(u8),
+    /// Removed the last character: echo `"\x08 \x08"`.
+    Backspace,
+    /// Ignored: a control character, or the line is full.
+    Ignored,
+    /// Enter: the line is complete, get it with [`Shell::line`].
+    Line,
+}
+
+/// Line buffer of a serial shell.
+pub struct Shell {
+    buffer: [u8; LINE_LENGTH],
+    len: usize,
+    // Length of the line completed by the last Enter.
+    complete: Option<usize>,
+    // The last character was a carriage return.
+    after_cr: bool,
+}
+
+impl Shell {
+    /// An empty line, for a `static`.
+    pub const fn new() -> Self {
+        Shell { buffer: [0; LINE_LENGTH], len: 0, complete: None, after_cr: false }
+    }
+
+    /// Feeds one received character.
+    pub fn push(&mut self, byte: u8) -> Input {
+        // Terminals send Enter as CR, LF or both: one line either way.
+        let after_cr = core::mem::replace(&mut self.after_cr, byte == b'\r');
+        if byte == b'\n' && after_cr {
+            return Input::Ignored;
+        }
+        // A new line starts on the first character after Enter.
+        if self.complete.take().is_some() {
+            self.len = 0;
+        }
+        match byte {
+            b'\r' | b'\n' => {
+                self.complete = Some(self.len);
+                Input::Line
+            }
+            0x08 | 0x7f if self.len > 0 => {
+                self.len -= 1;
+                Input::Backspace
+            }
+            // Printable ASCII only, so the line is always a valid `str`.
+            0x20..=0x7e if self.len < LINE_LENGTH => {
+                self.buffer[self.len] = byte;
+                self.len += 1;
+                Input::Char(byte)
+            }
+            _ => Input::Ignored,
+        }
+    }
+
+    /// The line completed by the last Enter, trimmed, until the next
+    /// character.
+    pub fn line(&self) -> Option<&str> {
+        let len = self.complete?;
+        core::str::from_utf8(&self.buffer[..len]).ok().map(str::trim)
+    }
+
+    /// The line completed by the last Enter, as [`Shell::line`], its
+    /// checksum checked with `crc`, set to [`FRAME_CRC`], if it has one.
+    pub fn received(&self, crc: &mut Crc) -> Option<Result<Received<'_>, Error>> {
+        let line = self.line()?;
+        let Some((line, checksum)) = line.rsplit_once(CHECKSUM_SEPARATOR) else {
+            return Some(Ok(Received { line, checked: false }));
+        };
+        let valid = checksum.len() == 4
+            && checksum.bytes().all(|byte| byte.is_ascii_hexdigit())
+            && u32::from_str_radix(checksum, 16) == Ok(crc.checksum(line.as_bytes()));
+        if !valid {
+            return Some(Err(Error::Checksum));
+        }
+        Some(Ok(Received { line: line.trim(), checked: true }))
+    }
+
+    /// Echoes `input` on `out`, as a terminal expects.
+    pub fn echo<W: Write>(&self, out: &mut W, input: Input) -> fmt::Result {
+        match input {
+            Input::Char(byte) => out.write_char(byte as char),
+            Input::Backspace => out.write_str("\x08 \x08"),
+            Input::Line => out.write_str("\r\n"),
+            Input::Ignored => Ok(()),
+        }
+    }
+
+    /// Writes the prompt on `out`.
+    pub fn prompt<W: Write>(&self, out: &mut W) -> fmt::Result {
+        out.write_str(PROMPT)
+    }
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A reply to a checksummed line: what is written to it goes to `out`, and
+/// [`Frame::finish`] adds the line of its checksum.
+pub struct Frame<'a, W: Write> {
+    out: &'a mut W,
+    crc: &'a mut Crc,
+}
+
+impl<'a, W: Write> Frame<'a, W> {
+    /// Starts a reply on `out`, its checksum computed by `crc`, set to
+    /// [`FRAME_CRC`].
+    pub fn new(out: &'a mut W, crc: &'a mut Crc) -> Self {
+        crc.reset();
+        Frame { out, crc }
+    }
+
+    /// Writes the checksum of the reply, `*` and four hex digits, on a line.
+    pub fn finish(self) -> fmt::Result {
+        write!(self.out, "{}{:04X}\r\n", CHECKSUM_SEPARATOR, self.crc.result())
+    }
+}
+
+impl<W: Write> Write for Frame<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.crc.feed(s.as_bytes());
+        self.out.write_str(s)
+    }
+}
@@ -0,0 +1,122 @@
+//! 8-bit software PWM on any output pins, from a fast timer interrupt.
+//!
+//! Timer channels drive only the pins they are wired to; [`SoftPwm`] drives
+//! any: each call of [`SoftPwm::tick`] is one of the 256 steps of a PWM
+//! period, and sets high at step 0 the pins with a duty above zero, then low
+//! each pin at the step of its duty. A timer interrupting [`tick_rate`]
+//! times a second calls it, for a carrier of the given frequency: 100 Hz,
+//! no flicker to the eye, takes an interrupt every 39 µs.
+//!
+//! That is a lot of interrupts, and the step has to fit between two of them
+//! with time to spare for the rest of the program: [`budget`] is the cycles
+//! between two ticks, and a [`profile::Budget`] around the handler tells how
+//! much of it the tick takes in the worst case.
+//!
+//! New duties take effect at the start of the next period, so a period
+//! never shows half of the old duty and half of the new.
+//!
+//! [`profile::Budget`]: crate::profile::Budget
+
+use embedded_hal::digital::OutputPin;
+
+use stm32g4xx_hal as hal;
+
+use hal::time::Hertz;
+
+/// Steps of a period: duties go from 0, off, to 255, lit but for one step.
+pub const STEPS: u32 = 256;
+
+/// Software PWM errors.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    /// No channel of that index.
+    InvalidChannel,
+    /// A carrier of zero, or too fast for the core clock.
+    InvalidCarrier,
+}
+
+/// The tick rate for a carrier of `carrier`.
+pub const fn tick_rate(carrier: Hertz) -> Hertz {
+    Hertz(carrier.0 * STEPS)
+}
+
+/// The cycles of `sysclk` between two ticks, for a carrier of `carrier`.
+pub fn budget(carrier: Hertz, sysclk: Hertz) -> Result<u32, Error> {
+    let rate = tick_rate(carrier).0;
+    if rate == 0 || rate > sysclk.0 {
+        return Err(Error::InvalidCarrier);
+    }
+    Ok(sysclk.0 / rate)
+}
+
+/// `N` pins driven with a duty each.
+pub struct SoftPwm<P, const N: usize> {
+    pins: [P; N],
+    // The duties being set, and those of the running period.
+    duties: [u8; N],
+    active: [u8; N],
+    step: u8,
+}
+
+impl<P: OutputPin, const N: usize> SoftPwm<P, N> {
+    /// Takes the pins, all off.
+    pub fn new(mut pins: [P; N]) -> Self {
+        for pin in pins.iter_mut() {
+            pin.set_low().ok();
+        }
+        SoftPwm { pins, duties: [0; N], active: [0; N], step: 0 }
+    }
+
+    /// The number of channels.
+    pub const fn len(&self) -> usize {
+        N
+    }
+
+    /// Returns `true` with no channel.
+    pub const fn is_empty(&self) -> bool {
+        N == 0
+    }
+
+    /// Sets the duty of `channel`, out of 256, from the next period.
+    pub fn set_duty(&mut self, channel: usize, duty: u8) -> Result<(), Error> {
+        *self.duties.get_mut(channel).ok_or(Error::InvalidChannel)? = duty;
+        Ok(())
+    }
+
+    /// The duty of `channel` set last.
+    pub fn duty(&self, channel: usize) -> Result<u8, Error> {
+        self.duties.get(channel).copied().ok_or(Error::InvalidChannel)
+    }
+
+    /// Sets the duties of all channels, from the next period.
+    pub fn set_duties(&mut self, duties: [u8; N]) {
+        self.duties = duties;
+    }
+
+    /// One step of the period: call [`tick_rate`] times a second.
+    pub fn tick(&mut self) {
+        if self.step == 0 {
+            self.active = self.duties;
+            for (pin, &duty) in self.pins.iter_mut().zip(self.active.iter()) {
+                if duty > 0 {
+                    pin.set_high().ok();
+                }
+            }
+        } else {
+            for (pin, &duty) in self.pins.iter_mut().zip(self.active.iter()) {
+                if duty == self.step {
+                    pin.set_low().ok();
+                }
+            }
+        }
+        self.step = self.step.wrapping_add(1);
+    }
+
+    /// Sets all pins low and returns them.
+    pub fn release(mut self) -> [P; N] {
+        for pin in self.pins.iter_mut() {
+            pin.set_low().ok();
+        }
+        self.pins
+    }
+}
@@ -0,0 +1,275 @@
+//! SPI1 on the Arduino header and an SSD1306 OLED display driver.
+//!
+//! PA5, the usual SPI1 clock on D13, drives the user LED on the Nucleo, so
+//! [`spi1`] uses the alternative pins instead: SCK on D3 (PB3) and MOSI on D4
+//! (PB5). The displays only listen, so no MISO pin is taken. The chip
+//! select, data/command and reset lines of the display are ordinary outputs.
+//!
+//! [`Ssd1306`] drives a 128x64 SSD1306 module in 4-wire SPI mode. Text is
+//! drawn into a frame buffer in RAM with a 5x7 font, through
+//! [`core::fmt::Write`], and [`Ssd1306::flush`] sends the whole buffer to
+//! the display. It takes an `SpiDevice` of `embedded-hal` 1.0, the bus with
+//! the chip select: on this board a [`compat::ExclusiveDevice`] of the
+//! wrapped [`Spi1`] and pin.
+//!
+//! [`compat::ExclusiveDevice`]: crate::compat::ExclusiveDevice
+
+use core::fmt;
+
+use stm32g4xx_hal as hal;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
+use embedded_hal::spi::SpiDevice;
+
+use hal::gpio::{gpiob, Alternate, AF5};
+use hal::rcc::Rcc;
+use hal::spi::{NoMiso, Spi, SpiExt, MODE_0};
+use hal::stm32::SPI1;
+use hal::time::U32Ext;
+
+/// Pins of [`Spi1`]: SCK on PB3 and MOSI on PB5.
+pub type Spi1Pins = (gpiob::PB3<Alternate<AF5>>, NoMiso, gpiob::PB5<Alternate<AF5>>);
+
+/// SPI1, transmit only.
+pub type Spi1 = Spi<SPI1, Spi1Pins>;
+
+/// Configures SPI1 on PB3/PB5 in mode 0 at up to 8 MHz.
+///
+/// The HAL picks the highest clock not above 8 MHz that PCLK2 can divide to.
+pub fn spi1(spi: SPI1, sck: gpiob::PB3<Alternate<AF5>>, mosi: gpiob::PB5<Alternate<AF5>>, rcc: &mut Rcc) -> Spi1 {
+    spi.spi((sck, NoMiso, mosi), MODE_0, 8.mhz(), rcc)
+}
+
+/// Width of the display in pixels.
+pub const WIDTH: usize = 128;
+/// Height of the display in pixels.
+pub const HEIGHT: usize = 64;
+/// Text lines of 8 pixels.
+pub const LINES: usize = HEIGHT / 8;
+/// Characters per text line: 5 pixels of glyph and 1 of spacing each.
+pub const COLUMNS: usize = WIDTH / 6;
+
+/// Commands bringing the display from reset to on, with horizontal addressing.
+const INIT: [u8; 25] = [
+    0xAE, // display off
+    0xD5, 0x80, // oscillator frequency and clock divider
+    0xA8, 0x3F, // multiplex ratio, 64 rows
+    0xD3, 0x00, // no display offset
+    0x40, // start line 0
+    0x8D, 0x14, // charge pump on
+    0x20, 0x00, // horizontal addressing mode
+    0xA1, // columns mirrored
+    0xC8, // rows mirrored: together, upright on the usual modules
+    0xDA, 0x12, // alternative COM pin configuration
+    0x81, 0xCF, // contrast
+    0xD9, 0xF1, // pre-charge period
+    0xDB, 0x40, // VCOMH deselect level
+    0xA4, // display the RAM content
+    0xA6, // not inverted
+    0xAF, // display on
+];
+
+/// SSD1306 128x64 OLED display on SPI.
+pub struct Ssd1306<SPI, DC> {
+    spi: SPI,
+    dc: DC,
+    buffer: [u8; WIDTH * LINES],
+    column: usize,
+    line: usize,
+}
+
+impl<SPI, DC> Ssd1306<SPI, DC>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+{
+    /// Creates the driver with an empty frame buffer.
+    ///
+    /// Nothing is sent until [`init`](Self::init) is called.
+    pub fn new(spi: SPI, dc: DC) -> Self {
+        Ssd1306 { spi, dc, buffer: [0; WIDTH * LINES], column: 0, line: 0 }
+    }
+
+    /// Pulses the reset line of the display.
+    ///
+    /// Modules without a reset pin reset themselves on power-up.
+    pub fn reset<RST: OutputPin, D: DelayNs>(&mut self, rst: &mut RST, delay: &mut D) {
+        rst.set_low().ok();
+        delay.delay_ms(1);
+        rst.set_high().ok();
+        delay.delay_ms(1);
+    }
+
+    /// Switches the display on and shows the frame buffer.
+    pub fn init(&mut self) -> Result<(), SPI::Error> {
+        self.command(&INIT)?;
+        self.flush()
+    }
+
+    /// Clears the frame buffer and moves the cursor to the top left corner.
+    pub fn clear(&mut self) {
+        self.buffer = [0; WIDTH * LINES];
+        self.set_cursor(0, 0);
+    }
+
+    /// Moves the cursor to `column` (0 to [`COLUMNS`] - 1) of text `line` (0 to [`LINES`] - 1).
+    pub fn set_cursor(&mut self, column: usize, line: usize) {
+        self.column = column.min(COLUMNS);
+        self.line = line.min(LINES - 1);
+    }
+
+    /// Draws one character at the cursor and advances it.
+    ///
+    /// `'\n'` starts the next line. Text past the end of a line is dropped,
+    /// and characters outside printable ASCII are drawn as `?`.
+    pub fn draw_char(&mut self, c: char) {
+        if c == '\n' {
+            self.set_cursor(0, self.line + 1);
+            return;
+        }
+        if self.column == COLUMNS {
+            return;
+        }
+
+        let glyph = match c {
+            ' '..='~' => &FONT[c as usize - ' ' as usize],
+            _ => &FONT['?' as usize - ' ' as usize],
+        };
+        let start = self.line * WIDTH + self.column * 6;
+        self.buffer[start..start + 5].copy_from_slice(glyph);
+        self.buffer[start + 5] = 0;
+        self.column += 1;
+    }
+
+    /// Sends the frame buffer to the display.
+    ///
+    /// About 1 ms on an 8 MHz bus.
+    pub fn flush(&mut self) -> Result<(), SPI::Error> {
+        // Whole screen: columns 0 to 127, pages 0 to 7.
+        self.command(&[0x21, 0, (WIDTH - 1) as u8, 0x22, 0, (LINES - 1) as u8])?;
+        self.dc.set_high().ok();
+        self.spi.write(&self.buffer)
+    }
+
+    /// Returns the device and the data/command pin.
+    pub fn release(self) -> (SPI, DC) {
+        (self.spi, self.dc)
+    }
+
+    fn command(&mut self, bytes: &[u8]) -> Result<(), SPI::Error> {
+        self.dc.set_low().ok();
+        self.spi.write(bytes)
+    }
+}
+
+impl<SPI, DC> fmt::Write for Ssd1306<SPI, DC>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        s.chars().for_each(|c| self.draw_char(c));
+        Ok(())
+    }
+}
+
+/// 5x7 glyphs of printable ASCII, from ' ' to '~'. One byte per column,
+/// least significant bit at the top.
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // f
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // j
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // l
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // t
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // v
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
@@ -0,0 +1,89 @@
+//! Stack usage, measured by painting the stack.
+//!
+//! The stack runs from the top of RAM, `_stack_start` in the `link.x` of
+//! `cortex-m-rt`, down to the end of the statics, `_stack_end`. [`paint`],
+//! first thing in `main`, fills what lies below the stack pointer with
+//! [`PAINT`]. Every frame pushed later overwrites some of it, the frames of
+//! the interrupts included, and nothing paints it back, so the lowest word
+//! no longer painted is the deepest the stack has gone: the
+//! [`high_water_mark`]. The words never touched are the [`headroom`], which
+//! shrinks with each interrupt that can preempt another.
+//!
+//! A read walks the stack up from its bottom to the first word changed,
+//! close to 32k words on a board with 128 KiB of RAM and little of it in
+//! statics, so [`report`] belongs in the main loop, not in an interrupt.
+//!
+//! The high-water mark is a lower bound: a frame that reserves stack and
+//! leaves its deepest words unwritten goes unseen. Before [`paint`], the
+//! reads are meaningless and give the whole stack as used.
+
+/// The word the free stack is painted with.
+pub const PAINT: u32 = 0xCCCC_CCCC;
+
+/// Headroom below which [`report`] warns, in bytes.
+pub const LOW_HEADROOM: usize = 1024;
+
+unsafe extern "C" {
+    static _stack_start: u32;
+    static _stack_end: u32;
+}
+
+// The bottom and the top of the stack.
+fn bounds() -> (usize, usize) {
+    (&raw const _stack_end as usize, &raw const _stack_start as usize)
+}
+
+/// Paints the stack below the stack pointer with [`PAINT`].
+///
+/// Call it once, first thing in `main`: what is painted after the stack grew
+/// is not measured any more.
+pub fn paint() {
+    let (bottom, _) = bounds();
+    let sp = cortex_m::register::msp::read() as usize;
+    let mut word = bottom as *mut u32;
+    // Nothing lives below the stack pointer while this runs: an interrupt
+    // pushes its frame there and pops it before returning.
+    while (word as usize) < sp {
+        unsafe {
+            word.write_volatile(PAINT);
+            word = word.add(1);
+        }
+    }
+}
+
+/// The size of the stack, in bytes.
+pub fn size() -> usize {
+    let (bottom, top) = bounds();
+    top - bottom
+}
+
+/// The most the stack has used since [`paint`], in bytes.
+pub fn high_water_mark() -> usize {
+    let (bottom, top) = bounds();
+    let mut word = bottom as *const u32;
+    while (word as usize) < top && unsafe { word.read_volatile() } == PAINT {
+        word = unsafe { word.add(1) };
+    }
+    top - word as usize
+}
+
+/// The stack never used since [`paint`], in bytes.
+pub fn headroom() -> usize {
+    size() - high_water_mark()
+}
+
+/// The stack in use now, in bytes.
+pub fn current() -> usize {
+    let (_, top) = bounds();
+    top - cortex_m::register::msp::read() as usize
+}
+
+/// Logs the high-water mark and the headroom, a warning under [`LOW_HEADROOM`].
+pub fn report() {
+    let (used, size) = (high_water_mark(), size());
+    if size - used < LOW_HEADROOM {
+        defmt::warn!("Stack: {} of {} bytes used at most, {} free", used, size, size - used);
+    } else {
+        defmt::info!("Stack: {} of {} bytes used at most, {} free", used, size, size - used);
+    }
+}
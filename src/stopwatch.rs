@@ -0,0 +1,103 @@
+//! Stopwatch with laps, on the monotonic clock.
+//!
+//! [`Stopwatch`] takes the times of its events as [`Instant`]s, so an
+//! interrupt handler timestamps the event itself, a button press for
+//! example, with [`monotonic::now`](crate::monotonic::now) as the first thing
+//! it does: the lap times then do not depend on how long the handler took to
+//! get there or to log the last lap. It keeps the last `LAPS` split times.
+
+use crate::monotonic::Instant;
+
+/// A completed lap.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct Lap {
+    /// Laps since the start, from 1.
+    pub number: u32,
+    /// Microseconds since the previous lap, or since the start.
+    pub split_us: u64,
+    /// Microseconds since the start.
+    pub total_us: u64,
+}
+
+/// A stopwatch keeping the split times of its last `LAPS` laps.
+pub struct Stopwatch<const LAPS: usize> {
+    started: Option<Instant>,
+    last: Instant,
+    // The elapsed time when stopped.
+    stopped_us: u64,
+    laps: [u64; LAPS],
+    count: u32,
+}
+
+impl<const LAPS: usize> Stopwatch<LAPS> {
+    /// A stopped stopwatch at zero, for a `static`.
+    pub const fn new() -> Self {
+        Stopwatch { started: None, last: Instant::ZERO, stopped_us: 0, laps: [0; LAPS], count: 0 }
+    }
+
+    /// Starts from zero at `now`, dropping the laps of the last run.
+    pub fn start(&mut self, now: Instant) {
+        *self = Stopwatch::new();
+        self.started = Some(now);
+        self.last = now;
+    }
+
+    /// Returns `true` between [`Stopwatch::start`] and [`Stopwatch::stop`].
+    pub fn is_running(&self) -> bool {
+        self.started.is_some()
+    }
+
+    /// Ends a lap at `now`; `None` while stopped.
+    pub fn lap(&mut self, now: Instant) -> Option<Lap> {
+        let started = self.started?;
+        let split_us = now.since(self.last);
+        self.last = now;
+        if LAPS > 0 {
+            self.laps[self.count as usize % LAPS] = split_us;
+        }
+        self.count += 1;
+        Some(Lap { number: self.count, split_us, total_us: now.since(started) })
+    }
+
+    /// Stops at `now` and returns the total time; `None` if already stopped.
+    pub fn stop(&mut self, now: Instant) -> Option<u64> {
+        let started = self.started.take()?;
+        self.stopped_us = now.since(started);
+        Some(self.stopped_us)
+    }
+
+    /// Microseconds since the start, at `now`, or of the last run once stopped.
+    pub fn elapsed(&self, now: Instant) -> u64 {
+        match self.started {
+            Some(started) => now.since(started),
+            None => self.stopped_us,
+        }
+    }
+
+    /// The laps completed since the start.
+    pub fn laps(&self) -> u32 {
+        self.count
+    }
+
+    /// The split time of lap `number`, if one of the last `LAPS`.
+    pub fn split(&self, number: u32) -> Option<u64> {
+        if number == 0 || number > self.count || self.count - number >= LAPS as u32 {
+            return None;
+        }
+        Some(self.laps[(number - 1) as usize % LAPS])
+    }
+
+    /// The fastest of the laps kept, with its number.
+    pub fn best(&self) -> Option<(u32, u64)> {
+        let first = self.count.saturating_sub(LAPS as u32) + 1;
+        (first..=self.count)
+            .filter_map(|number| self.split(number).map(|split| (number, split)))
+            .min_by_key(|&(_, split)| split)
+    }
+}
+
+impl<const LAPS: usize> Default for Stopwatch<LAPS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,180 @@
+//! Supply monitoring: the brown-out reset level and the PVD.
+//!
+//! Two detectors watch VDD. The brown-out reset holds the chip in reset
+//! below its level, set in the option bytes: [`set_bor_level`] writes it,
+//! for the next reset. The PVD, the programmable voltage detector, fires
+//! first, at a level above it set at run time with [`Pvd::enable`], as EXTI
+//! line 16 on the `PVD_PVM` vector: on both edges, VDD dipping under the
+//! level and coming back over it.
+//!
+//! The program writes the vector and calls [`Pvd::on_interrupt`] for the
+//! [`Event`]. On the dip it makes its outputs safe, a motor stopped, a
+//! relay open, then [`park`]s: a warning over defmt, the interrupts off and
+//! the core asleep, until the supply comes back and the chip resets or it
+//! keeps falling and the brown-out reset takes over. The handler cannot be
+//! preempted by its own vector, which a `wfi` would wait for: the core
+//! sleeps in a `wfe` instead, woken by any interrupt going pending with
+//! SEVONPEND set, [`sleep_while`]. A flaky USB supply
+//! then restarts the demo from reset, with no run on a core below its
+//! rated voltage:
+//!
+//! ```text
+//! #[interrupt]
+//! fn PVD_PVM() {
+//!     if pvd.on_interrupt() == Event::Low {
+//!         led.set_low().ok();
+//!         supply::park();
+//!     }
+//! }
+//! ```
+
+use stm32g4xx_hal as hal;
+
+use hal::rcc::Enable;
+use hal::stm32::{EXTI, FLASH, Interrupt, PWR, RCC};
+
+use cortex_m::peripheral::{NVIC, SCB};
+
+use crate::optionbytes::{self, BorLevel, Changes};
+
+/// The PVD threshold, on a falling VDD; it clears some 100 mV higher.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum PvdLevel {
+    /// About 2.0 V.
+    Level0 = 0,
+    /// About 2.15 V.
+    Level1 = 1,
+    /// About 2.3 V.
+    Level2 = 2,
+    /// About 2.45 V.
+    Level3 = 3,
+    /// About 2.6 V.
+    Level4 = 4,
+    /// About 2.75 V.
+    Level5 = 5,
+    /// About 2.85 V.
+    Level6 = 6,
+    /// The PVD_IN pin, PB7, against 1.2 V.
+    External = 7,
+}
+
+/// A crossing of the PVD level.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Event {
+    /// VDD fell under the level.
+    Low,
+    /// VDD rose back over it.
+    Restored,
+}
+
+// SEVONPEND in SCB.SCR.
+const SEVONPEND: u32 = 1 << 4;
+
+// EXTI line of the PVD.
+const PVD_LINE: u32 = 1 << 16;
+
+/// The brown-out reset level of the option bytes.
+pub fn bor_level() -> BorLevel {
+    optionbytes::read().bor_level
+}
+
+/// Writes `level` into the option bytes, for the next reset or
+/// `optionbytes::reload`; returns `true` if it changed.
+pub fn set_bor_level(flash: &mut FLASH, level: BorLevel) -> Result<bool, optionbytes::Error> {
+    if bor_level() == level {
+        return Ok(false);
+    }
+    optionbytes::write(flash, Changes { bor_level: Some(level), ..Changes::default() })?;
+    Ok(true)
+}
+
+fn pwr() -> &'static hal::stm32::pwr::RegisterBlock {
+    unsafe {
+        //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+        let rcc = &(*RCC::ptr());
+        PWR::enable(rcc);
+        &(*PWR::ptr())
+    }
+}
+
+/// Returns `true` while VDD is under the PVD level.
+pub fn is_low() -> bool {
+    pwr().sr2.read().pvdo().bit_is_set()
+}
+
+/// The PVD, interrupting on both crossings of its level.
+pub struct Pvd {
+    level: PvdLevel,
+}
+
+impl Pvd {
+    /// Turns the PVD on at `level`, its EXTI line on both edges and its
+    /// vector unmasked.
+    pub fn enable(level: PvdLevel, exti: &mut EXTI) -> Self {
+        let pwr = pwr();
+        pwr.cr2.modify(|_, w| unsafe { w.pls().bits(level as u8) });
+        pwr.cr2.modify(|_, w| w.pvde().set_bit());
+
+        // PVDO rises as VDD falls.
+        exti.rtsr1.modify(|_, w| w.rt16().set_bit());
+        exti.ftsr1.modify(|_, w| w.ft16().set_bit());
+        exti.pr1.write(|w| w.pif16().set_bit());
+        exti.imr1.modify(|_, w| w.im16().set_bit());
+        unsafe { NVIC::unmask(Interrupt::PVD_PVM) };
+        Pvd { level }
+    }
+
+    /// The level set.
+    pub fn level(&self) -> PvdLevel {
+        self.level
+    }
+
+    /// Clears the line and tells which way VDD crossed: the body of the
+    /// `PVD_PVM` interrupt.
+    pub fn on_interrupt(&mut self) -> Event {
+        clear_line();
+        if is_low() { Event::Low } else { Event::Restored }
+    }
+
+    /// Turns the PVD off.
+    pub fn disable(self, exti: &mut EXTI) {
+        NVIC::mask(Interrupt::PVD_PVM);
+        exti.imr1.modify(|_, w| w.im16().clear_bit());
+        pwr().cr2.modify(|_, w| w.pvde().clear_bit());
+    }
+}
+
+fn clear_line() {
+    // NOTE(unsafe) write of the line's pending bit alone
+    unsafe { (*EXTI::ptr()).pr1.write(|w| w.bits(PVD_LINE)) };
+}
+
+/// Parks the chip on a low supply: warns, turns the interrupts off and
+/// sleeps until VDD is back over the PVD level, then resets. Make the
+/// outputs safe first.
+pub fn park() -> ! {
+    defmt::warn!("VDD under the PVD level: parked");
+    cortex_m::interrupt::disable();
+    sleep_while(Interrupt::PVD_PVM, || {
+        clear_line();
+        is_low()
+    });
+    SCB::sys_reset();
+}
+
+/// Sleeps while `asleep` returns `true`, woken each time `interrupt` goes
+/// pending, masked, disabled or of a priority the running handler blocks:
+/// a `wfe` with SEVONPEND set, where a `wfi` would only wake for an
+/// interrupt that preempts.
+pub fn sleep_while(interrupt: Interrupt, mut asleep: impl FnMut() -> bool) {
+    // NOTE(unsafe) read-modify-write of SCR, from a handler or with the
+    // interrupts off.
+    unsafe { (*SCB::PTR).scr.modify(|scr| scr | SEVONPEND) };
+    while asleep() {
+        // Pending no more, so its next request is a new event.
+        NVIC::unpend(interrupt);
+        if asleep() {
+            cortex_m::asm::wfe();
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! defmt logs over ITM and SWO, instead of RTT, with the `swo` feature.
+//!
+//! Some debug tooling reads the trace output of the core rather than RTT's
+//! buffer in RAM. With the `swo` feature the crate's defmt logger writes
+//! each log frame to stimulus port 0 of the ITM, which the TPIU sends out of
+//! the SWO pin, PB3 (its function out of reset), to the ST-LINK. The host
+//! side reads port 0 at [`BAUD`] and decodes the frames with `defmt-print`.
+//!
+//! The TPIU divides the core clock down to the baud rate: [`init`] sets the
+//! trace up with the prescaler for the clocks in use, and [`set_clock`]
+//! changes it after each change of the system clock, `clocks::freeze`,
+//! `clocks::set_sysclk` or a clock failure. [`BAUD`] divides both the 16 MHz
+//! HSI and the 170 MHz PLL; another clock gets the closest rate, which the
+//! host has to match.
+//!
+//! Until [`init`] runs, and while the debugger has the ITM off, the logs are
+//! dropped.
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use cortex_m::peripheral::{DCB, ITM, TPIU};
+
+use stm32g4xx_hal as hal;
+
+use hal::rcc::Clocks;
+use hal::stm32::DBGMCU;
+
+/// SWO baud rate, within what the ST-LINK of every board reads.
+pub const BAUD: u32 = 2_000_000;
+
+// Unlock key of the ITM registers.
+const ITM_KEY: u32 = 0xC5AC_CE55;
+
+// ITM_TCR: trace bus ID 1, synchronisation packets and the ITM on; busy.
+const TCR_ENABLE: u32 = 1 << 16 | 1 << 2 | 1;
+const TCR_BUSY: u32 = 1 << 23;
+
+// TPIU: the SWO in NRZ (UART) encoding, and the formatter bypassed.
+const SPPR_NRZ: u32 = 0b10;
+const FFCR_BYPASS: u32 = 0x100;
+
+/// Sends the ITM out of the SWO pin at [`BAUD`], for the core clock of
+/// `clocks`.
+pub fn init(dcb: &mut DCB, itm: &mut ITM, tpiu: &mut TPIU, dbgmcu: &DBGMCU, clocks: &Clocks) {
+    dcb.enable_trace();
+    // The trace pins on, in asynchronous mode: the SWO alone.
+    dbgmcu.cr.modify(|_, w| unsafe { w.trace_ioen().set_bit().trace_mode().bits(0) });
+    unsafe {
+        tpiu.sppr.write(SPPR_NRZ);
+        tpiu.ffcr.write(FFCR_BYPASS);
+        tpiu.acpr.write(prescaler(clocks.core_clk.0));
+        itm.lar.write(ITM_KEY);
+        itm.tcr.write(TCR_ENABLE);
+        // Port 0 on, and open to unprivileged code.
+        itm.ter[0].write(1);
+        itm.tpr.write(0);
+    }
+}
+
+/// Changes the SWO prescaler for the core clock of `clocks`, once the ITM
+/// has sent what it holds.
+pub fn set_clock(itm: &ITM, tpiu: &mut TPIU, clocks: &Clocks) {
+    while itm.tcr.read() & TCR_BUSY != 0 {}
+    unsafe { tpiu.acpr.write(prescaler(clocks.core_clk.0)) }
+}
+
+// The ACPR value dividing `core` Hz down closest to `BAUD`.
+fn prescaler(core: u32) -> u32 {
+    ((core + BAUD / 2) / BAUD).max(1) - 1
+}
+
+#[defmt::global_logger]
+struct Logger;
+
+// The logger's state, behind the critical section `acquire` holds.
+struct State {
+    taken: AtomicBool,
+    restore: UnsafeCell<critical_section::RestoreState>,
+    encoder: UnsafeCell<defmt::Encoder>,
+}
+
+// NOTE(unsafe) the cells are only used between `acquire` and `release`
+unsafe impl Sync for State {}
+
+static STATE: State = State {
+    taken: AtomicBool::new(false),
+    restore: UnsafeCell::new(critical_section::RestoreState::invalid()),
+    encoder: UnsafeCell::new(defmt::Encoder::new()),
+};
+
+// Writes `bytes` to port 0, unless the ITM or the port is off: a write
+// there would wait for ever.
+fn emit(bytes: &[u8]) {
+    // NOTE(unsafe) the logger is the only user of port 0
+    let itm = unsafe { &mut *ITM::PTR };
+    if itm.tcr.read() & 1 == 0 || itm.ter[0].read() & 1 == 0 {
+        return;
+    }
+    cortex_m::itm::write_all(&mut itm.stim[0], bytes);
+}
+
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {
+        let restore = unsafe { critical_section::acquire() };
+        if STATE.taken.load(Ordering::Relaxed) {
+            panic!("defmt logger taken reentrantly");
+        }
+        STATE.taken.store(true, Ordering::Relaxed);
+        unsafe {
+            STATE.restore.get().write(restore);
+            (*STATE.encoder.get()).start_frame(emit);
+        }
+    }
+
+    unsafe fn flush() {
+        // NOTE(unsafe) atomic read with no side effects
+        while unsafe { (*ITM::PTR).tcr.read() } & TCR_BUSY != 0 {}
+    }
+
+    unsafe fn release() {
+        unsafe {
+            (*STATE.encoder.get()).end_frame(emit);
+            STATE.taken.store(false, Ordering::Relaxed);
+            critical_section::release(STATE.restore.get().read());
+        }
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        unsafe { (*STATE.encoder.get()).write(bytes, emit) }
+    }
+}
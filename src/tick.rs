@@ -0,0 +1,221 @@
+//! The millisecond tick, on SysTick or on TIM6.
+//!
+//! The debouncers, the timer wheel and the sampling loops of the examples
+//! want a call every millisecond and the milliseconds since boot. Where the
+//! tick comes from is the program's choice: an RTOS keeps SysTick for
+//! itself, and a bare-metal program may spend every general-purpose timer on
+//! PWM. Both sources implement [`TickSource`]; the `tick-tim6` feature picks
+//! TIM6, the basic timer, as the [`Source`] of the build, SysTick otherwise.
+//!
+//! [`tick_handler!`] defines the interrupt of that source, the `SysTick`
+//! exception or `TIM6_DACUNDER`: it counts the millisecond for [`now_ms`]
+//! and calls the handler set with [`on_tick`]. Code written against these
+//! two runs unchanged on either source:
+//!
+//! ```text
+//! nucleo_g474re::tick_handler!();
+//!
+//! tick::Source::new(cp.SYST).start(&rcc.clocks); // or dp.TIM6, with `tick-tim6`
+//! tick::on_tick(sample_buttons);
+//! let now = tick::now_ms();
+//! ```
+//!
+//! The count is 32 bits of milliseconds, wrapping after 49 days, as the
+//! debouncers expect. For microseconds there are the `monotonic` and
+//! `timebase` modules. SysTick is `monotonic`'s: [`SysTickSource`] starts
+//! it through [`monotonic::start`](crate::monotonic::start), and the tick
+//! counts its millisecond as well, so a program reads both clocks with the
+//! one handler of [`tick_handler!`].
+
+use core::cell::Cell;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use cortex_m::interrupt::Mutex;
+use cortex_m::peripheral::{NVIC, SYST};
+
+use stm32g4xx_hal as hal;
+
+use hal::rcc::{Clocks, Enable, GetBusFreq, Reset};
+use hal::stm32::{Interrupt, RCC, TIM6};
+
+/// Ticks per second.
+pub const TICK_HZ: u32 = 1000;
+
+/// A tick handler.
+pub type Handler = fn();
+
+/// The tick source of the build.
+#[cfg(not(feature = "tick-tim6"))]
+pub type Source = SysTickSource;
+/// The tick source of the build.
+#[cfg(feature = "tick-tim6")]
+pub type Source = Tim6Source;
+
+// Milliseconds counted by `tick`.
+static MILLIS: AtomicU32 = AtomicU32::new(0);
+// Create a Global Variable for the tick handler.
+static HANDLER: Mutex<Cell<Option<Handler>>> = Mutex::new(Cell::new(None));
+
+/// A timer interrupting every millisecond.
+pub trait TickSource {
+    /// Starts the ticks, the count from zero.
+    fn start(&mut self, clocks: &Clocks);
+
+    /// Stops the ticks; the count keeps its value.
+    fn stop(&mut self);
+}
+
+/// SysTick on the core clock, HCLK, as the `monotonic` clock.
+pub struct SysTickSource {
+    syst: SYST,
+}
+
+impl SysTickSource {
+    pub fn new(syst: SYST) -> Self {
+        SysTickSource { syst }
+    }
+
+    /// Stops the ticks and returns SysTick.
+    pub fn release(mut self) -> SYST {
+        self.stop();
+        self.syst
+    }
+}
+
+impl TickSource for SysTickSource {
+    fn start(&mut self, clocks: &Clocks) {
+        MILLIS.store(0, Ordering::Release);
+        crate::monotonic::start(&mut self.syst, clocks.core_clk);
+    }
+
+    fn stop(&mut self) {
+        self.syst.disable_interrupt();
+        self.syst.disable_counter();
+    }
+}
+
+/// TIM6, the basic timer, counting microseconds up to a thousand.
+pub struct Tim6Source {
+    tim: TIM6,
+}
+
+impl Tim6Source {
+    pub fn new(tim: TIM6) -> Self {
+        Tim6Source { tim }
+    }
+
+    /// Stops the ticks and returns TIM6.
+    pub fn release(mut self) -> TIM6 {
+        self.stop();
+        self.tim
+    }
+}
+
+impl TickSource for Tim6Source {
+    fn start(&mut self, clocks: &Clocks) {
+        MILLIS.store(0, Ordering::Release);
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            TIM6::enable(rcc);
+            TIM6::reset(rcc);
+        }
+        let psc = TIM6::get_timer_frequency(clocks).0 / 1_000_000 - 1;
+        self.tim.psc.write(|w| unsafe { w.psc().bits(psc as u16) });
+        self.tim.arr.write(|w| unsafe { w.bits(1_000_000 / TICK_HZ - 1) });
+        // Load the prescaler with an update, clearing its flag after.
+        self.tim.egr.write(|w| w.ug().set_bit());
+        self.tim.sr.write(|w| w.uif().clear_bit());
+        self.tim.dier.write(|w| w.uie().set_bit());
+        self.tim.cr1.modify(|_, w| w.cen().set_bit());
+        unsafe { NVIC::unmask(Interrupt::TIM6_DACUNDER) };
+    }
+
+    fn stop(&mut self) {
+        NVIC::mask(Interrupt::TIM6_DACUNDER);
+        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+        self.tim.dier.write(|w| w.uie().clear_bit());
+    }
+}
+
+/// Calls `handler` on every tick, after the count, in place of the handler
+/// before it, which is returned.
+pub fn on_tick(handler: Handler) -> Option<Handler> {
+    cortex_m::interrupt::free(|cs| HANDLER.borrow(cs).replace(Some(handler)))
+}
+
+/// Removes the tick handler and returns it.
+pub fn clear() -> Option<Handler> {
+    cortex_m::interrupt::free(|cs| HANDLER.borrow(cs).take())
+}
+
+/// Milliseconds since the source started, modulo 2^32.
+pub fn now_ms() -> u32 {
+    MILLIS.load(Ordering::Acquire)
+}
+
+/// Milliseconds since `since`, a reading of [`now_ms`].
+pub fn elapsed_ms(since: u32) -> u32 {
+    now_ms().wrapping_sub(since)
+}
+
+/// Counts a millisecond, for `monotonic` too on SysTick, and calls the tick
+/// handler: the body of the source's interrupt, for a program writing its
+/// own.
+pub fn tick() {
+    #[cfg(not(feature = "tick-tim6"))]
+    crate::monotonic::tick();
+    MILLIS.fetch_add(1, Ordering::AcqRel);
+    if let Some(handler) = cortex_m::interrupt::free(|cs| HANDLER.borrow(cs).get()) {
+        handler();
+    }
+}
+
+#[doc(hidden)]
+pub fn __clear_tim6() {
+    // NOTE(unsafe) write of the flag alone, from its own interrupt
+    unsafe { (*TIM6::ptr()).sr.write(|w| w.uif().clear_bit()) };
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use cortex_m_rt::exception;
+    pub use stm32g4xx_hal::interrupt;
+}
+
+/// Defines the interrupt handler of the build's [`Source`], counting the
+/// ticks and calling the [`on_tick`] handler. Invoke once, at the top level
+/// of the program.
+#[cfg(not(feature = "tick-tim6"))]
+#[macro_export]
+macro_rules! tick_handler {
+    () => {
+        mod __tick_handler {
+            use $crate::tick::__private::exception;
+
+            #[exception]
+            fn SysTick() {
+                $crate::tick::tick();
+            }
+        }
+    };
+}
+
+/// Defines the interrupt handler of the build's [`Source`], counting the
+/// ticks and calling the [`on_tick`] handler. Invoke once, at the top level
+/// of the program.
+#[cfg(feature = "tick-tim6")]
+#[macro_export]
+macro_rules! tick_handler {
+    () => {
+        mod __tick_handler {
+            use $crate::tick::__private::interrupt;
+
+            #[interrupt]
+            fn TIM6_DACUNDER() {
+                $crate::tick::__clear_tim6();
+                $crate::tick::tick();
+            }
+        }
+    };
+}
@@ -0,0 +1,49 @@
+//! Microseconds on TIM2, counting free on its 32 bits.
+//!
+//! [`start`] takes TIM2 for good, the blink's usual timer, and runs it at
+//! 1 MHz with the largest reload: its counter is a timestamp in
+//! microseconds, read in a single load with no interrupt behind it.
+//! [`micros`] reads it and [`elapsed_us`] takes the time since an earlier
+//! reading, across the wrap at 2^32 microseconds, about 71 minutes: an
+//! interval shorter than that is always right. The blink moves to another
+//! timer, TIM3 in the `micros_timebase` example.
+//!
+//! The prescaler comes from the clocks of [`start`]: after a change of the
+//! system clock the counter runs at another rate. For 64-bit timestamps
+//! from SysTick, there is the `monotonic` module.
+
+use stm32g4xx_hal as hal;
+
+use hal::rcc::{Clocks, Enable, GetBusFreq, Reset};
+use hal::stm32::{RCC, TIM2};
+
+/// Counter ticks per second.
+pub const TICK_HZ: u32 = 1_000_000;
+
+/// Runs TIM2 free at one tick a microsecond, from zero.
+pub fn start(tim: TIM2, clocks: &Clocks) {
+    unsafe {
+        //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+        let rcc = &(*RCC::ptr());
+        TIM2::enable(rcc);
+        TIM2::reset(rcc);
+    }
+    let psc = TIM2::get_timer_frequency(clocks).0 / TICK_HZ - 1;
+    tim.psc.write(|w| unsafe { w.psc().bits(psc as u16) });
+    tim.arr.write(|w| unsafe { w.bits(u32::MAX) });
+    // The prescaler is loaded on an update event: one now, which also
+    // clears the counter.
+    tim.egr.write(|w| w.ug().set_bit());
+    tim.cr1.modify(|_, w| w.cen().set_bit());
+}
+
+/// Microseconds since [`start`], modulo 2^32.
+pub fn micros() -> u32 {
+    // NOTE(unsafe) atomic read with no side effects
+    unsafe { (*TIM2::ptr()).cnt.read().bits() }
+}
+
+/// Microseconds since `since`, a reading of [`micros`].
+pub fn elapsed_us(since: u32) -> u32 {
+    micros().wrapping_sub(since)
+}
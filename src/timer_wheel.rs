@@ -0,0 +1,176 @@
+//! Software timer wheel: many one-shot timers on a single periodic tick.
+//!
+//! The wheel itself is in the `logic` crate, tested on the host, and
+//! re-exported here with the SysTick driver of its tickless mode.
+//!
+//! A hardware timer per delay runs out quickly, and most delays of an
+//! application (a beep, a debounce, a timeout) need no better than a
+//! millisecond. [`TimerWheel`] keeps up to `TIMERS` pending events on a ring
+//! of `SLOTS` slots, one slot per tick: a timer due in `ticks` sits in the
+//! slot the cursor reaches then, and counts the turns of the ring it still
+//! has to wait. Call [`TimerWheel::tick`] from a periodic interrupt, SysTick
+//! for example, then take the events that fell due with
+//! [`TimerWheel::expired`] until it returns `None`. Shared with other
+//! interrupts, the wheel goes in a `Mutex` like any other global.
+//!
+//! Events are plain values, an enum of the application for example, handed
+//! back as they were scheduled; an event can schedule the next one while it
+//! is being handled.
+//!
+//! # Tickless
+//!
+//! A tick every millisecond wakes the core a thousand times a second, for
+//! nothing most of the time. [`Tickless`] runs SysTick as a one-shot timer
+//! instead: [`Tickless::program`], called before `wfi`, sets it to run out
+//! at the nearest deadline of the wheel, and stops it with no timer
+//! pending. When it runs out, [`Tickless::expire`] moves the wheel on by all
+//! the ticks of the period at once; the events fall due on the same ticks as
+//! with a periodic tick, and a timer scheduled in the middle of a period
+//! first brings the wheel up to date with [`Tickless::catch_up`].
+//!
+//! ```text
+//! loop {
+//!     // Programmed and asleep with interrupts masked: an interrupt in
+//!     // between still wakes the `wfi`, and is taken after it.
+//!     cortex_m::interrupt::free(|cs| {
+//!         TICKLESS.borrow(cs).borrow_mut().program(&mut WHEEL.borrow(cs).borrow_mut());
+//!         cortex_m::asm::wfi();
+//!     });
+//! }
+//! ```
+
+use cortex_m::peripheral::syst::SystClkSource;
+use cortex_m::peripheral::{SCB, SYST};
+
+use stm32g4xx_hal as hal;
+
+use hal::time::Hertz;
+
+pub use nucleo_g474re_logic::timer_wheel::{Error, TimerWheel};
+
+/// SysTick as a one-shot timer for the next deadline of a [`TimerWheel`] of
+/// millisecond ticks.
+pub struct Tickless {
+    syst: SYST,
+    cycles_per_tick: u32,
+    // Ticks of the running period, those the wheel has had of them, and the
+    // cycles of its first tick that had elapsed before it started.
+    programmed: u32,
+    advanced: u32,
+    offset: u32,
+    running: bool,
+}
+
+impl Tickless {
+    /// Takes SysTick, stopped until the first [`Tickless::program`].
+    /// `sysclk` is the core clock.
+    pub fn new(mut syst: SYST, sysclk: Hertz) -> Self {
+        syst.disable_counter();
+        syst.set_clock_source(SystClkSource::Core);
+        syst.enable_interrupt();
+        Tickless {
+            syst,
+            cycles_per_tick: sysclk.0 / 1000,
+            programmed: 0,
+            advanced: 0,
+            offset: 0,
+            running: false,
+        }
+    }
+
+    /// Returns `true` while a period runs.
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// The longest period SysTick can count, in ticks.
+    pub fn max_ticks(&self) -> u32 {
+        (1 << 24) / self.cycles_per_tick
+    }
+
+    /// Moves `wheel` on by the ticks elapsed in the running period: call
+    /// before scheduling from an interrupt, so the timer counts from now.
+    pub fn catch_up<E: Copy + PartialEq, const SLOTS: usize, const TIMERS: usize>(
+        &mut self,
+        wheel: &mut TimerWheel<E, SLOTS, TIMERS>,
+    ) {
+        if !self.running {
+            return;
+        }
+        let ticks = if SCB::is_pendst_pending() {
+            self.programmed
+        } else {
+            (self.elapsed() / self.cycles_per_tick).min(self.programmed)
+        };
+        wheel.advance(ticks - self.advanced);
+        self.advanced = ticks;
+    }
+
+    /// Moves `wheel` on by the rest of the period that ran out and stops
+    /// SysTick: call from the SysTick handler, then take the events that
+    /// fell due.
+    pub fn expire<E: Copy + PartialEq, const SLOTS: usize, const TIMERS: usize>(
+        &mut self,
+        wheel: &mut TimerWheel<E, SLOTS, TIMERS>,
+    ) {
+        if !self.running {
+            return;
+        }
+        wheel.advance(self.programmed - self.advanced);
+        self.stop();
+    }
+
+    /// Sets SysTick to run out at the nearest deadline of `wheel`, or stops
+    /// it with no timer pending: call before `wfi`, with interrupts masked.
+    pub fn program<E: Copy + PartialEq, const SLOTS: usize, const TIMERS: usize>(
+        &mut self,
+        wheel: &mut TimerWheel<E, SLOTS, TIMERS>,
+    ) {
+        // The period ran out, and the handler programs nothing: the call
+        // after it does.
+        if SCB::is_pendst_pending() {
+            return;
+        }
+        self.catch_up(wheel);
+        let Some(next) = wheel.next_due() else {
+            self.stop();
+            return;
+        };
+        let next = next.clamp(1, self.max_ticks());
+        if self.running && next == self.programmed - self.advanced {
+            return;
+        }
+
+        // The new period starts where the current tick is, not at its start.
+        let fraction = if self.running { self.elapsed() % self.cycles_per_tick } else { 0 };
+        self.syst.disable_counter();
+        if SCB::is_pendst_pending() {
+            // Run out meanwhile: the period is the handler's.
+            self.syst.enable_counter();
+            return;
+        }
+        self.syst.set_reload(next * self.cycles_per_tick - fraction - 1);
+        self.syst.clear_current();
+        self.syst.enable_counter();
+        self.programmed = next;
+        self.advanced = 0;
+        self.offset = fraction;
+        self.running = true;
+    }
+
+    /// Stops SysTick and returns it.
+    pub fn release(mut self) -> SYST {
+        self.stop();
+        self.syst
+    }
+
+    fn stop(&mut self) {
+        self.syst.disable_counter();
+        self.running = false;
+    }
+
+    // Cycles since the start of the first tick of the period.
+    fn elapsed(&self) -> u32 {
+        SYST::get_reload() - SYST::get_current() + self.offset
+    }
+}
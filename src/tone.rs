@@ -0,0 +1,204 @@
+//! Tones and melodies on a passive buzzer, from TIM3 or TIM4 PWM.
+//!
+//! A passive buzzer (a piezo disc or a small magnetic transducer, without
+//! its own oscillator) sounds at the frequency it is driven with. [`Buzzer`]
+//! drives it with a square wave from channel 1 of a general-purpose timer:
+//!
+//! | Timer | CH1 pin   |
+//! |-------|-----------|
+//! | TIM3  | PA6 (D12) |
+//! | TIM4  | PB6 (D10) |
+//!
+//! The other side of the buzzer goes to ground; a magnetic one draws more
+//! than a pin can give and needs a transistor. The note constants cover
+//! two octaves around A4, in equal temperament rounded to the hertz.
+//!
+//! [`Melody`] steps through a list of (note, duration) pairs: each call to
+//! [`Melody::play_next`] starts the next note and returns how long it lasts,
+//! for the caller to schedule the following call, with the `timer_wheel` for
+//! example.
+
+use core::ops::Deref;
+
+use stm32g4xx_hal as hal;
+
+use hal::gpio::{gpioa, gpiob, Alternate, AF2};
+use hal::rcc::{Enable, Reset};
+use hal::stm32::{tim3, RCC, TIM3, TIM4};
+use hal::time::Hertz;
+
+/// No sound, for the pauses of a [`Melody`].
+pub const REST: Hertz = Hertz(0);
+pub const C4: Hertz = Hertz(262);
+pub const CS4: Hertz = Hertz(277);
+pub const D4: Hertz = Hertz(294);
+pub const DS4: Hertz = Hertz(311);
+pub const E4: Hertz = Hertz(330);
+pub const F4: Hertz = Hertz(349);
+pub const FS4: Hertz = Hertz(370);
+pub const G4: Hertz = Hertz(392);
+pub const GS4: Hertz = Hertz(415);
+pub const A4: Hertz = Hertz(440);
+pub const AS4: Hertz = Hertz(466);
+pub const B4: Hertz = Hertz(494);
+pub const C5: Hertz = Hertz(523);
+pub const CS5: Hertz = Hertz(554);
+pub const D5: Hertz = Hertz(587);
+pub const DS5: Hertz = Hertz(622);
+pub const E5: Hertz = Hertz(659);
+pub const F5: Hertz = Hertz(698);
+pub const FS5: Hertz = Hertz(740);
+pub const G5: Hertz = Hertz(784);
+pub const GS5: Hertz = Hertz(831);
+pub const A5: Hertz = Hertz(880);
+pub const AS5: Hertz = Hertz(932);
+pub const B5: Hertz = Hertz(988);
+pub const C6: Hertz = Hertz(1047);
+
+/// A note and how long it lasts, in milliseconds.
+pub type Note = (Hertz, u16);
+
+/// Channel 1 output pin of TIM3.
+pub type Tim3Pin = gpioa::PA6<Alternate<AF2>>;
+
+/// Channel 1 output pin of TIM4.
+pub type Tim4Pin = gpiob::PB6<Alternate<AF2>>;
+
+/// A general-purpose timer for the buzzer, with its pin.
+pub trait Instance: Deref<Target = tim3::RegisterBlock> + Enable + Reset {
+    /// CH1.
+    type Pin;
+}
+
+impl Instance for TIM3 {
+    type Pin = Tim3Pin;
+}
+
+impl Instance for TIM4 {
+    type Pin = Tim4Pin;
+}
+
+/// Passive buzzer on channel 1 of TIM3 or TIM4.
+pub struct Buzzer<TIM: Instance> {
+    tim: TIM,
+    pin: TIM::Pin,
+    clock: Hertz,
+}
+
+impl<TIM: Instance> Buzzer<TIM> {
+    /// Sets up the timer, silent.
+    ///
+    /// `clock` is the timer kernel clock, `rcc.clocks.apb1_tim_clk`.
+    pub fn new(tim: TIM, pin: TIM::Pin, clock: Hertz) -> Self {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            TIM::enable(rcc);
+            TIM::reset(rcc);
+        }
+
+        // Output held low until the first tone.
+        tim.ccmr1_output().write(|w| w.oc1m().force_inactive().oc1pe().set_bit());
+        tim.ccer.write(|w| w.cc1e().set_bit());
+        tim.cr1.write(|w| w.arpe().set_bit());
+        Buzzer { tim, pin, clock }
+    }
+
+    /// Sounds `frequency`, at 50% duty, until the next tone or
+    /// [`Buzzer::silence`]. [`REST`] is silence.
+    pub fn tone(&mut self, frequency: Hertz) {
+        if frequency.0 == 0 {
+            self.silence();
+            return;
+        }
+
+        let ticks = self.clock.0 / frequency.0;
+        let psc = ticks.saturating_sub(1) / (1 << 16);
+        let arr = (ticks / (psc + 1)).max(2) - 1;
+        self.tim.psc.write(|w| unsafe { w.psc().bits(psc as u16) });
+        self.tim.arr.write(|w| unsafe { w.bits(arr) });
+        self.tim.ccr[0].write(|w| unsafe { w.bits(arr.div_ceil(2)) });
+
+        // Load the new period at once, not at the end of the current one,
+        // without an update interrupt.
+        self.tim.cr1.modify(|_, w| w.urs().set_bit());
+        self.tim.egr.write(|w| w.ug().set_bit());
+        self.tim.ccmr1_output().modify(|_, w| w.oc1m().pwm_mode1());
+        self.tim.cr1.modify(|_, w| w.urs().clear_bit().cen().set_bit());
+    }
+
+    /// Stops the sound: the output goes low and the timer stops.
+    pub fn silence(&mut self) {
+        self.tim.ccmr1_output().modify(|_, w| w.oc1m().force_inactive());
+        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+    }
+
+    /// The frequency sounding, as the prescaler and period round it, or
+    /// `None` while silent.
+    pub fn frequency(&self) -> Option<Hertz> {
+        if self.tim.cr1.read().cen().bit_is_clear() {
+            return None;
+        }
+        let psc = self.tim.psc.read().bits() + 1;
+        let arr = self.tim.arr.read().bits() + 1;
+        Some(Hertz(self.clock.0 / psc / arr))
+    }
+
+    /// Stops the timer and returns the peripheral and the pin.
+    pub fn release(mut self) -> (TIM, TIM::Pin) {
+        self.silence();
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            TIM::disable(rcc);
+        }
+        (self.tim, self.pin)
+    }
+}
+
+/// Plays a list of notes, one call per note.
+pub struct Melody {
+    notes: &'static [Note],
+    next: usize,
+}
+
+impl Melody {
+    /// A melody at its first note, for a `static`.
+    pub const fn new(notes: &'static [Note]) -> Self {
+        Melody { notes, next: 0 }
+    }
+
+    /// Starts the next note on `buzzer` and returns its duration in
+    /// milliseconds.
+    ///
+    /// After the last note, silences the buzzer and returns `None`.
+    pub fn play_next<TIM: Instance>(&mut self, buzzer: &mut Buzzer<TIM>) -> Option<u16> {
+        match self.notes.get(self.next) {
+            Some(&(frequency, duration_ms)) => {
+                self.next += 1;
+                buzzer.tone(frequency);
+                Some(duration_ms)
+            }
+            None => {
+                buzzer.silence();
+                None
+            }
+        }
+    }
+
+    /// Returns `true` once every note has started.
+    pub fn is_done(&self) -> bool {
+        self.next >= self.notes.len()
+    }
+
+    /// Goes back to the first note.
+    pub fn restart(&mut self) {
+        self.next = 0;
+    }
+
+    /// Replaces the notes, from the first one.
+    pub fn set_notes(&mut self, notes: &'static [Note]) {
+        self.notes = notes;
+        self.next = 0;
+    }
+}
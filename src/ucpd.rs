@@ -0,0 +1,499 @@
+//! USB Power Delivery sink on UCPD1.
+//!
+//! The UCPD peripheral handles the physical layer of USB PD: the Rd pull-downs
+//! advertising a sink on the CC lines, the CC voltage monitoring that detects
+//! a source, and the BMC encoding, framing and CRC of the messages. [`Sink`]
+//! adds the protocol layer of a simple sink on top: it acknowledges every
+//! message with GoodCRC, parses the Source_Capabilities of the charger,
+//! requests the highest fixed supply not above the configured voltage and
+//! follows the source through Accept and PS_RDY.
+//!
+//! UCPD1 has CC1 on PB6 and CC2 on PB4. The Nucleo has no USB-C connector,
+//! so a receptacle breakout has to be wired to these two pins and GND. VBUS
+//! must not go to the board: after a 9 V contract it carries 9 V.
+//!
+//! The sink sends its messages by polling the transmitter, from the interrupt
+//! handler that received the message they answer. Messages the source did not
+//! acknowledge are not retried: the source then restarts the negotiation with
+//! a soft or a hard reset, which the sink follows.
+
+use stm32g4xx_hal as hal;
+
+use hal::gpio::{gpiob, Analog};
+use hal::rcc::{Enable, Reset};
+use hal::stm32::{PWR, RCC, UCPD1};
+
+/// SOP ordered set: Sync-1, Sync-1, Sync-1, Sync-2.
+const SOP: u32 = 0x18 | 0x18 << 5 | 0x18 << 10 | 0x11 << 15;
+
+/// Longest non-extended message: header and 7 data objects.
+const MAX_MESSAGE: usize = 2 + 7 * 4;
+
+/// Control message types.
+const GOOD_CRC: u8 = 0x01;
+const ACCEPT: u8 = 0x03;
+const REJECT: u8 = 0x04;
+const PS_RDY: u8 = 0x06;
+const WAIT: u8 = 0x0C;
+const SOFT_RESET: u8 = 0x0D;
+const NOT_SUPPORTED: u8 = 0x10;
+/// Data message types.
+const SOURCE_CAPABILITIES: u8 = 0x01;
+
+/// Specification revisions, as coded in the message header.
+const REVISION_2: u8 = 0b01;
+const REVISION_3: u8 = 0b10;
+
+/// Power Data Object: one supply offered by the source.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Pdo {
+    /// Fixed voltage, the kind every charger offers.
+    Fixed { millivolts: u32, max_milliamps: u32 },
+    /// Battery, voltage within a range and limited power.
+    Battery { min_millivolts: u32, max_millivolts: u32, max_milliwatts: u32 },
+    /// Unregulated supply with a voltage within a range.
+    Variable { min_millivolts: u32, max_millivolts: u32, max_milliamps: u32 },
+    /// Programmable Power Supply of USB PD 3.0, adjustable in 20 mV steps.
+    Pps { min_millivolts: u32, max_millivolts: u32, max_milliamps: u32 },
+}
+
+impl Pdo {
+    fn parse(object: u32) -> Pdo {
+        match object >> 30 {
+            0b00 => Pdo::Fixed {
+                millivolts: (object >> 10 & 0x3FF) * 50,
+                max_milliamps: (object & 0x3FF) * 10,
+            },
+            0b01 => Pdo::Battery {
+                min_millivolts: (object >> 10 & 0x3FF) * 50,
+                max_millivolts: (object >> 20 & 0x3FF) * 50,
+                max_milliwatts: (object & 0x3FF) * 250,
+            },
+            0b10 => Pdo::Variable {
+                min_millivolts: (object >> 10 & 0x3FF) * 50,
+                max_millivolts: (object >> 20 & 0x3FF) * 50,
+                max_milliamps: (object & 0x3FF) * 10,
+            },
+            _ => Pdo::Pps {
+                min_millivolts: (object >> 8 & 0xFF) * 100,
+                max_millivolts: (object >> 17 & 0xFF) * 100,
+                max_milliamps: (object & 0x7F) * 50,
+            },
+        }
+    }
+}
+
+/// An explicit power contract.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct Contract {
+    pub millivolts: u32,
+    pub milliamps: u32,
+}
+
+/// What the sink wants from the source.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct Config {
+    /// Highest acceptable voltage; 5000 keeps the default supply.
+    pub max_millivolts: u32,
+    /// Current needed at that voltage, capped at what the source offers.
+    pub milliamps: u32,
+}
+
+/// What [`Sink::handle`] did.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum SinkEvent {
+    /// A source was connected on CC line 1 or 2.
+    Attached { cc: u8 },
+    /// The source was disconnected.
+    Detached,
+    /// The source sent its capabilities, see [`Sink::capabilities`], and a
+    /// supply was requested.
+    Capabilities,
+    /// The source accepted the request and is changing the voltage.
+    Accepted,
+    /// The source rejected the request, or told the sink to wait.
+    Rejected,
+    /// The new voltage is ready.
+    Ready(Contract),
+    /// The source reset the link; a fresh negotiation follows.
+    HardReset,
+    /// A message was lost to a receive overrun or a CRC error.
+    Dropped,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Detached,
+    WaitCapabilities,
+    WaitAccept,
+    WaitReady,
+    Ready,
+}
+
+/// USB PD sink on UCPD1.
+pub struct Sink {
+    ucpd: UCPD1,
+    cc1: gpiob::PB6<Analog>,
+    cc2: gpiob::PB4<Analog>,
+    config: Config,
+    state: State,
+    revision: u8,
+    message_id: u8,
+    last_received_id: Option<u8>,
+    buffer: [u8; MAX_MESSAGE],
+    received: usize,
+    overrun: bool,
+    capabilities: [Option<Pdo>; 7],
+    requested: Option<Contract>,
+    contract: Option<Contract>,
+}
+
+impl Sink {
+    /// Presents a sink on both CC lines and waits for a source.
+    pub fn new(ucpd: UCPD1, cc1: gpiob::PB6<Analog>, cc2: gpiob::PB4<Analog>, config: Config) -> Self {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            PWR::enable(rcc);
+            UCPD1::enable(rcc);
+            UCPD1::reset(rcc);
+
+            // Hand the CC lines from the dead battery pull-downs over to UCPD1.
+            let pwr = &(*PWR::ptr());
+            pwr.cr3.modify(|_, w| w.ucpd1_dbdis().set_bit());
+        }
+
+        // HSI16 / 2 = 8 MHz, bit rate 8 MHz / 14 / 2 = 286 kbit/s, the
+        // settings of the ST reference drivers.
+        ucpd.cfg1.write(|w| unsafe {
+            w.psc_usbpdclk()
+                .bits(0b001)
+                .hbitclkdiv()
+                .bits(13)
+                .transwin()
+                .bits(7)
+                .ifrgap()
+                .bits(16)
+                // SOP messages only.
+                .rxordseten()
+                .bits(0b1)
+        });
+        ucpd.cfg1.modify(|_, w| w.ucpden().set_bit());
+        // Sink: Rd on both CC lines.
+        ucpd.cr.write(|w| unsafe { w.anamode().set_bit().ccenable().bits(0b11) });
+
+        Sink {
+            ucpd,
+            cc1,
+            cc2,
+            config,
+            state: State::Detached,
+            revision: REVISION_3,
+            message_id: 0,
+            last_received_id: None,
+            buffer: [0; MAX_MESSAGE],
+            received: 0,
+            overrun: false,
+            capabilities: [None; 7],
+            requested: None,
+            contract: None,
+        }
+    }
+
+    /// Starts interrupt generation on CC changes, received bytes, messages and
+    /// hard resets.
+    ///
+    /// Note, you will also have to unmask the UCPD1 interrupt in the NVIC. The
+    /// bytes arrive every 35 us, so the interrupt needs a high priority or
+    /// short neighbours.
+    pub fn listen(&mut self) {
+        self.ucpd.imr.write(|w| {
+            w.typecevt1ie()
+                .set_bit()
+                .typecevt2ie()
+                .set_bit()
+                .rxneie()
+                .set_bit()
+                .rxovrie()
+                .set_bit()
+                .rxmsgendie()
+                .set_bit()
+                .rxhrstdetie()
+                .set_bit()
+        });
+    }
+
+    /// Stops interrupt generation.
+    pub fn unlisten(&mut self) {
+        self.ucpd.imr.reset();
+    }
+
+    /// Serves one pending condition of UCPD1.
+    ///
+    /// Call it until it returns `None` from the interrupt handler: that
+    /// clears every flag raising the interrupt.
+    pub fn handle(&mut self) -> Option<SinkEvent> {
+        loop {
+            let sr = self.ucpd.sr.read();
+
+            if sr.typecevt1().bit_is_set() || sr.typecevt2().bit_is_set() {
+                self.ucpd.icr.write(|w| w.typecevt1cf().set_bit().typecevt2cf().set_bit());
+                // A source pulls its CC line above vRa with Rp.
+                let cc1 = sr.typec_vstate_cc1().bits() != 0;
+                let cc2 = sr.typec_vstate_cc2().bits() != 0;
+                match (self.state, cc1 || cc2) {
+                    (State::Detached, true) => {
+                        self.ucpd.cr.modify(|_, w| w.phyccsel().bit(!cc1).phyrxen().set_bit());
+                        self.restart();
+                        return Some(SinkEvent::Attached { cc: if cc1 { 1 } else { 2 } });
+                    }
+                    (State::Detached, false) | (_, true) => continue,
+                    (_, false) => {
+                        self.ucpd.cr.modify(|_, w| w.phyrxen().clear_bit());
+                        self.restart();
+                        self.state = State::Detached;
+                        return Some(SinkEvent::Detached);
+                    }
+                }
+            }
+
+            if sr.rxhrstdet().bit_is_set() {
+                self.ucpd.icr.write(|w| w.rxhrstdetcf().set_bit());
+                self.restart();
+                return Some(SinkEvent::HardReset);
+            }
+
+            if sr.rxne().bit_is_set() {
+                let byte = self.ucpd.rxdr.read().rxdata().bits();
+                match self.buffer.get_mut(self.received) {
+                    Some(slot) => *slot = byte,
+                    // Extended messages are longer: they are dropped.
+                    None => self.overrun = true,
+                }
+                self.received += 1;
+                continue;
+            }
+
+            if sr.rxovr().bit_is_set() {
+                self.ucpd.icr.write(|w| w.rxovrcf().set_bit());
+                self.overrun = true;
+                continue;
+            }
+
+            if sr.rxmsgend().bit_is_set() {
+                self.ucpd.icr.write(|w| w.rxmsgendcf().set_bit());
+                let len = core::mem::take(&mut self.received);
+                if core::mem::take(&mut self.overrun) || sr.rxerr().bit_is_set() {
+                    return Some(SinkEvent::Dropped);
+                }
+                match self.process(len) {
+                    Some(event) => return Some(event),
+                    None => continue,
+                }
+            }
+
+            return None;
+        }
+    }
+
+    /// Changes what the sink wants.
+    ///
+    /// Under a contract, the new supply is requested right away; otherwise
+    /// the request follows the next Source_Capabilities.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+        if self.state == State::Ready {
+            self.request();
+        }
+    }
+
+    /// Supplies offered in the last Source_Capabilities message.
+    pub fn capabilities(&self) -> impl Iterator<Item = Pdo> + '_ {
+        self.capabilities.iter().map_while(|pdo| *pdo)
+    }
+
+    /// The contract in force, `None` while on the default 5 V.
+    pub fn contract(&self) -> Option<Contract> {
+        self.contract
+    }
+
+    /// Removes the pull-downs and returns the peripheral and the pins.
+    pub fn release(self) -> (UCPD1, gpiob::PB6<Analog>, gpiob::PB4<Analog>) {
+        self.ucpd.cr.reset();
+        self.ucpd.cfg1.modify(|_, w| w.ucpden().clear_bit());
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            UCPD1::disable(rcc);
+        }
+        (self.ucpd, self.cc1, self.cc2)
+    }
+
+    /// Forgets the protocol state, back to the default 5 V supply.
+    fn restart(&mut self) {
+        self.state = State::WaitCapabilities;
+        self.message_id = 0;
+        self.last_received_id = None;
+        self.received = 0;
+        self.overrun = false;
+        self.requested = None;
+        self.contract = None;
+    }
+
+    /// Handles the message of `len` bytes in the receive buffer.
+    fn process(&mut self, len: usize) -> Option<SinkEvent> {
+        if len < 2 {
+            return None;
+        }
+        let header = u16::from_le_bytes([self.buffer[0], self.buffer[1]]);
+        let kind = (header & 0x1F) as u8;
+        let revision = (header >> 6 & 0b11) as u8;
+        let id = (header >> 9 & 0b111) as u8;
+        let objects = (header >> 12 & 0b111) as usize;
+        let extended = header >> 15 != 0;
+
+        // Sources only send GoodCRC to acknowledge the messages of the sink.
+        if kind == GOOD_CRC && objects == 0 {
+            return None;
+        }
+
+        self.revision = revision.min(REVISION_3);
+        self.transmit(GOOD_CRC, id, &[]);
+
+        // The source resends a message whose GoodCRC it missed.
+        if self.last_received_id == Some(id) && !(kind == SOFT_RESET && objects == 0) {
+            return None;
+        }
+        self.last_received_id = Some(id);
+
+        if extended {
+            self.reply_unsupported();
+            return None;
+        }
+
+        if objects > 0 {
+            if kind != SOURCE_CAPABILITIES || len < 2 + 4 * objects {
+                self.reply_unsupported();
+                return None;
+            }
+            self.capabilities = [None; 7];
+            for (slot, object) in self.capabilities.iter_mut().zip(self.buffer[2..2 + 4 * objects].chunks_exact(4)) {
+                *slot = Some(Pdo::parse(u32::from_le_bytes([object[0], object[1], object[2], object[3]])));
+            }
+            self.request();
+            return Some(SinkEvent::Capabilities);
+        }
+
+        match kind {
+            ACCEPT if self.state == State::WaitAccept => {
+                self.state = State::WaitReady;
+                Some(SinkEvent::Accepted)
+            }
+            REJECT | WAIT if self.state == State::WaitAccept => {
+                // The previous contract, if any, stays in force.
+                self.state = if self.contract.is_some() { State::Ready } else { State::WaitCapabilities };
+                Some(SinkEvent::Rejected)
+            }
+            PS_RDY if self.state == State::WaitReady => {
+                self.state = State::Ready;
+                self.contract = self.requested;
+                self.contract.map(SinkEvent::Ready)
+            }
+            SOFT_RESET => {
+                self.restart();
+                self.last_received_id = Some(id);
+                let message_id = self.next_message_id();
+                self.transmit(ACCEPT, message_id, &[]);
+                None
+            }
+            ACCEPT | REJECT | WAIT | PS_RDY | GOOD_CRC => None,
+            // Ping, source capabilities requests and the like.
+            _ => {
+                self.reply_unsupported();
+                None
+            }
+        }
+    }
+
+    /// Requests the highest fixed supply within the configured voltage.
+    fn request(&mut self) {
+        let mut choice = (1, 5000, 0);
+        for (position, pdo) in self.capabilities().enumerate() {
+            if let Pdo::Fixed { millivolts, max_milliamps } = pdo
+                && millivolts <= self.config.max_millivolts
+                && millivolts >= choice.1
+            {
+                choice = (position as u32 + 1, millivolts, max_milliamps);
+            }
+        }
+        let (position, millivolts, max_milliamps) = choice;
+        let milliamps = self.config.milliamps.min(max_milliamps);
+        let mismatch = millivolts < self.config.max_millivolts || milliamps < self.config.milliamps;
+
+        // Request Data Object for a fixed supply, the sink not suspending
+        // when the USB bus does.
+        let rdo = position << 28
+            | (mismatch as u32) << 26
+            | 1 << 24
+            | (milliamps / 10) << 10
+            | (milliamps / 10);
+        self.requested = Some(Contract { millivolts, milliamps });
+        self.state = State::WaitAccept;
+        let message_id = self.next_message_id();
+        // Request is data message 0x02.
+        self.transmit(0x02, message_id, &[rdo]);
+    }
+
+    /// Answers a message the sink does not handle.
+    fn reply_unsupported(&mut self) {
+        let kind = if self.revision >= REVISION_3 { NOT_SUPPORTED } else { REJECT };
+        let message_id = self.next_message_id();
+        self.transmit(kind, message_id, &[]);
+    }
+
+    fn next_message_id(&mut self) -> u8 {
+        let id = self.message_id;
+        self.message_id = (id + 1) % 8;
+        id
+    }
+
+    /// Sends one SOP message, waiting for the end of the transmission.
+    ///
+    /// Returns `false` if the transmission was discarded or aborted, because
+    /// the source was sending at the same time.
+    fn transmit(&mut self, kind: u8, id: u8, objects: &[u32]) -> bool {
+        // Upward facing data port, sink power role.
+        let header = kind as u16
+            | (self.revision.max(REVISION_2) as u16) << 6
+            | (id as u16) << 9
+            | (objects.len() as u16) << 12;
+        let mut message = [0; MAX_MESSAGE];
+        message[..2].copy_from_slice(&header.to_le_bytes());
+        for (bytes, object) in message[2..].chunks_exact_mut(4).zip(objects) {
+            bytes.copy_from_slice(&object.to_le_bytes());
+        }
+        let len = 2 + 4 * objects.len();
+
+        self.ucpd.tx_ordset.write(|w| unsafe { w.txordset().bits(SOP) });
+        self.ucpd.tx_paysz.write(|w| unsafe { w.txpaysz().bits(len as u16) });
+        self.ucpd.cr.modify(|_, w| unsafe { w.txmode().bits(0b00).txsend().set_bit() });
+
+        let mut bytes = message[..len].iter();
+        loop {
+            let sr = self.ucpd.sr.read();
+            if sr.txmsgsent().bit_is_set() {
+                self.ucpd.icr.write(|w| w.txmsgsentcf().set_bit());
+                return true;
+            }
+            if sr.txmsgdisc().bit_is_set() || sr.txmsgabt().bit_is_set() {
+                self.ucpd.icr.write(|w| w.txmsgdisccf().set_bit().txmsgabtcf().set_bit().txundcf().set_bit());
+                return false;
+            }
+            if sr.txis().bit_is_set()
+                && let Some(&byte) = bytes.next()
+            {
+                self.ucpd.txdr.write(|w| unsafe { w.txdata().bits(byte) });
+            }
+        }
+    }
+}
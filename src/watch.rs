@@ -0,0 +1,221 @@
+//! Live variables on an RTT channel of their own, with the `watch` feature.
+//!
+//! defmt-rtt has one RTT channel, the logs. With the `watch` feature the
+//! crate has its own RTT control block instead, with two up channels: 0,
+//! `defmt`, for the logs, which the crate's defmt logger writes as
+//! defmt-rtt would, and 1, `watch`, for [`publish`]:
+//!
+//! ```text
+//! delay=500 presses=3 uptime=12400 load=4
+//! ```
+//!
+//! One line of `key=value` pairs per call, for a host script to split and
+//! plot (`scripts/watch_plot.py`). The program calls it from a timer, at
+//! the rate of the plot: each line is a few microseconds of copying.
+//!
+//! Neither channel waits for the host unless it asks, with the blocking
+//! mode: the logs are then cut where the channel is full, and a line that
+//! does not fit is dropped whole, so the host never reads half of one.
+
+use core::cell::UnsafeCell;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Bytes of a [`publish`]ed line, past which its last pairs are dropped.
+pub const LINE: usize = 128;
+
+// Buffer sizes of the channels.
+const LOG_SIZE: usize = 1024;
+const WATCH_SIZE: usize = 256;
+
+// The modes of a channel, in its flags: the host sets them.
+const MODE_MASK: usize = 0b11;
+const MODE_BLOCK_IF_FULL: usize = 2;
+const MODE_NON_BLOCKING_TRIM: usize = 1;
+
+// The RTT control block, as the debug probe looks for it.
+#[repr(C)]
+struct Header {
+    id: [u8; 16],
+    max_up_channels: usize,
+    max_down_channels: usize,
+    up: [Channel; 2],
+}
+
+// An up channel: the target writes at `write`, the host reads at `read`.
+#[repr(C)]
+struct Channel {
+    name: *const u8,
+    buffer: *mut u8,
+    size: usize,
+    write: AtomicUsize,
+    read: AtomicUsize,
+    flags: AtomicUsize,
+}
+
+// NOTE(unsafe) the buffers behind the pointers are only written in a
+// critical section, and only read by the host
+unsafe impl Sync for Header {}
+
+struct Buffer<const N: usize>(UnsafeCell<[u8; N]>);
+
+// NOTE(unsafe) written through the control block only
+unsafe impl<const N: usize> Sync for Buffer<N> {}
+
+static LOG_BUFFER: Buffer<LOG_SIZE> = Buffer(UnsafeCell::new([0; LOG_SIZE]));
+static WATCH_BUFFER: Buffer<WATCH_SIZE> = Buffer(UnsafeCell::new([0; WATCH_SIZE]));
+
+#[unsafe(no_mangle)]
+static _SEGGER_RTT: Header = Header {
+    id: *b"SEGGER RTT\0\0\0\0\0\0",
+    max_up_channels: 2,
+    max_down_channels: 0,
+    up: [
+        Channel {
+            name: c"defmt".as_ptr().cast(),
+            buffer: LOG_BUFFER.0.get().cast(),
+            size: LOG_SIZE,
+            write: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            flags: AtomicUsize::new(MODE_NON_BLOCKING_TRIM),
+        },
+        Channel {
+            name: c"watch".as_ptr().cast(),
+            buffer: WATCH_BUFFER.0.get().cast(),
+            size: WATCH_SIZE,
+            write: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            flags: AtomicUsize::new(0),
+        },
+    ],
+};
+
+impl Channel {
+    // Writes `bytes`: all of them, waiting for the host, in the blocking
+    // mode; otherwise what fits, or for a `whole` write all or nothing.
+    fn write(&self, mut bytes: &[u8], whole: bool) {
+        let blocking = self.flags.load(Ordering::Relaxed) & MODE_MASK == MODE_BLOCK_IF_FULL;
+        if whole && !blocking && self.free() < bytes.len() {
+            return;
+        }
+        while !bytes.is_empty() {
+            let written = self.write_some(bytes);
+            if written == 0 && !blocking {
+                return;
+            }
+            bytes = &bytes[written..];
+        }
+    }
+
+    // Bytes the host has room for: one slot stays empty, to tell a full
+    // buffer from an empty one.
+    fn free(&self) -> usize {
+        let read = self.read.load(Ordering::Acquire);
+        let write = self.write.load(Ordering::Relaxed);
+        (read + self.size - write - 1) % self.size
+    }
+
+    // Writes what fits up to the end of the buffer, and returns its length.
+    fn write_some(&self, bytes: &[u8]) -> usize {
+        let write = self.write.load(Ordering::Relaxed);
+        let count = bytes.len().min(self.free()).min(self.size - write);
+        // NOTE(unsafe) within the buffer, in the part the host is not reading
+        unsafe { core::ptr::copy_nonoverlapping(bytes.as_ptr(), self.buffer.add(write), count) };
+        self.write.store((write + count) % self.size, Ordering::Release);
+        count
+    }
+}
+
+// A line of `publish`, cut at `LINE`.
+struct Line {
+    bytes: [u8; LINE],
+    len: usize,
+}
+
+impl Write for Line {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        if end > LINE {
+            return Err(fmt::Error);
+        }
+        self.bytes[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Writes `values` to the `watch` channel, as one line of `key=value`
+/// pairs, unless the host is too far behind.
+///
+/// The pairs not fitting in [`LINE`] bytes, with the newline, are dropped.
+pub fn publish(values: &[(&str, u32)]) {
+    let mut line = Line { bytes: [0; LINE], len: 0 };
+    for (index, (key, value)) in values.iter().enumerate() {
+        let start = line.len;
+        let separator = if index == 0 { "" } else { " " };
+        // Room kept for the newline.
+        if write!(line, "{}{}={}", separator, key, value).is_err() || line.len == LINE {
+            line.len = start;
+            break;
+        }
+    }
+    line.bytes[line.len] = b'\n';
+    critical_section::with(|_| _SEGGER_RTT.up[1].write(&line.bytes[..=line.len], true));
+}
+
+#[defmt::global_logger]
+struct Logger;
+
+// The logger's state, behind the critical section `acquire` holds.
+struct State {
+    taken: AtomicBool,
+    restore: UnsafeCell<critical_section::RestoreState>,
+    encoder: UnsafeCell<defmt::Encoder>,
+}
+
+// NOTE(unsafe) the cells are only used between `acquire` and `release`
+unsafe impl Sync for State {}
+
+static STATE: State = State {
+    taken: AtomicBool::new(false),
+    restore: UnsafeCell::new(critical_section::RestoreState::invalid()),
+    encoder: UnsafeCell::new(defmt::Encoder::new()),
+};
+
+fn emit(bytes: &[u8]) {
+    _SEGGER_RTT.up[0].write(bytes, false);
+}
+
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {
+        let restore = unsafe { critical_section::acquire() };
+        if STATE.taken.load(Ordering::Relaxed) {
+            panic!("defmt logger taken reentrantly");
+        }
+        STATE.taken.store(true, Ordering::Relaxed);
+        unsafe {
+            STATE.restore.get().write(restore);
+            (*STATE.encoder.get()).start_frame(emit);
+        }
+    }
+
+    unsafe fn flush() {
+        // Only the host empties the channel: wait for it in the blocking mode.
+        let channel = &_SEGGER_RTT.up[0];
+        if channel.flags.load(Ordering::Relaxed) & MODE_MASK == MODE_BLOCK_IF_FULL {
+            while channel.read.load(Ordering::Acquire) != channel.write.load(Ordering::Relaxed) {}
+        }
+    }
+
+    unsafe fn release() {
+        unsafe {
+            (*STATE.encoder.get()).end_frame(emit);
+            STATE.taken.store(false, Ordering::Relaxed);
+            critical_section::release(STATE.restore.get().read());
+        }
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        unsafe { (*STATE.encoder.get()).write(bytes, emit) }
+    }
+}
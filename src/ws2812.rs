@@ -0,0 +1,217 @@
+//! WS2812 ("NeoPixel") LED strips on TIM3 channel 1 and DMA.
+//!
+//! A WS2812 takes 24 bits, green, red then blue, most significant bit first,
+//! at 800 kbit/s: every bit is a 1.25 us period starting high, 0.4 us high
+//! for a 0 and 0.8 us for a 1. Each LED keeps the first 24 bits it sees and
+//! passes the rest down the strip; the line held low for more than 280 us
+//! latches the colors.
+//!
+//! TIM3 runs PWM at 800 kHz on PA6 (D12), one period per bit, and requests a
+//! DMA transfer on every update event: the DMA writes the duty of the next
+//! bit to CCR1, which the preload only applies from the following period, so
+//! every bit gets a full period whatever the DMA latency. The duties come
+//! from a buffer of [`buffer_len`] slots, which [`Ws2812::encode`] fills from
+//! the colors: 24 slots per LED, then [`RESET_SLOTS`] of zero duty, which end
+//! the frame with the latch and leave the line low once the DMA is done.
+//!
+//! The strip's data input wants at least 0.7 * its supply: a 5 V strip needs
+//! a level shifter on PA6, or a first LED run from a lower supply.
+
+use stm32g4xx_hal as hal;
+
+use hal::dma::{traits::TargetAddress, MemoryToPeripheral};
+use hal::gpio::{gpioa::PA6, Alternate, AF2};
+use hal::rcc::{Clocks, Enable, GetBusFreq, Reset};
+use hal::stm32::{RCC, TIM3};
+
+/// Bit rate of the protocol.
+pub const BIT_RATE: u32 = 800_000;
+
+/// Slots of zero duty ending every frame: 300 us, the latch of the WS2812B.
+pub const RESET_SLOTS: usize = 240;
+
+/// DMAMUX request line of the TIM3 update event.
+const TIM3_UP_REQUEST: u8 = 65;
+
+/// The number of buffer slots for `leds` LEDs, for the `static` buffer.
+///
+/// A transfer moves at most `dma::MAX_LEN` slots, some 2700 LEDs.
+pub const fn buffer_len(leds: usize) -> usize {
+    leds * 24 + RESET_SLOTS
+}
+
+/// A color, 8 bits per channel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, defmt::Format)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb::new(0, 0, 0);
+    pub const RED: Rgb = Rgb::new(255, 0, 0);
+    pub const GREEN: Rgb = Rgb::new(0, 255, 0);
+    pub const BLUE: Rgb = Rgb::new(0, 0, 255);
+    pub const YELLOW: Rgb = Rgb::new(255, 255, 0);
+    pub const CYAN: Rgb = Rgb::new(0, 255, 255);
+    pub const MAGENTA: Rgb = Rgb::new(255, 0, 255);
+    pub const WHITE: Rgb = Rgb::new(255, 255, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Rgb { r, g, b }
+    }
+
+    /// The color at `brightness` out of 255.
+    pub const fn scaled(self, brightness: u8) -> Self {
+        Rgb::new(scale(self.r, brightness), scale(self.g, brightness), scale(self.b, brightness))
+    }
+
+    /// The 24 bits in the order of the wire: green, red, blue.
+    const fn grb(self) -> u32 {
+        (self.g as u32) << 16 | (self.r as u32) << 8 | self.b as u32
+    }
+}
+
+const fn scale(channel: u8, brightness: u8) -> u8 {
+    ((channel as u16 * (brightness as u16 + 1)) >> 8) as u8
+}
+
+/// WS2812 errors.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    /// No LED of that index.
+    InvalidLed,
+    /// The buffer has fewer than [`buffer_len`] slots for the colors.
+    BufferTooShort,
+}
+
+/// The colors of a strip of `N` LEDs, LED 0 the nearest to the board.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame<const N: usize> {
+    colors: [Rgb; N],
+}
+
+impl<const N: usize> Frame<N> {
+    /// A frame of every LED off.
+    pub const fn new() -> Self {
+        Frame { colors: [Rgb::BLACK; N] }
+    }
+
+    /// The number of LEDs.
+    pub const fn len(&self) -> usize {
+        N
+    }
+
+    /// Returns `true` for a strip of no LED.
+    pub const fn is_empty(&self) -> bool {
+        N == 0
+    }
+
+    /// Sets the color of LED `index`.
+    pub fn set(&mut self, index: usize, color: Rgb) -> Result<(), Error> {
+        let slot = self.colors.get_mut(index).ok_or(Error::InvalidLed)?;
+        *slot = color;
+        Ok(())
+    }
+
+    /// The color of LED `index`.
+    pub fn get(&self, index: usize) -> Option<Rgb> {
+        self.colors.get(index).copied()
+    }
+
+    /// Sets every LED to `color`.
+    pub fn fill(&mut self, color: Rgb) {
+        self.colors = [color; N];
+    }
+
+    /// The colors, LED 0 first.
+    pub fn colors(&self) -> &[Rgb] {
+        &self.colors
+    }
+}
+
+impl<const N: usize> Default for Frame<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// TIM3 channel 1 on PA6 clocking bits out of a DMA buffer.
+///
+/// Use it as the peripheral side of a memory-to-peripheral DMA transfer, not
+/// circular: one transfer sends one frame.
+pub struct Ws2812 {
+    tim: TIM3,
+    _pin: PA6<Alternate<AF2>>,
+    // Duties of a 0 bit and of a 1 bit, in timer ticks.
+    zero: u16,
+    one: u16,
+}
+
+impl Ws2812 {
+    /// Starts TIM3 at [`BIT_RATE`], the line low, requesting a duty from the
+    /// DMA on every period.
+    pub fn new(tim: TIM3, pin: PA6<Alternate<AF2>>, clocks: &Clocks) -> Self {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            TIM3::enable(rcc);
+            TIM3::reset(rcc);
+        }
+
+        let period = TIM3::get_timer_frequency(clocks).0 / BIT_RATE;
+        tim.psc.write(|w| unsafe { w.psc().bits(0) });
+        tim.arr.write(|w| unsafe { w.bits(period - 1) });
+        tim.ccr1().write(|w| unsafe { w.bits(0) });
+        tim.ccmr1_output().write(|w| w.oc1m().pwm_mode1().oc1pe().set_bit());
+        tim.ccer.write(|w| w.cc1e().set_bit());
+        tim.cr1.modify(|_, w| w.arpe().set_bit());
+        // Load the registers, without a DMA request yet.
+        tim.egr.write(|w| w.ug().set_bit());
+        tim.dier.write(|w| w.ude().set_bit());
+        tim.cr1.modify(|_, w| w.cen().set_bit());
+
+        Ws2812 {
+            tim,
+            _pin: pin,
+            // 0.4 us and 0.8 us out of 1.25 us, rounded.
+            zero: ((period * 8 + 12) / 25) as u16,
+            one: ((period * 16 + 12) / 25) as u16,
+        }
+    }
+
+    /// Writes the duties of `colors` to `buffer`, then zeroes the rest of it.
+    pub fn encode(&self, colors: &[Rgb], buffer: &mut [u16]) -> Result<(), Error> {
+        if buffer.len() < buffer_len(colors.len()) {
+            return Err(Error::BufferTooShort);
+        }
+        let (bits, reset) = buffer.split_at_mut(colors.len() * 24);
+        for (slots, color) in bits.chunks_exact_mut(24).zip(colors) {
+            let grb = color.grb();
+            for (bit, slot) in slots.iter_mut().enumerate() {
+                *slot = if grb & (1 << (23 - bit)) != 0 { self.one } else { self.zero };
+            }
+        }
+        reset.fill(0);
+        Ok(())
+    }
+
+    /// Stops the timer and returns the peripheral and the pin.
+    pub fn release(self) -> (TIM3, PA6<Alternate<AF2>>) {
+        self.tim.dier.write(|w| w.ude().clear_bit());
+        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+        self.tim.ccer.write(|w| w.cc1e().clear_bit());
+        (self.tim, self._pin)
+    }
+}
+
+unsafe impl TargetAddress<MemoryToPeripheral> for Ws2812 {
+    type MemSize = u16;
+
+    const REQUEST_LINE: Option<u8> = Some(TIM3_UP_REQUEST);
+
+    fn address(&self) -> u32 {
+        self.tim.ccr1() as *const _ as u32
+    }
+}
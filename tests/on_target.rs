@@ -0,0 +1,222 @@
+//! On-target tests of the library logic that needs no wiring: the keypad
+//! debouncing, the deadlines of the timer wheel, the prescaler and period
+//! of a timer rate and the checksums of the shell frames, on the CRC unit.
+//!
+//! They run on the board through the probe like any example: defmt-test
+//! logs each test over RTT, a failed assertion stops the run through
+//! panic-probe, and the last test passing ends it with a semihosting exit,
+//! so `probe-rs run` returns the result as its exit status:
+//!
+//! ```text
+//! cargo test --test on_target
+//! ```
+
+#![no_main]
+#![no_std]
+
+use defmt_rtt as _;
+use panic_probe as _;
+
+use core::cell::Cell;
+use core::convert::Infallible;
+
+use embedded_hal::digital::{ErrorType, InputPin, OutputPin};
+
+use nucleo_g474re::keypad::{Key, Keypad};
+use nucleo_g474re::shell::Shell;
+
+// A row output driving nothing.
+pub struct Row;
+
+impl ErrorType for Row {
+    type Error = Infallible;
+}
+
+impl OutputPin for Row {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+// A column input, low while the test holds its key down.
+pub struct Column<'a>(&'a Cell<bool>);
+
+impl ErrorType for Column<'_> {
+    type Error = Infallible;
+}
+
+impl InputPin for Column<'_> {
+    fn is_high(&mut self) -> Result<bool, Infallible> {
+        Ok(!self.0.get())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Infallible> {
+        Ok(self.0.get())
+    }
+}
+
+pub const KEY: Key = Key { row: 0, col: 0 };
+
+// Feeds `line` to `shell`, Enter included.
+pub fn type_line(shell: &mut Shell, line: &[u8]) {
+    for &byte in line {
+        shell.push(byte);
+    }
+    shell.push(b'\r');
+}
+
+// Scans a one-key pad `scans` times with the key `down` or not.
+pub fn hold(keypad: &mut Keypad<Row, Column, 1, 1>, key: &Cell<bool>, down: bool, scans: u8) {
+    key.set(down);
+    for _ in 0..scans {
+        keypad.scan();
+    }
+}
+
+
+#[defmt_test::tests]
+mod tests {
+    use core::cell::Cell;
+
+    use stm32g4xx_hal as hal;
+
+    use hal::time::Hertz;
+
+    use nucleo_g474re::clocks::{timer_prescaler, HSI, SYSCLK_170MHZ};
+    use nucleo_g474re::crc::Crc;
+    use nucleo_g474re::keypad::{Event, Keypad, DEBOUNCE_SCANS};
+    use nucleo_g474re::shell::{self, Received, Shell, FRAME_CRC};
+    use nucleo_g474re::timer_wheel::{Error, TimerWheel};
+
+    use super::{hold, type_line, Column, Row, KEY};
+
+    #[test]
+    fn debounce_waits_for_agreeing_scans() {
+        let key = Cell::new(false);
+        let mut keypad = Keypad::new([Row], [Column(&key)]);
+
+        hold(&mut keypad, &key, true, DEBOUNCE_SCANS - 1);
+        defmt::assert_eq!(keypad.next_event(), None);
+        defmt::assert!(!keypad.is_pressed(KEY));
+
+        hold(&mut keypad, &key, true, 1);
+        defmt::assert_eq!(keypad.next_event(), Some(Event::Down(KEY)));
+        defmt::assert!(keypad.is_pressed(KEY));
+    }
+
+    #[test]
+    fn debounce_restarts_on_a_bounce() {
+        let key = Cell::new(false);
+        let mut keypad = Keypad::new([Row], [Column(&key)]);
+
+        hold(&mut keypad, &key, true, DEBOUNCE_SCANS - 1);
+        hold(&mut keypad, &key, false, 1);
+        hold(&mut keypad, &key, true, DEBOUNCE_SCANS - 1);
+        defmt::assert_eq!(keypad.next_event(), None);
+
+        hold(&mut keypad, &key, true, 1);
+        defmt::assert_eq!(keypad.next_event(), Some(Event::Down(KEY)));
+    }
+
+    #[test]
+    fn debounce_reports_the_release() {
+        let key = Cell::new(false);
+        let mut keypad = Keypad::new([Row], [Column(&key)]);
+
+        hold(&mut keypad, &key, true, DEBOUNCE_SCANS);
+        hold(&mut keypad, &key, false, DEBOUNCE_SCANS);
+        defmt::assert_eq!(keypad.next_event(), Some(Event::Down(KEY)));
+        defmt::assert_eq!(keypad.next_event(), Some(Event::Up(KEY)));
+        defmt::assert_eq!(keypad.next_event(), None);
+        defmt::assert!(!keypad.is_pressed(KEY));
+    }
+
+    #[test]
+    fn wheel_expires_on_the_deadline() {
+        let mut wheel = TimerWheel::<u8, 8, 4>::new();
+        defmt::assert_eq!(wheel.next_due(), None);
+
+        wheel.schedule(3, 1).unwrap();
+        defmt::assert_eq!(wheel.next_due(), Some(3));
+        wheel.tick();
+        wheel.tick();
+        defmt::assert_eq!(wheel.expired(), None);
+        defmt::assert_eq!(wheel.next_due(), Some(1));
+
+        wheel.tick();
+        defmt::assert_eq!(wheel.next_due(), Some(0));
+        defmt::assert_eq!(wheel.expired(), Some(1));
+        defmt::assert_eq!(wheel.expired(), None);
+        defmt::assert_eq!(wheel.next_due(), None);
+    }
+
+    #[test]
+    fn wheel_deadlines_past_a_turn() {
+        let mut wheel = TimerWheel::<u8, 8, 4>::new();
+        wheel.tick();
+
+        wheel.schedule(20, 2).unwrap();
+        defmt::assert_eq!(wheel.next_due(), Some(20));
+        wheel.advance(19);
+        defmt::assert_eq!(wheel.expired(), None);
+        defmt::assert_eq!(wheel.next_due(), Some(1));
+
+        wheel.advance(1);
+        defmt::assert_eq!(wheel.expired(), Some(2));
+    }
+
+    #[test]
+    fn wheel_nearest_deadline_first() {
+        let mut wheel = TimerWheel::<u8, 8, 2>::new();
+        wheel.schedule(5, 1).unwrap();
+        wheel.schedule(0, 2).unwrap();
+        defmt::assert_eq!(wheel.next_due(), Some(1));
+        defmt::assert_eq!(wheel.schedule(1, 3), Err(Error::Full));
+
+        defmt::assert!(wheel.cancel(2));
+        defmt::assert_eq!(wheel.next_due(), Some(5));
+    }
+
+    #[test]
+    fn prescaler_of_known_rates() {
+        defmt::assert_eq!(timer_prescaler(SYSCLK_170MHZ, Hertz(10_000)), (0, 16_999));
+        defmt::assert_eq!(timer_prescaler(SYSCLK_170MHZ, Hertz(1000)), (2, 56_665));
+        defmt::assert_eq!(timer_prescaler(HSI, Hertz(1)), (244, 65_305));
+        // Faster than the clock: as fast as the timer goes.
+        defmt::assert_eq!(timer_prescaler(HSI, Hertz(20_000_000)), (0, 0));
+    }
+
+    #[test]
+    fn prescaler_period_fits_and_is_close() {
+        for clock in [HSI, SYSCLK_170MHZ] {
+            for rate in [1, 3, 50, 440, 1000, 44_100, 1_000_000] {
+                let (psc, arr) = timer_prescaler(clock, Hertz(rate));
+                defmt::assert!(arr <= 0xFFFF);
+                let actual = clock.0 / (psc as u32 + 1) / (arr + 1);
+                // Within 1% of the rate, or 1 Hz below.
+                defmt::assert!(actual.abs_diff(rate) <= rate / 100 + 1, "{} Hz for {} Hz", actual, rate);
+            }
+        }
+    }
+
+    #[test]
+    fn frames_checked_against_their_crc() {
+        let dp = hal::stm32::Peripherals::take().unwrap();
+        let mut crc = Crc::new(dp.CRC, FRAME_CRC);
+        let mut shell = Shell::new();
+
+        type_line(&mut shell, b"period 300*8491");
+        defmt::assert_eq!(shell.received(&mut crc), Some(Ok(Received { line: "period 300", checked: true })));
+        type_line(&mut shell, b"period 301*8491");
+        defmt::assert_eq!(shell.received(&mut crc), Some(Err(shell::Error::Checksum)));
+        type_line(&mut shell, b"period 300*849");
+        defmt::assert_eq!(shell.received(&mut crc), Some(Err(shell::Error::Checksum)));
+        // A line typed by a person, with no checksum.
+        type_line(&mut shell, b"config");
+        defmt::assert_eq!(shell.received(&mut crc), Some(Ok(Received { line: "config", checked: false })));
+    }
+}